@@ -3,7 +3,7 @@
 use aliri_braid::braid;
 use modyne::{
     expr, keys, types::Expiry, Aggregate, Entity, EntityDef, EntityExt, Error, Projection,
-    ProjectionExt, QueryInput, QueryInputExt, Table,
+    ProjectionExt, QueryInput, QueryInputExt, Table, WritableTable,
 };
 
 #[derive(Clone, Debug)]
@@ -41,6 +41,8 @@ impl Table for App {
     }
 }
 
+impl WritableTable for App {}
+
 impl App {
     pub async fn create_session(&self, session: Session) -> Result<(), Error> {
         session.create().execute(self).await?;
@@ -48,7 +50,7 @@ impl App {
     }
 
     pub async fn get_session(&self, session_token: uuid::Uuid) -> Result<Option<Session>, Error> {
-        let now = time::OffsetDateTime::now_utc();
+        let now = self.clock().now();
         self.get_session_with_now(session_token, now).await
     }
 
@@ -8,17 +8,10 @@ use modyne::{
 };
 
 #[test_log::test(tokio::test)]
-#[ignore = "this test requires a local DynamoDB instance running on localhost:4566 and may be \
-            slow"]
+#[ignore = "this test requires Docker to start a LocalStack container and may be slow"]
 async fn localstack_only_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let config = aws_config::defaults(aws_config::BehaviorVersion::latest())
-        .endpoint_url("http://localhost:4566")
-        .credentials_provider(aws_credential_types::Credentials::new(
-            "test", "test", None, None, "static",
-        ))
-        .load()
-        .await;
-    let client = aws_sdk_dynamodb::Client::new(&config);
+    let localstack = modyne::testing::localstack().await;
+    let client = localstack.client().clone();
     let app = App::new(client);
 
     let _ = app.delete_table().send().await;
@@ -85,17 +78,10 @@ async fn localstack_only_test() -> Result<(), Box<dyn std::error::Error + Send +
 }
 
 #[test_log::test(tokio::test)]
-#[ignore = "this test requires a local DynamoDB instance running on localhost:4566 and may be \
-            slow"]
+#[ignore = "this test requires Docker to start a LocalStack container and may be slow"]
 async fn batch_put_get_delete() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let config = aws_config::defaults(aws_config::BehaviorVersion::latest())
-        .endpoint_url("http://localhost:4566")
-        .credentials_provider(aws_credential_types::Credentials::new(
-            "test", "test", None, None, "static",
-        ))
-        .load()
-        .await;
-    let client = aws_sdk_dynamodb::Client::new(&config);
+    let localstack = modyne::testing::localstack().await;
+    let client = localstack.client().clone();
     let app = App::new_with_table(client, "SessionStore_BatchTest");
 
     let _ = app.delete_table().send().await;
@@ -1,14 +1,13 @@
 #![doc = include_str!("../README.md")]
 
-use core::fmt;
 use std::collections::HashMap;
 
 use aliri_braid::braid;
 use modyne::{
-    expr, keys, model::TransactWrite, projections, read_projection, Aggregate, Entity, EntityExt,
-    Error, Item, Projection, QueryInput, QueryInputExt, Table,
+    expr, keys, model::TransactWrite, projections, read_projection, types::ids::KsuidId, Aggregate,
+    Entity, EntityExt, Error, Item, Projection, QueryInput, QueryInputExt, Table, WritableTable,
 };
-use svix_ksuid::{Ksuid, KsuidLike};
+use svix_ksuid::Ksuid;
 
 pub struct App {
     table_name: std::sync::Arc<str>,
@@ -41,18 +40,13 @@ impl Table for App {
     }
 }
 
+impl WritableTable for App {}
+
+modyne::table_entities!(App => [Customer, Order, OrderItem]);
+
 impl App {
     pub async fn create_customer(&self, input: Customer) -> Result<(), Error> {
-        let email_entity = CustomerEmail {
-            email: input.email.clone(),
-            user_name: input.user_name.clone(),
-        };
-
-        let _result = TransactWrite::new()
-            .operation(input.create())
-            .operation(email_entity.create())
-            .execute(self)
-            .await?;
+        let _result = input.create_with_derived().execute(self).await?;
 
         Ok(())
     }
@@ -99,13 +93,11 @@ impl App {
     }
 
     pub async fn save_order(&self, order: Order, items: Vec<OrderItem>) -> Result<(), Error> {
-        let mut builder = TransactWrite::new().operation(order.create());
-
-        for item in items {
-            builder = builder.operation(item.create());
-        }
-
-        let _result = builder.execute(self).await?;
+        let _result = TransactWrite::new()
+            .operation(order.create())
+            .operations(items.into_iter().map(|item| item.create()))
+            .execute(self)
+            .await?;
 
         Ok(())
     }
@@ -166,6 +158,7 @@ pub struct UserName;
 pub struct UserEmail;
 
 #[derive(Debug, modyne::EntityDef, serde::Serialize, serde::Deserialize)]
+#[entity(unique = "email")]
 pub struct Customer {
     pub user_name: UserName,
     pub name: String,
@@ -198,60 +191,13 @@ impl Entity for Customer {
     }
 }
 
-#[derive(Debug, modyne::EntityDef, serde::Serialize, serde::Deserialize)]
-struct CustomerEmail {
-    user_name: UserName,
-    email: UserEmail,
-}
-
-impl Entity for CustomerEmail {
-    type KeyInput<'a> = &'a UserEmailRef;
-    type Table = App;
-    type IndexKeys = ();
-
-    fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary {
-        let common = format!("CUSTOMEREMAIL#{}", input);
-        keys::Primary {
-            hash: common.clone(),
-            range: common,
-        }
-    }
-
-    fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
-        Self::primary_key(&self.email).into()
-    }
-}
-
-#[derive(
-    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
-)]
-#[serde(transparent)]
-pub struct OrderId(Ksuid);
-
-impl OrderId {
-    #[allow(clippy::new_without_default)]
-    pub fn new() -> Self {
-        Self(Ksuid::new(None, None))
-    }
-}
-
-impl fmt::Display for OrderId {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
-    }
-}
-
-impl std::str::FromStr for OrderId {
-    type Err = svix_ksuid::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ksuid::from_str(s).map(Self)
-    }
-}
+pub type OrderId = KsuidId<Order>;
 
 #[derive(Debug, modyne::EntityDef, serde::Serialize, serde::Deserialize)]
 pub struct Order {
+    #[entity(key(borrow = "UserNameRef"))]
     pub user_name: UserName,
+    #[entity(key(copy))]
     pub order_id: OrderId,
     #[serde(with = "time::serde::rfc3339")]
     pub created_at: time::OffsetDateTime,
@@ -269,11 +215,6 @@ pub enum OrderStatus {
     Delivered,
 }
 
-pub struct OrderKeyInput<'a> {
-    user_name: &'a UserNameRef,
-    order_id: OrderId,
-}
-
 impl Entity for Order {
     type KeyInput<'a> = OrderKeyInput<'a>;
     type Table = App;
@@ -288,10 +229,7 @@ impl Entity for Order {
 
     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
         keys::FullKey {
-            primary: Self::primary_key(OrderKeyInput {
-                user_name: &self.user_name,
-                order_id: self.order_id,
-            }),
+            primary: Self::primary_key(self.key_input()),
             indexes: keys::Gsi1 {
                 hash: format!("ORDER#{}", self.order_id),
                 range: format!("ORDER#{}", self.order_id),
@@ -463,6 +401,74 @@ mod tests {
     );
     }
 
+    #[test]
+    fn verify_customer_orders_query_request() {
+        let rendered = modyne::testing::assert_query(&CustomerOrdersQuery {
+            user_name: UserNameRef::from_static("alexdebrie"),
+        });
+
+        assert_eq!(rendered.index_name, None);
+        assert_eq!(rendered.key_condition.expression, "#key_PK = :key_PK");
+        assert_eq!(rendered.key_condition.names["#key_PK"], "PK");
+        assert_eq!(
+            rendered.key_condition.values[":key_PK"],
+            AttributeValue::S("CUSTOMER#alexdebrie".into())
+        );
+        assert!(rendered.filter.is_none());
+        assert_eq!(
+            rendered.projection.unwrap().expression,
+            "user_name,order_id,created_at,number_of_items,amount,#prj_000,#prj_001,email,entity_type"
+        );
+        assert!(!rendered.consistent_read);
+        assert!(rendered.scan_index_forward);
+    }
+
+    #[test]
+    fn verify_order_with_items_query_request() {
+        let rendered = modyne::testing::assert_query(&OrderWithItemsQuery {
+            order_id: "1VrgXBQ0VCshuQUnh1HrDIHQNwY".parse().unwrap(),
+        });
+
+        assert_eq!(rendered.index_name, Some("GSI1"));
+        assert_eq!(rendered.key_condition.names["#key_PK"], "GSI1PK");
+        assert_eq!(
+            rendered.key_condition.values[":key_PK"],
+            AttributeValue::S("ORDER#1VrgXBQ0VCshuQUnh1HrDIHQNwY".into())
+        );
+        assert!(rendered.filter.is_none());
+        assert!(rendered.projection.is_some());
+    }
+
+    #[test]
+    fn verify_entity_key_patterns_do_not_collide() {
+        let order_id: OrderId = "1VrgXBQ0VCshuQUnh1HrDIHQNwY".parse().unwrap();
+
+        let findings = modyne::analyze::<App>()
+            .sample(&Customer {
+                user_name: UserName::from_static("alexdebrie"),
+                name: "Alex DeBrie".into(),
+                email: UserEmail::from_static("alexdebrie1@gmail.com"),
+                addresses: HashMap::new(),
+            })
+            .sample(&Order {
+                user_name: UserName::from_static("alexdebrie"),
+                order_id,
+                created_at: time::OffsetDateTime::from_unix_timestamp(1578016664).unwrap(),
+                number_of_items: 1,
+                status: OrderStatus::Shipped,
+                amount: 67.43,
+            })
+            .sample(&OrderItem {
+                order_id: order_id.into_inner(),
+                item_id: ItemId::from_static("1"),
+                description: "Amazon Echo".into(),
+                price: 67.43,
+            })
+            .findings();
+
+        assert!(findings.is_empty(), "{findings:?}");
+    }
+
     #[test]
     fn verify_order_entity_full_item_serializes_as_expected() {
         let order_id = "1VrgXBQ0VCshuQUnh1HrDIHQNwY".parse().unwrap();
@@ -1,19 +1,19 @@
 #![doc = include_str!("../README.md")]
 
-use core::fmt;
 use std::{collections::VecDeque, num::NonZeroU32};
 
 use aliri_braid::braid;
 use aws_sdk_dynamodb::operation::scan::ScanOutput;
 use modyne::{
-    expr,
-    keys::{self, IndexKey},
-    model::{Scan, ScanSegment, TransactWrite},
-    projections, read_projection, Aggregate, AttributeValue, Entity, EntityExt, EntityTypeNameRef,
-    Error, Item, ProjectionExt, QueryInput, QueryInputExt, ScanInput, Table,
+    expr, keys,
+    model::{Scan, ScanSegment, TransactWrite, TransactWriteItem},
+    projections, read_projection,
+    time_series::{Bucket, TimeSeriesKey, TimeSeriesQuery},
+    types::ids::KsuidId,
+    Aggregate, AttributeValue, Entity, EntityExt, EntityTypeNameRef, Error, Item, ProjectionExt,
+    QueryInput, QueryInputExt, ScanInput, SingletonEntity, Table, WritableTable,
 };
 use serde_dynamo::string_set::StringSet;
-use svix_ksuid::{Ksuid, KsuidLike};
 use time::format_description::well_known::Rfc3339;
 
 #[derive(Clone, Debug)]
@@ -74,6 +74,8 @@ impl Table for App {
     }
 }
 
+impl WritableTable for App {}
+
 impl App {
     pub async fn create_deal(&self, deal: Deal) -> Result<(), Error> {
         deal.create().execute(self).await?;
@@ -81,22 +83,7 @@ impl App {
     }
 
     pub async fn create_brand(&self, brand: Brand) -> Result<(), Error> {
-        let expression = expr::Update::new("ADD #brands :brands SET #entity_type = :entity_type")
-            .name("#brands", "brands")
-            .value(":brands", StringSet(vec![&brand.brand_name]))
-            .name("#entity_type", "entity_type")
-            .value(
-                ":entity_type",
-                StringSet(vec![<Brands as modyne::EntityDef>::ENTITY_TYPE]),
-            );
-        let update = Brands::update(()).expression(expression);
-
-        TransactWrite::new()
-            .operation(update)
-            .operation(brand.create())
-            .execute(self)
-            .await?;
-
+        brand.create_with_derived().execute(self).await?;
         Ok(())
     }
 
@@ -152,8 +139,7 @@ impl App {
     }
 
     pub async fn get_front_page(&self) -> Result<FrontPage, Error> {
-        let result = FrontPage::get(()).execute(self).await?;
-        FrontPage::from_item(result.item.unwrap_or_default())
+        FrontPage::get_or_default(self).await
     }
 
     pub async fn get_category(
@@ -165,8 +151,7 @@ impl App {
     }
 
     pub async fn get_editors_choice_page(&self) -> Result<EditorsChoice, Error> {
-        let result = EditorsChoice::get(()).execute(self).await?;
-        EditorsChoice::from_item(result.item.unwrap_or_default())
+        EditorsChoice::get_or_default(self).await
     }
 
     pub async fn get_deal(&self, deal_id: DealId) -> Result<Option<Deal>, Error> {
@@ -180,25 +165,12 @@ impl App {
         date: time::Date,
         last_seen: Option<DealId>,
     ) -> Result<Vec<Deal>, Error> {
-        const DEFAULT_LIMIT: u32 = 25;
-        let mut limit = DEFAULT_LIMIT;
-        let mut query_input = DealsByDateQuery { date, last_seen };
-
-        let mut agg = Vec::with_capacity(DEFAULT_LIMIT as usize);
-
-        for _ in 0..5 {
-            let result = query_input.query().limit(limit).execute(self).await?;
-
-            agg.reduce(result.items.unwrap_or_default())?;
-
-            query_input.date = query_input.date.previous_day().unwrap();
-            limit = limit.saturating_sub(result.count as u32);
-            if limit == 0 {
-                break;
-            }
-        }
-
-        Ok(agg)
+        recent_deal_days(date)
+            .execute(self, |partition| DealsByDateQuery {
+                date: partition.start().date(),
+                last_seen,
+            })
+            .await
     }
 
     pub async fn get_brand_deals_by_date(
@@ -207,29 +179,13 @@ impl App {
         date: time::Date,
         last_seen: Option<DealId>,
     ) -> Result<Vec<Deal>, Error> {
-        const DEFAULT_LIMIT: u32 = 25;
-        let mut limit = DEFAULT_LIMIT;
-        let mut query_input = BrandDealsByDateQuery {
-            brand,
-            date,
-            last_seen,
-        };
-
-        let mut agg = Vec::with_capacity(DEFAULT_LIMIT as usize);
-
-        for _ in 0..5 {
-            let result = query_input.query().limit(limit).execute(self).await?;
-
-            agg.reduce(result.items.unwrap_or_default())?;
-
-            query_input.date = query_input.date.previous_day().unwrap();
-            limit = limit.saturating_sub(result.count as u32);
-            if limit == 0 {
-                break;
-            }
-        }
-
-        Ok(agg)
+        recent_deal_days(date)
+            .execute(self, |partition| BrandDealsByDateQuery {
+                brand,
+                date: partition.start().date(),
+                last_seen,
+            })
+            .await
     }
 
     pub async fn get_category_deals_by_date(
@@ -238,39 +194,17 @@ impl App {
         date: time::Date,
         last_seen: Option<DealId>,
     ) -> Result<Vec<Deal>, Error> {
-        const DEFAULT_LIMIT: u32 = 25;
-        let mut limit = DEFAULT_LIMIT;
-        let mut query_input = CategoryDealsByDateQuery {
-            category,
-            date,
-            last_seen,
-        };
-
-        let mut agg = Vec::with_capacity(DEFAULT_LIMIT as usize);
-
-        for _ in 0..5 {
-            let result = query_input.query().limit(limit).execute(self).await?;
-
-            agg.reduce(result.items.unwrap_or_default())?;
-
-            query_input.date = query_input.date.previous_day().unwrap();
-            limit = limit.saturating_sub(result.count as u32);
-            if limit == 0 {
-                break;
-            }
-        }
-
-        Ok(agg)
+        recent_deal_days(date)
+            .execute(self, |partition| CategoryDealsByDateQuery {
+                category,
+                date: partition.start().date(),
+                last_seen,
+            })
+            .await
     }
 
     pub async fn get_all_brands(&self) -> Result<Brands, Error> {
-        let resp = Brands::get(()).execute(self).await?;
-
-        Ok(resp
-            .item
-            .map(Brands::from_item)
-            .transpose()?
-            .unwrap_or(Brands { brands: Vec::new() }))
+        Brands::get_or_default(self).await
     }
 
     pub async fn put_brand_like(
@@ -408,7 +342,7 @@ impl App {
         body: String,
     ) -> Result<MessageId, Error> {
         let now = time::OffsetDateTime::now_utc();
-        let message_id = MessageId::new(now);
+        let message_id = MessageId::at(now);
         let message = Message {
             user_name: to.to_owned(),
             message_id,
@@ -428,11 +362,10 @@ impl App {
         user_name: &UserNameRef,
         message_id: MessageId,
     ) -> Result<(), Error> {
-        let expression = expr::Update::new("SET #unread = :unread, REMOVE #GSIPK, #GSISK")
+        let expression = expr::Update::new("SET #unread = :unread")
             .name("#unread", "unread")
-            .name("#GSIPK", keys::Gsi1::INDEX_DEFINITION.hash_key())
-            .name("#GSISK", keys::Gsi1::INDEX_DEFINITION.range_key().unwrap())
-            .value(":unread", false);
+            .value(":unread", false)
+            .remove_index_keys::<keys::Gsi1>();
 
         Message::update((user_name, message_id))
             .expression(expression)
@@ -598,32 +531,7 @@ pub struct CategoryName;
 #[braid(serde)]
 pub struct UserName;
 
-#[derive(
-    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
-)]
-#[serde(transparent)]
-pub struct DealId(Ksuid);
-
-impl DealId {
-    #[allow(clippy::new_without_default)]
-    pub fn new(now: time::OffsetDateTime) -> Self {
-        Self(Ksuid::new(Some(now), None))
-    }
-}
-
-impl fmt::Display for DealId {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
-    }
-}
-
-impl std::str::FromStr for DealId {
-    type Err = svix_ksuid::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ksuid::from_str(s).map(Self)
-    }
-}
+pub type DealId = KsuidId<Deal>;
 
 #[derive(Debug, modyne::EntityDef, serde::Serialize, serde::Deserialize)]
 pub struct Deal {
@@ -698,6 +606,19 @@ impl Entity for Brand {
             indexes: (),
         }
     }
+
+    fn derive_writes(&self) -> Vec<TransactWriteItem> {
+        let expression = expr::Update::new("ADD #brands :brands SET #entity_type = :entity_type")
+            .name("#brands", "brands")
+            .value(":brands", StringSet(vec![&self.brand_name]))
+            .name("#entity_type", App::ENTITY_TYPE_ATTRIBUTE)
+            .value(
+                ":entity_type",
+                StringSet(vec![<Brands as modyne::EntityDef>::ENTITY_TYPE]),
+            );
+
+        vec![Brands::update(()).expression(expression).into()]
+    }
 }
 
 #[derive(Debug, modyne::EntityDef, serde::Serialize, serde::Deserialize)]
@@ -757,7 +678,7 @@ impl Entity for BrandWatch {
     }
 }
 
-#[derive(Debug, modyne::EntityDef, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Default, modyne::EntityDef, serde::Serialize, serde::Deserialize)]
 pub struct Brands {
     #[serde(
         default,
@@ -767,24 +688,9 @@ pub struct Brands {
     pub brands: Vec<BrandName>,
 }
 
-impl Entity for Brands {
-    type KeyInput<'a> = ();
+impl SingletonEntity for Brands {
     type Table = App;
-    type IndexKeys = ();
-
-    fn primary_key(_: Self::KeyInput<'_>) -> keys::Primary {
-        keys::Primary {
-            hash: "BRANDS".to_string(),
-            range: "BRANDS".to_string(),
-        }
-    }
-
-    fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
-        keys::FullKey {
-            primary: Self::primary_key(()),
-            indexes: (),
-        }
-    }
+    const KEY: &'static str = "BRANDS";
 }
 
 #[derive(Debug, modyne::EntityDef, serde::Serialize, serde::Deserialize)]
@@ -878,56 +784,26 @@ impl Entity for CategoryWatch {
     }
 }
 
-#[derive(Debug, modyne::EntityDef, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Default, modyne::EntityDef, serde::Serialize, serde::Deserialize)]
 pub struct FrontPage {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub featured_deals: Vec<FeaturedDeal>,
 }
 
-impl Entity for FrontPage {
-    type KeyInput<'a> = ();
+impl SingletonEntity for FrontPage {
     type Table = App;
-    type IndexKeys = ();
-
-    fn primary_key(_: Self::KeyInput<'_>) -> keys::Primary {
-        keys::Primary {
-            hash: "FRONTPAGE".to_string(),
-            range: "FRONTPAGE".to_string(),
-        }
-    }
-
-    fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
-        keys::FullKey {
-            primary: Self::primary_key(()),
-            indexes: (),
-        }
-    }
+    const KEY: &'static str = "FRONTPAGE";
 }
 
-#[derive(Debug, modyne::EntityDef, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Default, modyne::EntityDef, serde::Serialize, serde::Deserialize)]
 pub struct EditorsChoice {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub featured_deals: Vec<FeaturedDeal>,
 }
 
-impl Entity for EditorsChoice {
-    type KeyInput<'a> = ();
+impl SingletonEntity for EditorsChoice {
     type Table = App;
-    type IndexKeys = ();
-
-    fn primary_key(_: Self::KeyInput<'_>) -> keys::Primary {
-        keys::Primary {
-            hash: "EDITORSCHOICE".to_string(),
-            range: "EDITORSCHOICE".to_string(),
-        }
-    }
-
-    fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
-        keys::FullKey {
-            primary: Self::primary_key(()),
-            indexes: (),
-        }
-    }
+    const KEY: &'static str = "EDITORSCHOICE";
 }
 
 #[derive(Debug, modyne::EntityDef, serde::Serialize, serde::Deserialize)]
@@ -974,32 +850,7 @@ impl Entity for User {
     }
 }
 
-#[derive(
-    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
-)]
-#[serde(transparent)]
-pub struct MessageId(Ksuid);
-
-impl MessageId {
-    #[allow(clippy::new_without_default)]
-    pub fn new(now: time::OffsetDateTime) -> Self {
-        Self(Ksuid::new(Some(now), None))
-    }
-}
-
-impl fmt::Display for MessageId {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        self.0.fmt(f)
-    }
-}
-
-impl std::str::FromStr for MessageId {
-    type Err = svix_ksuid::Error;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ksuid::from_str(s).map(Self)
-    }
-}
+pub type MessageId = KsuidId<Message>;
 
 #[derive(Debug, modyne::EntityDef, serde::Serialize, serde::Deserialize)]
 pub struct Message {
@@ -1161,11 +1012,8 @@ impl QueryInput for DealsByDateQuery {
     fn key_condition(&self) -> expr::KeyCondition<Self::Index> {
         let date = format_as_date(self.date);
         let partition = format!("DEALS#{}", date);
-        let bound = self
-            .last_seen
-            .map(|id| format!("DEAL#{}", id))
-            .unwrap_or_else(|| "DEAL$".to_string());
-        expr::KeyCondition::in_partition(partition).less_than(bound)
+        let bound = self.last_seen.map(|id| format!("DEAL#{}", id));
+        expr::KeyCondition::in_partition(partition).less_than_opt(bound)
     }
 }
 
@@ -1185,11 +1033,8 @@ impl QueryInput for BrandDealsByDateQuery<'_> {
     fn key_condition(&self) -> expr::KeyCondition<Self::Index> {
         let date = self.date.format(&Rfc3339).unwrap();
         let partition = format!("BRAND#{}#{}", self.brand, date).to_ascii_uppercase();
-        let bound = self
-            .last_seen
-            .map(|id| format!("DEAL#{}", id))
-            .unwrap_or_else(|| "DEAL$".to_string());
-        expr::KeyCondition::in_partition(partition).less_than(bound)
+        let bound = self.last_seen.map(|id| format!("DEAL#{}", id));
+        expr::KeyCondition::in_partition(partition).less_than_opt(bound)
     }
 }
 
@@ -1209,14 +1054,22 @@ impl QueryInput for CategoryDealsByDateQuery<'_> {
     fn key_condition(&self) -> expr::KeyCondition<Self::Index> {
         let date = self.date.format(&Rfc3339).unwrap();
         let partition = format!("CATEGORY#{}#{}", self.category, date).to_ascii_uppercase();
-        let bound = self
-            .last_seen
-            .map(|id| format!("DEAL#{}", id))
-            .unwrap_or_else(|| "DEAL$".to_string());
-        expr::KeyCondition::in_partition(partition).less_than(bound)
+        let bound = self.last_seen.map(|id| format!("DEAL#{}", id));
+        expr::KeyCondition::in_partition(partition).less_than_opt(bound)
     }
 }
 
+/// Prepares a query of the five days of deals ending on the given date
+///
+/// The query inputs form their partition keys from the date of each day,
+/// and scan backward, so deals are returned newest first.
+fn recent_deal_days(date: time::Date) -> TimeSeriesQuery {
+    let end = date.midnight().assume_utc();
+    TimeSeriesKey::new("DEALS", Bucket::Day)
+        .query(end - time::Duration::days(4), end)
+        .limit(25)
+}
+
 fn format_as_date(time: time::Date) -> String {
     static FORMAT: std::sync::OnceLock<Vec<time::format_description::FormatItem<'static>>> =
         std::sync::OnceLock::new();
@@ -1230,7 +1083,7 @@ fn format_as_date(time: time::Date) -> String {
 
 #[cfg(test)]
 mod tests {
-    use modyne::keys::PrimaryKey;
+    use modyne::keys::{IndexKey, PrimaryKey};
 
     use super::*;
 
@@ -1239,7 +1092,7 @@ mod tests {
         let now = time::OffsetDateTime::now_utc();
         let message = Message {
             user_name: "user".into(),
-            message_id: MessageId::new(now),
+            message_id: MessageId::at(now),
             subject: "subject".into(),
             body: "body".into(),
             created_at: now,
@@ -1259,7 +1112,7 @@ mod tests {
         let now = time::OffsetDateTime::now_utc();
         let message = Message {
             user_name: "user".into(),
-            message_id: MessageId::new(now),
+            message_id: MessageId::at(now),
             subject: "subject".into(),
             body: "body".into(),
             created_at: now,
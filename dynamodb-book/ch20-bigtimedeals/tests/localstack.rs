@@ -6,19 +6,12 @@ use dynamodb_book_ch20_bigtimedeals::{
 use modyne::TestTableExt;
 
 #[test_log::test(tokio::test)]
-#[ignore = "this test requires a local DynamoDB instance running on localhost:4566 and may be \
-            slow"]
+#[ignore = "this test requires Docker to start a LocalStack container and may be slow"]
 async fn localstack_only_test() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     use futures::stream::TryStreamExt;
 
-    let config = aws_config::defaults(aws_config::BehaviorVersion::latest())
-        .endpoint_url("http://localhost:4566")
-        .credentials_provider(aws_credential_types::Credentials::new(
-            "test", "test", None, None, "static",
-        ))
-        .load()
-        .await;
-    let client = aws_sdk_dynamodb::Client::new(&config);
+    let localstack = modyne::testing::localstack().await;
+    let client = localstack.client().clone();
     let app = App::new(client);
 
     let _ = app.delete_table().send().await;
@@ -78,7 +71,7 @@ async fn localstack_only_test() -> Result<(), Box<dyn std::error::Error + Send +
 
     let now = time::OffsetDateTime::now_utc();
     app.create_deal(Deal {
-        deal_id: DealId::new(now),
+        deal_id: DealId::at(now),
         title: "mtest_deal".to_string(),
         link: "mtest_deal".to_string(),
         price: 19.99,
@@ -90,7 +83,7 @@ async fn localstack_only_test() -> Result<(), Box<dyn std::error::Error + Send +
 
     let then = now.saturating_sub(time::Duration::days(1));
     app.create_deal(Deal {
-        deal_id: DealId::new(then),
+        deal_id: DealId::at(then),
         title: "mtest_deal".to_string(),
         link: "mtest_deal".to_string(),
         price: 19.99,
@@ -102,7 +95,7 @@ async fn localstack_only_test() -> Result<(), Box<dyn std::error::Error + Send +
 
     let thenthen = then.saturating_sub(time::Duration::days(1));
     app.create_deal(Deal {
-        deal_id: DealId::new(thenthen),
+        deal_id: DealId::at(thenthen),
         title: "mtest_deal".to_string(),
         link: "mtest_deal".to_string(),
         price: 19.99,
@@ -114,7 +107,7 @@ async fn localstack_only_test() -> Result<(), Box<dyn std::error::Error + Send +
 
     let thenthen = thenthen.saturating_sub(time::Duration::days(1));
     app.create_deal(Deal {
-        deal_id: DealId::new(thenthen),
+        deal_id: DealId::at(thenthen),
         title: "mtest_deal".to_string(),
         link: "mtest_deal".to_string(),
         price: 19.99,
@@ -126,7 +119,7 @@ async fn localstack_only_test() -> Result<(), Box<dyn std::error::Error + Send +
 
     let thenthen = thenthen.saturating_sub(time::Duration::days(1));
     app.create_deal(Deal {
-        deal_id: DealId::new(thenthen),
+        deal_id: DealId::at(thenthen),
         title: "mtest_deal".to_string(),
         link: "mtest_deal".to_string(),
         price: 19.99,
@@ -137,7 +130,7 @@ async fn localstack_only_test() -> Result<(), Box<dyn std::error::Error + Send +
     .await?;
 
     app.create_deal(Deal {
-        deal_id: DealId::new(thenthen),
+        deal_id: DealId::at(thenthen),
         title: "mtest_deal".to_string(),
         link: "mtest_deal".to_string(),
         price: 19.99,
@@ -149,7 +142,7 @@ async fn localstack_only_test() -> Result<(), Box<dyn std::error::Error + Send +
 
     let thenthen = thenthen.saturating_sub(time::Duration::days(1));
     app.create_deal(Deal {
-        deal_id: DealId::new(thenthen),
+        deal_id: DealId::at(thenthen),
         title: "mtest_deal".to_string(),
         link: "mtest_deal".to_string(),
         price: 19.99,
@@ -161,7 +154,7 @@ async fn localstack_only_test() -> Result<(), Box<dyn std::error::Error + Send +
 
     let thenthen = thenthen.saturating_sub(time::Duration::days(1));
     app.create_deal(Deal {
-        deal_id: DealId::new(thenthen),
+        deal_id: DealId::at(thenthen),
         title: "mtest_deal".to_string(),
         link: "mtest_deal".to_string(),
         price: 19.99,
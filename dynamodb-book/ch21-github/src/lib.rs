@@ -4,7 +4,7 @@ use std::collections::{BTreeMap, BTreeSet};
 
 use aliri_braid::braid;
 use compact_str::{format_compact, CompactString};
-use modyne::{keys, Entity, Table};
+use modyne::{keys, Entity, Table, WritableTable};
 use svix_ksuid::Ksuid;
 use time::format_description::well_known::Rfc3339;
 
@@ -40,6 +40,8 @@ impl Table for App {
     }
 }
 
+impl WritableTable for App {}
+
 #[braid(serde)]
 pub struct OwnerName(CompactString);
 
@@ -1,34 +1,318 @@
-use quote::quote;
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::ext::IdentExt;
 
 use crate::{
     case::RenameRule,
-    parsing::{get_field_names, ContainerAttrs},
+    parsing::{
+        check_set_fields, get_field_attributes, get_field_names, get_key_fields,
+        get_transient_fields, get_variant_field_names, is_transient, ContainerAttrs, KeyField,
+        KeyFieldMode,
+    },
 };
 
 pub fn generate(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
-    let syn::Data::Struct(data) = &input.data else {
-        return Err(syn::Error::new_spanned(
-            input,
-            "EntityDef may only be defined on a struct",
-        ));
+    let cont_attrs = ContainerAttrs::from_ast(&input.attrs)?;
+    let (field_names, field_attributes, key_fields, transient_fields) = match &input.data {
+        syn::Data::Struct(data) => {
+            check_set_fields(&data.fields)?;
+            (
+                get_field_names(cont_attrs.rename_rule, &data.fields)?,
+                get_field_attributes(cont_attrs.rename_rule, &data.fields)?,
+                get_key_fields(&data.fields)?,
+                get_transient_fields(&cont_attrs, &data.fields)?,
+            )
+        }
+        syn::Data::Enum(data) => {
+            for variant in &data.variants {
+                check_set_fields(&variant.fields)?;
+                if let Some(field) = get_key_fields(&variant.fields)?.first() {
+                    return Err(syn::Error::new_spanned(
+                        &field.ident,
+                        "key fields are not supported on enum entities",
+                    ));
+                }
+                for field in &variant.fields {
+                    if is_transient(field)? {
+                        return Err(syn::Error::new_spanned(
+                            field,
+                            "transient fields are not supported on enum entities",
+                        ));
+                    }
+                }
+            }
+            (
+                get_variant_field_names(&cont_attrs, data)?,
+                Vec::new(),
+                Vec::new(),
+                Vec::new(),
+            )
+        }
+        syn::Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &input,
+                "EntityDef may only be defined on a struct or enum",
+            ));
+        }
     };
 
-    let cont_attrs = ContainerAttrs::from_ast(&input.attrs)?;
-    let field_names = get_field_names(cont_attrs.rename_rule, data)?;
+    let entity_type = match (&cont_attrs.entity_type, &cont_attrs.name) {
+        (Some(entity_type), None) => quote! { #entity_type },
+        (Some(entity_type), Some(_)) => {
+            return Err(syn::Error::new_spanned(
+                entity_type,
+                "an entity type expression cannot be combined with `#[serde(rename = \"...\")]`",
+            ));
+        }
+        (None, Some(name)) => {
+            let name = name.value();
+            quote! { ::modyne::EntityTypeNameRef::from_static(#name) }
+        }
+        (None, None) => {
+            let name = RenameRule::SnakeCase.apply_to_variant(&input.ident.to_string());
+            quote! { ::modyne::EntityTypeNameRef::from_static(#name) }
+        }
+    };
+    let input_ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
 
-    let name = if let Some(name) = &cont_attrs.name {
-        name.value()
+    let key_input = if key_fields.is_empty() {
+        if let Some(key_input) = &cont_attrs.key_input {
+            return Err(syn::Error::new_spanned(
+                key_input,
+                "a key input name requires at least one field marked with `#[entity(key)]`",
+            ));
+        }
+        quote! {}
     } else {
-        RenameRule::SnakeCase.apply_to_variant(&input.ident.to_string())
+        if let Some(field) = key_fields
+            .iter()
+            .find(|f| mentions_generics(f.ty.to_token_stream(), &input.generics))
+        {
+            return Err(syn::Error::new_spanned(
+                &field.ty,
+                "key fields may not use the generic parameters of the entity",
+            ));
+        }
+        generate_key_input(&input, &cont_attrs, &key_fields)
     };
-    let input_ident = &input.ident;
+
+    for unique in &cont_attrs.unique {
+        if !field_names.is_empty() && !field_names.contains(&unique.value()) {
+            return Err(syn::Error::new_spanned(
+                unique,
+                "a unique attribute must be one of the entity's attributes",
+            ));
+        }
+    }
+    let unique = &cont_attrs.unique;
+    let unique_index = &cont_attrs.unique_index;
+    let unique_index_checks = if input.generics.params.is_empty() {
+        generate_unique_index_checks(&input.ident, unique_index)
+    } else {
+        quote! {}
+    };
+    let upgrades = &cont_attrs.upgrades;
+    let field_constants = if input.generics.params.is_empty() && !field_attributes.is_empty() {
+        generate_field_constants(&input, &field_attributes)
+    } else {
+        quote! {}
+    };
+    let (fields, attributes): (Vec<_>, Vec<_>) = field_attributes
+        .into_iter()
+        .map(|(ident, attribute)| (ident.unraw().to_string(), attribute))
+        .unzip();
 
     Ok(quote! {
-        impl ::modyne::EntityDef for #input_ident {
-            const ENTITY_TYPE: &'static ::modyne::EntityTypeNameRef = ::modyne::EntityTypeNameRef::from_static(#name);
+        impl #impl_generics ::modyne::EntityDef for #input_ident #ty_generics #where_clause {
+            const ENTITY_TYPE: &'static ::modyne::EntityTypeNameRef = #entity_type;
             const PROJECTED_ATTRIBUTES: &'static [&'static str] = &[
                 #(#field_names ,)*
             ];
+            const UNIQUE_ATTRIBUTES: &'static [&'static str] = &[
+                #(#unique ,)*
+            ];
+            const UNIQUE_INDEXES: &'static [&'static str] = &[
+                #(#unique_index ,)*
+            ];
+            const FIELD_ATTRIBUTES: &'static [(&'static str, &'static str)] = &[
+                #((#fields, #attributes) ,)*
+            ];
+            const TRANSIENT_ATTRIBUTES: &'static [&'static str] = &[
+                #(#transient_fields ,)*
+            ];
+            const SCHEMA_UPGRADES: &'static [fn(::modyne::Item) -> ::modyne::Item] = &[
+                #(#upgrades ,)*
+            ];
         }
+
+        #key_input
+        #field_constants
+        #unique_index_checks
     })
 }
+
+/// Generates a struct holding a typed [`Field`] for each field of the
+/// entity, and a `FIELDS` constant on the entity to access them
+fn generate_field_constants(
+    input: &syn::DeriveInput,
+    field_attributes: &[(syn::Ident, String)],
+) -> proc_macro2::TokenStream {
+    let input_ident = &input.ident;
+    let vis = &input.vis;
+    let fields_ident = format_ident!("{}Fields", input_ident);
+
+    let idents = field_attributes.iter().map(|(ident, _)| ident);
+    let docs = field_attributes.iter().map(|(ident, attribute)| {
+        format!(
+            "The `{}` field, stored in the `{attribute}` attribute",
+            ident.unraw()
+        )
+    });
+    let values = field_attributes.iter().map(|(ident, attribute)| {
+        let name = ident.unraw().to_string();
+        quote! { #ident: ::modyne::Field::new(#name, #attribute) }
+    });
+
+    let struct_doc = format!("The fields of [`{}`]", input_ident);
+
+    quote! {
+        #[doc = #struct_doc]
+        #[derive(Clone, Copy, Debug)]
+        #[allow(dead_code)]
+        #vis struct #fields_ident {
+            #(
+                #[doc = #docs]
+                pub #idents: ::modyne::Field<#input_ident>,
+            )*
+        }
+
+        #[allow(dead_code)]
+        impl #input_ident {
+            /// The fields of the entity, naming the attributes they are
+            /// stored in for use in typed conditions
+            #vis const FIELDS: #fields_ident = #fields_ident {
+                #( #values, )*
+            };
+        }
+    }
+}
+
+/// Checks at compile time that each unique index is one of the entity's index keys
+///
+/// Generic entities are instead checked when their claims are computed.
+fn generate_unique_index_checks(
+    ident: &syn::Ident,
+    unique_index: &[syn::LitStr],
+) -> proc_macro2::TokenStream {
+    let checks = unique_index.iter().map(|index| {
+        let message = format!(
+            "unique index `{}` is not one of the index keys of `{ident}`",
+            index.value()
+        );
+        quote_spanned! {index.span()=>
+            const _: () = ::std::assert!(
+                ::modyne::__private::has_index(
+                    <<#ident as ::modyne::Entity>::IndexKeys as ::modyne::keys::IndexKeys>::KEY_DEFINITIONS,
+                    #index,
+                ),
+                #message,
+            );
+        }
+    });
+
+    quote! { #(#checks)* }
+}
+
+fn generate_key_input(
+    input: &syn::DeriveInput,
+    cont_attrs: &ContainerAttrs,
+    key_fields: &[KeyField],
+) -> proc_macro2::TokenStream {
+    let input_ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let vis = &input.vis;
+    let key_ident = cont_attrs
+        .key_input
+        .clone()
+        .unwrap_or_else(|| format_ident!("{}KeyInput", input_ident));
+
+    let borrows = key_fields
+        .iter()
+        .any(|f| !matches!(f.mode, KeyFieldMode::Copy));
+    let lifetime = if borrows {
+        quote! { <'a> }
+    } else {
+        quote! {}
+    };
+    let elided = if borrows {
+        quote! { <'_> }
+    } else {
+        quote! {}
+    };
+
+    let idents: Vec<_> = key_fields.iter().map(|f| &f.ident).collect();
+    let types = key_fields.iter().map(|f| {
+        let ty = &f.ty;
+        match &f.mode {
+            KeyFieldMode::Ref if is_string(ty) => quote! { &'a str },
+            KeyFieldMode::Ref => quote! { &'a #ty },
+            KeyFieldMode::Borrow(borrowed) => quote! { &'a #borrowed },
+            KeyFieldMode::Copy => quote! { #ty },
+        }
+    });
+    let values = key_fields.iter().map(|f| {
+        let ident = &f.ident;
+        match &f.mode {
+            KeyFieldMode::Ref if is_string(&f.ty) => quote! { self.#ident.as_str() },
+            KeyFieldMode::Ref => quote! { &self.#ident },
+            KeyFieldMode::Borrow(borrowed) => {
+                quote! { ::std::borrow::Borrow::<#borrowed>::borrow(&self.#ident) }
+            }
+            KeyFieldMode::Copy => quote! { self.#ident },
+        }
+    });
+
+    let struct_doc = format!("Key input for [`{}`]", input_ident);
+
+    quote! {
+        #[doc = #struct_doc]
+        #[derive(Clone, Copy)]
+        #vis struct #key_ident #lifetime {
+            #( pub #idents: #types, )*
+        }
+
+        impl #impl_generics #input_ident #ty_generics #where_clause {
+            /// Borrows the fields that make up the key input for this entity
+            #[inline]
+            #vis fn key_input(&self) -> #key_ident #elided {
+                #key_ident {
+                    #( #idents: #values, )*
+                }
+            }
+        }
+    }
+}
+
+/// Whether the tokens name any of the generic parameters
+///
+/// The generated key input struct is not generic, so key fields cannot
+/// refer to the entity's type or lifetime parameters.
+fn mentions_generics(tokens: proc_macro2::TokenStream, generics: &syn::Generics) -> bool {
+    tokens.into_iter().any(|token| match token {
+        proc_macro2::TokenTree::Ident(ident) => generics.params.iter().any(|param| match param {
+            syn::GenericParam::Type(param) => param.ident == ident,
+            syn::GenericParam::Lifetime(param) => param.lifetime.ident == ident,
+            syn::GenericParam::Const(param) => param.ident == ident,
+        }),
+        proc_macro2::TokenTree::Group(group) => mentions_generics(group.stream(), generics),
+        _ => false,
+    })
+}
+
+fn is_string(ty: &syn::Type) -> bool {
+    let syn::Type::Path(path) = ty else {
+        return false;
+    };
+
+    path.qself.is_none() && path.path.is_ident("String")
+}
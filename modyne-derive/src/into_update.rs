@@ -0,0 +1,85 @@
+use quote::quote;
+
+use crate::parsing::{get_update_fields, ContainerAttrs, UpdateFieldMode};
+
+pub fn generate(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let syn::Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "IntoUpdate may only be defined on a struct",
+        ));
+    };
+
+    if !matches!(data.fields, syn::Fields::Named(_)) {
+        return Err(syn::Error::new_spanned(
+            &input,
+            "IntoUpdate may only be defined on a struct with named fields",
+        ));
+    }
+
+    let cont_attrs = ContainerAttrs::from_ast(&input.attrs)?;
+    let fields = get_update_fields(cont_attrs.rename_rule, &data.fields)?;
+    let input_ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let assignments = fields.iter().map(|field| {
+        let ident = &field.ident;
+        let path = &field.path;
+        let assign = match field.mode {
+            UpdateFieldMode::Set => quote! {
+                builder.set(&[path, &[#(#path),*]].concat(), value);
+            },
+            UpdateFieldMode::Append => quote! {
+                builder.append(&[path, &[#(#path),*]].concat(), value);
+            },
+            UpdateFieldMode::Nested => quote! {
+                ::modyne::IntoUpdate::write_update(value, builder, &[path, &[#(#path),*]].concat());
+            },
+            UpdateFieldMode::Entries => quote! {
+                for (key, value) in value {
+                    builder.set(
+                        &[path, &[#(#path,)* ::std::convert::AsRef::<str>::as_ref(&key)]].concat(),
+                        value,
+                    );
+                }
+            },
+        };
+
+        if is_option(&field.ty) {
+            quote! {
+                if let ::std::option::Option::Some(value) = self.#ident {
+                    #assign
+                }
+            }
+        } else {
+            quote! {
+                {
+                    let value = self.#ident;
+                    #assign
+                }
+            }
+        }
+    });
+
+    Ok(quote! {
+        impl #impl_generics ::modyne::IntoUpdate for #input_ident #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn write_update(self, builder: &mut ::modyne::expr::UpdateBuilder, path: &[&str]) {
+                #(#assignments)*
+            }
+        }
+    })
+}
+
+fn is_option(ty: &syn::Type) -> bool {
+    let syn::Type::Path(path) = ty else {
+        return false;
+    };
+
+    path.qself.is_none()
+        && path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option")
+}
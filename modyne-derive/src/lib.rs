@@ -2,6 +2,7 @@ extern crate proc_macro;
 
 mod case;
 mod entity_def;
+mod into_update;
 mod parsing;
 mod projection;
 mod symbol;
@@ -9,7 +10,7 @@ mod symbol;
 use proc_macro::TokenStream;
 use syn::parse_macro_input;
 
-#[proc_macro_derive(EntityDef, attributes(serde))]
+#[proc_macro_derive(EntityDef, attributes(serde, entity))]
 pub fn derive_entity_def(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as syn::DeriveInput);
 
@@ -26,3 +27,12 @@ pub fn derive_projection(input: TokenStream) -> TokenStream {
         .unwrap_or_else(|err| err.into_compile_error())
         .into()
 }
+
+#[proc_macro_derive(IntoUpdate, attributes(serde, update))]
+pub fn derive_into_update(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+
+    crate::into_update::generate(input)
+        .unwrap_or_else(|err| err.into_compile_error())
+        .into()
+}
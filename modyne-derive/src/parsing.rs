@@ -3,25 +3,102 @@ use crate::{case::RenameRule, symbol::*};
 pub struct ContainerAttrs {
     pub name: Option<syn::LitStr>,
     pub rename_rule: RenameRule,
+    pub rename_all_fields: RenameRule,
+    pub tagging: Tagging,
     pub entity: Option<syn::Path>,
+    pub entity_type: Option<syn::Expr>,
+    pub key_input: Option<syn::Ident>,
+    pub unique: Vec<syn::LitStr>,
+    pub unique_index: Vec<syn::LitStr>,
+    pub upgrades: Vec<syn::Path>,
+    pub default: bool,
+}
+
+/// The serde representation of an enum
+pub enum Tagging {
+    /// `{"Variant": {...}}`
+    External,
+    /// `{"tag": "Variant", ...}`
+    Internal(String),
+    /// `{"tag": "Variant", "content": {...}}`
+    Adjacent(String, String),
+    /// `{...}`
+    None,
+}
+
+/// How a field marked with `#[entity(key)]` is exposed on the generated key input
+pub enum KeyFieldMode {
+    /// Borrow the field as `&'a T`
+    Ref,
+    /// Borrow the field as `&'a B` where `T: Borrow<B>`
+    Borrow(Box<syn::Type>),
+    /// Copy the field by value
+    Copy,
+}
+
+pub struct KeyField {
+    pub ident: syn::Ident,
+    pub ty: syn::Type,
+    pub mode: KeyFieldMode,
 }
 
 impl ContainerAttrs {
     pub fn from_ast(ast: &[syn::Attribute]) -> syn::Result<Self> {
         let mut name = None;
         let mut rename_rule = RenameRule::None;
+        let mut rename_all_fields = RenameRule::None;
+        let mut tag = None;
+        let mut content = None;
+        let mut untagged = false;
         let mut entity = None;
+        let mut entity_type = None;
+        let mut key_input = None;
+        let mut unique = Vec::new();
+        let mut unique_index = Vec::new();
+        let mut upgrades = Vec::new();
+        let mut default = false;
 
         for attr in ast {
             if attr.path() == ENTITY {
                 attr.parse_nested_meta(|inner| {
+                    if inner.path == KEY_INPUT {
+                        let lit = get_lit_str2(ENTITY, KEY_INPUT, &inner)?;
+                        key_input = Some(lit.parse::<syn::Ident>()?);
+                        return Ok(());
+                    }
+                    if inner.path == UNIQUE {
+                        unique.push(get_lit_str2(ENTITY, UNIQUE, &inner)?);
+                        return Ok(());
+                    }
+                    if inner.path == UNIQUE_INDEX {
+                        unique_index.push(get_lit_str2(ENTITY, UNIQUE_INDEX, &inner)?);
+                        return Ok(());
+                    }
+                    if inner.path == ENTITY_TYPE {
+                        entity_type = Some(inner.value()?.parse::<syn::Expr>()?);
+                        return Ok(());
+                    }
+                    if inner.path == UPGRADE {
+                        let lit = get_lit_str2(ENTITY, UPGRADE, &inner)?;
+                        upgrades.push(lit.parse::<syn::Path>()?);
+                        return Ok(());
+                    }
                     if entity.is_some() {
                         return Err(syn::Error::new_spanned(
                             inner.path,
                             "only one entity type can be specified",
                         ));
                     }
-                    entity = Some(inner.path);
+                    let mut path = inner.path;
+                    if inner.input.peek(syn::Token![<]) {
+                        // Meta paths are parsed mod-style, so generic arguments
+                        // on a generic entity type are parsed separately
+                        let args: syn::AngleBracketedGenericArguments = inner.input.parse()?;
+                        if let Some(last) = path.segments.last_mut() {
+                            last.arguments = syn::PathArguments::AngleBracketed(args);
+                        }
+                    }
+                    entity = Some(path);
                     Ok(())
                 })?;
             } else if attr.path() == SERDE {
@@ -33,12 +110,27 @@ impl ContainerAttrs {
 
                 attr.parse_nested_meta(|meta| {
                     if meta.path == RENAME {
-                        name = Some(get_lit_str2(RENAME, RENAME, &meta)?);
+                        name = Some(get_lit_str2(SERDE, RENAME, &meta)?);
                     } else if meta.path == RENAME_ALL {
-                        rename_rule = RenameRule::from_str(
-                            &get_lit_str2(RENAME_ALL, RENAME_ALL, &meta)?.value(),
+                        rename_rule =
+                            RenameRule::from_str(&get_lit_str2(SERDE, RENAME_ALL, &meta)?.value())
+                                .map_err(|err| syn::Error::new_spanned(attr, err))?;
+                    } else if meta.path == RENAME_ALL_FIELDS {
+                        rename_all_fields = RenameRule::from_str(
+                            &get_lit_str2(SERDE, RENAME_ALL_FIELDS, &meta)?.value(),
                         )
                         .map_err(|err| syn::Error::new_spanned(attr, err))?;
+                    } else if meta.path == TAG {
+                        tag = Some(get_lit_str2(SERDE, TAG, &meta)?.value());
+                    } else if meta.path == CONTENT {
+                        content = Some(get_lit_str2(SERDE, CONTENT, &meta)?.value());
+                    } else if meta.path == UNTAGGED {
+                        untagged = true;
+                    } else if meta.path == DEFAULT {
+                        default = true;
+                        if meta.input.peek(syn::Token![=]) {
+                            let _: syn::Expr = meta.value()?.parse()?;
+                        }
                     } else if meta.input.peek(syn::Token![=]) {
                         let _: syn::Expr = meta.value()?.parse()?;
                     } else if meta.input.lookahead1().peek(syn::token::Paren) {
@@ -52,23 +144,347 @@ impl ContainerAttrs {
             }
         }
 
+        let tagging = match (tag, content, untagged) {
+            (None, None, false) => Tagging::External,
+            (Some(tag), None, false) => Tagging::Internal(tag),
+            (Some(tag), Some(content), false) => Tagging::Adjacent(tag, content),
+            (None, None, true) => Tagging::None,
+            _ => {
+                return Err(syn::Error::new(
+                    proc_macro2::Span::call_site(),
+                    "unsupported combination of serde enum tagging attributes",
+                ))
+            }
+        };
+
         Ok(Self {
             name,
             rename_rule,
+            rename_all_fields,
+            tagging,
             entity,
+            entity_type,
+            key_input,
+            unique,
+            unique_index,
+            upgrades,
+            default,
         })
     }
 }
 
-pub fn get_field_names(
-    rename_rule: RenameRule,
-    data: &syn::DataStruct,
+pub fn get_key_fields(fields: &syn::Fields) -> syn::Result<Vec<KeyField>> {
+    let mut key_fields = Vec::new();
+
+    for field in fields {
+        let Some(mode) = EntityFieldAttrs::from_ast(&field.attrs)?.key else {
+            continue;
+        };
+
+        let ident = field
+            .ident
+            .clone()
+            .ok_or_else(|| syn::Error::new_spanned(field, "expected a named field"))?;
+
+        key_fields.push(KeyField {
+            ident,
+            ty: field.ty.clone(),
+            mode,
+        });
+    }
+
+    Ok(key_fields)
+}
+
+/// Lists the attribute names of the fields marked with `#[entity(transient)]`
+///
+/// As transient attributes are never written, each field must be able to
+/// take a default when read, either through `#[serde(default)]` on the field
+/// or on the container.
+pub fn get_transient_fields(
+    cont_attrs: &ContainerAttrs,
+    fields: &syn::Fields,
 ) -> syn::Result<Vec<String>> {
+    let mut transient_fields = Vec::new();
+
+    for field in fields {
+        if !is_transient(field)? {
+            continue;
+        }
+
+        if !cont_attrs.default && !field_has_default(&field.attrs)? {
+            return Err(syn::Error::new_spanned(
+                field,
+                "a transient field must accept a default when read, add `#[serde(default)]`",
+            ));
+        }
+
+        let (_, name) = field_name_override_from_attrs(&field.attrs)?;
+        let name = if let Some(name) = name {
+            name
+        } else {
+            get_field_name(cont_attrs.rename_rule, field.ident.as_ref())?
+        };
+
+        transient_fields.push(name);
+    }
+
+    Ok(transient_fields)
+}
+
+/// Requires that fields holding a set attribute are skipped when empty
+///
+/// DynamoDB rejects empty sets, so serializing an entity with an empty set
+/// would fail at runtime rather than at compile time.
+pub fn check_set_fields(fields: &syn::Fields) -> syn::Result<()> {
+    for field in fields {
+        if is_set_type(&field.ty) && !field_skips_serializing(&field.attrs)? {
+            return Err(syn::Error::new_spanned(
+                field,
+                "DynamoDB does not allow empty sets, add \
+                 `#[serde(default, skip_serializing_if = \"...::is_empty\")]` to the set field",
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the type is one of the set attribute types of `modyne::types`
+fn is_set_type(ty: &syn::Type) -> bool {
+    let syn::Type::Path(path) = ty else {
+        return false;
+    };
+
+    path.path.segments.last().is_some_and(|segment| {
+        segment.ident == "StringSet" || segment.ident == "NumberSet" || segment.ident == "BinarySet"
+    })
+}
+
+/// Whether the field is marked with `#[entity(transient)]`
+pub fn is_transient(field: &syn::Field) -> syn::Result<bool> {
+    Ok(EntityFieldAttrs::from_ast(&field.attrs)?.transient)
+}
+
+/// The `#[entity(...)]` attributes of a field
+struct EntityFieldAttrs {
+    key: Option<KeyFieldMode>,
+    transient: bool,
+    attributes: Option<Vec<String>>,
+}
+
+impl EntityFieldAttrs {
+    fn from_ast(attrs: &[syn::Attribute]) -> syn::Result<Self> {
+        let mut mode = None;
+        let mut transient = false;
+        let mut attributes = None;
+
+        for attr in attrs {
+            if attr.path() != ENTITY {
+                continue;
+            }
+
+            attr.parse_nested_meta(|meta| {
+                if meta.path == TRANSIENT {
+                    if mode.is_some() {
+                        return Err(meta.error("a key field cannot be transient"));
+                    }
+                    transient = true;
+                    return Ok(());
+                }
+                if meta.path == ATTRIBUTES {
+                    if attributes.is_some() {
+                        return Err(meta.error("attributes may only be listed once"));
+                    }
+                    let content;
+                    syn::parenthesized!(content in meta.input);
+                    let names = content.parse_terminated(
+                        <syn::LitStr as syn::parse::Parse>::parse,
+                        syn::Token![,],
+                    )?;
+                    attributes = Some(names.iter().map(syn::LitStr::value).collect());
+                    return Ok(());
+                }
+                if meta.path != KEY {
+                    return Err(meta.error(
+                        "unsupported entity field attribute, expected `key`, `transient`, or \
+                         `attributes(...)`",
+                    ));
+                }
+                if transient {
+                    return Err(meta.error("a key field cannot be transient"));
+                }
+                if mode.is_some() {
+                    return Err(meta.error("a field may only be marked as a key once"));
+                }
+
+                let mut field_mode = KeyFieldMode::Ref;
+                if meta.input.lookahead1().peek(syn::token::Paren) {
+                    meta.parse_nested_meta(|inner| {
+                        if inner.path == COPY {
+                            field_mode = KeyFieldMode::Copy;
+                        } else if inner.path == BORROW {
+                            let lit = get_lit_str2(KEY, BORROW, &inner)?;
+                            field_mode = KeyFieldMode::Borrow(Box::new(lit.parse()?));
+                        } else {
+                            return Err(inner.error("expected `copy` or `borrow = \"...\"`"));
+                        }
+                        Ok(())
+                    })?;
+                }
+
+                mode = Some(field_mode);
+                Ok(())
+            })?;
+        }
+
+        Ok(Self {
+            key: mode,
+            transient,
+            attributes,
+        })
+    }
+}
+
+/// How a field of an `IntoUpdate` struct is assigned
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum UpdateFieldMode {
+    /// `SET #a = :v`
+    Set,
+    /// `SET #a = list_append(#a, :v)`
+    Append,
+    /// Assign the fields of a nested update under the attribute
+    Nested,
+    /// Assign each entry of a map under the attribute
+    Entries,
+}
+
+pub struct UpdateField {
+    pub ident: syn::Ident,
+    pub ty: syn::Type,
+    pub path: Vec<String>,
+    pub mode: UpdateFieldMode,
+}
+
+pub fn get_update_fields(
+    rename_rule: RenameRule,
+    fields: &syn::Fields,
+) -> syn::Result<Vec<UpdateField>> {
+    let mut update_fields = Vec::new();
+
+    for field in fields {
+        let ident = field
+            .ident
+            .clone()
+            .ok_or_else(|| syn::Error::new_spanned(field, "expected a named field"))?;
+
+        let mut path = None;
+        let mut mode = None;
+        let mut skip = false;
+
+        for attr in &field.attrs {
+            if attr.path() != UPDATE {
+                continue;
+            }
+
+            attr.parse_nested_meta(|meta| {
+                let field_mode = if meta.path == PATH {
+                    let lit = get_lit_str2(UPDATE, PATH, &meta)?;
+                    let segments: Vec<_> = lit.value().split('.').map(String::from).collect();
+                    if segments.iter().any(String::is_empty) {
+                        return Err(syn::Error::new_spanned(
+                            lit,
+                            "an update path must be a `.`-separated list of attribute names",
+                        ));
+                    }
+                    path = Some(segments);
+                    return Ok(());
+                } else if meta.path == SKIP {
+                    skip = true;
+                    return Ok(());
+                } else if meta.path == APPEND {
+                    UpdateFieldMode::Append
+                } else if meta.path == NESTED {
+                    UpdateFieldMode::Nested
+                } else if meta.path == ENTRIES {
+                    UpdateFieldMode::Entries
+                } else {
+                    return Err(meta.error(
+                        "unsupported update field attribute, expected `path`, `append`, `nested`, `entries`, or `skip`",
+                    ));
+                };
+
+                if mode.is_some() {
+                    return Err(meta.error(
+                        "only one of `append`, `nested`, or `entries` may be specified",
+                    ));
+                }
+                mode = Some(field_mode);
+                Ok(())
+            })?;
+        }
+
+        if skip {
+            continue;
+        }
+
+        let path = if let Some(path) = path {
+            path
+        } else {
+            let (flat, name) = field_name_override_from_attrs(&field.attrs)?;
+            if flat {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "flattened fields are not supported by IntoUpdate, use `#[update(nested)]` instead",
+                ));
+            }
+            let name = if let Some(name) = name {
+                name
+            } else {
+                get_field_name(rename_rule, Some(&ident))?
+            };
+            vec![name]
+        };
+
+        update_fields.push(UpdateField {
+            ident,
+            ty: field.ty.clone(),
+            path,
+            mode: mode.unwrap_or(UpdateFieldMode::Set),
+        });
+    }
+
+    Ok(update_fields)
+}
+
+/// Lists the attribute names of the fields
+///
+/// A flattened field contributes the attributes listed with
+/// `#[entity(attributes(...))]`. Without that list, the attributes of the
+/// flattened type cannot be identified, and an empty list is returned so
+/// that all attributes will be projected.
+pub fn get_field_names(rename_rule: RenameRule, fields: &syn::Fields) -> syn::Result<Vec<String>> {
     let mut field_names = Vec::new();
 
-    for field in &data.fields {
+    for field in fields {
+        let field_attrs = EntityFieldAttrs::from_ast(&field.attrs)?;
+        if field_attrs.transient {
+            continue;
+        }
+
         let (flat, name) = field_name_override_from_attrs(&field.attrs)?;
 
+        if let Some(attributes) = field_attrs.attributes {
+            if !flat {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "attributes may only be listed on a field marked with `#[serde(flatten)]`",
+                ));
+            }
+            field_names.extend(attributes);
+            continue;
+        }
+
         if flat {
             return Ok(Vec::new());
         }
@@ -85,6 +501,158 @@ pub fn get_field_names(
     Ok(field_names)
 }
 
+/// Pairs each field's name with the attribute name it is serialized to
+///
+/// Flattened fields are omitted, as they do not correspond to a single
+/// attribute, as are transient fields, which are never stored.
+pub fn get_field_attributes(
+    rename_rule: RenameRule,
+    fields: &syn::Fields,
+) -> syn::Result<Vec<(syn::Ident, String)>> {
+    let mut field_attributes = Vec::new();
+
+    for field in fields {
+        let Some(ident) = &field.ident else {
+            continue;
+        };
+
+        if is_transient(field)? {
+            continue;
+        }
+
+        let (flat, name) = field_name_override_from_attrs(&field.attrs)?;
+        if flat {
+            continue;
+        }
+
+        let name = if let Some(name) = name {
+            name
+        } else {
+            get_field_name(rename_rule, Some(ident))?
+        };
+
+        field_attributes.push((ident.clone(), name));
+    }
+
+    Ok(field_attributes)
+}
+
+/// Computes the union of the attributes used by the variants of an enum
+///
+/// An empty list is returned if any variant's attributes cannot be
+/// identified, such as a newtype variant stored without a `content`
+/// attribute, in which case all attributes will be projected.
+pub fn get_variant_field_names(
+    cont_attrs: &ContainerAttrs,
+    data: &syn::DataEnum,
+) -> syn::Result<Vec<String>> {
+    let mut field_names = Vec::new();
+    let mut push = |name: String| {
+        if !field_names.contains(&name) {
+            field_names.push(name);
+        }
+    };
+
+    match &cont_attrs.tagging {
+        Tagging::Internal(tag) => push(tag.clone()),
+        Tagging::Adjacent(tag, content) => {
+            push(tag.clone());
+            push(content.clone());
+        }
+        Tagging::External | Tagging::None => {}
+    }
+
+    for variant in &data.variants {
+        let attrs = VariantAttrs::from_ast(&variant.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+
+        match &cont_attrs.tagging {
+            Tagging::Adjacent(..) => continue,
+            Tagging::External => {
+                if !matches!(variant.fields, syn::Fields::Unit) {
+                    push(attrs.name.unwrap_or_else(|| {
+                        cont_attrs
+                            .rename_rule
+                            .apply_to_variant(&variant.ident.to_string())
+                    }));
+                }
+                continue;
+            }
+            Tagging::Internal(_) | Tagging::None => {}
+        }
+
+        match &variant.fields {
+            syn::Fields::Named(_) => {
+                let rename_rule = attrs.rename_rule.unwrap_or(cont_attrs.rename_all_fields);
+                let names = get_field_names(rename_rule, &variant.fields)?;
+                if names.is_empty() && !variant.fields.is_empty() {
+                    return Ok(Vec::new());
+                }
+                names.into_iter().for_each(&mut push);
+            }
+            syn::Fields::Unnamed(_) => return Ok(Vec::new()),
+            syn::Fields::Unit => {}
+        }
+    }
+
+    Ok(field_names)
+}
+
+struct VariantAttrs {
+    name: Option<String>,
+    rename_rule: Option<RenameRule>,
+    skip: bool,
+}
+
+impl VariantAttrs {
+    fn from_ast(attrs: &[syn::Attribute]) -> syn::Result<Self> {
+        let mut name = None;
+        let mut rename_rule = None;
+        let mut skip = false;
+
+        for attr in attrs {
+            if attr.path() != SERDE {
+                continue;
+            }
+
+            if let syn::Meta::List(meta) = &attr.meta {
+                if meta.tokens.is_empty() {
+                    continue;
+                }
+            }
+
+            attr.parse_nested_meta(|meta| {
+                if meta.path == RENAME {
+                    name = Some(get_lit_str2(SERDE, RENAME, &meta)?.value());
+                } else if meta.path == RENAME_ALL {
+                    rename_rule = Some(
+                        RenameRule::from_str(&get_lit_str2(SERDE, RENAME_ALL, &meta)?.value())
+                            .map_err(|err| syn::Error::new_spanned(attr, err))?,
+                    );
+                } else if meta.path == SKIP || meta.path == SKIP_DESERIALIZING {
+                    skip = true;
+                } else if meta.input.peek(syn::Token![=]) {
+                    let _: syn::Expr = meta.value()?.parse()?;
+                } else if meta.input.lookahead1().peek(syn::token::Paren) {
+                    meta.parse_nested_meta(|inner| {
+                        let _: syn::Expr = inner.value()?.parse()?;
+                        Ok(())
+                    })?;
+                }
+                Ok(())
+            })?;
+        }
+
+        Ok(Self {
+            name,
+            rename_rule,
+            skip,
+        })
+    }
+}
+
 fn get_field_name(rename_rule: RenameRule, name: Option<&syn::Ident>) -> syn::Result<String> {
     let name = name
         .ok_or_else(|| syn::Error::new_spanned(name, "expected a named field"))?
@@ -110,7 +678,7 @@ fn field_name_override_from_attrs(attrs: &[syn::Attribute]) -> syn::Result<(bool
 
         attr.parse_nested_meta(|meta| {
             if meta.path == RENAME {
-                name = Some(get_lit_str2(RENAME, RENAME, &meta)?.value());
+                name = Some(get_lit_str2(SERDE, RENAME, &meta)?.value());
             } else if meta.path == FLATTEN {
                 flat = true;
                 // return Err(meta.error("flatten is not currently supported by EntityDef"));
@@ -129,6 +697,77 @@ fn field_name_override_from_attrs(attrs: &[syn::Attribute]) -> syn::Result<(bool
     Ok((flat, name))
 }
 
+/// Whether `serde` may leave the field out of the serialized output
+fn field_skips_serializing(attrs: &[syn::Attribute]) -> syn::Result<bool> {
+    let mut skips = false;
+
+    for attr in attrs {
+        if attr.path() != SERDE {
+            continue;
+        }
+
+        if let syn::Meta::List(meta) = &attr.meta {
+            if meta.tokens.is_empty() {
+                continue;
+            }
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path == SKIP
+                || meta.path == SKIP_SERIALIZING
+                || meta.path == SKIP_SERIALIZING_IF
+            {
+                skips = true;
+            }
+            if meta.input.peek(syn::Token![=]) {
+                let _: syn::Expr = meta.value()?.parse()?;
+            } else if meta.input.lookahead1().peek(syn::token::Paren) {
+                meta.parse_nested_meta(|inner| {
+                    let _: syn::Expr = inner.value()?.parse()?;
+                    Ok(())
+                })?;
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(skips)
+}
+
+/// Whether `serde` will fill in the field when it is missing from the input
+fn field_has_default(attrs: &[syn::Attribute]) -> syn::Result<bool> {
+    let mut default = false;
+
+    for attr in attrs {
+        if attr.path() != SERDE {
+            continue;
+        }
+
+        if let syn::Meta::List(meta) = &attr.meta {
+            if meta.tokens.is_empty() {
+                continue;
+            }
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path == DEFAULT || meta.path == SKIP || meta.path == SKIP_DESERIALIZING {
+                default = true;
+            }
+            if meta.input.peek(syn::Token![=]) {
+                let _: syn::Expr = meta.value()?.parse()?;
+            } else if meta.input.lookahead1().peek(syn::token::Paren) {
+                meta.parse_nested_meta(|inner| {
+                    let _: syn::Expr = inner.value()?.parse()?;
+                    Ok(())
+                })?;
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(default)
+}
+
 pub fn get_lit_str2(
     attr_name: Symbol,
     meta_item_name: Symbol,
@@ -147,8 +786,8 @@ pub fn get_lit_str2(
         Ok(lit.clone())
     } else {
         Err(meta.error(format!(
-            "expected serde {} attribute to be a string: `{} = \"...\"`",
-            attr_name, meta_item_name
+            "expected {} {} attribute to be a string: `{} = \"...\"`",
+            attr_name, meta_item_name, meta_item_name
         )))
     }
 }
@@ -11,8 +11,9 @@ pub fn generate(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream
     };
 
     let cont_attrs = ContainerAttrs::from_ast(&input.attrs)?;
-    let field_names = get_field_names(cont_attrs.rename_rule, data)?;
+    let field_names = get_field_names(cont_attrs.rename_rule, &data.fields)?;
     let input_ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
     let entity_type = cont_attrs.entity.as_ref().ok_or_else(|| {
         syn::Error::new_spanned(
             &input,
@@ -20,13 +21,23 @@ pub fn generate(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream
         )
     })?;
 
-    Ok(quote! {
-        impl ::modyne::Projection for #input_ident {
+    let projection = quote! {
+        impl #impl_generics ::modyne::Projection for #input_ident #ty_generics #where_clause {
             type Entity = #entity_type;
             const PROJECTED_ATTRIBUTES: &'static [&'static str] = &[
                 #(#field_names ,)*
             ];
         }
+    };
+
+    // The attribute check runs in a free constant, where the generic
+    // parameters of the projection are not in scope
+    if !input.generics.params.is_empty() {
+        return Ok(projection);
+    }
+
+    Ok(quote! {
+        #projection
 
         /// Verify that the projection only contains attributes from the related entity
         ///
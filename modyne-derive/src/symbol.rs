@@ -5,11 +5,35 @@ use syn::{Ident, Path};
 #[derive(Copy, Clone)]
 pub struct Symbol(&'static str);
 
+pub const APPEND: Symbol = Symbol("append");
+pub const ATTRIBUTES: Symbol = Symbol("attributes");
+pub const BORROW: Symbol = Symbol("borrow");
+pub const CONTENT: Symbol = Symbol("content");
+pub const COPY: Symbol = Symbol("copy");
+pub const DEFAULT: Symbol = Symbol("default");
 pub const ENTITY: Symbol = Symbol("entity");
+pub const ENTRIES: Symbol = Symbol("entries");
+pub const ENTITY_TYPE: Symbol = Symbol("entity_type");
 pub const FLATTEN: Symbol = Symbol("flatten");
+pub const KEY: Symbol = Symbol("key");
+pub const KEY_INPUT: Symbol = Symbol("key_input");
+pub const NESTED: Symbol = Symbol("nested");
+pub const PATH: Symbol = Symbol("path");
 pub const RENAME: Symbol = Symbol("rename");
 pub const RENAME_ALL: Symbol = Symbol("rename_all");
+pub const RENAME_ALL_FIELDS: Symbol = Symbol("rename_all_fields");
 pub const SERDE: Symbol = Symbol("serde");
+pub const SKIP: Symbol = Symbol("skip");
+pub const SKIP_DESERIALIZING: Symbol = Symbol("skip_deserializing");
+pub const SKIP_SERIALIZING: Symbol = Symbol("skip_serializing");
+pub const SKIP_SERIALIZING_IF: Symbol = Symbol("skip_serializing_if");
+pub const TAG: Symbol = Symbol("tag");
+pub const TRANSIENT: Symbol = Symbol("transient");
+pub const UNIQUE: Symbol = Symbol("unique");
+pub const UNIQUE_INDEX: Symbol = Symbol("unique_index");
+pub const UNTAGGED: Symbol = Symbol("untagged");
+pub const UPDATE: Symbol = Symbol("update");
+pub const UPGRADE: Symbol = Symbol("upgrade");
 
 impl PartialEq<Symbol> for Ident {
     fn eq(&self, word: &Symbol) -> bool {
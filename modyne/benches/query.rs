@@ -0,0 +1,170 @@
+//! Benchmarks for executing queries against a canned in-memory HTTP client
+//!
+//! The HTTP client responds immediately without any network activity, so
+//! these benchmarks measure the overhead of preparing each request and
+//! processing its response, including the cost of paginating through
+//! multiple pages during hydration.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use aws_sdk_dynamodb::config::{BehaviorVersion, Credentials, Region};
+use aws_smithy_runtime::client::http::test_util::infallible_client_fn;
+use aws_smithy_types::body::SdkBody;
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use modyne::{expr, keys, Entity, EntityDef, EntityTypeNameRef, QueryInput, QueryInputExt, Table};
+
+const PAGES: usize = 4;
+
+struct BenchTable {
+    client: aws_sdk_dynamodb::Client,
+}
+
+impl BenchTable {
+    fn new() -> Self {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let http_client = infallible_client_fn(move |_request| {
+            let page = requests.fetch_add(1, Ordering::Relaxed) % PAGES;
+            http::Response::builder()
+                .status(200)
+                .body(SdkBody::from(page_body(page)))
+                .unwrap()
+        });
+
+        let config = aws_sdk_dynamodb::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("AKID", "SECRET", None, None, "bench"))
+            .endpoint_url("http://localhost:8000")
+            .http_client(http_client)
+            .build();
+
+        Self {
+            client: aws_sdk_dynamodb::Client::from_conf(config),
+        }
+    }
+}
+
+impl Table for BenchTable {
+    type PrimaryKey = keys::Primary;
+    type IndexKeys = keys::Gsi1;
+
+    fn table_name(&self) -> &str {
+        "bench"
+    }
+
+    fn client(&self) -> &aws_sdk_dynamodb::Client {
+        &self.client
+    }
+}
+
+/// Renders a page of results, with every page but the last indicating that more remain
+fn page_body(page: usize) -> String {
+    let items: Vec<_> = (0..25)
+        .map(|i| {
+            format!(
+                r#"{{"PK":{{"S":"ORDER#{page}"}},"SK":{{"S":"ITEM#{i:04}"}},"GSI1PK":{{"S":"ORDERS"}},"GSI1SK":{{"S":"ITEM#{i:04}"}},"entity_type":{{"S":"order_item"}},"order_id":{{"S":"{page}"}},"item_id":{{"S":"{i:04}"}},"description":{{"S":"A reasonably sized description of an order item"}},"quantity":{{"N":"{i}"}}}}"#
+            )
+        })
+        .collect();
+
+    let last_evaluated_key = if page + 1 < PAGES {
+        format!(r#","LastEvaluatedKey":{{"PK":{{"S":"ORDER#{page}"}},"SK":{{"S":"ITEM#0024"}}}}"#)
+    } else {
+        String::new()
+    };
+
+    format!(
+        r#"{{"Items":[{}],"Count":25,"ScannedCount":25{last_evaluated_key}}}"#,
+        items.join(",")
+    )
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct OrderItem {
+    order_id: String,
+    item_id: String,
+    description: String,
+    quantity: u32,
+}
+
+impl EntityDef for OrderItem {
+    const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("order_item");
+    const PROJECTED_ATTRIBUTES: &'static [&'static str] =
+        &["order_id", "item_id", "description", "quantity"];
+}
+
+impl Entity for OrderItem {
+    type KeyInput<'a> = (&'a str, &'a str);
+    type Table = BenchTable;
+    type IndexKeys = keys::Gsi1;
+
+    fn primary_key((order_id, item_id): Self::KeyInput<'_>) -> keys::Primary {
+        keys::Primary {
+            hash: format!("ORDER#{order_id}"),
+            range: format!("ITEM#{item_id}"),
+        }
+    }
+
+    fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+        keys::FullKey {
+            primary: Self::primary_key((&self.order_id, &self.item_id)),
+            indexes: keys::Gsi1 {
+                hash: "ORDERS".to_string(),
+                range: format!("ITEM#{}", self.item_id),
+            },
+        }
+    }
+}
+
+struct OrderItemsQuery<'a> {
+    order_id: &'a str,
+}
+
+impl QueryInput for OrderItemsQuery<'_> {
+    type Index = keys::Primary;
+    type Aggregate = Vec<OrderItem>;
+
+    fn key_condition(&self) -> expr::KeyCondition<Self::Index> {
+        expr::KeyCondition::in_partition(format!("ORDER#{}", self.order_id)).begins_with("ITEM#")
+    }
+
+    fn filter_expression(&self) -> Option<expr::Filter> {
+        Some(
+            expr::Filter::new("#quantity > :min AND #description <> :empty")
+                .name("#quantity", "quantity")
+                .name("#description", "description")
+                .value(":min", 0)
+                .value(":empty", ""),
+        )
+    }
+}
+
+fn query_benchmarks(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let table = &BenchTable::new();
+    let input = OrderItemsQuery { order_id: "1234" };
+
+    c.bench_function("query/execute_single_page", |b| {
+        b.to_async(&runtime).iter_batched(
+            || input.query(),
+            |query| async move { query.execute(table).await.unwrap() },
+            BatchSize::SmallInput,
+        )
+    });
+
+    c.bench_function("query/hydrate_pages", |b| {
+        b.to_async(&runtime).iter_batched(
+            || input.query(),
+            |query| async move { query.hydrate::<_, Vec<OrderItem>>(table).await.unwrap() },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+criterion_group!(benches, query_benchmarks);
+criterion_main!(benches);
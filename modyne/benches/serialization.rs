@@ -0,0 +1,229 @@
+//! Benchmarks for converting entities to and from items and building expressions
+//!
+//! With the `bench-baseline` feature enabled, the conversions are also
+//! measured using `serde_dynamo` directly, quantifying the overhead added
+//! by modyne for key generation and entity type handling.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use modyne::{
+    expr, keys, Entity, EntityDef, EntityExt, EntityTypeNameRef, Projection, ProjectionExt,
+    ProjectionSet, QueryInput, QueryInputExt, Table,
+};
+
+struct BenchTable;
+
+impl Table for BenchTable {
+    type PrimaryKey = keys::Primary;
+    type IndexKeys = (keys::Gsi1, keys::Gsi2);
+
+    fn table_name(&self) -> &str {
+        "bench"
+    }
+
+    fn client(&self) -> &aws_sdk_dynamodb::Client {
+        unimplemented!("benchmarks do not execute requests")
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+struct Order {
+    customer_id: String,
+    order_id: String,
+    status: String,
+    created_at: String,
+    shipping_address: Address,
+    notes: Option<String>,
+    total_cents: u64,
+    item_count: u32,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+struct Address {
+    street: String,
+    city: String,
+    region: String,
+    postal_code: String,
+}
+
+impl EntityDef for Order {
+    const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("order");
+    const PROJECTED_ATTRIBUTES: &'static [&'static str] = &[
+        "customer_id",
+        "order_id",
+        "status",
+        "created_at",
+        "shipping_address",
+        "notes",
+        "total_cents",
+        "item_count",
+    ];
+}
+
+impl Entity for Order {
+    type KeyInput<'a> = (&'a str, &'a str);
+    type Table = BenchTable;
+    type IndexKeys = (keys::Gsi1, keys::Gsi2);
+
+    fn primary_key((customer_id, order_id): Self::KeyInput<'_>) -> keys::Primary {
+        keys::Primary {
+            hash: format!("CUSTOMER#{customer_id}"),
+            range: format!("ORDER#{order_id}"),
+        }
+    }
+
+    fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+        keys::FullKey {
+            primary: Self::primary_key((&self.customer_id, &self.order_id)),
+            indexes: (
+                keys::Gsi1 {
+                    hash: format!("ORDER#{}", self.order_id),
+                    range: format!("ORDER#{}", self.order_id),
+                },
+                keys::Gsi2 {
+                    hash: format!("STATUS#{}", self.status),
+                    range: self.created_at.clone(),
+                },
+            ),
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[allow(dead_code)]
+struct OrderSummary {
+    order_id: String,
+    status: String,
+    total_cents: u64,
+}
+
+impl Projection for OrderSummary {
+    type Entity = Order;
+    const PROJECTED_ATTRIBUTES: &'static [&'static str] = &["order_id", "status", "total_cents"];
+}
+
+struct OrdersByStatusQuery<'a> {
+    status: &'a str,
+    since: &'a str,
+}
+
+impl QueryInput for OrdersByStatusQuery<'_> {
+    type Index = keys::Gsi2;
+    type Aggregate = Vec<Order>;
+
+    fn key_condition(&self) -> expr::KeyCondition<Self::Index> {
+        expr::KeyCondition::in_partition(format!("STATUS#{}", self.status))
+            .greater_than_or_equal(self.since)
+    }
+
+    fn filter_expression(&self) -> Option<expr::Filter> {
+        Some(
+            expr::Filter::new("#total_cents >= :min_total")
+                .name("#total_cents", "total_cents")
+                .value(":min_total", 10_000),
+        )
+    }
+}
+
+fn order() -> Order {
+    Order {
+        customer_id: "alexdebrie".to_string(),
+        order_id: "1VrgXBQ0VCshuQUnh1HrDIHQNwY".to_string(),
+        status: "SHIPPED".to_string(),
+        created_at: "2023-12-07T13:45:00.250000000Z".to_string(),
+        shipping_address: Address {
+            street: "111 1st Street".to_string(),
+            city: "Omaha".to_string(),
+            region: "NE".to_string(),
+            postal_code: "68102".to_string(),
+        },
+        notes: Some("Leave the package at the side door".to_string()),
+        total_cents: 12_499,
+        item_count: 3,
+    }
+}
+
+fn serialization_benchmarks(c: &mut Criterion) {
+    let item = order().into_item();
+
+    c.bench_function("entity/into_item", |b| {
+        b.iter_batched(order, |order| order.into_item(), BatchSize::SmallInput)
+    });
+
+    c.bench_function("entity/from_item", |b| {
+        b.iter_batched(
+            || item.clone(),
+            |item| Order::from_item(item).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+
+    c.bench_function("projection/from_item", |b| {
+        b.iter_batched(
+            || item.clone(),
+            |item| OrderSummary::from_item(item).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+
+    c.bench_function("projection_set/try_from_item", |b| {
+        b.iter_batched(
+            || item.clone(),
+            |item| <Order as ProjectionSet>::try_from_item(item).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+#[cfg(feature = "bench-baseline")]
+fn baseline_benchmarks(c: &mut Criterion) {
+    use modyne::Item;
+
+    let item: Item = serde_dynamo::aws_sdk_dynamodb_1::to_item(order()).unwrap();
+
+    c.bench_function("baseline/serde_dynamo_to_item", |b| {
+        b.iter_batched(
+            order,
+            |order| -> Item { serde_dynamo::aws_sdk_dynamodb_1::to_item(order).unwrap() },
+            BatchSize::SmallInput,
+        )
+    });
+
+    c.bench_function("baseline/serde_dynamo_from_item", |b| {
+        b.iter_batched(
+            || item.clone(),
+            |item| -> Order { serde_dynamo::aws_sdk_dynamodb_1::from_item(item).unwrap() },
+            BatchSize::SmallInput,
+        )
+    });
+}
+
+#[cfg(not(feature = "bench-baseline"))]
+fn baseline_benchmarks(_: &mut Criterion) {}
+
+fn expression_benchmarks(c: &mut Criterion) {
+    c.bench_function("projection/expression", |b| {
+        b.iter(|| {
+            expr::Projection::new(
+                black_box(<Order as EntityDef>::PROJECTED_ATTRIBUTES)
+                    .iter()
+                    .copied(),
+            )
+        })
+    });
+
+    c.bench_function("query/construct", |b| {
+        let input = OrdersByStatusQuery {
+            status: "SHIPPED",
+            since: "2023-12-01T00:00:00.000000000Z",
+        };
+        b.iter(|| black_box(&input).query())
+    });
+}
+
+criterion_group!(
+    benches,
+    serialization_benchmarks,
+    baseline_benchmarks,
+    expression_benchmarks
+);
+criterion_main!(benches);
@@ -0,0 +1,563 @@
+//! Combinators for composing aggregates
+//!
+//! Read models are often built from several smaller aggregates. Rather than
+//! implementing [`Aggregate::merge()`] from scratch for each combination,
+//! aggregates can be composed:
+//!
+//! * a pair `(A, B)` of aggregates is itself an aggregate, merging each item
+//!   into both halves, and projecting the attributes needed by either, and
+//! * [`Filtered<A, P>`] only merges the items accepted by an
+//!   [`ItemPredicate`] into the inner aggregate, and
+//! * [`SizeAccounted<A, T>`] records the approximate size of the items
+//!   merged into the inner aggregate, by entity type, to show which entity
+//!   types dominate the storage and read traffic of a partition.
+//!
+//! Pairs may be nested, such as `(A, (B, C))`, to compose more than two
+//! aggregates.
+//!
+//! ```
+//! use modyne::{
+//!     aggregate::{Filtered, ItemPredicate},
+//!     AttributeValue, Item,
+//! };
+//! # use modyne::{keys, Entity, EntityDef, Table};
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! # #[derive(Debug, EntityDef, serde::Serialize, serde::Deserialize)]
+//! # struct Order { order_id: String, status: String }
+//! # impl Entity for Order {
+//! #     type KeyInput<'a> = &'a str;
+//! #     type Table = App;
+//! #     type IndexKeys = ();
+//! #     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+//! #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+//! # }
+//! # #[derive(Debug, EntityDef, serde::Serialize, serde::Deserialize)]
+//! # struct Customer { user_name: String }
+//! # impl Entity for Customer {
+//! #     type KeyInput<'a> = &'a str;
+//! #     type Table = App;
+//! #     type IndexKeys = ();
+//! #     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+//! #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+//! # }
+//!
+//! #[derive(Debug, Default)]
+//! struct Shipped;
+//!
+//! impl ItemPredicate for Shipped {
+//!     fn accepts(item: &Item) -> bool {
+//!         item.get("status") == Some(&AttributeValue::S("SHIPPED".into()))
+//!     }
+//! }
+//!
+//! /// The customers in a partition, along with their shipped orders
+//! type CustomerView = (Vec<Customer>, Filtered<Vec<Order>, Shipped>);
+//! ```
+
+use std::{collections::HashMap, fmt, marker::PhantomData};
+
+use crate::{
+    expr, Aggregate, EntityTypeName, EntityTypeNameRef, Error, Item, ProjectionSet, Table,
+};
+
+/// A projection from one of two projection sets
+///
+/// This is the projection set of a pair of aggregates. An item is parsed
+/// into the left projection set if possible, and otherwise into the right.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Either<L, R> {
+    /// A projection from the left projection set
+    Left(L),
+
+    /// A projection from the right projection set
+    Right(R),
+}
+
+impl<L, R> ProjectionSet for Either<L, R>
+where
+    L: ProjectionSet + 'static,
+    R: ProjectionSet + 'static,
+{
+    fn try_from_item(item: Item) -> Result<Option<Self>, Error> {
+        if let Some(left) = L::try_from_item(item.clone())? {
+            return Ok(Some(Self::Left(left)));
+        }
+
+        Ok(R::try_from_item(item)?.map(Self::Right))
+    }
+
+    fn try_from_item_all(item: Item) -> Result<Vec<Self>, Error> {
+        let mut parsed: Vec<_> = L::try_from_item_all(item.clone())?
+            .into_iter()
+            .map(Self::Left)
+            .collect();
+        parsed.extend(R::try_from_item_all(item)?.into_iter().map(Self::Right));
+        Ok(parsed)
+    }
+
+    fn projection_expression() -> Option<expr::StaticProjection> {
+        union_projection::<Self>(L::projection_expression(), R::projection_expression())
+    }
+
+    fn entity_types() -> Vec<&'static EntityTypeNameRef> {
+        let (left, right) = (L::entity_types(), R::entity_types());
+        if left.is_empty() || right.is_empty() {
+            return Vec::new();
+        }
+
+        let mut entity_types = left;
+        for entity_type in right {
+            if !entity_types.contains(&entity_type) {
+                entity_types.push(entity_type);
+            }
+        }
+        entity_types
+    }
+}
+
+impl<A, B> Aggregate for (A, B)
+where
+    A: Aggregate + 'static,
+    B: Aggregate + 'static,
+{
+    type Projections = Either<A::Projections, B::Projections>;
+
+    fn projection_expression() -> Option<expr::StaticProjection> {
+        union_projection::<Self>(A::projection_expression(), B::projection_expression())
+    }
+
+    fn merge(&mut self, item: Item) -> Result<(), Error> {
+        self.0.merge(item.clone())?;
+        self.1.merge(item)
+    }
+}
+
+/// A predicate deciding which items are merged into a [`Filtered`] aggregate
+///
+/// The predicate is evaluated on the client, after the items have been read,
+/// so items that are rejected still consume read capacity. Where possible,
+/// prefer a key condition or filter expression on the query.
+pub trait ItemPredicate {
+    /// Whether the item should be merged into the aggregate
+    fn accepts(item: &Item) -> bool;
+}
+
+/// An aggregate that only merges the items accepted by a predicate
+pub struct Filtered<A, P> {
+    inner: A,
+    predicate: PhantomData<fn() -> P>,
+}
+
+impl<A, P> Filtered<A, P> {
+    /// Returns a reference to the inner aggregate
+    #[inline]
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// Returns the inner aggregate
+    #[inline]
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+}
+
+impl<A: fmt::Debug, P> fmt::Debug for Filtered<A, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Filtered")
+            .field("inner", &self.inner)
+            .field("predicate", &std::any::type_name::<P>())
+            .finish()
+    }
+}
+
+impl<A: Default, P> Default for Filtered<A, P> {
+    fn default() -> Self {
+        Self {
+            inner: A::default(),
+            predicate: PhantomData,
+        }
+    }
+}
+
+impl<A, P> Aggregate for Filtered<A, P>
+where
+    A: Aggregate,
+    P: ItemPredicate,
+{
+    type Projections = A::Projections;
+
+    fn projection_expression() -> Option<expr::StaticProjection> {
+        A::projection_expression()
+    }
+
+    fn merge(&mut self, item: Item) -> Result<(), Error> {
+        if P::accepts(&item) {
+            self.inner.merge(item)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Size statistics for the items of one entity type
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ItemSizes {
+    /// The number of items
+    pub count: u64,
+
+    /// The combined estimated size of the items, in bytes
+    pub total_bytes: u64,
+
+    /// The estimated size of the largest item, in bytes
+    pub max_bytes: u64,
+}
+
+impl ItemSizes {
+    /// The average estimated size of the items, in bytes
+    pub fn average_bytes(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.total_bytes as f64 / self.count as f64
+        }
+    }
+
+    fn record(&mut self, size: u64) {
+        self.count += 1;
+        self.total_bytes += size;
+        self.max_bytes = self.max_bytes.max(size);
+    }
+
+    fn combine(mut self, other: &Self) -> Self {
+        self.count += other.count;
+        self.total_bytes += other.total_bytes;
+        self.max_bytes = self.max_bytes.max(other.max_bytes);
+        self
+    }
+}
+
+/// An aggregate that records the size of the items merged into it, by
+/// entity type
+///
+/// Each item is measured with [`size::item_size()`][crate::size::item_size()]
+/// before it is merged into the inner aggregate, and grouped by the entity
+/// type read using the conventions of the table `T`. Sizes are those of the
+/// items as read, so when the aggregate's projection omits attributes, they
+/// understate the stored size of the items.
+///
+/// ```
+/// # use modyne::{keys, Entity, EntityDef, Table};
+/// # struct App;
+/// # impl Table for App {
+/// #     type PrimaryKey = keys::Primary;
+/// #     type IndexKeys = keys::Gsi1;
+/// #     fn table_name(&self) -> &str { unimplemented!() }
+/// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+/// # }
+/// # #[derive(Debug, EntityDef, serde::Serialize, serde::Deserialize)]
+/// # struct Order { order_id: String }
+/// # impl Entity for Order {
+/// #     type KeyInput<'a> = &'a str;
+/// #     type Table = App;
+/// #     type IndexKeys = ();
+/// #     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+/// #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+/// # }
+/// use modyne::aggregate::SizeAccounted;
+///
+/// type MeasuredOrders = SizeAccounted<Vec<Order>, App>;
+///
+/// fn report(orders: &MeasuredOrders) {
+///     for (entity_type, sizes) in orders.sizes() {
+///         println!(
+///             "{}: {} items, {} bytes",
+///             entity_type.map_or("<unknown>", |t| t.as_str()),
+///             sizes.count,
+///             sizes.total_bytes,
+///         );
+///     }
+/// }
+/// ```
+pub struct SizeAccounted<A, T> {
+    inner: A,
+    sizes: HashMap<Option<EntityTypeName>, ItemSizes>,
+    table: PhantomData<fn() -> T>,
+}
+
+impl<A, T> SizeAccounted<A, T> {
+    /// Returns a reference to the inner aggregate
+    #[inline]
+    pub fn inner(&self) -> &A {
+        &self.inner
+    }
+
+    /// Returns the inner aggregate
+    #[inline]
+    pub fn into_inner(self) -> A {
+        self.inner
+    }
+
+    /// The sizes of the items of each entity type, largest total first
+    ///
+    /// Items without a readable entity type are grouped under `None`.
+    pub fn sizes(&self) -> Vec<(Option<&EntityTypeNameRef>, ItemSizes)> {
+        let mut sizes: Vec<_> = self
+            .sizes
+            .iter()
+            .map(|(entity_type, sizes)| (entity_type.as_deref(), *sizes))
+            .collect();
+        sizes.sort_by(|(l_type, l), (r_type, r)| {
+            r.total_bytes
+                .cmp(&l.total_bytes)
+                .then_with(|| l_type.map(|t| t.as_str()).cmp(&r_type.map(|t| t.as_str())))
+        });
+        sizes
+    }
+
+    /// The sizes of the items of the given entity type
+    pub fn sizes_of(&self, entity_type: &EntityTypeNameRef) -> ItemSizes {
+        self.sizes
+            .get(&Some(entity_type.to_owned()))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The sizes of all items, regardless of entity type
+    pub fn total(&self) -> ItemSizes {
+        self.sizes
+            .values()
+            .fold(ItemSizes::default(), ItemSizes::combine)
+    }
+}
+
+impl<A: fmt::Debug, T> fmt::Debug for SizeAccounted<A, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SizeAccounted")
+            .field("inner", &self.inner)
+            .field("sizes", &self.sizes)
+            .finish()
+    }
+}
+
+impl<A: Default, T> Default for SizeAccounted<A, T> {
+    fn default() -> Self {
+        Self {
+            inner: A::default(),
+            sizes: HashMap::new(),
+            table: PhantomData,
+        }
+    }
+}
+
+impl<A, T> Aggregate for SizeAccounted<A, T>
+where
+    A: Aggregate,
+    T: Table,
+{
+    type Projections = A::Projections;
+
+    fn projection_expression() -> Option<expr::StaticProjection> {
+        A::projection_expression()
+    }
+
+    fn merge(&mut self, item: Item) -> Result<(), Error> {
+        let size = crate::size::item_size(&item) as u64;
+        let entity_type = crate::__private::get_table_entity_type::<T>(&item)
+            .ok()
+            .map(ToOwned::to_owned);
+        self.sizes.entry(entity_type).or_default().record(size);
+        self.inner.merge(item)
+    }
+}
+
+/// Computes the projection covering the attributes of both projections
+///
+/// Returns `None` if either projection retrieves full items. The union is
+/// computed once for each combinator type `C` and leaked, in the same way as
+/// the projection expressions of single entity types.
+fn union_projection<C: 'static>(
+    left: Option<expr::StaticProjection>,
+    right: Option<expr::StaticProjection>,
+) -> Option<expr::StaticProjection> {
+    use std::{any::TypeId, collections::BTreeMap, sync::RwLock};
+
+    static UNION_PROJECTION_EXPRESSION: RwLock<BTreeMap<TypeId, expr::StaticProjection>> =
+        RwLock::new(BTreeMap::new());
+
+    let (left, right) = (left?, right?);
+
+    {
+        let projections = UNION_PROJECTION_EXPRESSION.read().unwrap();
+        if let Some(&projection) = projections.get(&TypeId::of::<C>()) {
+            return Some(projection);
+        }
+    }
+
+    let mut projections = UNION_PROJECTION_EXPRESSION.write().unwrap();
+    let projection = *projections.entry(TypeId::of::<C>()).or_insert_with(|| {
+        expr::Projection::new(left.attribute_names().chain(right.attribute_names())).leak()
+    });
+    Some(projection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{keys, AttributeValue, Entity, EntityDef, EntityExt, EntityTypeNameRef, Table};
+
+    struct TestTable;
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+
+        fn table_name(&self) -> &str {
+            unimplemented!()
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    struct Order {
+        id: String,
+        status: String,
+    }
+
+    impl EntityDef for Order {
+        const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("order");
+        const PROJECTED_ATTRIBUTES: &'static [&'static str] = &["id", "status"];
+    }
+
+    impl Entity for Order {
+        type KeyInput<'a> = &'a str;
+        type Table = TestTable;
+        type IndexKeys = ();
+
+        fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+            keys::Primary {
+                hash: "CUSTOMER".to_string(),
+                range: format!("ORDER#{id}"),
+            }
+        }
+
+        fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+            Self::primary_key(&self.id).into()
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    struct Customer {
+        user_name: String,
+    }
+
+    impl EntityDef for Customer {
+        const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("customer");
+        const PROJECTED_ATTRIBUTES: &'static [&'static str] = &["user_name"];
+    }
+
+    impl Entity for Customer {
+        type KeyInput<'a> = ();
+        type Table = TestTable;
+        type IndexKeys = ();
+
+        fn primary_key(_: Self::KeyInput<'_>) -> keys::Primary {
+            keys::Primary {
+                hash: "CUSTOMER".to_string(),
+                range: "CUSTOMER".to_string(),
+            }
+        }
+
+        fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+            Self::primary_key(()).into()
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct Shipped;
+
+    impl ItemPredicate for Shipped {
+        fn accepts(item: &Item) -> bool {
+            item.get("status") == Some(&AttributeValue::S("SHIPPED".to_string()))
+        }
+    }
+
+    fn items() -> Vec<Item> {
+        vec![
+            Customer {
+                user_name: "alexdebrie".to_string(),
+            }
+            .into_item(),
+            Order {
+                id: "1".to_string(),
+                status: "SHIPPED".to_string(),
+            }
+            .into_item(),
+            Order {
+                id: "2".to_string(),
+                status: "PENDING".to_string(),
+            }
+            .into_item(),
+        ]
+    }
+
+    #[test]
+    fn pairs_merge_items_into_both_aggregates() {
+        let mut aggregate = <(Vec<Customer>, Vec<Order>)>::default();
+        aggregate.reduce(items()).unwrap();
+
+        assert_eq!(aggregate.0.len(), 1);
+        assert_eq!(aggregate.1.len(), 2);
+    }
+
+    #[test]
+    fn filtered_aggregates_skip_rejected_items() {
+        let mut aggregate = <(Vec<Customer>, Filtered<Vec<Order>, Shipped>)>::default();
+        aggregate.reduce(items()).unwrap();
+
+        assert_eq!(aggregate.0.len(), 1);
+        let orders = aggregate.1.into_inner();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].id, "1");
+    }
+
+    #[test]
+    fn size_accounted_aggregates_record_sizes_by_entity_type() {
+        let mut aggregate = <SizeAccounted<(Vec<Customer>, Vec<Order>), TestTable>>::default();
+        aggregate.reduce(items()).unwrap();
+
+        assert_eq!(aggregate.inner().1.len(), 2);
+
+        let orders = aggregate.sizes_of(Order::ENTITY_TYPE);
+        let order_sizes: Vec<_> = items()[1..]
+            .iter()
+            .map(|item| crate::size::item_size(item) as u64)
+            .collect();
+        assert_eq!(orders.count, 2);
+        assert_eq!(orders.total_bytes, order_sizes.iter().sum::<u64>());
+        assert_eq!(orders.max_bytes, *order_sizes.iter().max().unwrap());
+
+        let sizes = aggregate.sizes();
+        assert_eq!(sizes[0].0, Some(Order::ENTITY_TYPE));
+        assert_eq!(sizes[1].0, Some(Customer::ENTITY_TYPE));
+        assert_eq!(aggregate.total().count, 3);
+    }
+
+    #[test]
+    fn pairs_project_the_attributes_of_both_aggregates() {
+        let projection =
+            <(Vec<Customer>, Vec<Order>) as Aggregate>::projection_expression().unwrap();
+        let mut attributes: Vec<_> = projection.attribute_names().collect();
+        attributes.sort_unstable();
+
+        assert_eq!(attributes, ["entity_type", "id", "status", "user_name"]);
+    }
+}
@@ -0,0 +1,457 @@
+//! Analysis of the key patterns used by the entities stored in a table
+//!
+//! In a single-table design, entities of different types often share a
+//! partition, being distinguished only by the prefix of their sort keys. If
+//! the sort key prefix of one entity type is itself a prefix of another's,
+//! a `begins_with` key condition intended for one type will also match
+//! items of the other. If two entity types produce the same primary key,
+//! writing one will overwrite the other.
+//!
+//! The analyzer returned by [`analyze()`][crate::analyze()] inspects the keys
+//! produced by sample entities to find such problems. Each entity type
+//! registered with the table using [`table_entities!`][crate::table_entities!]
+//! should be sampled at least once. Samples that share key inputs, such as
+//! a customer and that customer's orders, are most likely to reveal
+//! problems.
+//!
+//! The prefix of a key value is the portion up to and including the first
+//! `#` separator, ignoring any leading separators. For example, the prefix
+//! of `#ORDER#1234` is `#ORDER#`. Values without a separator, and numbers,
+//! may be identifiers such as email addresses, so they are grouped under
+//! the `<unprefixed>` placeholder rather than reported.
+//!
+//! ```
+//! # use modyne::{keys, Entity, EntityDef, Table};
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! #[derive(EntityDef, serde::Serialize, serde::Deserialize)]
+//! struct Order {
+//!     user_name: String,
+//!     order_id: String,
+//! }
+//!
+//! impl Entity for Order {
+//!     type KeyInput<'a> = (&'a str, &'a str);
+//!     type Table = App;
+//!     type IndexKeys = ();
+//!
+//!     fn primary_key((user_name, order_id): Self::KeyInput<'_>) -> keys::Primary {
+//!         keys::Primary {
+//!             hash: format!("CUSTOMER#{user_name}"),
+//!             range: format!("ORDER#{order_id}"),
+//!         }
+//!     }
+//!
+//!     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+//!         Self::primary_key((&self.user_name, &self.order_id)).into()
+//!     }
+//! }
+//!
+//! #[derive(EntityDef, serde::Serialize, serde::Deserialize)]
+//! struct Return {
+//!     user_name: String,
+//!     return_id: String,
+//! }
+//!
+//! impl Entity for Return {
+//!     type KeyInput<'a> = (&'a str, &'a str);
+//!     type Table = App;
+//!     type IndexKeys = ();
+//!
+//!     fn primary_key((user_name, return_id): Self::KeyInput<'_>) -> keys::Primary {
+//!         keys::Primary {
+//!             hash: format!("CUSTOMER#{user_name}"),
+//!             range: format!("ORDER#RETURN#{return_id}"),
+//!         }
+//!     }
+//!
+//!     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+//!         Self::primary_key((&self.user_name, &self.return_id)).into()
+//!     }
+//! }
+//!
+//! modyne::table_entities!(App => [Order, Return]);
+//!
+//! let findings = modyne::analyze::<App>()
+//!     .sample(&Order { user_name: "alexdebrie".into(), order_id: "1234".into() })
+//!     .sample(&Return { user_name: "alexdebrie".into(), return_id: "5678".into() })
+//!     .findings();
+//!
+//! assert_eq!(findings.len(), 1);
+//! assert_eq!(
+//!     findings[0].to_string(),
+//!     "sort key prefix `ORDER#` of `order` is a prefix of sort key prefix \
+//!      `ORDER#` of `return` in partitions prefixed `CUSTOMER#` of the table",
+//! );
+//! ```
+
+use std::{collections::BTreeSet, fmt, marker::PhantomData};
+
+use crate::{
+    keys::{IndexKeys, KeyDefinition, PrimaryKey},
+    registry::EntityRegistry,
+    AttributeValue, Entity, EntityTypeNameRef, Item,
+};
+
+/// An analyzer of the key patterns used by the entities stored in a table
+///
+/// Created by [`analyze()`][crate::analyze()].
+#[must_use]
+pub struct Analysis<T> {
+    samples: Vec<Sample>,
+    table: PhantomData<fn() -> T>,
+}
+
+struct Sample {
+    entity_type: &'static EntityTypeNameRef,
+    key: Item,
+}
+
+impl<T> fmt::Debug for Analysis<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Analysis")
+            .field("table", &std::any::type_name::<T>())
+            .field("samples", &self.samples.len())
+            .finish()
+    }
+}
+
+impl<T: EntityRegistry> Default for Analysis<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: EntityRegistry> Analysis<T> {
+    /// Prepares an analysis of the entities registered with the table
+    pub fn new() -> Self {
+        Self {
+            samples: Vec::new(),
+            table: PhantomData,
+        }
+    }
+
+    /// Adds the keys of a sample entity to the analysis
+    pub fn sample<E>(mut self, entity: &E) -> Self
+    where
+        E: Entity<Table = T>,
+    {
+        self.samples.push(Sample {
+            entity_type: E::ENTITY_TYPE,
+            key: entity.full_key().into_key(),
+        });
+        self
+    }
+
+    /// Analyzes the sampled keys
+    ///
+    /// Each finding is also logged as a warning.
+    pub fn findings(&self) -> Vec<KeyFinding> {
+        let mut findings = Vec::new();
+
+        for entity in T::ENTITIES {
+            if !self
+                .samples
+                .iter()
+                .any(|s| s.entity_type == entity.entity_type())
+            {
+                findings.push(KeyFinding::Unsampled {
+                    entity_type: entity.entity_type(),
+                });
+            }
+        }
+
+        let mut unregistered = BTreeSet::new();
+        for sample in &self.samples {
+            if T::descriptor(sample.entity_type).is_none()
+                && unregistered.insert(sample.entity_type)
+            {
+                findings.push(KeyFinding::Unregistered {
+                    entity_type: sample.entity_type,
+                });
+            }
+        }
+
+        self.find_shared_primary_keys(&mut findings);
+
+        let definitions =
+            std::iter::once(KeyDefinition::from(T::PrimaryKey::PRIMARY_KEY_DEFINITION)).chain(
+                T::IndexKeys::KEY_DEFINITIONS
+                    .iter()
+                    .map(|def| KeyDefinition::from(*def)),
+            );
+        for definition in definitions {
+            self.find_prefix_overlaps(&definition, &mut findings);
+        }
+
+        for finding in &findings {
+            tracing::warn!(%finding, "key pattern problem");
+        }
+
+        findings
+    }
+
+    fn find_shared_primary_keys(&self, findings: &mut Vec<KeyFinding>) {
+        let definition = T::PrimaryKey::PRIMARY_KEY_DEFINITION;
+        let mut reported = BTreeSet::new();
+
+        for (index, sample) in self.samples.iter().enumerate() {
+            for other in &self.samples[..index] {
+                if other.entity_type == sample.entity_type {
+                    continue;
+                }
+
+                let same_key = std::iter::once(definition.hash_key)
+                    .chain(definition.range_key)
+                    .all(|attr| {
+                        sample.key.contains_key(attr) && sample.key.get(attr) == other.key.get(attr)
+                    });
+                if same_key && reported.insert((other.entity_type, sample.entity_type)) {
+                    findings.push(KeyFinding::SharedPrimaryKey {
+                        first: other.entity_type,
+                        second: sample.entity_type,
+                    });
+                }
+            }
+        }
+    }
+
+    fn find_prefix_overlaps(&self, definition: &KeyDefinition, findings: &mut Vec<KeyFinding>) {
+        let patterns: BTreeSet<_> = self
+            .samples
+            .iter()
+            .filter_map(|sample| {
+                let hash = key_prefix(sample.key.get(definition.hash_key())?)?;
+                let range = match definition.range_key() {
+                    Some(attr) => Some(key_prefix(sample.key.get(attr)?)?),
+                    None => None,
+                };
+                Some((sample.entity_type, hash, range))
+            })
+            .collect();
+
+        for (first, first_hash, first_range) in &patterns {
+            for (second, second_hash, second_range) in &patterns {
+                if first == second {
+                    continue;
+                }
+
+                let (first, second) = (*first, *second);
+                let finding = match (first_range, second_range) {
+                    (Some(first_range), Some(second_range)) => {
+                        if first_hash != second_hash
+                            || !second_range.starts_with(first_range.as_str())
+                            || (first_range == second_range && first > second)
+                        {
+                            continue;
+                        }
+
+                        KeyFinding::SortKeyPrefixOverlap {
+                            index_name: definition.index_name(),
+                            partition_prefix: first_hash.clone(),
+                            first,
+                            first_prefix: first_range.clone(),
+                            second,
+                            second_prefix: second_range.clone(),
+                        }
+                    }
+                    _ => {
+                        if !second_hash.starts_with(first_hash.as_str())
+                            || (first_hash == second_hash && first > second)
+                        {
+                            continue;
+                        }
+
+                        KeyFinding::PartitionKeyPrefixOverlap {
+                            index_name: definition.index_name(),
+                            first,
+                            first_prefix: first_hash.clone(),
+                            second,
+                            second_prefix: second_hash.clone(),
+                        }
+                    }
+                };
+
+                if !findings.contains(&finding) {
+                    findings.push(finding);
+                }
+            }
+        }
+    }
+}
+
+/// The prefix reported for key values without a separator
+pub(crate) const UNPREFIXED: &str = "<unprefixed>";
+
+/// Extracts the prefix of a key value
+///
+/// Numbers and strings without a separator are reported as [`UNPREFIXED`].
+/// Returns `None` for values that are not strings or numbers.
+pub(crate) fn key_prefix(value: &AttributeValue) -> Option<String> {
+    let value = match value {
+        AttributeValue::S(s) => s.as_str(),
+        AttributeValue::N(_) => UNPREFIXED,
+        _ => return None,
+    };
+
+    let start = value.len() - value.trim_start_matches('#').len();
+    let prefix = match value[start..].find('#') {
+        Some(end) => &value[..start + end + 1],
+        None => UNPREFIXED,
+    };
+
+    Some(prefix.to_string())
+}
+
+/// A potential problem with the key patterns of the entities stored in a table
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum KeyFinding {
+    /// A registered entity type was not sampled
+    Unsampled {
+        /// The entity type
+        entity_type: &'static EntityTypeNameRef,
+    },
+
+    /// An entity type was sampled, but is not registered with the table
+    Unregistered {
+        /// The entity type
+        entity_type: &'static EntityTypeNameRef,
+    },
+
+    /// Samples of two entity types produced the same primary key, so
+    /// writing one would overwrite the other
+    SharedPrimaryKey {
+        /// The entity type sampled first
+        first: &'static EntityTypeNameRef,
+
+        /// The entity type sampled second
+        second: &'static EntityTypeNameRef,
+    },
+
+    /// The partition key prefix of one entity type is a prefix of another's
+    /// in an index without a sort key
+    PartitionKeyPrefixOverlap {
+        /// The name of the index, or `None` for the table itself
+        index_name: Option<&'static str>,
+
+        /// The entity type with the shorter prefix
+        first: &'static EntityTypeNameRef,
+
+        /// The partition key prefix of the first entity type
+        first_prefix: String,
+
+        /// The entity type with the longer prefix
+        second: &'static EntityTypeNameRef,
+
+        /// The partition key prefix of the second entity type
+        second_prefix: String,
+    },
+
+    /// Two entity types share partitions, and the sort key prefix of one
+    /// is a prefix of the other's
+    SortKeyPrefixOverlap {
+        /// The name of the index, or `None` for the table itself
+        index_name: Option<&'static str>,
+
+        /// The prefix of the shared partitions
+        partition_prefix: String,
+
+        /// The entity type with the shorter prefix
+        first: &'static EntityTypeNameRef,
+
+        /// The sort key prefix of the first entity type
+        first_prefix: String,
+
+        /// The entity type with the longer prefix
+        second: &'static EntityTypeNameRef,
+
+        /// The sort key prefix of the second entity type
+        second_prefix: String,
+    },
+}
+
+struct IndexLabel(Option<&'static str>);
+
+impl fmt::Display for IndexLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(index_name) => write!(f, "index `{index_name}`"),
+            None => f.write_str("the table"),
+        }
+    }
+}
+
+impl fmt::Display for KeyFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsampled { entity_type } => {
+                write!(f, "registered entity type `{entity_type}` was not sampled")
+            }
+            Self::Unregistered { entity_type } => {
+                write!(f, "sampled entity type `{entity_type}` is not registered")
+            }
+            Self::SharedPrimaryKey { first, second } => {
+                write!(f, "`{first}` and `{second}` produced the same primary key")
+            }
+            Self::PartitionKeyPrefixOverlap {
+                index_name,
+                first,
+                first_prefix,
+                second,
+                second_prefix,
+            } => write!(
+                f,
+                "partition key prefix `{first_prefix}` of `{first}` is a prefix of partition key \
+                 prefix `{second_prefix}` of `{second}` in {}",
+                IndexLabel(*index_name),
+            ),
+            Self::SortKeyPrefixOverlap {
+                index_name,
+                partition_prefix,
+                first,
+                first_prefix,
+                second,
+                second_prefix,
+            } => write!(
+                f,
+                "sort key prefix `{first_prefix}` of `{first}` is a prefix of sort key prefix \
+                 `{second_prefix}` of `{second}` in partitions prefixed `{partition_prefix}` of {}",
+                IndexLabel(*index_name),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_prefix_ends_at_first_separator() {
+        let prefix = |s: &str| key_prefix(&AttributeValue::S(s.into()));
+
+        assert_eq!(prefix("CUSTOMER#alexdebrie").as_deref(), Some("CUSTOMER#"));
+        assert_eq!(prefix("#ORDER#1234").as_deref(), Some("#ORDER#"));
+        assert_eq!(prefix("ORDER#1234#ITEM#5").as_deref(), Some("ORDER#"));
+        assert_eq!(key_prefix(&AttributeValue::Bool(true)), None);
+    }
+
+    #[test]
+    fn values_without_a_prefix_are_not_reported() {
+        let prefix = |s: &str| key_prefix(&AttributeValue::S(s.into()));
+
+        assert_eq!(prefix("CUSTOMERS").as_deref(), Some(UNPREFIXED));
+        assert_eq!(prefix("alex@example.com").as_deref(), Some(UNPREFIXED));
+        assert_eq!(prefix("##").as_deref(), Some(UNPREFIXED));
+        assert_eq!(
+            key_prefix(&AttributeValue::N("5551234".into())).as_deref(),
+            Some(UNPREFIXED)
+        );
+    }
+}
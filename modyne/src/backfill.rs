@@ -0,0 +1,558 @@
+//! Backfilling key attributes after changes to the key design
+//!
+//! When a new secondary index is added to an existing single-table design, or
+//! the way an entity computes its keys changes, every existing item must be
+//! rewritten so that its key attributes match the output of
+//! [`Entity::full_key`]. A [`Backfill`] scans the table using parallel
+//! segments, deserializes each item of a registered entity type, recomputes
+//! its keys, and writes back only the key attributes that changed.
+//!
+//! ```no_run
+//! # use modyne::{keys, Entity, EntityDef, EntityTypeNameRef, Table, WritableTable};
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = (keys::Gsi1, keys::Gsi2);
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! # impl WritableTable for App {}
+//! # #[derive(serde::Deserialize)]
+//! # struct Order { id: String }
+//! # impl EntityDef for Order {
+//! #     const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("order");
+//! # }
+//! # impl Entity for Order {
+//! #     type KeyInput<'a> = &'a str;
+//! #     type Table = App;
+//! #     type IndexKeys = keys::Gsi2;
+//! #     fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+//! #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+//! # }
+//! use modyne::backfill::Backfill;
+//!
+//! # async fn example(app: App) -> Result<(), modyne::Error> {
+//! let report = Backfill::new(&app)
+//!     .entity::<Order>()
+//!     .segments(4)
+//!     .max_writes_per_second(100)
+//!     .execute()
+//!     .await?;
+//!
+//! println!("updated {} of {} items", report.updated, report.scanned);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each write is conditioned on the key attributes still holding the values
+//! that were scanned, so an item concurrently rewritten by the application is
+//! left untouched and counted as a conflict. Key attributes for secondary
+//! indexes that the entity no longer populates are removed. The primary key
+//! of an item cannot be changed in place; items whose recomputed primary key
+//! differs from the stored one are skipped and reported.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    error::ItemDeserializationError,
+    expr, keys,
+    model::{Scan, ScanSegment, Update},
+    AttributeValue, Entity, EntityTypeNameRef, Error, Item, Table, WritableTable,
+};
+
+type RecomputeKeys = fn(Item) -> Result<Item, Error>;
+
+/// A backfill of key attributes across all items in a table
+#[must_use]
+pub struct Backfill<'a, T> {
+    table: &'a T,
+    entities: HashMap<&'static EntityTypeNameRef, RecomputeKeys>,
+    segments: u32,
+    page_size: Option<u32>,
+    max_writes_per_second: Option<u32>,
+    dry_run: bool,
+}
+
+impl<'a, T> fmt::Debug for Backfill<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Backfill")
+            .field("entities", &self.entities.keys().collect::<Vec<_>>())
+            .field("segments", &self.segments)
+            .field("page_size", &self.page_size)
+            .field("max_writes_per_second", &self.max_writes_per_second)
+            .field("dry_run", &self.dry_run)
+            .finish()
+    }
+}
+
+impl<'a, T> Backfill<'a, T>
+where
+    T: WritableTable,
+    T::PrimaryKey: keys::Key,
+{
+    /// Prepares a backfill against the given table
+    ///
+    /// By default, the table is scanned as a single segment and writes are
+    /// not rate limited.
+    pub fn new(table: &'a T) -> Self {
+        Self {
+            table,
+            entities: HashMap::new(),
+            segments: 1,
+            page_size: None,
+            max_writes_per_second: None,
+            dry_run: false,
+        }
+    }
+
+    /// Registers an entity type whose keys should be recomputed
+    ///
+    /// Items with an entity type that has not been registered are left
+    /// untouched.
+    pub fn entity<E>(mut self) -> Self
+    where
+        E: Entity<Table = T> + serde::de::DeserializeOwned,
+    {
+        self.entities.insert(E::ENTITY_TYPE, recompute_keys::<E>);
+        self
+    }
+
+    /// Scans the table using the given number of parallel segments
+    pub fn segments(mut self, segments: u32) -> Self {
+        self.segments = segments.max(1);
+        self
+    }
+
+    /// Sets the maximum number of items to evaluate in each scan request
+    pub fn page_size(mut self, page_size: u32) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /// Limits the rate of writes across all segments
+    pub fn max_writes_per_second(mut self, writes: u32) -> Self {
+        self.max_writes_per_second = Some(writes).filter(|&w| w > 0);
+        self
+    }
+
+    /// Computes the report without writing any changes to the table
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Executes the backfill, returning a summary of the changes made
+    pub async fn execute(self) -> Result<BackfillReport, Error> {
+        let limiter = self
+            .max_writes_per_second
+            .map(|writes| RateLimiter::new(Duration::from_secs(1) / writes));
+
+        let total_segments = i32::try_from(self.segments).unwrap_or(i32::MAX);
+        let segments = (0..total_segments).map(|segment| {
+            self.segment(
+                ScanSegment {
+                    segment,
+                    total_segments,
+                },
+                limiter.as_ref(),
+            )
+        });
+
+        let reports = futures_util::future::try_join_all(segments).await?;
+        Ok(reports
+            .into_iter()
+            .fold(BackfillReport::default(), BackfillReport::merge))
+    }
+
+    async fn segment(
+        &self,
+        segment: ScanSegment,
+        limiter: Option<&RateLimiter>,
+    ) -> Result<BackfillReport, Error> {
+        let mut report = BackfillReport::default();
+        let mut items = Scan::<T::PrimaryKey>::new()
+            .segment(segment)
+            .set_limit(self.page_size)
+            .stream(self.table);
+
+        while let Some(item) = items.next_item().await {
+            let item = item?;
+            report.scanned += 1;
+
+            let Some(recompute) = crate::__private::get_table_entity_type::<T>(&item)
+                .ok()
+                .and_then(|entity_type| self.entities.get(entity_type))
+            else {
+                report.unregistered += 1;
+                continue;
+            };
+
+            let computed = recompute(item.clone())?;
+            let Some(changes) = KeyChanges::<T>::new(&item, &computed) else {
+                report.primary_key_mismatches += 1;
+                tracing::warn!(
+                    segment = segment.segment,
+                    "recomputed primary key differs from the stored primary key; skipping item"
+                );
+                continue;
+            };
+
+            if changes.is_empty() {
+                report.unchanged += 1;
+                continue;
+            }
+
+            if self.dry_run {
+                report.updated += 1;
+                continue;
+            }
+
+            if let Some(limiter) = limiter {
+                limiter.acquire().await;
+            }
+
+            match changes.into_update().execute(self.table).await {
+                Ok(_) => report.updated += 1,
+                Err(error) => {
+                    let error = Error::from(error);
+                    if !error.is_conditional_check_failed_exception() {
+                        return Err(error);
+                    }
+                    report.conflicts += 1;
+                }
+            }
+        }
+
+        tracing::debug!(
+            segment = segment.segment,
+            total_segments = segment.total_segments,
+            scanned = report.scanned,
+            updated = report.updated,
+            "backfill segment complete"
+        );
+
+        Ok(report)
+    }
+}
+
+/// A summary of the changes made by a [`Backfill`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BackfillReport {
+    /// The number of items scanned
+    pub scanned: u64,
+
+    /// The number of items whose key attributes were rewritten
+    ///
+    /// During a dry run, this is the number of items that would have been rewritten.
+    pub updated: u64,
+
+    /// The number of items whose key attributes were already up to date
+    pub unchanged: u64,
+
+    /// The number of items with an entity type that was not registered
+    pub unregistered: u64,
+
+    /// The number of items that were modified concurrently and left untouched
+    pub conflicts: u64,
+
+    /// The number of items skipped because their primary key would change
+    pub primary_key_mismatches: u64,
+}
+
+impl BackfillReport {
+    fn merge(self, other: Self) -> Self {
+        Self {
+            scanned: self.scanned + other.scanned,
+            updated: self.updated + other.updated,
+            unchanged: self.unchanged + other.unchanged,
+            unregistered: self.unregistered + other.unregistered,
+            conflicts: self.conflicts + other.conflicts,
+            primary_key_mismatches: self.primary_key_mismatches + other.primary_key_mismatches,
+        }
+    }
+}
+
+pub(crate) fn recompute_keys<E>(item: Item) -> Result<Item, Error>
+where
+    E: Entity + serde::de::DeserializeOwned,
+{
+    let entity: E = crate::codec::from_item(item)
+        .map_err(|error| ItemDeserializationError::new(E::ENTITY_TYPE, error))?;
+    Ok(crate::computed_keys(&entity))
+}
+
+/// The differences between the stored and recomputed key attributes of an item
+struct KeyChanges<T> {
+    key: Item,
+    set: Vec<(&'static str, Option<AttributeValue>, AttributeValue)>,
+    remove: Vec<(&'static str, AttributeValue)>,
+    table: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T: Table> KeyChanges<T> {
+    /// Computes the changes, or `None` if the primary key would change
+    fn new(item: &Item, computed: &Item) -> Option<Self> {
+        use keys::{IndexKeys, PrimaryKey};
+
+        let primary = T::PrimaryKey::PRIMARY_KEY_DEFINITION;
+        let mut key = Item::new();
+        for attr in std::iter::once(primary.hash_key).chain(primary.range_key) {
+            let stored = item.get(attr)?;
+            if computed.get(attr) != Some(stored) {
+                return None;
+            }
+            key.insert(attr.to_string(), stored.clone());
+        }
+
+        let mut set: Vec<(&'static str, Option<AttributeValue>, AttributeValue)> = Vec::new();
+        let mut remove = Vec::new();
+        let index_attributes = T::IndexKeys::KEY_DEFINITIONS
+            .iter()
+            .flat_map(|index| std::iter::once(index.hash_key()).chain(index.range_key()));
+        for attr in index_attributes {
+            if set.iter().any(|(a, ..)| *a == attr) || remove.iter().any(|(a, _)| *a == attr) {
+                continue;
+            }
+
+            match (item.get(attr), computed.get(attr)) {
+                (stored, Some(computed)) if stored != Some(computed) => {
+                    set.push((attr, stored.cloned(), computed.clone()));
+                }
+                (Some(stored), None) => remove.push((attr, stored.clone())),
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            key,
+            set,
+            remove,
+            table: std::marker::PhantomData,
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.set.is_empty() && self.remove.is_empty()
+    }
+
+    fn expressions(&self) -> (String, String) {
+        let mut assignments = Vec::new();
+        let mut removals = Vec::new();
+        let mut conditions = Vec::new();
+
+        for (i, (_, stored, _)) in self.set.iter().enumerate() {
+            assignments.push(format!("#set{i} = :set{i}"));
+            conditions.push(match stored {
+                Some(_) => format!("#set{i} = :old{i}"),
+                None => format!("attribute_not_exists(#set{i})"),
+            });
+        }
+
+        for i in 0..self.remove.len() {
+            removals.push(format!("#rem{i}"));
+            conditions.push(format!("#rem{i} = :rem{i}"));
+        }
+
+        let mut update = String::new();
+        if !assignments.is_empty() {
+            update.push_str("SET ");
+            update.push_str(&assignments.join(", "));
+        }
+        if !removals.is_empty() {
+            if !update.is_empty() {
+                update.push(' ');
+            }
+            update.push_str("REMOVE ");
+            update.push_str(&removals.join(", "));
+        }
+
+        (update, conditions.join(" AND "))
+    }
+
+    fn into_update(self) -> crate::model::ConditionalUpdate {
+        let (update, condition) = self.expressions();
+        let mut update = expr::Update::new(update);
+        let mut condition = expr::Condition::new(condition);
+
+        for (i, (attr, stored, computed)) in self.set.into_iter().enumerate() {
+            update = update
+                .name(&format!("#set{i}"), attr)
+                .raw_value(&format!(":set{i}"), computed);
+            condition = condition.name(&format!("#set{i}"), attr);
+            if let Some(stored) = stored {
+                condition = condition.raw_value(&format!(":old{i}"), stored);
+            }
+        }
+
+        for (i, (attr, stored)) in self.remove.into_iter().enumerate() {
+            update = update.name(&format!("#rem{i}"), attr);
+            condition = condition
+                .name(&format!("#rem{i}"), attr)
+                .raw_value(&format!(":rem{i}"), stored);
+        }
+
+        Update::new(self.key)
+            .expression(update)
+            .condition(condition)
+    }
+}
+
+/// Spaces out writes shared between concurrent segments
+struct RateLimiter {
+    interval: Duration,
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    async fn acquire(&self) {
+        let slot = {
+            let mut next = self.next.lock().unwrap_or_else(PoisonError::into_inner);
+            let slot = (*next).max(Instant::now());
+            *next = slot + self.interval;
+            slot
+        };
+
+        tokio::time::sleep_until(slot.into()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = (keys::Gsi1, keys::Gsi2);
+
+        fn table_name(&self) -> &str {
+            "TestTable"
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    struct ListedTable;
+
+    impl Table for ListedTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = (keys::Gsi1, keys::EntityTypeIndex);
+
+        fn table_name(&self) -> &str {
+            "ListedTable"
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Listed {
+        id: String,
+    }
+
+    impl crate::EntityDef for Listed {
+        const ENTITY_TYPE: &'static crate::EntityTypeNameRef =
+            crate::EntityTypeNameRef::from_static("listed");
+    }
+
+    impl Entity for Listed {
+        type KeyInput<'a> = &'a str;
+        type Table = ListedTable;
+        type IndexKeys = keys::Gsi1;
+
+        fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+            keys::Primary {
+                hash: format!("LISTED#{id}"),
+                range: format!("LISTED#{id}"),
+            }
+        }
+
+        fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+            keys::FullKey {
+                primary: Self::primary_key(&self.id),
+                indexes: keys::Gsi1 {
+                    hash: "LISTED".to_string(),
+                    range: format!("LISTED#{}", self.id),
+                },
+            }
+        }
+
+        fn entity_type_index_key(&self) -> Option<String> {
+            Some(self.id.clone())
+        }
+    }
+
+    fn item(attrs: &[(&str, &str)]) -> Item {
+        attrs
+            .iter()
+            .map(|(k, v)| (k.to_string(), AttributeValue::S(v.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn detects_new_and_stale_index_keys() {
+        let stored = item(&[("PK", "A"), ("SK", "B"), ("GSI1PK", "OLD"), ("GSI1SK", "C")]);
+        let computed = item(&[("PK", "A"), ("SK", "B"), ("GSI2PK", "NEW"), ("GSI2SK", "D")]);
+
+        let changes = KeyChanges::<TestTable>::new(&stored, &computed).unwrap();
+        let (update, condition) = changes.expressions();
+
+        assert_eq!(
+            update,
+            "SET #set0 = :set0, #set1 = :set1 REMOVE #rem0, #rem1"
+        );
+        assert_eq!(
+            condition,
+            "attribute_not_exists(#set0) AND attribute_not_exists(#set1) AND #rem0 = :rem0 AND #rem1 = :rem1"
+        );
+        assert_eq!(changes.key, item(&[("PK", "A"), ("SK", "B")]));
+    }
+
+    #[test]
+    fn unchanged_keys_produce_no_changes() {
+        let stored = item(&[("PK", "A"), ("SK", "B"), ("GSI1PK", "C"), ("GSI1SK", "D")]);
+        let changes = KeyChanges::<TestTable>::new(&stored, &stored.clone()).unwrap();
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn entity_type_index_keys_are_recomputed() {
+        use crate::EntityExt;
+
+        let stored = Listed {
+            id: "1234".to_string(),
+        }
+        .into_item();
+        let computed = recompute_keys::<Listed>(stored.clone()).unwrap();
+        assert_eq!(computed.get("ETPK"), stored.get("ETPK"));
+        assert_eq!(computed.get("ETSK"), stored.get("ETSK"));
+
+        let changes = KeyChanges::<ListedTable>::new(&stored, &computed).unwrap();
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn primary_key_changes_are_rejected() {
+        let stored = item(&[("PK", "A"), ("SK", "B")]);
+        let computed = item(&[("PK", "A"), ("SK", "C")]);
+        assert!(KeyChanges::<TestTable>::new(&stored, &computed).is_none());
+    }
+}
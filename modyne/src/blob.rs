@@ -0,0 +1,364 @@
+//! Overflow storage for attributes that are too large to store inline
+//!
+//! DynamoDB limits items to 400 KB. Entities that occasionally carry large
+//! payloads can wrap those attributes in a [`LargeAttribute`], which keeps
+//! small values inline on the item, but moves values exceeding a threshold
+//! into an external [`BlobStore`], leaving only a [`BlobPointer`] behind.
+//!
+//! # Consistency
+//!
+//! Writes to a blob store do not participate in DynamoDB transactions, so
+//! some care is needed to avoid dangling pointers:
+//!
+//! * Store the blob _before_ writing the item that points to it, and use a
+//!   fresh blob key for every write rather than overwriting an existing
+//!   blob. A failed item write then leaves at most an orphaned blob, never
+//!   an item pointing at missing or partially-written data.
+//! * Delete a replaced or removed blob only _after_ the item write that
+//!   stops referencing it has succeeded.
+//! * Orphaned blobs should be expected. Configure a lifecycle policy or
+//!   periodic sweep on the blob store to clean them up.
+//!
+//! Pointers record the entity tag reported by the store, which allows
+//! implementations to detect that a blob was modified out from under an
+//! item.
+//!
+//! With the `s3` feature enabled, [`S3BlobStore`] provides an
+//! implementation backed by Amazon S3.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{size, Error};
+
+/// A pointer to a value held in a [`BlobStore`]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobPointer {
+    /// The bucket or container holding the blob
+    pub bucket: String,
+
+    /// The key of the blob within the bucket
+    pub key: String,
+
+    /// The entity tag of the blob when it was stored, if reported by the store
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+
+    /// The size of the blob, in bytes
+    pub size: u64,
+}
+
+/// An error reported by a [`BlobStore`]
+#[derive(Debug, thiserror::Error)]
+#[error("blob store error")]
+pub struct BlobError {
+    #[source]
+    source: Box<dyn std::error::Error + Send + Sync>,
+}
+
+impl BlobError {
+    /// Wraps an underlying error reported by a blob store
+    #[inline]
+    pub fn new(source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Self {
+            source: source.into(),
+        }
+    }
+}
+
+/// A store for values that are too large to be kept inline on an item
+#[async_trait::async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Stores a blob under the given key, returning a pointer to the stored blob
+    async fn put(&self, key: &str, body: Vec<u8>) -> Result<BlobPointer, BlobError>;
+
+    /// Retrieves the blob referenced by the pointer
+    async fn get(&self, pointer: &BlobPointer) -> Result<Vec<u8>, BlobError>;
+
+    /// Deletes the blob referenced by the pointer
+    async fn delete(&self, pointer: &BlobPointer) -> Result<(), BlobError>;
+}
+
+/// An attribute value that may be stored inline or in a [`BlobStore`]
+///
+/// Values are serialized as a map with a single `inline` or `blob` attribute.
+/// Values held in a blob store are encoded as JSON.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LargeAttribute<T> {
+    /// The value is stored inline on the item
+    Inline(T),
+
+    /// The value is stored in a blob store
+    Blob(BlobPointer),
+}
+
+impl<T> LargeAttribute<T> {
+    /// Returns the inline value, if it has been loaded
+    #[inline]
+    pub fn inline(&self) -> Option<&T> {
+        match self {
+            Self::Inline(value) => Some(value),
+            Self::Blob(_) => None,
+        }
+    }
+
+    /// Returns the pointer to the blob, if the value is held in a blob store
+    #[inline]
+    pub fn pointer(&self) -> Option<&BlobPointer> {
+        match self {
+            Self::Inline(_) => None,
+            Self::Blob(pointer) => Some(pointer),
+        }
+    }
+}
+
+impl<T: Serialize> LargeAttribute<T> {
+    /// Wraps a value, moving it to the blob store under the given key if its
+    /// serialized size exceeds `threshold` bytes
+    pub async fn store<S>(value: T, store: &S, key: &str, threshold: usize) -> Result<Self, Error>
+    where
+        S: BlobStore + ?Sized,
+    {
+        let attr = crate::codec::to_attribute_value(&value).map_err(BlobError::new)?;
+        if size::attribute_value_size(&attr) <= threshold {
+            return Ok(Self::Inline(value));
+        }
+
+        let body = serde_json::to_vec(&value).map_err(BlobError::new)?;
+        let pointer = store.put(key, body).await?;
+        Ok(Self::Blob(pointer))
+    }
+}
+
+impl<T: DeserializeOwned> LargeAttribute<T> {
+    /// Returns the value, fetching it from the blob store if necessary
+    pub async fn load<S>(self, store: &S) -> Result<T, Error>
+    where
+        S: BlobStore + ?Sized,
+    {
+        match self {
+            Self::Inline(value) => Ok(value),
+            Self::Blob(pointer) => fetch(&pointer, store).await,
+        }
+    }
+
+    /// Fetches the value from the blob store if necessary, replacing the
+    /// pointer with the inline value
+    ///
+    /// Note that serializing the attribute after resolving it will store the
+    /// value inline.
+    pub async fn resolve<S>(&mut self, store: &S) -> Result<&T, Error>
+    where
+        S: BlobStore + ?Sized,
+    {
+        if let Self::Blob(pointer) = self {
+            let value = fetch(pointer, store).await?;
+            *self = Self::Inline(value);
+        }
+
+        match self {
+            Self::Inline(value) => Ok(value),
+            Self::Blob(_) => unreachable!("blob attributes are resolved above"),
+        }
+    }
+}
+
+async fn fetch<T, S>(pointer: &BlobPointer, store: &S) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+    S: BlobStore + ?Sized,
+{
+    let body = store.get(pointer).await?;
+    let value = serde_json::from_slice(&body).map_err(BlobError::new)?;
+    Ok(value)
+}
+
+#[cfg(feature = "s3")]
+pub use self::s3::S3BlobStore;
+
+#[cfg(feature = "s3")]
+mod s3 {
+    use aws_sdk_s3::primitives::ByteStream;
+
+    use super::{BlobError, BlobPointer, BlobStore};
+
+    /// A [`BlobStore`] backed by an Amazon S3 bucket
+    #[derive(Clone, Debug)]
+    pub struct S3BlobStore {
+        client: aws_sdk_s3::Client,
+        bucket: String,
+        prefix: String,
+    }
+
+    impl S3BlobStore {
+        /// Creates a blob store that writes to the given bucket
+        pub fn new(client: aws_sdk_s3::Client, bucket: impl Into<String>) -> Self {
+            Self {
+                client,
+                bucket: bucket.into(),
+                prefix: String::new(),
+            }
+        }
+
+        /// Prepends a prefix to the key of every blob written by this store
+        pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+            self.prefix = prefix.into();
+            self
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl BlobStore for S3BlobStore {
+        async fn put(&self, key: &str, body: Vec<u8>) -> Result<BlobPointer, BlobError> {
+            let key = format!("{}{}", self.prefix, key);
+            let size = body.len() as u64;
+            let output = self
+                .client
+                .put_object()
+                .bucket(&self.bucket)
+                .key(&key)
+                .body(ByteStream::from(body))
+                .send()
+                .await
+                .map_err(BlobError::new)?;
+
+            Ok(BlobPointer {
+                bucket: self.bucket.clone(),
+                key,
+                etag: output.e_tag,
+                size,
+            })
+        }
+
+        async fn get(&self, pointer: &BlobPointer) -> Result<Vec<u8>, BlobError> {
+            let output = self
+                .client
+                .get_object()
+                .bucket(&pointer.bucket)
+                .key(&pointer.key)
+                .set_if_match(pointer.etag.clone())
+                .send()
+                .await
+                .map_err(BlobError::new)?;
+
+            let body = output.body.collect().await.map_err(BlobError::new)?;
+            Ok(body.into_bytes().to_vec())
+        }
+
+        async fn delete(&self, pointer: &BlobPointer) -> Result<(), BlobError> {
+            self.client
+                .delete_object()
+                .bucket(&pointer.bucket)
+                .key(&pointer.key)
+                .send()
+                .await
+                .map_err(BlobError::new)?;
+
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Mutex};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct MemoryStore {
+        blobs: Mutex<HashMap<String, Vec<u8>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl BlobStore for MemoryStore {
+        async fn put(&self, key: &str, body: Vec<u8>) -> Result<BlobPointer, BlobError> {
+            let size = body.len() as u64;
+            self.blobs.lock().unwrap().insert(key.to_string(), body);
+            Ok(BlobPointer {
+                bucket: "memory".to_string(),
+                key: key.to_string(),
+                etag: None,
+                size,
+            })
+        }
+
+        async fn get(&self, pointer: &BlobPointer) -> Result<Vec<u8>, BlobError> {
+            self.blobs
+                .lock()
+                .unwrap()
+                .get(&pointer.key)
+                .cloned()
+                .ok_or_else(|| BlobError::new(format!("no blob at {}", pointer.key)))
+        }
+
+        async fn delete(&self, pointer: &BlobPointer) -> Result<(), BlobError> {
+            self.blobs.lock().unwrap().remove(&pointer.key);
+            Ok(())
+        }
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn small_values_are_stored_inline() {
+        let store = MemoryStore::default();
+        let attr = runtime()
+            .block_on(LargeAttribute::store(
+                "small".to_string(),
+                &store,
+                "key",
+                64,
+            ))
+            .unwrap();
+
+        assert_eq!(attr.inline().map(String::as_str), Some("small"));
+        assert!(store.blobs.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn large_values_round_trip_through_the_store() {
+        let store = MemoryStore::default();
+        let value = "x".repeat(100);
+        let runtime = runtime();
+        let attr = runtime
+            .block_on(LargeAttribute::store(value.clone(), &store, "key", 64))
+            .unwrap();
+
+        let pointer = attr.pointer().unwrap();
+        assert_eq!(pointer.key, "key");
+        assert_eq!(pointer.size, value.len() as u64 + 2);
+
+        let item = crate::codec::to_attribute_value(&attr).unwrap();
+        let attr: LargeAttribute<String> = crate::codec::from_attribute_value(item).unwrap();
+        assert_eq!(runtime.block_on(attr.clone().load(&store)).unwrap(), value);
+
+        let mut attr = attr;
+        assert_eq!(runtime.block_on(attr.resolve(&store)).unwrap(), &value);
+        assert_eq!(attr.inline(), Some(&value));
+    }
+
+    #[test]
+    fn missing_blobs_fail_to_load() {
+        let store = MemoryStore::default();
+        let runtime = runtime();
+        let attr = runtime
+            .block_on(LargeAttribute::store("x".repeat(100), &store, "key", 64))
+            .unwrap();
+        runtime
+            .block_on(store.delete(attr.pointer().unwrap()))
+            .unwrap();
+
+        let error = runtime.block_on(attr.clone().load(&store)).unwrap_err();
+        let source = std::error::Error::source(&error).unwrap();
+        assert!(source.is::<BlobError>());
+
+        let mut attr = attr;
+        assert!(runtime.block_on(attr.resolve(&store)).is_err());
+        assert!(attr.pointer().is_some());
+    }
+}
@@ -0,0 +1,706 @@
+//! Applying an update to every item matching a query
+//!
+//! Data repairs often take the form of "set this attribute on every item in
+//! that partition". [`Query::update_matching()`] pages through the items
+//! matching a query and applies the same update expression to each of them,
+//! either with individual `UpdateItem` requests or with one transaction per
+//! chunk of items.
+//!
+//! ```no_run
+//! # use modyne::{keys, Table, WritableTable};
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! # impl WritableTable for App {}
+//! use modyne::{bulk_update::UpdateMatchingOptions, expr, model::Query};
+//!
+//! # async fn example(app: App) -> Result<(), modyne::Error> {
+//! let update = expr::Update::new("SET #status = :status")
+//!     .name("#status", "status")
+//!     .value(":status", "pending");
+//!
+//! let report = Query::new(expr::KeyCondition::<keys::Gsi1>::in_partition("STATUS#stuck"))
+//!     .update_matching(
+//!         &app,
+//!         update,
+//!         UpdateMatchingOptions::new()
+//!             .concurrency(16)
+//!             .on_progress(|report| println!("updated {} items", report.updated)),
+//!     )
+//!     .await?;
+//!
+//! println!("updated {} of {} items", report.updated, report.matched);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Each update is conditioned on the item still existing, so that an item
+//! deleted after it was read is not recreated. Such items are counted as
+//! [missing][UpdateMatchingReport::missing]. When updating in transactions,
+//! a transaction canceled only because some of its items were deleted is
+//! retried without those items.
+//!
+//! If an update fails, the [error][UpdateMatchingError] holds the report of
+//! the items updated so far, along with the key from which the query can be
+//! resumed.
+
+use std::{fmt, sync::Arc};
+
+use aws_sdk_dynamodb::{error::SdkError, operation::transact_write_items::TransactWriteItemsError};
+
+use crate::{
+    expr, keys,
+    model::{primary_key_of, Query, TransactWrite, Update},
+    Error, Item, Table, WritableTable,
+};
+
+/// The maximum number of operations in a single transaction
+const MAX_TRANSACTION_SIZE: usize = 100;
+
+type Progress = Arc<dyn Fn(&UpdateMatchingReport) + Send + Sync>;
+
+/// Options controlling how [`Query::update_matching()`] applies updates
+///
+/// By default, up to 8 items are updated concurrently with individual
+/// `UpdateItem` requests.
+#[derive(Clone)]
+#[must_use]
+pub struct UpdateMatchingOptions {
+    concurrency: usize,
+    transaction_size: Option<usize>,
+    dry_run: bool,
+    progress: Option<Progress>,
+}
+
+impl fmt::Debug for UpdateMatchingOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("UpdateMatchingOptions")
+            .field("concurrency", &self.concurrency)
+            .field("transaction_size", &self.transaction_size)
+            .field("dry_run", &self.dry_run)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+impl Default for UpdateMatchingOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 8,
+            transaction_size: None,
+            dry_run: false,
+            progress: None,
+        }
+    }
+}
+
+impl UpdateMatchingOptions {
+    /// Prepares the default options
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of requests in flight at once
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Updates the items in transactions of up to the given number of items
+    ///
+    /// The size is limited to the 100 operations allowed in a transaction.
+    /// The items in a transaction are updated together or not at all, which
+    /// costs twice the write capacity of individual updates.
+    pub fn transactions(mut self, size: usize) -> Self {
+        self.transaction_size = Some(size.clamp(1, MAX_TRANSACTION_SIZE));
+        self
+    }
+
+    /// Reads the matching items without updating them
+    ///
+    /// The report counts the items that would have been updated as
+    /// [`matched`][UpdateMatchingReport::matched], while no items are counted
+    /// as [`updated`][UpdateMatchingReport::updated].
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+        self
+    }
+
+    /// Calls the given function with the progress so far after each page
+    /// of matching items has been updated
+    pub fn on_progress(
+        mut self,
+        progress: impl Fn(&UpdateMatchingReport) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    pub(crate) async fn execute<K, T>(
+        self,
+        mut query: Query<K>,
+        table: &T,
+        update: expr::Update,
+    ) -> Result<UpdateMatchingReport, UpdateMatchingError>
+    where
+        K: keys::Key,
+        T: WritableTable,
+    {
+        let mut report = UpdateMatchingReport::default();
+        let mut last_evaluated_key = None;
+        let interrupted = |source: Error, report, last_evaluated_key| UpdateMatchingError {
+            source,
+            report,
+            last_evaluated_key,
+        };
+        loop {
+            let output = match query.execute_page(table).await {
+                Ok(output) => output,
+                Err(error) => return Err(interrupted(error.into(), report, last_evaluated_key)),
+            };
+            report.pages += 1;
+
+            let keys: Vec<Item> = output
+                .items
+                .unwrap_or_default()
+                .iter()
+                .map(primary_key_of::<T>)
+                .collect();
+            report.matched += keys.len() as u64;
+
+            if !self.dry_run {
+                let result = match self.transaction_size {
+                    Some(size) => {
+                        self.update_in_transactions(table, &update, &keys, size, &mut report)
+                            .await
+                    }
+                    None => self.update_each(table, &update, &keys, &mut report).await,
+                };
+                if let Err(error) = result {
+                    return Err(interrupted(error, report, last_evaluated_key));
+                }
+            }
+
+            if let Some(progress) = &self.progress {
+                progress(&report);
+            }
+
+            match output.last_evaluated_key {
+                Some(key) => {
+                    last_evaluated_key = Some(key.clone());
+                    query = query.exclusive_start_key(key);
+                }
+                None => break,
+            }
+        }
+
+        tracing::debug!(
+            matched = report.matched,
+            updated = report.updated,
+            missing = report.missing,
+            dry_run = self.dry_run,
+            "update of matching items complete"
+        );
+
+        Ok(report)
+    }
+
+    async fn update_each<T: WritableTable>(
+        &self,
+        table: &T,
+        update: &expr::Update,
+        keys: &[Item],
+        report: &mut UpdateMatchingReport,
+    ) -> Result<(), Error> {
+        for wave in keys.chunks(self.concurrency) {
+            let updates = wave
+                .iter()
+                .map(|key| conditional_update::<T>(key, update).execute(table));
+
+            for result in futures_util::future::join_all(updates).await {
+                match result {
+                    Ok(_) => report.updated += 1,
+                    Err(error) => {
+                        let error = Error::from(error);
+                        if !error.is_conditional_check_failed_exception() {
+                            return Err(error);
+                        }
+                        report.missing += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn update_in_transactions<T: WritableTable>(
+        &self,
+        table: &T,
+        update: &expr::Update,
+        keys: &[Item],
+        size: usize,
+        report: &mut UpdateMatchingReport,
+    ) -> Result<(), Error> {
+        for wave in keys.chunks(size * self.concurrency) {
+            let transactions = wave
+                .chunks(size)
+                .map(|chunk| update_chunk(table, update, chunk));
+
+            let mut first_error = None;
+            for (updated, missing, result) in futures_util::future::join_all(transactions).await {
+                report.updated += updated;
+                report.missing += missing;
+                if let Err(error) = result {
+                    first_error.get_or_insert(error);
+                }
+            }
+
+            if let Some(error) = first_error {
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Updates a chunk of items in a transaction, returning the number of items
+/// updated and missing
+///
+/// If the transaction is canceled only because some of the items no longer
+/// exist, those items are counted as missing and the transaction is retried
+/// with the others.
+async fn update_chunk<T: WritableTable>(
+    table: &T,
+    update: &expr::Update,
+    chunk: &[Item],
+) -> (u64, u64, Result<(), Error>) {
+    let mut keys = chunk.to_vec();
+    let mut missing = 0;
+    while !keys.is_empty() {
+        let result = keys
+            .iter()
+            .fold(TransactWrite::new(), |tx, key| {
+                tx.operation(conditional_update::<T>(key, update))
+            })
+            .execute(table)
+            .await;
+
+        let error = match result {
+            Ok(_) => return (keys.len() as u64, missing, Ok(())),
+            Err(error) => error,
+        };
+
+        let Some(failed) = failed_conditions(&error).filter(|f| f.len() == keys.len()) else {
+            return (0, missing, Err(error.into()));
+        };
+
+        let before = keys.len();
+        let mut failed = failed.into_iter();
+        keys.retain(|_| !failed.next().unwrap_or_default());
+        missing += (before - keys.len()) as u64;
+    }
+
+    (0, missing, Ok(()))
+}
+
+/// Which operations of a canceled transaction failed their condition, if
+/// the transaction was canceled for no other reason
+fn failed_conditions(error: &SdkError<TransactWriteItemsError>) -> Option<Vec<bool>> {
+    let Some(TransactWriteItemsError::TransactionCanceledException(e)) = error.as_service_error()
+    else {
+        return None;
+    };
+
+    let failed: Vec<bool> = e
+        .cancellation_reasons()
+        .iter()
+        .map(|reason| match reason.code() {
+            Some("ConditionalCheckFailed") => Some(true),
+            None | Some("None") => Some(false),
+            Some(_) => None,
+        })
+        .collect::<Option<_>>()?;
+
+    failed.contains(&true).then_some(failed)
+}
+
+/// An update of the item with the given key, conditioned on the item existing
+fn conditional_update<T: Table>(
+    key: &Item,
+    update: &expr::Update,
+) -> crate::model::ConditionalUpdate {
+    let hash_key = <T::PrimaryKey as keys::PrimaryKey>::PRIMARY_KEY_DEFINITION.hash_key;
+    Update::new(key.clone())
+        .expression(update.clone())
+        .condition(expr::Condition::new("attribute_exists(#PK)").name("#PK", hash_key))
+}
+
+/// A summary of the items updated by [`Query::update_matching()`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UpdateMatchingReport {
+    /// The number of pages of matching items read
+    pub pages: u64,
+
+    /// The number of items matching the query
+    pub matched: u64,
+
+    /// The number of items updated, which is always zero during a dry run
+    pub updated: u64,
+
+    /// The number of items that were deleted after being read and were not updated
+    pub missing: u64,
+}
+
+/// An error that interrupted [`Query::update_matching()`]
+///
+/// Along with the error, this holds the report of the items updated
+/// before the error, and the key from which the query can be resumed.
+#[derive(Debug, thiserror::Error)]
+#[error("update of matching items interrupted after {} items were updated", report.updated)]
+pub struct UpdateMatchingError {
+    #[source]
+    source: Error,
+    report: UpdateMatchingReport,
+    last_evaluated_key: Option<Item>,
+}
+
+impl UpdateMatchingError {
+    /// The progress made before the error
+    ///
+    /// The items of the page being updated when the error occurred are
+    /// included in the counts, whether or not they were updated.
+    #[inline]
+    pub fn report(&self) -> &UpdateMatchingReport {
+        &self.report
+    }
+
+    /// The last evaluated key of the last page whose items were all
+    /// updated, or `None` if the error occurred on the first page
+    ///
+    /// Passing this key to [`Query::exclusive_start_key()`] resumes the
+    /// update from the page that was interrupted. Items of that page which
+    /// were already updated are updated again.
+    #[inline]
+    pub fn last_evaluated_key(&self) -> Option<&Item> {
+        self.last_evaluated_key.as_ref()
+    }
+
+    /// The error that interrupted the update
+    #[inline]
+    pub fn into_source(self) -> Error {
+        self.source
+    }
+}
+
+impl From<UpdateMatchingError> for Error {
+    #[inline]
+    fn from(error: UpdateMatchingError) -> Self {
+        error.source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use aws_sdk_dynamodb::{
+        config::http::HttpResponse,
+        error::{ErrorMetadata, SdkError},
+        operation::{
+            query::QueryOutput,
+            transact_write_items::TransactWriteItemsOutput,
+            update_item::{UpdateItemError, UpdateItemOutput},
+        },
+        types::{
+            error::{ConditionalCheckFailedException, TransactionCanceledException},
+            CancellationReason,
+        },
+    };
+    use aws_smithy_types::body::SdkBody;
+
+    use super::*;
+    use crate::{
+        mock::{ops, MockTable, Operation},
+        AttributeValue,
+    };
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            unimplemented!()
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    impl WritableTable for TestTable {}
+
+    fn item(pk: &str) -> Item {
+        [
+            ("PK".to_string(), AttributeValue::S(pk.into())),
+            ("SK".to_string(), AttributeValue::S("ORDER".into())),
+            (
+                "GSI1PK".to_string(),
+                AttributeValue::S("STATUS#stuck".into()),
+            ),
+        ]
+        .into()
+    }
+
+    fn page(items: &[&str], last: Option<&str>) -> QueryOutput {
+        QueryOutput::builder()
+            .set_items(Some(items.iter().map(|pk| item(pk)).collect()))
+            .set_last_evaluated_key(last.map(item))
+            .build()
+    }
+
+    fn expect_pages(table: &MockTable<TestTable>) {
+        table
+            .expect::<ops::Query>(|e| e.times(1).returning(page(&["A", "B", "C"], Some("C"))))
+            .expect::<ops::Query>(|e| e.times(1).returning(page(&["D"], None)));
+    }
+
+    fn query() -> Query<keys::Gsi1> {
+        Query::new(expr::KeyCondition::in_partition("STATUS#stuck"))
+    }
+
+    fn update() -> expr::Update {
+        expr::Update::new("SET #status = :status")
+            .name("#status", "status")
+            .value(":status", "pending")
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn updates_each_matching_item_across_pages() {
+        let table = MockTable::<TestTable>::new("test");
+        expect_pages(&table);
+        table.expect::<ops::UpdateItem>(|e| e.times(4));
+
+        let progress = Arc::new(AtomicU64::new(0));
+        let seen = progress.clone();
+        let report = runtime()
+            .block_on(
+                query().update_matching(
+                    &table,
+                    update(),
+                    UpdateMatchingOptions::new()
+                        .concurrency(2)
+                        .on_progress(move |report| seen.store(report.updated, Ordering::SeqCst)),
+                ),
+            )
+            .unwrap();
+
+        assert_eq!(
+            report,
+            UpdateMatchingReport {
+                pages: 2,
+                matched: 4,
+                updated: 4,
+                missing: 0,
+            }
+        );
+        assert_eq!(progress.load(Ordering::SeqCst), 4);
+
+        let inputs = table.inputs::<ops::UpdateItem>();
+        assert_eq!(inputs[0].key, Some(primary_key_of::<TestTable>(&item("A"))));
+        assert_eq!(
+            inputs[0].condition_expression.as_deref(),
+            Some("attribute_exists(#cnd_PK)")
+        );
+        table.verify();
+    }
+
+    #[test]
+    fn items_deleted_after_being_read_are_counted_as_missing() {
+        let table = MockTable::<TestTable>::new("test");
+        table.expect::<ops::Query>(|e| e.times(1).returning(page(&["A", "B"], None)));
+        table
+            .expect::<ops::UpdateItem>(|e| {
+                e.times(1).responding(|_| {
+                    Err(SdkError::service_error(
+                        UpdateItemError::ConditionalCheckFailedException(
+                            ConditionalCheckFailedException::builder().build(),
+                        ),
+                        HttpResponse::new(400u16.try_into().unwrap(), SdkBody::empty()),
+                    ))
+                })
+            })
+            .expect::<ops::UpdateItem>(|e| {
+                e.times(1)
+                    .responding(|_| Ok(UpdateItemOutput::builder().build()))
+            });
+
+        let report = runtime()
+            .block_on(query().update_matching(&table, update(), UpdateMatchingOptions::new()))
+            .unwrap();
+
+        assert_eq!(report.updated, 1);
+        assert_eq!(report.missing, 1);
+        table.verify();
+    }
+
+    #[test]
+    fn items_can_be_updated_in_transactions() {
+        let table = MockTable::<TestTable>::new("test");
+        expect_pages(&table);
+        table.expect::<ops::TransactWriteItems>(|e| e.times(3));
+
+        let report = runtime()
+            .block_on(query().update_matching(
+                &table,
+                update(),
+                UpdateMatchingOptions::new().transactions(2),
+            ))
+            .unwrap();
+
+        assert_eq!(report.updated, 4);
+        let sizes: Vec<_> = table
+            .inputs::<ops::TransactWriteItems>()
+            .iter()
+            .map(|input| input.transact_items.as_ref().unwrap().len())
+            .collect();
+        assert_eq!(sizes, [2, 1, 1]);
+        assert_eq!(table.calls(Operation::UpdateItem), 0);
+        table.verify();
+    }
+
+    fn canceled(codes: &[&str]) -> SdkError<TransactWriteItemsError> {
+        let error = TransactionCanceledException::builder()
+            .set_cancellation_reasons(Some(
+                codes
+                    .iter()
+                    .map(|code| CancellationReason::builder().code(*code).build())
+                    .collect(),
+            ))
+            .meta(
+                ErrorMetadata::builder()
+                    .code("TransactionCanceledException")
+                    .build(),
+            )
+            .build();
+        SdkError::service_error(
+            TransactWriteItemsError::TransactionCanceledException(error),
+            HttpResponse::new(400u16.try_into().unwrap(), SdkBody::empty()),
+        )
+    }
+
+    #[test]
+    fn deleted_items_are_left_out_of_retried_transactions() {
+        let table = MockTable::<TestTable>::new("test");
+        table.expect::<ops::Query>(|e| e.times(1).returning(page(&["A", "B", "C"], None)));
+        table
+            .expect::<ops::TransactWriteItems>(|e| {
+                e.times(1)
+                    .responding(|_| Err(canceled(&["None", "ConditionalCheckFailed", "None"])))
+            })
+            .expect::<ops::TransactWriteItems>(|e| {
+                e.times(1)
+                    .responding(|_| Ok(TransactWriteItemsOutput::builder().build()))
+            });
+
+        let report = runtime()
+            .block_on(query().update_matching(
+                &table,
+                update(),
+                UpdateMatchingOptions::new().transactions(3),
+            ))
+            .unwrap();
+
+        assert_eq!(report.updated, 2);
+        assert_eq!(report.missing, 1);
+        let inputs = table.inputs::<ops::TransactWriteItems>();
+        let retried = inputs[1].transact_items.as_ref().unwrap();
+        let keys: Vec<_> = retried
+            .iter()
+            .map(|op| op.update.as_ref().unwrap().key.clone())
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                primary_key_of::<TestTable>(&item("A")),
+                primary_key_of::<TestTable>(&item("C")),
+            ]
+        );
+        table.verify();
+    }
+
+    #[test]
+    fn errors_hold_the_progress_and_the_key_to_resume_from() {
+        let table = MockTable::<TestTable>::new("test");
+        table
+            .expect::<ops::Query>(|e| e.times(1).returning(page(&["A", "B", "C"], Some("C"))))
+            .expect::<ops::Query>(|e| e.times(1).returning(page(&["D", "E"], None)));
+        table
+            .expect::<ops::TransactWriteItems>(|e| e.times(4))
+            .expect::<ops::TransactWriteItems>(|e| {
+                e.times(1)
+                    .responding(|_| Err(SdkError::timeout_error("timed out")))
+            });
+
+        let error = runtime()
+            .block_on(query().update_matching(
+                &table,
+                update(),
+                UpdateMatchingOptions::new().transactions(1).concurrency(2),
+            ))
+            .unwrap_err();
+
+        assert_eq!(
+            *error.report(),
+            UpdateMatchingReport {
+                pages: 2,
+                matched: 5,
+                updated: 4,
+                missing: 0,
+            }
+        );
+        assert_eq!(error.last_evaluated_key(), Some(&item("C")));
+        table.verify();
+    }
+
+    #[test]
+    fn dry_runs_only_read_the_matching_items() {
+        let table = MockTable::<TestTable>::new("test");
+        expect_pages(&table);
+
+        let report = runtime()
+            .block_on(query().update_matching(
+                &table,
+                update(),
+                UpdateMatchingOptions::new().dry_run(),
+            ))
+            .unwrap();
+
+        assert_eq!(
+            report,
+            UpdateMatchingReport {
+                pages: 2,
+                matched: 4,
+                updated: 0,
+                missing: 0,
+            }
+        );
+        assert_eq!(table.calls(Operation::UpdateItem), 0);
+        table.verify();
+    }
+}
@@ -0,0 +1,763 @@
+//! Caching of hydrated aggregates and point reads
+//!
+//! Read-heavy aggregates, such as front pages or category listings, can be
+//! cached with an [`AggregateCache`]. Entries are keyed by the table, its
+//! key scope, and the rendered query, including the partition, sort key
+//! bounds, projection, and filter, and expire after a fixed time-to-live.
+//!
+//! Entries are invalidated when items in the same partition are written
+//! through modyne. To enable this, forward the [`Table::after_write`] hook
+//! to the cache:
+//!
+//! ```no_run
+//! use modyne::{cache::AggregateCache, keys, Item, Table};
+//!
+//! struct App {
+//!     client: aws_sdk_dynamodb::Client,
+//!     cache: AggregateCache,
+//! }
+//!
+//! impl Table for App {
+//!     type PrimaryKey = keys::Primary;
+//!     type IndexKeys = keys::Gsi1;
+//!
+//!     fn table_name(&self) -> &str {
+//!         "MyTable"
+//!     }
+//!
+//!     fn client(&self) -> &aws_sdk_dynamodb::Client {
+//!         &self.client
+//!     }
+//!
+//!     fn after_write(&self, key: &Item) {
+//!         self.cache.invalidate(key);
+//!     }
+//! }
+//! ```
+//!
+//! Single puts report the index keys of both the new item and the item it
+//! replaced, so moving an item between index partitions invalidates both.
+//! Updates, deletes, and writes in transactions and batches only carry the
+//! primary key of the affected item, so writes of this kind conservatively
+//! invalidate all cached queries against secondary indexes. Writes made
+//! outside of this process are not observed, so the time-to-live bounds how
+//! stale a cached aggregate may become.
+//!
+//! ## Point reads
+//!
+//! Items read by [`Get`][crate::model::Get] operations can be cached with an
+//! [`ItemCache`], keyed by the table and the item's primary key. The cache is
+//! opted into by returning it from [`Table::item_cache`], after which gets
+//! are served from the cache and writes made through modyne invalidate the
+//! cached items with the same keys without any further setup.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use modyne::{cache::ItemCache, keys, Table};
+//!
+//! struct App {
+//!     client: aws_sdk_dynamodb::Client,
+//!     items: ItemCache,
+//! }
+//!
+//! impl Table for App {
+//!     type PrimaryKey = keys::Primary;
+//!     type IndexKeys = keys::Gsi1;
+//!
+//!     fn table_name(&self) -> &str {
+//!         "MyTable"
+//!     }
+//!
+//!     fn client(&self) -> &aws_sdk_dynamodb::Client {
+//!         &self.client
+//!     }
+//!
+//!     fn item_cache(&self) -> Option<&ItemCache> {
+//!         Some(&self.items)
+//!     }
+//! }
+//!
+//! let items = ItemCache::new(10_000, Duration::from_secs(30));
+//! ```
+//!
+//! Gets with a projection expression, or with strongly consistent reads,
+//! are not served from the cache. The entries are held in memory by
+//! default, but can be kept in any [`ItemCacheBackend`], such as a `moka`
+//! cache when the `moka` feature is enabled.
+
+use std::{
+    any::Any,
+    fmt,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    keys::{self, PrimaryKey},
+    lru::Lru,
+    model::Query,
+    Aggregate, AttributeValue, Error, Item, Table,
+};
+
+/// A cache of hydrated aggregates, keyed by the table and the rendered query
+pub struct AggregateCache {
+    ttl: Duration,
+    generation: AtomicU64,
+    entries: Mutex<Lru<String, CacheEntry>>,
+}
+
+struct CacheEntry {
+    partition_attribute: &'static str,
+    partition: AttributeValue,
+    expires_at: Instant,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+impl fmt::Debug for AggregateCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let entries = self.entries();
+        f.debug_struct("AggregateCache")
+            .field("ttl", &self.ttl)
+            .field("capacity", &entries.capacity())
+            .field("len", &entries.len())
+            .finish()
+    }
+}
+
+impl AggregateCache {
+    /// Creates a cache holding at most `capacity` aggregates, each for at most `ttl`
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            ttl,
+            generation: AtomicU64::new(0),
+            entries: Mutex::new(Lru::new(capacity)),
+        }
+    }
+
+    /// Returns the cached aggregate for the query against the table, or
+    /// loads and caches it
+    ///
+    /// Entries are kept apart by the table's name and
+    /// [key scope][Table::key_scope()], so the same query against
+    /// different tables or tenants is loaded separately. If an invalidation
+    /// occurs while the aggregate is being loaded, the loaded value is
+    /// returned, but not cached.
+    pub async fn get_or_load<T, K, A, F, Fut, E>(
+        &self,
+        table: &T,
+        query: Query<K>,
+        load: F,
+    ) -> Result<A, E>
+    where
+        T: Table,
+        K: keys::Key,
+        A: Clone + Send + Sync + 'static,
+        F: FnOnce(Query<K>) -> Fut,
+        Fut: Future<Output = Result<A, E>>,
+    {
+        let fingerprint = aggregate_key(table, &query);
+        if let Some(value) = self.get(&fingerprint) {
+            return Ok(value);
+        }
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let partition_attribute = K::DEFINITION.hash_key();
+        let partition = query.partition_key().clone();
+
+        let value = load(query).await?;
+
+        let mut entries = self.entries();
+        if self.generation.load(Ordering::Acquire) == generation {
+            entries.insert(
+                fingerprint,
+                CacheEntry {
+                    partition_attribute,
+                    partition,
+                    expires_at: Instant::now() + self.ttl,
+                    value: Arc::new(value.clone()),
+                },
+            );
+        }
+
+        Ok(value)
+    }
+
+    /// Returns the cached aggregate for the query, or hydrates the aggregate
+    /// from the table using [`Query::hydrate`] and caches it
+    pub async fn query<K, A, T>(&self, table: &T, query: Query<K>) -> Result<A, Error>
+    where
+        K: keys::Key,
+        A: Aggregate + Clone + Send + Sync + 'static,
+        T: Table,
+    {
+        self.get_or_load(table, query, |query| query.hydrate(table))
+            .await
+    }
+
+    /// Invalidates cached aggregates that may contain the written item
+    ///
+    /// The item should contain the key attributes of the written item, as
+    /// provided to [`Table::after_write`].
+    pub fn invalidate(&self, key: &Item) {
+        let mut entries = self.entries();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.retain(|_, entry| {
+            key.get(entry.partition_attribute)
+                .is_some_and(|value| value != &entry.partition)
+        });
+    }
+
+    /// Removes all cached aggregates
+    pub fn clear(&self) {
+        let mut entries = self.entries();
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.clear();
+    }
+
+    fn get<A: Clone + 'static>(&self, fingerprint: &str) -> Option<A> {
+        let mut entries = self.entries();
+        let entry = entries.get(fingerprint)?;
+        let value = if entry.expires_at > Instant::now() {
+            entry.value.downcast_ref::<A>().cloned()
+        } else {
+            None
+        };
+
+        if value.is_none() {
+            entries.remove(fingerprint);
+        }
+
+        value
+    }
+
+    fn entries(&self) -> MutexGuard<'_, Lru<String, CacheEntry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A read-through cache of items read by primary key
+///
+/// See the [module documentation][self] for details.
+pub struct ItemCache {
+    ttl: Duration,
+    generation: AtomicU64,
+    backend: Box<dyn ItemCacheBackend>,
+}
+
+impl fmt::Debug for ItemCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ItemCache")
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ItemCache {
+    /// Creates an in-memory cache holding at most `capacity` items, each for at most `ttl`
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self::with_backend(LruBackend::new(capacity), ttl)
+    }
+
+    /// Creates a cache that keeps its entries in the given backend, each for at most `ttl`
+    pub fn with_backend(backend: impl ItemCacheBackend, ttl: Duration) -> Self {
+        Self {
+            ttl,
+            generation: AtomicU64::new(0),
+            backend: Box::new(backend),
+        }
+    }
+
+    /// Invalidates the cached item with the key
+    ///
+    /// The key should contain the primary key attributes of the item. This
+    /// is called automatically for writes made through modyne to the table
+    /// returning this cache from [`Table::item_cache`].
+    pub fn invalidate<T: Table>(&self, table: &T, key: &Item) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.backend.remove(&cache_key(table, key));
+    }
+
+    /// Removes all cached items
+    pub fn clear(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.backend.clear();
+    }
+
+    /// Returns the cached result of reading the key, if it has not expired
+    ///
+    /// A cached result of `None` records that no item exists at the key.
+    pub(crate) fn get<T: Table>(&self, table: &T, key: &Item) -> Option<Option<Item>> {
+        let cache_key = cache_key(table, key);
+        let entry = self.backend.get(&cache_key)?;
+        if entry.expires_at <= Instant::now() {
+            self.backend.remove(&cache_key);
+            return None;
+        }
+
+        Some(entry.item)
+    }
+
+    /// The current generation, which changes with every invalidation
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Caches the result of reading the key, unless an invalidation has
+    /// occurred since the read began at the given generation
+    pub(crate) fn insert<T: Table>(
+        &self,
+        table: &T,
+        key: &Item,
+        item: Option<Item>,
+        generation: u64,
+    ) {
+        if self.generation() != generation {
+            return;
+        }
+
+        self.backend.insert(
+            cache_key(table, key),
+            CachedItem {
+                item,
+                expires_at: Instant::now() + self.ttl,
+            },
+        );
+    }
+}
+
+/// Renders the table, key scope, and fingerprint of a query
+fn aggregate_key<T: Table, K: keys::Key>(table: &T, query: &Query<K>) -> String {
+    format!(
+        "{}\0{}\0{}",
+        table.table_name(),
+        table.key_scope().map_or("", |scope| scope.prefix()),
+        query.fingerprint(),
+    )
+}
+
+/// Renders the table, key scope, and primary key attributes of a key
+fn cache_key<T: Table>(table: &T, key: &Item) -> String {
+    let primary = T::PrimaryKey::PRIMARY_KEY_DEFINITION;
+    let values: Vec<_> = std::iter::once(primary.hash_key)
+        .chain(primary.range_key)
+        .map(|attr| {
+            key.get(attr)
+                .and_then(|value| crate::json::typed(value).ok())
+                .unwrap_or(serde_json::Value::Null)
+        })
+        .collect();
+
+    format!(
+        "{}\0{}\0{}",
+        table.table_name(),
+        table.key_scope().map_or("", |scope| scope.prefix()),
+        serde_json::Value::Array(values),
+    )
+}
+
+/// An entry held by an [`ItemCache`]
+#[derive(Clone, Debug)]
+pub struct CachedItem {
+    /// The item read, or `None` if no item exists at the key
+    pub item: Option<Item>,
+
+    /// The instant after which the entry must no longer be used
+    pub expires_at: Instant,
+}
+
+/// A store for the entries of an [`ItemCache`]
+///
+/// Expiry is enforced by the [`ItemCache`], so a backend only needs to bound
+/// its size. Implementations must be safe to use from multiple threads.
+pub trait ItemCacheBackend: Send + Sync + 'static {
+    /// Returns the entry stored under the key
+    fn get(&self, key: &str) -> Option<CachedItem>;
+
+    /// Stores an entry under the key, replacing any existing entry
+    fn insert(&self, key: String, entry: CachedItem);
+
+    /// Removes the entry stored under the key
+    fn remove(&self, key: &str);
+
+    /// Removes all entries
+    fn clear(&self);
+}
+
+/// An in-memory backend that evicts the least recently used entries
+pub struct LruBackend {
+    entries: Mutex<Lru<String, CachedItem>>,
+}
+
+impl fmt::Debug for LruBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let entries = self.entries();
+        f.debug_struct("LruBackend")
+            .field("capacity", &entries.capacity())
+            .field("len", &entries.len())
+            .finish()
+    }
+}
+
+impl LruBackend {
+    /// Creates a backend holding at most `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(Lru::new(capacity)),
+        }
+    }
+
+    fn entries(&self) -> MutexGuard<'_, Lru<String, CachedItem>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl ItemCacheBackend for LruBackend {
+    fn get(&self, key: &str) -> Option<CachedItem> {
+        self.entries().get(key).cloned()
+    }
+
+    fn insert(&self, key: String, entry: CachedItem) {
+        self.entries().insert(key, entry);
+    }
+
+    fn remove(&self, key: &str) {
+        self.entries().remove(key);
+    }
+
+    fn clear(&self) {
+        self.entries().clear();
+    }
+}
+
+#[cfg(feature = "moka")]
+impl ItemCacheBackend for moka::sync::Cache<String, CachedItem> {
+    fn get(&self, key: &str) -> Option<CachedItem> {
+        moka::sync::Cache::get(self, key)
+    }
+
+    fn insert(&self, key: String, entry: CachedItem) {
+        moka::sync::Cache::insert(self, key, entry);
+    }
+
+    fn remove(&self, key: &str) {
+        self.invalidate(key);
+    }
+
+    fn clear(&self) {
+        self.invalidate_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_dynamodb::{
+        operation::{batch_write_item::BatchWriteItemOutput, put_item::PutItemOutput},
+        types::{PutRequest, ReturnValue, WriteRequest},
+    };
+
+    use super::*;
+    use crate::{
+        client::DynamoClient,
+        expr,
+        mock::{ops, MockTable, Operation},
+        model::{BatchWrite, Delete, Get, Put},
+        scope::{KeyScope, Scoped},
+        WritableTable,
+    };
+
+    struct TestTable;
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+
+        fn table_name(&self) -> &str {
+            "test"
+        }
+    }
+
+    struct CachedTable {
+        mock: MockTable<TestTable>,
+        items: ItemCache,
+    }
+
+    impl Table for CachedTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            self.mock.table_name()
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            self.mock.client()
+        }
+
+        fn dynamo_client(&self) -> &dyn DynamoClient {
+            self.mock.dynamo_client()
+        }
+
+        fn item_cache(&self) -> Option<&ItemCache> {
+            Some(&self.items)
+        }
+    }
+
+    impl WritableTable for CachedTable {}
+
+    fn key() -> Item {
+        keys::Primary {
+            hash: "PK".to_string(),
+            range: "SK".to_string(),
+        }
+        .into_key()
+    }
+
+    #[test]
+    fn gets_are_served_from_the_cache_until_written() {
+        let table = CachedTable {
+            mock: MockTable::new("test"),
+            items: ItemCache::new(10, Duration::from_secs(60)),
+        };
+        let mut item = key();
+        item.insert("name".to_string(), AttributeValue::S("cached".into()));
+        table
+            .mock
+            .expect::<ops::GetItem>(|e| e.returning_item(Some(item.clone())))
+            .expect::<ops::DeleteItem>(|e| e.times(1));
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        for _ in 0..2 {
+            let output = runtime.block_on(Get::new(key()).execute(&table)).unwrap();
+            assert_eq!(output.item.as_ref(), Some(&item));
+        }
+        assert_eq!(table.mock.calls(Operation::GetItem), 1);
+
+        runtime
+            .block_on(Get::new(key()).execute_with_consistency(&table, true))
+            .unwrap();
+        assert_eq!(table.mock.calls(Operation::GetItem), 2);
+
+        runtime
+            .block_on(Delete::new(key()).execute(&table))
+            .unwrap();
+        runtime.block_on(Get::new(key()).execute(&table)).unwrap();
+        assert_eq!(table.mock.calls(Operation::GetItem), 3);
+    }
+
+    #[test]
+    fn expired_entries_are_not_used() {
+        let table = TestTable;
+        let cache = ItemCache::new(10, Duration::ZERO);
+        cache.insert(&table, &key(), None, cache.generation());
+        assert!(cache.get(&table, &key()).is_none());
+    }
+
+    #[test]
+    fn invalidations_during_a_read_prevent_caching() {
+        let table = TestTable;
+        let cache = ItemCache::new(10, Duration::from_secs(60));
+        let generation = cache.generation();
+        cache.invalidate(&table, &key());
+        cache.insert(&table, &key(), None, generation);
+        assert!(cache.get(&table, &key()).is_none());
+
+        cache.insert(&table, &key(), None, cache.generation());
+        assert_eq!(cache.get(&table, &key()), Some(None));
+    }
+
+    struct AggregateTable {
+        mock: MockTable<TestTable>,
+        aggregates: AggregateCache,
+    }
+
+    impl Table for AggregateTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            self.mock.table_name()
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            self.mock.client()
+        }
+
+        fn dynamo_client(&self) -> &dyn DynamoClient {
+            self.mock.dynamo_client()
+        }
+
+        fn after_write(&self, key: &Item) {
+            self.aggregates.invalidate(key);
+        }
+    }
+
+    impl WritableTable for AggregateTable {}
+
+    impl AggregateTable {
+        fn new() -> Self {
+            Self {
+                mock: MockTable::new("test"),
+                aggregates: AggregateCache::new(10, Duration::from_secs(60)),
+            }
+        }
+
+        /// Caches a value for the query, returning whether it was already cached
+        fn cache<K: keys::Key>(&self, runtime: &tokio::runtime::Runtime, query: Query<K>) -> bool {
+            let mut loaded = false;
+            runtime
+                .block_on(self.aggregates.get_or_load(self, query, |_| {
+                    loaded = true;
+                    async { Ok::<_, Error>(()) }
+                }))
+                .unwrap();
+            !loaded
+        }
+
+        fn is_cached<K: keys::Key>(&self, query: Query<K>) -> bool {
+            self.aggregates
+                .get::<()>(&aggregate_key(self, &query))
+                .is_some()
+        }
+    }
+
+    fn primary(partition: &str) -> Query<keys::Primary> {
+        Query::new(expr::KeyCondition::in_partition(partition))
+    }
+
+    fn gsi1(partition: &str) -> Query<keys::Gsi1> {
+        Query::new(expr::KeyCondition::in_partition(partition))
+    }
+
+    fn item(partition: &str, index_partition: &str) -> Item {
+        let mut item = keys::Primary {
+            hash: partition.to_string(),
+            range: "SK".to_string(),
+        }
+        .into_key();
+        item.insert(
+            "GSI1PK".to_string(),
+            AttributeValue::S(index_partition.to_string()),
+        );
+        item.insert("GSI1SK".to_string(), AttributeValue::S("SK".to_string()));
+        item
+    }
+
+    #[test]
+    fn aggregates_are_cached_until_their_partition_is_written() {
+        let table = AggregateTable::new();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        assert!(!table.cache(&runtime, primary("P1")));
+        assert!(table.cache(&runtime, primary("P1")));
+        table.cache(&runtime, primary("P2"));
+        table.cache(&runtime, gsi1("A"));
+
+        table.aggregates.invalidate(&item("P1", "B"));
+        assert!(!table.is_cached(primary("P1")));
+        assert!(table.is_cached(primary("P2")));
+        assert!(table.is_cached(gsi1("A")));
+
+        table.aggregates.invalidate(&key());
+        assert!(table.is_cached(primary("P2")));
+        assert!(!table.is_cached(gsi1("A")));
+    }
+
+    #[test]
+    fn puts_invalidate_the_index_partitions_an_item_moves_between() {
+        let table = AggregateTable::new();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        for partition in ["A", "B", "C"] {
+            table.cache(&runtime, gsi1(partition));
+        }
+        table.mock.expect::<ops::PutItem>(|e| {
+            e.times(1).returning(
+                PutItemOutput::builder()
+                    .set_attributes(Some(item("P1", "A")))
+                    .build(),
+            )
+        });
+
+        let output = runtime
+            .block_on(Put::new(item("P1", "B")).execute(&table))
+            .unwrap();
+
+        assert!(!table.is_cached(gsi1("A")));
+        assert!(!table.is_cached(gsi1("B")));
+        assert!(table.is_cached(gsi1("C")));
+        let input = &table.mock.inputs::<ops::PutItem>()[0];
+        assert_eq!(input.return_values, Some(ReturnValue::AllOld));
+        assert_eq!(output.attributes, None);
+    }
+
+    #[test]
+    fn unprocessed_batch_writes_do_not_invalidate() {
+        let table = AggregateTable::new();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        table.cache(&runtime, primary("P1"));
+        table.cache(&runtime, primary("P2"));
+        let unprocessed = WriteRequest::builder()
+            .put_request(
+                PutRequest::builder()
+                    .set_item(Some(item("P2", "B")))
+                    .build()
+                    .unwrap(),
+            )
+            .build();
+        table.mock.expect::<ops::BatchWriteItem>(|e| {
+            e.times(1).returning(
+                BatchWriteItemOutput::builder()
+                    .unprocessed_items("test", vec![unprocessed])
+                    .build(),
+            )
+        });
+
+        runtime
+            .block_on(
+                BatchWrite::new()
+                    .operation(Put::new(item("P1", "A")))
+                    .operation(Put::new(item("P2", "B")))
+                    .execute(&table),
+            )
+            .unwrap();
+
+        assert!(!table.is_cached(primary("P1")));
+        assert!(table.is_cached(primary("P2")));
+    }
+
+    #[test]
+    fn aggregates_are_cached_per_key_scope() {
+        let cache = AggregateCache::new(10, Duration::from_secs(60));
+        let acme = Scoped::new(MockTable::<TestTable>::new("test"), KeyScope::new("acme"));
+        let globex = Scoped::new(MockTable::<TestTable>::new("test"), KeyScope::new("globex"));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let load = |table: &Scoped<MockTable<TestTable>>, tenant: &'static str| {
+            runtime
+                .block_on(
+                    cache.get_or_load(table, primary("P1"), |_| async { Ok::<_, Error>(tenant) }),
+                )
+                .unwrap()
+        };
+
+        assert_eq!(load(&acme, "acme"), "acme");
+        assert_eq!(load(&globex, "globex"), "globex");
+        assert_eq!(load(&acme, "other"), "acme");
+        assert_eq!(load(&globex, "other"), "globex");
+    }
+}
@@ -0,0 +1,500 @@
+//! Entities whose serialized form spans multiple items
+//!
+//! DynamoDB limits items to 400 KB. An entity that may exceed that limit can
+//! implement [`ChunkedEntity`], which stores the serialized entity split
+//! across several items in the same partition. Each chunk is stored under
+//! the entity's sort key with a `#CHUNK#000`, `#CHUNK#001`, … suffix, so that
+//! all chunks can be retrieved with a single query. The secondary index keys
+//! of the entity are only written to the first chunk.
+//!
+//! Chunks are written together in a single transaction using
+//! [`ChunkedEntityExt::put_chunks()`], and are reassembled when read into a
+//! [`Chunked`] aggregate. Because a transaction can include at most 100
+//! operations, an entity can span at most 100 chunks; larger entities are
+//! rejected with an error before any transaction is prepared.
+//!
+//! When an entity shrinks, chunks beyond the new chunk count are left in
+//! place. Every chunk records a version derived from the full serialized
+//! entity, so these stale chunks are ignored during reassembly. They can be
+//! removed by deleting the chunks beyond the current count.
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    hash::Hasher,
+    marker::PhantomData,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    error::{MalformedChunkError, TooManyChunksError},
+    expr, keys,
+    model::{primary_key_of, Put, Query, TransactWrite},
+    Aggregate, AttributeValue, Entity, EntityDiscriminator, Error, Item, ProjectionSet, Table,
+};
+
+const CHUNK_INDEX: &str = "chunk_index";
+const CHUNK_COUNT: &str = "chunk_count";
+const CHUNK_VERSION: &str = "chunk_version";
+const CHUNK_DATA: &str = "chunk_data";
+const CHUNK_SEPARATOR: &str = "#CHUNK#";
+
+/// The maximum number of operations in a transaction, and so of chunks
+const MAX_CHUNKS: usize = 100;
+
+/// An entity that is stored split across multiple items
+///
+/// The table's primary key must have a string sort key.
+pub trait ChunkedEntity: Entity + Serialize + DeserializeOwned {
+    /// The maximum number of bytes of the serialized entity stored in each chunk
+    ///
+    /// The default leaves room within the item size limit for the key
+    /// attributes of the chunk.
+    const CHUNK_SIZE: usize = 384 * 1024;
+}
+
+/// Extension methods for [`ChunkedEntity`] types
+pub trait ChunkedEntityExt: ChunkedEntity {
+    /// Splits the entity into the items holding each of its chunks
+    ///
+    /// Fails if the entity would span more than 100 chunks.
+    fn to_chunk_items(&self) -> Result<Vec<Item>, Error>;
+
+    /// Prepare a transaction that writes all of the chunks of the entity
+    fn put_chunks(&self) -> Result<TransactWrite, Error>;
+
+    /// Prepare a query that reads all of the chunks of an entity
+    ///
+    /// The results of the query can be reassembled with a [`Chunked`] aggregate.
+    fn query_chunks(input: Self::KeyInput<'_>) -> Query<<Self::Table as Table>::PrimaryKey>
+    where
+        <Self::Table as Table>::PrimaryKey: keys::Key;
+}
+
+impl<E: ChunkedEntity> ChunkedEntityExt for E {
+    fn to_chunk_items(&self) -> Result<Vec<Item>, Error> {
+        let payload = serde_json::to_vec(self)?;
+        let version = payload_version(&payload);
+        let chunks: Vec<_> = payload.chunks(E::CHUNK_SIZE.max(1)).collect();
+        let count = chunks.len();
+        if count > MAX_CHUNKS {
+            return Err(TooManyChunksError {
+                entity_type: E::ENTITY_TYPE,
+                count,
+                max: MAX_CHUNKS,
+            }
+            .into());
+        }
+
+        let full_key: Item = crate::codec::to_item(self.full_key()).unwrap();
+        let (range_key, base) = base_range::<E::Table>(&full_key);
+        let primary = primary_key_of::<E::Table>(&full_key);
+
+        let items = chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, data)| {
+                let mut item = if index == 0 {
+                    full_key.clone()
+                } else {
+                    primary.clone()
+                };
+
+                item.insert(
+                    range_key.to_string(),
+                    AttributeValue::S(chunk_range(&base, index)),
+                );
+                if let EntityDiscriminator::Attribute = <E::Table as Table>::ENTITY_DISCRIMINATOR {
+                    item.insert(
+                        <E::Table as Table>::ENTITY_TYPE_ATTRIBUTE.to_string(),
+                        <E::Table as Table>::serialize_entity_type(E::ENTITY_TYPE),
+                    );
+                }
+                item.insert(CHUNK_INDEX.into(), AttributeValue::N(index.to_string()));
+                item.insert(CHUNK_COUNT.into(), AttributeValue::N(count.to_string()));
+                item.insert(CHUNK_VERSION.into(), AttributeValue::S(version.clone()));
+                item.insert(
+                    CHUNK_DATA.into(),
+                    AttributeValue::B(aws_sdk_dynamodb::primitives::Blob::new(data)),
+                );
+                item
+            })
+            .collect();
+
+        Ok(items)
+    }
+
+    fn put_chunks(&self) -> Result<TransactWrite, Error> {
+        let transaction = self
+            .to_chunk_items()?
+            .into_iter()
+            .fold(TransactWrite::new(), |tx, item| {
+                tx.operation(Put::new(item))
+            });
+
+        Ok(transaction)
+    }
+
+    fn query_chunks(input: Self::KeyInput<'_>) -> Query<<Self::Table as Table>::PrimaryKey>
+    where
+        <Self::Table as Table>::PrimaryKey: keys::Key,
+    {
+        use keys::PrimaryKey;
+
+        let key = E::primary_key(input).into_key();
+        let (_, base) = base_range::<E::Table>(&key);
+        let partition = primary_key_of::<E::Table>(&key)
+            .remove(<E::Table as Table>::PrimaryKey::PRIMARY_KEY_DEFINITION.hash_key)
+            .expect("primary key includes the partition key");
+
+        Query::new(
+            expr::KeyCondition::in_partition_value(partition)
+                .begins_with(format!("{base}{CHUNK_SEPARATOR}")),
+        )
+    }
+}
+
+/// Derives a version for the serialized entity, used to detect stale chunks
+fn payload_version(payload: &[u8]) -> String {
+    let mut hasher = fnv::FnvHasher::default();
+    hasher.write(payload);
+    format!("{:016x}", hasher.finish())
+}
+
+fn chunk_range(base: &str, index: usize) -> String {
+    format!("{base}{CHUNK_SEPARATOR}{index:03}")
+}
+
+/// Extracts the name and string value of the sort key from an item
+///
+/// # Panics
+///
+/// Panics if the table does not have a string sort key.
+fn base_range<T: Table>(item: &Item) -> (&'static str, String) {
+    use keys::PrimaryKey;
+
+    let range_key = T::PrimaryKey::PRIMARY_KEY_DEFINITION
+        .range_key
+        .expect("chunked entities require a table with a sort key");
+    let base = item
+        .get(range_key)
+        .and_then(|v| v.as_s().ok())
+        .expect("chunked entities require a string sort key");
+
+    (range_key, base.clone())
+}
+
+/// A single chunk of a [`ChunkedEntity`]
+///
+/// This is the projection used by the [`Chunked`] aggregate, and is not
+/// usually used directly.
+#[derive(Debug)]
+pub struct Chunk<E> {
+    partition: AttributeValue,
+    base: String,
+    index: usize,
+    count: usize,
+    version: String,
+    data: Vec<u8>,
+    entity: PhantomData<fn() -> E>,
+}
+
+impl<E: ChunkedEntity> Chunk<E> {
+    fn from_item(mut item: Item) -> Result<Self, MalformedChunkError> {
+        use keys::PrimaryKey;
+
+        let definition = <E::Table as Table>::PrimaryKey::PRIMARY_KEY_DEFINITION;
+        let partition = item
+            .remove(definition.hash_key)
+            .ok_or_else(|| MalformedChunkError::new("missing partition key"))?;
+        let range = definition
+            .range_key
+            .and_then(|range_key| item.remove(range_key))
+            .and_then(|range| range.as_s().ok().cloned())
+            .ok_or_else(|| MalformedChunkError::new("missing sort key"))?;
+        let base = range
+            .rsplit_once(CHUNK_SEPARATOR)
+            .map(|(base, _)| base.to_string())
+            .ok_or_else(|| MalformedChunkError::new("sort key has no chunk suffix"))?;
+
+        let number = |item: &mut Item, name: &str| {
+            item.remove(name)
+                .and_then(|v| v.as_n().ok().and_then(|n| n.parse::<usize>().ok()))
+                .ok_or_else(|| MalformedChunkError::new("missing chunk index or count"))
+        };
+        let index = number(&mut item, CHUNK_INDEX)?;
+        let count = number(&mut item, CHUNK_COUNT)?;
+
+        let version = match item.remove(CHUNK_VERSION) {
+            Some(AttributeValue::S(version)) => version,
+            _ => return Err(MalformedChunkError::new("missing chunk version")),
+        };
+        let data = match item.remove(CHUNK_DATA) {
+            Some(AttributeValue::B(data)) => data.into_inner(),
+            _ => return Err(MalformedChunkError::new("missing chunk data")),
+        };
+
+        Ok(Self {
+            partition,
+            base,
+            index,
+            count,
+            version,
+            data,
+            entity: PhantomData,
+        })
+    }
+}
+
+impl<E: ChunkedEntity> ProjectionSet for Chunk<E> {
+    fn try_from_item(item: Item) -> Result<Option<Self>, Error> {
+        let entity_type = crate::__private::get_table_entity_type::<E::Table>(&item)?;
+        if entity_type != E::ENTITY_TYPE || !item.contains_key(CHUNK_DATA) {
+            return Ok(None);
+        }
+
+        Ok(Some(Self::from_item(item)?))
+    }
+
+    fn projection_expression() -> Option<expr::StaticProjection> {
+        None
+    }
+
+    #[inline]
+    fn entity_types() -> Vec<&'static crate::EntityTypeNameRef> {
+        vec![E::ENTITY_TYPE]
+    }
+}
+
+/// An aggregate that reassembles [`ChunkedEntity`] values from their chunks
+///
+/// Items that are not chunks of the entity type are ignored. Entities are
+/// available once all of their chunks have been merged, regardless of the
+/// order in which the chunks were read.
+#[derive(Debug)]
+pub struct Chunked<E> {
+    entities: Vec<E>,
+    pending: BTreeMap<(String, String), BTreeMap<usize, Chunk<E>>>,
+    completed: BTreeSet<(String, String)>,
+}
+
+impl<E> Default for Chunked<E> {
+    fn default() -> Self {
+        Self {
+            entities: Vec::new(),
+            pending: BTreeMap::new(),
+            completed: BTreeSet::new(),
+        }
+    }
+}
+
+impl<E> Chunked<E> {
+    /// The entities that have been fully reassembled
+    #[inline]
+    pub fn entities(&self) -> &[E] {
+        &self.entities
+    }
+
+    /// Consumes the aggregate, returning the fully reassembled entities
+    #[inline]
+    pub fn into_entities(self) -> Vec<E> {
+        self.entities
+    }
+
+    /// Whether any entities are still missing chunks
+    #[inline]
+    pub fn is_incomplete(&self) -> bool {
+        !self.pending.is_empty()
+    }
+}
+
+impl<E: ChunkedEntity> Chunked<E> {
+    fn insert(&mut self, chunk: Chunk<E>) -> Result<(), Error> {
+        let group = (format!("{:?}", chunk.partition), chunk.base.clone());
+        if self.completed.contains(&group) {
+            // Any further chunks are stale leftovers from a larger version
+            return Ok(());
+        }
+
+        let chunks = self.pending.entry(group.clone()).or_default();
+        chunks.insert(chunk.index, chunk);
+
+        // The first chunk is always current, as every write replaces it
+        let Some(first) = chunks.get(&0) else {
+            return Ok(());
+        };
+
+        let (count, version) = (first.count, first.version.clone());
+        let complete = (0..count).all(|index| {
+            chunks
+                .get(&index)
+                .is_some_and(|chunk| chunk.version == version)
+        });
+
+        if complete {
+            let chunks = self.pending.remove(&group).unwrap_or_default();
+            let payload: Vec<u8> = chunks
+                .into_values()
+                .take(count)
+                .flat_map(|chunk| chunk.data)
+                .collect();
+            self.entities.push(serde_json::from_slice(&payload)?);
+            self.completed.insert(group);
+        }
+
+        Ok(())
+    }
+}
+
+impl<E: ChunkedEntity> Aggregate for Chunked<E> {
+    type Projections = Chunk<E>;
+
+    fn merge(&mut self, item: Item) -> Result<(), Error> {
+        match Chunk::<E>::try_from_item(item)? {
+            Some(chunk) => self.insert(chunk),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EntityDef, EntityTypeNameRef};
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            unimplemented!()
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    struct Document {
+        id: String,
+        body: String,
+    }
+
+    impl EntityDef for Document {
+        const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("doc");
+    }
+
+    impl Entity for Document {
+        type KeyInput<'a> = &'a str;
+        type Table = TestTable;
+        type IndexKeys = keys::Gsi1;
+
+        fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+            keys::Primary {
+                hash: format!("DOC#{id}"),
+                range: "DOC".to_string(),
+            }
+        }
+
+        fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+            keys::FullKey {
+                primary: Self::primary_key(&self.id),
+                indexes: keys::Gsi1 {
+                    hash: "DOCS".to_string(),
+                    range: format!("DOC#{}", self.id),
+                },
+            }
+        }
+    }
+
+    impl ChunkedEntity for Document {
+        const CHUNK_SIZE: usize = 16;
+    }
+
+    fn document(body: &str) -> Document {
+        Document {
+            id: "1".into(),
+            body: body.into(),
+        }
+    }
+
+    #[test]
+    fn splits_entity_into_chunks() {
+        let items = document("a body that spans several chunks")
+            .to_chunk_items()
+            .unwrap();
+        assert_eq!(items.len(), 4);
+
+        let ranges: Vec<_> = items.iter().map(|i| i["SK"].as_s().unwrap()).collect();
+        assert_eq!(
+            ranges,
+            [
+                "DOC#CHUNK#000",
+                "DOC#CHUNK#001",
+                "DOC#CHUNK#002",
+                "DOC#CHUNK#003"
+            ]
+        );
+
+        assert!(items.iter().all(|i| i["PK"].as_s().unwrap() == "DOC#1"));
+        assert!(items[0].contains_key("GSI1PK"));
+        assert!(items[1..].iter().all(|i| !i.contains_key("GSI1PK")));
+    }
+
+    #[test]
+    fn rejects_entities_spanning_too_many_chunks() {
+        let body = "x".repeat(Document::CHUNK_SIZE * MAX_CHUNKS);
+        let error = document(&body).put_chunks().unwrap_err();
+        assert_eq!(error.kind(), crate::ErrorKind::Validation);
+        assert!(std::error::Error::source(&error)
+            .unwrap()
+            .is::<TooManyChunksError>());
+
+        let body = "x".repeat(Document::CHUNK_SIZE * (MAX_CHUNKS - 2));
+        assert_eq!(document(&body).to_chunk_items().unwrap().len(), MAX_CHUNKS);
+    }
+
+    #[test]
+    fn reassembles_chunks_in_any_order() {
+        let expected = document("a body that spans several chunks");
+        let mut items = expected.to_chunk_items().unwrap();
+        items.reverse();
+
+        let mut aggregate = Chunked::<Document>::default();
+        aggregate.reduce(items).unwrap();
+        assert!(!aggregate.is_incomplete());
+        assert_eq!(aggregate.into_entities(), [expected]);
+    }
+
+    #[test]
+    fn ignores_stale_chunks_from_a_larger_version() {
+        let stale = document("a much longer body that spans a great many chunks");
+        let expected = document("short");
+
+        let stale_items = stale.to_chunk_items().unwrap();
+        let mut items = expected.to_chunk_items().unwrap();
+        assert!(stale_items.len() > items.len());
+        items.extend(stale_items.into_iter().skip(items.len()));
+
+        let mut aggregate = Chunked::<Document>::default();
+        aggregate.reduce(items).unwrap();
+        assert!(!aggregate.is_incomplete());
+        assert_eq!(aggregate.entities(), [expected]);
+    }
+
+    #[test]
+    fn waits_for_missing_chunks() {
+        let mut items = document("a body that spans several chunks")
+            .to_chunk_items()
+            .unwrap();
+        items.remove(2);
+
+        let mut aggregate = Chunked::<Document>::default();
+        aggregate.reduce(items).unwrap();
+        assert!(aggregate.is_incomplete());
+        assert!(aggregate.entities().is_empty());
+    }
+}
@@ -0,0 +1,434 @@
+//! Abstraction over the client used to send DynamoDB operations
+//!
+//! Operations are sent through the [`DynamoClient`] returned by
+//! [`Table::dynamo_client()`][crate::Table::dynamo_client()]. By default,
+//! this is the standard SDK client returned by
+//! [`Table::client()`][crate::Table::client()]. A table may instead return
+//! any other implementation, such as a DynamoDB Accelerator (DAX) client or
+//! a wrapper that adds middleware around the standard client.
+//!
+//! ```
+//! use aws_sdk_dynamodb::{
+//!     error::SdkError,
+//!     operation::get_item::{GetItemError, GetItemInput, GetItemOutput},
+//! };
+//! use modyne::client::DynamoClient;
+//! # use aws_sdk_dynamodb::operation::{
+//! #     batch_get_item::*, batch_write_item::*, delete_item::*, put_item::*, query::*, scan::*,
+//! #     transact_get_items::*, transact_write_items::*, update_item::*,
+//! # };
+//!
+//! /// Counts the items read through the standard client
+//! #[derive(Debug)]
+//! struct CountingClient {
+//!     inner: aws_sdk_dynamodb::Client,
+//!     reads: std::sync::atomic::AtomicU64,
+//! }
+//!
+//! #[async_trait::async_trait]
+//! impl DynamoClient for CountingClient {
+//!     async fn get_item(
+//!         &self,
+//!         input: GetItemInput,
+//!     ) -> Result<GetItemOutput, SdkError<GetItemError>> {
+//!         self.reads.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+//!         DynamoClient::get_item(&self.inner, input).await
+//!     }
+//!     // ...
+//! #   async fn put_item(&self, input: PutItemInput) -> Result<PutItemOutput, SdkError<PutItemError>> { DynamoClient::put_item(&self.inner, input).await }
+//! #   async fn update_item(&self, input: UpdateItemInput) -> Result<UpdateItemOutput, SdkError<UpdateItemError>> { DynamoClient::update_item(&self.inner, input).await }
+//! #   async fn delete_item(&self, input: DeleteItemInput) -> Result<DeleteItemOutput, SdkError<DeleteItemError>> { DynamoClient::delete_item(&self.inner, input).await }
+//! #   async fn query(&self, input: QueryInput) -> Result<QueryOutput, SdkError<QueryError>> { DynamoClient::query(&self.inner, input).await }
+//! #   async fn scan(&self, input: ScanInput) -> Result<ScanOutput, SdkError<ScanError>> { DynamoClient::scan(&self.inner, input).await }
+//! #   async fn batch_get_item(&self, input: BatchGetItemInput) -> Result<BatchGetItemOutput, SdkError<BatchGetItemError>> { DynamoClient::batch_get_item(&self.inner, input).await }
+//! #   async fn batch_write_item(&self, input: BatchWriteItemInput) -> Result<BatchWriteItemOutput, SdkError<BatchWriteItemError>> { DynamoClient::batch_write_item(&self.inner, input).await }
+//! #   async fn transact_get_items(&self, input: TransactGetItemsInput) -> Result<TransactGetItemsOutput, SdkError<TransactGetItemsError>> { DynamoClient::transact_get_items(&self.inner, input).await }
+//! #   async fn transact_write_items(&self, input: TransactWriteItemsInput) -> Result<TransactWriteItemsOutput, SdkError<TransactWriteItemsError>> { DynamoClient::transact_write_items(&self.inner, input).await }
+//! }
+//! ```
+//!
+//! The standard client's inherent methods of the same names return fluent
+//! builders, so the trait's methods must be called with the fully qualified
+//! syntax shown above.
+//!
+//! A single operation can also carry a [`Customization`], which the
+//! standard client applies to that request only, such as to add an
+//! interceptor or to override the configuration of the client.
+
+use std::{fmt, future::Future, time::Duration};
+
+use aws_sdk_dynamodb::{
+    client::customize::CustomizableOperation,
+    config::{Intercept, SharedInterceptor},
+    error::{BuildError, SdkError},
+    operation::{
+        batch_get_item::{BatchGetItemError, BatchGetItemInput, BatchGetItemOutput},
+        batch_write_item::{BatchWriteItemError, BatchWriteItemInput, BatchWriteItemOutput},
+        delete_item::{DeleteItemError, DeleteItemInput, DeleteItemOutput},
+        get_item::{GetItemError, GetItemInput, GetItemOutput},
+        put_item::{PutItemError, PutItemInput, PutItemOutput},
+        query::{QueryError, QueryInput, QueryOutput},
+        scan::{ScanError, ScanInput, ScanOutput},
+        transact_get_items::{
+            TransactGetItemsError, TransactGetItemsInput, TransactGetItemsOutput,
+        },
+        transact_write_items::{
+            TransactWriteItemsError, TransactWriteItemsInput, TransactWriteItemsOutput,
+        },
+        update_item::{UpdateItemError, UpdateItemInput, UpdateItemOutput},
+    },
+};
+
+/// A client able to send the DynamoDB operations used by this crate
+#[async_trait::async_trait]
+pub trait DynamoClient: Send + Sync {
+    /// Sends a `GetItem` operation
+    async fn get_item(&self, input: GetItemInput) -> Result<GetItemOutput, SdkError<GetItemError>>;
+
+    /// Sends a `PutItem` operation
+    async fn put_item(&self, input: PutItemInput) -> Result<PutItemOutput, SdkError<PutItemError>>;
+
+    /// Sends an `UpdateItem` operation
+    async fn update_item(
+        &self,
+        input: UpdateItemInput,
+    ) -> Result<UpdateItemOutput, SdkError<UpdateItemError>>;
+
+    /// Sends a `DeleteItem` operation
+    async fn delete_item(
+        &self,
+        input: DeleteItemInput,
+    ) -> Result<DeleteItemOutput, SdkError<DeleteItemError>>;
+
+    /// Sends a `Query` operation
+    async fn query(&self, input: QueryInput) -> Result<QueryOutput, SdkError<QueryError>>;
+
+    /// Sends a `Scan` operation
+    async fn scan(&self, input: ScanInput) -> Result<ScanOutput, SdkError<ScanError>>;
+
+    /// Sends a `BatchGetItem` operation
+    async fn batch_get_item(
+        &self,
+        input: BatchGetItemInput,
+    ) -> Result<BatchGetItemOutput, SdkError<BatchGetItemError>>;
+
+    /// Sends a `BatchWriteItem` operation
+    async fn batch_write_item(
+        &self,
+        input: BatchWriteItemInput,
+    ) -> Result<BatchWriteItemOutput, SdkError<BatchWriteItemError>>;
+
+    /// Sends a `TransactGetItems` operation
+    async fn transact_get_items(
+        &self,
+        input: TransactGetItemsInput,
+    ) -> Result<TransactGetItemsOutput, SdkError<TransactGetItemsError>>;
+
+    /// Sends a `TransactWriteItems` operation
+    async fn transact_write_items(
+        &self,
+        input: TransactWriteItemsInput,
+    ) -> Result<TransactWriteItemsOutput, SdkError<TransactWriteItemsError>>;
+}
+
+/// Customizations applied to a single operation by the standard SDK client
+///
+/// A customization is attached to an operation with its builder's
+/// `with_customization()` method. When the operation is sent through the
+/// standard [`aws_sdk_dynamodb::Client`], the interceptors are registered
+/// and the configuration override is applied for that request alone, as
+/// with the SDK's own [`CustomizableOperation`]. This allows, for example,
+/// adding request headers, classifying retries differently, or overriding
+/// the endpoint for one call. The operation's span and instrumentation are
+/// unaffected.
+///
+/// Other [`DynamoClient`] implementations may read the customization of the
+/// operation being sent with [`Customization::current()`].
+///
+/// ```
+/// use aws_sdk_dynamodb::config::{Builder, Region};
+/// use modyne::{client::Customization, keys, keys::PrimaryKey, model::Get};
+///
+/// let customization =
+///     Customization::new().config_override(Builder::new().region(Region::new("us-west-2")));
+///
+/// let get = Get::new(keys::Primary { hash: "A".into(), range: "B".into() }.into_key())
+///     .with_customization(customization);
+/// # let _ = get;
+/// ```
+#[derive(Clone, Default)]
+pub struct Customization {
+    interceptors: Vec<SharedInterceptor>,
+    config_override: Option<aws_sdk_dynamodb::config::Builder>,
+}
+
+impl fmt::Debug for Customization {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Customization")
+            .field("interceptors", &self.interceptors.len())
+            .field("config_override", &self.config_override.is_some())
+            .finish()
+    }
+}
+
+tokio::task_local! {
+    static CURRENT: Customization;
+}
+
+impl Customization {
+    /// Creates a customization that changes nothing
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an interceptor for the request
+    pub fn interceptor(mut self, interceptor: impl Intercept + 'static) -> Self {
+        self.interceptors.push(SharedInterceptor::new(interceptor));
+        self
+    }
+
+    /// Overrides the configuration of the client for the request
+    ///
+    /// Only the settings given in the override are changed. If called more
+    /// than once, the last override is used.
+    pub fn config_override(mut self, config: impl Into<aws_sdk_dynamodb::config::Builder>) -> Self {
+        self.config_override = Some(config.into());
+        self
+    }
+
+    /// The customization of the operation currently being sent, if any
+    ///
+    /// This is only available while a [`DynamoClient`] is sending an
+    /// operation that was given a customization.
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    /// Applies the customization to an operation of the standard SDK client
+    pub fn apply<T, E, B>(
+        &self,
+        mut operation: CustomizableOperation<T, E, B>,
+    ) -> CustomizableOperation<T, E, B> {
+        for interceptor in &self.interceptors {
+            operation = operation.interceptor(interceptor.clone());
+        }
+        if let Some(config) = &self.config_override {
+            operation = operation.config_override(config.clone());
+        }
+        operation
+    }
+
+    /// Makes the customization current while sending an operation
+    pub(crate) async fn scope<F: Future>(customization: Option<Self>, operation: F) -> F::Output {
+        match customization {
+            Some(customization) => CURRENT.scope(customization, operation).await,
+            None => operation.await,
+        }
+    }
+}
+
+/// Sends a fluent builder of the standard SDK client, applying the current
+/// customization, if any
+macro_rules! send_customized {
+    ($operation:expr) => {{
+        let operation = $operation;
+        match Customization::current() {
+            Some(customization) => customization.apply(operation.customize()).send().await,
+            None => operation.send().await,
+        }
+    }};
+}
+
+/// Sends operations through the standard SDK client
+///
+/// Parameters of the legacy DynamoDB API, such as `AttributesToGet` and
+/// `Expected`, are not used by this crate and are not forwarded. The
+/// [`Customization`] of each operation, if any, is applied to its request.
+#[async_trait::async_trait]
+impl DynamoClient for aws_sdk_dynamodb::Client {
+    async fn get_item(&self, input: GetItemInput) -> Result<GetItemOutput, SdkError<GetItemError>> {
+        send_customized!(self
+            .get_item()
+            .set_table_name(input.table_name)
+            .set_key(input.key)
+            .set_consistent_read(input.consistent_read)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_projection_expression(input.projection_expression)
+            .set_expression_attribute_names(input.expression_attribute_names))
+    }
+
+    async fn put_item(&self, input: PutItemInput) -> Result<PutItemOutput, SdkError<PutItemError>> {
+        send_customized!(self
+            .put_item()
+            .set_table_name(input.table_name)
+            .set_item(input.item)
+            .set_return_values(input.return_values)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_return_item_collection_metrics(input.return_item_collection_metrics)
+            .set_condition_expression(input.condition_expression)
+            .set_expression_attribute_names(input.expression_attribute_names)
+            .set_expression_attribute_values(input.expression_attribute_values)
+            .set_return_values_on_condition_check_failure(
+                input.return_values_on_condition_check_failure,
+            ))
+    }
+
+    async fn update_item(
+        &self,
+        input: UpdateItemInput,
+    ) -> Result<UpdateItemOutput, SdkError<UpdateItemError>> {
+        send_customized!(self
+            .update_item()
+            .set_table_name(input.table_name)
+            .set_key(input.key)
+            .set_return_values(input.return_values)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_return_item_collection_metrics(input.return_item_collection_metrics)
+            .set_update_expression(input.update_expression)
+            .set_condition_expression(input.condition_expression)
+            .set_expression_attribute_names(input.expression_attribute_names)
+            .set_expression_attribute_values(input.expression_attribute_values)
+            .set_return_values_on_condition_check_failure(
+                input.return_values_on_condition_check_failure,
+            ))
+    }
+
+    async fn delete_item(
+        &self,
+        input: DeleteItemInput,
+    ) -> Result<DeleteItemOutput, SdkError<DeleteItemError>> {
+        send_customized!(self
+            .delete_item()
+            .set_table_name(input.table_name)
+            .set_key(input.key)
+            .set_return_values(input.return_values)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_return_item_collection_metrics(input.return_item_collection_metrics)
+            .set_condition_expression(input.condition_expression)
+            .set_expression_attribute_names(input.expression_attribute_names)
+            .set_expression_attribute_values(input.expression_attribute_values)
+            .set_return_values_on_condition_check_failure(
+                input.return_values_on_condition_check_failure,
+            ))
+    }
+
+    async fn query(&self, input: QueryInput) -> Result<QueryOutput, SdkError<QueryError>> {
+        send_customized!(self
+            .query()
+            .set_table_name(input.table_name)
+            .set_index_name(input.index_name)
+            .set_select(input.select)
+            .set_limit(input.limit)
+            .set_consistent_read(input.consistent_read)
+            .set_scan_index_forward(input.scan_index_forward)
+            .set_exclusive_start_key(input.exclusive_start_key)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_projection_expression(input.projection_expression)
+            .set_filter_expression(input.filter_expression)
+            .set_key_condition_expression(input.key_condition_expression)
+            .set_expression_attribute_names(input.expression_attribute_names)
+            .set_expression_attribute_values(input.expression_attribute_values))
+    }
+
+    async fn scan(&self, input: ScanInput) -> Result<ScanOutput, SdkError<ScanError>> {
+        send_customized!(self
+            .scan()
+            .set_table_name(input.table_name)
+            .set_index_name(input.index_name)
+            .set_select(input.select)
+            .set_limit(input.limit)
+            .set_consistent_read(input.consistent_read)
+            .set_segment(input.segment)
+            .set_total_segments(input.total_segments)
+            .set_exclusive_start_key(input.exclusive_start_key)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_projection_expression(input.projection_expression)
+            .set_filter_expression(input.filter_expression)
+            .set_expression_attribute_names(input.expression_attribute_names)
+            .set_expression_attribute_values(input.expression_attribute_values))
+    }
+
+    async fn batch_get_item(
+        &self,
+        input: BatchGetItemInput,
+    ) -> Result<BatchGetItemOutput, SdkError<BatchGetItemError>> {
+        send_customized!(self
+            .batch_get_item()
+            .set_request_items(input.request_items)
+            .set_return_consumed_capacity(input.return_consumed_capacity))
+    }
+
+    async fn batch_write_item(
+        &self,
+        input: BatchWriteItemInput,
+    ) -> Result<BatchWriteItemOutput, SdkError<BatchWriteItemError>> {
+        send_customized!(self
+            .batch_write_item()
+            .set_request_items(input.request_items)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_return_item_collection_metrics(input.return_item_collection_metrics))
+    }
+
+    async fn transact_get_items(
+        &self,
+        input: TransactGetItemsInput,
+    ) -> Result<TransactGetItemsOutput, SdkError<TransactGetItemsError>> {
+        send_customized!(self
+            .transact_get_items()
+            .set_transact_items(input.transact_items)
+            .set_return_consumed_capacity(input.return_consumed_capacity))
+    }
+
+    async fn transact_write_items(
+        &self,
+        input: TransactWriteItemsInput,
+    ) -> Result<TransactWriteItemsOutput, SdkError<TransactWriteItemsError>> {
+        send_customized!(self
+            .transact_write_items()
+            .set_transact_items(input.transact_items)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_return_item_collection_metrics(input.return_item_collection_metrics)
+            .set_client_request_token(input.client_request_token))
+    }
+}
+
+/// Sends an operation once its input has been built, converting a failure
+/// to build the input into the error returned by the operation
+pub(crate) async fn send<I, O, E, F>(
+    input: Result<I, BuildError>,
+    send: impl FnOnce(I) -> F,
+) -> Result<O, SdkError<E>>
+where
+    F: Future<Output = Result<O, SdkError<E>>>,
+{
+    match input {
+        Ok(input) => send(input).await,
+        Err(error) => Err(SdkError::construction_failure(error)),
+    }
+}
+
+/// Limits the time allowed for an operation to complete, if a timeout is given
+///
+/// The timeout is recorded on the operation's span. If it elapses, the
+/// operation is canceled and a timeout error is returned in its place.
+pub(crate) async fn timeout<O, E, F>(
+    span: &tracing::Span,
+    timeout: Option<Duration>,
+    operation: F,
+) -> Result<O, SdkError<E>>
+where
+    F: Future<Output = Result<O, SdkError<E>>>,
+{
+    let Some(timeout) = timeout else {
+        return operation.await;
+    };
+
+    span.record("modyne.timeout_ms", timeout.as_millis() as u64);
+    match tokio::time::timeout(timeout, operation).await {
+        Ok(result) => result,
+        Err(_) => Err(SdkError::timeout_error(
+            crate::error::OperationTimeoutError { timeout },
+        )),
+    }
+}
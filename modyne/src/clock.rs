@@ -0,0 +1,127 @@
+//! Sources of the current time
+//!
+//! Helpers in this crate that depend on the current time, such as
+//! [`Expiry::unexpired_filter()`][crate::types::Expiry::unexpired_filter()]
+//! and [`Idempotency::with_clock()`][crate::idempotency::Idempotency::with_clock()],
+//! read it from a [`Clock`]. Tables provide their clock through
+//! [`Table::clock()`][crate::Table::clock()], which defaults to the
+//! [`SystemClock`]. A [`FixedClock`] can be used instead to make tests
+//! deterministic.
+//!
+//! Helpers that wait between attempts, such as
+//! [`ReadAfterWrite`][crate::read_after_write::ReadAfterWrite], also sleep
+//! through the clock. A [`FixedClock`] advances its time instead of
+//! sleeping, so that retries complete immediately in tests.
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use modyne::clock::{Clock, FixedClock};
+//!
+//! let start = time::OffsetDateTime::from_unix_timestamp(1_701_950_400).unwrap();
+//! let clock = FixedClock::new(start);
+//! clock.advance(Duration::from_secs(60));
+//!
+//! assert_eq!(clock.now().unix_timestamp(), 1_701_950_460);
+//! ```
+
+use std::{
+    fmt,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use futures_util::future::BoxFuture;
+use time::OffsetDateTime;
+
+/// A source of the current time
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// The current time
+    fn now(&self) -> OffsetDateTime;
+
+    /// Waits until the given duration has elapsed
+    ///
+    /// Defaults to sleeping on the Tokio timer.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+/// The delay before retrying after the given number of failed attempts
+///
+/// The delay grows exponentially from 50 ms, and is jittered to between
+/// half and all of the exponential delay so that clients retrying at the
+/// same time spread out their attempts.
+pub(crate) fn backoff(attempt: u32) -> Duration {
+    use std::hash::{BuildHasher, Hasher};
+
+    let max = Duration::from_millis(50) * 2u32.pow(attempt.min(10));
+    let half = max / 2;
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    half + Duration::from_nanos(random % (half.as_nanos() as u64 + 1))
+}
+
+/// A clock that reads the system time
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc()
+    }
+}
+
+/// A clock that only changes when explicitly set or advanced
+#[derive(Debug)]
+pub struct FixedClock {
+    now: Mutex<OffsetDateTime>,
+}
+
+impl FixedClock {
+    /// Construct a clock stopped at the given time
+    pub fn new(now: OffsetDateTime) -> Self {
+        Self {
+            now: Mutex::new(now),
+        }
+    }
+
+    /// Sets the current time
+    pub fn set(&self, now: OffsetDateTime) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) = now;
+    }
+
+    /// Moves the current time forward by the given duration
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner) += duration;
+    }
+}
+
+impl Clock for FixedClock {
+    fn now(&self) -> OffsetDateTime {
+        *self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Advances the current time by the given duration without waiting
+    fn sleep(&self, duration: Duration) -> BoxFuture<'_, ()> {
+        self.advance(duration);
+        Box::pin(std::future::ready(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_is_jittered_within_the_exponential_delay() {
+        for attempt in [1, 3, 10, 20] {
+            let max = Duration::from_millis(50) * 2u32.pow(attempt.min(10));
+            let delays: Vec<_> = (0..16).map(|_| backoff(attempt)).collect();
+            assert!(delays.iter().all(|d| *d >= max / 2 && *d <= max));
+            assert!(delays.iter().any(|d| *d != delays[0]));
+        }
+    }
+}
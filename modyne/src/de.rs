@@ -0,0 +1,398 @@
+//! Deserialization of items without taking ownership of their attributes
+//!
+//! `serde_dynamo` deserializes from owned attribute values, so every string
+//! is copied even when the target type could borrow it. This deserializer
+//! works from a reference to an item instead, lending out strings and
+//! binary values so that types using `&str`, `&[u8]`, or `Cow` can avoid
+//! copying large attributes.
+
+use std::collections::hash_map;
+
+use serde::de::{self, value::BorrowedStrDeserializer, Error as _, Unexpected, Visitor};
+
+use crate::{AttributeValue, Item};
+
+type Error = serde_dynamo::Error;
+
+/// Deserializes a value from a borrowed DynamoDB item
+pub(crate) fn from_item_ref<'de, T>(item: &'de Item) -> Result<T, Error>
+where
+    T: de::Deserialize<'de>,
+{
+    T::deserialize(ValueRef::Map(item))
+}
+
+#[derive(Clone, Copy)]
+enum ValueRef<'de> {
+    Attribute(&'de AttributeValue),
+    Map(&'de Item),
+    String(&'de str),
+    Number(&'de str),
+    Binary(&'de [u8]),
+}
+
+impl<'de> ValueRef<'de> {
+    /// Unwraps scalar and map attributes so that they can be handled uniformly
+    /// with the members of sets
+    fn resolve(self) -> Self {
+        match self {
+            Self::Attribute(AttributeValue::S(s)) => Self::String(s),
+            Self::Attribute(AttributeValue::N(n)) => Self::Number(n),
+            Self::Attribute(AttributeValue::B(b)) => Self::Binary(b.as_ref()),
+            Self::Attribute(AttributeValue::M(m)) => Self::Map(m),
+            other => other,
+        }
+    }
+
+    fn is_null(self) -> bool {
+        matches!(self, Self::Attribute(AttributeValue::Null(_)))
+    }
+}
+
+fn visit_number<'de, V: Visitor<'de>>(n: &'de str, visitor: V) -> Result<V::Value, Error> {
+    if let Ok(n) = n.parse::<u64>() {
+        visitor.visit_u64(n)
+    } else if let Ok(n) = n.parse::<i64>() {
+        visitor.visit_i64(n)
+    } else if let Ok(n) = n.parse::<f64>() {
+        visitor.visit_f64(n)
+    } else {
+        Err(Error::invalid_value(Unexpected::Str(n), &"a number"))
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(value: ValueRef) -> Result<T, Error> {
+    match value.resolve() {
+        ValueRef::Number(n) => n
+            .parse()
+            .map_err(|_| Error::invalid_value(Unexpected::Str(n), &"an integer")),
+        _ => Err(Error::custom("expected a number attribute")),
+    }
+}
+
+impl<'de> de::Deserializer<'de> for ValueRef<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.resolve() {
+            Self::String(s) => visitor.visit_borrowed_str(s),
+            Self::Number(n) => visit_number(n, visitor),
+            Self::Binary(b) => visitor.visit_borrowed_bytes(b),
+            Self::Map(m) => visitor.visit_map(MapRef {
+                iter: m.iter(),
+                value: None,
+            }),
+            Self::Attribute(value) => match value {
+                AttributeValue::Bool(b) => visitor.visit_bool(*b),
+                AttributeValue::Null(_) => visitor.visit_unit(),
+                AttributeValue::L(l) => visitor.visit_seq(SeqRef(l.iter().map(Self::Attribute))),
+                AttributeValue::Ss(ss) => {
+                    visitor.visit_seq(SeqRef(ss.iter().map(|s| Self::String(s))))
+                }
+                AttributeValue::Ns(ns) => {
+                    visitor.visit_seq(SeqRef(ns.iter().map(|n| Self::Number(n))))
+                }
+                AttributeValue::Bs(bs) => {
+                    visitor.visit_seq(SeqRef(bs.iter().map(|b| Self::Binary(b.as_ref()))))
+                }
+                _ => Err(Error::custom("unsupported attribute value")),
+            },
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.resolve() {
+            Self::String(s) | Self::Number(s) => visitor.visit_borrowed_str(s),
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_i128(parse_number(self)?)
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_u128(parse_number(self)?)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        if self.is_null() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        match self.resolve() {
+            Self::String(s) => visitor.visit_enum(BorrowedStrDeserializer::new(s)),
+            Self::Map(m) if m.len() == 1 => {
+                let (variant, value) = m.iter().next().expect("map has exactly one entry");
+                visitor.visit_enum(EnumRef { variant, value })
+            }
+            _ => Err(Error::custom(
+                "expected a string or a map with a single entry for an enum",
+            )),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char bytes byte_buf unit
+        unit_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct SeqRef<I>(I);
+
+impl<'de, I> de::SeqAccess<'de> for SeqRef<I>
+where
+    I: ExactSizeIterator<Item = ValueRef<'de>>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        self.0
+            .next()
+            .map(|value| seed.deserialize(value))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct MapRef<'de> {
+    iter: hash_map::Iter<'de, String, AttributeValue>,
+    value: Option<&'de AttributeValue>,
+}
+
+impl<'de> de::MapAccess<'de> for MapRef<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let Some((key, value)) = self.iter.next() else {
+            return Ok(None);
+        };
+
+        self.value = Some(value);
+        seed.deserialize(BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| Error::custom("value requested before key"))?;
+        seed.deserialize(ValueRef::Attribute(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct EnumRef<'de> {
+    variant: &'de str,
+    value: &'de AttributeValue,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumRef<'de> {
+    type Error = Error;
+    type Variant = VariantRef<'de>;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Self::Error> {
+        let variant = seed.deserialize(BorrowedStrDeserializer::<Error>::new(self.variant))?;
+        Ok((variant, VariantRef(self.value)))
+    }
+}
+
+struct VariantRef<'de>(&'de AttributeValue);
+
+impl<'de> de::VariantAccess<'de> for VariantRef<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Self::Error> {
+        seed.deserialize(ValueRef::Attribute(self.0))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_seq(ValueRef::Attribute(self.0), visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        de::Deserializer::deserialize_map(ValueRef::Attribute(self.0), visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, collections::BTreeSet};
+
+    use aws_sdk_dynamodb::primitives::Blob;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    enum Status {
+        Active,
+        Suspended { reason: String },
+    }
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Document<'a> {
+        id: &'a str,
+        #[serde(borrow)]
+        body: Cow<'a, str>,
+        thumbnail: &'a [u8],
+        version: u32,
+        score: f64,
+        archived: Option<bool>,
+        tags: BTreeSet<&'a str>,
+        history: Vec<i64>,
+        status: Status,
+        previous_status: Status,
+        counter: u128,
+    }
+
+    fn item() -> Item {
+        [
+            ("id", AttributeValue::S("doc-1".into())),
+            ("body", AttributeValue::S("a very long body".into())),
+            ("thumbnail", AttributeValue::B(Blob::new(vec![0x89, 0x50]))),
+            ("version", AttributeValue::N("3".into())),
+            ("score", AttributeValue::N("-1.5".into())),
+            ("archived", AttributeValue::Null(true)),
+            (
+                "tags",
+                AttributeValue::Ss(vec!["draft".into(), "internal".into()]),
+            ),
+            (
+                "history",
+                AttributeValue::L(vec![
+                    AttributeValue::N("1".into()),
+                    AttributeValue::N("-2".into()),
+                ]),
+            ),
+            ("status", AttributeValue::S("Active".into())),
+            (
+                "previous_status",
+                AttributeValue::M(
+                    [(
+                        "Suspended".to_string(),
+                        AttributeValue::M(
+                            [("reason".to_string(), AttributeValue::S("spam".into()))].into(),
+                        ),
+                    )]
+                    .into(),
+                ),
+            ),
+            (
+                "counter",
+                AttributeValue::N("340282366920938463463374607431768211455".into()),
+            ),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect()
+    }
+
+    #[test]
+    fn deserializes_borrowing_from_the_item() {
+        let item = item();
+        let doc: Document = from_item_ref(&item).unwrap();
+
+        assert_eq!(doc.id, "doc-1");
+        assert!(matches!(doc.body, Cow::Borrowed("a very long body")));
+        assert_eq!(doc.thumbnail, &[0x89, 0x50]);
+        assert_eq!(doc.version, 3);
+        assert_eq!(doc.score, -1.5);
+        assert_eq!(doc.archived, None);
+        assert_eq!(doc.tags, BTreeSet::from(["draft", "internal"]));
+        assert_eq!(doc.history, [1, -2]);
+        assert_eq!(doc.status, Status::Active);
+        assert_eq!(
+            doc.previous_status,
+            Status::Suspended {
+                reason: "spam".into()
+            }
+        );
+        assert_eq!(doc.counter, u128::MAX);
+    }
+
+    #[test]
+    fn matches_owned_deserialization() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Owned {
+            id: String,
+            version: u32,
+            score: f64,
+            archived: Option<bool>,
+            history: Vec<i64>,
+            status: Status,
+            previous_status: Status,
+        }
+
+        let item = item();
+        let borrowed: Owned = from_item_ref(&item).unwrap();
+        let owned: Owned = crate::codec::from_item(item).unwrap();
+        assert_eq!(borrowed, owned);
+    }
+
+    #[test]
+    fn rejects_mismatched_types() {
+        #[derive(Debug, serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Mismatched {
+            id: u32,
+        }
+
+        let item = item();
+        assert!(from_item_ref::<Mismatched>(&item).is_err());
+    }
+}
@@ -0,0 +1,231 @@
+//! Listing entities by type through a dedicated global secondary index
+//!
+//! Many single-table designs include a global secondary index keyed by
+//! entity type, so that administrative tools can list all entities of a
+//! type without scanning the table. This module supports that convention
+//! using the [`EntityTypeIndex`][keys::EntityTypeIndex] key.
+//!
+//! To use it, include [`keys::EntityTypeIndex`] in the table's
+//! [`IndexKeys`][crate::Table::IndexKeys] and have each listed entity
+//! return a sort key from
+//! [`entity_type_index_key()`][Entity::entity_type_index_key()]. The index
+//! key attributes are then populated whenever the entity is converted into
+//! an item. Entities that do not opt in are not added to the index.
+//!
+//! Because the entity type is the partition key of the index, every entity
+//! of a type is written to the same index partition. This is well suited to
+//! occasional listing, but a busy entity type may be throttled by the write
+//! throughput limits of a single partition.
+//!
+//! ```no_run
+//! use modyne::{entity_index, keys, Entity, EntityDef, Table};
+//!
+//! struct App;
+//!
+//! impl Table for App {
+//!     type PrimaryKey = keys::Primary;
+//!     type IndexKeys = (keys::Gsi1, keys::EntityTypeIndex);
+//!     fn table_name(&self) -> &str { unimplemented!() }
+//!     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! }
+//!
+//! #[derive(Debug, EntityDef, serde::Serialize, serde::Deserialize)]
+//! struct Customer {
+//!     user_name: String,
+//! }
+//!
+//! impl Entity for Customer {
+//!     type KeyInput<'a> = &'a str;
+//!     type Table = App;
+//!     type IndexKeys = ();
+//!
+//!     fn primary_key(user_name: Self::KeyInput<'_>) -> keys::Primary {
+//!         keys::Primary {
+//!             hash: format!("CUSTOMER#{user_name}"),
+//!             range: format!("CUSTOMER#{user_name}"),
+//!         }
+//!     }
+//!
+//!     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+//!         Self::primary_key(&self.user_name).into()
+//!     }
+//!
+//!     fn entity_type_index_key(&self) -> Option<String> {
+//!         Some(self.user_name.clone())
+//!     }
+//! }
+//!
+//! # async fn example(app: App) -> Result<(), modyne::Error> {
+//! let paginator = entity_index::list_by_entity_type::<Customer>();
+//!
+//! let first = paginator.fetch(&app, 100, None).await?;
+//! for customer in &first.aggregate {
+//!     println!("{}", customer.user_name);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{fmt, marker::PhantomData};
+
+use crate::{expr, keys, pagination::Paginator, Entity, QueryInput};
+
+/// A query for the entities of a type in the [entity type index][keys::EntityTypeIndex]
+pub struct ListByEntityType<E> {
+    entity: PhantomData<fn() -> E>,
+}
+
+impl<E> ListByEntityType<E> {
+    /// Prepares to list the entities of a type
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            entity: PhantomData,
+        }
+    }
+}
+
+impl<E> Default for ListByEntityType<E> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> fmt::Debug for ListByEntityType<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ListByEntityType")
+            .field("entity", &std::any::type_name::<E>())
+            .finish()
+    }
+}
+
+impl<E> QueryInput for ListByEntityType<E>
+where
+    E: Entity + serde::de::DeserializeOwned + 'static,
+{
+    type Index = keys::EntityTypeIndex;
+    type Aggregate = Vec<E>;
+
+    fn key_condition(&self) -> expr::KeyCondition<Self::Index> {
+        expr::KeyCondition::in_partition(E::ENTITY_TYPE.as_str())
+    }
+}
+
+/// Paginates the entities of a type listed in the [entity type index][keys::EntityTypeIndex]
+///
+/// Entities are returned in the order of their
+/// [entity type index keys][Entity::entity_type_index_key()].
+pub fn list_by_entity_type<E>() -> Paginator<ListByEntityType<E>>
+where
+    E: Entity + serde::de::DeserializeOwned + 'static,
+{
+    Paginator::new(ListByEntityType::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EntityDef, EntityExt, EntityTypeNameRef, Table};
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::EntityTypeIndex;
+
+        fn table_name(&self) -> &str {
+            unimplemented!()
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    struct Listed {
+        id: String,
+        listed: bool,
+    }
+
+    impl EntityDef for Listed {
+        const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("listed");
+    }
+
+    impl Entity for Listed {
+        type KeyInput<'a> = &'a str;
+        type Table = TestTable;
+        type IndexKeys = ();
+
+        fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+            keys::Primary {
+                hash: format!("LISTED#{id}"),
+                range: format!("LISTED#{id}"),
+            }
+        }
+
+        fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+            Self::primary_key(&self.id).into()
+        }
+
+        fn entity_type_index_key(&self) -> Option<String> {
+            self.listed.then(|| self.id.clone())
+        }
+    }
+
+    #[test]
+    fn opted_in_entities_populate_the_index_keys() {
+        let item = Listed {
+            id: "1234".to_string(),
+            listed: true,
+        }
+        .into_item();
+        assert_eq!(item["ETPK"].as_s().unwrap(), "listed");
+        assert_eq!(item["ETSK"].as_s().unwrap(), "1234");
+
+        let item = Listed {
+            id: "1234".to_string(),
+            listed: false,
+        }
+        .into_item();
+        assert!(!item.contains_key("ETPK"));
+        assert!(!item.contains_key("ETSK"));
+    }
+
+    #[test]
+    fn index_sync_includes_the_entity_type_index_keys() {
+        let listed = Listed {
+            id: "1234".to_string(),
+            listed: true,
+        };
+        let unlisted = Listed {
+            id: "1234".to_string(),
+            listed: false,
+        };
+
+        let update = expr::IndexSync::new(&listed, &unlisted).into_update();
+        assert_eq!(update.expression, "REMOVE #upd_idx_rem0, #upd_idx_rem1");
+        assert_eq!(
+            update.names,
+            [
+                ("#upd_idx_rem0".to_string(), "ETPK".to_string()),
+                ("#upd_idx_rem1".to_string(), "ETSK".to_string()),
+            ]
+        );
+
+        let update = expr::IndexSync::new(&unlisted, &listed).into_update();
+        assert_eq!(
+            update.expression,
+            "SET #upd_idx_set0 = :upd_idx_set0, #upd_idx_set1 = :upd_idx_set1"
+        );
+    }
+
+    #[test]
+    fn listing_queries_the_entity_type_partition() {
+        let condition = ListByEntityType::<Listed>::new().key_condition();
+        assert_eq!(condition.expression(), "#key_PK = :key_PK");
+        assert!(condition.names().eq([("#key_PK", "ETPK")]));
+        assert_eq!(condition.partition_key().as_s().unwrap(), "listed");
+    }
+}
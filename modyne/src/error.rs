@@ -1,13 +1,16 @@
+use std::fmt;
+
 use aws_sdk_dynamodb::{
-    error::SdkError,
+    error::{ErrorMetadata, ProvideErrorMetadata, SdkError},
     operation::{
+        batch_get_item::BatchGetItemError, batch_write_item::BatchWriteItemError,
         delete_item::DeleteItemError, get_item::GetItemError, put_item::PutItemError,
         query::QueryError, scan::ScanError, transact_get_items::TransactGetItemsError,
         transact_write_items::TransactWriteItemsError, update_item::UpdateItemError,
     },
 };
 
-use crate::EntityTypeNameRef;
+use crate::{blob::BlobError, EntityTypeNameRef, Item, Table};
 
 /// An error that occurred while interacting with DynamoDB
 #[derive(Debug, thiserror::Error)]
@@ -22,7 +25,7 @@ impl Error {
     ///
     /// [AWS]: https://docs.aws.amazon.com/amazondynamodb/latest/developerguide/Expressions.ConditionExpressions.html
     pub fn is_conditional_check_failed_exception(&self) -> bool {
-        match &*self.0 {
+        match self.inner() {
             InnerError::PutItem(SdkError::ServiceError(e)) => {
                 e.err().is_conditional_check_failed_exception()
             }
@@ -50,7 +53,7 @@ impl Error {
     ///
     /// [AWS]: https://docs.aws.amazon.com/amazondynamodb/latest/developerguide/WorkingWithTables.html#ProvisionedThroughput
     pub fn is_provisioned_throughput_exceeded_exception(&self) -> bool {
-        match &*self.0 {
+        match self.inner() {
             InnerError::GetItem(SdkError::ServiceError(e)) => {
                 e.err().is_provisioned_throughput_exceeded_exception()
             }
@@ -95,7 +98,7 @@ impl Error {
     ///
     /// [AWS]: https://docs.aws.amazon.com/amazondynamodb/latest/developerguide/Limits.html
     pub fn is_request_limit_exceeded(&self) -> bool {
-        match &*self.0 {
+        match self.inner() {
             InnerError::GetItem(SdkError::ServiceError(e)) => e.err().is_request_limit_exceeded(),
             InnerError::Query(SdkError::ServiceError(e)) => e.err().is_request_limit_exceeded(),
             InnerError::Scan(SdkError::ServiceError(e)) => e.err().is_request_limit_exceeded(),
@@ -115,6 +118,373 @@ impl Error {
             _ => false,
         }
     }
+
+    /// Returns true if a transaction or batch was executed without any
+    /// operations
+    ///
+    /// Such requests are rejected before being sent to DynamoDB.
+    pub fn is_empty_transaction(&self) -> bool {
+        self.has_source::<EmptyTransactionError>()
+    }
+
+    /// Classifies the error into a category of failure
+    ///
+    /// This allows application code to branch on the kind of failure
+    /// without matching on the error types of each individual operation.
+    /// For transactions that were canceled, the classification is based
+    /// on the reasons given for the cancellation.
+    pub fn kind(&self) -> ErrorKind {
+        if let InnerError::UnprocessedItems(_) = self.inner() {
+            return ErrorKind::Throttled;
+        }
+
+        if self.is_invalid_expression()
+            || self.is_empty_transaction()
+            || self.has_source::<TooManyChunksError>()
+        {
+            return ErrorKind::Validation;
+        }
+
+        if self.is_timeout() {
+            return ErrorKind::Timeout;
+        }
+
+        let Some(meta) = self.service_error_metadata() else {
+            return ErrorKind::Other;
+        };
+
+        classify(meta.code(), meta.message(), &self.cancellation_reasons())
+    }
+
+    /// The error code returned by DynamoDB, if the error was returned by the service
+    pub fn code(&self) -> Option<&str> {
+        self.service_error_metadata()?.code()
+    }
+
+    /// The error message returned by DynamoDB, if the error was returned by the service
+    pub fn message(&self) -> Option<&str> {
+        self.service_error_metadata()?.message()
+    }
+
+    /// Returns the underlying SDK error, if the error was produced by an
+    /// operation with the given error type
+    ///
+    /// ```
+    /// use aws_sdk_dynamodb::operation::put_item::PutItemError;
+    ///
+    /// fn is_put_error(error: &modyne::Error) -> bool {
+    ///     error.sdk_error::<PutItemError>().is_some()
+    /// }
+    /// ```
+    pub fn sdk_error<E>(&self) -> Option<&SdkError<E>>
+    where
+        E: std::error::Error + 'static,
+    {
+        std::error::Error::source(self.inner())?.downcast_ref()
+    }
+
+    /// Details of the operation that failed, if known
+    ///
+    /// Context is recorded for errors produced by operations that this crate
+    /// executes on behalf of the caller, such as batch writes retried until
+    /// all items are processed, or entity-level operations like
+    /// read-modify-write. The `execute` methods of single operations, such
+    /// as [`Put`][crate::model::Put], [`Update`][crate::model::Update],
+    /// [`Delete`][crate::model::Delete], and
+    /// [`TransactWrite`][crate::model::TransactWrite], return the SDK error
+    /// unchanged, so errors converted from their output carry no context.
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match &*self.0 {
+            InnerError::Context(e) => Some(&e.context),
+            _ => None,
+        }
+    }
+
+    /// Attaches context to the error, unless it already has context
+    pub(crate) fn with_context(self, context: ErrorContext) -> Self {
+        if let InnerError::Context(_) = &*self.0 {
+            return self;
+        }
+
+        ContextError {
+            context,
+            inner: self.0,
+        }
+        .into()
+    }
+
+    /// In debug builds, explains a failure to deserialize an item read
+    /// through a secondary index because of a missing attribute
+    ///
+    /// Items in an index with a `KEYS_ONLY` or `INCLUDE` projection lack
+    /// the attributes that are not projected into the index. Rather than
+    /// surfacing the generic deserialization error, the error names the
+    /// index and the missing attribute, so that the index's projection or
+    /// the projection read through it can be corrected.
+    pub(crate) fn with_index_projection(self, index_name: Option<&'static str>) -> Self {
+        let Some(index_name) = index_name else {
+            return self;
+        };
+
+        if !cfg!(debug_assertions) {
+            return self;
+        }
+
+        match *self.0 {
+            InnerError::ItemDeserialization(error) => match missing_field(&error.source) {
+                Some(attribute) => UnprojectedAttributeError {
+                    index_name,
+                    attribute,
+                    source: error,
+                }
+                .into(),
+                None => error.into(),
+            },
+            inner => Self(Box::new(inner)),
+        }
+    }
+
+    /// The underlying error, without any context
+    fn inner(&self) -> &InnerError {
+        match &*self.0 {
+            InnerError::Context(e) => &e.inner,
+            inner => inner,
+        }
+    }
+
+    /// Whether the error, or the failure to construct a request, was caused
+    /// by an expression that did not pass validation
+    fn is_invalid_expression(&self) -> bool {
+        self.has_source::<InvalidExpressionError>()
+    }
+
+    /// Whether an error of the given type appears in the source chain
+    fn has_source<E: std::error::Error + 'static>(&self) -> bool {
+        let mut source = std::error::Error::source(self.inner());
+        while let Some(error) = source {
+            if error.is::<E>() {
+                return true;
+            }
+            source = error.source();
+        }
+        false
+    }
+
+    /// Whether the operation timed out, either because of a timeout set on
+    /// the operation or one configured on the client
+    fn is_timeout(&self) -> bool {
+        matches!(
+            self.inner(),
+            InnerError::GetItem(SdkError::TimeoutError(_))
+                | InnerError::Query(SdkError::TimeoutError(_))
+                | InnerError::Scan(SdkError::TimeoutError(_))
+                | InnerError::PutItem(SdkError::TimeoutError(_))
+                | InnerError::DeleteItem(SdkError::TimeoutError(_))
+                | InnerError::UpdateItem(SdkError::TimeoutError(_))
+                | InnerError::BatchGetItem(SdkError::TimeoutError(_))
+                | InnerError::BatchWriteItem(SdkError::TimeoutError(_))
+                | InnerError::TransactGetItems(SdkError::TimeoutError(_))
+                | InnerError::TransactWriteItems(SdkError::TimeoutError(_))
+        )
+    }
+
+    fn service_error_metadata(&self) -> Option<&ErrorMetadata> {
+        let meta = match self.inner() {
+            InnerError::GetItem(SdkError::ServiceError(e)) => e.err().meta(),
+            InnerError::Query(SdkError::ServiceError(e)) => e.err().meta(),
+            InnerError::Scan(SdkError::ServiceError(e)) => e.err().meta(),
+            InnerError::PutItem(SdkError::ServiceError(e)) => e.err().meta(),
+            InnerError::DeleteItem(SdkError::ServiceError(e)) => e.err().meta(),
+            InnerError::UpdateItem(SdkError::ServiceError(e)) => e.err().meta(),
+            InnerError::BatchGetItem(SdkError::ServiceError(e)) => e.err().meta(),
+            InnerError::BatchWriteItem(SdkError::ServiceError(e)) => e.err().meta(),
+            InnerError::TransactGetItems(SdkError::ServiceError(e)) => e.err().meta(),
+            InnerError::TransactWriteItems(SdkError::ServiceError(e)) => e.err().meta(),
+            _ => return None,
+        };
+        Some(meta)
+    }
+
+    fn cancellation_reasons(&self) -> Vec<&str> {
+        let reasons = match self.inner() {
+            InnerError::TransactGetItems(SdkError::ServiceError(e)) => match e.err() {
+                TransactGetItemsError::TransactionCanceledException(e) => &e.cancellation_reasons,
+                _ => return Vec::new(),
+            },
+            InnerError::TransactWriteItems(SdkError::ServiceError(e)) => match e.err() {
+                TransactWriteItemsError::TransactionCanceledException(e) => &e.cancellation_reasons,
+                _ => return Vec::new(),
+            },
+            _ => return Vec::new(),
+        };
+
+        reasons
+            .iter()
+            .flatten()
+            .filter_map(|r| r.code.as_deref())
+            .collect()
+    }
+}
+
+/// Details of the operation that produced an [`Error`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ErrorContext {
+    operation: &'static str,
+    table_name: String,
+    entity_type: Option<&'static EntityTypeNameRef>,
+    key: Option<String>,
+}
+
+impl ErrorContext {
+    pub(crate) fn new<T: Table>(table: &T, operation: &'static str) -> Self {
+        Self {
+            operation,
+            table_name: table.table_name().to_string(),
+            entity_type: None,
+            key: None,
+        }
+    }
+
+    pub(crate) fn with_entity_type(mut self, entity_type: &'static EntityTypeNameRef) -> Self {
+        self.entity_type = Some(entity_type);
+        self
+    }
+
+    pub(crate) fn with_key<T: Table>(mut self, key: &Item) -> Self {
+        self.key = Some(T::redact_key(key));
+        self
+    }
+
+    /// The name of the DynamoDB operation, such as `PutItem`
+    #[inline]
+    pub fn operation(&self) -> &'static str {
+        self.operation
+    }
+
+    /// The name of the table operated on
+    #[inline]
+    pub fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    /// The type of the entity operated on, if known
+    #[inline]
+    pub fn entity_type(&self) -> Option<&'static EntityTypeNameRef> {
+        self.entity_type
+    }
+
+    /// The primary key of the item involved, if known
+    ///
+    /// The key is formatted with [`Table::redact_key()`].
+    #[inline]
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} on table `{}`", self.operation, self.table_name)?;
+        if let Some(entity_type) = self.entity_type {
+            write!(f, " for entity type `{entity_type}`")?;
+        }
+        if let Some(key) = &self.key {
+            write!(f, " with key {key}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A normalized category of failure for an [`Error`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// A condition expression evaluated to false
+    ConditionFailed,
+
+    /// The request was throttled due to exceeding provisioned throughput,
+    /// an account request limit, or because items were left unprocessed
+    Throttled,
+
+    /// A transaction conflicted with another ongoing operation on the same items
+    TransactionConflict,
+
+    /// An item, or an item collection, exceeded the maximum allowed size
+    ItemTooLarge,
+
+    /// The request was rejected as invalid
+    Validation,
+
+    /// The request did not complete before its timeout elapsed
+    Timeout,
+
+    /// Any other error, including errors that did not originate from DynamoDB
+    Other,
+}
+
+/// Whether a write transaction failed only because it conflicted with
+/// another request, such that it may succeed if retried
+pub(crate) fn is_transaction_conflict(error: &SdkError<TransactWriteItemsError>) -> bool {
+    let Some(error) = error.as_service_error() else {
+        return false;
+    };
+
+    let reasons: Vec<_> = match error {
+        TransactWriteItemsError::TransactionCanceledException(e) => e
+            .cancellation_reasons
+            .iter()
+            .flatten()
+            .filter_map(|r| r.code.as_deref())
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    classify(error.code(), error.message(), &reasons) == ErrorKind::TransactionConflict
+}
+
+fn classify(code: Option<&str>, message: Option<&str>, reasons: &[&str]) -> ErrorKind {
+    let has_reason = |reason: &str| reasons.contains(&reason);
+
+    if code == Some("ConditionalCheckFailedException") || has_reason("ConditionalCheckFailed") {
+        return ErrorKind::ConditionFailed;
+    }
+
+    if matches!(
+        code,
+        Some("TransactionConflictException" | "TransactionInProgressException")
+    ) || has_reason("TransactionConflict")
+    {
+        return ErrorKind::TransactionConflict;
+    }
+
+    if matches!(
+        code,
+        Some(
+            "ProvisionedThroughputExceededException"
+                | "RequestLimitExceeded"
+                | "ThrottlingException"
+        )
+    ) || has_reason("ProvisionedThroughputExceeded")
+        || has_reason("ThrottlingError")
+    {
+        return ErrorKind::Throttled;
+    }
+
+    let too_large = |message: Option<&str>| {
+        message.is_some_and(|m| m.contains("size has exceeded the maximum allowed size"))
+    };
+    if code == Some("ItemCollectionSizeLimitExceededException")
+        || has_reason("ItemCollectionSizeLimitExceeded")
+        || (code == Some("ValidationException") && too_large(message))
+    {
+        return ErrorKind::ItemTooLarge;
+    }
+
+    if code == Some("ValidationException") || has_reason("ValidationError") {
+        return ErrorKind::Validation;
+    }
+
+    ErrorKind::Other
 }
 
 impl<T> From<T> for Error
@@ -135,11 +505,52 @@ pub(crate) enum InnerError {
     PutItem(#[from] SdkError<PutItemError>),
     DeleteItem(#[from] SdkError<DeleteItemError>),
     UpdateItem(#[from] SdkError<UpdateItemError>),
+    BatchGetItem(#[from] SdkError<BatchGetItemError>),
+    BatchWriteItem(#[from] SdkError<BatchWriteItemError>),
     TransactGetItems(#[from] SdkError<TransactGetItemsError>),
     TransactWriteItems(#[from] SdkError<TransactWriteItemsError>),
     ItemDeserialization(#[from] ItemDeserializationError),
+    UnprojectedAttribute(#[from] UnprojectedAttributeError),
     MissingEntityType(#[from] MissingEntityTypeError),
+    UnexpectedEntityType(#[from] UnexpectedEntityTypeError),
     MalformedEntityType(#[from] MalformedEntityTypeError),
+    Blob(#[from] BlobError),
+    Io(#[from] std::io::Error),
+    Json(#[from] serde_json::Error),
+    MalformedChunk(#[from] MalformedChunkError),
+    TooManyChunks(#[from] TooManyChunksError),
+    InvalidRecord(#[from] InvalidRecordError),
+    UnprocessedItems(#[from] UnprocessedItemsError),
+    DuplicateEntityType(#[from] DuplicateEntityTypeError),
+    FixtureConsistency(#[from] FixtureConsistencyError),
+    ReadAfterWrite(#[from] ReadAfterWriteError),
+    InvalidSaga(#[from] InvalidSagaError),
+    InvalidExpression(#[from] InvalidExpressionError),
+    InvalidCursor(#[from] InvalidCursorError),
+    InvalidSchemaVersion(#[from] InvalidSchemaVersionError),
+    Context(#[from] ContextError),
+}
+
+/// An error annotated with the operation that produced it
+///
+/// The source of this error is the source of the annotated error, so that
+/// the context appears once in the chain of sources.
+#[derive(Debug)]
+pub(crate) struct ContextError {
+    context: ErrorContext,
+    inner: Box<InnerError>,
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed", self.context)
+    }
+}
+
+impl std::error::Error for ContextError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        std::error::Error::source(&*self.inner)
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -162,11 +573,150 @@ impl ItemDeserializationError {
     }
 }
 
+/// Extracts the name of the missing field from a deserialization error
+fn missing_field(error: &serde_dynamo::Error) -> Option<String> {
+    let message = error.to_string();
+    let field = message.strip_prefix("missing field `")?.split_once('`')?.0;
+    Some(field.to_string())
+}
+
+/// An item read through a secondary index was missing an attribute that
+/// may not be projected into the index
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "item read through index `{index_name}` is missing attribute `{attribute}`; if the index \
+     has a `KEYS_ONLY` or `INCLUDE` projection, add the attribute to the projection of the \
+     index or read a projection that does not require it"
+)]
+pub(crate) struct UnprojectedAttributeError {
+    index_name: &'static str,
+    attribute: String,
+    source: ItemDeserializationError,
+}
+
+/// An item holding a chunk of an entity was missing required attributes
+#[derive(Debug, thiserror::Error)]
+#[error("malformed entity chunk: {reason}")]
+pub(crate) struct MalformedChunkError {
+    reason: &'static str,
+}
+
+impl MalformedChunkError {
+    #[inline]
+    pub(crate) fn new(reason: &'static str) -> Self {
+        Self { reason }
+    }
+}
+
+/// An entity was too large to be written in a single transaction of chunks
+#[derive(Debug, thiserror::Error)]
+#[error("entity of type `{entity_type}` spans {count} chunks, but at most {max} can be written in a transaction")]
+pub(crate) struct TooManyChunksError {
+    pub(crate) entity_type: &'static EntityTypeNameRef,
+    pub(crate) count: usize,
+    pub(crate) max: usize,
+}
+
+/// A record being imported was invalid
+#[derive(Debug, thiserror::Error)]
+#[error("invalid record on line {line}: {reason}")]
+pub(crate) struct InvalidRecordError {
+    pub(crate) line: u64,
+    pub(crate) reason: String,
+}
+
+/// Items remained unprocessed by a batch write after exhausting all attempts
+#[derive(Debug, thiserror::Error)]
+#[error("{count} items remained unprocessed after {attempts} attempts")]
+pub(crate) struct UnprocessedItemsError {
+    pub(crate) count: usize,
+    pub(crate) attempts: u32,
+}
+
+/// Two entities registered with a table share an entity type name
+#[derive(Debug, thiserror::Error)]
+#[error("entity type `{entity_type}` is claimed by both `{first}` and `{second}`")]
+pub(crate) struct DuplicateEntityTypeError {
+    pub(crate) entity_type: &'static EntityTypeNameRef,
+    pub(crate) first: &'static str,
+    pub(crate) second: &'static str,
+}
+
+/// Fixture items did not become visible in a secondary index before the timeout
+#[derive(Debug, thiserror::Error)]
+#[error("{pending} fixture items were not visible in index `{index}` after {timeout:?}")]
+pub(crate) struct FixtureConsistencyError {
+    pub(crate) index: &'static str,
+    pub(crate) pending: usize,
+    pub(crate) timeout: std::time::Duration,
+}
+
+/// A recently written item was not visible after exhausting all read attempts
+#[derive(Debug, thiserror::Error)]
+#[error("expected item was not visible after {attempts} attempts")]
+pub(crate) struct ReadAfterWriteError {
+    pub(crate) attempts: u32,
+}
+
+/// A saga cannot be executed or compensated in its persisted state
+#[derive(Debug, thiserror::Error)]
+#[error("saga `{id}` {reason}")]
+pub(crate) struct InvalidSagaError {
+    pub(crate) id: String,
+    pub(crate) reason: String,
+}
+
+/// An expression exceeded a limit enforced by DynamoDB, was malformed, or
+/// referred to an undefined or unused placeholder
+#[derive(Debug, thiserror::Error)]
+#[error("invalid {kind} expression: {reason}")]
+pub(crate) struct InvalidExpressionError {
+    pub(crate) kind: &'static str,
+    pub(crate) reason: String,
+}
+
+/// A transaction or batch was executed without any operations
+#[derive(Debug, thiserror::Error)]
+#[error("{operation} requires at least one operation")]
+pub(crate) struct EmptyTransactionError {
+    pub(crate) operation: &'static str,
+}
+
+/// An encoded pagination cursor could not be decoded
+#[derive(Debug, thiserror::Error)]
+#[error("invalid cursor: {reason}")]
+pub(crate) struct InvalidCursorError {
+    pub(crate) reason: &'static str,
+}
+
+/// The schema version attribute of an item could not be read
+#[derive(Debug, thiserror::Error)]
+#[error("invalid schema version for entity type `{entity_type}`: {reason}")]
+pub(crate) struct InvalidSchemaVersionError {
+    pub(crate) entity_type: &'static EntityTypeNameRef,
+    pub(crate) reason: &'static str,
+}
+
+/// An operation did not complete before its timeout elapsed
+#[derive(Debug, thiserror::Error)]
+#[error("operation timed out after {timeout:?}")]
+pub(crate) struct OperationTimeoutError {
+    pub(crate) timeout: std::time::Duration,
+}
+
 /// The entity type attribute was not found on the item
 #[derive(Debug, thiserror::Error)]
 #[error("entity type attribute is missing from the item")]
 pub(crate) struct MissingEntityTypeError {}
 
+/// An item was found where an entity of another type was expected
+#[derive(Debug, thiserror::Error)]
+#[error("expected an item of entity type `{expected}`, found `{found}`")]
+pub(crate) struct UnexpectedEntityTypeError {
+    pub(crate) expected: &'static EntityTypeNameRef,
+    pub(crate) found: String,
+}
+
 /// The entity type attribute was found, but was malformed and could not be extracted
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
@@ -179,3 +729,196 @@ pub enum MalformedEntityTypeError {
     #[error("entity type attribute value is malformed and could not be extracted from the item")]
     Custom(#[from] Box<dyn std::error::Error + Send + Sync>),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_error_codes() {
+        let cases = [
+            (
+                "ConditionalCheckFailedException",
+                ErrorKind::ConditionFailed,
+            ),
+            (
+                "TransactionConflictException",
+                ErrorKind::TransactionConflict,
+            ),
+            (
+                "ProvisionedThroughputExceededException",
+                ErrorKind::Throttled,
+            ),
+            ("RequestLimitExceeded", ErrorKind::Throttled),
+            (
+                "ItemCollectionSizeLimitExceededException",
+                ErrorKind::ItemTooLarge,
+            ),
+            ("ValidationException", ErrorKind::Validation),
+            ("ResourceNotFoundException", ErrorKind::Other),
+        ];
+
+        for (code, kind) in cases {
+            assert_eq!(classify(Some(code), None, &[]), kind, "{code}");
+        }
+    }
+
+    #[test]
+    fn classifies_oversized_items() {
+        let message = "Item size has exceeded the maximum allowed size";
+        assert_eq!(
+            classify(Some("ValidationException"), Some(message), &[]),
+            ErrorKind::ItemTooLarge
+        );
+    }
+
+    #[test]
+    fn classifies_transaction_cancellation_reasons() {
+        let code = Some("TransactionCanceledException");
+        assert_eq!(
+            classify(code, None, &["None", "ConditionalCheckFailed"]),
+            ErrorKind::ConditionFailed
+        );
+        assert_eq!(
+            classify(code, None, &["TransactionConflict", "None"]),
+            ErrorKind::TransactionConflict
+        );
+        assert_eq!(
+            classify(code, None, &["ThrottlingError"]),
+            ErrorKind::Throttled
+        );
+        assert_eq!(classify(code, None, &["None"]), ErrorKind::Other);
+    }
+
+    #[test]
+    fn invalid_expressions_are_validation_errors() {
+        let invalid = || InvalidExpressionError {
+            kind: "update",
+            reason: "attribute value `:upd_x` is not defined".into(),
+        };
+        assert_eq!(Error::from(invalid()).kind(), ErrorKind::Validation);
+
+        let sdk_error = SdkError::<PutItemError>::construction_failure(invalid());
+        let error = Error::from(sdk_error);
+        assert_eq!(error.kind(), ErrorKind::Validation);
+        assert!(error.code().is_none());
+    }
+
+    #[test]
+    fn empty_transactions_are_validation_errors() {
+        let sdk_error =
+            SdkError::<TransactWriteItemsError>::construction_failure(EmptyTransactionError {
+                operation: "TransactWriteItems",
+            });
+        let error = Error::from(sdk_error);
+        assert!(error.is_empty_transaction());
+        assert_eq!(error.kind(), ErrorKind::Validation);
+
+        let other = Error::from(InvalidExpressionError {
+            kind: "update",
+            reason: "attribute value `:upd_x` is not defined".into(),
+        });
+        assert!(!other.is_empty_transaction());
+    }
+
+    #[test]
+    fn timeouts_are_classified() {
+        let error = Error::from(SdkError::<GetItemError>::timeout_error(
+            OperationTimeoutError {
+                timeout: std::time::Duration::from_millis(250),
+            },
+        ));
+        assert_eq!(error.kind(), ErrorKind::Timeout);
+        assert!(error.code().is_none());
+    }
+
+    #[test]
+    fn context_is_reported_once_in_the_source_chain() {
+        struct TestTable;
+
+        impl Table for TestTable {
+            type PrimaryKey = crate::keys::Primary;
+            type IndexKeys = crate::keys::Gsi1;
+
+            fn table_name(&self) -> &str {
+                "TestTable"
+            }
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn redact_key(key: &Item) -> String {
+                crate::instrumentation::redacted_key(key)
+            }
+        }
+
+        let key: Item = [(
+            "PK".to_string(),
+            crate::AttributeValue::S("CUSTOMER#alex".into()),
+        )]
+        .into();
+        let context = ErrorContext::new(&TestTable, "BatchWriteItem")
+            .with_entity_type(EntityTypeNameRef::from_static("customer"))
+            .with_key::<TestTable>(&key);
+        let error = Error::from(UnprocessedItemsError {
+            count: 1,
+            attempts: 2,
+        })
+        .with_context(context.clone())
+        .with_context(ErrorContext::new(&TestTable, "PutItem"));
+
+        assert_eq!(error.context(), Some(&context));
+        assert_eq!(error.kind(), ErrorKind::Throttled);
+
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(
+            source.to_string(),
+            "BatchWriteItem on table `TestTable` for entity type `customer` with key \
+             {\"PK\": \"CUSTOMER#<redacted>\"} failed"
+        );
+        let cause = source.source().unwrap();
+        assert_eq!(
+            cause.to_string(),
+            "1 items remained unprocessed after 2 attempts"
+        );
+        assert!(cause.source().is_none());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn missing_attributes_of_index_items_name_the_index() {
+        #[derive(Debug, serde::Deserialize)]
+        struct Customer {
+            _email: String,
+        }
+
+        let deserialization_error = || {
+            let source = serde_dynamo::from_item::<_, Customer>(Item::new()).unwrap_err();
+            Error::from(ItemDeserializationError::new(
+                EntityTypeNameRef::from_static("customer"),
+                source,
+            ))
+        };
+
+        let error = deserialization_error().with_index_projection(Some("GSI1"));
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(
+            source.to_string(),
+            "item read through index `GSI1` is missing attribute `_email`; if the index has a \
+             `KEYS_ONLY` or `INCLUDE` projection, add the attribute to the projection of the \
+             index or read a projection that does not require it"
+        );
+        assert_eq!(
+            source.source().unwrap().to_string(),
+            "failed to deserialize item of type `customer`"
+        );
+
+        let error = deserialization_error().with_index_projection(None);
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(
+            source.to_string(),
+            "failed to deserialize item of type `customer`"
+        );
+    }
+}
@@ -0,0 +1,400 @@
+//! Exporting table contents as JSON Lines
+//!
+//! An [`Export`] scans a table using parallel segments and writes each item
+//! as a line of JSON, either as plain JSON suitable for deserializing with
+//! `serde`, or as DynamoDB JSON, which preserves the type of each attribute
+//! and matches the format used by DynamoDB's export to Amazon S3. Exports in
+//! DynamoDB JSON can be loaded back into a table, such as when seeding a test
+//! environment created with [`TestTableExt`][crate::TestTableExt].
+//!
+//! ```no_run
+//! # use modyne::{keys, EntityTypeNameRef, Table};
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! use modyne::export::{Export, ExportFormat};
+//!
+//! # async fn example(app: App) -> Result<(), modyne::Error> {
+//! let file = std::fs::File::create("orders.jsonl")?;
+//! let report = Export::new(&app)
+//!     .format(ExportFormat::DynamoDb)
+//!     .entity_type(EntityTypeNameRef::from_static("order"))
+//!     .segments(4)
+//!     .execute(std::io::BufWriter::new(file))
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! # Checkpoints
+//!
+//! Long-running exports can be resumed. After each page of results has been
+//! written and flushed, the checkpoint callback receives an
+//! [`ExportCheckpoint`] recording the progress of every segment. The
+//! checkpoint can be serialized and later passed to [`Export::resume_from`]
+//! to continue where the export left off. Because a page may be written
+//! before its checkpoint is persisted, a resumed export may repeat some of
+//! the items from the last page written, so consumers should tolerate
+//! duplicate lines.
+
+use std::{
+    collections::HashSet,
+    fmt,
+    io::Write,
+    sync::{Mutex, PoisonError},
+};
+
+use crate::{
+    json, keys,
+    model::{Scan, ScanSegment},
+    EntityTypeName, EntityTypeNameRef, Error, Item, Table,
+};
+
+/// The format of each exported line
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Plain JSON, with attribute values converted to their natural JSON
+    /// representations
+    ///
+    /// Binary values are encoded as base64 strings, and sets as arrays.
+    #[default]
+    Plain,
+
+    /// DynamoDB JSON, with each item wrapped in an `Item` object and each
+    /// attribute value tagged with its type
+    DynamoDb,
+}
+
+type CheckpointCallback<'a> = Box<dyn FnMut(&ExportCheckpoint) + Send + 'a>;
+
+/// An export of the items in a table
+#[must_use]
+pub struct Export<'a, T> {
+    table: &'a T,
+    format: ExportFormat,
+    segments: u32,
+    page_size: Option<u32>,
+    entity_types: Option<HashSet<EntityTypeName>>,
+    checkpoint: Option<ExportCheckpoint>,
+    on_checkpoint: Option<CheckpointCallback<'a>>,
+}
+
+impl<'a, T> fmt::Debug for Export<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Export")
+            .field("format", &self.format)
+            .field("segments", &self.segments)
+            .field("page_size", &self.page_size)
+            .field("entity_types", &self.entity_types)
+            .field("checkpoint", &self.checkpoint)
+            .field("on_checkpoint", &self.on_checkpoint.is_some())
+            .finish()
+    }
+}
+
+impl<'a, T> Export<'a, T>
+where
+    T: Table,
+    T::PrimaryKey: keys::Key,
+{
+    /// Prepares an export of all items in the table as plain JSON
+    pub fn new(table: &'a T) -> Self {
+        Self {
+            table,
+            format: ExportFormat::default(),
+            segments: 1,
+            page_size: None,
+            entity_types: None,
+            checkpoint: None,
+            on_checkpoint: None,
+        }
+    }
+
+    /// Sets the format of each exported line
+    pub fn format(mut self, format: ExportFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Scans the table using the given number of parallel segments
+    ///
+    /// This setting is ignored when resuming from a checkpoint, which
+    /// records the number of segments in use.
+    pub fn segments(mut self, segments: u32) -> Self {
+        self.segments = segments.max(1);
+        self
+    }
+
+    /// Sets the maximum number of items to evaluate in each scan request
+    pub fn page_size(mut self, page_size: u32) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /// Restricts the export to items of the given entity type
+    ///
+    /// May be called multiple times to export several entity types. Items
+    /// of other types are skipped, but still consume read capacity.
+    pub fn entity_type(mut self, entity_type: &EntityTypeNameRef) -> Self {
+        self.entity_types
+            .get_or_insert_with(HashSet::new)
+            .insert(entity_type.to_owned());
+        self
+    }
+
+    /// Resumes a previous export from a checkpoint
+    pub fn resume_from(mut self, checkpoint: ExportCheckpoint) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// Registers a callback to receive a checkpoint after each page is written
+    pub fn on_checkpoint(mut self, callback: impl FnMut(&ExportCheckpoint) + Send + 'a) -> Self {
+        self.on_checkpoint = Some(Box::new(callback));
+        self
+    }
+
+    /// Executes the export, writing one line per item to the writer
+    pub async fn execute<W: Write + Send>(self, writer: W) -> Result<ExportReport, Error> {
+        let checkpoint = self.checkpoint.clone().unwrap_or_else(|| ExportCheckpoint {
+            segments: vec![SegmentCheckpoint::default(); self.segments as usize],
+        });
+        let total_segments = i32::try_from(checkpoint.segments.len()).unwrap_or(i32::MAX);
+
+        let pending: Vec<_> = checkpoint
+            .segments
+            .iter()
+            .enumerate()
+            .filter(|(_, segment)| !segment.complete)
+            .map(|(index, segment)| (index, segment.start_key.clone()))
+            .collect();
+
+        let output = Mutex::new(Output {
+            writer,
+            checkpoint,
+            on_checkpoint: self.on_checkpoint,
+        });
+
+        let format = self.format;
+        let page_size = self.page_size;
+        let entity_types = self.entity_types.as_ref();
+        let table = self.table;
+        let segments = pending.into_iter().map(|(index, start_key)| {
+            let output = &output;
+            async move {
+                let mut report = ExportReport::default();
+                let mut start_key = start_key.map(json::from_dynamodb_json).transpose()?;
+
+                loop {
+                    let scan = Scan::<T::PrimaryKey>::new()
+                        .segment(ScanSegment {
+                            segment: index as i32,
+                            total_segments,
+                        })
+                        .set_limit(page_size)
+                        .set_exclusive_start_key(start_key);
+
+                    let page = scan.execute(table).await?;
+                    let items = page.items.unwrap_or_default();
+                    report.scanned += items.len() as u64;
+
+                    let mut lines = Vec::new();
+                    for item in items {
+                        if !is_exported::<T>(&item, entity_types) {
+                            continue;
+                        }
+
+                        write_line(&mut lines, &item, format)?;
+                        report.exported += 1;
+                    }
+
+                    start_key = page.last_evaluated_key;
+                    output
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .commit(index, &lines, start_key.as_ref())?;
+
+                    if start_key.is_none() {
+                        break;
+                    }
+                }
+
+                Ok::<_, Error>(report)
+            }
+        });
+
+        let reports = futures_util::future::try_join_all(segments).await?;
+        Ok(reports
+            .into_iter()
+            .fold(ExportReport::default(), ExportReport::merge))
+    }
+}
+
+struct Output<'a, W> {
+    writer: W,
+    checkpoint: ExportCheckpoint,
+    on_checkpoint: Option<CheckpointCallback<'a>>,
+}
+
+impl<'a, W: Write> Output<'a, W> {
+    /// Writes a page of lines, then records the segment's progress
+    fn commit(&mut self, segment: usize, lines: &[u8], next: Option<&Item>) -> Result<(), Error> {
+        let start_key = next.map(json::to_dynamodb_json).transpose()?;
+        self.writer.write_all(lines)?;
+        self.writer.flush()?;
+
+        let progress = &mut self.checkpoint.segments[segment];
+        progress.start_key = start_key;
+        progress.complete = next.is_none();
+
+        if let Some(on_checkpoint) = &mut self.on_checkpoint {
+            on_checkpoint(&self.checkpoint);
+        }
+
+        Ok(())
+    }
+}
+
+fn is_exported<T: Table>(item: &Item, entity_types: Option<&HashSet<EntityTypeName>>) -> bool {
+    let Some(entity_types) = entity_types else {
+        return true;
+    };
+
+    crate::__private::get_table_entity_type::<T>(item)
+        .is_ok_and(|entity_type| entity_types.contains(entity_type))
+}
+
+fn write_line(out: &mut Vec<u8>, item: &Item, format: ExportFormat) -> Result<(), Error> {
+    match format {
+        ExportFormat::Plain => serde_json::to_writer(&mut *out, &json::to_plain_json(item)?)?,
+        ExportFormat::DynamoDb => serde_json::to_writer(
+            &mut *out,
+            &serde_json::json!({ "Item": json::to_dynamodb_json(item)? }),
+        )?,
+    }
+    out.push(b'\n');
+    Ok(())
+}
+
+/// The progress of an [`Export`], from which it can be resumed
+///
+/// The checkpoint can be serialized to persist progress between runs.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ExportCheckpoint {
+    segments: Vec<SegmentCheckpoint>,
+}
+
+impl ExportCheckpoint {
+    /// Returns true if every segment of the export has completed
+    pub fn is_complete(&self) -> bool {
+        self.segments.iter().all(|segment| segment.complete)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+struct SegmentCheckpoint {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    start_key: Option<serde_json::Map<String, serde_json::Value>>,
+    complete: bool,
+}
+
+/// A summary of the items processed by an [`Export`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExportReport {
+    /// The number of items scanned
+    pub scanned: u64,
+
+    /// The number of items written
+    pub exported: u64,
+}
+
+impl ExportReport {
+    fn merge(self, other: Self) -> Self {
+        Self {
+            scanned: self.scanned + other.scanned,
+            exported: self.exported + other.exported,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{keys, AttributeValue};
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            "TestTable"
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    fn item(entity_type: &str) -> Item {
+        [
+            ("PK".to_string(), AttributeValue::S("A".into())),
+            (
+                "entity_type".to_string(),
+                AttributeValue::S(entity_type.into()),
+            ),
+        ]
+        .into()
+    }
+
+    #[test]
+    fn writes_dynamodb_json_lines() {
+        let mut out = Vec::new();
+        write_line(&mut out, &item("order"), ExportFormat::DynamoDb).unwrap();
+        let line: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(line["Item"]["PK"]["S"], "A");
+        assert_eq!(out.last(), Some(&b'\n'));
+    }
+
+    #[test]
+    fn filters_by_entity_type() {
+        let entity_types = HashSet::from([EntityTypeName::from_static("order")]);
+        assert!(is_exported::<TestTable>(
+            &item("order"),
+            Some(&entity_types)
+        ));
+        assert!(!is_exported::<TestTable>(
+            &item("user"),
+            Some(&entity_types)
+        ));
+        assert!(is_exported::<TestTable>(&item("user"), None));
+    }
+
+    #[test]
+    fn commit_records_segment_progress() {
+        let mut checkpoints = Vec::new();
+        let mut output = Output {
+            writer: Vec::new(),
+            checkpoint: ExportCheckpoint {
+                segments: vec![SegmentCheckpoint::default(); 2],
+            },
+            on_checkpoint: Some(Box::new(|c: &ExportCheckpoint| checkpoints.push(c.clone()))),
+        };
+
+        output.commit(0, b"{}\n", Some(&item("order"))).unwrap();
+        output.commit(1, b"", None).unwrap();
+        output.commit(0, b"{}\n", None).unwrap();
+        drop(output);
+
+        assert_eq!(checkpoints.len(), 3);
+        assert!(checkpoints[0].segments[0].start_key.is_some());
+        assert!(!checkpoints[1].is_complete());
+        assert!(checkpoints[2].is_complete());
+    }
+}
@@ -5,7 +5,7 @@ use std::{fmt, marker::PhantomData};
 use aws_sdk_dynamodb::types::AttributeValue;
 use fnv::FnvHashSet;
 
-use crate::keys;
+use crate::{error::InvalidExpressionError, keys, Entity, Item};
 
 /// A builder for a key condition expression, used in query operations
 #[must_use]
@@ -63,6 +63,15 @@ where
         }
     }
 
+    /// Get items in the partition with the given key value
+    pub(crate) fn in_partition_value(partition_key: AttributeValue) -> Self {
+        KeyCondition {
+            partition_key,
+            sort_key: None,
+            key_type: PhantomData,
+        }
+    }
+
     /// Get the item where the sort key is equal to the given value
     ///
     /// # Panics
@@ -142,10 +151,75 @@ where
         self
     }
 
+    /// Get items where the sort key is less than the given value, if any
+    ///
+    /// When no value is given, all items in the partition are included. This
+    /// is useful when paging backward from an optional cursor.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the given value cannot be serialized to an `AttributeValue`.
+    pub fn less_than_opt<V: serde::Serialize>(self, sort: Option<V>) -> Self {
+        match sort {
+            Some(sort) => self.less_than(sort),
+            None => self,
+        }
+    }
+
+    /// Get items where the sort key is greater than the given value, if any
+    ///
+    /// When no value is given, all items in the partition are included. This
+    /// is useful when paging forward from an optional cursor.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the given value cannot be serialized to an `AttributeValue`.
+    pub fn greater_than_opt<V: serde::Serialize>(self, sort: Option<V>) -> Self {
+        match sort {
+            Some(sort) => self.greater_than(sort),
+            None => self,
+        }
+    }
+
+    /// Get items where the sort key begins with the given prefix and, if a
+    /// cursor is given, comes strictly after the cursor
+    ///
+    /// Without a cursor, this is equivalent to
+    /// [`begins_with()`][Self::begins_with()]. With a cursor, the condition
+    /// becomes a `BETWEEN` over the values following the cursor up to the end
+    /// of the prefix, as DynamoDB does not allow `begins_with` to be combined
+    /// with another sort key comparison.
+    pub fn within_prefix_after(
+        self,
+        prefix: impl Into<String>,
+        cursor: Option<impl Into<String>>,
+    ) -> Self {
+        let prefix = prefix.into();
+        match cursor {
+            Some(cursor) => {
+                let mut start = cursor.into();
+                start.push('\0');
+                let mut end = prefix;
+                end.push(char::MAX);
+                self.between(start, end)
+            }
+            None => self.begins_with(prefix),
+        }
+    }
+
     /// Get items where the sort key begins with the given value
     pub fn begins_with(mut self, sort: impl Into<String>) -> Self {
         Self::ensure_range_key();
-        self.sort_key = Some(SortKeyCondition::BeginsWith(sort.into()));
+        self.sort_key = Some(SortKeyCondition::BeginsWith(AttributeValue::S(sort.into())));
+        self
+    }
+
+    /// Get items where a binary sort key begins with the given bytes
+    pub fn begins_with_bytes(mut self, prefix: impl Into<Vec<u8>>) -> Self {
+        Self::ensure_range_key();
+        self.sort_key = Some(SortKeyCondition::BeginsWith(AttributeValue::B(
+            aws_sdk_dynamodb::primitives::Blob::new(prefix),
+        )));
         self
     }
 
@@ -164,6 +238,10 @@ where
         }
     }
 
+    pub(crate) fn partition_key(&self) -> &AttributeValue {
+        &self.partition_key
+    }
+
     pub(crate) fn expression(&self) -> &'static str {
         match &self.sort_key {
             Some(SortKeyCondition::Equal(_)) => PARTITION_EQ_KEY_EXPRESSION,
@@ -189,11 +267,12 @@ where
         names.into_iter().flatten()
     }
 
-    pub(crate) fn values(self) -> impl Iterator<Item = (&'static str, AttributeValue)> {
+    pub(crate) fn values(&self) -> impl Iterator<Item = (&'static str, &AttributeValue)> {
+        let partition_key = &self.partition_key;
         let values = if K::DEFINITION.range_key().is_some() {
-            match self.sort_key {
+            match &self.sort_key {
                 Some(SortKeyCondition::Between { start, end }) => [
-                    Some((":key_PK", self.partition_key)),
+                    Some((":key_PK", partition_key)),
                     Some((":key_SK_START", start)),
                     Some((":key_SK_END", end)),
                 ],
@@ -202,21 +281,13 @@ where
                     | SortKeyCondition::LessThan(v)
                     | SortKeyCondition::LessThanOrEqual(v)
                     | SortKeyCondition::GreaterThan(v)
-                    | SortKeyCondition::GreaterThanOrEqual(v),
-                ) => [
-                    Some((":key_PK", self.partition_key)),
-                    Some((":key_SK", v)),
-                    None,
-                ],
-                Some(SortKeyCondition::BeginsWith(prefix)) => [
-                    Some((":key_PK", self.partition_key)),
-                    Some((":key_SK", AttributeValue::S(prefix))),
-                    None,
-                ],
-                None => [Some((":key_PK", self.partition_key)), None, None],
+                    | SortKeyCondition::GreaterThanOrEqual(v)
+                    | SortKeyCondition::BeginsWith(v),
+                ) => [Some((":key_PK", partition_key)), Some((":key_SK", v)), None],
+                None => [Some((":key_PK", partition_key)), None, None],
             }
         } else {
-            [Some((":key_PK", self.partition_key)), None, None]
+            [Some((":key_PK", partition_key)), None, None]
         };
 
         values.into_iter().flatten()
@@ -235,7 +306,7 @@ enum SortKeyCondition {
     LessThanOrEqual(AttributeValue),
     GreaterThan(AttributeValue),
     GreaterThanOrEqual(AttributeValue),
-    BeginsWith(String),
+    BeginsWith(AttributeValue),
 }
 
 /// A compiled filter expression
@@ -278,13 +349,21 @@ impl Filter {
 
     /// Add a value to the expression
     ///
+    /// A value wrapped in [`Sensitive`][crate::types::Sensitive] is added as
+    /// a [sensitive value][Self::sensitive_value()].
+    ///
     /// # Panics
     ///
     /// Panics if the given value cannot be serialized to an `AttributeValue`.
     pub fn value(mut self, name: &str, value: impl serde::Serialize) -> Self {
         let name = format!(":flt_{}", name.trim_start_matches(':'));
+        let sensitive = crate::types::is_sensitive(&value);
         let value = serde_dynamo::to_attribute_value(value).unwrap();
-        self.values.push((name, value));
+        if sensitive {
+            self.sensitive_values.push((name, value));
+        } else {
+            self.values.push((name, value));
+        }
         self
     }
 
@@ -299,6 +378,13 @@ impl Filter {
         self.sensitive_values.push((name, value));
         self
     }
+
+    /// Add an already-encoded value to the expression
+    pub(crate) fn raw_value(mut self, name: &str, value: AttributeValue) -> Self {
+        let name = format!(":flt_{}", name.trim_start_matches(':'));
+        self.values.push((name, value));
+        self
+    }
 }
 
 impl fmt::Debug for Filter {
@@ -355,13 +441,21 @@ impl Update {
 
     /// Add a value to the expression
     ///
+    /// A value wrapped in [`Sensitive`][crate::types::Sensitive] is added as
+    /// a [sensitive value][Self::sensitive_value()].
+    ///
     /// # Panics
     ///
     /// Panics if the given value cannot be serialized to an `AttributeValue`.
     pub fn value(mut self, name: &str, value: impl serde::Serialize) -> Self {
         let name = format!(":upd_{}", name.trim_start_matches(':'));
+        let sensitive = crate::types::is_sensitive(&value);
         let value = serde_dynamo::to_attribute_value(value).unwrap();
-        self.values.push((name, value));
+        if sensitive {
+            self.sensitive_values.push((name, value));
+        } else {
+            self.values.push((name, value));
+        }
         self
     }
 
@@ -376,22 +470,894 @@ impl Update {
         self.sensitive_values.push((name, value));
         self
     }
+
+    /// Add an already-encoded value to the expression
+    pub(crate) fn raw_value(mut self, name: &str, value: AttributeValue) -> Self {
+        let name = format!(":upd_{}", name.trim_start_matches(':'));
+        self.values.push((name, value));
+        self
+    }
+
+    /// Sets the key attributes of a secondary index to the given key
+    ///
+    /// The assignments are added to the expression's `SET` clause, adding
+    /// that clause if it is not present. The partition key of a local
+    /// secondary index is the table's partition key, so only its sort key
+    /// is set.
+    ///
+    /// ```
+    /// use modyne::{expr, keys};
+    ///
+    /// let update = expr::Update::new("SET #status = :status")
+    ///     .name("#status", "status")
+    ///     .value(":status", "OPEN")
+    ///     .set_index_keys(keys::Gsi1 {
+    ///         hash: "OPEN".to_string(),
+    ///         range: "ORDER#1234".to_string(),
+    ///     });
+    ///
+    /// assert_eq!(
+    ///     update.expression,
+    ///     "SET #upd_ikey0 = :upd_ikey0, #upd_ikey1 = :upd_ikey1, #upd_status = :upd_status",
+    /// );
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key cannot be serialized to a DynamoDB item.
+    pub fn set_index_keys<K: keys::IndexKey>(mut self, key: K) -> Self {
+        let mut item = crate::codec::to_item(key).unwrap();
+
+        let mut assignments = Vec::with_capacity(2);
+        for attr in index_key_attributes::<K>() {
+            if let Some(value) = item.remove(attr) {
+                let i = self.next_index_key_placeholder();
+                let name = format!("#upd_ikey{i}");
+                let value_name = format!(":upd_ikey{i}");
+                assignments.push(format!("{name} = {value_name}"));
+                self.names.push((name, attr.to_string()));
+                self.values.push((value_name, value));
+            }
+        }
+
+        merge_clause(&mut self.expression, "SET", &assignments);
+        self
+    }
+
+    /// Removes the key attributes of a secondary index
+    ///
+    /// This removes the item from a sparse index. The removals are added to
+    /// the expression's `REMOVE` clause, adding that clause if it is not
+    /// present. The partition key of a local secondary index is the table's
+    /// partition key, so only its sort key is removed.
+    ///
+    /// ```
+    /// use modyne::{expr, keys};
+    ///
+    /// let update = expr::Update::new("SET #unread = :unread")
+    ///     .name("#unread", "unread")
+    ///     .value(":unread", false)
+    ///     .remove_index_keys::<keys::Gsi1>();
+    ///
+    /// assert_eq!(
+    ///     update.expression,
+    ///     "SET #upd_unread = :upd_unread REMOVE #upd_ikey0, #upd_ikey1",
+    /// );
+    /// ```
+    pub fn remove_index_keys<K: keys::IndexKey>(mut self) -> Self {
+        let mut removals = Vec::with_capacity(2);
+        for attr in index_key_attributes::<K>() {
+            let name = format!("#upd_ikey{}", self.next_index_key_placeholder());
+            removals.push(name.clone());
+            self.names.push((name, attr.to_string()));
+        }
+
+        merge_clause(&mut self.expression, "REMOVE", &removals);
+        self
+    }
+
+    fn next_index_key_placeholder(&self) -> usize {
+        self.names
+            .iter()
+            .filter(|(name, _)| name.starts_with("#upd_ikey"))
+            .count()
+    }
+}
+
+/// The attributes of an index key that may be modified by an update
+fn index_key_attributes<K: keys::IndexKey>() -> impl Iterator<Item = &'static str> {
+    let (hash_key, range_key) = match K::INDEX_DEFINITION {
+        keys::SecondaryIndexDefinition::Global(def) => (Some(def.hash_key), def.range_key),
+        keys::SecondaryIndexDefinition::Local(def) => (None, Some(def.range_key)),
+    };
+    hash_key.into_iter().chain(range_key)
+}
+
+impl fmt::Debug for Update {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Update")
+            .field("expression", &self.expression)
+            .field("names", &self.names)
+            .field("values", &self.values)
+            .field(
+                "sensitive_values",
+                &format_args!("<{} values>", self.sensitive_values.len()),
+            )
+            .finish()
+    }
+}
+
+/// The changes to secondary index key attributes between two states of an entity
+///
+/// Index key attributes that differ are set to their current values, and
+/// those that are no longer present, such as when an entity leaves a sparse
+/// index, are removed. The changes can be used as a standalone update
+/// expression or merged into an existing one with [`apply()`][Self::apply()].
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct IndexSync {
+    set: Vec<(&'static str, AttributeValue)>,
+    remove: Vec<&'static str>,
+}
+
+impl IndexSync {
+    /// Computes the changes to the index keys between the previous and current entity
+    ///
+    /// The keys of the [entity type index][keys::EntityTypeIndex] are
+    /// included, so that entities entering or leaving that index are synced.
+    pub fn new<E: Entity>(previous: &E, current: &E) -> Self {
+        use keys::{IndexKey, IndexKeys};
+
+        Self::diff(
+            E::IndexKeys::KEY_DEFINITIONS
+                .iter()
+                .chain([&keys::EntityTypeIndex::INDEX_DEFINITION]),
+            &crate::computed_keys(previous),
+            &crate::computed_keys(current),
+        )
+    }
+
+    fn diff<'a>(
+        definitions: impl IntoIterator<Item = &'a keys::SecondaryIndexDefinition>,
+        previous: &Item,
+        current: &Item,
+    ) -> Self {
+        let mut set = Vec::new();
+        let mut remove = Vec::new();
+
+        let attributes = definitions
+            .into_iter()
+            .flat_map(|index| std::iter::once(index.hash_key()).chain(index.range_key()));
+        for attr in attributes {
+            if set.iter().any(|(a, _)| *a == attr) || remove.contains(&attr) {
+                continue;
+            }
+
+            match (previous.get(attr), current.get(attr)) {
+                (previous, Some(current)) if previous != Some(current) => {
+                    set.push((attr, current.clone()));
+                }
+                (Some(_), None) => remove.push(attr),
+                _ => {}
+            }
+        }
+
+        Self { set, remove }
+    }
+
+    /// Whether none of the index keys have changed
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty() && self.remove.is_empty()
+    }
+
+    /// Converts the changes into a standalone update expression
+    ///
+    /// If there are no changes, the resulting expression will be empty.
+    pub fn into_update(self) -> Update {
+        self.apply(Update::new(""))
+    }
+
+    /// Merges the changes into an existing update expression
+    ///
+    /// Assignments are added to the expression's `SET` clause and removals to
+    /// its `REMOVE` clause, adding those clauses if they are not present. The
+    /// existing expression should not also modify any of the index key
+    /// attributes.
+    pub fn apply(self, mut update: Update) -> Update {
+        let mut assignments = Vec::with_capacity(self.set.len());
+        for (i, (attr, value)) in self.set.into_iter().enumerate() {
+            let name = format!("#upd_idx_set{i}");
+            let value_name = format!(":upd_idx_set{i}");
+            assignments.push(format!("{name} = {value_name}"));
+            update.names.push((name, attr.to_string()));
+            update.values.push((value_name, value));
+        }
+
+        let mut removals = Vec::with_capacity(self.remove.len());
+        for (i, attr) in self.remove.into_iter().enumerate() {
+            let name = format!("#upd_idx_rem{i}");
+            removals.push(name.clone());
+            update.names.push((name, attr.to_string()));
+        }
+
+        merge_clause(&mut update.expression, "SET", &assignments);
+        merge_clause(&mut update.expression, "REMOVE", &removals);
+        update
+    }
+}
+
+/// The attributes that changed between two versions of an item
+///
+/// Attributes with a new or different value are set, and those missing from
+/// the new version are removed. Comparison is made on whole top-level
+/// attributes, so a change to any entry of a map or list sets the entire
+/// attribute. The changes can be used as a minimal update expression, which
+/// consumes write capacity by the size of the item like any other write, but
+/// avoids sending attributes that have not changed.
+///
+/// ```
+/// use modyne::{AttributeValue, Item};
+///
+/// let old: Item = [
+///     ("name".to_string(), AttributeValue::S("Alex".into())),
+///     ("nickname".to_string(), AttributeValue::S("Al".into())),
+/// ]
+/// .into();
+/// let new: Item = [("name".to_string(), AttributeValue::S("Alexander".into()))].into();
+///
+/// let diff = modyne::diff(&old, &new);
+/// assert_eq!(diff.changed().collect::<Vec<_>>(), ["name", "nickname"]);
+/// assert_eq!(
+///     diff.into_update().expression,
+///     "SET #upd_diff_set0 = :upd_diff_set0 REMOVE #upd_diff_rem0",
+/// );
+/// ```
+#[derive(Clone, Default)]
+#[must_use]
+pub struct ItemDiff {
+    set: Vec<(String, AttributeValue)>,
+    remove: Vec<String>,
+}
+
+impl fmt::Debug for ItemDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ItemDiff")
+            .field(
+                "set",
+                &self.set.iter().map(|(attr, _)| attr).collect::<Vec<_>>(),
+            )
+            .field("remove", &self.remove)
+            .finish()
+    }
+}
+
+impl ItemDiff {
+    /// Computes the attributes that changed between the old and new item
+    ///
+    /// Attributes are listed in order of their names.
+    pub fn new(old: &Item, new: &Item) -> Self {
+        let mut set: Vec<_> = new
+            .iter()
+            .filter(|(attr, value)| old.get(*attr) != Some(value))
+            .map(|(attr, value)| (attr.clone(), value.clone()))
+            .collect();
+        set.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let mut remove: Vec<_> = old
+            .keys()
+            .filter(|attr| !new.contains_key(*attr))
+            .cloned()
+            .collect();
+        remove.sort();
+
+        Self { set, remove }
+    }
+
+    /// Computes the attributes that changed between two states of an entity
+    ///
+    /// The entity type and key attributes are included in the comparison, so
+    /// changes to computed index keys are captured along with the fields that
+    /// they derive from. The attributes of the primary key are never
+    /// included, as they cannot be updated.
+    pub fn between<E>(old: &E, new: &E) -> Self
+    where
+        E: Entity + serde::Serialize,
+    {
+        let primary =
+            <<E::Table as crate::Table>::PrimaryKey as keys::PrimaryKey>::PRIMARY_KEY_DEFINITION;
+        Self::new(&crate::entity_to_item(old), &crate::entity_to_item(new))
+            .without(std::iter::once(primary.hash_key).chain(primary.range_key))
+    }
+
+    /// Leaves the given attributes out of the changes
+    pub fn without<'a>(mut self, attributes: impl IntoIterator<Item = &'a str>) -> Self {
+        for attr in attributes {
+            self.set.retain(|(a, _)| a != attr);
+            self.remove.retain(|a| a != attr);
+        }
+        self
+    }
+
+    /// Whether no attributes have changed
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty() && self.remove.is_empty()
+    }
+
+    /// The names of all attributes that changed, whether set or removed
+    pub fn changed(&self) -> impl Iterator<Item = &str> {
+        let mut changed: Vec<_> = self
+            .set
+            .iter()
+            .map(|(attr, _)| attr.as_str())
+            .chain(self.remove.iter().map(String::as_str))
+            .collect();
+        changed.sort_unstable();
+        changed.into_iter()
+    }
+
+    /// The attributes that were added or given a different value, with
+    /// their new values
+    #[inline]
+    pub fn set(&self) -> &[(String, AttributeValue)] {
+        &self.set
+    }
+
+    /// The attributes that were removed
+    #[inline]
+    pub fn removed(&self) -> &[String] {
+        &self.remove
+    }
+
+    /// Converts the changes into a standalone update expression
+    ///
+    /// If there are no changes, the resulting expression will be empty.
+    pub fn into_update(self) -> Update {
+        self.apply(Update::new(""))
+    }
+
+    /// Merges the changes into an existing update expression
+    ///
+    /// Assignments are added to the expression's `SET` clause and removals to
+    /// its `REMOVE` clause, adding those clauses if they are not present.
+    /// Values are added as sensitive values, as they may hold any attribute
+    /// of the item. The existing expression should not also modify any of the
+    /// changed attributes.
+    pub fn apply(self, mut update: Update) -> Update {
+        let mut assignments = Vec::with_capacity(self.set.len());
+        for (i, (attr, value)) in self.set.into_iter().enumerate() {
+            let name = format!("#upd_diff_set{i}");
+            let value_name = format!(":upd_diff_set{i}");
+            assignments.push(format!("{name} = {value_name}"));
+            update.names.push((name, attr));
+            update.sensitive_values.push((value_name, value));
+        }
+
+        let mut removals = Vec::with_capacity(self.remove.len());
+        for (i, attr) in self.remove.into_iter().enumerate() {
+            let name = format!("#upd_diff_rem{i}");
+            removals.push(name.clone());
+            update.names.push((name, attr));
+        }
+
+        merge_clause(&mut update.expression, "SET", &assignments);
+        merge_clause(&mut update.expression, "REMOVE", &removals);
+        update
+    }
+}
+
+/// A builder for `SET` clauses that assign values to nested attribute paths
+///
+/// Each path is a sequence of attribute names, with later names addressing
+/// entries of the map stored in the preceding attribute. Every name in a
+/// path is given a placeholder, so names need not be escaped. Paths should
+/// not overlap, as DynamoDB rejects updates that modify the same attribute
+/// twice.
+///
+/// This builder is used by [`IntoUpdate`][crate::IntoUpdate] implementations.
+///
+/// ```
+/// use modyne::expr::UpdateBuilder;
+///
+/// let mut builder = UpdateBuilder::new();
+/// builder.set(&["addresses", "home"], "123 Main St");
+/// builder.append(&["history"], ["moved"]);
+///
+/// let update = builder.into_update();
+/// assert_eq!(
+///     update.expression,
+///     "SET #upd_path0.#upd_path1 = :upd_path0, #upd_path2 = list_append(#upd_path2, :upd_path1)",
+/// );
+/// ```
+#[derive(Default)]
+#[must_use]
+pub struct UpdateBuilder {
+    assignments: Vec<String>,
+    names: Vec<(String, String)>,
+    values: Vec<(String, AttributeValue)>,
+    sensitive_values: Vec<(String, AttributeValue)>,
+}
+
+impl UpdateBuilder {
+    /// Create a new, empty builder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Assigns a value to the attribute at the given path
+    ///
+    /// A value wrapped in [`Sensitive`][crate::types::Sensitive] is added as
+    /// a sensitive value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path is empty or if the given value cannot be
+    /// serialized to an `AttributeValue`.
+    pub fn set(&mut self, path: &[&str], value: impl serde::Serialize) -> &mut Self {
+        let path = self.path(path);
+        let value = self.value(value);
+        self.assignments.push(format!("{path} = {value}"));
+        self
+    }
+
+    /// Appends the elements of a list value to the list at the given path
+    ///
+    /// # Panics
+    ///
+    /// Panics if the path is empty or if the given value cannot be
+    /// serialized to an `AttributeValue`.
+    pub fn append(&mut self, path: &[&str], value: impl serde::Serialize) -> &mut Self {
+        let path = self.path(path);
+        let value = self.value(value);
+        self.assignments
+            .push(format!("{path} = list_append({path}, {value})"));
+        self
+    }
+
+    /// Whether no assignments have been added
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.assignments.is_empty()
+    }
+
+    /// Converts the assignments into a standalone update expression
+    ///
+    /// If there are no assignments, the resulting expression will be empty.
+    pub fn into_update(self) -> Update {
+        self.apply(Update::new(""))
+    }
+
+    /// Merges the assignments into the `SET` clause of an existing update
+    /// expression, adding the clause if it is not present
+    pub fn apply(self, mut update: Update) -> Update {
+        update.names.extend(self.names);
+        update.values.extend(self.values);
+        update.sensitive_values.extend(self.sensitive_values);
+        merge_clause(&mut update.expression, "SET", &self.assignments);
+        update
+    }
+
+    fn path(&mut self, path: &[&str]) -> String {
+        assert!(!path.is_empty(), "an update path must not be empty");
+
+        let placeholders: Vec<_> = path
+            .iter()
+            .map(|&attr| {
+                if let Some((name, _)) = self.names.iter().find(|(_, a)| a == attr) {
+                    return name.clone();
+                }
+
+                let name = format!("#upd_path{}", self.names.len());
+                self.names.push((name.clone(), attr.to_string()));
+                name
+            })
+            .collect();
+        placeholders.join(".")
+    }
+
+    fn value(&mut self, value: impl serde::Serialize) -> String {
+        let name = format!(
+            ":upd_path{}",
+            self.values.len() + self.sensitive_values.len()
+        );
+        let sensitive = crate::types::is_sensitive(&value);
+        let value = serde_dynamo::to_attribute_value(value).unwrap();
+        if sensitive {
+            self.sensitive_values.push((name.clone(), value));
+        } else {
+            self.values.push((name.clone(), value));
+        }
+        name
+    }
+}
+
+impl fmt::Debug for UpdateBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpdateBuilder")
+            .field("assignments", &self.assignments)
+            .field("names", &self.names)
+            .field("values", &self.values)
+            .field(
+                "sensitive_values",
+                &format_args!("<{} values>", self.sensitive_values.len()),
+            )
+            .finish()
+    }
+}
+
+/// A composable fragment of an update expression for a common pattern
+///
+/// Snippets are created with [`if_not_exists_set()`], [`list_append()`],
+/// [`increment()`], [`remove_fields()`], [`set_nested()`], [`add_to_set()`],
+/// and [`delete_from_set()`], and can be
+/// combined with [`and()`][Self::and()]. Placeholders are assigned when a
+/// snippet is merged into an update, continuing from those of any snippets
+/// already applied, so several snippets can be merged into the same
+/// expression without their names or values colliding. As with any update,
+/// the fragments should not modify the same attribute twice.
+///
+/// ```
+/// use modyne::expr;
+///
+/// let update = expr::increment("views", 1)
+///     .and(expr::list_append("tags", ["new"]))
+///     .and(expr::remove_fields(["draft"]))
+///     .into_update();
+///
+/// assert_eq!(
+///     update.expression,
+///     "SET #upd_snip0 = if_not_exists(#upd_snip0, :upd_snip0) + :upd_snip1, \
+///      #upd_snip1 = list_append(if_not_exists(#upd_snip1, :upd_snip2), :upd_snip3) \
+///      REMOVE #upd_snip2",
+/// );
+/// ```
+#[derive(Clone)]
+#[must_use]
+pub struct UpdateSnippet {
+    operations: Vec<SnippetOperation>,
+}
+
+#[derive(Clone)]
+enum SnippetOperation {
+    Set {
+        path: Vec<String>,
+        value: SnippetValue,
+    },
+    IfNotExistsSet {
+        path: Vec<String>,
+        value: SnippetValue,
+    },
+    ListAppend {
+        path: Vec<String>,
+        values: SnippetValue,
+    },
+    Increment {
+        path: Vec<String>,
+        delta: i64,
+    },
+    Remove {
+        path: Vec<String>,
+    },
+    AddToSet {
+        path: Vec<String>,
+        values: SnippetValue,
+    },
+    DeleteFromSet {
+        path: Vec<String>,
+        values: SnippetValue,
+    },
+}
+
+#[derive(Clone)]
+struct SnippetValue {
+    value: AttributeValue,
+    sensitive: bool,
+}
+
+impl SnippetValue {
+    fn new(value: impl serde::Serialize) -> Self {
+        let sensitive = crate::types::is_sensitive(&value);
+        let value = serde_dynamo::to_attribute_value(value).unwrap();
+        Self { value, sensitive }
+    }
+}
+
+/// Assigns a value to an attribute only if the attribute does not exist
+///
+/// # Panics
+///
+/// Panics if the given value cannot be serialized to an `AttributeValue`.
+pub fn if_not_exists_set(field: &str, value: impl serde::Serialize) -> UpdateSnippet {
+    UpdateSnippet::single(SnippetOperation::IfNotExistsSet {
+        path: vec![field.to_string()],
+        value: SnippetValue::new(value),
+    })
+}
+
+/// Appends values to the end of a list attribute, creating the list if the
+/// attribute does not exist
+///
+/// # Panics
+///
+/// Panics if the given values cannot be serialized to an `AttributeValue`.
+pub fn list_append<V: serde::Serialize>(
+    field: &str,
+    values: impl IntoIterator<Item = V>,
+) -> UpdateSnippet {
+    let values: Vec<V> = values.into_iter().collect();
+    UpdateSnippet::single(SnippetOperation::ListAppend {
+        path: vec![field.to_string()],
+        values: SnippetValue::new(values),
+    })
+}
+
+/// Adds to a number attribute, treating an attribute that does not exist
+/// as zero
+///
+/// A negative delta decrements the attribute.
+pub fn increment(field: &str, delta: i64) -> UpdateSnippet {
+    UpdateSnippet::single(SnippetOperation::Increment {
+        path: vec![field.to_string()],
+        delta,
+    })
+}
+
+/// Removes attributes from the item
+pub fn remove_fields<'a>(fields: impl IntoIterator<Item = &'a str>) -> UpdateSnippet {
+    UpdateSnippet {
+        operations: fields
+            .into_iter()
+            .map(|field| SnippetOperation::Remove {
+                path: vec![field.to_string()],
+            })
+            .collect(),
+    }
+}
+
+/// Assigns a value to the attribute at a nested path
+///
+/// Later names in the path address entries of the map stored in the
+/// preceding attribute. The maps along the path must already exist.
+///
+/// # Panics
+///
+/// Panics if the path is empty or if the given value cannot be serialized
+/// to an `AttributeValue`.
+pub fn set_nested(path: &[&str], value: impl serde::Serialize) -> UpdateSnippet {
+    assert!(!path.is_empty(), "an update path must not be empty");
+    UpdateSnippet::single(SnippetOperation::Set {
+        path: path.iter().map(|&attr| attr.to_string()).collect(),
+        value: SnippetValue::new(value),
+    })
+}
+
+/// Adds elements to a set attribute, creating the set if the attribute does
+/// not exist
+///
+/// # Panics
+///
+/// Panics if the set is empty, as DynamoDB rejects empty sets.
+pub fn add_to_set(field: &str, values: impl crate::types::AttributeSet) -> UpdateSnippet {
+    assert!(
+        !values.is_empty(),
+        "cannot add an empty set to an attribute"
+    );
+    UpdateSnippet::single(SnippetOperation::AddToSet {
+        path: vec![field.to_string()],
+        values: SnippetValue::new(values),
+    })
+}
+
+/// Removes elements from a set attribute
+///
+/// DynamoDB removes the attribute entirely if no elements remain in the set.
+///
+/// # Panics
+///
+/// Panics if the set is empty, as DynamoDB rejects empty sets.
+pub fn delete_from_set(field: &str, values: impl crate::types::AttributeSet) -> UpdateSnippet {
+    assert!(
+        !values.is_empty(),
+        "cannot delete an empty set from an attribute"
+    );
+    UpdateSnippet::single(SnippetOperation::DeleteFromSet {
+        path: vec![field.to_string()],
+        values: SnippetValue::new(values),
+    })
+}
+
+impl UpdateSnippet {
+    fn single(operation: SnippetOperation) -> Self {
+        Self {
+            operations: vec![operation],
+        }
+    }
+
+    /// Combines this snippet with another
+    pub fn and(mut self, other: UpdateSnippet) -> Self {
+        self.operations.extend(other.operations);
+        self
+    }
+
+    /// Whether the snippet contains no operations
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Converts the snippet into a standalone update expression
+    ///
+    /// If the snippet is empty, the resulting expression will be empty.
+    pub fn into_update(self) -> Update {
+        self.apply(Update::new(""))
+    }
+
+    /// Merges the snippet into an existing update expression
+    ///
+    /// Assignments are added to the expression's `SET` clause, removals to
+    /// its `REMOVE` clause, and set additions and deletions to its `ADD` and
+    /// `DELETE` clauses, adding those clauses if they are not present.
+    pub fn apply(self, mut update: Update) -> Update {
+        let mut assignments = Vec::new();
+        let mut removals = Vec::new();
+        let mut additions = Vec::new();
+        let mut deletions = Vec::new();
+        for operation in self.operations {
+            match operation {
+                SnippetOperation::Set { path, value } => {
+                    let path = snippet_path(&mut update, path);
+                    let value = snippet_value(&mut update, value);
+                    assignments.push(format!("{path} = {value}"));
+                }
+                SnippetOperation::IfNotExistsSet { path, value } => {
+                    let path = snippet_path(&mut update, path);
+                    let value = snippet_value(&mut update, value);
+                    assignments.push(format!("{path} = if_not_exists({path}, {value})"));
+                }
+                SnippetOperation::ListAppend { path, values } => {
+                    let path = snippet_path(&mut update, path);
+                    let empty = snippet_value(&mut update, SnippetValue::new(Vec::<()>::new()));
+                    let values = snippet_value(&mut update, values);
+                    assignments.push(format!(
+                        "{path} = list_append(if_not_exists({path}, {empty}), {values})"
+                    ));
+                }
+                SnippetOperation::Increment { path, delta } => {
+                    let path = snippet_path(&mut update, path);
+                    let zero = snippet_value(&mut update, SnippetValue::new(0));
+                    let delta = snippet_value(&mut update, SnippetValue::new(delta));
+                    assignments.push(format!("{path} = if_not_exists({path}, {zero}) + {delta}"));
+                }
+                SnippetOperation::Remove { path } => {
+                    removals.push(snippet_path(&mut update, path));
+                }
+                SnippetOperation::AddToSet { path, values } => {
+                    let path = snippet_path(&mut update, path);
+                    let values = snippet_value(&mut update, values);
+                    additions.push(format!("{path} {values}"));
+                }
+                SnippetOperation::DeleteFromSet { path, values } => {
+                    let path = snippet_path(&mut update, path);
+                    let values = snippet_value(&mut update, values);
+                    deletions.push(format!("{path} {values}"));
+                }
+            }
+        }
+
+        merge_clause(&mut update.expression, "SET", &assignments);
+        merge_clause(&mut update.expression, "REMOVE", &removals);
+        merge_clause(&mut update.expression, "ADD", &additions);
+        merge_clause(&mut update.expression, "DELETE", &deletions);
+        update
+    }
 }
 
-impl fmt::Debug for Update {
+impl fmt::Debug for UpdateSnippet {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Update")
-            .field("expression", &self.expression)
-            .field("names", &self.names)
-            .field("values", &self.values)
-            .field(
-                "sensitive_values",
-                &format_args!("<{} values>", self.sensitive_values.len()),
-            )
+        let operations: Vec<_> = self
+            .operations
+            .iter()
+            .map(|operation| match operation {
+                SnippetOperation::Set { path, .. } => ("set", path.join(".")),
+                SnippetOperation::IfNotExistsSet { path, .. } => {
+                    ("if_not_exists_set", path.join("."))
+                }
+                SnippetOperation::ListAppend { path, .. } => ("list_append", path.join(".")),
+                SnippetOperation::Increment { path, .. } => ("increment", path.join(".")),
+                SnippetOperation::Remove { path } => ("remove", path.join(".")),
+                SnippetOperation::AddToSet { path, .. } => ("add_to_set", path.join(".")),
+                SnippetOperation::DeleteFromSet { path, .. } => ("delete_from_set", path.join(".")),
+            })
+            .collect();
+        f.debug_struct("UpdateSnippet")
+            .field("operations", &operations)
             .finish()
     }
 }
 
+/// Assigns placeholders to each name in a snippet path, reusing those that
+/// earlier snippets assigned to the same attribute
+fn snippet_path(update: &mut Update, path: Vec<String>) -> String {
+    let placeholders: Vec<_> = path
+        .into_iter()
+        .map(|attr| {
+            let existing = update
+                .names
+                .iter()
+                .find(|(name, a)| name.starts_with("#upd_snip") && *a == attr);
+            if let Some((name, _)) = existing {
+                return name.clone();
+            }
+
+            let count = update
+                .names
+                .iter()
+                .filter(|(name, _)| name.starts_with("#upd_snip"))
+                .count();
+            let name = format!("#upd_snip{count}");
+            update.names.push((name.clone(), attr));
+            name
+        })
+        .collect();
+    placeholders.join(".")
+}
+
+/// Assigns the next snippet placeholder to a value
+fn snippet_value(update: &mut Update, value: SnippetValue) -> String {
+    let count = update
+        .values
+        .iter()
+        .chain(&update.sensitive_values)
+        .filter(|(name, _)| name.starts_with(":upd_snip"))
+        .count();
+    let name = format!(":upd_snip{count}");
+    if value.sensitive {
+        update.sensitive_values.push((name.clone(), value.value));
+    } else {
+        update.values.push((name.clone(), value.value));
+    }
+    name
+}
+
+/// Adds items to the start of a clause in an update expression, or appends
+/// the clause if it is not already present
+fn merge_clause(expression: &mut String, keyword: &str, items: &[String]) {
+    if items.is_empty() {
+        return;
+    }
+
+    let list = items.join(", ");
+    if let Some(end) = find_clause(expression, keyword) {
+        expression.insert_str(end, &format!(" {list},"));
+    } else {
+        expression.truncate(expression.trim_end().len());
+        if !expression.is_empty() {
+            expression.push(' ');
+        }
+        expression.push_str(keyword);
+        expression.push(' ');
+        expression.push_str(&list);
+    }
+}
+
+/// Finds the end of a clause keyword in an update expression
+///
+/// Keywords are reserved words, so they cannot appear unescaped as
+/// attribute names, and escaped names and values are always prefixed.
+fn find_clause(expression: &str, keyword: &str) -> Option<usize> {
+    let is_word = |c: u8| c.is_ascii_alphanumeric() || matches!(c, b'_' | b'#' | b':');
+    let bytes = expression.as_bytes();
+
+    (0..bytes.len().saturating_sub(keyword.len() - 1)).find_map(|start| {
+        let end = start + keyword.len();
+        let matches = bytes[start..end].eq_ignore_ascii_case(keyword.as_bytes())
+            && (start == 0 || !is_word(bytes[start - 1]))
+            && (end == bytes.len() || !is_word(bytes[end]));
+        matches.then_some(end)
+    })
+}
+
 #[derive(Clone)]
 #[must_use]
 /// A compiled condition expression
@@ -432,13 +1398,21 @@ impl Condition {
 
     /// Add a value to the expression
     ///
+    /// A value wrapped in [`Sensitive`][crate::types::Sensitive] is added as
+    /// a [sensitive value][Self::sensitive_value()].
+    ///
     /// # Panics
     ///
     /// Panics if the given value cannot be serialized to an `AttributeValue`.
     pub fn value(mut self, name: &str, value: impl serde::Serialize) -> Self {
         let name = format!(":cnd_{}", name.trim_start_matches(':'));
+        let sensitive = crate::types::is_sensitive(&value);
         let value = serde_dynamo::to_attribute_value(value).unwrap();
-        self.values.push((name, value));
+        if sensitive {
+            self.sensitive_values.push((name, value));
+        } else {
+            self.values.push((name, value));
+        }
         self
     }
 
@@ -453,6 +1427,20 @@ impl Condition {
         self.sensitive_values.push((name, value));
         self
     }
+
+    /// Add an already-encoded value to the expression
+    pub(crate) fn raw_value(mut self, name: &str, value: AttributeValue) -> Self {
+        let name = format!(":cnd_{}", name.trim_start_matches(':'));
+        self.values.push((name, value));
+        self
+    }
+
+    /// Add an already-encoded sensitive value to the expression
+    pub(crate) fn raw_sensitive_value(mut self, name: &str, value: AttributeValue) -> Self {
+        let name = format!(":cnd_{}", name.trim_start_matches(':'));
+        self.sensitive_values.push((name, value));
+        self
+    }
 }
 
 impl fmt::Debug for Condition {
@@ -469,6 +1457,246 @@ impl fmt::Debug for Condition {
     }
 }
 
+/// The maximum length of an expression, in bytes
+pub const MAX_EXPRESSION_LENGTH: usize = 4096;
+
+/// The maximum length of an expression attribute name or value placeholder, in bytes
+pub const MAX_PLACEHOLDER_LENGTH: usize = 255;
+
+/// The maximum depth of a document path in an expression
+pub const MAX_PATH_DEPTH: usize = 32;
+
+impl Filter {
+    /// Checks the expression's syntax and placeholders, and the limits
+    /// enforced by DynamoDB
+    ///
+    /// Operations validate their filter expressions before sending a
+    /// request, so this is only needed to report problems earlier.
+    ///
+    /// ```
+    /// use modyne::expr;
+    ///
+    /// let filter = expr::Filter::new("#status = :status").name("#status", "status");
+    /// assert!(filter.validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<(), crate::Error> {
+        self.check()?;
+        Ok(())
+    }
+
+    pub(crate) fn check(&self) -> Result<(), InvalidExpressionError> {
+        check_expression(
+            "filter",
+            CONDITION_FUNCTIONS,
+            &self.expression,
+            &self.names,
+            self.values.iter().chain(&self.sensitive_values),
+        )
+    }
+}
+
+impl Update {
+    /// Checks the expression's syntax and placeholders, and the limits
+    /// enforced by DynamoDB
+    ///
+    /// Operations validate their update expressions before sending a
+    /// request, so this is only needed to report problems earlier.
+    pub fn validate(&self) -> Result<(), crate::Error> {
+        self.check()?;
+        Ok(())
+    }
+
+    pub(crate) fn check(&self) -> Result<(), InvalidExpressionError> {
+        check_expression(
+            "update",
+            UPDATE_FUNCTIONS,
+            &self.expression,
+            &self.names,
+            self.values.iter().chain(&self.sensitive_values),
+        )
+    }
+}
+
+impl Condition {
+    /// Checks the expression's syntax and placeholders, and the limits
+    /// enforced by DynamoDB
+    ///
+    /// Operations validate their condition expressions before sending a
+    /// request, so this is only needed to report problems earlier.
+    pub fn validate(&self) -> Result<(), crate::Error> {
+        self.check()?;
+        Ok(())
+    }
+
+    pub(crate) fn check(&self) -> Result<(), InvalidExpressionError> {
+        check_expression(
+            "condition",
+            CONDITION_FUNCTIONS,
+            &self.expression,
+            &self.names,
+            self.values.iter().chain(&self.sensitive_values),
+        )
+    }
+}
+
+/// The functions available in condition and filter expressions
+const CONDITION_FUNCTIONS: &[&str] = &[
+    "attribute_exists",
+    "attribute_not_exists",
+    "attribute_type",
+    "begins_with",
+    "contains",
+    "size",
+];
+
+/// The functions available in update expressions
+const UPDATE_FUNCTIONS: &[&str] = &["if_not_exists", "list_append"];
+
+/// Keywords that may be followed by a parenthesized operand
+const PARENTHESIZED_KEYWORDS: &[&str] = &["AND", "OR", "NOT", "IN"];
+
+/// Checks the length of an expression, its syntax, that its placeholders
+/// are all defined, used, and within the length limit, and the depth of its
+/// document paths
+fn check_expression<'a>(
+    kind: &'static str,
+    functions: &[&str],
+    expression: &str,
+    names: &[(String, String)],
+    values: impl Iterator<Item = &'a (String, AttributeValue)> + Clone,
+) -> Result<(), InvalidExpressionError> {
+    let invalid = |reason: String| InvalidExpressionError { kind, reason };
+
+    if expression.len() > MAX_EXPRESSION_LENGTH {
+        return Err(invalid(format!(
+            "expression is {} bytes, exceeding the maximum of {MAX_EXPRESSION_LENGTH}",
+            expression.len()
+        )));
+    }
+
+    let placeholders = names
+        .iter()
+        .map(|(name, _)| name)
+        .chain(values.clone().map(|(name, _)| name));
+    for placeholder in placeholders {
+        if placeholder.len() > MAX_PLACEHOLDER_LENGTH {
+            return Err(invalid(format!(
+                "placeholder `{placeholder}` is {} bytes, exceeding the maximum of \
+                 {MAX_PLACEHOLDER_LENGTH}",
+                placeholder.len()
+            )));
+        }
+    }
+
+    check_syntax(expression, functions).map_err(invalid)?;
+
+    let mut used_names = Vec::new();
+    let mut used_values = Vec::new();
+    let is_path_char =
+        |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '#' | '.' | '[' | ']');
+    for token in expression.split(|c: char| !is_path_char(c) && c != ':') {
+        if token.starts_with(':') {
+            if !values.clone().any(|(name, _)| name == token) {
+                return Err(invalid(format!("attribute value `{token}` is not defined")));
+            }
+            used_values.push(token);
+            continue;
+        }
+
+        for segment in token.split(['.', '[']) {
+            if !segment.starts_with('#') {
+                continue;
+            }
+            if !names.iter().any(|(name, _)| name == segment) {
+                return Err(invalid(format!(
+                    "attribute name `{segment}` is not defined"
+                )));
+            }
+            used_names.push(segment);
+        }
+
+        let depth = token.split(['.', '[']).count();
+        if depth > MAX_PATH_DEPTH {
+            return Err(invalid(format!(
+                "document path `{token}` is nested {depth} levels deep, exceeding the maximum \
+                 of {MAX_PATH_DEPTH}"
+            )));
+        }
+    }
+
+    // DynamoDB rejects placeholders that are defined but never used
+    if let Some((name, _)) = names
+        .iter()
+        .find(|(name, _)| !used_names.contains(&name.as_str()))
+    {
+        return Err(invalid(format!("attribute name `{name}` is not used")));
+    }
+    if let Some((name, _)) = values
+        .clone()
+        .find(|(name, _)| !used_values.contains(&name.as_str()))
+    {
+        return Err(invalid(format!("attribute value `{name}` is not used")));
+    }
+
+    Ok(())
+}
+
+/// Checks that the parentheses and brackets of an expression are balanced
+/// and that it only calls the given functions
+fn check_syntax(expression: &str, functions: &[&str]) -> Result<(), String> {
+    let mut open = Vec::new();
+    let mut word_start = None;
+    let mut last_word = None;
+
+    for (i, c) in expression.char_indices() {
+        if c.is_ascii_alphanumeric() || matches!(c, '_' | '#' | ':') {
+            word_start.get_or_insert(i);
+            continue;
+        }
+        if let Some(start) = word_start.take() {
+            last_word = Some(&expression[start..i]);
+        }
+        if c.is_whitespace() {
+            continue;
+        }
+
+        match c {
+            '(' => {
+                if let Some(word) = last_word {
+                    let is_keyword = PARENTHESIZED_KEYWORDS
+                        .iter()
+                        .any(|keyword| keyword.eq_ignore_ascii_case(word));
+                    if !is_keyword && !functions.contains(&word) {
+                        return Err(format!("unknown function `{word}`"));
+                    }
+                }
+                open.push((c, i));
+            }
+            '[' => open.push((c, i)),
+            ')' | ']' => {
+                let expected = if c == ')' { '(' } else { '[' };
+                match open.pop() {
+                    Some((opened, _)) if opened == expected => {}
+                    Some((opened, at)) => {
+                        return Err(format!(
+                            "`{c}` at offset {i} does not close `{opened}` at offset {at}"
+                        ));
+                    }
+                    None => return Err(format!("unmatched `{c}` at offset {i}")),
+                }
+            }
+            _ => {}
+        }
+        last_word = None;
+    }
+
+    if let Some((opened, at)) = open.pop() {
+        return Err(format!("unclosed `{opened}` at offset {at}"));
+    }
+
+    Ok(())
+}
+
 /// A compiled projection expression
 #[derive(Clone, Debug, PartialEq, Eq)]
 #[must_use]
@@ -491,14 +1719,57 @@ pub struct StaticProjection {
     pub names: &'static [(&'static str, &'static str)],
 }
 
+impl StaticProjection {
+    /// The names of the projected attributes, with any placeholders resolved
+    pub fn attribute_names(&self) -> impl Iterator<Item = &'static str> {
+        resolve_attribute_names(self.expression, self.names.iter().copied())
+    }
+}
+
+impl From<StaticProjection> for Projection {
+    fn from(projection: StaticProjection) -> Self {
+        Self {
+            expression: projection.expression.to_owned(),
+            names: projection
+                .names
+                .iter()
+                .map(|&(l, r)| (l.to_owned(), r.to_owned()))
+                .collect(),
+        }
+    }
+}
+
+/// Resolves the placeholders in a projection expression to attribute names
+fn resolve_attribute_names<'a, N>(expression: &'a str, names: N) -> impl Iterator<Item = &'a str>
+where
+    N: Iterator<Item = (&'a str, &'a str)> + Clone,
+{
+    expression
+        .split(',')
+        .map(str::trim)
+        .filter(|attr| !attr.is_empty())
+        .map(move |attr| {
+            names
+                .clone()
+                .find(|(name, _)| *name == attr)
+                .map_or(attr, |(_, value)| value)
+        })
+}
+
 impl Projection {
+    /// The names of the projected attributes, with any placeholders resolved
+    pub fn attribute_names(&self) -> impl Iterator<Item = &str> {
+        resolve_attribute_names(
+            &self.expression,
+            self.names.iter().map(|(l, r)| (l.as_str(), r.as_str())),
+        )
+    }
+
     /// Create a new projection expression from a set of attribute names
     pub fn new<'a, I>(attr_names: I) -> Self
     where
         I: IntoIterator<Item = &'a str>,
     {
-        let reserved_words = Self::reserved_words();
-
         let mut seen = FnvHashSet::default();
         let mut expression = String::with_capacity(512);
         let mut names = Vec::new();
@@ -509,20 +1780,8 @@ impl Projection {
                 continue;
             }
 
-            const LONGEST_RESERVED: usize = 14;
-            let reserved = if s.len() <= LONGEST_RESERVED {
-                let mut buf = [0u8; LONGEST_RESERVED];
-                let len = LONGEST_RESERVED.min(s.len());
-                let buf = &mut buf[..len];
-                buf.copy_from_slice(&s.as_bytes()[..len]);
-                buf.make_ascii_uppercase();
-                reserved_words.contains(buf)
-            } else {
-                false
-            };
-
             let is_invalid = |c: u8| !c.is_ascii_alphanumeric() && c != b'_';
-            if reserved || s.bytes().any(is_invalid) {
+            if Self::is_reserved_word(s) || s.bytes().any(is_invalid) {
                 let var = format!("#prj_{count:03}");
                 count += 1;
                 expression.push_str(&var);
@@ -556,6 +1815,21 @@ impl Projection {
         }
     }
 
+    /// Whether the attribute name is a DynamoDB reserved word, which must be
+    /// substituted with a placeholder when used in an expression
+    pub(crate) fn is_reserved_word(name: &str) -> bool {
+        const LONGEST_RESERVED: usize = 14;
+        if name.len() > LONGEST_RESERVED {
+            return false;
+        }
+
+        let mut buf = [0u8; LONGEST_RESERVED];
+        let buf = &mut buf[..name.len()];
+        buf.copy_from_slice(name.as_bytes());
+        buf.make_ascii_uppercase();
+        Self::reserved_words().contains(&*buf)
+    }
+
     fn reserved_words() -> &'static FnvHashSet<&'static [u8]> {
         static RESERVED_WORDS_SET: std::sync::OnceLock<FnvHashSet<&'static [u8]>> =
             std::sync::OnceLock::new();
@@ -1152,6 +2426,150 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn valid_expressions_pass_preflight_checks() {
+        Update::new("SET #a.#b[0] = if_not_exists(#a.#b[0], :zero) + :one REMOVE #c")
+            .name("#a", "a")
+            .name("#b", "b")
+            .name("#c", "c")
+            .value(":zero", 0)
+            .sensitive_value(":one", 1)
+            .validate()
+            .unwrap();
+        Condition::new("attribute_exists(#pk) AND size(#tags) < :max")
+            .name("#pk", "PK")
+            .name("#tags", "tags")
+            .value(":max", 10)
+            .validate()
+            .unwrap();
+    }
+
+    #[test]
+    fn preflight_checks_report_undefined_placeholders() {
+        let error = Filter::new("#status = :status")
+            .name("#status", "status")
+            .check()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid filter expression: attribute value `:flt_status` is not defined"
+        );
+
+        let error = Condition::new("attribute_exists(#a.#b)")
+            .name("#a", "a")
+            .check()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid condition expression: attribute name `#cnd_b` is not defined"
+        );
+    }
+
+    #[test]
+    fn preflight_checks_report_syntax_errors() {
+        let error = Condition::new("attribute_exists(#a AND #b = :b")
+            .name("#a", "a")
+            .name("#b", "b")
+            .value(":b", 1)
+            .check()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid condition expression: unclosed `(` at offset 16"
+        );
+
+        let error = Filter::new("#a[0) = :a")
+            .name("#a", "a")
+            .value(":a", 1)
+            .check()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid filter expression: `)` at offset 8 does not close `[` at offset 6"
+        );
+
+        let error = Condition::new("attribute_exist(#a)")
+            .name("#a", "a")
+            .check()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid condition expression: unknown function `attribute_exist`"
+        );
+
+        let error = Update::new("SET #a = size(#a)")
+            .name("#a", "a")
+            .check()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid update expression: unknown function `size`"
+        );
+
+        Filter::new("#a IN (:a, :b) AND NOT (#b BETWEEN :a AND :b)")
+            .name("#a", "a")
+            .name("#b", "b")
+            .value(":a", 1)
+            .value(":b", 2)
+            .check()
+            .unwrap();
+    }
+
+    #[test]
+    fn preflight_checks_report_unused_placeholders() {
+        let error = Filter::new("#status = :status")
+            .name("#status", "status")
+            .name("#kind", "kind")
+            .value(":status", "open")
+            .check()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid filter expression: attribute name `#flt_kind` is not used"
+        );
+
+        let error = Update::new("REMOVE #a")
+            .name("#a", "a")
+            .sensitive_value(":secret", "hunter2")
+            .check()
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid update expression: attribute value `:upd_secret` is not used"
+        );
+    }
+
+    #[test]
+    fn preflight_checks_enforce_limits() {
+        let long = (0..MAX_EXPRESSION_LENGTH).map(|_| " ").collect::<String>();
+        let error = Update::new(format!("REMOVE #a{long}"))
+            .name("#a", "a")
+            .check()
+            .unwrap_err();
+        assert!(error.to_string().contains("exceeding the maximum of 4096"));
+
+        let placeholder = format!(":{}", "v".repeat(MAX_PLACEHOLDER_LENGTH));
+        let error = Condition::new(format!("#a = {placeholder}"))
+            .name("#a", "a")
+            .value(&placeholder, 1)
+            .check()
+            .unwrap_err();
+        assert!(error.to_string().contains("exceeding the maximum of 255"));
+
+        let path = vec!["#a"; MAX_PATH_DEPTH + 1].join(".");
+        let error = Update::new(format!("REMOVE {path}"))
+            .name("#a", "a")
+            .check()
+            .unwrap_err();
+        assert!(error.to_string().contains("is nested 33 levels deep"));
+
+        let path = vec!["#a"; MAX_PATH_DEPTH].join(".");
+        Update::new(format!("REMOVE {path}"))
+            .name("#a", "a")
+            .check()
+            .unwrap();
+    }
+
     #[test]
     fn ensure_expected_substitutions_for_projection_expression() {
         const TEST_SET: &[&str] = &[
@@ -1183,6 +2601,197 @@ mod tests {
         );
     }
 
+    fn key_item(attrs: &[(&str, &str)]) -> Item {
+        attrs
+            .iter()
+            .map(|(k, v)| (k.to_string(), AttributeValue::S(v.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn index_sync_sets_changed_and_removes_stale_keys() {
+        use keys::IndexKey;
+
+        let previous = key_item(&[
+            ("GSI1PK", "A"),
+            ("GSI1SK", "B"),
+            ("GSI2PK", "C"),
+            ("GSI2SK", "D"),
+        ]);
+        let current = key_item(&[("GSI1PK", "A"), ("GSI1SK", "E")]);
+        let definitions = [keys::Gsi1::INDEX_DEFINITION, keys::Gsi2::INDEX_DEFINITION];
+
+        let update = IndexSync::diff(&definitions, &previous, &current).into_update();
+        assert_eq!(
+            update.expression,
+            "SET #upd_idx_set0 = :upd_idx_set0 REMOVE #upd_idx_rem0, #upd_idx_rem1"
+        );
+        assert_eq!(
+            update.names,
+            [
+                ("#upd_idx_set0".to_string(), "GSI1SK".to_string()),
+                ("#upd_idx_rem0".to_string(), "GSI2PK".to_string()),
+                ("#upd_idx_rem1".to_string(), "GSI2SK".to_string()),
+            ]
+        );
+        assert_eq!(update.values.len(), 1);
+    }
+
+    #[test]
+    fn index_sync_merges_into_existing_clauses() {
+        use keys::IndexKey;
+
+        let previous = key_item(&[("GSI1PK", "A"), ("GSI1SK", "B")]);
+        let current = key_item(&[("GSI1PK", "A"), ("GSI1SK", "C")]);
+        let sync = IndexSync::diff(&[keys::Gsi1::INDEX_DEFINITION], &previous, &current);
+
+        let update = sync
+            .clone()
+            .apply(Update::new("set #unread = :unread").value(":unread", false));
+        assert_eq!(
+            update.expression,
+            "set #upd_idx_set0 = :upd_idx_set0, #upd_unread = :upd_unread"
+        );
+
+        let update = sync.apply(Update::new("REMOVE #unread"));
+        assert_eq!(
+            update.expression,
+            "REMOVE #upd_unread SET #upd_idx_set0 = :upd_idx_set0"
+        );
+    }
+
+    #[test]
+    fn index_sync_is_empty_without_changes() {
+        use keys::IndexKey;
+
+        let key = key_item(&[("GSI1PK", "A"), ("GSI1SK", "B")]);
+        let sync = IndexSync::diff(&[keys::Gsi1::INDEX_DEFINITION], &key, &key);
+        assert!(sync.is_empty());
+    }
+
+    #[test]
+    fn item_diff_sets_changed_and_removes_missing_attributes() {
+        let old = key_item(&[("a", "1"), ("b", "2"), ("c", "3")]);
+        let new = key_item(&[("a", "1"), ("b", "4"), ("d", "5")]);
+
+        let diff = ItemDiff::new(&old, &new);
+        assert_eq!(diff.changed().collect::<Vec<_>>(), ["b", "c", "d"]);
+
+        let update = diff.without(["d"]).into_update();
+        assert_eq!(
+            update.expression,
+            "SET #upd_diff_set0 = :upd_diff_set0 REMOVE #upd_diff_rem0"
+        );
+        assert_eq!(
+            update.names,
+            [
+                ("#upd_diff_set0".to_string(), "b".to_string()),
+                ("#upd_diff_rem0".to_string(), "c".to_string()),
+            ]
+        );
+        assert!(update.values.is_empty());
+        assert_eq!(update.sensitive_values.len(), 1);
+    }
+
+    #[test]
+    fn item_diff_is_empty_without_changes() {
+        let item = key_item(&[("a", "1"), ("b", "2")]);
+        assert!(ItemDiff::new(&item, &item).is_empty());
+    }
+
+    #[test]
+    fn update_builder_reuses_names_across_paths() {
+        let mut builder = UpdateBuilder::new();
+        builder
+            .set(&["addresses", "home"], "123 Main St")
+            .set(&["addresses", "work"], "456 Elm St")
+            .set(&["name"], crate::types::Sensitive("Alex"));
+
+        let update = builder.apply(Update::new("REMOVE #unread"));
+        assert_eq!(
+            update.expression,
+            "REMOVE #upd_unread SET #upd_path0.#upd_path1 = :upd_path0, \
+             #upd_path0.#upd_path2 = :upd_path1, #upd_path3 = :upd_path2"
+        );
+        assert_eq!(update.names.len(), 4);
+        assert_eq!(update.values.len(), 2);
+        assert_eq!(update.sensitive_values.len(), 1);
+    }
+
+    #[test]
+    fn update_snippets_continue_placeholders_across_merges() {
+        let update = Update::new("SET #a = :a").name("#a", "a").value(":a", 1);
+        let update =
+            set_nested(&["address", "home"], crate::types::Sensitive("1 Main St")).apply(update);
+        let update = if_not_exists_set("created", "2024-01-01")
+            .and(set_nested(&["address", "work"], "2 Elm St"))
+            .apply(update);
+
+        assert_eq!(
+            update.expression,
+            "SET #upd_snip2 = if_not_exists(#upd_snip2, :upd_snip1), \
+             #upd_snip0.#upd_snip3 = :upd_snip2, #upd_snip0.#upd_snip1 = :upd_snip0, \
+             #upd_a = :upd_a"
+        );
+        assert_eq!(update.names.len(), 5);
+        assert_eq!(update.values.len(), 3);
+        assert_eq!(update.sensitive_values.len(), 1);
+        update.validate().unwrap();
+    }
+
+    #[test]
+    fn set_snippets_merge_into_add_and_delete_clauses() {
+        use crate::types::{NumberSet, StringSet};
+
+        let update = Update::new("ADD #a :a").name("#a", "a").value(":a", 1);
+        let tags: StringSet<String> = ["new"].map(String::from).into_iter().collect();
+        let stale: StringSet<String> = ["old"].map(String::from).into_iter().collect();
+        let update = add_to_set("tags", tags)
+            .and(delete_from_set("tags", stale))
+            .and(add_to_set(
+                "scores",
+                [3u32].into_iter().collect::<NumberSet<_>>(),
+            ))
+            .apply(update);
+
+        assert_eq!(
+            update.expression,
+            "ADD #upd_snip0 :upd_snip0, #upd_snip1 :upd_snip2, #upd_a :upd_a \
+             DELETE #upd_snip0 :upd_snip1"
+        );
+        assert_eq!(update.names.len(), 3);
+        update.validate().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "empty set")]
+    fn set_snippets_reject_empty_sets() {
+        let _ = add_to_set("tags", crate::types::StringSet::<String>::new());
+    }
+
+    #[test]
+    fn index_key_updates_skip_local_partition_keys() {
+        let update = Update::new("")
+            .remove_index_keys::<keys::Lsi1>()
+            .set_index_keys(keys::Gsi2 {
+                hash: "A".to_string(),
+                range: "B".to_string(),
+            });
+
+        assert_eq!(
+            update.expression,
+            "REMOVE #upd_ikey0 SET #upd_ikey1 = :upd_ikey1, #upd_ikey2 = :upd_ikey2"
+        );
+        assert_eq!(
+            update.names,
+            [
+                ("#upd_ikey0".to_string(), "LSI1SK".to_string()),
+                ("#upd_ikey1".to_string(), "GSI2PK".to_string()),
+                ("#upd_ikey2".to_string(), "GSI2SK".to_string()),
+            ]
+        );
+    }
+
     #[test]
     fn projection_expression_filters_out_duplicates() {
         const TEST_SET: &[&str] = &["alpha", "void", "beta", "alpha", "void", "green"];
@@ -1197,7 +2806,7 @@ mod tests {
     fn key_condition_expression_partition_only_doesnt_include_sort_key_variable() {
         let condition: KeyCondition<keys::Primary> = KeyCondition::in_partition("orange");
         let names: HashMap<_, _> = condition.names().collect();
-        let values: HashMap<_, _> = condition.values().collect();
+        let values: HashMap<_, _> = condition.values().map(|(k, v)| (k, v.clone())).collect();
 
         let expected_names: HashMap<_, _> = [("#key_PK", "PK")].into_iter().collect();
         let expected_values: HashMap<_, _> = [(":key_PK", AttributeValue::S("orange".into()))]
@@ -1213,7 +2822,7 @@ mod tests {
         let condition: KeyCondition<keys::Primary> =
             KeyCondition::in_partition("orange").specific_item("green");
         let names: HashMap<_, _> = condition.names().collect();
-        let values: HashMap<_, _> = condition.values().collect();
+        let values: HashMap<_, _> = condition.values().map(|(k, v)| (k, v.clone())).collect();
 
         let expected_names: HashMap<_, _> =
             [("#key_PK", "PK"), ("#key_SK", "SK")].into_iter().collect();
@@ -1233,7 +2842,7 @@ mod tests {
         let condition: KeyCondition<keys::Primary> =
             KeyCondition::in_partition("orange").between("aqua", "turquoise");
         let names: HashMap<_, _> = condition.names().collect();
-        let values: HashMap<_, _> = condition.values().collect();
+        let values: HashMap<_, _> = condition.values().map(|(k, v)| (k, v.clone())).collect();
 
         let expected_names: HashMap<_, _> =
             [("#key_PK", "PK"), ("#key_SK", "SK")].into_iter().collect();
@@ -1248,4 +2857,41 @@ mod tests {
         assert_eq!(names, expected_names);
         assert_eq!(values, expected_values);
     }
+
+    #[test]
+    fn key_condition_optional_bounds_fall_back_to_the_partition() {
+        let condition: KeyCondition<keys::Primary> =
+            KeyCondition::in_partition("orange").less_than_opt(None::<&str>);
+        assert_eq!(condition.expression(), PARTITION_KEY_EXPRESSION);
+
+        let condition: KeyCondition<keys::Primary> =
+            KeyCondition::in_partition("orange").less_than_opt(Some("green"));
+        assert_eq!(condition.expression(), PARTITION_LT_KEY_EXPRESSION);
+
+        let condition: KeyCondition<keys::Primary> =
+            KeyCondition::in_partition("orange").greater_than_opt(Some("green"));
+        assert_eq!(condition.expression(), PARTITION_GT_KEY_EXPRESSION);
+    }
+
+    #[test]
+    fn key_condition_within_prefix_after_cursor() {
+        let condition: KeyCondition<keys::Primary> =
+            KeyCondition::in_partition("orange").within_prefix_after("DEAL#", None::<String>);
+        let values: HashMap<_, _> = condition.values().map(|(k, v)| (k, v.clone())).collect();
+        assert_eq!(condition.expression(), PARTITION_BEGINS_WITH_KEY_EXPRESSION);
+        assert_eq!(values[":key_SK"], AttributeValue::S("DEAL#".into()));
+
+        let condition: KeyCondition<keys::Primary> =
+            KeyCondition::in_partition("orange").within_prefix_after("DEAL#", Some("DEAL#123"));
+        let values: HashMap<_, _> = condition.values().map(|(k, v)| (k, v.clone())).collect();
+        assert_eq!(condition.expression(), PARTITION_BETWEEN_KEY_EXPRESSION);
+        assert_eq!(
+            values[":key_SK_START"],
+            AttributeValue::S("DEAL#123\0".into())
+        );
+        assert_eq!(
+            values[":key_SK_END"],
+            AttributeValue::S(format!("DEAL#{}", char::MAX))
+        );
+    }
 }
@@ -0,0 +1,603 @@
+//! Seeding a table with entities for integration tests
+//!
+//! [`Fixtures`] collects the entities that a test expects to find in the
+//! table, writes them in batches, and waits until each item is visible in
+//! the secondary indexes of the table, so that a test does not observe the
+//! eventual consistency of global secondary indexes. The returned
+//! [`FixtureGuard`] deletes the written items when the test is done.
+//!
+//! ```no_run
+//! # use modyne::{keys, Entity, EntityDef, EntityTypeNameRef, Table, WritableTable};
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! # impl WritableTable for App {}
+//! # #[derive(serde::Serialize)]
+//! # struct Order { id: String }
+//! # impl EntityDef for Order {
+//! #     const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("order");
+//! # }
+//! # impl Entity for Order {
+//! #     type KeyInput<'a> = &'a str;
+//! #     type Table = App;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+//! #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+//! # }
+//! use modyne::fixtures::Fixtures;
+//!
+//! # async fn example(app: App) -> Result<(), modyne::Error> {
+//! let fixtures = Fixtures::new(&app)
+//!     .entities((0..10).map(|i| Order { id: format!("order-{i}") }))
+//!     .load()
+//!     .await?;
+//!
+//! // ... exercise the application against the seeded table ...
+//!
+//! fixtures.teardown().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Items are written in the order they were added. If several items share a
+//! primary key, the one added last is written. Items are written in batches
+//! of up to 25 items, and items left unprocessed by DynamoDB are retried
+//! with exponential backoff.
+//!
+//! The items must be deleted by calling [`FixtureGuard::teardown()`].
+//! Dropping a guard without tearing it down panics, rather than silently
+//! leaving the items in the table. If the guard is dropped while the thread
+//! is already panicking, such as when a test fails, the items are left in
+//! place and a warning is logged.
+
+use std::{collections::HashMap, fmt, time::Duration};
+
+use aws_sdk_dynamodb::operation::query::QueryInput;
+
+use crate::{
+    client::{self, DynamoClient},
+    error::FixtureConsistencyError,
+    keys,
+    model::{batch_write_with_retry, primary_key_of, BatchWrite, Delete, Put},
+    scope::ScopedClient,
+    AttributeValue, Entity, EntityExt, Error, Item, Table, WritableTable,
+};
+
+const MAX_BATCH_SIZE: usize = 25;
+
+/// A set of items to be written to a table for a test
+#[must_use]
+pub struct Fixtures<'a, T> {
+    table: &'a T,
+    items: Vec<Item>,
+    max_attempts: u32,
+    consistency_timeout: Duration,
+}
+
+impl<'a, T> fmt::Debug for Fixtures<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Fixtures")
+            .field("items", &self.items.len())
+            .field("max_attempts", &self.max_attempts)
+            .field("consistency_timeout", &self.consistency_timeout)
+            .finish()
+    }
+}
+
+impl<'a, T: WritableTable> Fixtures<'a, T> {
+    /// Prepares an empty set of fixtures for the table
+    pub fn new(table: &'a T) -> Self {
+        Self {
+            table,
+            items: Vec::new(),
+            max_attempts: 8,
+            consistency_timeout: Duration::from_secs(10),
+        }
+    }
+
+    /// Adds an entity to the fixtures
+    pub fn entity<E>(mut self, entity: E) -> Self
+    where
+        E: Entity<Table = T> + serde::Serialize,
+    {
+        self.items.push(entity.into_item());
+        self
+    }
+
+    /// Adds several entities to the fixtures
+    pub fn entities<E, I>(self, entities: I) -> Self
+    where
+        E: Entity<Table = T> + serde::Serialize,
+        I: IntoIterator<Item = E>,
+    {
+        entities.into_iter().fold(self, Self::entity)
+    }
+
+    /// Adds a raw item to the fixtures
+    ///
+    /// The item must include the primary key attributes of the table.
+    pub fn item(mut self, item: Item) -> Self {
+        self.items.push(item);
+        self
+    }
+
+    /// Sets the maximum number of attempts made to write each batch
+    ///
+    /// Defaults to 8 attempts.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Sets how long to wait for the items to become visible in the
+    /// secondary indexes of the table
+    ///
+    /// Defaults to 10 seconds.
+    pub fn consistency_timeout(mut self, timeout: Duration) -> Self {
+        self.consistency_timeout = timeout;
+        self
+    }
+
+    /// Writes the fixtures and waits for them to become visible in the
+    /// secondary indexes of the table
+    ///
+    /// If the items cannot be written or do not become visible before the
+    /// timeout, any items written are deleted before returning the error.
+    pub async fn load(self) -> Result<FixtureGuard<'a, T>, Error> {
+        let mut items: Vec<Item> = Vec::with_capacity(self.items.len());
+        for item in self.items {
+            let key = primary_key_of::<T>(&item);
+            items.retain(|other| primary_key_of::<T>(other) != key);
+            items.push(item);
+        }
+
+        let mut guard = FixtureGuard {
+            table: self.table,
+            keys: Vec::with_capacity(items.len()),
+            max_attempts: self.max_attempts,
+        };
+
+        for chunk in items.chunks(MAX_BATCH_SIZE) {
+            guard.keys.extend(chunk.iter().map(primary_key_of::<T>));
+            let batch = chunk.iter().fold(BatchWrite::new(), |batch, item| {
+                batch.operation(Put::new(item.clone()))
+            });
+            if let Err(error) = batch_write_with_retry(self.table, batch, self.max_attempts).await {
+                let _ = guard.teardown().await;
+                return Err(error);
+            }
+        }
+
+        if let Err(error) = wait_for_indexes(self.table, &items, self.consistency_timeout).await {
+            let _ = guard.teardown().await;
+            return Err(error);
+        }
+
+        Ok(guard)
+    }
+}
+
+/// Waits until each item is visible in every secondary index that it is
+/// projected into
+async fn wait_for_indexes<T: Table>(
+    table: &T,
+    items: &[Item],
+    timeout: Duration,
+) -> Result<(), Error> {
+    let deadline = table.clock().now() + timeout;
+
+    for definition in <T::IndexKeys as keys::IndexKeys>::KEY_DEFINITIONS {
+        let attributes: Vec<&str> = std::iter::once(definition.hash_key())
+            .chain(definition.range_key())
+            .collect();
+
+        let mut expected: Vec<(Vec<AttributeValue>, Vec<Item>)> = Vec::new();
+        for item in items {
+            let index_key: Option<Vec<_>> = attributes
+                .iter()
+                .map(|attr| item.get(*attr).cloned())
+                .collect();
+            let Some(index_key) = index_key else {
+                continue;
+            };
+            let key = primary_key_of::<T>(item);
+            match expected.iter_mut().find(|(other, _)| *other == index_key) {
+                Some((_, keys)) => keys.push(key),
+                None => expected.push((index_key, vec![key])),
+            }
+        }
+
+        let mut delay = Duration::from_millis(10);
+        loop {
+            let mut pending = 0;
+            for (index_key, keys) in &mut expected {
+                let visible = read_index_keys(table, definition, &attributes, index_key).await?;
+                keys.retain(|key| !visible.contains(key));
+                pending += keys.len();
+            }
+            expected.retain(|(_, keys)| !keys.is_empty());
+
+            if pending == 0 {
+                break;
+            }
+
+            if table.clock().now() + delay > deadline {
+                return Err(FixtureConsistencyError {
+                    index: definition.index_name(),
+                    pending,
+                    timeout,
+                }
+                .into());
+            }
+
+            table.clock().sleep(delay).await;
+            delay = (delay * 2).min(Duration::from_secs(1));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads the primary keys of the items in the index with the given index key
+async fn read_index_keys<T: Table>(
+    table: &T,
+    definition: &keys::SecondaryIndexDefinition,
+    attributes: &[&str],
+    index_key: &[AttributeValue],
+) -> Result<Vec<Item>, Error> {
+    let placeholders = ["key_PK", "key_SK"];
+    let key_condition_expression = placeholders[..attributes.len()]
+        .iter()
+        .map(|placeholder| format!("#{placeholder} = :{placeholder}"))
+        .collect::<Vec<_>>()
+        .join(" AND ");
+    let names: HashMap<String, String> = placeholders
+        .iter()
+        .zip(attributes)
+        .map(|(placeholder, attr)| (format!("#{placeholder}"), attr.to_string()))
+        .collect();
+    let values: Item = placeholders
+        .iter()
+        .zip(index_key)
+        .map(|(placeholder, value)| (format!(":{placeholder}"), value.clone()))
+        .collect();
+
+    let mut keys = Vec::new();
+    let mut exclusive_start_key = None;
+    let scoped = ScopedClient::new(table);
+    loop {
+        let input = QueryInput::builder()
+            .table_name(table.table_name())
+            .index_name(definition.index_name())
+            .key_condition_expression(&key_condition_expression)
+            .set_expression_attribute_names(Some(names.clone()))
+            .set_expression_attribute_values(Some(values.clone()))
+            .set_exclusive_start_key(exclusive_start_key)
+            .build();
+        let output = client::send(input, |input| scoped.query(input)).await?;
+
+        keys.extend(output.items.iter().flatten().map(primary_key_of::<T>));
+        exclusive_start_key = output.last_evaluated_key;
+        if exclusive_start_key.is_none() {
+            return Ok(keys);
+        }
+    }
+}
+
+/// The fixtures written to a table, deleted when the guard is torn down
+pub struct FixtureGuard<'a, T: WritableTable> {
+    table: &'a T,
+    keys: Vec<Item>,
+    max_attempts: u32,
+}
+
+impl<'a, T: WritableTable> fmt::Debug for FixtureGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FixtureGuard")
+            .field("table", &self.table.table_name())
+            .field("keys", &self.keys.len())
+            .finish()
+    }
+}
+
+impl<'a, T: WritableTable> FixtureGuard<'a, T> {
+    /// The primary keys of the written items, in the order they were written
+    #[inline]
+    pub fn keys(&self) -> &[Item] {
+        &self.keys
+    }
+
+    /// Deletes the written items
+    pub async fn teardown(mut self) -> Result<(), Error> {
+        delete_keys(
+            self.table,
+            std::mem::take(&mut self.keys),
+            self.max_attempts,
+        )
+        .await
+    }
+}
+
+impl<'a, T: WritableTable> Drop for FixtureGuard<'a, T> {
+    fn drop(&mut self) {
+        if self.keys.is_empty() {
+            return;
+        }
+
+        let count = self.keys.len();
+        if std::thread::panicking() {
+            tracing::warn!(
+                table = self.table.table_name(),
+                count,
+                "fixtures dropped during a panic were not deleted"
+            );
+            return;
+        }
+
+        panic!(
+            "fixtures for table `{}` dropped without being deleted; call `teardown()` before \
+             dropping the guard ({count} items left in place)",
+            self.table.table_name(),
+        );
+    }
+}
+
+async fn delete_keys<T: WritableTable>(
+    table: &T,
+    keys: Vec<Item>,
+    max_attempts: u32,
+) -> Result<(), Error> {
+    let mut keys = keys.into_iter().peekable();
+    while keys.peek().is_some() {
+        let batch = keys
+            .by_ref()
+            .take(MAX_BATCH_SIZE)
+            .fold(BatchWrite::new(), |batch, key| {
+                batch.operation(Delete::new(key))
+            });
+        batch_write_with_retry(table, batch, max_attempts).await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use aws_sdk_dynamodb::operation::batch_write_item::BatchWriteItemOutput;
+
+    use crate::{
+        clock::FixedClock,
+        mock::{ops, MockTable, Operation},
+        EntityDef, EntityTypeNameRef,
+    };
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            unimplemented!()
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    impl WritableTable for TestTable {}
+
+    #[derive(Clone, Debug, serde::Serialize)]
+    struct Order {
+        id: u32,
+        customer: &'static str,
+    }
+
+    impl EntityDef for Order {
+        const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("order");
+    }
+
+    impl Entity for Order {
+        type KeyInput<'a> = u32;
+        type Table = MockTable<TestTable>;
+        type IndexKeys = keys::Gsi1;
+
+        fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+            keys::Primary {
+                hash: format!("ORDER#{id}"),
+                range: format!("ORDER#{id}"),
+            }
+        }
+
+        fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+            keys::FullKey {
+                primary: Self::primary_key(self.id),
+                indexes: keys::Gsi1 {
+                    hash: format!("CUSTOMER#{}", self.customer),
+                    range: format!("ORDER#{}", self.id),
+                },
+            }
+        }
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn fixtures_are_written_in_batches_and_torn_down() {
+        let table = MockTable::<TestTable>::new("fixtures");
+        let orders: Vec<_> = (0..30)
+            .map(|id| Order {
+                id,
+                customer: "alex",
+            })
+            .collect();
+        let items: Vec<Item> = orders
+            .iter()
+            .map(|order| order.clone().into_item())
+            .collect();
+
+        table
+            .expect::<ops::BatchWriteItem>(|e| e.times(4))
+            .expect::<ops::Query>(|e| {
+                e.on_index("GSI1")
+                    .with_key_value("CUSTOMER#alex")
+                    .responding(move |input| {
+                        let sort = &input.expression_attribute_values.as_ref().unwrap()[":key_SK"];
+                        let items = items
+                            .iter()
+                            .filter(|item| item["GSI1SK"] == *sort)
+                            .cloned()
+                            .collect();
+                        Ok(aws_sdk_dynamodb::operation::query::QueryOutput::builder()
+                            .set_items(Some(items))
+                            .build())
+                    })
+                    .times(30)
+            });
+
+        runtime().block_on(async {
+            let guard = Fixtures::new(&table).entities(orders).load().await.unwrap();
+            assert_eq!(guard.keys().len(), 30);
+            guard.teardown().await.unwrap();
+        });
+
+        let batches = table.inputs::<ops::BatchWriteItem>();
+        let sizes: Vec<_> = batches
+            .iter()
+            .map(|input| input.request_items.as_ref().unwrap()["fixtures"].len())
+            .collect();
+        assert_eq!(sizes, [25, 5, 25, 5]);
+        assert!(batches[2].request_items.as_ref().unwrap()["fixtures"]
+            .iter()
+            .all(|request| request.delete_request.is_some()));
+        assert_eq!(table.calls(Operation::Query), 30);
+        table.verify();
+    }
+
+    #[test]
+    fn later_fixtures_replace_earlier_ones_with_the_same_key() {
+        let table = MockTable::<TestTable>::new("fixtures");
+        table.expect::<ops::BatchWriteItem>(|e| e.times(2));
+
+        let items = [
+            Item::from([
+                ("PK".to_string(), AttributeValue::S("A".into())),
+                ("SK".to_string(), AttributeValue::S("A".into())),
+                ("version".to_string(), AttributeValue::N("1".into())),
+            ]),
+            Item::from([
+                ("PK".to_string(), AttributeValue::S("A".into())),
+                ("SK".to_string(), AttributeValue::S("A".into())),
+                ("version".to_string(), AttributeValue::N("2".into())),
+            ]),
+        ];
+
+        runtime().block_on(async {
+            let guard = items
+                .into_iter()
+                .fold(Fixtures::new(&table), Fixtures::item)
+                .load()
+                .await
+                .unwrap();
+            assert_eq!(guard.keys().len(), 1);
+            guard.teardown().await.unwrap();
+        });
+
+        let batches = table.inputs::<ops::BatchWriteItem>();
+        let requests = &batches[0].request_items.as_ref().unwrap()["fixtures"];
+        assert_eq!(requests.len(), 1);
+        assert_eq!(
+            requests[0].put_request.as_ref().unwrap().item["version"],
+            AttributeValue::N("2".into())
+        );
+    }
+    #[test]
+    fn unprocessed_items_are_retried_after_a_backoff_on_the_table_clock() {
+        let start = time::OffsetDateTime::from_unix_timestamp(1_701_950_400).unwrap();
+        let table = MockTable::<TestTable>::new("fixtures").with_clock(FixedClock::new(start));
+        let first = AtomicBool::new(true);
+        table.expect::<ops::BatchWriteItem>(|e| {
+            e.responding(move |input| {
+                let unprocessed = first
+                    .swap(false, Ordering::SeqCst)
+                    .then(|| input.request_items.clone().unwrap());
+                Ok(BatchWriteItemOutput::builder()
+                    .set_unprocessed_items(unprocessed)
+                    .build())
+            })
+        });
+
+        let item = Item::from([
+            ("PK".to_string(), AttributeValue::S("A".into())),
+            ("SK".to_string(), AttributeValue::S("A".into())),
+        ]);
+        runtime().block_on(async {
+            let guard = Fixtures::new(&table).item(item).load().await.unwrap();
+            guard.teardown().await.unwrap();
+        });
+
+        assert_eq!(table.calls(Operation::BatchWriteItem), 3);
+        assert!(table.clock().now() > start);
+    }
+
+    #[test]
+    fn items_missing_from_an_index_time_out_on_the_table_clock() {
+        let start = time::OffsetDateTime::from_unix_timestamp(1_701_950_400).unwrap();
+        let table = MockTable::<TestTable>::new("fixtures").with_clock(FixedClock::new(start));
+        table
+            .expect::<ops::BatchWriteItem>(|e| e.times(2))
+            .expect::<ops::Query>(|e| e.on_index("GSI1"));
+
+        let order = Order {
+            id: 1,
+            customer: "alex",
+        };
+        let error = runtime()
+            .block_on(
+                Fixtures::new(&table)
+                    .entity(order)
+                    .consistency_timeout(Duration::from_secs(5))
+                    .load(),
+            )
+            .unwrap_err();
+
+        let source = std::error::Error::source(&error).unwrap();
+        assert!(source.is::<FixtureConsistencyError>());
+        assert!(source.to_string().contains("GSI1"), "{source}");
+        assert!(table.calls(Operation::Query) > 1);
+        assert!(table.clock().now() > start);
+        assert!(table.clock().now() <= start + Duration::from_secs(5));
+        let batches = table.inputs::<ops::BatchWriteItem>();
+        assert!(batches[1].request_items.as_ref().unwrap()["fixtures"]
+            .iter()
+            .all(|request| request.delete_request.is_some()));
+    }
+
+    #[test]
+    #[should_panic(expected = "dropped without being deleted")]
+    fn dropping_fixtures_without_teardown_panics() {
+        let table = MockTable::<TestTable>::new("fixtures");
+        table.expect::<ops::BatchWriteItem>(|e| e.times(1));
+
+        let item = Item::from([
+            ("PK".to_string(), AttributeValue::S("A".into())),
+            ("SK".to_string(), AttributeValue::S("A".into())),
+        ]);
+        runtime().block_on(async {
+            let _guard = Fixtures::new(&table).item(item).load().await.unwrap();
+        });
+    }
+}
@@ -0,0 +1,531 @@
+//! Sampling of partition access to find hot partitions
+//!
+//! A partition that receives a disproportionate share of the requests to a
+//! table or index is throttled long before the table as a whole reaches its
+//! capacity. A [`PartitionHeat`] samples the partitions accessed by recent
+//! operations and reports the partition key prefixes that were accessed
+//! most often within a sliding window, for each index. Hot partitions can
+//! then be found while they are still warm, before they are throttled.
+//!
+//! Each operation executed through modyne reports the partition it
+//! accesses to
+//! [`Table::record_partition_access()`][crate::Table::record_partition_access()].
+//! To sample operations, forward the hook to the sampler:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use modyne::{
+//!     heat::{PartitionHeat, PartitionHeatOptions},
+//!     instrumentation::PartitionAccess,
+//!     keys, Table,
+//! };
+//!
+//! struct App {
+//!     client: aws_sdk_dynamodb::Client,
+//!     heat: PartitionHeat,
+//! }
+//!
+//! impl Table for App {
+//!     type PrimaryKey = keys::Primary;
+//!     type IndexKeys = keys::Gsi1;
+//!
+//!     fn table_name(&self) -> &str {
+//!         "MyTable"
+//!     }
+//!
+//!     fn client(&self) -> &aws_sdk_dynamodb::Client {
+//!         &self.client
+//!     }
+//!
+//!     fn record_partition_access(&self, access: &PartitionAccess<'_>) {
+//!         self.heat.record(access);
+//!     }
+//! }
+//!
+//! # fn example(client: aws_sdk_dynamodb::Client) {
+//! let heat = PartitionHeat::new(
+//!     PartitionHeatOptions::new()
+//!         .window(Duration::from_secs(60))
+//!         .sample_every(10)
+//!         .summary_interval(Duration::from_secs(60)),
+//! );
+//! let app = App { client, heat };
+//!
+//! for index in app.heat.report(5).indexes() {
+//!     println!("{index}");
+//! }
+//! # }
+//! ```
+//!
+//! Only the prefix of each partition key value is retained, so that values
+//! such as user names or email addresses are not held in memory or logged.
+//! As in [`analysis`][crate::analysis], the prefix of a value is the portion
+//! up to and including the first `#` separator, ignoring any leading
+//! separators. Numbers and values without a separator are counted under
+//! the `<unprefixed>` placeholder. Values that are neither strings nor
+//! numbers are not sampled.
+//! Scans do not access a single partition and are never sampled.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::Duration,
+};
+
+use time::OffsetDateTime;
+
+use crate::{
+    analysis::key_prefix,
+    clock::{Clock, SystemClock},
+    instrumentation::PartitionAccess,
+};
+
+/// Options for a [`PartitionHeat`] sampler
+///
+/// By default, every operation is sampled over a window of one minute,
+/// holding at most 10,000 samples, and no summary is logged.
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct PartitionHeatOptions {
+    window: Duration,
+    sample_every: u64,
+    max_samples: usize,
+    summary_interval: Option<Duration>,
+    summary_top: usize,
+    clock: Arc<dyn Clock>,
+}
+
+impl Default for PartitionHeatOptions {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(60),
+            sample_every: 1,
+            max_samples: 10_000,
+            summary_interval: None,
+            summary_top: 5,
+            clock: Arc::new(SystemClock),
+        }
+    }
+}
+
+impl PartitionHeatOptions {
+    /// Prepares the default options
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how far back samples are counted in a report
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Samples only one of every `n` operations
+    ///
+    /// Sampling reduces the cost of recording on busy tables, while the
+    /// relative heat of each partition remains representative.
+    pub fn sample_every(mut self, n: u64) -> Self {
+        self.sample_every = n.max(1);
+        self
+    }
+
+    /// Sets the greatest number of samples held, discarding the oldest
+    /// samples once the limit is reached
+    pub fn max_samples(mut self, max_samples: usize) -> Self {
+        self.max_samples = max_samples.max(1);
+        self
+    }
+
+    /// Logs a summary of the hottest partitions each time the interval elapses
+    ///
+    /// The summary is logged as a `tracing` event at the `INFO` level for
+    /// each index, listing the `top` hottest partition key prefixes. The
+    /// interval is timed by a background task, which requires the sampler
+    /// to be created within a Tokio runtime.
+    pub fn summary_interval(mut self, interval: Duration) -> Self {
+        self.summary_interval = Some(interval);
+        self
+    }
+
+    /// Sets the number of partition key prefixes listed for each index in a
+    /// logged summary
+    pub fn summary_top(mut self, top: usize) -> Self {
+        self.summary_top = top;
+        self
+    }
+
+    /// Sets the clock used to time samples
+    ///
+    /// Defaults to the [system clock][SystemClock].
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+}
+
+/// A sampler of the partitions accessed by recent operations
+///
+/// See the [module documentation][self] for details.
+pub struct PartitionHeat {
+    shared: Arc<Shared>,
+    summary: Option<tokio::task::JoinHandle<()>>,
+}
+
+struct Shared {
+    window: Duration,
+    sample_every: u64,
+    max_samples: usize,
+    clock: Arc<dyn Clock>,
+    operations: AtomicU64,
+    samples: Mutex<VecDeque<Sample>>,
+}
+
+struct Sample {
+    at: OffsetDateTime,
+    index_name: Option<&'static str>,
+    prefix: String,
+}
+
+impl fmt::Debug for PartitionHeat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PartitionHeat")
+            .field("window", &self.shared.window)
+            .field("sample_every", &self.shared.sample_every)
+            .field("samples", &self.shared.samples().len())
+            .finish()
+    }
+}
+
+impl PartitionHeat {
+    /// Prepares a sampler without any samples
+    ///
+    /// # Panics
+    ///
+    /// Panics if a summary interval is configured and the sampler is not
+    /// created within a Tokio runtime.
+    pub fn new(options: PartitionHeatOptions) -> Self {
+        let shared = Arc::new(Shared {
+            window: options.window,
+            sample_every: options.sample_every,
+            max_samples: options.max_samples,
+            clock: options.clock,
+            operations: AtomicU64::new(0),
+            samples: Mutex::new(VecDeque::new()),
+        });
+
+        let top = options.summary_top;
+        let summary = options.summary_interval.map(|interval| {
+            let shared = Arc::clone(&shared);
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                ticker.tick().await;
+                loop {
+                    ticker.tick().await;
+                    for index in shared.report(top).indexes {
+                        tracing::info!(
+                            index = index.index_name.unwrap_or("<table>"),
+                            sampled = index.sampled,
+                            hottest = %index,
+                            "partition heat"
+                        );
+                    }
+                }
+            })
+        });
+
+        Self { shared, summary }
+    }
+
+    /// Records an access to a partition, if it is sampled
+    pub fn record(&self, access: &PartitionAccess<'_>) {
+        let operation = self.shared.operations.fetch_add(1, Ordering::Relaxed);
+        if operation % self.shared.sample_every != 0 {
+            return;
+        }
+
+        let Some(prefix) = key_prefix(access.partition()) else {
+            return;
+        };
+
+        let sample = Sample {
+            at: self.shared.clock.now(),
+            index_name: access.index_name(),
+            prefix,
+        };
+
+        let mut samples = self.shared.samples();
+        self.shared.expire(&mut samples, sample.at);
+        if samples.len() >= self.shared.max_samples {
+            samples.pop_front();
+        }
+        samples.push_back(sample);
+    }
+
+    /// Reports the `top` hottest partition key prefixes of each index
+    /// within the window
+    pub fn report(&self, top: usize) -> HeatReport {
+        self.shared.report(top)
+    }
+
+    /// Discards all samples
+    pub fn clear(&self) {
+        self.shared.samples().clear();
+    }
+}
+
+impl Drop for PartitionHeat {
+    fn drop(&mut self) {
+        if let Some(summary) = self.summary.take() {
+            summary.abort();
+        }
+    }
+}
+
+impl Shared {
+    fn report(&self, top: usize) -> HeatReport {
+        let mut samples = self.samples();
+        self.expire(&mut samples, self.clock.now());
+
+        let mut counts: HashMap<Option<&'static str>, HashMap<&str, usize>> = HashMap::new();
+        for sample in samples.iter() {
+            *counts
+                .entry(sample.index_name)
+                .or_default()
+                .entry(&sample.prefix)
+                .or_default() += 1;
+        }
+
+        let mut indexes: Vec<_> = counts
+            .into_iter()
+            .map(|(index_name, prefixes)| {
+                let sampled = prefixes.values().sum();
+                let mut hottest: Vec<_> = prefixes
+                    .into_iter()
+                    .map(|(prefix, count)| PrefixHeat {
+                        prefix: prefix.to_string(),
+                        count,
+                        sampled,
+                    })
+                    .collect();
+                hottest.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.prefix.cmp(&b.prefix)));
+                hottest.truncate(top);
+
+                IndexHeat {
+                    index_name,
+                    sampled,
+                    hottest,
+                }
+            })
+            .collect();
+        indexes.sort_by_key(|index| index.index_name);
+
+        HeatReport {
+            window: self.window,
+            indexes,
+        }
+    }
+
+    /// Discards samples that have left the window
+    fn expire(&self, samples: &mut VecDeque<Sample>, now: OffsetDateTime) {
+        let start = now - self.window;
+        while samples.front().is_some_and(|sample| sample.at <= start) {
+            samples.pop_front();
+        }
+    }
+
+    fn samples(&self) -> MutexGuard<'_, VecDeque<Sample>> {
+        self.samples.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// The hottest partitions of each index within a window
+#[derive(Clone, Debug)]
+pub struct HeatReport {
+    window: Duration,
+    indexes: Vec<IndexHeat>,
+}
+
+impl HeatReport {
+    /// The window over which samples were counted
+    #[inline]
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// The indexes accessed within the window, with the table itself first
+    #[inline]
+    pub fn indexes(&self) -> &[IndexHeat] {
+        &self.indexes
+    }
+
+    /// The hottest partitions of the given index, or of the table itself if
+    /// no index name is given
+    pub fn index(&self, index_name: Option<&str>) -> Option<&IndexHeat> {
+        self.indexes
+            .iter()
+            .find(|index| index.index_name == index_name)
+    }
+}
+
+/// The hottest partitions of an index within a window
+#[derive(Clone, Debug)]
+pub struct IndexHeat {
+    index_name: Option<&'static str>,
+    sampled: usize,
+    hottest: Vec<PrefixHeat>,
+}
+
+impl IndexHeat {
+    /// The name of the index, or `None` for the table itself
+    #[inline]
+    pub fn index_name(&self) -> Option<&'static str> {
+        self.index_name
+    }
+
+    /// The number of sampled accesses to the index
+    #[inline]
+    pub fn sampled(&self) -> usize {
+        self.sampled
+    }
+
+    /// The most frequently accessed partition key prefixes, hottest first
+    #[inline]
+    pub fn hottest(&self) -> &[PrefixHeat] {
+        &self.hottest
+    }
+}
+
+impl fmt::Display for IndexHeat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.index_name.unwrap_or("<table>"))?;
+        f.write_str(":")?;
+        for (i, prefix) in self.hottest.iter().enumerate() {
+            f.write_str(if i == 0 { " " } else { ", " })?;
+            write!(f, "{prefix}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The sampled accesses to the partitions with a common key prefix
+#[derive(Clone, Debug)]
+pub struct PrefixHeat {
+    prefix: String,
+    count: usize,
+    sampled: usize,
+}
+
+impl PrefixHeat {
+    /// The partition key prefix
+    #[inline]
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// The number of sampled accesses to partitions with the prefix
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The fraction of the sampled accesses to the index that were made to
+    /// partitions with the prefix
+    #[inline]
+    pub fn share(&self) -> f64 {
+        self.count as f64 / self.sampled as f64
+    }
+}
+
+impl fmt::Display for PrefixHeat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, {:.1}%)",
+            self.prefix,
+            self.count,
+            self.share() * 100.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::FixedClock, AttributeValue};
+
+    #[test]
+    fn reports_the_hottest_prefixes_of_each_index() {
+        let clock = Arc::new(FixedClock::new(OffsetDateTime::UNIX_EPOCH));
+        let heat = PartitionHeat::new(PartitionHeatOptions::new().clock(clock.clone()));
+
+        let record = |index_name, partition: &str| {
+            let partition = AttributeValue::S(partition.to_string());
+            heat.record(&PartitionAccess::new("Query", index_name, &partition));
+        };
+
+        record(None, "CUSTOMER#alex");
+        record(None, "CUSTOMER#blair");
+        record(None, "ORDER#1234");
+        record(Some("GSI1"), "STATUS#OPEN");
+
+        clock.advance(Duration::from_secs(61));
+        record(None, "ORDER#5678");
+
+        let report = heat.report(5);
+        let table = report.index(None).unwrap();
+        assert_eq!(table.sampled(), 1);
+        assert_eq!(table.hottest()[0].prefix(), "ORDER#");
+        assert!(report.index(Some("GSI1")).is_none());
+
+        clock.set(OffsetDateTime::UNIX_EPOCH);
+        heat.clear();
+        record(None, "CUSTOMER#alex");
+        record(None, "CUSTOMER#blair");
+        record(None, "ORDER#1234");
+        record(Some("GSI1"), "STATUS#OPEN");
+
+        let report = heat.report(1);
+        assert_eq!(report.indexes().len(), 2);
+        assert_eq!(
+            report.indexes()[0].to_string(),
+            "<table>: CUSTOMER# (2, 66.7%)"
+        );
+        assert_eq!(report.indexes()[1].to_string(), "GSI1: STATUS# (1, 100.0%)");
+    }
+
+    #[test]
+    fn unprefixed_partitions_are_reported_under_a_placeholder() {
+        let heat = PartitionHeat::new(PartitionHeatOptions::new());
+        for partition in [
+            AttributeValue::S("alex@example.com".to_string()),
+            AttributeValue::N("5551234".to_string()),
+            AttributeValue::S("CUSTOMER#alex".to_string()),
+        ] {
+            heat.record(&PartitionAccess::new("GetItem", None, &partition));
+        }
+
+        let report = heat.report(5);
+        let table = report.index(None).unwrap();
+        let prefixes: Vec<_> = table.hottest().iter().map(PrefixHeat::prefix).collect();
+        assert_eq!(prefixes, ["<unprefixed>", "CUSTOMER#"]);
+        assert!(!table.to_string().contains("alex@example.com"));
+    }
+
+    #[test]
+    fn samples_one_of_every_n_operations() {
+        let heat = PartitionHeat::new(PartitionHeatOptions::new().sample_every(3));
+        let partition = AttributeValue::S("CUSTOMER#alex".to_string());
+        for _ in 0..7 {
+            heat.record(&PartitionAccess::new("GetItem", None, &partition));
+        }
+
+        assert_eq!(heat.report(5).index(None).unwrap().sampled(), 3);
+    }
+}
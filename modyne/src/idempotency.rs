@@ -0,0 +1,453 @@
+//! Idempotent transactional writes
+//!
+//! DynamoDB deduplicates [`TransactWrite`] requests that share a client
+//! request token for ten minutes. An [`Idempotency`] derives that token
+//! deterministically from a caller-supplied idempotency key and the contents
+//! of the transaction, so that retries of the same business operation reuse
+//! the same token without the caller needing to persist it.
+//!
+//! To deduplicate operations beyond that window, such as across process
+//! restarts, the transaction can also create an [`IdempotencyRecord`] for
+//! the key. If the record already exists, the transaction fails its
+//! condition check and the operation is known to have been applied already.
+//! Records carry a `ttl` attribute holding their expiration time in epoch
+//! seconds, so that they can be removed by DynamoDB's time-to-live feature
+//! when it is enabled on that attribute.
+//!
+//! ```no_run
+//! # use modyne::{keys, Table, WritableTable};
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! # impl WritableTable for App {}
+//! # fn debit_account() -> modyne::model::Update { unimplemented!() }
+//! use std::time::Duration;
+//!
+//! use modyne::{idempotency::Idempotency, model::TransactWrite};
+//!
+//! # async fn example(app: App) -> Result<(), modyne::Error> {
+//! let idempotency = Idempotency::new("payment-8f14e45f").ttl(Duration::from_secs(86400));
+//!
+//! let debit = modyne::expr::Update::new("SET balance = balance - :amount").value(":amount", 100);
+//! let transaction = TransactWrite::new().operation(debit_account().expression(debit));
+//!
+//! let result = idempotency.apply::<App>(transaction).execute(&app).await;
+//! match result.map_err(modyne::Error::from) {
+//!     Ok(_) => println!("payment applied"),
+//!     Err(error) if error.is_conditional_check_failed_exception() => {
+//!         println!("payment was already applied");
+//!     }
+//!     Err(error) => return Err(error),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The same [`Idempotency`] value should be reused when retrying an
+//! operation, as the record it creates includes the time at which the
+//! helper was constructed.
+
+use std::{fmt, hash::Hasher, marker::PhantomData, time::Duration};
+
+use fnv::FnvHasher;
+use time::OffsetDateTime;
+
+use crate::{
+    clock::Clock,
+    keys,
+    model::{Get, TransactWrite},
+    AttributeValue, Entity, EntityDef, EntityExt, EntityTypeNameRef, Error, Item, Table,
+};
+
+/// Derives client request tokens and idempotency records for a business operation
+#[derive(Clone, Debug)]
+pub struct Idempotency {
+    key: String,
+    created_at: OffsetDateTime,
+    ttl: Duration,
+}
+
+impl Idempotency {
+    /// Prepares an idempotent operation identified by the given key
+    ///
+    /// Idempotency records expire after one day by default.
+    pub fn new(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            created_at: OffsetDateTime::now_utc(),
+            ttl: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+
+    /// Prepares an idempotent operation identified by the given key, reading
+    /// the creation time of its record from the clock
+    ///
+    /// This is typically used with a [`Table::clock()`].
+    pub fn with_clock(key: impl Into<String>, clock: &dyn Clock) -> Self {
+        Self {
+            created_at: clock.now(),
+            ..Self::new(key)
+        }
+    }
+
+    /// Sets how long the idempotency record should be retained
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// The idempotency key
+    #[inline]
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Derives a client request token from the key and the contents of the transaction
+    pub fn token(&self, transaction: &TransactWrite) -> String {
+        transaction.idempotency_token(&self.key)
+    }
+
+    /// Applies a derived client request token to the transaction, and adds
+    /// an operation creating the idempotency record for the key
+    ///
+    /// If a record for the key already exists, the transaction will fail
+    /// with a conditional check failure.
+    pub fn apply<T>(&self, transaction: TransactWrite) -> TransactWrite
+    where
+        T: Table<PrimaryKey = keys::Primary> + 'static,
+    {
+        let token = self.token(&transaction);
+        let record = IdempotencyRecord::<T> {
+            key: self.key.clone(),
+            token: token.clone(),
+            created_at: self.created_at,
+            expires_at: self.created_at + self.ttl,
+            table: PhantomData,
+        };
+
+        transaction
+            .client_request_token(token)
+            .operation(record.create())
+    }
+
+    /// Fetches the idempotency record for the key, if the operation has been applied
+    pub async fn lookup<T>(&self, table: &T) -> Result<Option<IdempotencyRecord<T>>, Error>
+    where
+        T: Table<PrimaryKey = keys::Primary> + 'static,
+    {
+        let output = IdempotencyRecord::<T>::get(&self.key)
+            .execute_with_consistency(table, true)
+            .await?;
+
+        output.item.map(crate::ProjectionExt::from_item).transpose()
+    }
+
+    /// Prepares a get operation for the idempotency record of the key
+    pub fn get<T>(&self) -> Get
+    where
+        T: Table<PrimaryKey = keys::Primary> + 'static,
+    {
+        IdempotencyRecord::<T>::get(&self.key)
+    }
+}
+
+/// A persisted record of an applied idempotent operation
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct IdempotencyRecord<T> {
+    /// The idempotency key
+    pub key: String,
+
+    /// The client request token used when the operation was applied
+    pub token: String,
+
+    /// When the operation was applied
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+
+    /// When the record expires and may be removed
+    #[serde(rename = "ttl", with = "time::serde::timestamp")]
+    pub expires_at: OffsetDateTime,
+
+    #[serde(skip)]
+    table: PhantomData<fn() -> T>,
+}
+
+impl<T> fmt::Debug for IdempotencyRecord<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IdempotencyRecord")
+            .field("key", &self.key)
+            .field("token", &self.token)
+            .field("created_at", &self.created_at)
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+impl<T> Clone for IdempotencyRecord<T> {
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            token: self.token.clone(),
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+            table: PhantomData,
+        }
+    }
+}
+
+impl<T> EntityDef for IdempotencyRecord<T> {
+    const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("idempotency");
+}
+
+impl<T: Table<PrimaryKey = keys::Primary>> Entity for IdempotencyRecord<T> {
+    type KeyInput<'a> = &'a str;
+    type Table = T;
+    type IndexKeys = ();
+
+    fn primary_key(key: Self::KeyInput<'_>) -> keys::Primary {
+        keys::Primary {
+            hash: format!("IDEMPOTENCY#{key}"),
+            range: "IDEMPOTENCY".to_string(),
+        }
+    }
+
+    fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+        Self::primary_key(&self.key).into()
+    }
+}
+
+/// Hashes the contents of an operation into a client request token
+///
+/// Items and expression attributes are hashed in a canonical order, so that
+/// the token does not depend on the iteration order of hash maps.
+pub(crate) struct TokenHasher {
+    hashers: [FnvHasher; 2],
+}
+
+impl TokenHasher {
+    pub(crate) fn new(key: &str) -> Self {
+        let mut hasher = Self {
+            hashers: [
+                FnvHasher::default(),
+                FnvHasher::with_key(0x6c62_272e_07bb_0142),
+            ],
+        };
+        hasher.write_str(key);
+        hasher
+    }
+
+    pub(crate) fn write_str(&mut self, value: &str) {
+        self.write_bytes(value.as_bytes());
+    }
+
+    pub(crate) fn write_item(&mut self, item: &Item) {
+        let mut attributes: Vec<_> = item.iter().collect();
+        attributes.sort_unstable_by_key(|(name, _)| *name);
+        self.write_len(attributes.len());
+        for (name, value) in attributes {
+            self.write_str(name);
+            self.write_value(value);
+        }
+    }
+
+    pub(crate) fn write_expression(
+        &mut self,
+        expression: &str,
+        names: &[(String, String)],
+        values: &[(String, AttributeValue)],
+        sensitive_values: &[(String, AttributeValue)],
+    ) {
+        self.write_str(expression);
+
+        let mut names: Vec<_> = names.iter().collect();
+        names.sort_unstable();
+        self.write_len(names.len());
+        for (name, value) in names {
+            self.write_str(name);
+            self.write_str(value);
+        }
+
+        let mut values: Vec<_> = values.iter().chain(sensitive_values).collect();
+        values.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        self.write_len(values.len());
+        for (name, value) in values {
+            self.write_str(name);
+            self.write_value(value);
+        }
+    }
+
+    fn write_value(&mut self, value: &AttributeValue) {
+        match value {
+            AttributeValue::S(s) => {
+                self.write_tag(b'S');
+                self.write_str(s);
+            }
+            AttributeValue::N(n) => {
+                self.write_tag(b'N');
+                self.write_str(n);
+            }
+            AttributeValue::B(b) => {
+                self.write_tag(b'B');
+                self.write_bytes(b.as_ref());
+            }
+            AttributeValue::Bool(b) => {
+                self.write_tag(b'T');
+                self.write_tag(u8::from(*b));
+            }
+            AttributeValue::Null(_) => self.write_tag(b'0'),
+            AttributeValue::Ss(ss) => self.write_set(b's', ss),
+            AttributeValue::Ns(ns) => self.write_set(b'n', ns),
+            AttributeValue::Bs(bs) => {
+                self.write_tag(b'b');
+                let mut bs: Vec<_> = bs.iter().map(|b| b.as_ref()).collect();
+                bs.sort_unstable();
+                self.write_len(bs.len());
+                for b in bs {
+                    self.write_bytes(b);
+                }
+            }
+            AttributeValue::L(l) => {
+                self.write_tag(b'L');
+                self.write_len(l.len());
+                for value in l {
+                    self.write_value(value);
+                }
+            }
+            AttributeValue::M(m) => {
+                self.write_tag(b'M');
+                self.write_item(m);
+            }
+            _ => self.write_tag(b'?'),
+        }
+    }
+
+    fn write_set(&mut self, tag: u8, values: &[String]) {
+        self.write_tag(tag);
+        let mut values: Vec<_> = values.iter().collect();
+        values.sort_unstable();
+        self.write_len(values.len());
+        for value in values {
+            self.write_str(value);
+        }
+    }
+
+    fn write_tag(&mut self, tag: u8) {
+        for hasher in &mut self.hashers {
+            hasher.write_u8(tag);
+        }
+    }
+
+    fn write_len(&mut self, len: usize) {
+        for hasher in &mut self.hashers {
+            hasher.write_u64(len as u64);
+        }
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_len(bytes.len());
+        for hasher in &mut self.hashers {
+            hasher.write(bytes);
+        }
+    }
+
+    /// Produces a 32-character token, within DynamoDB's 36-character limit
+    pub(crate) fn finish(self) -> String {
+        let [a, b] = self.hashers;
+        format!("{:016x}{:016x}", a.finish(), b.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr;
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            "TestTable"
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    fn transaction(amount: u32) -> TransactWrite {
+        let key: Item = [
+            ("PK".to_string(), AttributeValue::S("ACCOUNT#1".into())),
+            ("SK".to_string(), AttributeValue::S("ACCOUNT#1".into())),
+        ]
+        .into();
+
+        TransactWrite::new().operation(crate::model::Update::new(key).expression(
+            expr::Update::new("SET balance = balance - :amount").value(":amount", amount),
+        ))
+    }
+
+    #[test]
+    fn tokens_are_deterministic() {
+        let idempotency = Idempotency::new("payment-1");
+        let token = idempotency.token(&transaction(100));
+
+        assert_eq!(token.len(), 32);
+        assert_eq!(token, idempotency.token(&transaction(100)));
+        assert_eq!(
+            token,
+            Idempotency::new("payment-1").token(&transaction(100))
+        );
+        assert_ne!(token, idempotency.token(&transaction(200)));
+        assert_ne!(
+            token,
+            Idempotency::new("payment-2").token(&transaction(100))
+        );
+    }
+
+    #[test]
+    fn item_hashing_ignores_attribute_order() {
+        let mut a = TokenHasher::new("key");
+        let mut b = TokenHasher::new("key");
+
+        let mut item = Item::new();
+        for i in 0..32 {
+            item.insert(format!("attr{i}"), AttributeValue::N(i.to_string()));
+        }
+        let mut entries: Vec<_> = item.clone().into_iter().collect();
+        entries.reverse();
+        let reordered: Item = entries.into_iter().collect();
+
+        a.write_item(&item);
+        b.write_item(&reordered);
+        assert_eq!(a.finish(), b.finish());
+    }
+
+    #[test]
+    fn records_use_the_ttl_attribute() {
+        let idempotency = Idempotency::new("payment-1").ttl(Duration::from_secs(60));
+        let record = IdempotencyRecord::<TestTable> {
+            key: idempotency.key().to_string(),
+            token: "token".to_string(),
+            created_at: idempotency.created_at,
+            expires_at: idempotency.created_at + idempotency.ttl,
+            table: PhantomData,
+        };
+
+        let item = record.into_item();
+        assert_eq!(
+            item["PK"],
+            AttributeValue::S("IDEMPOTENCY#payment-1".into())
+        );
+        assert_eq!(
+            item["ttl"],
+            AttributeValue::N((idempotency.created_at.unix_timestamp() + 60).to_string())
+        );
+    }
+}
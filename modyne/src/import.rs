@@ -0,0 +1,447 @@
+//! Importing table contents from JSON Lines
+//!
+//! An [`Import`] reads items written as JSON Lines, such as those produced by
+//! an [`Export`][crate::export::Export], and writes them to a table. This is
+//! useful for seeding test environments from fixtures or cloning data
+//! between environments.
+//!
+//! Every record is validated before being written. The entity type of each
+//! record must have been registered with [`Import::entity`], the record must
+//! deserialize as that entity, and its key attributes must match those
+//! computed by [`Entity::full_key`]. Alternatively, [`Import::recompute_keys`]
+//! rewrites the key attributes of each record rather than rejecting records
+//! with stale keys.
+//!
+//! ```no_run
+//! # use modyne::{keys, Entity, EntityDef, EntityTypeNameRef, Table, WritableTable};
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! # impl WritableTable for App {}
+//! # #[derive(serde::Deserialize)]
+//! # struct Order { id: String }
+//! # impl EntityDef for Order {
+//! #     const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("order");
+//! # }
+//! # impl Entity for Order {
+//! #     type KeyInput<'a> = &'a str;
+//! #     type Table = App;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+//! #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+//! # }
+//! use modyne::{export::ExportFormat, import::Import};
+//!
+//! # async fn example(app: App) -> Result<(), modyne::Error> {
+//! let file = tokio::fs::File::open("orders.jsonl").await?;
+//! let report = Import::new(&app)
+//!     .format(ExportFormat::DynamoDb)
+//!     .entity::<Order>()
+//!     .execute(tokio::io::BufReader::new(file))
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Items are written in batches of up to 25 items. Items left unprocessed
+//! by DynamoDB, such as when throttled, are retried with exponential
+//! backoff.
+//!
+//! Each item is written with a put, replacing any item already stored with
+//! the same primary key, including any index key attributes it had. Items
+//! stored under a primary key that no record maps to, such as when
+//! re-importing records whose keys have since been recomputed, are left in
+//! place and must be removed separately.
+
+use std::{collections::HashMap, fmt};
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+use crate::{
+    backfill::recompute_keys,
+    error::InvalidRecordError,
+    export::ExportFormat,
+    json,
+    model::{batch_write_with_retry, BatchWrite, Put},
+    Entity, EntityTypeNameRef, Error, Item, Table, WritableTable,
+};
+
+const MAX_BATCH_SIZE: usize = 25;
+
+type RecomputeKeys = fn(Item) -> Result<Item, Error>;
+
+/// An import of items into a table
+#[must_use]
+pub struct Import<'a, T> {
+    table: &'a T,
+    format: ExportFormat,
+    entities: HashMap<&'static EntityTypeNameRef, RecomputeKeys>,
+    skip_unknown: bool,
+    recompute_keys: bool,
+    max_attempts: u32,
+}
+
+impl<'a, T> fmt::Debug for Import<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Import")
+            .field("format", &self.format)
+            .field("entities", &self.entities.keys().collect::<Vec<_>>())
+            .field("skip_unknown", &self.skip_unknown)
+            .field("recompute_keys", &self.recompute_keys)
+            .field("max_attempts", &self.max_attempts)
+            .finish()
+    }
+}
+
+impl<'a, T: WritableTable> Import<'a, T> {
+    /// Prepares an import of plain JSON records into the table
+    pub fn new(table: &'a T) -> Self {
+        Self {
+            table,
+            format: ExportFormat::default(),
+            entities: HashMap::new(),
+            skip_unknown: false,
+            recompute_keys: false,
+            max_attempts: 8,
+        }
+    }
+
+    /// Sets the format of each record
+    ///
+    /// Plain JSON values are converted to their natural DynamoDB
+    /// representations, so attributes that require type information, such as
+    /// binary values or sets, should be imported from DynamoDB JSON.
+    pub fn format(mut self, format: ExportFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Registers an entity type that may be imported
+    pub fn entity<E>(mut self) -> Self
+    where
+        E: Entity<Table = T> + serde::de::DeserializeOwned,
+    {
+        self.entities.insert(E::ENTITY_TYPE, recompute_keys::<E>);
+        self
+    }
+
+    /// Skips records with an unregistered entity type, rather than failing
+    pub fn skip_unknown(mut self) -> Self {
+        self.skip_unknown = true;
+        self
+    }
+
+    /// Rewrites the key attributes of each record, rather than failing when
+    /// they do not match the keys computed for the entity
+    pub fn recompute_keys(mut self) -> Self {
+        self.recompute_keys = true;
+        self
+    }
+
+    /// Sets the number of attempts made to write each batch before failing
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Executes the import, reading one record per line
+    ///
+    /// Blank lines are ignored. The import stops at the first invalid
+    /// record; batches written before that point are not rolled back.
+    pub async fn execute<R: AsyncBufRead + Unpin>(self, reader: R) -> Result<ImportReport, Error> {
+        let mut report = ImportReport::default();
+        let mut batch: Vec<Item> = Vec::with_capacity(MAX_BATCH_SIZE);
+
+        let mut lines = reader.lines();
+        let mut line_number = 0;
+        while let Some(line) = lines.next_line().await? {
+            line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            report.records += 1;
+            let Some(item) = self.validate(line_number, &line)? else {
+                report.skipped += 1;
+                continue;
+            };
+
+            let key = self.primary_key(&item);
+            if batch.iter().any(|other| self.primary_key(other) == key) {
+                report.imported += self.write(std::mem::take(&mut batch)).await?;
+            }
+
+            batch.push(item);
+            if batch.len() == MAX_BATCH_SIZE {
+                report.imported += self.write(std::mem::take(&mut batch)).await?;
+            }
+        }
+
+        if !batch.is_empty() {
+            report.imported += self.write(batch).await?;
+        }
+
+        Ok(report)
+    }
+
+    /// Parses and validates a record, returning `None` if it should be skipped
+    fn validate(&self, line: u64, record: &str) -> Result<Option<Item>, Error> {
+        let invalid = |reason: String| InvalidRecordError { line, reason };
+
+        let mut item = parse_record(record, self.format).map_err(invalid)?;
+
+        let entity_type = crate::__private::get_table_entity_type::<T>(&item)
+            .map_err(|error| invalid(describe(&error)))?;
+        let Some(recompute) = self.entities.get(entity_type) else {
+            if self.skip_unknown {
+                return Ok(None);
+            }
+            return Err(invalid(format!("unregistered entity type `{entity_type}`")).into());
+        };
+
+        let keys = recompute(item.clone()).map_err(|error| invalid(describe(&error)))?;
+        for attr in index_attributes::<T>() {
+            if keys.contains_key(attr) || !item.contains_key(attr) {
+                continue;
+            }
+            if !self.recompute_keys {
+                return Err(invalid(format!(
+                    "key attribute `{attr}` is not part of the computed key"
+                ))
+                .into());
+            }
+            item.remove(attr);
+        }
+        for (attr, value) in keys {
+            match item.get(&attr) {
+                Some(stored) if stored == &value => {}
+                _ if self.recompute_keys => {
+                    item.insert(attr, value);
+                }
+                _ => {
+                    return Err(invalid(format!(
+                        "key attribute `{attr}` does not match the computed key"
+                    ))
+                    .into());
+                }
+            }
+        }
+
+        Ok(Some(item))
+    }
+
+    fn primary_key(&self, item: &Item) -> Vec<Option<crate::AttributeValue>> {
+        use crate::keys::PrimaryKey;
+
+        let primary = T::PrimaryKey::PRIMARY_KEY_DEFINITION;
+        std::iter::once(primary.hash_key)
+            .chain(primary.range_key)
+            .map(|attr| item.get(attr).cloned())
+            .collect()
+    }
+
+    /// Writes a batch, retrying unprocessed items with exponential backoff
+    async fn write(&self, items: Vec<Item>) -> Result<u64, Error> {
+        let count = items.len() as u64;
+        let batch = items.into_iter().fold(BatchWrite::new(), |batch, item| {
+            batch.operation(Put::new(item))
+        });
+
+        batch_write_with_retry(self.table, batch, self.max_attempts).await?;
+        Ok(count)
+    }
+}
+
+fn index_attributes<T: Table>() -> impl Iterator<Item = &'static str> {
+    use crate::keys::IndexKeys;
+
+    T::IndexKeys::KEY_DEFINITIONS
+        .iter()
+        .flat_map(|index| std::iter::once(index.hash_key()).chain(index.range_key()))
+}
+
+fn parse_record(record: &str, format: ExportFormat) -> Result<Item, String> {
+    let value: serde_json::Value = serde_json::from_str(record).map_err(|e| e.to_string())?;
+    match format {
+        ExportFormat::Plain => crate::codec::to_item(value).map_err(|e| e.to_string()),
+        ExportFormat::DynamoDb => {
+            let serde_json::Value::Object(mut record) = value else {
+                return Err("expected a JSON object".to_string());
+            };
+            let item = match record.remove("Item") {
+                Some(serde_json::Value::Object(item)) if record.is_empty() => item,
+                _ => return Err("expected an object with a single `Item` field".to_string()),
+            };
+            json::from_dynamodb_json(item).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// Describes the underlying causes of an error
+fn describe(error: &Error) -> String {
+    let mut causes = Vec::new();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        causes.push(cause.to_string());
+        source = cause.source();
+    }
+    causes.join(": ")
+}
+
+/// A summary of the records processed by an [`Import`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ImportReport {
+    /// The number of records read
+    pub records: u64,
+
+    /// The number of items written to the table
+    pub imported: u64,
+
+    /// The number of records skipped due to an unregistered entity type
+    pub skipped: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{keys, AttributeValue, EntityDef};
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            "TestTable"
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    impl WritableTable for TestTable {}
+
+    #[derive(serde::Deserialize)]
+    struct Order {
+        id: String,
+    }
+
+    impl EntityDef for Order {
+        const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("order");
+    }
+
+    impl Entity for Order {
+        type KeyInput<'a> = &'a str;
+        type Table = TestTable;
+        type IndexKeys = ();
+
+        fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+            keys::Primary {
+                hash: format!("ORDER#{id}"),
+                range: "ORDER".to_string(),
+            }
+        }
+
+        fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+            Self::primary_key(&self.id).into()
+        }
+    }
+
+    const VALID: &str = r#"{"PK":"ORDER#1","SK":"ORDER","entity_type":"order","id":"1"}"#;
+    const STALE: &str = r#"{"PK":"ORDER#1","SK":"OLD","entity_type":"order","id":"1"}"#;
+    const INDEXED: &str =
+        r#"{"PK":"ORDER#1","SK":"ORDER","GSI1PK":"X","GSI1SK":"Y","entity_type":"order","id":"1"}"#;
+    const UNKNOWN: &str = r#"{"PK":"USER#1","SK":"USER","entity_type":"user"}"#;
+
+    #[test]
+    fn accepts_valid_records() {
+        let import = Import::new(&TestTable).entity::<Order>();
+        let item = import.validate(1, VALID).unwrap().unwrap();
+        assert_eq!(item["PK"], AttributeValue::S("ORDER#1".into()));
+    }
+
+    #[test]
+    fn rejects_stale_keys_unless_recomputing() {
+        let import = Import::new(&TestTable).entity::<Order>();
+        let error = import.validate(3, STALE).unwrap_err();
+        assert_eq!(
+            describe(&error),
+            "invalid record on line 3: key attribute `SK` does not match the computed key"
+        );
+
+        let import = import.recompute_keys();
+        let item = import.validate(3, STALE).unwrap().unwrap();
+        assert_eq!(item["SK"], AttributeValue::S("ORDER".into()));
+    }
+
+    #[test]
+    fn rejects_stale_index_keys_unless_recomputing() {
+        let import = Import::new(&TestTable).entity::<Order>();
+        let error = import.validate(2, INDEXED).unwrap_err();
+        assert_eq!(
+            describe(&error),
+            "invalid record on line 2: key attribute `GSI1PK` is not part of the computed key"
+        );
+
+        let import = import.recompute_keys();
+        let item = import.validate(2, INDEXED).unwrap().unwrap();
+        assert!(!item.contains_key("GSI1PK"));
+        assert!(!item.contains_key("GSI1SK"));
+        assert_eq!(item["PK"], AttributeValue::S("ORDER#1".into()));
+    }
+
+    #[test]
+    fn reads_records_asynchronously() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let records = format!("{UNKNOWN}\n\n{UNKNOWN}\n");
+        let import = Import::new(&TestTable).skip_unknown();
+        let report = runtime
+            .block_on(import.execute(records.as_bytes()))
+            .unwrap();
+        assert_eq!(report.records, 2);
+        assert_eq!(report.skipped, 2);
+
+        let records = format!("{UNKNOWN}\n\n{STALE}\n");
+        let import = Import::new(&TestTable).entity::<Order>().skip_unknown();
+        let error = runtime
+            .block_on(import.execute(records.as_bytes()))
+            .unwrap_err();
+        assert_eq!(
+            describe(&error),
+            "invalid record on line 3: key attribute `SK` does not match the computed key"
+        );
+    }
+
+    #[test]
+    fn unknown_entity_types_fail_unless_skipped() {
+        let import = Import::new(&TestTable).entity::<Order>();
+        assert!(import.validate(1, UNKNOWN).is_err());
+        assert!(import
+            .skip_unknown()
+            .validate(1, UNKNOWN)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn parses_dynamodb_json_records() {
+        let item = parse_record(
+            r#"{"Item":{"PK":{"S":"ORDER#1"},"count":{"N":"3"}}}"#,
+            ExportFormat::DynamoDb,
+        )
+        .unwrap();
+        assert_eq!(item["count"], AttributeValue::N("3".into()));
+
+        assert!(parse_record(r#"{"PK":{"S":"ORDER#1"}}"#, ExportFormat::DynamoDb).is_err());
+    }
+}
@@ -0,0 +1,607 @@
+//! Tracing instrumentation of DynamoDB operations
+//!
+//! Each DynamoDB operation executed by this crate is wrapped in a `tracing`
+//! span named `DynamoDB.<Operation>`, with attributes following the
+//! OpenTelemetry semantic conventions for DynamoDB. The version of the
+//! conventions used is selected with
+//! [`Table::SEMANTIC_CONVENTIONS`][crate::Table::SEMANTIC_CONVENTIONS].
+//!
+//! Regardless of the conventions selected, every span records:
+//!
+//! * `otel.status_code`, as `OK` or `ERROR` once the operation completes
+//! * `error.type`, the error code of a failed operation
+//! * `http.response.status_code`, the HTTP status of a failed operation, if
+//!   a response was received
+//! * `aws.dynamodb.table_names`
+//! * `aws.dynamodb.attributes_to_get`, for reads with a projection expression
+//! * `modyne.entity_types`, the entity types involved in the operation, if
+//!   known
+//!
+//! The entity types are known for single-item operations prepared through
+//! [`EntityExt`][crate::EntityExt] and for puts of entity items, as well as
+//! for queries and scans read into an aggregate, from the
+//! [entity types][crate::ProjectionSet::entity_types()] of its projection
+//! set. Recording the entity types alongside the consumed capacity allows
+//! read and write capacity to be attributed to each entity type sharing a
+//! table.
+//!
+//! Additional attributes can be recorded by overriding
+//! [`Table::record_span_attributes()`][crate::Table::record_span_attributes()].
+//! Because the fields of a span are fixed when it is created, only fields
+//! already declared on the span can be recorded. These are the fields
+//! listed above, those recorded by either version of the conventions, and
+//! the operation-specific `aws.dynamodb.*` fields.
+//!
+//! Operations that read or write items in a single partition also report
+//! the partition accessed to
+//! [`Table::record_partition_access()`][crate::Table::record_partition_access()],
+//! which can be forwarded to a [`PartitionHeat`][crate::heat::PartitionHeat]
+//! sampler to find hot partitions.
+//!
+//! Items skipped while reading into an aggregate, because their entity type
+//! is not part of the aggregate's projections, are counted by entity type
+//! and reported to
+//! [`Table::record_skipped_items()`][crate::Table::record_skipped_items()].
+//! Paginated queries and collected scans also return the
+//! [`SkippedItems`] alongside their aggregate.
+//!
+//! Key values recorded on spans are formatted by
+//! [`Table::redact_key()`][crate::Table::redact_key()], which can be
+//! overridden to keep sensitive values out of traces.
+//!
+//! ```
+//! use modyne::{instrumentation, keys, Item, Table};
+//!
+//! struct App;
+//!
+//! impl Table for App {
+//!     type PrimaryKey = keys::Primary;
+//!     type IndexKeys = keys::Gsi1;
+//!     fn table_name(&self) -> &str { unimplemented!() }
+//!     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//!
+//!     fn redact_key(key: &Item) -> String {
+//!         instrumentation::redacted_key(key)
+//!     }
+//! }
+//! ```
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use aws_sdk_dynamodb::error::{ProvideErrorMetadata, SdkError};
+use tracing::field;
+
+use crate::{expr, AttributeValue, EntityTypeName, EntityTypeNameRef, Item, Table};
+
+/// The version of the OpenTelemetry semantic conventions used for span attributes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SemanticConventions {
+    /// Semantic conventions v1.20
+    ///
+    /// Records `db.system`, `db.operation`, and `db.name`, with the table
+    /// name as the database name.
+    #[default]
+    V1_20,
+
+    /// Semantic conventions v1.26
+    ///
+    /// Records `db.system`, `db.operation.name`, and `db.collection.name`,
+    /// along with the `rpc.system`, `rpc.service`, and `rpc.method`
+    /// attributes of the AWS SDK conventions.
+    V1_26,
+}
+
+/// Creates the span for a DynamoDB operation, declaring the common fields
+/// and recording those that are known before the operation is sent
+///
+/// Any operation-specific fields are appended to the common fields.
+macro_rules! operation_span {
+    ($table:expr, $operation:literal $(, $($fields:tt)*)?) => {{
+        let table = $table;
+        let span = tracing::info_span!(
+            concat!("DynamoDB.", $operation),
+            span.kind = "client",
+            otel.status_code = tracing::field::Empty,
+            "error.type" = tracing::field::Empty,
+            http.response.status_code = tracing::field::Empty,
+            db.system = "dynamodb",
+            db.operation = tracing::field::Empty,
+            db.name = tracing::field::Empty,
+            db.operation.name = tracing::field::Empty,
+            db.collection.name = tracing::field::Empty,
+            rpc.system = tracing::field::Empty,
+            rpc.service = tracing::field::Empty,
+            rpc.method = tracing::field::Empty,
+            aws.dynamodb.table_names = tracing::field::Empty,
+            aws.dynamodb.attributes_to_get = tracing::field::Empty,
+            modyne.timeout_ms = tracing::field::Empty,
+            modyne.entity_types = tracing::field::Empty,
+            $($($fields)*)?
+        );
+        $crate::instrumentation::record_operation(&span, table, $operation);
+        span
+    }};
+}
+
+pub(crate) use operation_span;
+
+/// Records the attributes of the operation required by the table's semantic conventions
+pub(crate) fn record_operation<T: Table>(span: &tracing::Span, table: &T, operation: &'static str) {
+    let table_name = table.table_name();
+    match T::SEMANTIC_CONVENTIONS {
+        SemanticConventions::V1_20 => {
+            span.record("db.operation", operation);
+            span.record("db.name", table_name);
+        }
+        SemanticConventions::V1_26 => {
+            span.record("db.operation.name", operation);
+            span.record("db.collection.name", table_name);
+            span.record("rpc.system", "aws-api");
+            span.record("rpc.service", "DynamoDB");
+            span.record("rpc.method", operation);
+        }
+    }
+    span.record("aws.dynamodb.table_names", field::debug([table_name]));
+    table.record_span_attributes(span, operation);
+}
+
+/// Records the entity types involved in an operation, as a comma-separated list
+pub(crate) fn record_entity_types<'a>(
+    span: &tracing::Span,
+    entity_types: impl IntoIterator<Item = &'a EntityTypeNameRef>,
+) {
+    let mut recorded = String::new();
+    for entity_type in entity_types {
+        if !recorded.is_empty() {
+            recorded.push(',');
+        }
+        recorded.push_str(entity_type.as_str());
+    }
+
+    if !recorded.is_empty() {
+        span.record("modyne.entity_types", recorded.as_str());
+    }
+}
+
+/// An access to a single partition by an operation
+///
+/// Provided to [`Table::record_partition_access()`] for each operation
+/// that reads or writes items in a single partition.
+#[derive(Clone, Copy, Debug)]
+pub struct PartitionAccess<'a> {
+    operation: &'static str,
+    index_name: Option<&'static str>,
+    partition: &'a AttributeValue,
+}
+
+impl<'a> PartitionAccess<'a> {
+    /// Describes an access by the named operation to the partition with the
+    /// given key value, in the named index or the table itself
+    #[inline]
+    pub fn new(
+        operation: &'static str,
+        index_name: Option<&'static str>,
+        partition: &'a AttributeValue,
+    ) -> Self {
+        Self {
+            operation,
+            index_name,
+            partition,
+        }
+    }
+
+    /// The name of the DynamoDB operation, such as `GetItem` or `Query`
+    #[inline]
+    pub fn operation(&self) -> &'static str {
+        self.operation
+    }
+
+    /// The name of the index accessed, or `None` for the table itself
+    #[inline]
+    pub fn index_name(&self) -> Option<&'static str> {
+        self.index_name
+    }
+
+    /// The value of the partition key
+    #[inline]
+    pub fn partition(&self) -> &'a AttributeValue {
+        self.partition
+    }
+}
+
+/// Reports an access to the partition holding the given key to the table
+pub(crate) fn record_partition_access<T: Table>(table: &T, operation: &'static str, key: &Item) {
+    use crate::keys::PrimaryKey;
+
+    let hash_key = T::PrimaryKey::PRIMARY_KEY_DEFINITION.hash_key;
+    if let Some(partition) = key.get(hash_key) {
+        table.record_partition_access(&PartitionAccess::new(operation, None, partition));
+    }
+}
+
+/// Items skipped while reading into a projection set because their entity
+/// type is not part of the set
+///
+/// Items with an unknown entity type are skipped with a warning. Items
+/// skipped while reducing the results of a read into an aggregate are
+/// reported to [`Table::record_skipped_items()`], so that polluted
+/// partitions can be measured. The items skipped by any other code can be
+/// counted with [`SkippedItems::track()`].
+///
+/// ```
+/// use modyne::{instrumentation::SkippedItems, EntityTypeNameRef};
+///
+/// let mut skipped = SkippedItems::new();
+/// skipped.record(EntityTypeNameRef::from_static("legacy_order"));
+/// skipped.record(EntityTypeNameRef::from_static("legacy_order"));
+///
+/// assert_eq!(skipped.total(), 2);
+/// assert_eq!(skipped.count(EntityTypeNameRef::from_static("legacy_order")), 2);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SkippedItems {
+    entity_types: HashMap<EntityTypeName, u64>,
+}
+
+thread_local! {
+    static SKIPPED_ITEMS: RefCell<Option<SkippedItems>> = const { RefCell::new(None) };
+}
+
+impl SkippedItems {
+    /// Creates an empty summary
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs the closure, counting the items it skips on this thread
+    ///
+    /// Items are skipped synchronously while they are parsed, so the
+    /// closure should parse or reduce the items itself, rather than
+    /// spawning work elsewhere. When tracking is nested, the items skipped
+    /// are counted by both the inner and outer tracking.
+    pub fn track<R>(f: impl FnOnce() -> R) -> (R, Self) {
+        /// Restores the outer tracking, even if the closure panics
+        struct Scope(Option<Option<SkippedItems>>);
+
+        impl Scope {
+            fn finish(&mut self) -> SkippedItems {
+                let outer = self.0.take().flatten();
+                SKIPPED_ITEMS.with(|skipped| {
+                    let inner = skipped.replace(outer).unwrap_or_default();
+                    if let Some(outer) = skipped.borrow_mut().as_mut() {
+                        outer.merge(&inner);
+                    }
+                    inner
+                })
+            }
+        }
+
+        impl Drop for Scope {
+            fn drop(&mut self) {
+                if self.0.is_some() {
+                    self.finish();
+                }
+            }
+        }
+
+        let outer = SKIPPED_ITEMS.with(|skipped| skipped.replace(Some(Self::new())));
+        let mut scope = Scope(Some(outer));
+        let result = f();
+        (result, scope.finish())
+    }
+
+    /// Counts an item skipped with the given entity type
+    pub fn record(&mut self, entity_type: &EntityTypeNameRef) {
+        match self.entity_types.get_mut(entity_type) {
+            Some(count) => *count += 1,
+            None => {
+                self.entity_types.insert(entity_type.to_owned(), 1);
+            }
+        }
+    }
+
+    /// Adds the items counted by another summary
+    pub fn merge(&mut self, other: &Self) {
+        for (entity_type, count) in &other.entity_types {
+            *self.entity_types.entry(entity_type.clone()).or_default() += count;
+        }
+    }
+
+    /// Whether no items were skipped
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entity_types.is_empty()
+    }
+
+    /// The number of items skipped
+    pub fn total(&self) -> u64 {
+        self.entity_types.values().sum()
+    }
+
+    /// The number of items skipped with the given entity type
+    pub fn count(&self, entity_type: &EntityTypeNameRef) -> u64 {
+        self.entity_types.get(entity_type).copied().unwrap_or(0)
+    }
+
+    /// The entity types of the skipped items, with the number skipped,
+    /// ordered from the most skipped
+    pub fn entity_types(&self) -> Vec<(&EntityTypeNameRef, u64)> {
+        let mut entity_types: Vec<_> = self
+            .entity_types
+            .iter()
+            .map(|(entity_type, &count)| (&**entity_type, count))
+            .collect();
+        entity_types.sort_unstable_by(|(l_type, l), (r_type, r)| {
+            r.cmp(l).then_with(|| l_type.as_str().cmp(r_type.as_str()))
+        });
+        entity_types
+    }
+}
+
+/// Warns that an item was skipped due to its unknown entity type, counting
+/// it if the skipped items are being tracked
+pub(crate) fn skip_unknown_entity_type(entity_type: &EntityTypeNameRef) {
+    tracing::warn!(entity_type = entity_type.as_str(), "unknown entity type");
+    SKIPPED_ITEMS.with(|skipped| {
+        if let Some(skipped) = skipped.borrow_mut().as_mut() {
+            skipped.record(entity_type);
+        }
+    });
+}
+
+/// Runs the closure, reporting the items it skips to the table
+pub(crate) fn report_skipped_items<T: Table, R>(
+    table: &T,
+    operation: &'static str,
+    f: impl FnOnce() -> R,
+) -> (R, SkippedItems) {
+    let (result, skipped) = SkippedItems::track(f);
+    if !skipped.is_empty() {
+        table.record_skipped_items(operation, &skipped);
+    }
+    (result, skipped)
+}
+
+/// Records the attributes projected by a read
+pub(crate) fn record_projection(span: &tracing::Span, projection: Option<&expr::StaticProjection>) {
+    let Some(projection) = projection else {
+        return;
+    };
+
+    let attributes: Vec<&str> = projection.attribute_names().collect();
+    span.record("aws.dynamodb.attributes_to_get", field::debug(attributes));
+}
+
+/// Formats a key with its values redacted
+///
+/// Attribute names are retained. For string values, any prefix up to and
+/// including the first `#` is also retained, so that the type of the keyed
+/// item remains visible; `CUSTOMER#alexdebrie` is formatted as
+/// `CUSTOMER#<redacted>`. All other values are fully redacted.
+pub fn redacted_key(key: &Item) -> String {
+    let redacted: BTreeMap<&str, String> = key
+        .iter()
+        .map(|(name, value)| {
+            let value = match value {
+                AttributeValue::S(s) => match s.split_once('#') {
+                    Some((prefix, _)) => format!("{prefix}#<redacted>"),
+                    None => "<redacted>".to_string(),
+                },
+                _ => "<redacted>".to_string(),
+            };
+            (name.as_str(), value)
+        })
+        .collect();
+    format!("{redacted:?}")
+}
+
+/// Formats an item, or a map of expression attribute names or values, with
+/// its entries sorted by key
+///
+/// The `Debug` output of a `HashMap` lists its entries in an arbitrary order,
+/// which differs between runs. The sorted form is used wherever items and
+/// maps are recorded on spans, so that traces can be diffed and asserted on.
+/// Map and list attribute values are formatted recursively, so nested maps
+/// are sorted as well.
+///
+/// ```
+/// use modyne::{instrumentation, AttributeValue, Item};
+///
+/// let item: Item = [
+///     ("b".to_string(), AttributeValue::N("2".into())),
+///     ("a".to_string(), AttributeValue::S("1".into())),
+/// ]
+/// .into();
+///
+/// assert_eq!(
+///     format!("{:?}", instrumentation::sorted(&item)),
+///     r#"{"a": S("1"), "b": N("2")}"#
+/// );
+/// ```
+#[inline]
+pub fn sorted<T: ?Sized>(value: &T) -> Sorted<'_, T> {
+    Sorted(value)
+}
+
+/// A value formatted with the entries of its maps sorted by key
+///
+/// Created by [`sorted()`].
+pub struct Sorted<'a, T: ?Sized>(&'a T);
+
+impl<S> fmt::Debug for Sorted<'_, HashMap<String, String, S>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut entries: Vec<_> = self.0.iter().collect();
+        entries.sort_unstable_by_key(|(k, _)| *k);
+        f.debug_map().entries(entries).finish()
+    }
+}
+
+impl<S> fmt::Debug for Sorted<'_, HashMap<String, AttributeValue, S>> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut entries: Vec<_> = self.0.iter().map(|(k, v)| (k, Sorted(v))).collect();
+        entries.sort_unstable_by_key(|(k, _)| *k);
+        f.debug_map().entries(entries).finish()
+    }
+}
+
+impl<T> fmt::Debug for Sorted<'_, Option<T>>
+where
+    for<'v> Sorted<'v, T>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.as_ref().map(Sorted).fmt(f)
+    }
+}
+
+impl fmt::Debug for Sorted<'_, AttributeValue> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            AttributeValue::M(map) => f.debug_tuple("M").field(&Sorted(map)).finish(),
+            AttributeValue::L(list) => f
+                .debug_tuple("L")
+                .field(&list.iter().map(Sorted).collect::<Vec<_>>())
+                .finish(),
+            value => value.fmt(f),
+        }
+    }
+}
+
+impl fmt::Debug for Sorted<'_, String> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Records the status of a completed operation
+pub(crate) fn record_outcome<O, E>(span: &tracing::Span, result: &Result<O, SdkError<E>>)
+where
+    E: ProvideErrorMetadata,
+{
+    match result {
+        Ok(_) => {
+            span.record("otel.status_code", "OK");
+        }
+        Err(error) => {
+            span.record("otel.status_code", "ERROR");
+            span.record("error.type", error.code().unwrap_or(sdk_error_kind(error)));
+            if let Some(response) = error.raw_response() {
+                span.record("http.response.status_code", response.status().as_u16());
+            }
+        }
+    }
+}
+
+fn sdk_error_kind<E>(error: &SdkError<E>) -> &'static str {
+    match error {
+        SdkError::ConstructionFailure(_) => "ConstructionFailure",
+        SdkError::TimeoutError(_) => "TimeoutError",
+        SdkError::DispatchFailure(_) => "DispatchFailure",
+        SdkError::ResponseError(_) => "ResponseError",
+        SdkError::ServiceError(_) => "ServiceError",
+        _ => "SdkError",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacted_key_retains_names_and_prefixes() {
+        let key: Item = [
+            (
+                "PK".to_string(),
+                AttributeValue::S("CUSTOMER#alex@example.com".into()),
+            ),
+            (
+                "SK".to_string(),
+                AttributeValue::S("alex@example.com".into()),
+            ),
+            ("N".to_string(), AttributeValue::N("42".into())),
+        ]
+        .into();
+
+        assert_eq!(
+            redacted_key(&key),
+            r#"{"N": "<redacted>", "PK": "CUSTOMER#<redacted>", "SK": "<redacted>"}"#
+        );
+    }
+
+    #[test]
+    fn skipped_items_are_tracked_by_entity_type() {
+        let legacy = EntityTypeNameRef::from_static("legacy");
+        let stray = EntityTypeNameRef::from_static("stray");
+
+        skip_unknown_entity_type(legacy);
+        let ((), outer) = SkippedItems::track(|| {
+            skip_unknown_entity_type(stray);
+            let ((), inner) = SkippedItems::track(|| {
+                skip_unknown_entity_type(legacy);
+                skip_unknown_entity_type(legacy);
+            });
+            assert_eq!(inner.entity_types(), [(legacy, 2)]);
+        });
+
+        assert_eq!(outer.total(), 3);
+        assert_eq!(outer.entity_types(), [(legacy, 2), (stray, 1)]);
+        SKIPPED_ITEMS.with(|skipped| assert!(skipped.borrow().is_none()));
+    }
+
+    #[test]
+    fn sorted_items_order_nested_maps() {
+        let nested: Item = [
+            ("z".to_string(), AttributeValue::Bool(true)),
+            ("y".to_string(), AttributeValue::Null(true)),
+        ]
+        .into();
+        let item: Item = [
+            (
+                "list".to_string(),
+                AttributeValue::L(vec![AttributeValue::M(nested.clone())]),
+            ),
+            ("map".to_string(), AttributeValue::M(nested)),
+        ]
+        .into();
+
+        assert_eq!(
+            format!("{:?}", sorted(&item)),
+            r#"{"list": L([M({"y": Null(true), "z": Bool(true)})]), "map": M({"y": Null(true), "z": Bool(true)})}"#
+        );
+    }
+
+    #[test]
+    fn default_key_format_is_ordered() {
+        struct TestTable;
+
+        impl Table for TestTable {
+            type PrimaryKey = crate::keys::Primary;
+            type IndexKeys = crate::keys::Gsi1;
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+        }
+
+        let key: Item = [
+            ("SK".to_string(), AttributeValue::S("B".into())),
+            ("PK".to_string(), AttributeValue::S("A".into())),
+        ]
+        .into();
+
+        assert_eq!(
+            TestTable::redact_key(&key),
+            r#"{"PK": S("A"), "SK": S("B")}"#
+        );
+    }
+}
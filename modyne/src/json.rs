@@ -0,0 +1,210 @@
+//! Conversions between DynamoDB items and JSON
+
+use aws_sdk_dynamodb::primitives::Blob;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::de::Error as _;
+use serde_json::{Map, Number, Value};
+
+use crate::{AttributeValue, Item};
+
+/// Encodes an item as DynamoDB JSON, with each value tagged by its type
+///
+/// Fails if the item contains a value of a type unknown to this version of
+/// the SDK, rather than writing it out as something it is not.
+pub(crate) fn to_dynamodb_json(item: &Item) -> Result<Map<String, Value>, serde_json::Error> {
+    item.iter()
+        .map(|(name, value)| Ok((name.clone(), typed(value)?)))
+        .collect()
+}
+
+/// Decodes an item from DynamoDB JSON
+pub(crate) fn from_dynamodb_json(item: Map<String, Value>) -> Result<Item, serde_json::Error> {
+    item.into_iter()
+        .map(|(name, value)| Ok((name, from_typed(value)?)))
+        .collect()
+}
+
+/// Encodes an item as plain JSON, without type information
+///
+/// Binary values are encoded as base64 strings, and sets as arrays.
+pub(crate) fn to_plain_json(item: &Item) -> Result<Map<String, Value>, serde_json::Error> {
+    item.iter()
+        .map(|(name, value)| Ok((name.clone(), plain(value)?)))
+        .collect()
+}
+
+/// Encodes a single value as DynamoDB JSON
+pub(crate) fn typed(value: &AttributeValue) -> Result<Value, serde_json::Error> {
+    let (tag, value) = match value {
+        AttributeValue::S(s) => ("S", Value::String(s.clone())),
+        AttributeValue::N(n) => ("N", Value::String(n.clone())),
+        AttributeValue::B(b) => ("B", Value::String(STANDARD.encode(b))),
+        AttributeValue::Bool(b) => ("BOOL", Value::Bool(*b)),
+        AttributeValue::Null(n) => ("NULL", Value::Bool(*n)),
+        AttributeValue::Ss(ss) => ("SS", ss.iter().cloned().map(Value::String).collect()),
+        AttributeValue::Ns(ns) => ("NS", ns.iter().cloned().map(Value::String).collect()),
+        AttributeValue::Bs(bs) => (
+            "BS",
+            bs.iter()
+                .map(|b| Value::String(STANDARD.encode(b)))
+                .collect(),
+        ),
+        AttributeValue::L(l) => ("L", l.iter().map(typed).collect::<Result<_, _>>()?),
+        AttributeValue::M(m) => ("M", Value::Object(to_dynamodb_json(m)?)),
+        _ => return Err(unknown_value(value)),
+    };
+
+    Ok(Value::Object(Map::from_iter([(tag.to_string(), value)])))
+}
+
+/// Decodes a single value from DynamoDB JSON
+pub(crate) fn from_typed(value: Value) -> Result<AttributeValue, serde_json::Error> {
+    let Value::Object(map) = value else {
+        return Err(serde_json::Error::custom(
+            "expected a typed attribute value",
+        ));
+    };
+
+    let mut entries = map.into_iter();
+    let (Some((tag, value)), None) = (entries.next(), entries.next()) else {
+        return Err(serde_json::Error::custom(
+            "expected exactly one type tag on attribute value",
+        ));
+    };
+
+    let value = match tag.as_str() {
+        "S" => AttributeValue::S(serde_json::from_value(value)?),
+        "N" => AttributeValue::N(serde_json::from_value(value)?),
+        "B" => AttributeValue::B(decode_binary(value)?),
+        "BOOL" => AttributeValue::Bool(serde_json::from_value(value)?),
+        "NULL" => AttributeValue::Null(serde_json::from_value(value)?),
+        "SS" => AttributeValue::Ss(serde_json::from_value(value)?),
+        "NS" => AttributeValue::Ns(serde_json::from_value(value)?),
+        "BS" => AttributeValue::Bs(
+            serde_json::from_value::<Vec<Value>>(value)?
+                .into_iter()
+                .map(decode_binary)
+                .collect::<Result<_, _>>()?,
+        ),
+        "L" => AttributeValue::L(
+            serde_json::from_value::<Vec<Value>>(value)?
+                .into_iter()
+                .map(from_typed)
+                .collect::<Result<_, _>>()?,
+        ),
+        "M" => AttributeValue::M(from_dynamodb_json(serde_json::from_value(value)?)?),
+        tag => {
+            return Err(serde_json::Error::custom(format_args!(
+                "unknown attribute value type `{tag}`"
+            )))
+        }
+    };
+
+    Ok(value)
+}
+
+fn decode_binary(value: Value) -> Result<Blob, serde_json::Error> {
+    let encoded: String = serde_json::from_value(value)?;
+    STANDARD
+        .decode(encoded)
+        .map(Blob::new)
+        .map_err(serde_json::Error::custom)
+}
+
+fn plain(value: &AttributeValue) -> Result<Value, serde_json::Error> {
+    let value = match value {
+        AttributeValue::S(s) => Value::String(s.clone()),
+        AttributeValue::N(n) => number(n),
+        AttributeValue::B(b) => Value::String(STANDARD.encode(b)),
+        AttributeValue::Bool(b) => Value::Bool(*b),
+        AttributeValue::Null(_) => Value::Null,
+        AttributeValue::Ss(ss) => ss.iter().cloned().map(Value::String).collect(),
+        AttributeValue::Ns(ns) => ns.iter().map(|n| number(n)).collect(),
+        AttributeValue::Bs(bs) => bs
+            .iter()
+            .map(|b| Value::String(STANDARD.encode(b)))
+            .collect(),
+        AttributeValue::L(l) => l.iter().map(plain).collect::<Result<_, _>>()?,
+        AttributeValue::M(m) => Value::Object(to_plain_json(m)?),
+        _ => return Err(unknown_value(value)),
+    };
+
+    Ok(value)
+}
+
+fn unknown_value(value: &AttributeValue) -> serde_json::Error {
+    serde_json::Error::custom(format_args!(
+        "cannot encode attribute value of unknown type: {value:?}"
+    ))
+}
+
+/// Converts a DynamoDB number, falling back to a string if it cannot be
+/// represented as a JSON number without loss
+fn number(n: &str) -> Value {
+    n.parse::<Number>()
+        .ok()
+        .filter(|parsed| parsed.to_string() == n)
+        .map_or_else(|| Value::String(n.to_string()), Value::Number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Item {
+        [
+            ("PK".to_string(), AttributeValue::S("USER#1".into())),
+            ("count".to_string(), AttributeValue::N("42".into())),
+            ("ratio".to_string(), AttributeValue::N("0.1".into())),
+            (
+                "data".to_string(),
+                AttributeValue::B(Blob::new(b"hi".to_vec())),
+            ),
+            ("deleted".to_string(), AttributeValue::Null(true)),
+            (
+                "tags".to_string(),
+                AttributeValue::Ss(vec!["a".into(), "b".into()]),
+            ),
+            (
+                "nested".to_string(),
+                AttributeValue::M(
+                    [(
+                        "list".to_string(),
+                        AttributeValue::L(vec![AttributeValue::Bool(true)]),
+                    )]
+                    .into(),
+                ),
+            ),
+        ]
+        .into()
+    }
+
+    #[test]
+    fn dynamodb_json_round_trips() {
+        let item = sample();
+        let json = to_dynamodb_json(&item).unwrap();
+        assert_eq!(json["data"], serde_json::json!({ "B": "aGk=" }));
+        assert_eq!(json["count"], serde_json::json!({ "N": "42" }));
+        assert_eq!(from_dynamodb_json(json).unwrap(), item);
+    }
+
+    #[test]
+    fn plain_json_drops_type_information() {
+        let json = Value::Object(to_plain_json(&sample()).unwrap());
+        assert_eq!(json["PK"], "USER#1");
+        assert_eq!(json["count"], 42);
+        assert_eq!(json["ratio"], 0.1);
+        assert_eq!(json["data"], "aGk=");
+        assert_eq!(json["deleted"], Value::Null);
+        assert_eq!(json["nested"]["list"][0], true);
+    }
+
+    #[test]
+    fn rejects_unknown_type_tags() {
+        let json = serde_json::json!({ "PK": { "X": "value" } });
+        let Value::Object(json) = json else {
+            unreachable!()
+        };
+        assert!(from_dynamodb_json(json).is_err());
+    }
+}
@@ -9,7 +9,11 @@
 //! key.
 //!
 //! However, when used for a query or scan operation, the partition key
-//! must be provided.
+//! must be provided. Use `for_partition_of()` to derive it from the
+//! primary key of an entity, so that the two cannot drift apart. In debug
+//! builds, converting an entity into an item asserts that any LSI partition
+//! key provided by its [`full_key()`][crate::Entity::full_key()] matches the
+//! table's partition key.
 //!
 //! # Example
 //!
@@ -47,6 +51,79 @@
 //! assert_eq!(full_key["PK"].as_s().unwrap(), "PART#ABCD");
 //! assert_eq!(full_key["LSI1SK"].as_s().unwrap(), "LSI1#9876");
 //! ```
+//!
+//! Deriving the partition key for an LSI from an entity's primary key:
+//!
+//! ```
+//! use modyne::{keys, Entity, EntityDef};
+//! # struct App;
+//! # impl modyne::Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Lsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//!
+//! #[derive(EntityDef, serde::Serialize, serde::Deserialize)]
+//! struct Device {
+//!     device_id: String,
+//! }
+//!
+//! impl Entity for Device {
+//!     type KeyInput<'a> = &'a str;
+//!     type Table = App;
+//!     type IndexKeys = keys::Lsi1;
+//!
+//!     fn primary_key(device_id: Self::KeyInput<'_>) -> keys::Primary {
+//!         keys::Primary {
+//!             hash: format!("DEVICE#{device_id}"),
+//!             range: format!("DEVICE#{device_id}"),
+//!         }
+//!     }
+//!
+//!     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+//!         keys::FullKey {
+//!             primary: Self::primary_key(&self.device_id),
+//!             indexes: keys::Lsi1::for_partition_of::<Self>(self.device_id.as_str(), "DEVICE".into()),
+//!         }
+//!     }
+//! }
+//!
+//! let lsi = keys::Lsi1::for_partition_of::<Device>("ABCD", "READING#".into());
+//! assert_eq!(lsi.hash, "DEVICE#ABCD");
+//! ```
+//!
+//! # Overloaded global secondary indexes
+//!
+//! When several entity types share the attributes of a global secondary
+//! index, each giving them its own meaning, build their keys with
+//! [`Overloaded`]. Both key attributes are tagged with the entity type, and
+//! the key conditions it builds must name the entity type being queried, so
+//! that one entity type's items cannot be matched by accident when querying
+//! for another.
+//!
+//! # Non-string key attributes
+//!
+//! The built-in key types use string attributes, but each has a typed
+//! counterpart, such as [`TypedPrimary`] or [`TypedGsi1`], that allows
+//! using any [`KeyAttribute`] for its attributes. This permits using
+//! numeric sort keys, such as epoch timestamps, or [`Binary`] sort keys,
+//! with their native ordering semantics in range conditions.
+//!
+//! ```
+//! use modyne::keys::{self, KeyAttributeType, PrimaryKey};
+//!
+//! type EventKey = keys::TypedPrimary<String, u64>;
+//!
+//! let key = EventKey {
+//!     hash: "DEVICE#1234".to_string(),
+//!     range: 1_700_000_000,
+//! };
+//! assert_eq!(EventKey::ATTRIBUTE_TYPES.range, KeyAttributeType::Number);
+//!
+//! let key = key.into_key();
+//! assert_eq!(key["SK"].as_n().unwrap(), "1700000000");
+//! ```
 
 use crate::Item;
 
@@ -56,11 +133,146 @@ pub trait Key: Sized + serde::Serialize {
     const DEFINITION: KeyDefinition;
 }
 
+/// The scalar type of a key attribute
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub enum KeyAttributeType {
+    /// A string attribute (`S`)
+    String,
+
+    /// A number attribute (`N`)
+    Number,
+
+    /// A binary attribute (`B`)
+    Binary,
+}
+
+impl KeyAttributeType {
+    pub(crate) fn into_scalar(self) -> aws_sdk_dynamodb::types::ScalarAttributeType {
+        use aws_sdk_dynamodb::types::ScalarAttributeType;
+
+        match self {
+            Self::String => ScalarAttributeType::S,
+            Self::Number => ScalarAttributeType::N,
+            Self::Binary => ScalarAttributeType::B,
+        }
+    }
+}
+
+/// The scalar types of the hash and range attributes of a key
+///
+/// For keys without a range attribute, the range type is ignored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub struct KeyAttributeTypes {
+    /// The type of the hash attribute
+    pub hash: KeyAttributeType,
+
+    /// The type of the range attribute
+    pub range: KeyAttributeType,
+}
+
+impl KeyAttributeTypes {
+    /// Both attributes are strings
+    pub const STRING: Self = Self {
+        hash: KeyAttributeType::String,
+        range: KeyAttributeType::String,
+    };
+}
+
+/// A value that may be used as a key attribute
+pub trait KeyAttribute: serde::Serialize {
+    /// The scalar type of the attribute when serialized
+    const ATTRIBUTE_TYPE: KeyAttributeType;
+}
+
+impl KeyAttribute for String {
+    const ATTRIBUTE_TYPE: KeyAttributeType = KeyAttributeType::String;
+}
+
+macro_rules! number_key_attribute {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl KeyAttribute for $ty {
+                const ATTRIBUTE_TYPE: KeyAttributeType = KeyAttributeType::Number;
+            }
+        )*
+    };
+}
+
+number_key_attribute!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+/// A binary key attribute
+///
+/// A `Vec<u8>` is serialized as a list of numbers, so this wrapper is used
+/// to serialize bytes as a binary (`B`) attribute instead. Binary attributes
+/// are ordered by their unsigned bytes, making them suitable for values
+/// such as binary ULIDs.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub struct Binary(pub Vec<u8>);
+
+impl From<Vec<u8>> for Binary {
+    #[inline]
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl serde::Serialize for Binary {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Binary {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BinaryVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for BinaryVisitor {
+            type Value = Binary;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a binary attribute")
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Binary, E> {
+                Ok(Binary(bytes.to_vec()))
+            }
+
+            fn visit_byte_buf<E: serde::de::Error>(self, bytes: Vec<u8>) -> Result<Binary, E> {
+                Ok(Binary(bytes))
+            }
+        }
+
+        deserializer.deserialize_byte_buf(BinaryVisitor)
+    }
+}
+
+impl KeyAttribute for Binary {
+    const ATTRIBUTE_TYPE: KeyAttributeType = KeyAttributeType::Binary;
+}
+
 /// A set of keys used as secondary indexes
 pub trait IndexKeys: Sized {
     /// The definitions for the keys
     const KEY_DEFINITIONS: &'static [SecondaryIndexDefinition];
 
+    /// The types of the attributes of each key, in the same order as the definitions
+    ///
+    /// Keys without a corresponding entry are assumed to use string attributes.
+    const KEY_ATTRIBUTE_TYPES: &'static [KeyAttributeTypes] = &[];
+
+    /// Fails to evaluate if two of the keys target the same index with
+    /// conflicting definitions
+    ///
+    /// The same index may be listed more than once, as with `Gsi1` and
+    /// `Option<Gsi1>`, provided that each agrees on the kind of index and
+    /// the names and types of its key attributes. This is checked whenever
+    /// the keys are used to build an item or to create a table, so that a
+    /// conflict is reported as a compile-time error.
+    const ASSERT_CONSISTENT: () = assert!(
+        !has_conflicting_indexes(Self::KEY_DEFINITIONS, Self::KEY_ATTRIBUTE_TYPES),
+        "multiple index keys target the same secondary index with conflicting definitions"
+    );
+
     /// The intermediate type used to serialize the key
     type Serialize<'a>: serde::Serialize
     where
@@ -75,44 +287,143 @@ pub trait IndexKeys: Sized {
     }
 }
 
+/// Whether any two definitions share an index name but disagree on the kind
+/// of index or on the names or types of its key attributes
+const fn has_conflicting_indexes(
+    definitions: &[SecondaryIndexDefinition],
+    types: &[KeyAttributeTypes],
+) -> bool {
+    let mut i = 0;
+    while i < definitions.len() {
+        let mut j = i + 1;
+        while j < definitions.len() {
+            let (a, b) = (&definitions[i], &definitions[j]);
+            if str_eq(a.index_name(), b.index_name())
+                && !(same_index(a, b) && same_types(a, types_at(types, i), types_at(types, j)))
+            {
+                return true;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    false
+}
+
+const fn same_index(a: &SecondaryIndexDefinition, b: &SecondaryIndexDefinition) -> bool {
+    matches!(a, SecondaryIndexDefinition::Global(_))
+        == matches!(b, SecondaryIndexDefinition::Global(_))
+        && str_eq(a.hash_key(), b.hash_key())
+        && match (a.range_key(), b.range_key()) {
+            (Some(a), Some(b)) => str_eq(a, b),
+            (None, None) => true,
+            _ => false,
+        }
+}
+
+const fn same_types(
+    definition: &SecondaryIndexDefinition,
+    a: KeyAttributeTypes,
+    b: KeyAttributeTypes,
+) -> bool {
+    a.hash as u8 == b.hash as u8
+        && (definition.range_key().is_none() || a.range as u8 == b.range as u8)
+}
+
+const fn types_at(types: &[KeyAttributeTypes], index: usize) -> KeyAttributeTypes {
+    if index < types.len() {
+        types[index]
+    } else {
+        KeyAttributeTypes::STRING
+    }
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
 /// A DynamoDB primary key
 pub trait PrimaryKey: Sized + serde::Serialize {
     /// The definition for the primary key
     const PRIMARY_KEY_DEFINITION: PrimaryKeyDefinition;
 
+    /// The types of the key's attributes
+    const ATTRIBUTE_TYPES: KeyAttributeTypes = KeyAttributeTypes::STRING;
+
     /// Converts the key into a DynamoDB item
     fn into_key(self) -> Item {
         crate::codec::to_item(self).unwrap()
     }
 }
 
-/// The primary key for a DynamoDB table
-#[derive(Clone, Debug, serde::Serialize)]
-pub struct Primary {
+/// The primary key for a DynamoDB table, with string attributes
+pub type Primary = TypedPrimary<String, String>;
+
+/// The primary key for a DynamoDB table, with typed attributes
+#[derive(Clone, Debug, PartialEq, Eq, Ord, PartialOrd, serde::Serialize, serde::Deserialize)]
+pub struct TypedPrimary<H, R> {
     /// The partition key, with attribute name `PK`
     #[serde(rename = "PK")]
-    pub hash: String,
+    pub hash: H,
 
     /// The sort key, with attribute name `SK`
     #[serde(rename = "SK")]
-    pub range: String,
+    pub range: R,
 }
 
-impl PrimaryKey for Primary {
+impl<H: KeyAttribute, R: KeyAttribute> PrimaryKey for TypedPrimary<H, R> {
     const PRIMARY_KEY_DEFINITION: PrimaryKeyDefinition = PrimaryKeyDefinition {
         hash_key: "PK",
         range_key: Some("SK"),
     };
+
+    const ATTRIBUTE_TYPES: KeyAttributeTypes = KeyAttributeTypes {
+        hash: H::ATTRIBUTE_TYPE,
+        range: R::ATTRIBUTE_TYPE,
+    };
 }
 
-impl Key for Primary {
+impl<H: KeyAttribute, R: KeyAttribute> Key for TypedPrimary<H, R> {
     const DEFINITION: KeyDefinition = KeyDefinition::Primary(Self::PRIMARY_KEY_DEFINITION);
 }
 
+/// A primary key whose partition key can be shared with local secondary indexes
+pub trait PartitionedPrimaryKey: PrimaryKey {
+    /// The type of the partition key
+    type Partition;
+
+    /// Converts the key into its partition key
+    fn into_partition(self) -> Self::Partition;
+}
+
+impl<H: KeyAttribute, R: KeyAttribute> PartitionedPrimaryKey for TypedPrimary<H, R> {
+    type Partition = H;
+
+    #[inline]
+    fn into_partition(self) -> Self::Partition {
+        self.hash
+    }
+}
+
 /// A DynamoDB secondary index key
 pub trait IndexKey: Sized + serde::Serialize {
     /// The definition for the index
     const INDEX_DEFINITION: SecondaryIndexDefinition;
+
+    /// The types of the key's attributes
+    const ATTRIBUTE_TYPES: KeyAttributeTypes = KeyAttributeTypes::STRING;
 }
 
 impl<K: IndexKey> Key for K {
@@ -121,6 +432,7 @@ impl<K: IndexKey> Key for K {
 
 impl<K: IndexKey> IndexKey for Option<K> {
     const INDEX_DEFINITION: SecondaryIndexDefinition = K::INDEX_DEFINITION;
+    const ATTRIBUTE_TYPES: KeyAttributeTypes = K::ATTRIBUTE_TYPES;
 }
 
 /// The primary key for an item along with the relevant secondary index keys
@@ -146,8 +458,50 @@ where
 {
     /// Converts the key into a DynamoDB item
     pub fn into_key(self) -> Item {
+        let () = I::ASSERT_CONSISTENT;
         crate::codec::to_item(self).unwrap()
     }
+
+    /// Asserts that local secondary index keys use the table's partition key
+    ///
+    /// Local secondary index keys may leave the partition key empty, as it
+    /// is overridden by the primary key's partition key.
+    pub(crate) fn debug_assert_local_partitions(&self) {
+        if !cfg!(debug_assertions)
+            || !I::KEY_DEFINITIONS
+                .iter()
+                .any(|def| matches!(def, SecondaryIndexDefinition::Local(_)))
+        {
+            return;
+        }
+
+        let hash_key = P::PRIMARY_KEY_DEFINITION.hash_key;
+        let Some(local) = crate::codec::to_item(self.indexes.to_serialize())
+            .ok()
+            .and_then(|mut item| item.remove(hash_key))
+        else {
+            return;
+        };
+
+        let omitted = match &local {
+            crate::AttributeValue::S(s) => s.is_empty(),
+            crate::AttributeValue::N(n) => n == "0",
+            crate::AttributeValue::B(b) => b.as_ref().is_empty(),
+            _ => false,
+        };
+        if omitted {
+            return;
+        }
+
+        let primary = crate::codec::to_item(&self.primary)
+            .ok()
+            .and_then(|mut item| item.remove(hash_key));
+        assert!(
+            primary.as_ref() == Some(&local),
+            "the partition key of a local secondary index key ({local:?}) does not match the \
+             table's partition key ({primary:?})"
+        );
+    }
 }
 
 impl<P> From<P> for FullKey<P, ()>
@@ -172,90 +526,269 @@ where
 }
 
 macro_rules! gsi_key {
-    ($name:ident: $idx:literal, $pk:literal, $sk:literal) => {
-        /// The key for a global secondary index
-        #[derive(Clone, Debug, PartialEq, Eq, Ord, PartialOrd, serde::Serialize)]
-        pub struct $name {
+    ($name:ident, $typed:ident: $idx:literal, $pk:literal, $sk:literal) => {
+        /// The key for a global secondary index, with string attributes
+        pub type $name = $typed<String, String>;
+
+        /// The key for a global secondary index, with typed attributes
+        #[derive(
+            Clone, Debug, PartialEq, Eq, Ord, PartialOrd, serde::Serialize, serde::Deserialize,
+        )]
+        pub struct $typed<H, R> {
             #[doc = "The partition key, with attribute name `"]
             #[doc = $pk]
             #[doc = "`"]
             #[serde(rename = $pk)]
-            pub hash: String,
+            pub hash: H,
 
             #[doc = "The sort key, with attribute name `"]
-            #[doc = $pk]
+            #[doc = $sk]
             #[doc = "`"]
             #[serde(rename = $sk)]
-            pub range: String,
+            pub range: R,
         }
 
-        impl IndexKey for $name {
+        impl<H: KeyAttribute, R: KeyAttribute> IndexKey for $typed<H, R> {
             const INDEX_DEFINITION: SecondaryIndexDefinition =
                 SecondaryIndexDefinition::Global(GlobalSecondaryIndexDefinition {
                     index_name: $idx,
                     hash_key: $pk,
                     range_key: Some($sk),
                 });
+
+            const ATTRIBUTE_TYPES: KeyAttributeTypes = KeyAttributeTypes {
+                hash: H::ATTRIBUTE_TYPE,
+                range: R::ATTRIBUTE_TYPE,
+            };
+        }
+
+        impl OverloadableKey for $name {
+            #[inline]
+            fn from_tagged(hash: String, range: String) -> Self {
+                Self { hash, range }
+            }
         }
     };
 }
 
-gsi_key!(Gsi1: "GSI1", "GSI1PK", "GSI1SK");
-gsi_key!(Gsi2: "GSI2", "GSI2PK", "GSI2SK");
-gsi_key!(Gsi3: "GSI3", "GSI3PK", "GSI3SK");
-gsi_key!(Gsi4: "GSI4", "GSI4PK", "GSI4SK");
-gsi_key!(Gsi5: "GSI5", "GSI5PK", "GSI5SK");
-gsi_key!(Gsi6: "GSI6", "GSI6PK", "GSI6SK");
-gsi_key!(Gsi7: "GSI7", "GSI7PK", "GSI7SK");
-gsi_key!(Gsi8: "GSI8", "GSI8PK", "GSI8SK");
-gsi_key!(Gsi9: "GSI9", "GSI9PK", "GSI9SK");
-gsi_key!(Gsi10: "GSI10", "GSI10PK", "GSI10SK");
-gsi_key!(Gsi11: "GSI11", "GSI11PK", "GSI11SK");
-gsi_key!(Gsi12: "GSI12", "GSI12PK", "GSI12SK");
-gsi_key!(Gsi13: "GSI13", "GSI13PK", "GSI13SK");
-gsi_key!(Gsi14: "GSI14", "GSI14PK", "GSI14SK");
-gsi_key!(Gsi15: "GSI15", "GSI15PK", "GSI15SK");
-gsi_key!(Gsi16: "GSI16", "GSI16PK", "GSI16SK");
-gsi_key!(Gsi17: "GSI17", "GSI17PK", "GSI17SK");
-gsi_key!(Gsi18: "GSI18", "GSI18PK", "GSI18SK");
-gsi_key!(Gsi19: "GSI19", "GSI19PK", "GSI19SK");
-gsi_key!(Gsi20: "GSI20", "GSI20PK", "GSI20SK");
+gsi_key!(Gsi1, TypedGsi1: "GSI1", "GSI1PK", "GSI1SK");
+gsi_key!(Gsi2, TypedGsi2: "GSI2", "GSI2PK", "GSI2SK");
+gsi_key!(Gsi3, TypedGsi3: "GSI3", "GSI3PK", "GSI3SK");
+gsi_key!(Gsi4, TypedGsi4: "GSI4", "GSI4PK", "GSI4SK");
+gsi_key!(Gsi5, TypedGsi5: "GSI5", "GSI5PK", "GSI5SK");
+gsi_key!(Gsi6, TypedGsi6: "GSI6", "GSI6PK", "GSI6SK");
+gsi_key!(Gsi7, TypedGsi7: "GSI7", "GSI7PK", "GSI7SK");
+gsi_key!(Gsi8, TypedGsi8: "GSI8", "GSI8PK", "GSI8SK");
+gsi_key!(Gsi9, TypedGsi9: "GSI9", "GSI9PK", "GSI9SK");
+gsi_key!(Gsi10, TypedGsi10: "GSI10", "GSI10PK", "GSI10SK");
+gsi_key!(Gsi11, TypedGsi11: "GSI11", "GSI11PK", "GSI11SK");
+gsi_key!(Gsi12, TypedGsi12: "GSI12", "GSI12PK", "GSI12SK");
+gsi_key!(Gsi13, TypedGsi13: "GSI13", "GSI13PK", "GSI13SK");
+gsi_key!(Gsi14, TypedGsi14: "GSI14", "GSI14PK", "GSI14SK");
+gsi_key!(Gsi15, TypedGsi15: "GSI15", "GSI15PK", "GSI15SK");
+gsi_key!(Gsi16, TypedGsi16: "GSI16", "GSI16PK", "GSI16SK");
+gsi_key!(Gsi17, TypedGsi17: "GSI17", "GSI17PK", "GSI17SK");
+gsi_key!(Gsi18, TypedGsi18: "GSI18", "GSI18PK", "GSI18SK");
+gsi_key!(Gsi19, TypedGsi19: "GSI19", "GSI19PK", "GSI19SK");
+gsi_key!(Gsi20, TypedGsi20: "GSI20", "GSI20PK", "GSI20SK");
+
+/// A global secondary index key whose attributes can be overloaded with a
+/// different meaning for each entity type
+///
+/// Implemented for the global secondary index keys with string attributes,
+/// such as [`Gsi1`].
+pub trait OverloadableKey: IndexKey {
+    /// Constructs the key from attributes that have already been tagged
+    fn from_tagged(hash: String, range: String) -> Self;
+}
+
+/// A key for an overloaded global secondary index, tagged with the entity
+/// type whose meaning its attributes hold
+///
+/// In an overloaded index, different entity types store differently shaped
+/// values in the same key attributes. Both attributes of an overloaded key
+/// are prefixed with the entity type, so that a key built for one entity
+/// type never equals, nor shares a prefix with, a key built for another.
+/// Key conditions for overloaded indexes likewise name the entity type
+/// whose items are wanted, and only match items of that type, even when
+/// several entity types share a partition of the index.
+///
+/// ```
+/// use modyne::{keys, EntityDef, EntityTypeNameRef};
+///
+/// struct Order;
+///
+/// impl EntityDef for Order {
+///     const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("order");
+/// }
+///
+/// // In `Entity::full_key()`
+/// let key = keys::Overloaded::<keys::Gsi1>::for_entity::<Order>("alex", "2024-01-01").into_inner();
+/// assert_eq!(key.hash, "order#alex");
+/// assert_eq!(key.range, "order#2024-01-01");
+///
+/// // When querying the index, only orders in the partition are matched
+/// let condition = keys::Overloaded::<keys::Gsi1>::query::<Order>("alex");
+/// # let _ = condition;
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Overloaded<K> {
+    key: K,
+}
+
+impl<K: OverloadableKey> Overloaded<K> {
+    /// Constructs a key holding the meaning of the given entity type
+    pub fn for_entity<E: crate::EntityDef + ?Sized>(
+        hash: impl std::fmt::Display,
+        range: impl std::fmt::Display,
+    ) -> Self {
+        Self {
+            key: K::from_tagged(tag::<E>(hash), tag::<E>(range)),
+        }
+    }
+
+    /// Unwraps the tagged index key
+    #[inline]
+    pub fn into_inner(self) -> K {
+        self.key
+    }
+
+    /// A key condition matching every item of the entity type in the
+    /// given partition of the index
+    pub fn query<E: crate::EntityDef + ?Sized>(
+        hash: impl std::fmt::Display,
+    ) -> crate::expr::KeyCondition<K> {
+        crate::expr::KeyCondition::in_partition(tag::<E>(hash)).begins_with(tag::<E>(""))
+    }
+
+    /// A key condition matching the items of the entity type in the given
+    /// partition of the index whose sort key starts with the prefix
+    pub fn query_prefix<E: crate::EntityDef + ?Sized>(
+        hash: impl std::fmt::Display,
+        prefix: impl std::fmt::Display,
+    ) -> crate::expr::KeyCondition<K> {
+        crate::expr::KeyCondition::in_partition(tag::<E>(hash)).begins_with(tag::<E>(prefix))
+    }
+
+    /// A key condition matching the items of the entity type in the given
+    /// partition of the index with exactly the given sort key
+    pub fn query_item<E: crate::EntityDef + ?Sized>(
+        hash: impl std::fmt::Display,
+        range: impl std::fmt::Display,
+    ) -> crate::expr::KeyCondition<K> {
+        crate::expr::KeyCondition::in_partition(tag::<E>(hash)).specific_item(tag::<E>(range))
+    }
+
+    /// A key condition matching the items of the entity type in the given
+    /// partition of the index with sort keys between the start and end,
+    /// inclusive
+    pub fn query_between<E: crate::EntityDef + ?Sized>(
+        hash: impl std::fmt::Display,
+        start: impl std::fmt::Display,
+        end: impl std::fmt::Display,
+    ) -> crate::expr::KeyCondition<K> {
+        crate::expr::KeyCondition::in_partition(tag::<E>(hash))
+            .between(tag::<E>(start), tag::<E>(end))
+    }
+}
+
+/// Prefixes a value of an overloaded key attribute with the entity type
+fn tag<E: crate::EntityDef + ?Sized>(value: impl std::fmt::Display) -> String {
+    format!("{}#{value}", E::ENTITY_TYPE)
+}
+
+/// The key for a global secondary index that lists the entities of each type
+///
+/// Entities opt into this index by returning a sort key from
+/// [`Entity::entity_type_index_key()`][crate::Entity::entity_type_index_key()],
+/// in which case its attributes are populated automatically when the entity
+/// is converted into an item. To create the index, include this key in the
+/// table's [`IndexKeys`][crate::Table::IndexKeys]. See the
+/// [`entity_index`][crate::entity_index] module for details.
+#[derive(Clone, Debug, PartialEq, Eq, Ord, PartialOrd, serde::Serialize, serde::Deserialize)]
+pub struct EntityTypeIndex {
+    /// The entity type, with attribute name `ETPK`
+    #[serde(rename = "ETPK")]
+    pub hash: String,
+
+    /// The sort key of the entity within its type, with attribute name `ETSK`
+    #[serde(rename = "ETSK")]
+    pub range: String,
+}
+
+impl IndexKey for EntityTypeIndex {
+    const INDEX_DEFINITION: SecondaryIndexDefinition =
+        SecondaryIndexDefinition::Global(GlobalSecondaryIndexDefinition {
+            index_name: "EntityTypeIndex",
+            hash_key: "ETPK",
+            range_key: Some("ETSK"),
+        });
+}
 
 macro_rules! lsi_key {
-    ($name:ident: $idx:literal, $sk:literal) => {
-        /// The key for a local secondary index
+    ($name:ident, $typed:ident: $idx:literal, $sk:literal) => {
+        /// The key for a local secondary index, with string attributes
         ///
         /// See the [module documentation][crate::keys#Working_with_Local_Secondary_Indexes]
         /// for more information on how to use this type.
-        #[derive(Clone, Debug, PartialEq, Eq, Ord, PartialOrd, serde::Serialize)]
-        pub struct $name {
+        pub type $name = $typed<String, String>;
+
+        /// The key for a local secondary index, with typed attributes
+        ///
+        /// The partition key type should match that of the table's primary key.
+        #[derive(
+            Clone, Debug, PartialEq, Eq, Ord, PartialOrd, serde::Serialize, serde::Deserialize,
+        )]
+        pub struct $typed<H, R> {
             /// The partition key for the table, with attribute name `PK`
             #[serde(rename = "PK")]
-            pub hash: String,
+            pub hash: H,
 
             #[doc = "The sort key for the local secondary index, with attribute name `"]
             #[doc = $sk]
             #[doc = "`"]
             #[serde(rename = $sk)]
-            pub range: String,
+            pub range: R,
         }
 
-        impl IndexKey for $name {
+        impl<H: KeyAttribute, R: KeyAttribute> IndexKey for $typed<H, R> {
             const INDEX_DEFINITION: SecondaryIndexDefinition =
                 SecondaryIndexDefinition::Local(LocalSecondaryIndexDefinition {
                     index_name: $idx,
                     hash_key: "PK",
                     range_key: $sk,
                 });
+
+            const ATTRIBUTE_TYPES: KeyAttributeTypes = KeyAttributeTypes {
+                hash: H::ATTRIBUTE_TYPE,
+                range: R::ATTRIBUTE_TYPE,
+            };
+        }
+
+        impl<H, R> $typed<H, R> {
+            /// Construct a key in the partition of the entity with the given key input
+            ///
+            /// The partition key is derived from the entity's primary key, so that it
+            /// always matches the table's partition key.
+            pub fn for_partition_of<E>(input: E::KeyInput<'_>, range: R) -> Self
+            where
+                E: crate::Entity,
+                <E::Table as crate::Table>::PrimaryKey: PartitionedPrimaryKey<Partition = H>,
+            {
+                Self {
+                    hash: E::primary_key(input).into_partition(),
+                    range,
+                }
+            }
         }
     };
 }
 
-lsi_key!(Lsi1: "LSI1", "LSI1SK");
-lsi_key!(Lsi2: "LSI2", "LSI2SK");
-lsi_key!(Lsi3: "LSI3", "LSI3SK");
-lsi_key!(Lsi4: "LSI4", "LSI4SK");
-lsi_key!(Lsi5: "LSI5", "LSI5SK");
+lsi_key!(Lsi1, TypedLsi1: "LSI1", "LSI1SK");
+lsi_key!(Lsi2, TypedLsi2: "LSI2", "LSI2SK");
+lsi_key!(Lsi3, TypedLsi3: "LSI3", "LSI3SK");
+lsi_key!(Lsi4, TypedLsi4: "LSI4", "LSI4SK");
+lsi_key!(Lsi5, TypedLsi5: "LSI5", "LSI5SK");
 
 macro_rules! impl_key_tuples {
     ($i:ident; $($n:tt : $ty:ident),*$(,)?) => {
@@ -277,6 +810,11 @@ macro_rules! impl_key_tuples {
                     $ty::INDEX_DEFINITION,
                 )*
             ];
+            const KEY_ATTRIBUTE_TYPES: &'static [$crate::keys::KeyAttributeTypes] = &[
+                $(
+                    $ty::ATTRIBUTE_TYPES,
+                )*
+            ];
             type Serialize<'a> = $i<'a, $($ty),*>;
             #[inline]
             fn to_serialize(&self) -> Self::Serialize<'_> {
@@ -290,6 +828,7 @@ macro_rules! impl_key_tuples {
 
 impl<T: IndexKey> IndexKeys for T {
     const KEY_DEFINITIONS: &'static [SecondaryIndexDefinition] = &[T::INDEX_DEFINITION];
+    const KEY_ATTRIBUTE_TYPES: &'static [KeyAttributeTypes] = &[T::ATTRIBUTE_TYPES];
     type Serialize<'a> = &'a T
     where
         T: 'a;
@@ -524,6 +1063,41 @@ mod tests {
         assert_eq!(serialized["GSI1SK"], AttributeValue::S("range".to_string()));
     }
 
+    #[test]
+    fn overloaded_keys_are_tagged_with_their_entity_type() {
+        struct Order;
+        impl crate::EntityDef for Order {
+            const ENTITY_TYPE: &'static crate::EntityTypeNameRef =
+                crate::EntityTypeNameRef::from_static("order");
+        }
+
+        let key = Overloaded::<Gsi1>::for_entity::<Order>("alex", "2024").into_inner();
+        assert_eq!(key.hash, "order#alex");
+        assert_eq!(key.range, "order#2024");
+
+        let condition = Overloaded::<Gsi1>::query::<Order>("alex");
+        let values: std::collections::HashMap<_, _> =
+            condition.values().map(|(k, v)| (k, v.clone())).collect();
+        assert_eq!(
+            condition.expression(),
+            "#key_PK = :key_PK AND begins_with(#key_SK, :key_SK)"
+        );
+        assert_eq!(values[":key_PK"], AttributeValue::S("order#alex".into()));
+        assert_eq!(values[":key_SK"], AttributeValue::S("order#".into()));
+
+        let condition = Overloaded::<Gsi1>::query_between::<Order>("alex", "2023", "2024");
+        let values: std::collections::HashMap<_, _> =
+            condition.values().map(|(k, v)| (k, v.clone())).collect();
+        assert_eq!(
+            values[":key_SK_START"],
+            AttributeValue::S("order#2023".into())
+        );
+        assert_eq!(
+            values[":key_SK_END"],
+            AttributeValue::S("order#2024".into())
+        );
+    }
+
     #[test]
     fn test_lsi_key() {
         let key = Lsi1 {
@@ -578,4 +1152,115 @@ mod tests {
             AttributeValue::S("LSI3SK".to_string())
         );
     }
+
+    #[test]
+    fn repeated_indexes_must_agree() {
+        let () = <(Gsi1, Option<Gsi1>, Lsi1) as IndexKeys>::ASSERT_CONSISTENT;
+        assert!(!has_conflicting_indexes(
+            <(Gsi1, Option<Gsi1>, Lsi1)>::KEY_DEFINITIONS,
+            <(Gsi1, Option<Gsi1>, Lsi1)>::KEY_ATTRIBUTE_TYPES,
+        ));
+
+        assert!(has_conflicting_indexes(
+            <(Gsi1, TypedGsi1<String, u64>)>::KEY_DEFINITIONS,
+            <(Gsi1, TypedGsi1<String, u64>)>::KEY_ATTRIBUTE_TYPES,
+        ));
+
+        let renamed = GlobalSecondaryIndexDefinition {
+            index_name: "GSI1",
+            hash_key: "GSI2PK",
+            range_key: Some("GSI2SK"),
+        }
+        .into_index();
+        assert!(has_conflicting_indexes(
+            &[Gsi1::INDEX_DEFINITION, renamed],
+            &[]
+        ));
+
+        let local = LocalSecondaryIndexDefinition {
+            index_name: "GSI1",
+            hash_key: "GSI1PK",
+            range_key: "GSI1SK",
+        }
+        .into_index();
+        assert!(has_conflicting_indexes(
+            &[Gsi1::INDEX_DEFINITION, local],
+            &[]
+        ));
+    }
+
+    #[test]
+    fn omitted_local_partitions_are_accepted() {
+        let key = FullKey {
+            primary: Primary {
+                hash: "PK".to_string(),
+                range: "SK".to_string(),
+            },
+            indexes: Lsi1 {
+                hash: String::new(),
+                range: "LSI1SK".to_string(),
+            },
+        };
+        key.debug_assert_local_partitions();
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "does not match the table's partition key")]
+    fn mismatched_local_partitions_are_rejected() {
+        let key = FullKey {
+            primary: Primary {
+                hash: "PK".to_string(),
+                range: "SK".to_string(),
+            },
+            indexes: Lsi1 {
+                hash: "OTHER".to_string(),
+                range: "LSI1SK".to_string(),
+            },
+        };
+        key.debug_assert_local_partitions();
+    }
+
+    #[test]
+    fn test_typed_keys() {
+        let key = TypedPrimary {
+            hash: "hash".to_string(),
+            range: 42u64,
+        };
+        let serialized = key.into_key();
+        assert_eq!(serialized["PK"], AttributeValue::S("hash".to_string()));
+        assert_eq!(serialized["SK"], AttributeValue::N("42".to_string()));
+
+        let key = TypedGsi2 {
+            hash: "hash".to_string(),
+            range: Binary(vec![0x01, 0xff]),
+        };
+        let serialized = key.into_key();
+        assert_eq!(
+            serialized["GSI2SK"],
+            AttributeValue::B(aws_sdk_dynamodb::primitives::Blob::new(vec![0x01, 0xff]))
+        );
+    }
+
+    #[test]
+    fn test_key_attribute_types() {
+        assert_eq!(Primary::ATTRIBUTE_TYPES, KeyAttributeTypes::STRING);
+        assert_eq!(
+            <TypedLsi1<String, i64> as IndexKey>::ATTRIBUTE_TYPES,
+            KeyAttributeTypes {
+                hash: KeyAttributeType::String,
+                range: KeyAttributeType::Number,
+            }
+        );
+        assert_eq!(
+            <(Gsi1, TypedGsi2<String, Binary>)>::KEY_ATTRIBUTE_TYPES,
+            [
+                KeyAttributeTypes::STRING,
+                KeyAttributeTypes {
+                    hash: KeyAttributeType::String,
+                    range: KeyAttributeType::Binary,
+                },
+            ]
+        );
+    }
 }
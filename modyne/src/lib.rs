@@ -2,43 +2,110 @@
 #![warn(missing_docs)]
 #![deny(missing_debug_implementations)]
 #![deny(rustdoc::broken_intra_doc_links)]
-
+#![cfg_attr(test, allow(clippy::result_large_err))]
+
+pub mod aggregate;
+pub mod analysis;
+pub mod backfill;
+pub mod blob;
+pub mod bulk_update;
+pub mod cache;
+pub mod chunk;
+pub mod client;
+pub mod clock;
+mod de;
+pub mod entity_index;
 mod error;
+pub mod export;
 pub mod expr;
+#[cfg(any(test, feature = "testing"))]
+pub mod fixtures;
+pub mod heat;
+pub mod idempotency;
+pub mod import;
+pub mod instrumentation;
+mod json;
 pub mod keys;
+mod lru;
+#[cfg(any(test, feature = "testing"))]
+pub mod mock;
 pub mod model;
+pub mod naming;
+pub mod pagination;
+pub mod read_after_write;
+pub mod registry;
+pub mod saga;
+pub mod scope;
+pub mod session;
+pub mod size;
+pub mod stream;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod time_series;
 pub mod types;
+pub mod unique;
+pub mod write_buffer;
 
 use std::collections::HashMap;
 
 #[doc(inline)]
 pub use aws_sdk_dynamodb::types::AttributeValue;
 use keys::{IndexKeys, PrimaryKey};
-use model::{ConditionCheck, ConditionalPut, Delete, Get, Put, Query, Scan, Update};
+use model::{
+    ConditionCheck, ConditionalPut, ConditionalUpdate, CreateOrGet, Delete, Exists, Get,
+    IndexedQuery, Put, Query, ReadModifyWrite, Scan, TransactWrite, Update, UpdateWithExpr,
+};
 /// Derive macro for the [`trait@EntityDef`] trait
 ///
 /// This macro piggy-backs on the attributes used by the `serde_derive`
 /// crate. Note that using `flatten` will result in an empty projection
 /// expression, pulling _all_ attributes on the item because this macro
-/// cannot identify the field names used in the flattened structure.
+/// cannot identify the field names used in the flattened structure, unless
+/// those names are listed on the field with `#[entity(attributes(...))]`.
 #[cfg(feature = "derive")]
 pub use modyne_derive::EntityDef;
+/// Derive macro for the [`trait@IntoUpdate`] trait
+///
+/// Field names respect the `rename` and `rename_all` attributes used by the
+/// `serde_derive` crate. See [`trait@IntoUpdate`] for the `update`
+/// attributes that control how each field is assigned.
+#[cfg(feature = "derive")]
+pub use modyne_derive::IntoUpdate;
 /// Derive macro for the [`trait@Projection`] trait
 ///
 /// Like [`derive@EntityDef`], this macro piggy-backs on the attributes used by
 /// the `serde_derive` crate. Note that using `flatten` will result in
 /// an empty projection expression, pulling _all_ attributes on the item
 /// because this macro cannot identify the field names used in the
-/// flattened structure.
+/// flattened structure, unless those names are listed on the field with
+/// `#[entity(attributes("a", "b"))]`.
 ///
 /// Usage of this macro requires specifying the "parent" entity. For
 /// example, with an entity called `MyEntity`, the projection should
-/// have the following attribute: `#[entity(MyEntity)]`
+/// have the following attribute: `#[entity(MyEntity)]`. Generic projections
+/// may name a generic entity, such as `#[entity(Envelope<T>)]`; the check
+/// that the projected attributes exist on the entity is skipped for generic
+/// projections.
 #[cfg(feature = "derive")]
 pub use modyne_derive::Projection;
 use serde_dynamo::aws_sdk_dynamodb_1 as codec;
 
-pub use crate::error::{Error, MalformedEntityTypeError};
+pub use crate::error::{Error, ErrorContext, ErrorKind, MalformedEntityTypeError};
+
+/// Prepares an analysis of the key patterns used by the entities registered
+/// with a table
+///
+/// See the [`analysis`] module for details.
+pub fn analyze<T: registry::EntityRegistry>() -> analysis::Analysis<T> {
+    analysis::Analysis::new()
+}
+
+/// Computes the attributes that changed between two versions of an item
+///
+/// See [`expr::ItemDiff`] for details.
+pub fn diff(old: &Item, new: &Item) -> expr::ItemDiff {
+    expr::ItemDiff::new(old, new)
+}
 
 /// An alias for a DynamoDB item
 pub type Item = HashMap<String, AttributeValue>;
@@ -47,11 +114,79 @@ pub type Item = HashMap<String, AttributeValue>;
 #[aliri_braid::braid(serde)]
 pub struct EntityTypeName;
 
+/// The strategy used to identify the entity type of an item
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EntityDiscriminator {
+    /// The entity type is stored in the [`Table::ENTITY_TYPE_ATTRIBUTE`] attribute
+    Attribute,
+
+    /// The entity type is the prefix of a string attribute, up to the first
+    /// occurrence of the separator
+    ///
+    /// This is useful for adopting existing tables that distinguish entity
+    /// types by a key prefix. For example, with `attribute: "SK"` and
+    /// `separator: '#'`, an item with a sort key of `ORDER#1234` has the
+    /// entity type `ORDER`. No entity type attribute is written to items
+    /// when using this strategy.
+    Prefix {
+        /// The attribute holding the prefixed value
+        attribute: &'static str,
+
+        /// The separator following the entity type prefix
+        separator: char,
+    },
+}
+
 /// A description of a DynamoDB table
 pub trait Table {
     /// The attribute name used for storing the entity type
     const ENTITY_TYPE_ATTRIBUTE: &'static str = "entity_type";
 
+    /// The strategy used to identify the entity type of an item
+    const ENTITY_DISCRIMINATOR: EntityDiscriminator = EntityDiscriminator::Attribute;
+
+    /// The version of the OpenTelemetry semantic conventions followed by the
+    /// tracing spans of operations on this table
+    const SEMANTIC_CONVENTIONS: instrumentation::SemanticConventions =
+        instrumentation::SemanticConventions::V1_20;
+
+    /// The attribute holding the expiration time of items, if time to live is enabled
+    ///
+    /// DynamoDB deletes items some time after the epoch second stored in
+    /// this attribute has passed. Time to live is enabled on tables created
+    /// for testing with [`TestTableExt::update_time_to_live()`].
+    const TTL_ATTRIBUTE: Option<&'static str> = None;
+
+    /// Whether reads exclude items whose time to live has passed
+    ///
+    /// DynamoDB deletes expired items lazily, so they may still be read for
+    /// some time after they expire. When enabled for a table that declares a
+    /// [`TTL_ATTRIBUTE`][Table::TTL_ATTRIBUTE], queries and scans add a filter
+    /// keeping only items whose expiration time is later than the table's
+    /// [clock][Table::clock()], or that have no numeric expiration time, and
+    /// gets, including those in batches and transactions, treat an expired
+    /// item as not found. Generated projection expressions include the TTL
+    /// attribute.
+    ///
+    /// Gets with a custom projection that omits the TTL attribute cannot be
+    /// checked, and return the item as read.
+    const EXCLUDE_EXPIRED_ITEMS: bool = false;
+
+    /// The attribute holding the schema version of items, if schema versions are tracked
+    ///
+    /// Items of entity types with [schema upgrades][EntityDef::SCHEMA_UPGRADES]
+    /// are stamped with their current schema version in this attribute, and
+    /// items with an older version are upgraded before they are deserialized.
+    /// The attribute is also included in generated projection expressions.
+    const SCHEMA_VERSION_ATTRIBUTE: Option<&'static str> = None;
+
+    /// The view of changed items written to the table's stream, if a stream is enabled
+    ///
+    /// Tables created for testing with [`TestTableExt::create_table()`]
+    /// enable a stream with this view.
+    const STREAM_VIEW: Option<aws_sdk_dynamodb::types::StreamViewType> = None;
+
     /// The primary key to be used for the table
     type PrimaryKey: keys::PrimaryKey;
 
@@ -64,6 +199,29 @@ pub trait Table {
     /// Returns a reference to the DynamoDB client used by this table
     fn client(&self) -> &aws_sdk_dynamodb::Client;
 
+    /// Returns the client through which operations on this table are sent
+    ///
+    /// Defaults to the standard SDK client returned by
+    /// [`client()`][Table::client()]. Override this to send operations
+    /// through another [`DynamoClient`][client::DynamoClient], such as a
+    /// DynamoDB Accelerator (DAX) client. The standard client is still used
+    /// by [`TestTableExt`] to manage the table itself.
+    #[inline]
+    fn dynamo_client(&self) -> &dyn client::DynamoClient {
+        self.client()
+    }
+
+    /// Returns the scope applied to the partition keys of items in this table, if any
+    ///
+    /// When a scope is returned, every operation on the table transparently
+    /// prefixes partition keys on the way out and strips the prefix from
+    /// items that come back, isolating tenants that share the table. See
+    /// the [`scope`] module for details.
+    #[inline]
+    fn key_scope(&self) -> Option<&scope::KeyScope> {
+        None
+    }
+
     /// Deserializes the entity type from an attribute value
     ///
     /// In general, this function should not need to be overriden, but an override
@@ -89,6 +247,270 @@ pub trait Table {
     fn serialize_entity_type(entity_type: &EntityTypeNameRef) -> AttributeValue {
         AttributeValue::S(entity_type.to_string())
     }
+
+    /// Returns the cache serving point reads of items in this table, if any
+    ///
+    /// When a cache is returned, [`Get`] operations without a projection
+    /// expression are served from the cache when possible, and writes made
+    /// through modyne invalidate the cached items with the same keys. See
+    /// [`ItemCache`][cache::ItemCache] for details.
+    #[inline]
+    fn item_cache(&self) -> Option<&cache::ItemCache> {
+        None
+    }
+
+    /// Invoked after a write to an item in this table has succeeded
+    ///
+    /// The provided item contains the key attributes of the written item.
+    /// For single puts, this includes any secondary index keys present on the
+    /// item, and the hook is invoked again with the keys of the replaced item
+    /// if its secondary index keys differed. For updates, deletes, and writes
+    /// in transactions and batches, only the primary key is known. Items left
+    /// unprocessed by a batch write are not reported.
+    ///
+    /// This hook can be used to invalidate caches, such as an
+    /// [`AggregateCache`][cache::AggregateCache], that may hold the item.
+    #[inline]
+    fn after_write(&self, key: &Item) {
+        let _ = key;
+    }
+
+    /// Returns the clock used by time-dependent helpers operating on this table
+    ///
+    /// Defaults to the [system clock][clock::SystemClock]. Overriding this
+    /// with a [`FixedClock`][clock::FixedClock] allows tests of
+    /// time-dependent behavior to be deterministic.
+    #[inline]
+    fn clock(&self) -> &dyn clock::Clock {
+        &clock::SystemClock
+    }
+
+    /// Invoked when the tracing span for an operation on this table is created
+    ///
+    /// This hook can be used to record additional attributes on the span.
+    /// Only fields already declared on the span can be recorded; see the
+    /// [`instrumentation`] module for details.
+    #[inline]
+    fn record_span_attributes(&self, span: &tracing::Span, operation: &'static str) {
+        let _ = (span, operation);
+    }
+
+    /// Invoked when an operation on this table accesses a single partition
+    ///
+    /// Gets, puts, updates, and deletes access the partition holding the
+    /// item, and queries access the partition named by their key
+    /// condition. This hook can be forwarded to a
+    /// [`PartitionHeat`][heat::PartitionHeat] sampler to find hot partitions.
+    #[inline]
+    fn record_partition_access(&self, access: &instrumentation::PartitionAccess<'_>) {
+        let _ = access;
+    }
+
+    /// Invoked when items with an unknown entity type are skipped while
+    /// reading the results of an operation on this table into an aggregate
+    ///
+    /// Items with an entity type that is not part of the aggregate's
+    /// projections are skipped with a warning. This hook can be used to
+    /// count them by entity type, so that stray items polluting a partition
+    /// can be measured. It is not invoked for operations that skip no items.
+    /// See [`SkippedItems`][instrumentation::SkippedItems] for details.
+    #[inline]
+    fn record_skipped_items(
+        &self,
+        operation: &'static str,
+        skipped: &instrumentation::SkippedItems,
+    ) {
+        let _ = (operation, skipped);
+    }
+
+    /// Formats a key for the tracing spans of operations on this table
+    ///
+    /// Keys are recorded on spans as the `aws.dynamodb.key`,
+    /// `aws.dynamodb.exclusive_start_key`, and
+    /// `aws.dynamodb.key_condition_values` fields. By default, the key
+    /// attributes are formatted in full. If key values may contain
+    /// personally identifiable information, such as email addresses or
+    /// user names, this function can be overridden to redact those values,
+    /// for example by using [`instrumentation::redacted_key()`].
+    #[inline]
+    fn redact_key(key: &Item) -> String {
+        format!("{:?}", instrumentation::sorted(key))
+    }
+}
+
+/// A table that accepts writes
+///
+/// Puts, updates, deletes, batch writes, and write transactions can only
+/// be executed against tables that implement this marker trait, so it
+/// should be implemented alongside [`Table`] for any table that is
+/// written to:
+///
+/// ```
+/// # use modyne::{keys, Table, WritableTable};
+/// # struct App;
+/// # impl Table for App {
+/// #     type PrimaryKey = keys::Primary;
+/// #     type IndexKeys = keys::Gsi1;
+/// #     fn table_name(&self) -> &str { unimplemented!() }
+/// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+/// # }
+/// impl WritableTable for App {}
+/// ```
+///
+/// A [`ReadOnly`] handle never implements this trait.
+pub trait WritableTable: Table {}
+
+/// A read-only handle to a table
+///
+/// Reads through the handle behave exactly as they would against the
+/// wrapped table, but the handle does not implement [`WritableTable`], so
+/// executing a write against it fails to compile. This is useful for
+/// handles to read replicas, or for analytics jobs that must never modify
+/// the table.
+///
+/// ```no_run
+/// # use modyne::{keys, model::Query, Error, ReadOnly, Table};
+/// # struct App;
+/// # impl Table for App {
+/// #     type PrimaryKey = keys::Primary;
+/// #     type IndexKeys = keys::Gsi1;
+/// #     fn table_name(&self) -> &str { unimplemented!() }
+/// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+/// # }
+/// # async fn example(app: App) -> Result<(), Error> {
+/// let replica = ReadOnly::new(app);
+///
+/// let orders = Query::<keys::Primary>::new(
+///     modyne::expr::KeyCondition::in_partition("CUSTOMER#alex"),
+/// )
+/// .execute(&replica)
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Writes through the handle are rejected by the compiler:
+///
+/// ```compile_fail
+/// # use modyne::{keys, model::Delete, Error, ReadOnly, Table, WritableTable};
+/// # struct App;
+/// # impl Table for App {
+/// #     type PrimaryKey = keys::Primary;
+/// #     type IndexKeys = keys::Gsi1;
+/// #     fn table_name(&self) -> &str { unimplemented!() }
+/// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+/// # }
+/// # impl WritableTable for App {}
+/// # async fn example(app: App) -> Result<(), Error> {
+/// let replica = ReadOnly::new(app);
+///
+/// # let key = modyne::Item::new();
+/// Delete::new(key).execute(&replica).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ReadOnly<T> {
+    table: T,
+}
+
+impl<T: Table> ReadOnly<T> {
+    /// Wraps the table in a read-only handle
+    #[inline]
+    pub fn new(table: T) -> Self {
+        Self { table }
+    }
+
+    /// Returns a reference to the wrapped table
+    #[inline]
+    pub fn inner(&self) -> &T {
+        &self.table
+    }
+
+    /// Unwraps the handle, returning the wrapped table
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.table
+    }
+}
+
+impl<T: Table> Table for ReadOnly<T> {
+    const ENTITY_TYPE_ATTRIBUTE: &'static str = T::ENTITY_TYPE_ATTRIBUTE;
+    const ENTITY_DISCRIMINATOR: EntityDiscriminator = T::ENTITY_DISCRIMINATOR;
+    const SEMANTIC_CONVENTIONS: instrumentation::SemanticConventions = T::SEMANTIC_CONVENTIONS;
+    const TTL_ATTRIBUTE: Option<&'static str> = T::TTL_ATTRIBUTE;
+    const EXCLUDE_EXPIRED_ITEMS: bool = T::EXCLUDE_EXPIRED_ITEMS;
+    const SCHEMA_VERSION_ATTRIBUTE: Option<&'static str> = T::SCHEMA_VERSION_ATTRIBUTE;
+    const STREAM_VIEW: Option<aws_sdk_dynamodb::types::StreamViewType> = T::STREAM_VIEW;
+
+    type PrimaryKey = T::PrimaryKey;
+    type IndexKeys = T::IndexKeys;
+
+    #[inline]
+    fn table_name(&self) -> &str {
+        self.table.table_name()
+    }
+
+    #[inline]
+    fn client(&self) -> &aws_sdk_dynamodb::Client {
+        self.table.client()
+    }
+
+    #[inline]
+    fn dynamo_client(&self) -> &dyn client::DynamoClient {
+        self.table.dynamo_client()
+    }
+
+    #[inline]
+    fn key_scope(&self) -> Option<&scope::KeyScope> {
+        self.table.key_scope()
+    }
+
+    #[inline]
+    fn item_cache(&self) -> Option<&cache::ItemCache> {
+        self.table.item_cache()
+    }
+
+    #[inline]
+    fn deserialize_entity_type(
+        attr: &AttributeValue,
+    ) -> Result<&EntityTypeNameRef, MalformedEntityTypeError> {
+        T::deserialize_entity_type(attr)
+    }
+
+    #[inline]
+    fn serialize_entity_type(entity_type: &EntityTypeNameRef) -> AttributeValue {
+        T::serialize_entity_type(entity_type)
+    }
+
+    #[inline]
+    fn clock(&self) -> &dyn clock::Clock {
+        self.table.clock()
+    }
+
+    #[inline]
+    fn record_span_attributes(&self, span: &tracing::Span, operation: &'static str) {
+        self.table.record_span_attributes(span, operation);
+    }
+
+    #[inline]
+    fn record_partition_access(&self, access: &instrumentation::PartitionAccess<'_>) {
+        self.table.record_partition_access(access);
+    }
+
+    #[inline]
+    fn record_skipped_items(
+        &self,
+        operation: &'static str,
+        skipped: &instrumentation::SkippedItems,
+    ) {
+        self.table.record_skipped_items(operation, skipped);
+    }
+
+    #[inline]
+    fn redact_key(key: &Item) -> String {
+        T::redact_key(key)
+    }
 }
 
 /// The name and attribute definition for an [`Entity`]
@@ -139,49 +561,132 @@ pub trait Table {
 ///
 /// If a field is marked with serde's `flatten` modifier, then the projected
 /// attributes array will be empty due to the inability of the derive macro
-/// to inspect the fields that are available on the flattened type.
-pub trait EntityDef {
-    /// The name of the entity type
-    ///
-    /// This value will be used to set the `entity_type` attribute on
-    /// all items of this entity type in the DynamoDB table and should
-    /// be unique across all entity types in the table.
-    const ENTITY_TYPE: &'static EntityTypeNameRef;
-
-    /// The set of attributes that are projected into the entity
-    ///
-    /// By default, all attributes, including the index keys, are
-    /// projected into the entity. This can be overridden to only
-    /// project the subset of attributes that are needed for the
-    /// entity's use cases.
-    ///
-    /// Use of this attribute is optional, but recommended. If not
-    /// specified, then any aggregate that uses this entity type will
-    /// return the entire item from DynamoDB, which can lead to
-    /// unnecessary network and deserialization overhead.
-    const PROJECTED_ATTRIBUTES: &'static [&'static str] = &[];
-}
-
-/// An entity in a DynamoDB table
+/// to inspect the fields that are available on the flattened type. The
+/// attributes of the flattened type can instead be listed on the field with
+/// `#[entity(attributes(...))]`, using their serialized names.
 ///
-/// This trait is used to define the structure of an entity type in a
-/// DynamoDB table and how the entity may be queried.
+/// ```
+/// use modyne::EntityDef;
 ///
-/// Projections of the entity can be defined using the [`Projection`] trait.
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Address {
+///     street: String,
+///     city: String,
+/// }
 ///
-/// # Example
+/// #[derive(EntityDef)]
+/// struct Customer {
+///     user_name: String,
+///     #[serde(flatten)]
+///     #[entity(attributes("street", "city"))]
+///     address: Address,
+/// }
 ///
-/// Here we define a simple order entity type. To support write patterns, the
-/// order's primary key only requires the order's ID. However, to support an
-/// access pattern where we want to query all orders for a given user, we
-/// define a global secondary index with a partition key of `USER#<user_id>`
-/// and a sort key that includes the order's date, which allows us to more
-/// efficiently query for recent orders for a given user.
+/// assert_eq!(
+///     Customer::PROJECTED_ATTRIBUTES,
+///     &["user_name", "street", "city"],
+/// );
+/// ```
+///
+/// ## Enums
+///
+/// The derive macro may also be used on enums, in which case the projected
+/// attributes are the union of the attributes used by each variant,
+/// following serde's enum representation. For an internally tagged enum,
+/// this is the `tag` attribute along with the fields of each struct
+/// variant. For an adjacently tagged enum, this is the `tag` and `content`
+/// attributes, and for an externally tagged enum, this is the name of each
+/// non-unit variant. Variant-level `rename` and `rename_all` attributes and
+/// the container-level `rename_all_fields` attribute are respected.
+///
+/// As with `flatten`, if any variant's attributes cannot be identified,
+/// such as a newtype variant in an internally tagged or untagged enum,
+/// then the projected attributes array will be empty. Key inputs cannot be
+/// generated for enums.
+///
+/// ```
+/// use modyne::EntityDef;
+///
+/// #[derive(EntityDef)]
+/// #[serde(tag = "kind", rename_all = "snake_case", rename_all_fields = "camelCase")]
+/// enum Payment {
+///     Card { card_number: String, amount: u32 },
+///     BankTransfer { account_number: String, amount: u32 },
+///     Voucher,
+/// }
+///
+/// assert_eq!(
+///     Payment::PROJECTED_ATTRIBUTES,
+///     &["kind", "cardNumber", "amount", "accountNumber"],
+/// );
+/// ```
+///
+/// ## Key inputs
+///
+/// Fields marked with `#[entity(key)]` are used to generate a borrowed
+/// key input struct, named `{Entity}KeyInput` by default, along with a
+/// `key_input()` method on the entity that borrows those fields. The
+/// generated struct can then be used as the [`Entity::KeyInput`].
+///
+/// Fields are borrowed as `&'a T` by default, with `String` fields
+/// borrowed as `&'a str`. Use `#[entity(key(borrow = "TRef"))]` to borrow
+/// through [`Borrow`][std::borrow::Borrow], such as when using a braid's
+/// reference type, or `#[entity(key(copy))]` to copy the value. The name of
+/// the generated struct can be set with `#[entity(key_input = "Name")]`.
+///
+/// ```
+/// use modyne::EntityDef;
+///
+/// #[derive(EntityDef)]
+/// #[entity(key_input = "OrderId")]
+/// struct Order {
+///     #[entity(key)]
+///     customer: String,
+///     #[entity(key(copy))]
+///     order_number: u64,
+///     total: u32,
+/// }
+///
+/// let order = Order {
+///     customer: "alexdebrie".into(),
+///     order_number: 1234,
+///     total: 100,
+/// };
+///
+/// let OrderId { customer, order_number } = order.key_input();
+/// assert_eq!(customer, "alexdebrie");
+/// assert_eq!(order_number, 1234);
+/// ```
+///
+/// ## Unique attributes
+///
+/// Attributes marked with `#[entity(unique = "...")]` on the container are
+/// listed in [`UNIQUE_ATTRIBUTES`][EntityDef::UNIQUE_ATTRIBUTES], using the
+/// attribute's serialized name. The attribute may be repeated to constrain
+/// several attributes independently.
+///
+/// ```
+/// use modyne::EntityDef;
+///
+/// #[derive(EntityDef)]
+/// #[entity(unique = "email")]
+/// #[serde(rename_all = "camelCase")]
+/// struct Customer {
+///     user_name: String,
+///     email: String,
+/// }
+///
+/// assert_eq!(Customer::UNIQUE_ATTRIBUTES, &["email"]);
+/// ```
+///
+/// Similarly, secondary indexes named with `#[entity(unique_index = "...")]`
+/// are listed in [`UNIQUE_INDEXES`][EntityDef::UNIQUE_INDEXES], so that no
+/// two entities of the type share the same key in that index. Each index
+/// must be one of the entity's [index keys][Entity::IndexKeys], which is
+/// checked at compile time.
 ///
 /// ```
 /// use modyne::{keys, Entity, EntityDef};
-/// # use time::format_description::well_known::Rfc3339;
-/// #
 /// # struct App;
 /// # impl modyne::Table for App {
 /// #     type PrimaryKey = keys::Primary;
@@ -190,38 +695,455 @@ pub trait EntityDef {
 /// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
 /// # }
 ///
-/// #[derive(Debug, EntityDef, serde::Serialize, serde::Deserialize)]
-/// struct Order {
-///     user_id: String,
-///     order_id: String,
-///     #[serde(with = "time::serde::rfc3339")]
-///     order_date: time::OffsetDateTime,
-///     items: Vec<OrderItem>,
-/// }
-///
-/// #[derive(Debug, serde::Serialize, serde::Deserialize)]
-/// struct OrderItem {
-///     item_id: String,
-///     quantity: u32,
-/// }
-///
-/// struct OrderKeyInput<'a> {
-///     order_id: &'a str,
+/// #[derive(EntityDef, serde::Serialize, serde::Deserialize)]
+/// #[entity(unique_index = "GSI1")]
+/// struct Session {
+///     session_id: String,
+///     token: String,
 /// }
 ///
-/// impl Entity for Order {
-///     type KeyInput<'a> = OrderKeyInput<'a>;
+/// impl Entity for Session {
+///     type KeyInput<'a> = &'a str;
 ///     type Table = App;
 ///     type IndexKeys = keys::Gsi1;
+/// #   fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+/// #       keys::Primary { hash: format!("SESSION#{id}"), range: format!("SESSION#{id}") }
+/// #   }
+/// #   fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+/// #       keys::FullKey {
+/// #           primary: Self::primary_key(&self.session_id),
+/// #           indexes: keys::Gsi1 { hash: format!("TOKEN#{}", self.token), range: "SESSION".into() },
+/// #       }
+/// #   }
+/// }
 ///
-///     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary {
-///         keys::Primary {
-///             hash: format!("ORDER#{}", input.order_id),
-///             range: format!("ORDER#{}", input.order_id),
-///         }
-///     }
+/// assert_eq!(Session::UNIQUE_INDEXES, &["GSI1"]);
+/// ```
 ///
-///     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+/// Naming an index that is not one of the entity's index keys fails to compile.
+///
+/// ```compile_fail
+/// use modyne::{keys, Entity, EntityDef};
+/// # struct App;
+/// # impl modyne::Table for App {
+/// #     type PrimaryKey = keys::Primary;
+/// #     type IndexKeys = keys::Gsi1;
+/// #     fn table_name(&self) -> &str { unimplemented!() }
+/// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+/// # }
+///
+/// #[derive(EntityDef, serde::Serialize, serde::Deserialize)]
+/// #[entity(unique_index = "GSI2")]
+/// struct Session {
+///     session_id: String,
+///     token: String,
+/// }
+///
+/// impl Entity for Session {
+///     type KeyInput<'a> = &'a str;
+///     type Table = App;
+///     type IndexKeys = keys::Gsi1;
+/// #   fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+/// #       keys::Primary { hash: format!("SESSION#{id}"), range: format!("SESSION#{id}") }
+/// #   }
+/// #   fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+/// #       keys::FullKey {
+/// #           primary: Self::primary_key(&self.session_id),
+/// #           indexes: keys::Gsi1 { hash: format!("TOKEN#{}", self.token), range: "SESSION".into() },
+/// #       }
+/// #   }
+/// }
+/// ```
+///
+/// ## Field attributes
+///
+/// The name of each field is paired with the name of the attribute it is
+/// serialized to in [`FIELD_ATTRIBUTES`][EntityDef::FIELD_ATTRIBUTES],
+/// respecting `serde` renames. Flattened fields are not included.
+///
+/// For structs without generic parameters, a struct named after the entity
+/// with a `Fields` suffix is also generated, holding a typed [`Field`] for
+/// each of those fields. It is available through the `FIELDS` constant on
+/// the entity, for use in typed conditions.
+///
+/// ```
+/// use modyne::EntityDef;
+///
+/// #[derive(EntityDef)]
+/// #[serde(rename_all = "camelCase")]
+/// struct Customer {
+///     user_name: String,
+///     #[serde(rename = "emailAddress")]
+///     email: String,
+/// }
+///
+/// assert_eq!(
+///     Customer::FIELD_ATTRIBUTES,
+///     &[("user_name", "userName"), ("email", "emailAddress")],
+/// );
+/// assert_eq!(Customer::FIELDS.email.attribute(), "emailAddress");
+/// ```
+///
+/// ## Transient fields
+///
+/// Fields marked with `#[entity(transient)]` are kept only in memory. They
+/// are listed in [`TRANSIENT_ATTRIBUTES`][EntityDef::TRANSIENT_ATTRIBUTES],
+/// left out of the item written to DynamoDB, and excluded from the projected
+/// and field attributes. As the attribute is never stored, the field must
+/// accept a default when read, using `#[serde(default)]` on the field or the
+/// container.
+///
+/// ```
+/// use modyne::EntityDef;
+///
+/// #[derive(EntityDef)]
+/// struct Customer {
+///     user_name: String,
+///     #[entity(transient)]
+///     #[serde(default)]
+///     cache_hits: u32,
+/// }
+///
+/// assert_eq!(Customer::PROJECTED_ATTRIBUTES, &["user_name"]);
+/// assert_eq!(Customer::TRANSIENT_ATTRIBUTES, &["cache_hits"]);
+/// ```
+///
+/// ## Set fields
+///
+/// DynamoDB does not allow empty sets, so a field holding a
+/// [`StringSet`][types::StringSet], [`NumberSet`][types::NumberSet], or
+/// [`BinarySet`][types::BinarySet] must be skipped when it is empty, with
+/// `#[serde(skip_serializing_if = "...")]`, `#[serde(skip_serializing)]`, or
+/// `#[serde(skip)]`. Deriving fails for a set field without one of these,
+/// rather than failing when an empty set is written.
+///
+/// ```
+/// use modyne::{types::StringSet, EntityDef};
+///
+/// #[derive(EntityDef)]
+/// struct Post {
+///     #[serde(default, skip_serializing_if = "StringSet::is_empty")]
+///     tags: StringSet<String>,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use modyne::{types::StringSet, EntityDef};
+///
+/// #[derive(EntityDef)]
+/// struct Post {
+///     tags: StringSet<String>,
+/// }
+/// ```
+///
+/// ## Schema upgrades
+///
+/// Functions named with `#[entity(upgrade = "...")]` on the container are
+/// listed in [`SCHEMA_UPGRADES`][EntityDef::SCHEMA_UPGRADES] in the order
+/// given, the first upgrading items from schema version `0` to version `1`.
+///
+/// ```
+/// use modyne::{EntityDef, Item};
+///
+/// #[derive(EntityDef)]
+/// #[entity(upgrade = "rename_email")]
+/// struct Customer {
+///     user_name: String,
+///     email_address: String,
+/// }
+///
+/// fn rename_email(mut item: Item) -> Item {
+///     if let Some(email) = item.remove("email") {
+///         item.insert("email_address".into(), email);
+///     }
+///     item
+/// }
+///
+/// assert_eq!(Customer::SCHEMA_UPGRADES.len(), 1);
+/// ```
+///
+/// ## Generic entities
+///
+/// The derive macro may be used on generic types, with the bounds and
+/// where clauses of the type carried over to the generated implementation.
+/// By default, every instantiation shares the entity type name derived from
+/// the type's identifier. Where each instantiation needs its own name, an
+/// expression evaluating to a `&'static EntityTypeNameRef` can be given with
+/// `#[entity(entity_type = ...)]`, which may refer to the type parameters.
+/// Key fields may not use the type parameters, as the generated key input
+/// struct is not generic.
+///
+/// ```
+/// use modyne::{EntityDef, EntityTypeNameRef};
+///
+/// trait Payload {
+///     const ENVELOPE_TYPE: &'static EntityTypeNameRef;
+/// }
+///
+/// struct Invoice;
+///
+/// impl Payload for Invoice {
+///     const ENVELOPE_TYPE: &'static EntityTypeNameRef =
+///         EntityTypeNameRef::from_static("invoice_envelope");
+/// }
+///
+/// #[derive(EntityDef)]
+/// #[entity(entity_type = T::ENVELOPE_TYPE)]
+/// struct Envelope<T: Payload> {
+///     #[entity(key)]
+///     message_id: String,
+///     payload: T,
+/// }
+///
+/// assert_eq!(
+///     Envelope::<Invoice>::ENTITY_TYPE,
+///     EntityTypeNameRef::from_static("invoice_envelope"),
+/// );
+/// assert_eq!(
+///     Envelope::<Invoice>::PROJECTED_ATTRIBUTES,
+///     &["message_id", "payload"],
+/// );
+/// ```
+pub trait EntityDef {
+    /// The name of the entity type
+    ///
+    /// This value will be used to set the table's
+    /// [`ENTITY_TYPE_ATTRIBUTE`][Table::ENTITY_TYPE_ATTRIBUTE] on all
+    /// items of this entity type in the DynamoDB table and should be
+    /// unique across all entity types in the table.
+    const ENTITY_TYPE: &'static EntityTypeNameRef;
+
+    /// The set of attributes that are projected into the entity
+    ///
+    /// By default, all attributes, including the index keys, are
+    /// projected into the entity. This can be overridden to only
+    /// project the subset of attributes that are needed for the
+    /// entity's use cases.
+    ///
+    /// Use of this attribute is optional, but recommended. If not
+    /// specified, then any aggregate that uses this entity type will
+    /// return the entire item from DynamoDB, which can lead to
+    /// unnecessary network and deserialization overhead.
+    const PROJECTED_ATTRIBUTES: &'static [&'static str] = &[];
+
+    /// The set of attributes whose values must be unique across all
+    /// entities of this type
+    ///
+    /// Each value is claimed by a constraint item that is maintained
+    /// alongside the entity when using
+    /// [`create_with_derived()`][EntityExt::create_with_derived()],
+    /// [`replace_with_derived()`][EntityExt::replace_with_derived()], and
+    /// [`delete_with_unique()`][EntityExt::delete_with_unique()]. See the
+    /// [`unique`] module for details.
+    const UNIQUE_ATTRIBUTES: &'static [&'static str] = &[];
+
+    /// The names of the secondary indexes whose keys must be unique across
+    /// all entities of this type
+    ///
+    /// Each index key is claimed by a constraint item in the same way as a
+    /// [unique attribute value][EntityDef::UNIQUE_ATTRIBUTES]. Each name must
+    /// be the name of one of the entity's [index keys][Entity::IndexKeys].
+    const UNIQUE_INDEXES: &'static [&'static str] = &[];
+
+    /// The fields of the entity, paired with the names of the attributes
+    /// they are stored in
+    ///
+    /// When derived, each pair is also available as a typed [`Field`] for
+    /// use in conditions, such as
+    /// [`create_unless_attr_exists()`][EntityExt::create_unless_attr_exists()],
+    /// so that conditions follow any `serde` renames of the field.
+    const FIELD_ATTRIBUTES: &'static [(&'static str, &'static str)] = &[];
+
+    /// The attributes that are held only in memory and never persisted
+    ///
+    /// These attributes are removed from the item produced by
+    /// [`into_item()`][EntityExt::into_item()], so the corresponding fields
+    /// must be able to take a default value when the entity is read back,
+    /// such as with `#[serde(default)]`. When derived, fields marked with
+    /// `#[entity(transient)]` are listed here and are left out of the
+    /// [`PROJECTED_ATTRIBUTES`][EntityDef::PROJECTED_ATTRIBUTES].
+    const TRANSIENT_ATTRIBUTES: &'static [&'static str] = &[];
+
+    /// The upgrades applied to items written with older versions of the
+    /// entity's schema
+    ///
+    /// The upgrade at index `n` converts an item from schema version `n` to
+    /// version `n + 1`, so the current schema version is the number of
+    /// upgrades, and items without a schema version are at version `0`.
+    /// Upgrades are only applied when the table defines a
+    /// [`SCHEMA_VERSION_ATTRIBUTE`][Table::SCHEMA_VERSION_ATTRIBUTE].
+    ///
+    /// Upgrades are applied by [`from_item()`][ProjectionExt::from_item()]
+    /// before the item is deserialized, so old items can be read without
+    /// adding `serde` compatibility shims to the entity. Items with a newer
+    /// schema version than the entity are deserialized as they are.
+    ///
+    /// Since the attributes of older schemas are not known, reads of
+    /// projections of an entity type with upgrades retrieve full items
+    /// rather than applying a projection expression.
+    ///
+    /// ```
+    /// use modyne::{EntityDef, EntityTypeNameRef, Item};
+    ///
+    /// struct Customer;
+    ///
+    /// impl EntityDef for Customer {
+    ///     const ENTITY_TYPE: &'static EntityTypeNameRef =
+    ///         EntityTypeNameRef::from_static("customer");
+    ///     const SCHEMA_UPGRADES: &'static [fn(Item) -> Item] = &[split_name];
+    /// }
+    ///
+    /// /// Version 1 split `name` into `given_name` and `family_name`
+    /// fn split_name(mut item: Item) -> Item {
+    ///     if let Some(modyne::AttributeValue::S(name)) = item.remove("name") {
+    ///         let (given, family) = name.split_once(' ').unwrap_or((&name, ""));
+    ///         item.insert("given_name".into(), modyne::AttributeValue::S(given.into()));
+    ///         item.insert("family_name".into(), modyne::AttributeValue::S(family.into()));
+    ///     }
+    ///     item
+    /// }
+    /// ```
+    const SCHEMA_UPGRADES: &'static [fn(Item) -> Item] = &[];
+}
+
+/// A field of an entity, along with the name of the attribute it is stored in
+///
+/// Fields are used in typed conditions, such as
+/// [`attr_exists()`][EntityExt::attr_exists()], so that conditions follow
+/// any `serde` renames of the field. When deriving [`EntityDef`] for a
+/// struct, a field is generated for each of its
+/// [field attributes][EntityDef::FIELD_ATTRIBUTES], accessed through the
+/// entity's `FIELDS` constant, so that naming a field that does not exist
+/// fails to compile. Entities that implement [`EntityDef`] by hand can
+/// declare their fields with [`Field::new()`].
+///
+/// ```compile_fail
+/// use modyne::EntityDef;
+///
+/// #[derive(EntityDef)]
+/// struct Account {
+///     name: String,
+/// }
+///
+/// let email = Account::FIELDS.email;
+/// ```
+pub struct Field<E> {
+    name: &'static str,
+    attribute: &'static str,
+    entity: std::marker::PhantomData<fn() -> E>,
+}
+
+impl<E> Field<E> {
+    /// Declares a field of the entity stored in the given attribute
+    #[inline]
+    pub const fn new(name: &'static str, attribute: &'static str) -> Self {
+        Self {
+            name,
+            attribute,
+            entity: std::marker::PhantomData,
+        }
+    }
+
+    /// The name of the field in the Rust definition of the entity
+    #[inline]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The name of the attribute that the field is stored in
+    #[inline]
+    pub const fn attribute(&self) -> &'static str {
+        self.attribute
+    }
+}
+
+impl<E> Clone for Field<E> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E> Copy for Field<E> {}
+
+impl<E> PartialEq for Field<E> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.attribute == other.attribute
+    }
+}
+
+impl<E> Eq for Field<E> {}
+
+impl<E> std::fmt::Debug for Field<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Field")
+            .field("name", &self.name)
+            .field("attribute", &self.attribute)
+            .finish()
+    }
+}
+
+/// An entity in a DynamoDB table
+///
+/// This trait is used to define the structure of an entity type in a
+/// DynamoDB table and how the entity may be queried.
+///
+/// Projections of the entity can be defined using the [`Projection`] trait.
+///
+/// # Example
+///
+/// Here we define a simple order entity type. To support write patterns, the
+/// order's primary key only requires the order's ID. However, to support an
+/// access pattern where we want to query all orders for a given user, we
+/// define a global secondary index with a partition key of `USER#<user_id>`
+/// and a sort key that includes the order's date, which allows us to more
+/// efficiently query for recent orders for a given user.
+///
+/// ```
+/// use modyne::{keys, Entity, EntityDef};
+/// # use time::format_description::well_known::Rfc3339;
+/// #
+/// # struct App;
+/// # impl modyne::Table for App {
+/// #     type PrimaryKey = keys::Primary;
+/// #     type IndexKeys = keys::Gsi1;
+/// #     fn table_name(&self) -> &str { unimplemented!() }
+/// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+/// # }
+///
+/// #[derive(Debug, EntityDef, serde::Serialize, serde::Deserialize)]
+/// struct Order {
+///     user_id: String,
+///     order_id: String,
+///     #[serde(with = "time::serde::rfc3339")]
+///     order_date: time::OffsetDateTime,
+///     items: Vec<OrderItem>,
+/// }
+///
+/// #[derive(Debug, serde::Serialize, serde::Deserialize)]
+/// struct OrderItem {
+///     item_id: String,
+///     quantity: u32,
+/// }
+///
+/// struct OrderKeyInput<'a> {
+///     order_id: &'a str,
+/// }
+///
+/// impl Entity for Order {
+///     type KeyInput<'a> = OrderKeyInput<'a>;
+///     type Table = App;
+///     type IndexKeys = keys::Gsi1;
+///
+///     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary {
+///         keys::Primary {
+///             hash: format!("ORDER#{}", input.order_id),
+///             range: format!("ORDER#{}", input.order_id),
+///         }
+///     }
+///
+///     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
 ///         let order_date = self.order_date.format(&Rfc3339).unwrap();
 ///         keys::FullKey {
 ///             primary: Self::primary_key(OrderKeyInput { order_id: &self.order_id }),
@@ -303,72 +1225,350 @@ pub trait Entity: EntityDef + Sized {
     ///
     /// This is primarily used when upserting an entity into the database.
     fn full_key(&self) -> keys::FullKey<<Self::Table as Table>::PrimaryKey, Self::IndexKeys>;
-}
-
-/// Extension trait for [`Entity`] types
-pub trait EntityExt: Entity {
-    /// The definition for the entity's primary key
-    const KEY_DEFINITION: keys::PrimaryKeyDefinition =
-        <<Self::Table as Table>::PrimaryKey as keys::PrimaryKey>::PRIMARY_KEY_DEFINITION;
 
-    /// Convert the entity into a DynamoDB item
+    /// Generate the writes to derived items that accompany a write of this entity
     ///
-    /// The generated item will include all of the entity's attributes, as well
-    /// as the entity type and all index key attributes.
-    fn into_item(self) -> Item
-    where
-        Self: serde::Serialize,
-    {
-        let full_entity = FullEntity {
-            keys: self.full_key(),
-            entity: self,
-        };
-
-        let mut item = crate::codec::to_item(full_entity).unwrap();
-        if item
-            .insert(
-                <Self::Table as Table>::ENTITY_TYPE_ATTRIBUTE.to_string(),
-                <Self::Table as Table>::serialize_entity_type(Self::ENTITY_TYPE),
-            )
-            .is_some()
-        {
-            tracing::warn!(
-                "serialized entity had attribute collision with entity type attribute `{}`",
-                <Self::Table as Table>::ENTITY_TYPE_ATTRIBUTE,
-            );
-        }
-        item
-    }
-
-    /// Prepares a get operation for the entity
+    /// Derived items hold denormalized data that must be kept in sync with
+    /// the entity, such as a summary item listing the names of all entities
+    /// of a type. These writes are included in the transactions prepared by
+    /// [`EntityExt::create_with_derived()`] and [`EntityExt::put_with_derived()`].
+    ///
+    /// By default, an entity has no derived writes.
     #[inline]
-    fn get(input: Self::KeyInput<'_>) -> Get {
-        Get::new(Self::primary_key(input).into_key())
+    fn derive_writes(&self) -> Vec<model::TransactWriteItem> {
+        Vec::new()
     }
 
-    /// Prepares a put operation for the entity
+    /// The sort key of the entity in the [entity type index][keys::EntityTypeIndex]
+    ///
+    /// Returning a value opts the entity into the index, and the index key
+    /// attributes are added when the entity is converted into an item.
+    /// Entities of a type are listed in the order of their sort keys.
+    ///
+    /// By default, an entity is not listed in the entity type index.
     #[inline]
-    fn put(self) -> Put
-    where
-        Self: serde::Serialize,
-    {
-        Put::new(self.into_item())
+    fn entity_type_index_key(&self) -> Option<String> {
+        None
     }
+}
 
-    /// Prepares a put operation for the entity that requires that
-    /// no entity already exist with the same key
-    #[inline]
-    fn create(self) -> ConditionalPut
-    where
-        Self: serde::Serialize,
-    {
-        let condition = expr::Condition::new("attribute_not_exists(#PK)").name(
-            "#PK",
-            <<Self::Table as Table>::PrimaryKey as keys::PrimaryKey>::PRIMARY_KEY_DEFINITION
-                .hash_key,
-        );
-        self.put().condition(condition)
-    }
+/// An entity stored as a single document at a constant key
+///
+/// Some entities, such as a list of featured items shown on a front page,
+/// exist at most once in a table. Implementing this trait provides an
+/// [`Entity`] implementation that stores the entity with [`KEY`] as both its
+/// partition and sort key, taking `()` as its key input.
+///
+/// ```
+/// use modyne::{keys, EntityDef, SingletonEntity, Table};
+/// # struct App;
+/// # impl Table for App {
+/// #     type PrimaryKey = keys::Primary;
+/// #     type IndexKeys = keys::Gsi1;
+/// #     fn table_name(&self) -> &str { unimplemented!() }
+/// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+/// # }
+///
+/// #[derive(Debug, Default, EntityDef, serde::Serialize, serde::Deserialize)]
+/// struct FrontPage {
+///     #[serde(default)]
+///     featured_deals: Vec<String>,
+/// }
+///
+/// impl SingletonEntity for FrontPage {
+///     type Table = App;
+///     const KEY: &'static str = "FRONTPAGE";
+/// }
+///
+/// # async fn example(app: &App) -> Result<(), modyne::Error> {
+/// let front_page = FrontPage::get_or_default(app).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`KEY`]: SingletonEntity::KEY
+pub trait SingletonEntity: EntityDef + Sized {
+    /// The table in which the entity is stored
+    type Table: Table<PrimaryKey = keys::Primary>;
+
+    /// The value of both the partition and sort keys of the entity
+    const KEY: &'static str;
+
+    /// Reads the entity, returning its default value if it has not been
+    /// written
+    ///
+    /// This function executes the operation with eventual consistency.
+    fn get_or_default<T: Table>(table: &T) -> impl std::future::Future<Output = Result<Self, Error>>
+    where
+        Self: Default + serde::de::DeserializeOwned,
+    {
+        singleton_or_default(table)
+    }
+}
+
+async fn singleton_or_default<S, T>(table: &T) -> Result<S, Error>
+where
+    S: SingletonEntity + Default + serde::de::DeserializeOwned,
+    T: Table,
+{
+    let output = S::get(()).execute(table).await?;
+    match output.item {
+        Some(item) => S::from_item(item),
+        None => Ok(S::default()),
+    }
+}
+
+impl<S: SingletonEntity> Entity for S {
+    type KeyInput<'a> = ();
+    type Table = <S as SingletonEntity>::Table;
+    type IndexKeys = ();
+
+    fn primary_key(_: Self::KeyInput<'_>) -> keys::Primary {
+        keys::Primary {
+            hash: S::KEY.to_string(),
+            range: S::KEY.to_string(),
+        }
+    }
+
+    fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+        Self::primary_key(()).into()
+    }
+}
+
+/// Extension trait for [`Entity`] types
+pub trait EntityExt: Entity {
+    /// The definition for the entity's primary key
+    const KEY_DEFINITION: keys::PrimaryKeyDefinition =
+        <<Self::Table as Table>::PrimaryKey as keys::PrimaryKey>::PRIMARY_KEY_DEFINITION;
+
+    /// Convert the entity into a DynamoDB item
+    ///
+    /// The generated item will include all of the entity's attributes, as well
+    /// as the entity type and all index key attributes. Any
+    /// [transient attributes][EntityDef::TRANSIENT_ATTRIBUTES] are left out.
+    fn into_item(self) -> Item
+    where
+        Self: serde::Serialize,
+    {
+        entity_to_item(&self)
+    }
+
+    /// Prepares a get operation for the entity
+    #[inline]
+    fn get(input: Self::KeyInput<'_>) -> Get {
+        Get::new(Self::primary_key(input).into_key()).for_entity_type(Self::ENTITY_TYPE)
+    }
+
+    /// Prepares an operation that checks whether the entity exists
+    ///
+    /// Only the partition key is read back, avoiding the cost of retrieving
+    /// and deserializing the full item. To check whether any item exists
+    /// within a range of keys, use [`Query::exists()`].
+    #[inline]
+    fn exists(input: Self::KeyInput<'_>) -> Exists {
+        Exists::new(Self::primary_key(input).into_key()).for_entity_type(Self::ENTITY_TYPE)
+    }
+
+    /// Prepares a put operation for the entity
+    ///
+    /// The put writes only the entity's own item, so it can be executed on
+    /// its own or included in a batch or transaction. The entity's
+    /// [derived writes][Entity::derive_writes()] are not included, as
+    /// applying them atomically requires a transaction, which costs twice as
+    /// much capacity per item written; use
+    /// [`put_with_derived()`][EntityExt::put_with_derived()] to include them.
+    #[inline]
+    fn put(self) -> Put
+    where
+        Self: serde::Serialize,
+    {
+        Put::new(self.into_item())
+    }
+
+    /// Prepares a put operation for the entity that requires that
+    /// no entity already exist with the same key
+    ///
+    /// As with [`put()`][EntityExt::put()], neither the entity's
+    /// [derived writes][Entity::derive_writes()] nor claims on its unique
+    /// values are included; use
+    /// [`create_with_derived()`][EntityExt::create_with_derived()] to
+    /// include them.
+    #[inline]
+    fn create(self) -> ConditionalPut
+    where
+        Self: serde::Serialize,
+    {
+        let condition = expr::Condition::new("attribute_not_exists(#PK)").name(
+            "#PK",
+            <<Self::Table as Table>::PrimaryKey as keys::PrimaryKey>::PRIMARY_KEY_DEFINITION
+                .hash_key,
+        );
+        self.put().condition(condition)
+    }
+
+    /// Prepares a transaction that puts the entity along with its
+    /// [derived writes][Entity::derive_writes()]
+    fn put_with_derived(self) -> TransactWrite
+    where
+        Self: serde::Serialize,
+    {
+        let derived = self.derive_writes();
+        derived.into_iter().fold(
+            TransactWrite::new().operation(self.put()),
+            TransactWrite::operation,
+        )
+    }
+
+    /// Prepares a transaction that creates the entity along with its
+    /// [derived writes][Entity::derive_writes()]
+    ///
+    /// The transaction also claims each of the entity's
+    /// [unique attribute values][EntityDef::UNIQUE_ATTRIBUTES] and
+    /// [unique index keys][EntityDef::UNIQUE_INDEXES]. It will fail if an
+    /// entity already exists with the same key or if any of the values has
+    /// already been claimed, in which case none of the writes are applied.
+    fn create_with_derived(self) -> TransactWrite
+    where
+        Self: serde::Serialize,
+    {
+        let claims = unique::Unique::<Self>::from_item(&entity_to_item(&self));
+        let derived = self.derive_writes();
+        let transaction = derived.into_iter().fold(
+            TransactWrite::new().operation(self.create()),
+            TransactWrite::operation,
+        );
+        claims
+            .iter()
+            .map(unique::Unique::claim)
+            .fold(transaction, TransactWrite::operation)
+    }
+
+    /// Prepares a transaction that creates the entity only if no other entity
+    /// of its type has the same key in the given secondary index
+    ///
+    /// The index key is claimed by a constraint item, as described in the
+    /// [`unique`] module, in the same transaction as the entity is created.
+    /// The transaction will fail if an entity already exists with the same
+    /// primary key or if the index key has already been claimed. If the
+    /// entity does not have a key in the index, only the primary key is
+    /// checked.
+    ///
+    /// To maintain the claim as the entity changes, list the index in
+    /// [`UNIQUE_INDEXES`][EntityDef::UNIQUE_INDEXES] and use
+    /// [`replace_with_derived()`][EntityExt::replace_with_derived()] and
+    /// [`delete_with_unique()`][EntityExt::delete_with_unique()].
+    fn create_if_absent_on<K: keys::IndexKey>(self) -> TransactWrite
+    where
+        Self: serde::Serialize,
+    {
+        let claim = unique::Unique::<Self>::from_index(&entity_to_item(&self), K::INDEX_DEFINITION);
+        claim.iter().map(unique::Unique::claim).fold(
+            TransactWrite::new().operation(self.create()),
+            TransactWrite::operation,
+        )
+    }
+
+    /// Prepares a transaction that replaces an existing entity along with its
+    /// [derived writes][Entity::derive_writes()]
+    ///
+    /// Any [unique attribute values][EntityDef::UNIQUE_ATTRIBUTES] or
+    /// [unique index keys][EntityDef::UNIQUE_INDEXES] that differ from the previous state of the entity are released, and the
+    /// new values are claimed. The transaction will fail if the entity does
+    /// not exist or if any of the new values has already been claimed.
+    fn replace_with_derived(self, previous: &Self) -> TransactWrite
+    where
+        Self: serde::Serialize,
+    {
+        let (released, claimed) =
+            unique::changes::<Self>(&entity_to_item(previous), &entity_to_item(&self));
+        let derived = self.derive_writes();
+        let transaction = derived.into_iter().fold(
+            TransactWrite::new().operation(self.replace()),
+            TransactWrite::operation,
+        );
+        let transaction = released
+            .iter()
+            .map(unique::Unique::release)
+            .fold(transaction, TransactWrite::operation);
+        claimed
+            .iter()
+            .map(unique::Unique::claim)
+            .fold(transaction, TransactWrite::operation)
+    }
+
+    /// Prepares a put operation for the entity that, if an entity already
+    /// exists with the same key, returns the existing entity instead
+    ///
+    /// The existing entity is returned by the failed conditional write, so
+    /// only a single request is made in either case.
+    #[inline]
+    fn create_or_get(self) -> CreateOrGet<Self>
+    where
+        Self: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        CreateOrGet::new(self)
+    }
+
+    /// Prepares an optimistic read-modify-write of the entity
+    ///
+    /// The entity is read, passed to `modify` to produce its new state, and
+    /// the changed attributes are written back on the condition that the
+    /// entity has not changed in the meantime. If it has, the operation is
+    /// retried with the latest state of the entity after a backoff, up to a
+    /// configurable number of attempts.
+    ///
+    /// ```no_run
+    /// use modyne::{keys, Entity, EntityDef, EntityExt, Error};
+    /// # struct App;
+    /// # impl modyne::Table for App {
+    /// #     type PrimaryKey = keys::Primary;
+    /// #     type IndexKeys = keys::Gsi1;
+    /// #     fn table_name(&self) -> &str { unimplemented!() }
+    /// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+    /// # }
+    /// # impl modyne::WritableTable for App {}
+    ///
+    /// #[derive(Debug, EntityDef, serde::Serialize, serde::Deserialize)]
+    /// struct Counter {
+    ///     name: String,
+    ///     count: u64,
+    /// }
+    ///
+    /// impl Entity for Counter {
+    ///     type KeyInput<'a> = &'a str;
+    ///     type Table = App;
+    ///     type IndexKeys = ();
+    ///
+    ///     fn primary_key(name: Self::KeyInput<'_>) -> keys::Primary {
+    ///         keys::Primary {
+    ///             hash: format!("COUNTER#{name}"),
+    ///             range: format!("COUNTER#{name}"),
+    ///         }
+    ///     }
+    ///
+    ///     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+    ///         Self::primary_key(&self.name).into()
+    ///     }
+    /// }
+    ///
+    /// async fn increment(app: &App, name: &str) -> Result<Option<Counter>, Error> {
+    ///     Counter::read_modify_write(name, |mut counter| {
+    ///         counter.count += 1;
+    ///         counter
+    ///     })
+    ///     .max_attempts(5)
+    ///     .execute(app)
+    ///     .await
+    /// }
+    /// ```
+    #[inline]
+    fn read_modify_write<F>(key: Self::KeyInput<'_>, modify: F) -> ReadModifyWrite<Self, F>
+    where
+        Self: serde::Serialize + serde::de::DeserializeOwned,
+        F: FnMut(Self) -> Self,
+    {
+        ReadModifyWrite::new(Self::primary_key(key).into_key(), modify)
+    }
 
     /// Prepares a put operation for the entity that requires that
     /// an entity already exist with the same key
@@ -385,6 +1585,180 @@ pub trait EntityExt: Entity {
         self.put().condition(condition)
     }
 
+    /// The name of the attribute that a field of the entity is stored in
+    #[inline]
+    fn attribute_name(field: Field<Self>) -> &'static str {
+        field.attribute()
+    }
+
+    /// A condition that the attribute storing the given field exists
+    #[inline]
+    fn attr_exists(field: Field<Self>) -> expr::Condition {
+        expr::Condition::new("attribute_exists(#attr)").name("#attr", Self::attribute_name(field))
+    }
+
+    /// A condition that the attribute storing the given field does not exist
+    #[inline]
+    fn attr_not_exists(field: Field<Self>) -> expr::Condition {
+        expr::Condition::new("attribute_not_exists(#attr)")
+            .name("#attr", Self::attribute_name(field))
+    }
+
+    /// Prepares a put operation for the entity that requires that any
+    /// entity already stored with the same key not have a value for the
+    /// given field
+    ///
+    /// The put succeeds if no entity exists with the same key. The field
+    /// names the attribute it is stored in, as generated by the derive
+    /// macro in the entity's `FIELDS` constant.
+    ///
+    /// ```
+    /// use modyne::{keys, Entity, EntityDef, EntityExt};
+    /// # struct App;
+    /// # impl modyne::Table for App {
+    /// #     type PrimaryKey = keys::Primary;
+    /// #     type IndexKeys = keys::Gsi1;
+    /// #     fn table_name(&self) -> &str { unimplemented!() }
+    /// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+    /// # }
+    ///
+    /// #[derive(EntityDef, serde::Serialize)]
+    /// struct Account {
+    ///     name: String,
+    ///     #[serde(rename = "emailAddress")]
+    ///     email: Option<String>,
+    /// }
+    ///
+    /// impl Entity for Account {
+    ///     type KeyInput<'a> = &'a str;
+    ///     type Table = App;
+    ///     type IndexKeys = ();
+    ///
+    ///     fn primary_key(name: Self::KeyInput<'_>) -> keys::Primary {
+    ///         keys::Primary {
+    ///             hash: format!("ACCOUNT#{name}"),
+    ///             range: "ACCOUNT".to_string(),
+    ///         }
+    ///     }
+    ///
+    ///     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+    ///         Self::primary_key(&self.name).into()
+    ///     }
+    /// }
+    ///
+    /// let account = Account {
+    ///     name: "alexdebrie".into(),
+    ///     email: Some("alex@example.com".into()),
+    /// };
+    ///
+    /// let create = account.create_unless_attr_exists(Account::FIELDS.email);
+    ///
+    /// let condition = Account::attr_not_exists(Account::FIELDS.email);
+    /// assert_eq!(condition.expression, "attribute_not_exists(#cnd_attr)");
+    /// assert_eq!(
+    ///     condition.names,
+    ///     [("#cnd_attr".to_string(), "emailAddress".to_string())],
+    /// );
+    /// ```
+    #[inline]
+    fn create_unless_attr_exists(self, field: Field<Self>) -> ConditionalPut
+    where
+        Self: serde::Serialize,
+    {
+        self.put().condition(Self::attr_not_exists(field))
+    }
+
+    /// A condition that the stored entity exists and that the attributes
+    /// storing the given fields still hold the values they have in this
+    /// state of the entity
+    ///
+    /// Fields without a value in this state, such as a `None` skipped
+    /// during serialization, must also be absent from the stored entity.
+    /// Conditioning a write on this allows for optimistic concurrency on
+    /// chosen fields without a version attribute. The values are added as
+    /// sensitive values, as they may hold any attribute of the entity. A
+    /// field given more than once is only checked once.
+    fn unchanged_condition(&self, fields: &[Field<Self>]) -> expr::Condition
+    where
+        Self: serde::Serialize,
+    {
+        let item = entity_to_item(self);
+        unchanged_condition::<Self::Table>(fields.iter().map(|field| {
+            let attr = Self::attribute_name(*field);
+            (attr, item.get(attr))
+        }))
+    }
+
+    /// Prepares a put operation for the entity that requires that the
+    /// attributes storing the given fields have not changed since the
+    /// previous state of the entity was read
+    ///
+    /// The put fails if the entity does not exist, or if any of the fields
+    /// has a different value than in the previous state. See
+    /// [`unchanged_condition()`][EntityExt::unchanged_condition()] for
+    /// details.
+    ///
+    /// ```
+    /// use modyne::{keys, Entity, EntityDef, EntityExt};
+    /// # struct App;
+    /// # impl modyne::Table for App {
+    /// #     type PrimaryKey = keys::Primary;
+    /// #     type IndexKeys = keys::Gsi1;
+    /// #     fn table_name(&self) -> &str { unimplemented!() }
+    /// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+    /// # }
+    ///
+    /// #[derive(Clone, EntityDef, serde::Serialize)]
+    /// struct Account {
+    ///     name: String,
+    ///     balance: u64,
+    ///     #[serde(skip_serializing_if = "Option::is_none")]
+    ///     nickname: Option<String>,
+    /// }
+    /// # impl Entity for Account {
+    /// #     type KeyInput<'a> = &'a str;
+    /// #     type Table = App;
+    /// #     type IndexKeys = ();
+    /// #     fn primary_key(name: Self::KeyInput<'_>) -> keys::Primary {
+    /// #         keys::Primary {
+    /// #             hash: format!("ACCOUNT#{name}"),
+    /// #             range: "ACCOUNT".to_string(),
+    /// #         }
+    /// #     }
+    /// #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+    /// #         Self::primary_key(&self.name).into()
+    /// #     }
+    /// # }
+    ///
+    /// let previous = Account {
+    ///     name: "alexdebrie".into(),
+    ///     balance: 100,
+    ///     nickname: None,
+    /// };
+    ///
+    /// let fields = [Account::FIELDS.balance, Account::FIELDS.nickname];
+    /// let condition = previous.unchanged_condition(&fields);
+    /// assert_eq!(
+    ///     condition.expression,
+    ///     "attribute_exists(#cnd_PK) AND #cnd_a0 = :cnd_a0 \
+    ///      AND attribute_not_exists(#cnd_a1)",
+    /// );
+    ///
+    /// let current = Account {
+    ///     balance: 75,
+    ///     ..previous.clone()
+    /// };
+    /// let replace = current.replace_if_unchanged(&previous, &fields);
+    /// ```
+    #[inline]
+    fn replace_if_unchanged(self, previous: &Self, fields: &[Field<Self>]) -> ConditionalPut
+    where
+        Self: serde::Serialize,
+    {
+        let condition = previous.unchanged_condition(fields);
+        self.put().condition(condition)
+    }
+
     /// Prepares an update operation for the entity
     ///
     /// # Note
@@ -392,16 +1766,102 @@ pub trait EntityExt: Entity {
     /// If this update would change an attribute that is used in the creation of a key attribute,
     /// that key attribute must also be explicitly updated. In cases where the entire state of the
     /// entity is known, using a [`replace()`][EntityExt::replace()] may be better, as that will
-    /// also update any computed key attributes.
+    /// also update any computed key attributes. Alternatively, [`expr::IndexSync`] can be used
+    /// to include the changes to the index keys in the update expression.
     #[inline]
     fn update(key: Self::KeyInput<'_>) -> Update {
-        Update::new(Self::primary_key(key).into_key())
+        Update::new(Self::primary_key(key).into_key()).for_entity_type(Self::ENTITY_TYPE)
+    }
+
+    /// Prepares an update operation that brings the secondary index keys of
+    /// a stored entity in line with its current state
+    ///
+    /// Index keys that have changed since the previous state are set, and
+    /// those that are no longer present, such as when an entity leaves a
+    /// sparse index, are removed. Other attributes are left unchanged. The
+    /// item is identified by the primary key of the current state.
+    ///
+    /// Returns `None` if none of the index keys have changed.
+    fn sync_indexes(&self, previous: &Self) -> Option<UpdateWithExpr> {
+        let sync = expr::IndexSync::new(previous, self);
+        if sync.is_empty() {
+            return None;
+        }
+
+        let key = self.full_key().primary.into_key();
+        Some(
+            Update::new(key)
+                .for_entity_type(Self::ENTITY_TYPE)
+                .expression(sync.into_update()),
+        )
+    }
+
+    /// Prepares an update operation that applies only the attributes that
+    /// changed between two states of the entity
+    ///
+    /// Unlike [`replace()`][EntityExt::replace()], unchanged attributes are
+    /// not sent, which keeps requests small for large items with small
+    /// changes. Changes to computed index keys are included. The item is
+    /// identified by the primary key of the new state.
+    ///
+    /// The update is conditioned on the item existing and on each changed
+    /// attribute still holding its value in the old state, or still being
+    /// absent, so that it fails rather than overwriting concurrent changes
+    /// to those attributes. The old values are added as sensitive values.
+    ///
+    /// Returns `None` if nothing has changed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the two states have different primary keys.
+    fn update_from_diff(old: &Self, new: &Self) -> Option<ConditionalUpdate>
+    where
+        Self: serde::Serialize,
+    {
+        let key = new.full_key().primary.into_key();
+        assert!(
+            old.full_key().primary.into_key() == key,
+            "cannot diff states of entity type `{}` with different primary keys",
+            Self::ENTITY_TYPE
+        );
+
+        let diff = expr::ItemDiff::between(old, new);
+        if diff.is_empty() {
+            return None;
+        }
+
+        let old = entity_to_item(old);
+        let condition =
+            unchanged_condition::<Self::Table>(diff.changed().map(|attr| (attr, old.get(attr))));
+        Some(
+            Update::new(key)
+                .for_entity_type(Self::ENTITY_TYPE)
+                .expression(diff.into_update())
+                .condition(condition),
+        )
     }
 
     /// Prepares a delete operation for the entity
     #[inline]
     fn delete(key: Self::KeyInput<'_>) -> Delete {
-        Delete::new(Self::primary_key(key).into_key())
+        Delete::new(Self::primary_key(key).into_key()).for_entity_type(Self::ENTITY_TYPE)
+    }
+
+    /// Prepares a transaction that deletes the entity and releases each of
+    /// its [unique attribute values][EntityDef::UNIQUE_ATTRIBUTES] and
+    /// [unique index keys][EntityDef::UNIQUE_INDEXES]
+    fn delete_with_unique(&self) -> TransactWrite
+    where
+        Self: serde::Serialize,
+    {
+        let key = self.full_key().primary.into_key();
+        unique::Unique::<Self>::from_item(&entity_to_item(self))
+            .iter()
+            .map(unique::Unique::release)
+            .fold(
+                TransactWrite::new().operation(Delete::new(key)),
+                TransactWrite::operation,
+            )
     }
 
     /// Prepares a condition check operation for the entity, for transactional writes
@@ -413,6 +1873,81 @@ pub trait EntityExt: Entity {
 
 impl<T: Entity> EntityExt for T {}
 
+/// A partial update of an item, converted into a `SET` update expression
+///
+/// Each field assigns a value to an attribute of the item. Fields holding an
+/// `Option` are only assigned when they hold a value.
+///
+/// For easier implementation, use the [`derive@IntoUpdate`] derive macro.
+/// The attribute assigned by a field can be customized with the following
+/// attributes:
+///
+/// * `#[update(path = "a.b")]` assigns the field to the entry `b` of the
+///   map stored in attribute `a`. By default, a field assigns the attribute
+///   with the field's name, respecting `serde` renames.
+/// * `#[update(append)]` appends the elements of the field's list value to
+///   the list stored in the attribute using `list_append`.
+/// * `#[update(nested)]` assigns the fields of a nested `IntoUpdate` value to
+///   entries of the map stored in the attribute.
+/// * `#[update(entries)]` assigns each entry of the field's map value to an
+///   entry of the map stored in the attribute.
+/// * `#[update(skip)]` ignores the field.
+///
+/// ```
+/// use std::collections::HashMap;
+///
+/// use modyne::IntoUpdate;
+///
+/// #[derive(serde::Serialize)]
+/// struct Address {
+///     street: String,
+///     city: String,
+/// }
+///
+/// #[derive(IntoUpdate)]
+/// struct UpdateCustomer {
+///     name: Option<String>,
+///     #[update(path = "addresses.home")]
+///     home_address: Option<Address>,
+///     #[update(entries, path = "addresses")]
+///     other_addresses: HashMap<String, Address>,
+///     #[update(append)]
+///     tags: Vec<String>,
+/// }
+///
+/// let update = UpdateCustomer {
+///     name: None,
+///     home_address: Some(Address {
+///         street: "123 Main St".into(),
+///         city: "Springfield".into(),
+///     }),
+///     other_addresses: HashMap::new(),
+///     tags: vec!["returning".into()],
+/// }
+/// .into_update();
+///
+/// assert_eq!(
+///     update.expression,
+///     "SET #upd_path0.#upd_path1 = :upd_path0, #upd_path2 = list_append(#upd_path2, :upd_path1)",
+/// );
+/// ```
+pub trait IntoUpdate: Sized {
+    /// Adds the assignments of the update to the builder, relative to the
+    /// given attribute path
+    ///
+    /// The path is empty unless the update is nested within another.
+    fn write_update(self, builder: &mut expr::UpdateBuilder, path: &[&str]);
+
+    /// Converts the update into an update expression
+    ///
+    /// If no attributes are assigned, the resulting expression will be empty.
+    fn into_update(self) -> expr::Update {
+        let mut builder = expr::UpdateBuilder::new();
+        self.write_update(&mut builder, &[]);
+        builder.into_update()
+    }
+}
+
 /// A projection of an entity that may not contain all of the entity's attributes
 ///
 /// This trait can be used when querying a subset of an entity's attributes. In this way
@@ -454,30 +1989,109 @@ where
 /// Extension trait for [`Projection`] types
 pub trait ProjectionExt: Projection {
     /// Deserialize a DynamoDB item into this projection
+    ///
+    /// Any [schema upgrades][EntityDef::SCHEMA_UPGRADES] needed by the item
+    /// are applied before it is deserialized.
     fn from_item(item: Item) -> Result<Self, Error>;
-}
-
-impl<'a, P> ProjectionExt for P
-where
-    P: Projection + serde::Deserialize<'a>,
-{
-    fn from_item(item: Item) -> Result<Self, Error> {
-        let parsed = crate::codec::from_item(item).map_err(|error| {
-            crate::error::ItemDeserializationError::new(Self::Entity::ENTITY_TYPE, error)
-        })?;
-
-        Ok(parsed)
-    }
-}
 
-/// A description of the set of entity types that constitute an [`Aggregate`]
-///
-/// This trait is not generally implemented directly, but rather is generated
-/// by using the [`projections!`] macro.
-pub trait ProjectionSet: Sized {
-    /// Attempt to parse an known entity from a DynamoDB item
+    /// Deserialize a borrowed DynamoDB item into this projection
     ///
-    /// On an unknown entity type, this method should return `Ok(None)`.
+    /// Strings and binary values are lent out from the item rather than
+    /// copied, so projections with `&str`, `&[u8]`, or `Cow` fields can
+    /// avoid cloning large attributes. Because the item is borrowed,
+    /// [schema upgrades][EntityDef::SCHEMA_UPGRADES] are not applied.
+    ///
+    /// ```
+    /// use std::borrow::Cow;
+    ///
+    /// use modyne::{keys, Entity, EntityDef, EntityExt, Projection, ProjectionExt};
+    /// # use modyne::{EntityTypeNameRef, Table};
+    /// # struct Database;
+    /// # impl Table for Database {
+    /// #     type PrimaryKey = keys::Primary;
+    /// #     type IndexKeys = keys::Gsi1;
+    /// #     fn table_name(&self) -> &str { unimplemented!() }
+    /// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+    /// # }
+    ///
+    /// #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    /// struct Article {
+    ///     slug: String,
+    ///     body: String,
+    /// }
+    ///
+    /// # impl EntityDef for Article {
+    /// #     const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("article");
+    /// # }
+    /// # impl Entity for Article {
+    /// #     type KeyInput<'a> = &'a str;
+    /// #     type Table = Database;
+    /// #     type IndexKeys = keys::Gsi1;
+    /// #     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary {
+    /// #         keys::Primary { hash: format!("ARTICLE#{input}"), range: "ARTICLE".to_string() }
+    /// #     }
+    /// #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+    /// #         keys::FullKey {
+    /// #             primary: Self::primary_key(&self.slug),
+    /// #             indexes: keys::Gsi1 { hash: "ARTICLES".to_string(), range: self.slug.clone() },
+    /// #         }
+    /// #     }
+    /// # }
+    /// #[derive(Debug, serde::Deserialize)]
+    /// struct ArticleView<'a> {
+    ///     slug: &'a str,
+    ///     #[serde(borrow)]
+    ///     body: Cow<'a, str>,
+    /// }
+    ///
+    /// impl Projection for ArticleView<'_> {
+    ///     type Entity = Article;
+    /// }
+    ///
+    /// let item = Article {
+    ///     slug: "borrowing".to_string(),
+    ///     body: "A very long article body".to_string(),
+    /// }
+    /// .into_item();
+    ///
+    /// let article = ArticleView::from_item_ref(&item).unwrap();
+    /// assert_eq!(article.slug, "borrowing");
+    /// assert!(matches!(article.body, Cow::Borrowed(_)));
+    /// ```
+    fn from_item_ref<'de>(item: &'de Item) -> Result<Self, Error>
+    where
+        Self: serde::Deserialize<'de>,
+    {
+        let parsed = crate::de::from_item_ref(item).map_err(|error| {
+            crate::error::ItemDeserializationError::new(Self::Entity::ENTITY_TYPE, error)
+        })?;
+
+        Ok(parsed)
+    }
+}
+
+impl<'a, P> ProjectionExt for P
+where
+    P: Projection + serde::Deserialize<'a>,
+{
+    fn from_item(item: Item) -> Result<Self, Error> {
+        let item = upgrade_item::<Self::Entity>(item)?;
+        let parsed = crate::codec::from_item(item).map_err(|error| {
+            crate::error::ItemDeserializationError::new(Self::Entity::ENTITY_TYPE, error)
+        })?;
+
+        Ok(parsed)
+    }
+}
+
+/// A description of the set of entity types that constitute an [`Aggregate`]
+///
+/// This trait is not generally implemented directly, but rather is generated
+/// by using the [`projections!`] macro.
+pub trait ProjectionSet: Sized {
+    /// Attempt to parse an known entity from a DynamoDB item
+    ///
+    /// On an unknown entity type, this method should return `Ok(None)`.
     ///
     /// # Errors
     ///
@@ -486,11 +2100,46 @@ pub trait ProjectionSet: Sized {
     /// entity type attribute is missing from the item.
     fn try_from_item(item: Item) -> Result<Option<Self>, Error>;
 
+    /// Attempt to parse a DynamoDB item into every projection in the set
+    /// that matches its entity type
+    ///
+    /// When the set includes more than one projection of the same entity
+    /// type, [`try_from_item()`][ProjectionSet::try_from_item()] only returns
+    /// the first. This method returns each of them, in the order they are
+    /// declared, so that an aggregate can populate several views of an
+    /// entity from a single query. On an unknown entity type, this method
+    /// returns an empty list.
+    ///
+    /// By default, this returns the projection returned by
+    /// [`try_from_item()`][ProjectionSet::try_from_item()], if any.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the item cannot be parsed into
+    /// any of the matching projections or if the entity type attribute is
+    /// missing from the item.
+    fn try_from_item_all(item: Item) -> Result<Vec<Self>, Error> {
+        Ok(Self::try_from_item(item)?.into_iter().collect())
+    }
+
     /// Generate a projection expression for the aggregate
     ///
     /// This expression will include all of the attributes that are
     /// projected by any of the entity types in the aggregate.
     fn projection_expression() -> Option<expr::StaticProjection>;
+
+    /// The entity types that are parsed by this projection set
+    ///
+    /// These are recorded on the spans of queries and scans as the
+    /// `modyne.entity_types` field, so that the capacity consumed by an
+    /// operation can be attributed to the entity types involved. An empty
+    /// list indicates that the entity types are not known, such as for a
+    /// set that includes a [`RawItem`].
+    ///
+    /// By default, no entity types are reported.
+    fn entity_types() -> Vec<&'static EntityTypeNameRef> {
+        Vec::new()
+    }
 }
 
 /// Utility macro for defining an [`ProjectionSet`] used when querying items
@@ -513,32 +2162,72 @@ macro_rules! projections {
 
         impl $crate::ProjectionSet for $name {
             fn try_from_item(item: $crate::Item) -> ::std::result::Result<::std::option::Option<Self>, $crate::Error> {
-                let entity_type = $crate::__private::get_entity_type::<$ty>(&item)?;
+                let entity_type = $crate::__private::get_table_entity_type::<
+                    <$ty as $crate::__private::ProjectionSetMember>::Table,
+                >(&item)?;
 
                 let parsed =
-                if entity_type == <<$ty as $crate::Projection>::Entity as $crate::EntityDef>::ENTITY_TYPE {
-                    let parsed = <$ty as $crate::ProjectionExt>::from_item(item)
+                if <$ty as $crate::__private::ProjectionSetMember>::matches(entity_type) {
+                    let parsed = <$ty as $crate::__private::ProjectionSetMember>::from_item(item)
                         .map(Self::$ty)?;
                     ::std::option::Option::Some(parsed)
                 } else
                 $(
-                    if entity_type == <<$tys as $crate::Projection>::Entity as $crate::EntityDef>::ENTITY_TYPE {
-                        let parsed = <$tys as $crate::ProjectionExt>::from_item(item)
+                    if <$tys as $crate::__private::ProjectionSetMember>::matches(entity_type) {
+                        let parsed = <$tys as $crate::__private::ProjectionSetMember>::from_item(item)
                             .map(Self::$tys)?;
                         ::std::option::Option::Some(parsed)
                     } else
                 )*
                 {
-                    tracing::warn!(entity_type = entity_type.as_str(), "unknown entity type");
+                    $crate::__private::skip_unknown_entity_type(entity_type);
                     ::std::option::Option::None
                 };
 
                 ::std::result::Result::Ok(parsed)
             }
 
+            fn try_from_item_all(item: $crate::Item) -> ::std::result::Result<::std::vec::Vec<Self>, $crate::Error> {
+                let entity_type = $crate::__private::get_table_entity_type::<
+                    <$ty as $crate::__private::ProjectionSetMember>::Table,
+                >(&item)?;
+
+                let mut parsed = ::std::vec::Vec::new();
+                if <$ty as $crate::__private::ProjectionSetMember>::matches(entity_type) {
+                    parsed.push(<$ty as $crate::__private::ProjectionSetMember>::from_item(item.clone()).map(Self::$ty)?);
+                }
+                $(
+                    if <$tys as $crate::__private::ProjectionSetMember>::matches(entity_type) {
+                        parsed.push(<$tys as $crate::__private::ProjectionSetMember>::from_item(item.clone()).map(Self::$tys)?);
+                    }
+                )*
+
+                if parsed.is_empty() {
+                    $crate::__private::skip_unknown_entity_type(entity_type);
+                }
+
+                ::std::result::Result::Ok(parsed)
+            }
+
             fn projection_expression() -> ::std::option::Option<$crate::expr::StaticProjection> {
                 $crate::once_projection_expression!($ty,$($tys),*)
             }
+
+            fn entity_types() -> ::std::vec::Vec<&'static $crate::EntityTypeNameRef> {
+                [
+                    <$ty as $crate::__private::ProjectionSetMember>::ENTITY_TYPE,
+                    $(<$tys as $crate::__private::ProjectionSetMember>::ENTITY_TYPE,)*
+                ]
+                .into_iter()
+                .try_fold(::std::vec::Vec::new(), |mut entity_types, entity_type| {
+                    let entity_type = entity_type?;
+                    if !entity_types.contains(&entity_type) {
+                        entity_types.push(entity_type);
+                    }
+                    ::std::option::Option::Some(entity_types)
+                })
+                .unwrap_or_default()
+            }
         }
 
         // Verifies that the Table types are all equal via the `once_projection_expression!` macro
@@ -562,6 +2251,7 @@ macro_rules! projections {
 /// #     fn client(&self) -> &aws_sdk_dynamodb::Client {unimplemented!()}
 /// # }
 /// #
+/// # #[derive(serde::Deserialize)]
 /// # struct User {}
 /// # impl modyne::EntityDef for User {
 /// #     const ENTITY_TYPE: &'static modyne::EntityTypeNameRef = modyne::EntityTypeNameRef::from_static("user");
@@ -592,19 +2282,24 @@ macro_rules! once_projection_expression {
         $crate::ensure_table_types_are_same!($ty, $($tys),*);
 
         const PROJECTIONS: &'static [&'static [&'static str]] = &[
-            <$ty as $crate::Projection>::PROJECTED_ATTRIBUTES,
+            <$ty as $crate::__private::ProjectionSetMember>::PROJECTED_ATTRIBUTES,
             $(
-                <$tys as $crate::Projection>::PROJECTED_ATTRIBUTES,
+                <$tys as $crate::__private::ProjectionSetMember>::PROJECTED_ATTRIBUTES,
             )*
         ];
 
+        const PROJECTS_KEYS: bool =
+            <$ty as $crate::__private::ProjectionSetMember>::PROJECTS_KEYS
+            $(|| <$tys as $crate::__private::ProjectionSetMember>::PROJECTS_KEYS)*;
+
         static PROJECTION_ONCE: $crate::__private::OnceLock<
             ::std::option::Option<$crate::expr::StaticProjection>,
         > = $crate::__private::OnceLock::new();
 
         *PROJECTION_ONCE.get_or_init(|| {
-            $crate::__private::generate_projection_expression::<<<$ty as $crate::Projection>::Entity as $crate::Entity>::Table>(
+            $crate::__private::generate_keyed_projection_expression::<<$ty as $crate::__private::ProjectionSetMember>::Table>(
                 PROJECTIONS,
+                PROJECTS_KEYS,
             )
         })
     }};
@@ -630,6 +2325,79 @@ macro_rules! read_projection {
     }};
 }
 
+/// Utility macro for reading every matching projection from a DynamoDB item
+///
+/// Like [`read_projection!`], the projection set is inferred from the context
+/// in which this macro is used. The item is parsed into each projection in
+/// the set that matches its entity type, using
+/// [`ProjectionSet::try_from_item_all()`], so that an aggregate may include
+/// several projections of the same entity type. Unknown entity types produce
+/// no projections.
+///
+/// ```
+/// use modyne::{projections, read_projections, Aggregate, Error, Item, Projection};
+/// # use modyne::{keys, Entity, EntityDef, Table};
+/// # use std::collections::HashMap;
+/// # struct App;
+/// # impl Table for App {
+/// #     type PrimaryKey = keys::Primary;
+/// #     type IndexKeys = keys::Gsi1;
+/// #     fn table_name(&self) -> &str { unimplemented!() }
+/// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+/// # }
+/// # #[derive(Debug, EntityDef, serde::Serialize, serde::Deserialize)]
+/// # struct Order { order_id: String, status: String, amount: f64 }
+/// # impl Entity for Order {
+/// #     type KeyInput<'a> = &'a str;
+/// #     type Table = App;
+/// #     type IndexKeys = ();
+/// #     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+/// #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+/// # }
+///
+/// #[derive(Debug, Projection, serde::Deserialize)]
+/// #[entity(Order)]
+/// struct OrderSummary {
+///     order_id: String,
+/// }
+///
+/// projections! {
+///     enum OrderViews {
+///         OrderSummary,
+///         Order,
+///     }
+/// }
+///
+/// #[derive(Debug, Default)]
+/// struct Orders {
+///     summaries: Vec<OrderSummary>,
+///     details: HashMap<String, Order>,
+/// }
+///
+/// impl Aggregate for Orders {
+///     type Projections = OrderViews;
+///
+///     fn merge(&mut self, item: Item) -> Result<(), Error> {
+///         for projection in read_projections!(item)? {
+///             match projection {
+///                 OrderViews::OrderSummary(summary) => self.summaries.push(summary),
+///                 OrderViews::Order(order) => {
+///                     self.details.insert(order.order_id.clone(), order);
+///                 }
+///             }
+///         }
+///
+///         Ok(())
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! read_projections {
+    ($item:expr) => {{
+        <Self::Projections as $crate::ProjectionSet>::try_from_item_all($item)
+    }};
+}
+
 /// Ensures that the table types will match for all variants in a projection set
 #[macro_export]
 #[doc(hidden)]
@@ -652,8 +2420,8 @@ macro_rules! ensure_table_types_are_same {
             {}
 
             assert_table_types_match_for_all_projection_variants::<
-                <<$ty as $crate::Projection>::Entity as $crate::Entity>::Table,
-                <<$tys as $crate::Projection>::Entity as $crate::Entity>::Table,
+                <$ty as $crate::__private::ProjectionSetMember>::Table,
+                <$tys as $crate::__private::ProjectionSetMember>::Table,
             >();
         })* };
     };
@@ -667,6 +2435,18 @@ pub trait Aggregate: Default {
     /// This type is usually generated using the [`projections!`] macro.
     type Projections: ProjectionSet;
 
+    /// The projection expression used when querying items into the aggregate
+    ///
+    /// By default, this is the projection expression of the
+    /// [`Projections`][Aggregate::Projections]. Override this method to
+    /// return `None` to retrieve full items, such as when an entity in the
+    /// aggregate reads attributes that are not listed in its
+    /// [`PROJECTED_ATTRIBUTES`][EntityDef::PROJECTED_ATTRIBUTES].
+    #[inline]
+    fn projection_expression() -> Option<expr::StaticProjection> {
+        Self::Projections::projection_expression()
+    }
+
     /// Extends the aggregate with the entities represented by the given items
     fn reduce<I>(&mut self, items: I) -> Result<(), Error>
     where
@@ -697,7 +2477,7 @@ where
             let parsed = P::from_item(item)?;
             Ok(Some(parsed))
         } else {
-            tracing::warn!(entity_type = entity_type.as_str(), "unknown entity type");
+            crate::instrumentation::skip_unknown_entity_type(entity_type);
             Ok(None)
         }
     }
@@ -719,373 +2499,4370 @@ where
 
         // If we didn't find the projection, take a write lock and compute it
         let mut projections = ENTITY_PROJECTION_EXPRESSION.write().unwrap();
+        // The generated projection expression is leaked. This is safe since we're the
+        // only ones with a lock that allows generating an expression. Thus no unnecessary
+        // expressions will be generated (only one expression per projection; no
+        // unbounded leaks). This expression will then be reused for the rest of the
+        // process lifetime.
         *projections.entry(TypeId::of::<P>()).or_insert_with(|| {
-            // If the entity type doesn't have any projected attributes, then we can't
-            // generate a projection expression. This then means that _all_ attributes
-            // will be returned.
-            if !P::PROJECTED_ATTRIBUTES.iter().all(|a| !a.is_empty()) {
-                return None;
-            }
-
-            let projection =
-                expr::Projection::new(P::PROJECTED_ATTRIBUTES.iter().copied().chain([
-                    <<P::Entity as crate::Entity>::Table as crate::Table>::ENTITY_TYPE_ATTRIBUTE,
-                ]));
-
-            // Leak the generated projection expression. This is safe since we're the
-            // only ones with a lock that allows generating an expression. Thus no unnecessary
-            // expressions will be generated (only one expression per projection; no
-            // unbounded leaks). This expression will then be reused for the rest of the
-            // process lifetime.
-            Some(projection.leak())
+            crate::__private::generate_projection_expression::<<P::Entity as crate::Entity>::Table>(
+                &[crate::__private::projected_attributes::<P::Entity>(
+                    P::PROJECTED_ATTRIBUTES,
+                )],
+            )
         })
     }
-}
-
-impl<'a, P> Aggregate for Vec<P>
-where
-    P: Projection + serde::Deserialize<'a> + 'static,
-{
-    type Projections = P;
-
-    fn reduce<I>(&mut self, items: I) -> Result<(), Error>
-    where
-        I: IntoIterator<Item = Item>,
-    {
-        let items = items.into_iter();
-        self.reserve(items.size_hint().0);
-        for item in items {
-            self.merge(item)?;
-        }
-
-        Ok(())
-    }
 
-    fn merge(&mut self, item: Item) -> Result<(), Error> {
-        let entity = read_projection!(item)?;
-        self.push(entity);
-        Ok(())
+    #[inline]
+    fn entity_types() -> Vec<&'static EntityTypeNameRef> {
+        vec![<P::Entity as EntityDef>::ENTITY_TYPE]
     }
 }
 
-/// A value that can be used to query an aggregate
-pub trait QueryInput {
-    /// Whether to use consistent reads for the query
-    const CONSISTENT_READ: bool = false;
-
-    /// Whether to scan the index forward
-    const SCAN_INDEX_FORWARD: bool = true;
-
-    /// The index used to query the aggregate
-    type Index: keys::Key;
-
-    /// The aggregate that this query is for
-    type Aggregate: Aggregate;
-
-    /// The key condition to apply on this query
-    fn key_condition(&self) -> expr::KeyCondition<Self::Index>;
+/// An item of any entity type, kept in its raw form
+///
+/// A raw item matches every entity type, so it can be used as a catch-all
+/// in a [`projections!`] set, after the projections that should be parsed
+/// eagerly. This allows generic tooling, such as exporters or debuggers, to
+/// read every item in a partition, and keeps aggregates working when entity
+/// types that they do not yet know about are written to a partition. The item
+/// can be parsed later with [`parse()`][RawItem::parse()].
+///
+/// Because the [`projections!`] macro only accepts plain type names, use a
+/// type alias for the table:
+///
+/// ```
+/// use modyne::{keys, projections, EntityDef, Entity, RawItem, Table};
+/// # struct App;
+/// # impl Table for App {
+/// #     type PrimaryKey = keys::Primary;
+/// #     type IndexKeys = keys::Gsi1;
+/// #     fn table_name(&self) -> &str { unimplemented!() }
+/// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+/// # }
+/// # #[derive(Debug, EntityDef, serde::Serialize, serde::Deserialize)]
+/// # struct Order { order_id: String }
+/// # impl Entity for Order {
+/// #     type KeyInput<'a> = &'a str;
+/// #     type Table = App;
+/// #     type IndexKeys = ();
+/// #     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+/// #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+/// # }
+///
+/// type Other = RawItem<App>;
+///
+/// projections! {
+///     #[derive(Debug)]
+///     enum PartitionItem {
+///         Order,
+///         Other,
+///     }
+/// }
+/// ```
+///
+/// Since every item is retrieved in full, listing a raw item in a projection
+/// set disables the projection expression for the whole set. When an item is
+/// parsed with [`ProjectionSet::try_from_item_all()`], the raw item is
+/// included even if the item also matched other projections.
+pub struct RawItem<T> {
+    entity_type: EntityTypeName,
+    item: Item,
+    table: std::marker::PhantomData<fn() -> T>,
+}
 
-    /// Specify which items should be returned by the query
+impl<T: Table> RawItem<T> {
+    /// Wraps a DynamoDB item read from the table
     ///
-    /// This is a filter expression that is applied to items after reading but
-    /// before returning. Items scanned but not returned by the filter
-    /// expression will still be counted towards any limit and read
-    /// capacity quotas.
+    /// # Errors
     ///
-    /// Where possible, it is preferrable to rely on the key condition to
-    /// filter the set of items returned, as that will be more efficient.
-    #[inline]
-    fn filter_expression(&self) -> Option<expr::Filter> {
-        None
+    /// This method will return an error if the item does not identify its
+    /// entity type.
+    pub fn from_item(item: Item) -> Result<Self, Error> {
+        let entity_type = __private::get_table_entity_type::<T>(&item)?.to_owned();
+        Ok(Self {
+            entity_type,
+            item,
+            table: std::marker::PhantomData,
+        })
     }
-}
 
-/// Extensions to an aggregate query
-pub trait QueryInputExt: QueryInput {
-    /// Prepare a DynamoDB query
+    /// Parses the item into a projection set, if its entity type is part of the set
     ///
-    /// This will prepare a query operation for the input, applying
-    /// the key condition, filter expression, read consistency,
-    /// and scan direction as defined by the input. Additional settings can
-    /// be applied by chaining methods on the returned [`Query`] value.
-    fn query(&self) -> Query<Self::Index>;
+    /// Any entity type, including a single [`Projection`], is a projection set.
+    pub fn parse<P: ProjectionSet>(&self) -> Result<Option<P>, Error> {
+        P::try_from_item(self.item.clone())
+    }
 }
 
-impl<Q> QueryInputExt for Q
-where
-    Q: QueryInput + ?Sized,
-{
-    fn query(&self) -> Query<Self::Index> {
-        let mut query = Query::new(self.key_condition());
-
-        if let Some(projection) =
-            <<Self as QueryInput>::Aggregate as Aggregate>::Projections::projection_expression()
-        {
-            query = query.projection(projection);
-        }
+impl<T> RawItem<T> {
+    /// The entity type of the item
+    #[inline]
+    pub fn entity_type(&self) -> &EntityTypeNameRef {
+        &self.entity_type
+    }
 
-        if let Some(filter) = self.filter_expression() {
-            query = query.filter(filter);
-        }
+    /// The raw DynamoDB item
+    #[inline]
+    pub fn item(&self) -> &Item {
+        &self.item
+    }
 
-        if Self::CONSISTENT_READ {
-            query = query.consistent_read();
-        }
+    /// Returns the raw DynamoDB item
+    #[inline]
+    pub fn into_item(self) -> Item {
+        self.item
+    }
+}
 
-        if !Self::SCAN_INDEX_FORWARD {
-            query = query.scan_index_backward();
+impl<T> Clone for RawItem<T> {
+    fn clone(&self) -> Self {
+        Self {
+            entity_type: self.entity_type.clone(),
+            item: self.item.clone(),
+            table: std::marker::PhantomData,
         }
-
-        query
     }
 }
 
-/// A value that can be used to query an aggregate
-pub trait ScanInput {
-    /// Whether to use consistent reads for the scan
-    const CONSISTENT_READ: bool = false;
-
-    /// The index to be scanned
-    type Index: keys::Key;
+impl<T> std::fmt::Debug for RawItem<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RawItem")
+            .field("entity_type", &self.entity_type)
+            .field("item", &self.item)
+            .finish()
+    }
+}
 
-    /// Specify which items should be returned by the scan
-    ///
-    /// This is a filter expression that is applied to items after reading but
-    /// before returning. Items scanned but not returned by the filter
-    /// expression will still be counted towards any limit and read
-    /// capacity quotas.
+impl<T: Table> ProjectionSet for RawItem<T> {
     #[inline]
-    fn filter_expression(&self) -> Option<expr::Filter> {
-        None
+    fn try_from_item(item: Item) -> Result<Option<Self>, Error> {
+        Self::from_item(item).map(Some)
     }
 
-    /// Specify which attributes should be returned by the scan
-    ///
-    /// This is a projection expression that is applied to items being
-    /// returned. The full size of an item is counted toward read
-    /// capacity usage, regardless of which attributes are returned.
-    ///
-    /// The [`once_projection_expression!`] macro can be used to automatically
-    /// generate a projection expression from a known set of entities that
-    /// the scan will return.
     #[inline]
     fn projection_expression() -> Option<expr::StaticProjection> {
         None
     }
 }
 
-/// Extensions to an aggregate scan
-pub trait ScanInputExt: ScanInput {
-    /// Prepare a DynamoDB scan
-    ///
-    /// This will prepare a scan operation for the input, applying
-    /// filter expression and consistent read settings as defined by the input.
-    /// Additional settings can be applied by chaining methods
-    /// on the returned [`Scan`] value.
-    fn scan(&self) -> Scan<Self::Index>;
-}
+impl<T: Table> Aggregate for Vec<RawItem<T>> {
+    type Projections = RawItem<T>;
 
-impl<S> ScanInputExt for S
-where
-    S: ScanInput + ?Sized,
-{
-    fn scan(&self) -> Scan<Self::Index> {
-        let mut scan = Scan::new();
+    fn merge(&mut self, item: Item) -> Result<(), Error> {
+        self.push(RawItem::from_item(item)?);
+        Ok(())
+    }
+}
 
-        if let Some(filter) = self.filter_expression() {
-            scan = scan.filter(filter);
-        }
+/// A projection, along with the key attributes of the item it was read from
+///
+/// Entities rarely store the attributes that make up their keys, since these
+/// are derived from the entity when it is written. When the key an item was
+/// stored under is needed after reading it, such as the date partition of a
+/// global secondary index, wrapping a projection in `WithKeys` captures the
+/// primary and secondary index key attributes alongside it. The attributes
+/// are picked out of the item using the key definitions of the table, and are
+/// included in the projection expression.
+///
+/// ```
+/// use modyne::{keys, Entity, EntityDef, EntityExt, ProjectionSet, WithKeys};
+/// # use modyne::Table;
+/// # struct App;
+/// # impl Table for App {
+/// #     type PrimaryKey = keys::Primary;
+/// #     type IndexKeys = keys::Gsi1;
+/// #     fn table_name(&self) -> &str { unimplemented!() }
+/// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+/// # }
+///
+/// #[derive(Debug, EntityDef, serde::Serialize, serde::Deserialize)]
+/// struct Reading {
+///     sensor_id: String,
+///     taken_at: String,
+/// }
+///
+/// impl Entity for Reading {
+///     type KeyInput<'a> = (&'a str, &'a str);
+///     type Table = App;
+///     type IndexKeys = keys::Gsi1;
+///
+///     fn primary_key((sensor_id, taken_at): Self::KeyInput<'_>) -> keys::Primary {
+///         keys::Primary {
+///             hash: format!("SENSOR#{sensor_id}"),
+///             range: format!("READING#{taken_at}"),
+///         }
+///     }
+///
+///     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+///         keys::FullKey {
+///             primary: Self::primary_key((&self.sensor_id, &self.taken_at)),
+///             indexes: keys::Gsi1 {
+///                 hash: format!("DAY#{}", &self.taken_at[..10]),
+///                 range: self.taken_at.clone(),
+///             },
+///         }
+///     }
+/// }
+///
+/// let item = Reading {
+///     sensor_id: "42".to_string(),
+///     taken_at: "2023-12-07T10:00:00Z".to_string(),
+/// }
+/// .into_item();
+///
+/// let reading = WithKeys::<Reading>::try_from_item(item).unwrap().unwrap();
+/// let day = reading.index_key::<keys::Gsi1>().unwrap().unwrap();
+/// assert_eq!(day.hash, "DAY#2023-12-07");
+/// assert_eq!(reading.sensor_id, "42");
+/// ```
+pub struct WithKeys<P> {
+    projection: P,
+    keys: Item,
+}
 
-        if let Some(projection) = Self::projection_expression() {
-            scan = scan.projection(projection)
-        }
+impl<P: ProjectionExt> WithKeys<P> {
+    /// Deserializes a DynamoDB item into the projection, capturing its key attributes
+    pub fn from_item(item: Item) -> Result<Self, Error> {
+        let keys = key_attribute_names::<<P::Entity as Entity>::Table>()
+            .filter_map(|name| Some((name.to_string(), item.get(name)?.clone())))
+            .collect();
+        let projection = P::from_item(item)?;
+        Ok(Self { projection, keys })
+    }
 
-        if Self::CONSISTENT_READ {
-            scan = scan.consistent_read();
+    /// The primary key the item was stored under
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the primary key attributes were
+    /// not present in the item or cannot be deserialized.
+    pub fn primary_key(&self) -> Result<<<P::Entity as Entity>::Table as Table>::PrimaryKey, Error>
+    where
+        <<P::Entity as Entity>::Table as Table>::PrimaryKey: serde::de::DeserializeOwned,
+    {
+        self.parse_key()
+    }
+
+    /// The key of the item in a secondary index
+    ///
+    /// Returns `None` if the item was not present in the index, which is the
+    /// case for items that do not populate the hash key of a sparse index.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the key attributes cannot be
+    /// deserialized.
+    pub fn index_key<K>(&self) -> Result<Option<K>, Error>
+    where
+        K: keys::IndexKey + serde::de::DeserializeOwned,
+    {
+        if !self.keys.contains_key(K::INDEX_DEFINITION.hash_key()) {
+            return Ok(None);
         }
 
-        scan
+        self.parse_key().map(Some)
     }
-}
 
-#[derive(serde::Serialize)]
-struct FullEntity<T: Entity> {
-    #[serde(flatten)]
-    keys: keys::FullKey<<T::Table as Table>::PrimaryKey, T::IndexKeys>,
+    fn parse_key<K: serde::de::DeserializeOwned>(&self) -> Result<K, Error> {
+        let key = codec::from_item(self.keys.clone()).map_err(|error| {
+            crate::error::ItemDeserializationError::new(
+                <P::Entity as EntityDef>::ENTITY_TYPE,
+                error,
+            )
+        })?;
 
-    #[serde(flatten)]
-    entity: T,
+        Ok(key)
+    }
 }
 
-#[doc(hidden)]
-pub mod __private {
-    pub type OnceLock<T> = std::sync::OnceLock<T>;
+impl<P> WithKeys<P> {
+    /// The key attributes of the item
+    #[inline]
+    pub fn key_attributes(&self) -> &Item {
+        &self.keys
+    }
 
-    pub fn get_entity_type<P: crate::Projection>(
-        item: &crate::Item,
-    ) -> Result<&crate::EntityTypeNameRef, crate::Error> {
-        let entity_type_attr = item
-            .get(<<P::Entity as crate::Entity>::Table as crate::Table>::ENTITY_TYPE_ATTRIBUTE)
-            .ok_or(crate::error::MissingEntityTypeError {})?;
-        let entity_type =
-            <<P::Entity as crate::Entity>::Table as crate::Table>::deserialize_entity_type(
-                entity_type_attr,
-            )?;
-        Ok(entity_type)
+    /// Returns the projection, discarding the key attributes
+    #[inline]
+    pub fn into_inner(self) -> P {
+        self.projection
     }
+}
 
-    /// Generate a projection expression for the given entity types
-    pub fn generate_projection_expression<T: crate::Table>(
-        attributes: &[&[&str]],
-    ) -> Option<crate::expr::StaticProjection> {
-        if !attributes.iter().all(|attrs| !attrs.is_empty()) {
-            return None;
-        }
+impl<P> std::ops::Deref for WithKeys<P> {
+    type Target = P;
 
-        let expr = crate::expr::Projection::new(
-            attributes
-                .iter()
-                .copied()
-                .flatten()
-                .copied()
-                .chain([T::ENTITY_TYPE_ATTRIBUTE]),
-        );
-        Some(expr.leak())
+    #[inline]
+    fn deref(&self) -> &P {
+        &self.projection
     }
 }
 
-/// Extension trait for [`Table`] to provide convenience methods for testing operations
-///
-/// The methods within this trait are not recommended for use outside of testing contexts.
-/// They are not intended for use in creating or managing production deployments, and
-/// do not provide configurability generally required by those tools.
-pub trait TestTableExt {
-    /// Prepare a create table operation
-    ///
-    /// Table will be created with the primary key and index keys specified in _pay per request_
-    /// mode.
-    fn create_table(
-        &self,
-    ) -> aws_sdk_dynamodb::operation::create_table::builders::CreateTableFluentBuilder;
+impl<P: Clone> Clone for WithKeys<P> {
+    fn clone(&self) -> Self {
+        Self {
+            projection: self.projection.clone(),
+            keys: self.keys.clone(),
+        }
+    }
+}
 
-    /// Prepare a delete table operation
-    fn delete_table(
-        &self,
-    ) -> aws_sdk_dynamodb::operation::delete_table::builders::DeleteTableFluentBuilder;
+impl<P: std::fmt::Debug> std::fmt::Debug for WithKeys<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WithKeys")
+            .field("projection", &self.projection)
+            .field("keys", &self.keys)
+            .finish()
+    }
 }
 
-impl<T> TestTableExt for T
-where
-    T: Table,
-{
-    fn create_table(
-        &self,
-    ) -> aws_sdk_dynamodb::operation::create_table::builders::CreateTableFluentBuilder {
-        let definitions: std::collections::BTreeSet<_> =
-            <<Self as Table>::IndexKeys as keys::IndexKeys>::KEY_DEFINITIONS
-                .iter()
-                .copied()
-                .collect();
+impl<P: ProjectionExt + 'static> ProjectionSet for WithKeys<P> {
+    fn try_from_item(item: Item) -> Result<Option<Self>, Error> {
+        let entity_type = crate::__private::get_entity_type::<P>(&item)?;
+        if entity_type == <P::Entity as EntityDef>::ENTITY_TYPE {
+            Self::from_item(item).map(Some)
+        } else {
+            crate::instrumentation::skip_unknown_entity_type(entity_type);
+            Ok(None)
+        }
+    }
 
-        let mut builder = self
-            .client()
-            .create_table()
-            .set_table_name(Some(self.table_name().into()));
+    fn projection_expression() -> Option<expr::StaticProjection> {
+        use std::{any::TypeId, collections::BTreeMap, sync::RwLock};
 
-        for definition in definitions {
-            let hash = aws_sdk_dynamodb::types::AttributeDefinition::builder()
-                .set_attribute_name(Some(definition.hash_key().into()))
-                .set_attribute_type(Some(aws_sdk_dynamodb::types::ScalarAttributeType::S))
-                .build()
-                .expect("attribute name and attribute type are always provided");
-            let mut key_schema = vec![aws_sdk_dynamodb::types::KeySchemaElement::builder()
-                .set_attribute_name(Some(definition.hash_key().into()))
-                .set_key_type(Some(aws_sdk_dynamodb::types::KeyType::Hash))
-                .build()
-                .expect("attribute name and key type are always provided")];
-            builder = builder.attribute_definitions(hash);
-            if let Some(range_key) = definition.range_key() {
-                let range = aws_sdk_dynamodb::types::AttributeDefinition::builder()
-                    .set_attribute_name(Some(range_key.into()))
-                    .set_attribute_type(Some(aws_sdk_dynamodb::types::ScalarAttributeType::S))
-                    .build()
-                    .expect("attribute name and attribute type are always provided");
-                key_schema.push(
-                    aws_sdk_dynamodb::types::KeySchemaElement::builder()
-                        .set_attribute_name(Some(range_key.into()))
-                        .set_key_type(Some(aws_sdk_dynamodb::types::KeyType::Range))
-                        .build()
-                        .expect("attribute name and key type are always provided"),
-                );
-                builder = builder.attribute_definitions(range)
+        static KEYED_PROJECTION_EXPRESSION: RwLock<
+            BTreeMap<TypeId, Option<expr::StaticProjection>>,
+        > = RwLock::new(BTreeMap::new());
+
+        {
+            let projections = KEYED_PROJECTION_EXPRESSION.read().unwrap();
+            if let Some(&projection) = projections.get(&TypeId::of::<P>()) {
+                return projection;
             }
-            let gsi = aws_sdk_dynamodb::types::GlobalSecondaryIndex::builder()
-                .set_index_name(Some(definition.index_name().into()))
-                .set_projection(Some(
-                    aws_sdk_dynamodb::types::Projection::builder()
-                        .set_projection_type(Some(aws_sdk_dynamodb::types::ProjectionType::All))
-                        .build(),
-                ))
-                .set_key_schema(Some(key_schema))
-                .build()
-                .expect("index name and key schema are always provided");
-            builder = builder.global_secondary_indexes(gsi);
         }
 
-        let primary_key_definition =
-            <<Self as Table>::PrimaryKey as keys::PrimaryKey>::PRIMARY_KEY_DEFINITION;
-        let hash = aws_sdk_dynamodb::types::AttributeDefinition::builder()
-            .set_attribute_name(Some(primary_key_definition.hash_key.into()))
-            .set_attribute_type(Some(aws_sdk_dynamodb::types::ScalarAttributeType::S))
-            .build()
-            .expect("attribute name and attribute type are always provided");
-        let mut key_schema = vec![aws_sdk_dynamodb::types::KeySchemaElement::builder()
-            .set_attribute_name(Some(primary_key_definition.hash_key.into()))
-            .set_key_type(Some(aws_sdk_dynamodb::types::KeyType::Hash))
-            .build()
-            .expect("attribute name and key type are always provided")];
-        builder = builder.attribute_definitions(hash);
-        if let Some(range_key) = primary_key_definition.range_key {
-            let range = aws_sdk_dynamodb::types::AttributeDefinition::builder()
-                .set_attribute_name(Some(range_key.into()))
-                .set_attribute_type(Some(aws_sdk_dynamodb::types::ScalarAttributeType::S))
-                .build()
-                .expect("attribute name and attribute type are always provided");
-            key_schema.push(
-                aws_sdk_dynamodb::types::KeySchemaElement::builder()
-                    .set_attribute_name(Some(range_key.into()))
-                    .set_key_type(Some(aws_sdk_dynamodb::types::KeyType::Range))
-                    .build()
-                    .expect("attribute name and key type are always provided"),
-            );
-            builder = builder.attribute_definitions(range)
+        let mut projections = KEYED_PROJECTION_EXPRESSION.write().unwrap();
+        *projections.entry(TypeId::of::<P>()).or_insert_with(|| {
+            crate::__private::generate_keyed_projection_expression::<
+                <P::Entity as crate::Entity>::Table,
+            >(
+                &[crate::__private::projected_attributes::<P::Entity>(
+                    P::PROJECTED_ATTRIBUTES,
+                )],
+                true,
+            )
+        })
+    }
+
+    #[inline]
+    fn entity_types() -> Vec<&'static EntityTypeNameRef> {
+        vec![<P::Entity as EntityDef>::ENTITY_TYPE]
+    }
+}
+
+impl<P: ProjectionExt + 'static> Aggregate for Vec<WithKeys<P>> {
+    type Projections = WithKeys<P>;
+
+    fn merge(&mut self, item: Item) -> Result<(), Error> {
+        let entity = read_projection!(item)?;
+        self.push(entity);
+        Ok(())
+    }
+}
+
+/// The names of the key attributes of the table and its secondary indexes
+fn key_attribute_names<T: Table>() -> impl Iterator<Item = &'static str> {
+    let primary = T::PrimaryKey::PRIMARY_KEY_DEFINITION;
+    std::iter::once(primary.hash_key)
+        .chain(primary.range_key)
+        .chain(
+            T::IndexKeys::KEY_DEFINITIONS
+                .iter()
+                .flat_map(|index| std::iter::once(index.hash_key()).chain(index.range_key())),
+        )
+}
+
+impl<'a, P> Aggregate for Vec<P>
+where
+    P: Projection + serde::Deserialize<'a> + 'static,
+{
+    type Projections = P;
+
+    fn reduce<I>(&mut self, items: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = Item>,
+    {
+        let items = items.into_iter();
+        self.reserve(items.size_hint().0);
+        for item in items {
+            self.merge(item)?;
         }
 
-        builder
-            .set_key_schema(Some(key_schema))
-            .billing_mode(aws_sdk_dynamodb::types::BillingMode::PayPerRequest)
+        Ok(())
     }
 
-    fn delete_table(
-        &self,
-    ) -> aws_sdk_dynamodb::operation::delete_table::builders::DeleteTableFluentBuilder {
-        self.client()
-            .delete_table()
-            .set_table_name(Some(self.table_name().into()))
+    fn merge(&mut self, item: Item) -> Result<(), Error> {
+        let entity = read_projection!(item)?;
+        self.push(entity);
+        Ok(())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// A value that can be used to query an aggregate
+pub trait QueryInput {
+    /// Whether to use consistent reads for the query
+    ///
+    /// This is the default for [`consistent_read()`][QueryInput::consistent_read()].
+    const CONSISTENT_READ: bool = false;
+
+    /// Whether to scan the index forward
+    ///
+    /// This is the default for [`scan_index_forward()`][QueryInput::scan_index_forward()].
+    const SCAN_INDEX_FORWARD: bool = true;
+
+    /// The index used to query the aggregate
+    type Index: keys::Key;
+
+    /// The aggregate that this query is for
+    type Aggregate: Aggregate;
+
+    /// The key condition to apply on this query
+    fn key_condition(&self) -> expr::KeyCondition<Self::Index>;
+
+    /// Specify which items should be returned by the query
+    ///
+    /// This is a filter expression that is applied to items after reading but
+    /// before returning. Items scanned but not returned by the filter
+    /// expression will still be counted towards any limit and read
+    /// capacity quotas.
+    ///
+    /// Where possible, it is preferrable to rely on the key condition to
+    /// filter the set of items returned, as that will be more efficient.
+    #[inline]
+    fn filter_expression(&self) -> Option<expr::Filter> {
+        None
+    }
+
+    /// Whether to use consistent reads for this query
+    ///
+    /// Defaults to [`CONSISTENT_READ`][QueryInput::CONSISTENT_READ]. Override
+    /// this method when the read consistency depends on the input.
+    #[inline]
+    fn consistent_read(&self) -> bool {
+        Self::CONSISTENT_READ
+    }
+
+    /// Whether to scan the index forward for this query
+    ///
+    /// Defaults to [`SCAN_INDEX_FORWARD`][QueryInput::SCAN_INDEX_FORWARD].
+    /// Override this method when the order of the results depends on the
+    /// input.
+    ///
+    /// ```
+    /// use modyne::{expr, keys, QueryInput};
+    /// # use modyne::{EntityDef, Entity, Table};
+    /// #
+    /// # struct App;
+    /// # impl Table for App {
+    /// #     type PrimaryKey = keys::Primary;
+    /// #     type IndexKeys = keys::Gsi1;
+    /// #     fn table_name(&self) -> &str { unimplemented!() }
+    /// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+    /// # }
+    /// #
+    /// # #[derive(Debug, EntityDef, serde::Serialize, serde::Deserialize)]
+    /// # struct Order { user_id: String, order_id: String }
+    /// # impl Entity for Order {
+    /// #     type KeyInput<'a> = &'a str;
+    /// #     type Table = App;
+    /// #     type IndexKeys = keys::Gsi1;
+    /// #     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+    /// #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+    /// # }
+    ///
+    /// struct UserOrders {
+    ///     user_id: String,
+    ///     oldest_first: bool,
+    /// }
+    ///
+    /// impl QueryInput for UserOrders {
+    ///     type Index = keys::Gsi1;
+    ///     type Aggregate = Vec<Order>;
+    ///
+    ///     fn key_condition(&self) -> expr::KeyCondition<Self::Index> {
+    ///         expr::KeyCondition::in_partition(format!("USER#{}", self.user_id))
+    ///     }
+    ///
+    ///     fn scan_index_forward(&self) -> bool {
+    ///         self.oldest_first
+    ///     }
+    /// }
+    /// ```
+    #[inline]
+    fn scan_index_forward(&self) -> bool {
+        Self::SCAN_INDEX_FORWARD
+    }
+}
+
+/// Extensions to an aggregate query
+pub trait QueryInputExt: QueryInput {
+    /// Prepare a DynamoDB query
+    ///
+    /// This will prepare a query operation for the input, applying
+    /// the key condition, filter expression, read consistency,
+    /// and scan direction as defined by the input. Additional settings can
+    /// be applied by chaining methods on the returned [`Query`] value.
+    fn query(&self) -> Query<Self::Index>;
+}
+
+impl<Q> QueryInputExt for Q
+where
+    Q: QueryInput + ?Sized,
+{
+    fn query(&self) -> Query<Self::Index> {
+        prepare_query::<_, Self::Aggregate>(
+            self.key_condition(),
+            self.filter_expression(),
+            self.consistent_read(),
+            self.scan_index_forward(),
+        )
+    }
+}
+
+/// The index through which an [`IndexedQueryInput`] reads its aggregate,
+/// with the key condition to apply to that index
+pub enum IndexChoice<L, R> {
+    /// Query the left index
+    Left(expr::KeyCondition<L>),
+
+    /// Query the right index
+    Right(expr::KeyCondition<R>),
+}
+
+impl<L, R> std::fmt::Debug for IndexChoice<L, R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Left(condition) => f.debug_tuple("Left").field(condition).finish(),
+            Self::Right(condition) => f.debug_tuple("Right").field(condition).finish(),
+        }
+    }
+}
+
+impl<L, R> Clone for IndexChoice<L, R> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Left(condition) => Self::Left(condition.clone()),
+            Self::Right(condition) => Self::Right(condition.clone()),
+        }
+    }
+}
+
+impl<L: keys::Key, R: keys::Key> IndexChoice<L, R> {
+    /// Chooses the left index if the condition holds, and the right index
+    /// otherwise, building the key condition for the chosen index only
+    pub fn when(
+        condition: bool,
+        left: impl FnOnce() -> expr::KeyCondition<L>,
+        right: impl FnOnce() -> expr::KeyCondition<R>,
+    ) -> Self {
+        if condition {
+            Self::Left(left())
+        } else {
+            Self::Right(right())
+        }
+    }
+}
+
+/// A value that can be used to query an aggregate through one of two
+/// indexes, chosen by the value
+///
+/// An aggregate is often reachable through more than one index, such as
+/// when items are listed in ascending order of one attribute through one
+/// index and in descending order of another through a second index. The
+/// input chooses the index with its [`key_condition()`][Self::key_condition()],
+/// and the query prepared by [`IndexedQueryInputExt::query()`] can be
+/// executed without matching on the chosen index.
+///
+/// ```
+/// use modyne::{expr, keys, IndexChoice, IndexedQueryInput};
+/// # use modyne::{EntityDef, Entity, Table};
+/// #
+/// # struct App;
+/// # impl Table for App {
+/// #     type PrimaryKey = keys::Primary;
+/// #     type IndexKeys = (keys::Gsi1, keys::Gsi2);
+/// #     fn table_name(&self) -> &str { unimplemented!() }
+/// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+/// # }
+/// #
+/// # #[derive(Debug, EntityDef, serde::Serialize, serde::Deserialize)]
+/// # struct Order { user_id: String, order_id: String }
+/// # impl Entity for Order {
+/// #     type KeyInput<'a> = &'a str;
+/// #     type Table = App;
+/// #     type IndexKeys = (keys::Gsi1, keys::Gsi2);
+/// #     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+/// #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+/// # }
+///
+/// struct UserOrders {
+///     user_id: String,
+///     by_due_date: bool,
+/// }
+///
+/// impl IndexedQueryInput for UserOrders {
+///     type Left = keys::Gsi1;
+///     type Right = keys::Gsi2;
+///     type Aggregate = Vec<Order>;
+///
+///     fn key_condition(&self) -> IndexChoice<Self::Left, Self::Right> {
+///         let partition = format!("USER#{}", self.user_id);
+///         IndexChoice::when(
+///             self.by_due_date,
+///             || expr::KeyCondition::in_partition(partition.clone()),
+///             || expr::KeyCondition::in_partition(partition.clone()),
+///         )
+///     }
+/// }
+/// ```
+pub trait IndexedQueryInput {
+    /// Whether to use consistent reads for the query
+    ///
+    /// This is the default for [`consistent_read()`][IndexedQueryInput::consistent_read()].
+    const CONSISTENT_READ: bool = false;
+
+    /// Whether to scan the index forward
+    ///
+    /// This is the default for [`scan_index_forward()`][IndexedQueryInput::scan_index_forward()].
+    const SCAN_INDEX_FORWARD: bool = true;
+
+    /// The first index through which the aggregate can be queried
+    type Left: keys::Key;
+
+    /// The second index through which the aggregate can be queried
+    type Right: keys::Key;
+
+    /// The aggregate that this query is for
+    type Aggregate: Aggregate;
+
+    /// The index to query, with the key condition to apply to it
+    fn key_condition(&self) -> IndexChoice<Self::Left, Self::Right>;
+
+    /// Specify which items should be returned by the query
+    ///
+    /// See [`QueryInput::filter_expression()`] for details.
+    #[inline]
+    fn filter_expression(&self) -> Option<expr::Filter> {
+        None
+    }
+
+    /// Whether to use consistent reads for this query
+    ///
+    /// Defaults to [`CONSISTENT_READ`][IndexedQueryInput::CONSISTENT_READ].
+    /// Consistent reads are not supported by global secondary indexes.
+    #[inline]
+    fn consistent_read(&self) -> bool {
+        Self::CONSISTENT_READ
+    }
+
+    /// Whether to scan the index forward for this query
+    ///
+    /// Defaults to [`SCAN_INDEX_FORWARD`][IndexedQueryInput::SCAN_INDEX_FORWARD].
+    #[inline]
+    fn scan_index_forward(&self) -> bool {
+        Self::SCAN_INDEX_FORWARD
+    }
+}
+
+/// Extensions to an aggregate query through one of two indexes
+pub trait IndexedQueryInputExt: IndexedQueryInput {
+    /// Prepare a DynamoDB query through the index chosen by the input
+    ///
+    /// This applies the key condition, filter expression, read consistency,
+    /// and scan direction as defined by the input, in the same way as
+    /// [`QueryInputExt::query()`].
+    fn query(&self) -> IndexedQuery<Self::Left, Self::Right>;
+}
+
+impl<Q> IndexedQueryInputExt for Q
+where
+    Q: IndexedQueryInput + ?Sized,
+{
+    fn query(&self) -> IndexedQuery<Self::Left, Self::Right> {
+        let filter = self.filter_expression();
+        let consistent_read = self.consistent_read();
+        let scan_index_forward = self.scan_index_forward();
+        match self.key_condition() {
+            IndexChoice::Left(condition) => {
+                IndexedQuery::Left(prepare_query::<_, Self::Aggregate>(
+                    condition,
+                    filter,
+                    consistent_read,
+                    scan_index_forward,
+                ))
+            }
+            IndexChoice::Right(condition) => {
+                IndexedQuery::Right(prepare_query::<_, Self::Aggregate>(
+                    condition,
+                    filter,
+                    consistent_read,
+                    scan_index_forward,
+                ))
+            }
+        }
+    }
+}
+
+/// Prepares a query for an aggregate through an index
+fn prepare_query<K: keys::Key, A: Aggregate>(
+    key_condition: expr::KeyCondition<K>,
+    filter: Option<expr::Filter>,
+    consistent_read: bool,
+    scan_index_forward: bool,
+) -> Query<K> {
+    let mut query = Query::new(key_condition).for_projections::<A::Projections>();
+
+    if let Some(projection) = A::projection_expression() {
+        query = query.projection(projection);
+    }
+
+    if let Some(filter) = filter {
+        query = query.filter(filter);
+    }
+
+    if consistent_read {
+        query = query.consistent_read();
+    }
+
+    if !scan_index_forward {
+        query = query.scan_index_backward();
+    }
+
+    query
+}
+
+/// A value that can be used to query an aggregate
+pub trait ScanInput {
+    /// Whether to use consistent reads for the scan
+    const CONSISTENT_READ: bool = false;
+
+    /// The index to be scanned
+    type Index: keys::Key;
+
+    /// Specify which items should be returned by the scan
+    ///
+    /// This is a filter expression that is applied to items after reading but
+    /// before returning. Items scanned but not returned by the filter
+    /// expression will still be counted towards any limit and read
+    /// capacity quotas.
+    #[inline]
+    fn filter_expression(&self) -> Option<expr::Filter> {
+        None
+    }
+
+    /// Specify which attributes should be returned by the scan
+    ///
+    /// This is a projection expression that is applied to items being
+    /// returned. The full size of an item is counted toward read
+    /// capacity usage, regardless of which attributes are returned.
+    ///
+    /// The [`once_projection_expression!`] macro can be used to automatically
+    /// generate a projection expression from a known set of entities that
+    /// the scan will return.
+    #[inline]
+    fn projection_expression() -> Option<expr::StaticProjection> {
+        None
+    }
+}
+
+/// Extensions to an aggregate scan
+pub trait ScanInputExt: ScanInput {
+    /// Prepare a DynamoDB scan
+    ///
+    /// This will prepare a scan operation for the input, applying
+    /// filter expression and consistent read settings as defined by the input.
+    /// Additional settings can be applied by chaining methods
+    /// on the returned [`Scan`] value.
+    fn scan(&self) -> Scan<Self::Index>;
+
+    /// Scan the index, reducing the returned items into an aggregate
+    ///
+    /// The scan may be divided into parallel segments, and may be stopped
+    /// early once an item or read capacity budget has been spent, in which
+    /// case the returned [`ScanCursor`][model::ScanCursor] can be used to
+    /// resume the scan. If the input does not specify a projection
+    /// expression, the aggregate's projection expression is used.
+    ///
+    /// ```no_run
+    /// # use modyne::{keys, Aggregate, Error, Item, RawItem, ScanInput, ScanInputExt, Table};
+    /// # use modyne::model::ScanCollectOptions;
+    /// # struct App;
+    /// # impl Table for App {
+    /// #     type PrimaryKey = keys::Primary;
+    /// #     type IndexKeys = keys::Gsi1;
+    /// #     fn table_name(&self) -> &str { unimplemented!() }
+    /// #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+    /// # }
+    /// # #[derive(Default)]
+    /// # struct Users(Vec<Item>);
+    /// # impl Aggregate for Users {
+    /// #     type Projections = RawItem<App>;
+    /// #     fn merge(&mut self, item: Item) -> Result<(), Error> { self.0.push(item); Ok(()) }
+    /// # }
+    /// struct AllUsers;
+    ///
+    /// impl ScanInput for AllUsers {
+    ///     type Index = keys::Gsi1;
+    /// }
+    ///
+    /// # async fn example(app: App) -> Result<(), Error> {
+    /// let options = ScanCollectOptions::new()
+    ///     .segments(4)
+    ///     .read_capacity_limit(100.0);
+    /// let mut collected = AllUsers.scan_collect::<Users, _>(&app, options).await?;
+    ///
+    /// while let Some(cursor) = collected.cursor.take() {
+    ///     let options = ScanCollectOptions::new()
+    ///         .read_capacity_limit(100.0)
+    ///         .resume_from(cursor);
+    ///     collected = AllUsers.scan_collect(&app, options).await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    fn scan_collect<A, T>(
+        &self,
+        table: &T,
+        options: model::ScanCollectOptions,
+    ) -> impl std::future::Future<Output = Result<model::ScanCollected<A>, Error>>
+    where
+        A: Aggregate,
+        T: Table;
+}
+
+impl<S> ScanInputExt for S
+where
+    S: ScanInput + ?Sized,
+{
+    fn scan(&self) -> Scan<Self::Index> {
+        let mut scan = Scan::new();
+
+        if let Some(filter) = self.filter_expression() {
+            scan = scan.filter(filter);
+        }
+
+        if let Some(projection) = Self::projection_expression() {
+            scan = scan.projection(projection)
+        }
+
+        if Self::CONSISTENT_READ {
+            scan = scan.consistent_read();
+        }
+
+        scan
+    }
+
+    fn scan_collect<A, T>(
+        &self,
+        table: &T,
+        options: model::ScanCollectOptions,
+    ) -> impl std::future::Future<Output = Result<model::ScanCollected<A>, Error>>
+    where
+        A: Aggregate,
+        T: Table,
+    {
+        let mut scan = self.scan();
+        if Self::projection_expression().is_none() {
+            if let Some(projection) = A::projection_expression() {
+                scan = scan.projection(projection);
+            }
+        }
+
+        model::scan_collect(scan, table, options)
+    }
+}
+
+#[derive(serde::Serialize)]
+struct FullEntity<'a, T: Entity> {
+    #[serde(flatten)]
+    keys: keys::FullKey<<T::Table as Table>::PrimaryKey, T::IndexKeys>,
+
+    #[serde(flatten)]
+    entity: &'a T,
+}
+
+/// The key attributes computed for an entity, including the entity type index
+/// keys if the entity is listed in that index
+pub(crate) fn computed_keys<T: Entity>(entity: &T) -> Item {
+    let mut key = entity.full_key().into_key();
+    key.extend(entity_type_index_keys(entity));
+    key
+}
+
+fn entity_type_index_keys<T: Entity>(entity: &T) -> Item {
+    let Some(range) = entity.entity_type_index_key() else {
+        return Item::new();
+    };
+    let index = keys::EntityTypeIndex {
+        hash: T::ENTITY_TYPE.to_string(),
+        range,
+    };
+    crate::codec::to_item(index).unwrap()
+}
+
+/// Applies the schema upgrades of an entity type to an item written with an
+/// older version of its schema
+fn upgrade_item<E: Entity>(mut item: Item) -> Result<Item, Error> {
+    let Some(attr) = <E::Table as Table>::SCHEMA_VERSION_ATTRIBUTE else {
+        return Ok(item);
+    };
+    if E::SCHEMA_UPGRADES.is_empty() {
+        return Ok(item);
+    }
+
+    let invalid = |reason| crate::error::InvalidSchemaVersionError {
+        entity_type: E::ENTITY_TYPE,
+        reason,
+    };
+    let version = match item.get(attr) {
+        Some(AttributeValue::N(n)) => n
+            .parse::<usize>()
+            .map_err(|_| invalid("schema version is not a non-negative integer"))?,
+        Some(_) => return Err(invalid("schema version is not a number").into()),
+        None => 0,
+    };
+
+    let Some(upgrades) = E::SCHEMA_UPGRADES.get(version..) else {
+        return Ok(item);
+    };
+    for upgrade in upgrades {
+        item = upgrade(item);
+    }
+    if !upgrades.is_empty() {
+        tracing::debug!(
+            entity_type = %E::ENTITY_TYPE,
+            from_version = version,
+            to_version = E::SCHEMA_UPGRADES.len(),
+            "upgraded item schema"
+        );
+    }
+
+    Ok(item)
+}
+
+/// Builds a condition that the item exists and that each of the attributes
+/// still holds the given value, or is absent if no value is given
+///
+/// Values are added as sensitive values, as they may hold any attribute of
+/// the item. An attribute given more than once is only checked once.
+pub(crate) fn unchanged_condition<'a, T: Table>(
+    attributes: impl IntoIterator<Item = (&'a str, Option<&'a AttributeValue>)>,
+) -> expr::Condition {
+    let mut clauses = vec!["attribute_exists(#PK)".to_string()];
+    let mut names: Vec<(String, &str)> = Vec::new();
+    let mut values = Vec::new();
+    for (attr, value) in attributes {
+        if names.iter().any(|(_, seen)| *seen == attr) {
+            continue;
+        }
+
+        let index = names.len();
+        let name = format!("#a{index}");
+        match value {
+            Some(value) => {
+                clauses.push(format!("{name} = :a{index}"));
+                values.push((format!(":a{index}"), value.clone()));
+            }
+            None => clauses.push(format!("attribute_not_exists({name})")),
+        }
+        names.push((name, attr));
+    }
+
+    let hash_key = <T::PrimaryKey as keys::PrimaryKey>::PRIMARY_KEY_DEFINITION.hash_key;
+    let condition = expr::Condition::new(clauses.join(" AND ")).name("#PK", hash_key);
+    let condition = names
+        .into_iter()
+        .fold(condition, |condition, (name, attr)| {
+            condition.name(&name, attr)
+        });
+    values
+        .into_iter()
+        .fold(condition, |condition, (name, value)| {
+            condition.raw_sensitive_value(&name, value)
+        })
+}
+
+/// Serializes the entity into a DynamoDB item, including its entity type and key attributes
+pub(crate) fn entity_to_item<T>(entity: &T) -> Item
+where
+    T: Entity + serde::Serialize,
+{
+    let () = <T::IndexKeys as keys::IndexKeys>::ASSERT_CONSISTENT;
+    let full_entity = FullEntity {
+        keys: entity.full_key(),
+        entity,
+    };
+    full_entity.keys.debug_assert_local_partitions();
+
+    let mut item = crate::codec::to_item(full_entity).unwrap();
+    for attr in T::TRANSIENT_ATTRIBUTES {
+        item.remove(*attr);
+    }
+    if let Some(attr) = <T::Table as Table>::SCHEMA_VERSION_ATTRIBUTE {
+        if !T::SCHEMA_UPGRADES.is_empty() {
+            item.insert(
+                attr.to_string(),
+                AttributeValue::N(T::SCHEMA_UPGRADES.len().to_string()),
+            );
+        }
+    }
+    item.extend(entity_type_index_keys(entity));
+
+    if let EntityDiscriminator::Prefix { .. } = <T::Table as Table>::ENTITY_DISCRIMINATOR {
+        return item;
+    }
+
+    if item
+        .insert(
+            <T::Table as Table>::ENTITY_TYPE_ATTRIBUTE.to_string(),
+            <T::Table as Table>::serialize_entity_type(T::ENTITY_TYPE),
+        )
+        .is_some()
+    {
+        tracing::warn!(
+            "serialized entity had attribute collision with entity type attribute `{}`",
+            <T::Table as Table>::ENTITY_TYPE_ATTRIBUTE,
+        );
+    }
+    item
+}
+
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(feature = "proptest")]
+    pub use crate::testing::round_trip::{assert_entity_round_trip, assert_unique_entity_types};
+
+    pub type OnceLock<T> = std::sync::OnceLock<T>;
+
+    /// Whether an index with the given name is among the index definitions
+    #[inline]
+    pub const fn has_index(
+        definitions: &[crate::keys::SecondaryIndexDefinition],
+        name: &str,
+    ) -> bool {
+        crate::unique::has_index(definitions, name)
+    }
+
+    /// Warns that an item with an unknown entity type was skipped, counting
+    /// it if skipped items are being tracked
+    #[inline]
+    pub fn skip_unknown_entity_type(entity_type: &crate::EntityTypeNameRef) {
+        crate::instrumentation::skip_unknown_entity_type(entity_type);
+    }
+
+    /// A type that may be listed in the [`projections!`][crate::projections!] macro
+    ///
+    /// This is implemented for every [`Projection`][crate::Projection], as well
+    /// as for [`RawItem`][crate::RawItem], which matches any entity type.
+    pub trait ProjectionSetMember: Sized {
+        /// The table that items are read from
+        type Table: crate::Table;
+
+        /// The attributes to project, or an empty list to retrieve full items
+        const PROJECTED_ATTRIBUTES: &'static [&'static str];
+
+        /// Whether the key attributes of the table must also be projected
+        const PROJECTS_KEYS: bool = false;
+
+        /// The entity type parsed into this type, or `None` if it matches any entity type
+        const ENTITY_TYPE: Option<&'static crate::EntityTypeNameRef>;
+
+        /// Whether an item with the given entity type is parsed into this type
+        fn matches(entity_type: &crate::EntityTypeNameRef) -> bool;
+
+        /// Parses an item into this type
+        fn from_item(item: crate::Item) -> Result<Self, crate::Error>;
+    }
+
+    impl<'a, P> ProjectionSetMember for P
+    where
+        P: crate::Projection + serde::Deserialize<'a>,
+    {
+        type Table = <P::Entity as crate::Entity>::Table;
+
+        const PROJECTED_ATTRIBUTES: &'static [&'static str] =
+            projected_attributes::<P::Entity>(P::PROJECTED_ATTRIBUTES);
+
+        const ENTITY_TYPE: Option<&'static crate::EntityTypeNameRef> =
+            Some(<P::Entity as crate::EntityDef>::ENTITY_TYPE);
+
+        #[inline]
+        fn matches(entity_type: &crate::EntityTypeNameRef) -> bool {
+            entity_type == <P::Entity as crate::EntityDef>::ENTITY_TYPE
+        }
+
+        #[inline]
+        fn from_item(item: crate::Item) -> Result<Self, crate::Error> {
+            <P as crate::ProjectionExt>::from_item(item)
+        }
+    }
+
+    impl<P> ProjectionSetMember for crate::WithKeys<P>
+    where
+        P: crate::ProjectionExt,
+    {
+        type Table = <P::Entity as crate::Entity>::Table;
+
+        const PROJECTED_ATTRIBUTES: &'static [&'static str] =
+            projected_attributes::<P::Entity>(P::PROJECTED_ATTRIBUTES);
+
+        const PROJECTS_KEYS: bool = true;
+
+        const ENTITY_TYPE: Option<&'static crate::EntityTypeNameRef> =
+            Some(<P::Entity as crate::EntityDef>::ENTITY_TYPE);
+
+        #[inline]
+        fn matches(entity_type: &crate::EntityTypeNameRef) -> bool {
+            entity_type == <P::Entity as crate::EntityDef>::ENTITY_TYPE
+        }
+
+        #[inline]
+        fn from_item(item: crate::Item) -> Result<Self, crate::Error> {
+            crate::WithKeys::from_item(item)
+        }
+    }
+
+    impl<T: crate::Table> ProjectionSetMember for crate::RawItem<T> {
+        type Table = T;
+
+        const PROJECTED_ATTRIBUTES: &'static [&'static str] = &[];
+
+        const ENTITY_TYPE: Option<&'static crate::EntityTypeNameRef> = None;
+
+        #[inline]
+        fn matches(_: &crate::EntityTypeNameRef) -> bool {
+            true
+        }
+
+        #[inline]
+        fn from_item(item: crate::Item) -> Result<Self, crate::Error> {
+            crate::RawItem::from_item(item)
+        }
+    }
+
+    pub fn get_entity_type<P: crate::Projection>(
+        item: &crate::Item,
+    ) -> Result<&crate::EntityTypeNameRef, crate::Error> {
+        get_table_entity_type::<<<P as crate::Projection>::Entity as crate::Entity>::Table>(item)
+    }
+
+    /// Extract the entity type of an item in the table
+    pub fn get_table_entity_type<T: crate::Table>(
+        item: &crate::Item,
+    ) -> Result<&crate::EntityTypeNameRef, crate::Error> {
+        let entity_type_attr = item
+            .get(discriminator_attribute::<T>())
+            .ok_or(crate::error::MissingEntityTypeError {})?;
+
+        let entity_type = match T::ENTITY_DISCRIMINATOR {
+            crate::EntityDiscriminator::Attribute => T::deserialize_entity_type(entity_type_attr)?,
+            crate::EntityDiscriminator::Prefix { separator, .. } => {
+                let Ok(value) = entity_type_attr.as_s() else {
+                    return Err(crate::MalformedEntityTypeError::ExpectedStringValue.into());
+                };
+                let prefix = value.split(separator).next().unwrap_or_default();
+                crate::EntityTypeNameRef::from_str(prefix)
+            }
+        };
+        Ok(entity_type)
+    }
+
+    /// The attributes to project when reading a projection of the entity
+    ///
+    /// Items of an entity type with [schema upgrades][crate::EntityDef::SCHEMA_UPGRADES]
+    /// may still hold the attributes of an older schema, which the upgrades
+    /// read but which are not listed as projected attributes. For these
+    /// entity types, no attributes are listed, so that full items are
+    /// retrieved.
+    pub const fn projected_attributes<E: crate::Entity>(
+        attributes: &'static [&'static str],
+    ) -> &'static [&'static str] {
+        if E::SCHEMA_UPGRADES.is_empty()
+            || <E::Table as crate::Table>::SCHEMA_VERSION_ATTRIBUTE.is_none()
+        {
+            attributes
+        } else {
+            &[]
+        }
+    }
+
+    /// The attribute used to identify the entity type of an item in the table
+    pub const fn discriminator_attribute<T: crate::Table>() -> &'static str {
+        match T::ENTITY_DISCRIMINATOR {
+            crate::EntityDiscriminator::Attribute => T::ENTITY_TYPE_ATTRIBUTE,
+            crate::EntityDiscriminator::Prefix { attribute, .. } => attribute,
+        }
+    }
+
+    /// Generate a projection expression for the given entity types
+    ///
+    /// If the attributes of any entity type are unknown, either because none
+    /// are listed or because one is empty, then no projection expression is
+    /// generated, so that full items are retrieved.
+    pub fn generate_projection_expression<T: crate::Table>(
+        attributes: &[&[&str]],
+    ) -> Option<crate::expr::StaticProjection> {
+        generate_keyed_projection_expression::<T>(attributes, false)
+    }
+
+    /// Generates a projection expression that may also project the key attributes of the table
+    pub fn generate_keyed_projection_expression<T: crate::Table>(
+        attributes: &[&[&str]],
+        include_keys: bool,
+    ) -> Option<crate::expr::StaticProjection> {
+        if attributes
+            .iter()
+            .any(|attrs| attrs.is_empty() || attrs.iter().any(|a| a.is_empty()))
+        {
+            tracing::debug!(
+                table = std::any::type_name::<T>(),
+                "projected attributes of an entity type are unknown; retrieving full items"
+            );
+            return None;
+        }
+
+        let key_attributes: Vec<&str> = if include_keys {
+            crate::key_attribute_names::<T>().collect()
+        } else {
+            Vec::new()
+        };
+
+        let expr = crate::expr::Projection::new(
+            attributes
+                .iter()
+                .copied()
+                .flatten()
+                .copied()
+                .chain([discriminator_attribute::<T>()])
+                .chain(T::SCHEMA_VERSION_ATTRIBUTE)
+                .chain(T::TTL_ATTRIBUTE.filter(|_| T::EXCLUDE_EXPIRED_ITEMS))
+                .chain(key_attributes.iter().copied()),
+        );
+        Some(expr.leak())
+    }
+}
+
+/// Extension trait for [`Table`] to provide convenience methods for testing operations
+///
+/// The methods within this trait are not recommended for use outside of testing contexts.
+/// They are not intended for use in creating or managing production deployments, and
+/// do not provide configurability generally required by those tools.
+pub trait TestTableExt {
+    /// Prepare a create table operation
+    ///
+    /// Table will be created with the primary key and index keys specified in _pay per request_
+    /// mode, with a stream enabled if the table declares a [`STREAM_VIEW`][Table::STREAM_VIEW].
+    fn create_table(
+        &self,
+    ) -> aws_sdk_dynamodb::operation::create_table::builders::CreateTableFluentBuilder;
+
+    /// Prepare a create table operation with the given options
+    ///
+    /// Table will be created with the primary key and index keys specified, configured
+    /// with the billing mode, encryption, streams, and table class in the options.
+    fn create_table_with(
+        &self,
+        options: &CreateTableOptions,
+    ) -> aws_sdk_dynamodb::operation::create_table::builders::CreateTableFluentBuilder;
+
+    /// Prepare an operation enabling time to live on the table
+    ///
+    /// Time to live cannot be enabled while creating a table, so this operation
+    /// should be sent once the table is active. Returns `None` if the table
+    /// does not declare a [`TTL_ATTRIBUTE`][Table::TTL_ATTRIBUTE].
+    fn update_time_to_live(
+        &self,
+    ) -> Option<
+        aws_sdk_dynamodb::operation::update_time_to_live::builders::UpdateTimeToLiveFluentBuilder,
+    >;
+
+    /// Prepare a delete table operation
+    fn delete_table(
+        &self,
+    ) -> aws_sdk_dynamodb::operation::delete_table::builders::DeleteTableFluentBuilder;
+}
+
+impl<T> TestTableExt for T
+where
+    T: Table,
+{
+    fn create_table(
+        &self,
+    ) -> aws_sdk_dynamodb::operation::create_table::builders::CreateTableFluentBuilder {
+        self.create_table_with(&CreateTableOptions::default())
+    }
+
+    fn create_table_with(
+        &self,
+        options: &CreateTableOptions,
+    ) -> aws_sdk_dynamodb::operation::create_table::builders::CreateTableFluentBuilder {
+        let () = <<Self as Table>::IndexKeys as keys::IndexKeys>::ASSERT_CONSISTENT;
+        let attribute_types = <<Self as Table>::IndexKeys as keys::IndexKeys>::KEY_ATTRIBUTE_TYPES;
+        let definitions: std::collections::BTreeMap<_, _> =
+            <<Self as Table>::IndexKeys as keys::IndexKeys>::KEY_DEFINITIONS
+                .iter()
+                .copied()
+                .enumerate()
+                .map(|(i, definition)| {
+                    let types = attribute_types
+                        .get(i)
+                        .copied()
+                        .unwrap_or(keys::KeyAttributeTypes::STRING);
+                    (definition, types)
+                })
+                .collect();
+
+        let mut builder = self
+            .client()
+            .create_table()
+            .set_table_name(Some(self.table_name().into()));
+
+        for (definition, key_types) in definitions {
+            let hash = aws_sdk_dynamodb::types::AttributeDefinition::builder()
+                .set_attribute_name(Some(definition.hash_key().into()))
+                .set_attribute_type(Some(key_types.hash.into_scalar()))
+                .build()
+                .expect("attribute name and attribute type are always provided");
+            let mut key_schema = vec![aws_sdk_dynamodb::types::KeySchemaElement::builder()
+                .set_attribute_name(Some(definition.hash_key().into()))
+                .set_key_type(Some(aws_sdk_dynamodb::types::KeyType::Hash))
+                .build()
+                .expect("attribute name and key type are always provided")];
+            builder = builder.attribute_definitions(hash);
+            if let Some(range_key) = definition.range_key() {
+                let range = aws_sdk_dynamodb::types::AttributeDefinition::builder()
+                    .set_attribute_name(Some(range_key.into()))
+                    .set_attribute_type(Some(key_types.range.into_scalar()))
+                    .build()
+                    .expect("attribute name and attribute type are always provided");
+                key_schema.push(
+                    aws_sdk_dynamodb::types::KeySchemaElement::builder()
+                        .set_attribute_name(Some(range_key.into()))
+                        .set_key_type(Some(aws_sdk_dynamodb::types::KeyType::Range))
+                        .build()
+                        .expect("attribute name and key type are always provided"),
+                );
+                builder = builder.attribute_definitions(range)
+            }
+            let gsi = aws_sdk_dynamodb::types::GlobalSecondaryIndex::builder()
+                .set_index_name(Some(definition.index_name().into()))
+                .set_projection(Some(
+                    aws_sdk_dynamodb::types::Projection::builder()
+                        .set_projection_type(Some(aws_sdk_dynamodb::types::ProjectionType::All))
+                        .build(),
+                ))
+                .set_key_schema(Some(key_schema))
+                .set_provisioned_throughput(
+                    options
+                        .index_throughput(definition.index_name())
+                        .map(Throughput::into_provisioned),
+                )
+                .build()
+                .expect("index name and key schema are always provided");
+            builder = builder.global_secondary_indexes(gsi);
+        }
+
+        let primary_key_definition =
+            <<Self as Table>::PrimaryKey as keys::PrimaryKey>::PRIMARY_KEY_DEFINITION;
+        let primary_key_types = <<Self as Table>::PrimaryKey as keys::PrimaryKey>::ATTRIBUTE_TYPES;
+        let hash = aws_sdk_dynamodb::types::AttributeDefinition::builder()
+            .set_attribute_name(Some(primary_key_definition.hash_key.into()))
+            .set_attribute_type(Some(primary_key_types.hash.into_scalar()))
+            .build()
+            .expect("attribute name and attribute type are always provided");
+        let mut key_schema = vec![aws_sdk_dynamodb::types::KeySchemaElement::builder()
+            .set_attribute_name(Some(primary_key_definition.hash_key.into()))
+            .set_key_type(Some(aws_sdk_dynamodb::types::KeyType::Hash))
+            .build()
+            .expect("attribute name and key type are always provided")];
+        builder = builder.attribute_definitions(hash);
+        if let Some(range_key) = primary_key_definition.range_key {
+            let range = aws_sdk_dynamodb::types::AttributeDefinition::builder()
+                .set_attribute_name(Some(range_key.into()))
+                .set_attribute_type(Some(primary_key_types.range.into_scalar()))
+                .build()
+                .expect("attribute name and attribute type are always provided");
+            key_schema.push(
+                aws_sdk_dynamodb::types::KeySchemaElement::builder()
+                    .set_attribute_name(Some(range_key.into()))
+                    .set_key_type(Some(aws_sdk_dynamodb::types::KeyType::Range))
+                    .build()
+                    .expect("attribute name and key type are always provided"),
+            );
+            builder = builder.attribute_definitions(range)
+        }
+
+        let billing_mode = if options.throughput.is_some() {
+            aws_sdk_dynamodb::types::BillingMode::Provisioned
+        } else {
+            aws_sdk_dynamodb::types::BillingMode::PayPerRequest
+        };
+
+        builder
+            .set_key_schema(Some(key_schema))
+            .billing_mode(billing_mode)
+            .set_provisioned_throughput(options.throughput.map(Throughput::into_provisioned))
+            .set_sse_specification(options.sse.clone())
+            .set_stream_specification(options.stream_view.clone().or(Self::STREAM_VIEW).map(
+                |view| {
+                    aws_sdk_dynamodb::types::StreamSpecification::builder()
+                        .stream_enabled(true)
+                        .stream_view_type(view)
+                        .build()
+                        .expect("stream enabled is always provided")
+                },
+            ))
+            .set_table_class(options.table_class.clone())
+    }
+
+    fn update_time_to_live(
+        &self,
+    ) -> Option<
+        aws_sdk_dynamodb::operation::update_time_to_live::builders::UpdateTimeToLiveFluentBuilder,
+    > {
+        let attribute_name = Self::TTL_ATTRIBUTE?;
+        let specification = aws_sdk_dynamodb::types::TimeToLiveSpecification::builder()
+            .enabled(true)
+            .attribute_name(attribute_name)
+            .build()
+            .expect("enabled and attribute name are always provided");
+        Some(
+            self.client()
+                .update_time_to_live()
+                .set_table_name(Some(self.table_name().into()))
+                .time_to_live_specification(specification),
+        )
+    }
+
+    fn delete_table(
+        &self,
+    ) -> aws_sdk_dynamodb::operation::delete_table::builders::DeleteTableFluentBuilder {
+        self.client()
+            .delete_table()
+            .set_table_name(Some(self.table_name().into()))
+    }
+}
+
+/// Configuration for tables created with [`TestTableExt::create_table_with()`]
+///
+/// By default, tables are created in _pay per request_ mode, with default
+/// encryption, the table's [`STREAM_VIEW`][Table::STREAM_VIEW], and the
+/// standard table class.
+#[derive(Clone, Debug, Default)]
+#[must_use]
+pub struct CreateTableOptions {
+    throughput: Option<Throughput>,
+    index_throughput: HashMap<String, Throughput>,
+    sse: Option<aws_sdk_dynamodb::types::SseSpecification>,
+    stream_view: Option<aws_sdk_dynamodb::types::StreamViewType>,
+    table_class: Option<aws_sdk_dynamodb::types::TableClass>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Throughput {
+    read: i64,
+    write: i64,
+}
+
+impl Throughput {
+    fn into_provisioned(self) -> aws_sdk_dynamodb::types::ProvisionedThroughput {
+        aws_sdk_dynamodb::types::ProvisionedThroughput::builder()
+            .read_capacity_units(self.read)
+            .write_capacity_units(self.write)
+            .build()
+            .expect("read and write capacity units are always provided")
+    }
+}
+
+impl CreateTableOptions {
+    /// Prepare the default table configuration
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create the table in _pay per request_ mode
+    ///
+    /// This is the default. Any provisioned throughput is cleared.
+    pub fn pay_per_request(mut self) -> Self {
+        self.throughput = None;
+        self.index_throughput.clear();
+        self
+    }
+
+    /// Create the table in _provisioned_ mode with the given read and write capacity units
+    ///
+    /// Unless configured with [`index_provisioned()`][Self::index_provisioned()], each
+    /// global secondary index is provisioned with the same throughput as the table.
+    pub fn provisioned(mut self, read_capacity_units: i64, write_capacity_units: i64) -> Self {
+        self.throughput = Some(Throughput {
+            read: read_capacity_units,
+            write: write_capacity_units,
+        });
+        self
+    }
+
+    /// Provision a global secondary index with the given read and write capacity units
+    ///
+    /// This is ignored unless the table is [`provisioned()`][Self::provisioned()].
+    pub fn index_provisioned(
+        mut self,
+        index_name: impl Into<String>,
+        read_capacity_units: i64,
+        write_capacity_units: i64,
+    ) -> Self {
+        self.index_throughput.insert(
+            index_name.into(),
+            Throughput {
+                read: read_capacity_units,
+                write: write_capacity_units,
+            },
+        );
+        self
+    }
+
+    /// Configure server-side encryption of the table
+    pub fn sse(mut self, sse: aws_sdk_dynamodb::types::SseSpecification) -> Self {
+        self.sse = Some(sse);
+        self
+    }
+
+    /// Enable a stream on the table with the given view of changed items
+    ///
+    /// Defaults to the table's [`STREAM_VIEW`][Table::STREAM_VIEW].
+    pub fn stream(mut self, view: aws_sdk_dynamodb::types::StreamViewType) -> Self {
+        self.stream_view = Some(view);
+        self
+    }
+
+    /// Create the table with the given table class
+    pub fn table_class(mut self, table_class: aws_sdk_dynamodb::types::TableClass) -> Self {
+        self.table_class = Some(table_class);
+        self
+    }
+
+    fn index_throughput(&self, index_name: &str) -> Option<Throughput> {
+        let throughput = self.throughput?;
+        Some(
+            self.index_throughput
+                .get(index_name)
+                .copied()
+                .unwrap_or(throughput),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A table overriding every hook, for checking that the tables wrapping
+    /// it forward them
+    pub(crate) mod hooks {
+        use std::{
+            sync::{Mutex, PoisonError},
+            time::Duration,
+        };
+
+        use super::*;
+
+        pub(crate) struct HookedTable {
+            client: aws_sdk_dynamodb::Client,
+            scope: scope::KeyScope,
+            cache: cache::ItemCache,
+            clock: clock::FixedClock,
+            calls: Mutex<Vec<&'static str>>,
+        }
+
+        impl HookedTable {
+            pub(crate) fn new() -> Self {
+                let config = aws_sdk_dynamodb::Config::builder()
+                    .behavior_version(aws_sdk_dynamodb::config::BehaviorVersion::latest())
+                    .build();
+
+                Self {
+                    client: aws_sdk_dynamodb::Client::from_conf(config),
+                    scope: scope::KeyScope::new("tenant"),
+                    cache: cache::ItemCache::new(16, Duration::from_secs(60)),
+                    clock: clock::FixedClock::new(time::OffsetDateTime::UNIX_EPOCH),
+                    calls: Mutex::new(Vec::new()),
+                }
+            }
+
+            /// The hooks called so far, in order
+            pub(crate) fn calls(&self) -> Vec<&'static str> {
+                self.calls
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone()
+            }
+
+            fn record(&self, hook: &'static str) {
+                self.calls
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push(hook);
+            }
+        }
+
+        impl Table for HookedTable {
+            const ENTITY_TYPE_ATTRIBUTE: &'static str = "kind";
+            const ENTITY_DISCRIMINATOR: EntityDiscriminator = EntityDiscriminator::Prefix {
+                attribute: "SK",
+                separator: '#',
+            };
+            const SEMANTIC_CONVENTIONS: instrumentation::SemanticConventions =
+                instrumentation::SemanticConventions::V1_26;
+            const TTL_ATTRIBUTE: Option<&'static str> = Some("expires_at");
+            const EXCLUDE_EXPIRED_ITEMS: bool = true;
+            const SCHEMA_VERSION_ATTRIBUTE: Option<&'static str> = Some("schema_version");
+            const STREAM_VIEW: Option<aws_sdk_dynamodb::types::StreamViewType> =
+                Some(aws_sdk_dynamodb::types::StreamViewType::NewImage);
+
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn table_name(&self) -> &str {
+                "HookedTable"
+            }
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                &self.client
+            }
+
+            fn dynamo_client(&self) -> &dyn client::DynamoClient {
+                self.record("dynamo_client");
+                &self.client
+            }
+
+            fn key_scope(&self) -> Option<&scope::KeyScope> {
+                Some(&self.scope)
+            }
+
+            fn deserialize_entity_type(
+                _: &AttributeValue,
+            ) -> Result<&EntityTypeNameRef, MalformedEntityTypeError> {
+                Ok(EntityTypeNameRef::from_static("hooked"))
+            }
+
+            fn serialize_entity_type(_: &EntityTypeNameRef) -> AttributeValue {
+                AttributeValue::S("hooked".to_string())
+            }
+
+            fn item_cache(&self) -> Option<&cache::ItemCache> {
+                Some(&self.cache)
+            }
+
+            fn after_write(&self, _: &Item) {
+                self.record("after_write");
+            }
+
+            fn clock(&self) -> &dyn clock::Clock {
+                &self.clock
+            }
+
+            fn record_span_attributes(&self, _: &tracing::Span, _: &'static str) {
+                self.record("record_span_attributes");
+            }
+
+            fn record_partition_access(&self, _: &instrumentation::PartitionAccess<'_>) {
+                self.record("record_partition_access");
+            }
+
+            fn record_skipped_items(&self, _: &'static str, _: &instrumentation::SkippedItems) {
+                self.record("record_skipped_items");
+            }
+
+            fn redact_key(_: &Item) -> String {
+                "redacted".to_string()
+            }
+        }
+
+        /// Asserts that the wrapper forwards the hooks of the table it wraps
+        ///
+        /// The table name and clients are not checked, as wrappers may
+        /// replace them.
+        pub(crate) fn assert_forwards_hooks<W: Table>(wrapper: &W, table: &HookedTable) {
+            assert_eq!(W::ENTITY_TYPE_ATTRIBUTE, HookedTable::ENTITY_TYPE_ATTRIBUTE);
+            assert_eq!(W::ENTITY_DISCRIMINATOR, HookedTable::ENTITY_DISCRIMINATOR);
+            assert_eq!(W::SEMANTIC_CONVENTIONS, HookedTable::SEMANTIC_CONVENTIONS);
+            assert_eq!(W::TTL_ATTRIBUTE, HookedTable::TTL_ATTRIBUTE);
+            assert_eq!(W::EXCLUDE_EXPIRED_ITEMS, HookedTable::EXCLUDE_EXPIRED_ITEMS);
+            assert_eq!(
+                W::SCHEMA_VERSION_ATTRIBUTE,
+                HookedTable::SCHEMA_VERSION_ATTRIBUTE
+            );
+            assert_eq!(W::STREAM_VIEW, HookedTable::STREAM_VIEW);
+
+            let value = AttributeValue::Null(true);
+            assert!(W::deserialize_entity_type(&value).is_ok());
+            assert_eq!(
+                W::serialize_entity_type(EntityTypeNameRef::from_static("other")),
+                AttributeValue::S("hooked".to_string())
+            );
+            assert_eq!(W::redact_key(&Item::new()), "redacted");
+
+            assert!(
+                wrapper.key_scope().is_some(),
+                "`key_scope` is not forwarded"
+            );
+            assert!(
+                wrapper.item_cache().is_some(),
+                "`item_cache` is not forwarded"
+            );
+            assert_eq!(
+                wrapper.clock().now(),
+                time::OffsetDateTime::UNIX_EPOCH,
+                "`clock` is not forwarded"
+            );
+
+            wrapper.after_write(&Item::new());
+            wrapper.record_span_attributes(&tracing::Span::none(), "GetItem");
+            wrapper.record_partition_access(&instrumentation::PartitionAccess::new(
+                "GetItem", None, &value,
+            ));
+            wrapper.record_skipped_items("Query", &instrumentation::SkippedItems::new());
+            assert_eq!(
+                table.calls(),
+                [
+                    "after_write",
+                    "record_span_attributes",
+                    "record_partition_access",
+                    "record_skipped_items",
+                ]
+            );
+        }
+    }
+
+    mod standard {
+        use super::*;
+
+        struct TestTable;
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi13;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        impl WritableTable for TestTable {}
+
+        #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+        struct TestEntity {
+            id: String,
+            name: String,
+            email: String,
+        }
+
+        impl EntityDef for TestEntity {
+            const ENTITY_TYPE: &'static EntityTypeNameRef =
+                EntityTypeNameRef::from_static("test_ent");
+        }
+
+        impl Entity for TestEntity {
+            type KeyInput<'a> = (&'a str, &'a str);
+            type Table = TestTable;
+            type IndexKeys = keys::Gsi13;
+
+            fn primary_key((id, email): Self::KeyInput<'_>) -> keys::Primary {
+                keys::Primary {
+                    hash: format!("PK#{id}"),
+                    range: format!("NAME#{email}"),
+                }
+            }
+
+            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+                keys::FullKey {
+                    primary: Self::primary_key((&self.id, &self.email)),
+                    indexes: keys::Gsi13 {
+                        hash: format!("GSI13#{}", self.id),
+                        range: format!("GSI13#NAME#{}", self.name),
+                    },
+                }
+            }
+        }
+
+        #[test]
+        fn test_entity_serializes_as_expected() {
+            let entity = TestEntity {
+                id: "test1".to_string(),
+                name: "Test".to_string(),
+                email: "my_email@not_real.com".to_string(),
+            };
+
+            let item = entity.into_item();
+            assert_eq!(item.len(), 8);
+            assert_eq!(item["entity_type"].as_s().unwrap(), "test_ent");
+            assert_eq!(item["PK"].as_s().unwrap(), "PK#test1");
+            assert_eq!(item["SK"].as_s().unwrap(), "NAME#my_email@not_real.com");
+            assert_eq!(item["GSI13PK"].as_s().unwrap(), "GSI13#test1");
+            assert_eq!(item["GSI13SK"].as_s().unwrap(), "GSI13#NAME#Test");
+            assert_eq!(item["id"].as_s().unwrap(), "test1");
+            assert_eq!(item["name"].as_s().unwrap(), "Test");
+            assert_eq!(item["email"].as_s().unwrap(), "my_email@not_real.com");
+        }
+
+        #[test]
+        fn test_entity_deserializes_as_expected() {
+            let entity = TestEntity {
+                id: "test1".to_string(),
+                name: "Test".to_string(),
+                email: "my_email@not_real.com".to_string(),
+            };
+
+            let item = entity.clone().into_item();
+            let entity_type = TestTable::deserialize_entity_type(&item["entity_type"])
+                .unwrap()
+                .to_owned();
+            let clone = TestEntity::from_item(item).unwrap();
+
+            assert_eq!(entity, clone);
+            assert_eq!(entity_type, TestEntity::ENTITY_TYPE);
+        }
+
+        #[test]
+        fn update_from_diff_only_sets_changed_attributes() {
+            let old = TestEntity {
+                id: "test1".to_string(),
+                name: "Test".to_string(),
+                email: "my_email@not_real.com".to_string(),
+            };
+            let new = TestEntity {
+                name: "Renamed".to_string(),
+                ..old.clone()
+            };
+
+            assert!(TestEntity::update_from_diff(&old, &old).is_none());
+
+            let diff = expr::ItemDiff::between(&old, &new);
+            assert_eq!(diff.changed().collect::<Vec<_>>(), ["GSI13SK", "name"]);
+            assert!(TestEntity::update_from_diff(&old, &new).is_some());
+        }
+
+        #[test]
+        fn update_from_diff_requires_the_old_values_of_changed_attributes() {
+            let old = TestEntity {
+                id: "test1".to_string(),
+                name: "Test".to_string(),
+                email: "my_email@not_real.com".to_string(),
+            };
+            let new = TestEntity {
+                name: "Renamed".to_string(),
+                ..old.clone()
+            };
+
+            let table = crate::mock::MockTable::<TestTable>::new("test");
+            table.expect::<crate::mock::ops::UpdateItem>(|e| e.times(1));
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(
+                    TestEntity::update_from_diff(&old, &new)
+                        .unwrap()
+                        .execute(&table),
+                )
+                .unwrap();
+
+            let input = &table.inputs::<crate::mock::ops::UpdateItem>()[0];
+            assert_eq!(
+                input.condition_expression.as_deref(),
+                Some("attribute_exists(#cnd_PK) AND #cnd_a0 = :cnd_a0 AND #cnd_a1 = :cnd_a1")
+            );
+            let names = input.expression_attribute_names.as_ref().unwrap();
+            assert_eq!(names["#cnd_PK"], "PK");
+            assert_eq!(names["#cnd_a0"], "GSI13SK");
+            assert_eq!(names["#cnd_a1"], "name");
+            let values = input.expression_attribute_values.as_ref().unwrap();
+            assert_eq!(values[":cnd_a0"].as_s().unwrap(), "GSI13#NAME#Test");
+            assert_eq!(values[":cnd_a1"].as_s().unwrap(), "Test");
+            assert_eq!(values[":upd_diff_set1"].as_s().unwrap(), "Renamed");
+        }
+
+        #[test]
+        fn unchanged_condition_checks_each_field_once() {
+            let entity = TestEntity {
+                id: "test1".to_string(),
+                name: "Test".to_string(),
+                email: "my_email@not_real.com".to_string(),
+            };
+            let name = Field::<TestEntity>::new("name", "name");
+            let nickname = Field::<TestEntity>::new("nickname", "nickname");
+
+            let condition = entity.unchanged_condition(&[name, nickname, name, nickname]);
+            assert_eq!(
+                condition.expression,
+                "attribute_exists(#cnd_PK) AND #cnd_a0 = :cnd_a0 \
+                 AND attribute_not_exists(#cnd_a1)"
+            );
+            assert_eq!(
+                condition.names,
+                [
+                    ("#cnd_PK".to_string(), "PK".to_string()),
+                    ("#cnd_a0".to_string(), "name".to_string()),
+                    ("#cnd_a1".to_string(), "nickname".to_string()),
+                ]
+            );
+        }
+
+        #[test]
+        fn conditions_name_the_attribute_of_the_field() {
+            let email = Field::<TestEntity>::new("email", "emailAddress");
+            let condition = TestEntity::attr_not_exists(email);
+            assert_eq!(condition.expression, "attribute_not_exists(#cnd_attr)");
+            assert_eq!(
+                condition.names,
+                [("#cnd_attr".to_string(), "emailAddress".to_string())]
+            );
+        }
+
+        #[test]
+        fn with_keys_captures_the_key_attributes() {
+            let entity = TestEntity {
+                id: "test1".to_string(),
+                name: "Test".to_string(),
+                email: "my_email@not_real.com".to_string(),
+            };
+
+            let with_keys = WithKeys::<TestEntity>::try_from_item(entity.clone().into_item())
+                .unwrap()
+                .unwrap();
+
+            assert_eq!(*with_keys, entity);
+            assert_eq!(with_keys.key_attributes().len(), 4);
+            assert_eq!(
+                with_keys.primary_key().unwrap(),
+                TestEntity::primary_key(("test1", "my_email@not_real.com"))
+            );
+
+            let index = with_keys.index_key::<keys::Gsi13>().unwrap().unwrap();
+            assert_eq!(index.hash, "GSI13#test1");
+            assert_eq!(index.range, "GSI13#NAME#Test");
+            assert!(with_keys.index_key::<keys::Gsi1>().unwrap().is_none());
+        }
+    }
+
+    mod as_string_set {
+        use super::*;
+
+        struct TestTable;
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi13;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+
+            fn deserialize_entity_type(
+                attr: &AttributeValue,
+            ) -> Result<&EntityTypeNameRef, MalformedEntityTypeError> {
+                let values = attr.as_ss().map_err(|_| {
+                    MalformedEntityTypeError::Custom("expected a string set".into())
+                })?;
+                let value = values
+                    .first()
+                    .expect("a DynamoDB string set always has at least one element");
+                Ok(EntityTypeNameRef::from_str(value.as_str()))
+            }
+
+            fn serialize_entity_type(entity_type: &EntityTypeNameRef) -> AttributeValue {
+                AttributeValue::Ss(vec![entity_type.to_string()])
+            }
+        }
+
+        #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+        struct TestEntity {
+            id: String,
+            name: String,
+            email: String,
+        }
+
+        impl EntityDef for TestEntity {
+            const ENTITY_TYPE: &'static EntityTypeNameRef =
+                EntityTypeNameRef::from_static("test_ent");
+        }
+
+        impl Entity for TestEntity {
+            type KeyInput<'a> = (&'a str, &'a str);
+            type Table = TestTable;
+            type IndexKeys = keys::Gsi13;
+
+            fn primary_key((id, email): Self::KeyInput<'_>) -> keys::Primary {
+                keys::Primary {
+                    hash: format!("PK#{id}"),
+                    range: format!("NAME#{email}"),
+                }
+            }
+
+            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+                keys::FullKey {
+                    primary: Self::primary_key((&self.id, &self.email)),
+                    indexes: keys::Gsi13 {
+                        hash: format!("GSI13#{}", self.id),
+                        range: format!("GSI13#NAME#{}", self.name),
+                    },
+                }
+            }
+        }
+
+        #[test]
+        fn test_entity_serializes_as_expected() {
+            let entity = TestEntity {
+                id: "test1".to_string(),
+                name: "Test".to_string(),
+                email: "my_email@not_real.com".to_string(),
+            };
+
+            let item = entity.into_item();
+            assert_eq!(item.len(), 8);
+            assert_eq!(
+                item["entity_type"].as_ss().unwrap(),
+                &["test_ent".to_owned()]
+            );
+            assert_eq!(item["PK"].as_s().unwrap(), "PK#test1");
+            assert_eq!(item["SK"].as_s().unwrap(), "NAME#my_email@not_real.com");
+            assert_eq!(item["GSI13PK"].as_s().unwrap(), "GSI13#test1");
+            assert_eq!(item["GSI13SK"].as_s().unwrap(), "GSI13#NAME#Test");
+            assert_eq!(item["id"].as_s().unwrap(), "test1");
+            assert_eq!(item["name"].as_s().unwrap(), "Test");
+            assert_eq!(item["email"].as_s().unwrap(), "my_email@not_real.com");
+        }
+
+        #[test]
+        fn test_entity_deserializes_as_expected() {
+            let entity = TestEntity {
+                id: "test1".to_string(),
+                name: "Test".to_string(),
+                email: "my_email@not_real.com".to_string(),
+            };
+
+            let item = entity.clone().into_item();
+            let entity_type = TestTable::deserialize_entity_type(&item["entity_type"])
+                .unwrap()
+                .to_owned();
+            let clone = TestEntity::from_item(item).unwrap();
+
+            assert_eq!(entity, clone);
+            assert_eq!(entity_type, TestEntity::ENTITY_TYPE);
+        }
+    }
+
+    mod alternate_attribute {
+        use super::*;
+
+        struct TestTable;
+        impl Table for TestTable {
+            const ENTITY_TYPE_ATTRIBUTE: &'static str = "et";
+
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi13;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+        struct TestEntity {
+            id: String,
+            name: String,
+            email: String,
+        }
+
+        impl EntityDef for TestEntity {
+            const ENTITY_TYPE: &'static EntityTypeNameRef =
+                EntityTypeNameRef::from_static("test_ent");
+            const PROJECTED_ATTRIBUTES: &'static [&'static str] = &["id", "name", "email"];
+        }
+
+        impl Entity for TestEntity {
+            type KeyInput<'a> = (&'a str, &'a str);
+            type Table = TestTable;
+            type IndexKeys = keys::Gsi13;
+
+            fn primary_key((id, email): Self::KeyInput<'_>) -> keys::Primary {
+                keys::Primary {
+                    hash: format!("PK#{id}"),
+                    range: format!("NAME#{email}"),
+                }
+            }
+
+            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+                keys::FullKey {
+                    primary: Self::primary_key((&self.id, &self.email)),
+                    indexes: keys::Gsi13 {
+                        hash: format!("GSI13#{}", self.id),
+                        range: format!("GSI13#NAME#{}", self.name),
+                    },
+                }
+            }
+        }
+
+        #[test]
+        fn test_entity_serializes_as_expected() {
+            let entity = TestEntity {
+                id: "test1".to_string(),
+                name: "Test".to_string(),
+                email: "my_email@not_real.com".to_string(),
+            };
+
+            let item = entity.into_item();
+            assert_eq!(item.len(), 8);
+            assert_eq!(item["et"].as_s().unwrap(), "test_ent");
+            assert_eq!(item["PK"].as_s().unwrap(), "PK#test1");
+            assert_eq!(item["SK"].as_s().unwrap(), "NAME#my_email@not_real.com");
+            assert_eq!(item["GSI13PK"].as_s().unwrap(), "GSI13#test1");
+            assert_eq!(item["GSI13SK"].as_s().unwrap(), "GSI13#NAME#Test");
+            assert_eq!(item["id"].as_s().unwrap(), "test1");
+            assert_eq!(item["name"].as_s().unwrap(), "Test");
+            assert_eq!(item["email"].as_s().unwrap(), "my_email@not_real.com");
+        }
+
+        #[test]
+        fn test_entity_deserializes_as_expected() {
+            let entity = TestEntity {
+                id: "test1".to_string(),
+                name: "Test".to_string(),
+                email: "my_email@not_real.com".to_string(),
+            };
+
+            let item = entity.clone().into_item();
+            let entity_type = TestTable::deserialize_entity_type(&item["et"])
+                .unwrap()
+                .to_owned();
+            let clone = TestEntity::from_item(item).unwrap();
+
+            assert_eq!(entity, clone);
+            assert_eq!(entity_type, TestEntity::ENTITY_TYPE);
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        struct TestEntityName {
+            name: String,
+        }
+
+        impl Projection for TestEntityName {
+            type Entity = TestEntity;
+            const PROJECTED_ATTRIBUTES: &'static [&'static str] = &["name"];
+        }
+
+        projections! {
+            enum TestProjections {
+                TestEntity,
+                TestEntityName,
+            }
+        }
+
+        #[test]
+        fn projection_expressions_use_the_table_attribute() {
+            let projection = <TestEntity as ProjectionSet>::projection_expression().unwrap();
+            assert_eq!(projection.expression, "id,#prj_000,email,et");
+
+            let projection = <TestEntityName as ProjectionSet>::projection_expression().unwrap();
+            assert_eq!(projection.expression, "#prj_000,et");
+
+            let projection = TestProjections::projection_expression().unwrap();
+            assert_eq!(projection.expression, "id,#prj_000,email,et");
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        struct TestEntityAll {
+            #[serde(flatten)]
+            _attributes: HashMap<String, serde::de::IgnoredAny>,
+        }
+
+        impl Projection for TestEntityAll {
+            type Entity = TestEntity;
+            const PROJECTED_ATTRIBUTES: &'static [&'static str] = &[];
+        }
+
+        projections! {
+            #[allow(dead_code)]
+            enum TestMixedProjections {
+                TestEntityName,
+                TestEntityAll,
+            }
+        }
+
+        #[derive(Debug, Default)]
+        struct TestFullItems;
+
+        impl Aggregate for TestFullItems {
+            type Projections = TestProjections;
+
+            fn projection_expression() -> Option<expr::StaticProjection> {
+                None
+            }
+
+            fn merge(&mut self, _item: Item) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn unknown_projected_attributes_retrieve_full_items() {
+            assert!(<TestEntityAll as ProjectionSet>::projection_expression().is_none());
+            assert!(TestMixedProjections::projection_expression().is_none());
+            assert!(<Vec<TestEntityAll> as Aggregate>::projection_expression().is_none());
+            assert!(<TestFullItems as Aggregate>::projection_expression().is_none());
+        }
+
+        #[test]
+        fn projection_sets_list_their_entity_types() {
+            assert_eq!(
+                TestProjections::entity_types(),
+                vec![TestEntity::ENTITY_TYPE]
+            );
+            assert_eq!(
+                <Vec<TestEntityName> as Aggregate>::Projections::entity_types(),
+                vec![TestEntity::ENTITY_TYPE]
+            );
+            assert!(<RawItem<TestTable> as ProjectionSet>::entity_types().is_empty());
+        }
+
+        #[test]
+        fn projection_sets_read_the_table_attribute() {
+            let entity = TestEntity {
+                id: "test1".to_string(),
+                name: "Test".to_string(),
+                email: "my_email@not_real.com".to_string(),
+            };
+
+            let mut item = entity.clone().into_item();
+            let parsed = TestProjections::try_from_item(item.clone()).unwrap();
+            assert!(matches!(parsed, Some(TestProjections::TestEntity(e)) if e == entity));
+
+            let parsed = TestEntityName::try_from_item(item.clone())
+                .unwrap()
+                .unwrap();
+            assert_eq!(parsed.name, "Test");
+
+            let entity_type = item.remove("et").unwrap();
+            item.insert("entity_type".to_string(), entity_type);
+            assert!(TestProjections::try_from_item(item).is_err());
+        }
+
+        #[test]
+        fn projection_sets_yield_every_matching_projection() {
+            let entity = TestEntity {
+                id: "test1".to_string(),
+                name: "Test".to_string(),
+                email: "my_email@not_real.com".to_string(),
+            };
+
+            let item = entity.clone().into_item();
+            let parsed = TestProjections::try_from_item_all(item.clone()).unwrap();
+            assert_eq!(parsed.len(), 2);
+            assert!(matches!(&parsed[0], TestProjections::TestEntity(e) if *e == entity));
+            assert!(matches!(&parsed[1], TestProjections::TestEntityName(p) if p.name == "Test"));
+
+            let parsed = TestEntityName::try_from_item_all(item).unwrap();
+            assert_eq!(parsed.len(), 1);
+
+            let mut other = entity.into_item();
+            other.insert("et".to_string(), AttributeValue::S("other".to_string()));
+            assert!(TestProjections::try_from_item_all(other)
+                .unwrap()
+                .is_empty());
+        }
+
+        type TestRawItem = RawItem<TestTable>;
+
+        projections! {
+            enum TestCatchAllProjections {
+                TestEntityName,
+                TestRawItem,
+            }
+        }
+
+        #[test]
+        fn raw_items_match_any_entity_type() {
+            let entity = TestEntity {
+                id: "test1".to_string(),
+                name: "Test".to_string(),
+                email: "my_email@not_real.com".to_string(),
+            };
+
+            assert!(TestCatchAllProjections::projection_expression().is_none());
+
+            let item = entity.clone().into_item();
+            let parsed = TestCatchAllProjections::try_from_item(item.clone()).unwrap();
+            assert!(
+                matches!(parsed, Some(TestCatchAllProjections::TestEntityName(p)) if p.name == "Test")
+            );
+
+            let parsed = TestCatchAllProjections::try_from_item_all(item).unwrap();
+            assert_eq!(parsed.len(), 2);
+
+            let mut other = entity.clone().into_item();
+            other.insert("et".to_string(), AttributeValue::S("other".to_string()));
+            let Some(TestCatchAllProjections::TestRawItem(raw)) =
+                TestCatchAllProjections::try_from_item(other).unwrap()
+            else {
+                panic!("expected a raw item");
+            };
+            assert_eq!(raw.entity_type().as_str(), "other");
+            assert_eq!(raw.item()["id"].as_s().unwrap(), "test1");
+            assert!(raw.parse::<TestEntity>().unwrap().is_none());
+
+            let raw = TestRawItem::from_item(entity.clone().into_item()).unwrap();
+            assert_eq!(raw.parse::<TestEntity>().unwrap(), Some(entity));
+        }
+    }
+
+    mod prefix_discriminator {
+        use super::*;
+
+        struct TestTable;
+        impl Table for TestTable {
+            const ENTITY_DISCRIMINATOR: EntityDiscriminator = EntityDiscriminator::Prefix {
+                attribute: "SK",
+                separator: '#',
+            };
+
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi13;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+        struct TestEntity {
+            id: String,
+            name: String,
+        }
+
+        impl EntityDef for TestEntity {
+            const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("NAME");
+            const PROJECTED_ATTRIBUTES: &'static [&'static str] = &["id", "name"];
+        }
+
+        impl Entity for TestEntity {
+            type KeyInput<'a> = (&'a str, &'a str);
+            type Table = TestTable;
+            type IndexKeys = keys::Gsi13;
+
+            fn primary_key((id, name): Self::KeyInput<'_>) -> keys::Primary {
+                keys::Primary {
+                    hash: format!("PK#{id}"),
+                    range: format!("NAME#{name}"),
+                }
+            }
+
+            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+                keys::FullKey {
+                    primary: Self::primary_key((&self.id, &self.name)),
+                    indexes: keys::Gsi13 {
+                        hash: format!("GSI13#{}", self.id),
+                        range: format!("GSI13#NAME#{}", self.name),
+                    },
+                }
+            }
+        }
+
+        #[test]
+        fn test_entity_serializes_without_entity_type_attribute() {
+            let entity = TestEntity {
+                id: "test1".to_string(),
+                name: "Test".to_string(),
+            };
+
+            let item = entity.into_item();
+            assert_eq!(item.len(), 6);
+            assert!(!item.contains_key(TestTable::ENTITY_TYPE_ATTRIBUTE));
+            assert_eq!(item["SK"].as_s().unwrap(), "NAME#Test");
+        }
+
+        #[test]
+        fn test_entity_type_is_read_from_prefix() {
+            let entity = TestEntity {
+                id: "test1".to_string(),
+                name: "Test".to_string(),
+            };
+
+            let item = entity.clone().into_item();
+            let parsed = <TestEntity as ProjectionSet>::try_from_item(item).unwrap();
+
+            assert_eq!(parsed, Some(entity));
+        }
+
+        #[test]
+        fn test_projection_includes_discriminator_attribute() {
+            let projection = <TestEntity as ProjectionSet>::projection_expression().unwrap();
+
+            let projects_sk = projection.expression.split(',').any(|attr| attr == "SK")
+                || projection.names.iter().any(|(_, name)| *name == "SK");
+            assert!(projects_sk);
+        }
+    }
+
+    mod create_table {
+        use aws_sdk_dynamodb::types::{BillingMode, StreamViewType, TableClass};
+
+        use super::*;
+
+        struct TestTable;
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = (keys::Gsi1, keys::Gsi2);
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        struct StreamingTable;
+        impl Table for StreamingTable {
+            const TTL_ATTRIBUTE: Option<&'static str> = Some("expires_at");
+            const STREAM_VIEW: Option<StreamViewType> = Some(StreamViewType::NewImage);
+
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        #[test]
+        fn default_tables_are_pay_per_request() {
+            let table = mock::MockTable::<TestTable>::new("test");
+            let builder = table.create_table();
+            let input = builder.as_input();
+
+            assert_eq!(input.get_billing_mode(), &Some(BillingMode::PayPerRequest));
+            assert!(input.get_provisioned_throughput().is_none());
+            assert!(input.get_stream_specification().is_none());
+            assert!(input
+                .get_global_secondary_indexes()
+                .iter()
+                .flatten()
+                .all(|gsi| gsi.provisioned_throughput().is_none()));
+        }
+
+        #[test]
+        fn provisioned_tables_configure_each_index() {
+            let table = mock::MockTable::<TestTable>::new("test");
+            let options = CreateTableOptions::new()
+                .provisioned(10, 5)
+                .index_provisioned("GSI2", 2, 1)
+                .stream(StreamViewType::NewAndOldImages)
+                .table_class(TableClass::StandardInfrequentAccess);
+            let builder = table.create_table_with(&options);
+            let input = builder.as_input();
+
+            assert_eq!(input.get_billing_mode(), &Some(BillingMode::Provisioned));
+            let throughput = input.get_provisioned_throughput().as_ref().unwrap();
+            assert_eq!(
+                (
+                    throughput.read_capacity_units,
+                    throughput.write_capacity_units
+                ),
+                (10, 5)
+            );
+
+            let gsis = input.get_global_secondary_indexes().as_ref().unwrap();
+            let capacity = |name: &str| {
+                let gsi = gsis.iter().find(|gsi| gsi.index_name == name).unwrap();
+                let throughput = gsi.provisioned_throughput().unwrap();
+                (
+                    throughput.read_capacity_units,
+                    throughput.write_capacity_units,
+                )
+            };
+            assert_eq!(capacity("GSI1"), (10, 5));
+            assert_eq!(capacity("GSI2"), (2, 1));
+
+            let stream = input.get_stream_specification().as_ref().unwrap();
+            assert!(stream.stream_enabled);
+            assert_eq!(
+                stream.stream_view_type,
+                Some(StreamViewType::NewAndOldImages)
+            );
+            assert_eq!(
+                input.get_table_class(),
+                &Some(TableClass::StandardInfrequentAccess)
+            );
+        }
+
+        #[test]
+        fn table_stream_and_ttl_settings_are_honored() {
+            let table = mock::MockTable::<StreamingTable>::new("test");
+            let builder = table.create_table();
+            let stream = builder
+                .as_input()
+                .get_stream_specification()
+                .as_ref()
+                .unwrap();
+            assert_eq!(stream.stream_view_type, Some(StreamViewType::NewImage));
+
+            let builder = table
+                .create_table_with(&CreateTableOptions::new().stream(StreamViewType::KeysOnly));
+            let stream = builder
+                .as_input()
+                .get_stream_specification()
+                .as_ref()
+                .unwrap();
+            assert_eq!(stream.stream_view_type, Some(StreamViewType::KeysOnly));
+
+            let ttl = table.update_time_to_live().unwrap();
+            let specification = ttl
+                .as_input()
+                .get_time_to_live_specification()
+                .as_ref()
+                .unwrap();
+            assert!(specification.enabled);
+            assert_eq!(specification.attribute_name, "expires_at");
+
+            let table = mock::MockTable::<TestTable>::new("test");
+            assert!(table.update_time_to_live().is_none());
+        }
+    }
+
+    mod scan_collect {
+        use aws_sdk_dynamodb::{operation::scan::ScanOutput, types::ConsumedCapacity};
+
+        use super::*;
+        use crate::{
+            mock::{ops, MockTable, Operation},
+            model::ScanCollectOptions,
+        };
+
+        struct TestTable;
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        struct AllItems;
+        impl ScanInput for AllItems {
+            type Index = keys::Primary;
+        }
+
+        #[derive(Debug, Default)]
+        struct Ids(Vec<String>);
+        impl Aggregate for Ids {
+            type Projections = RawItem<TestTable>;
+
+            fn merge(&mut self, item: Item) -> Result<(), Error> {
+                self.0.push(item["PK"].as_s().unwrap().clone());
+                Ok(())
+            }
+        }
+
+        fn item(id: usize) -> Item {
+            [
+                ("PK".to_string(), AttributeValue::S(format!("ITEM#{id}"))),
+                ("SK".to_string(), AttributeValue::S(format!("ITEM#{id}"))),
+            ]
+            .into()
+        }
+
+        /// Serves pages of five items, resuming after the last key returned
+        fn page(input: &aws_sdk_dynamodb::operation::scan::ScanInput) -> ScanOutput {
+            let start = input.exclusive_start_key.as_ref().map_or(0, |key| {
+                key["PK"].as_s().unwrap()["ITEM#".len()..]
+                    .parse::<usize>()
+                    .unwrap()
+                    + 1
+            });
+            let end = input
+                .limit
+                .map_or(5, |limit| (start + limit as usize).min(5));
+            let items: Vec<_> = (start..end).map(item).collect();
+
+            ScanOutput::builder()
+                .set_last_evaluated_key((end < 5).then(|| item(end - 1)))
+                .count(items.len() as i32)
+                .scanned_count(items.len() as i32)
+                .set_items(Some(items))
+                .consumed_capacity(ConsumedCapacity::builder().capacity_units(1.0).build())
+                .build()
+        }
+
+        fn runtime() -> tokio::runtime::Runtime {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+        }
+
+        #[test]
+        fn item_limits_stop_the_scan_with_a_resumable_cursor() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::Scan>(|e| e.responding(|input| Ok(page(input))));
+
+            let options = ScanCollectOptions::new().page_size(2).item_limit(3);
+            let collected = runtime()
+                .block_on(AllItems.scan_collect::<Ids, _>(&table, options))
+                .unwrap();
+            assert_eq!(collected.aggregate.0, ["ITEM#0", "ITEM#1", "ITEM#2"]);
+            assert_eq!(collected.count, 3);
+            assert_eq!(collected.consumed_read_capacity, 2.0);
+
+            let limits: Vec<_> = table
+                .inputs::<ops::Scan>()
+                .iter()
+                .map(|input| input.limit)
+                .collect();
+            assert_eq!(limits, [Some(2), Some(1)]);
+
+            let cursor = collected.cursor.unwrap();
+            assert_eq!(cursor.total_segments(), 1);
+
+            let options = ScanCollectOptions::new().resume_from(cursor);
+            let collected = runtime()
+                .block_on(AllItems.scan_collect::<Ids, _>(&table, options))
+                .unwrap();
+            assert_eq!(collected.aggregate.0, ["ITEM#3", "ITEM#4"]);
+            assert!(collected.cursor.is_none());
+            assert_eq!(table.calls(Operation::Scan), 3);
+        }
+
+        #[test]
+        fn read_capacity_limits_apply_across_segments() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::Scan>(|e| e.responding(|input| Ok(page(input))));
+
+            let options = ScanCollectOptions::new()
+                .segments(2)
+                .page_size(1)
+                .read_capacity_limit(3.0);
+            let collected = runtime()
+                .block_on(AllItems.scan_collect::<Ids, _>(&table, options))
+                .unwrap();
+
+            let inputs = table.inputs::<ops::Scan>();
+            assert!(inputs.len() >= 3);
+            assert!(inputs.iter().all(|input| input.total_segments == Some(2)));
+            assert!(inputs.iter().any(|input| input.segment == Some(0)));
+            assert_eq!(collected.count, inputs.len() as u64);
+
+            let cursor = collected.cursor.unwrap();
+            assert_eq!(cursor.pending_segments().count(), 2);
+        }
+    }
+
+    mod hydrate {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        use aws_sdk_dynamodb::{
+            operation::{
+                batch_get_item::{BatchGetItemInput, BatchGetItemOutput},
+                query::{QueryInput, QueryOutput},
+            },
+            types::KeysAndAttributes,
+        };
+
+        use super::*;
+        use crate::{
+            mock::{ops, MockTable, Operation},
+            model::Query,
+        };
+
+        struct TestTable;
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        #[derive(Debug, Default)]
+        struct Ids(Vec<String>);
+        impl Aggregate for Ids {
+            type Projections = RawItem<TestTable>;
+
+            fn merge(&mut self, item: Item) -> Result<(), Error> {
+                self.0.push(item["SK"].as_s().unwrap().clone());
+                Ok(())
+            }
+        }
+
+        fn item(id: usize) -> Item {
+            [
+                ("PK".to_string(), AttributeValue::S("LIST".to_string())),
+                ("SK".to_string(), AttributeValue::S(format!("ITEM#{id}"))),
+            ]
+            .into()
+        }
+
+        /// Serves pages of up to two of five items, resuming after the last
+        /// key returned
+        fn page(input: &QueryInput) -> QueryOutput {
+            let start = input.exclusive_start_key.as_ref().map_or(0, |key| {
+                key["SK"].as_s().unwrap()["ITEM#".len()..]
+                    .parse::<usize>()
+                    .unwrap()
+                    + 1
+            });
+            let size = input.limit.map_or(2, |limit| (limit as usize).min(2));
+            let end = (start + size).min(5);
+            let items: Vec<_> = (start..end).map(item).collect();
+
+            QueryOutput::builder()
+                .set_last_evaluated_key((end < 5).then(|| item(end - 1)))
+                .count(items.len() as i32)
+                .scanned_count(items.len() as i32)
+                .set_items(Some(items))
+                .build()
+        }
+
+        fn query() -> Query<keys::Primary> {
+            Query::new(expr::KeyCondition::in_partition("LIST"))
+        }
+
+        fn runtime() -> tokio::runtime::Runtime {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+        }
+
+        #[test]
+        fn every_page_is_merged_in_order() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::Query>(|e| e.responding(|input| Ok(page(input))));
+
+            let mut pages = Vec::new();
+            let ids: Ids = runtime()
+                .block_on(
+                    query().hydrate_with_progress(&table, |progress, ids: &Ids| {
+                        pages.push((progress.pages, ids.0.len(), progress.has_more));
+                    }),
+                )
+                .unwrap();
+
+            assert_eq!(ids.0, ["ITEM#0", "ITEM#1", "ITEM#2", "ITEM#3", "ITEM#4"]);
+            assert_eq!(pages, [(1, 2, true), (2, 4, true), (3, 5, false)]);
+
+            let start_keys: Vec<_> = table
+                .inputs::<ops::Query>()
+                .iter()
+                .map(|input| {
+                    input
+                        .exclusive_start_key
+                        .as_ref()
+                        .map(|key| key["SK"].as_s().unwrap().clone())
+                })
+                .collect();
+            assert_eq!(
+                start_keys,
+                [None, Some("ITEM#1".to_string()), Some("ITEM#3".to_string())]
+            );
+        }
+
+        #[test]
+        fn limits_apply_across_pages() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::Query>(|e| e.responding(|input| Ok(page(input))));
+
+            let ids: Ids = runtime()
+                .block_on(query().limit(3).hydrate(&table))
+                .unwrap();
+
+            assert_eq!(ids.0, ["ITEM#0", "ITEM#1", "ITEM#2"]);
+            assert_eq!(table.calls(Operation::Query), 2);
+            let limits: Vec<_> = table
+                .inputs::<ops::Query>()
+                .iter()
+                .map(|input| input.limit)
+                .collect();
+            assert_eq!(limits, [Some(3), Some(1)]);
+        }
+
+        fn table() -> MockTable<TestTable> {
+            let start = time::OffsetDateTime::from_unix_timestamp(1_701_950_400).unwrap();
+            MockTable::<TestTable>::new("test").with_clock(clock::FixedClock::new(start))
+        }
+
+        fn requested_keys(input: &BatchGetItemInput) -> Vec<Item> {
+            input.request_items.as_ref().unwrap()["test"].keys.clone()
+        }
+
+        #[test]
+        fn hydrated_keys_follow_query_order_and_unprocessed_keys_are_retried() {
+            let table = table();
+            table.expect::<ops::Query>(|e| e.responding(|input| Ok(page(input))));
+
+            let first = AtomicBool::new(true);
+            table.expect::<ops::BatchGetItem>(|e| {
+                e.responding(move |input| {
+                    let mut keys = requested_keys(input);
+                    let unprocessed = if first.swap(false, Ordering::SeqCst) {
+                        vec![keys.remove(0)]
+                    } else {
+                        Vec::new()
+                    };
+                    keys.reverse();
+
+                    Ok(BatchGetItemOutput::builder()
+                        .responses("test", keys)
+                        .set_unprocessed_keys((!unprocessed.is_empty()).then(|| {
+                            [(
+                                "test".to_string(),
+                                KeysAndAttributes::builder()
+                                    .set_keys(Some(unprocessed))
+                                    .build()
+                                    .unwrap(),
+                            )]
+                            .into()
+                        }))
+                        .build())
+                })
+            });
+
+            let before = table.clock().now();
+            let ids: Ids = runtime()
+                .block_on(query().hydrate_from_keys(&table))
+                .unwrap();
+
+            assert_eq!(ids.0, ["ITEM#0", "ITEM#1", "ITEM#2", "ITEM#3", "ITEM#4"]);
+            assert!(table.clock().now() > before);
+
+            let requests: Vec<_> = table
+                .inputs::<ops::BatchGetItem>()
+                .iter()
+                .map(requested_keys)
+                .collect();
+            assert_eq!(
+                requests,
+                [
+                    vec![item(0), item(1)],
+                    vec![item(0)],
+                    vec![item(2), item(3)],
+                    vec![item(4)],
+                ]
+            );
+        }
+
+        #[test]
+        fn keys_left_unprocessed_on_every_attempt_are_throttled() {
+            let table = table();
+            table.expect::<ops::Query>(|e| e.responding(|input| Ok(page(input))));
+            table.expect::<ops::BatchGetItem>(|e| {
+                e.responding(|input| {
+                    Ok(BatchGetItemOutput::builder()
+                        .set_unprocessed_keys(Some(input.request_items.clone().unwrap()))
+                        .build())
+                })
+            });
+
+            let error = runtime()
+                .block_on(query().hydrate_from_keys::<_, Ids>(&table))
+                .unwrap_err();
+
+            assert_eq!(error.kind(), ErrorKind::Throttled);
+            assert_eq!(error.context().unwrap().operation(), "BatchGetItem");
+            assert_eq!(table.calls(Operation::BatchGetItem), 8);
+            assert_eq!(table.calls(Operation::Query), 1);
+        }
+    }
+
+    mod read_only {
+        use super::*;
+        use crate::mock::{ops, MockTable, Operation};
+
+        struct TestTable;
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        impl WritableTable for TestTable {}
+
+        fn assert_writable<T: WritableTable>() {}
+
+        #[test]
+        fn reads_pass_through_read_only_handles() {
+            assert_writable::<MockTable<TestTable>>();
+
+            let table = ReadOnly::new(MockTable::<TestTable>::new("test"));
+            table
+                .inner()
+                .expect::<ops::GetItem>(|e| e.returning_item(None));
+
+            let key = keys::Primary {
+                hash: "PK".to_string(),
+                range: "SK".to_string(),
+            };
+            let output = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(Get::new(key.into_key()).execute(&table))
+                .unwrap();
+
+            assert!(output.item.is_none());
+            assert_eq!(table.table_name(), "test");
+            assert_eq!(table.inner().calls(Operation::GetItem), 1);
+        }
+    }
+
+    mod transient {
+        use super::*;
+
+        struct TestTable;
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+        struct TestEntity {
+            id: String,
+            #[serde(default)]
+            hits: u32,
+        }
+
+        impl EntityDef for TestEntity {
+            const ENTITY_TYPE: &'static EntityTypeNameRef =
+                EntityTypeNameRef::from_static("test_ent");
+            const TRANSIENT_ATTRIBUTES: &'static [&'static str] = &["hits"];
+        }
+
+        impl Entity for TestEntity {
+            type KeyInput<'a> = &'a str;
+            type Table = TestTable;
+            type IndexKeys = ();
+
+            fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+                keys::Primary {
+                    hash: format!("PK#{id}"),
+                    range: "TEST".to_string(),
+                }
+            }
+
+            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+                keys::FullKey {
+                    primary: Self::primary_key(&self.id),
+                    indexes: (),
+                }
+            }
+        }
+
+        #[test]
+        fn transient_attributes_are_not_persisted() {
+            let entity = TestEntity {
+                id: "test1".to_string(),
+                hits: 3,
+            };
+
+            let item = entity.into_item();
+            assert!(!item.contains_key("hits"));
+            assert_eq!(item["id"].as_s().unwrap(), "test1");
+
+            let entity = TestEntity::from_item(item).unwrap();
+            assert_eq!(entity.hits, 0);
+        }
+    }
+
+    mod conflict_retry {
+        use aws_sdk_dynamodb::{
+            config::http::HttpResponse,
+            error::{ErrorMetadata, SdkError},
+            operation::transact_write_items::TransactWriteItemsError,
+            types::{error::TransactionCanceledException, CancellationReason},
+        };
+        use aws_smithy_types::body::SdkBody;
+
+        use super::*;
+        use crate::mock::{ops, MockTable, Operation};
+
+        struct TestTable;
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        impl WritableTable for TestTable {}
+
+        fn conflict() -> SdkError<TransactWriteItemsError> {
+            let error = TransactionCanceledException::builder()
+                .message("transaction cancelled")
+                .cancellation_reasons(
+                    CancellationReason::builder()
+                        .code("TransactionConflict")
+                        .build(),
+                )
+                .meta(
+                    ErrorMetadata::builder()
+                        .code("TransactionCanceledException")
+                        .build(),
+                )
+                .build();
+            SdkError::service_error(
+                TransactWriteItemsError::TransactionCanceledException(error),
+                HttpResponse::new(400u16.try_into().unwrap(), SdkBody::empty()),
+            )
+        }
+
+        fn transaction() -> TransactWrite {
+            let key = keys::Primary {
+                hash: "PK".to_string(),
+                range: "SK".to_string(),
+            };
+            TransactWrite::new()
+                .client_request_token("token")
+                .operation(Delete::new(key.into_key()))
+        }
+
+        fn runtime() -> tokio::runtime::Runtime {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()
+                .unwrap()
+        }
+
+        #[test]
+        fn conflicts_are_retried_with_the_same_token() {
+            let start = time::OffsetDateTime::from_unix_timestamp(1_701_950_400).unwrap();
+            let table =
+                MockTable::<TestTable>::new("test").with_clock(clock::FixedClock::new(start));
+            table
+                .expect::<ops::TransactWriteItems>(|e| e.times(2).responding(|_| Err(conflict())))
+                .expect::<ops::TransactWriteItems>(|e| e.times(1));
+
+            runtime()
+                .block_on(transaction().retry_conflicts(3).execute(&table))
+                .unwrap();
+
+            let inputs = table.inputs::<ops::TransactWriteItems>();
+            assert_eq!(inputs.len(), 3);
+            assert!(inputs
+                .iter()
+                .all(|input| input.client_request_token.as_deref() == Some("token")));
+            table.verify();
+
+            // Backoffs of 50-100 ms and 100-200 ms, waited on the table's clock
+            let waited = table.clock().now() - start;
+            assert!(waited >= time::Duration::milliseconds(150), "{waited}");
+            assert!(waited <= time::Duration::milliseconds(300), "{waited}");
+        }
+
+        #[test]
+        fn operations_can_be_collected_into_a_transaction() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::TransactWriteItems>(|e| e.times(1));
+
+            let delete = |hash: &str| {
+                let key = keys::Primary {
+                    hash: hash.to_string(),
+                    range: "SK".to_string(),
+                };
+                Delete::new(key.into_key())
+            };
+
+            let transaction: TransactWrite = ["A", "B"].into_iter().map(delete).collect();
+            runtime()
+                .block_on(transaction.operations([delete("C")]).execute(&table))
+                .unwrap();
+
+            let inputs = table.inputs::<ops::TransactWriteItems>();
+            assert_eq!(inputs[0].transact_items.as_ref().unwrap().len(), 3);
+            table.verify();
+        }
+
+        #[test]
+        fn conflicts_are_returned_without_retries() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::TransactWriteItems>(|e| e.responding(|_| Err(conflict())));
+
+            let error = runtime()
+                .block_on(transaction().execute(&table))
+                .unwrap_err();
+
+            assert!(crate::error::is_transaction_conflict(&error));
+            assert_eq!(table.calls(Operation::TransactWriteItems), 1);
+        }
+
+        #[test]
+        fn empty_requests_are_rejected_before_sending() {
+            let table = MockTable::<TestTable>::new("test");
+            let rt = runtime();
+
+            let error = Error::from(
+                rt.block_on(TransactWrite::new().execute(&table))
+                    .unwrap_err(),
+            );
+            assert!(error.is_empty_transaction());
+            assert_eq!(error.kind(), ErrorKind::Validation);
+
+            let error = Error::from(
+                rt.block_on(model::TransactGet::new().execute(&table))
+                    .unwrap_err(),
+            );
+            assert!(error.is_empty_transaction());
+
+            let error = Error::from(
+                rt.block_on(model::BatchWrite::new().execute(&table))
+                    .unwrap_err(),
+            );
+            assert!(error.is_empty_transaction());
+
+            let error = Error::from(
+                rt.block_on(model::BatchGet::new().execute(&table))
+                    .unwrap_err(),
+            );
+            assert!(error.is_empty_transaction());
+
+            assert_eq!(table.calls(Operation::TransactWriteItems), 0);
+            assert_eq!(table.calls(Operation::TransactGetItems), 0);
+            assert_eq!(table.calls(Operation::BatchWriteItem), 0);
+            assert_eq!(table.calls(Operation::BatchGetItem), 0);
+        }
+    }
+
+    mod exists {
+        use super::*;
+        use crate::mock::{ops, MockTable};
+
+        struct TestTable;
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+        struct TestEntity {
+            id: String,
+        }
+
+        impl EntityDef for TestEntity {
+            const ENTITY_TYPE: &'static EntityTypeNameRef =
+                EntityTypeNameRef::from_static("test_ent");
+        }
+
+        impl Entity for TestEntity {
+            type KeyInput<'a> = &'a str;
+            type Table = TestTable;
+            type IndexKeys = ();
+
+            fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+                keys::Primary {
+                    hash: format!("PK#{id}"),
+                    range: "TEST".to_string(),
+                }
+            }
+
+            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+                keys::FullKey {
+                    primary: Self::primary_key(&self.id),
+                    indexes: (),
+                }
+            }
+        }
+
+        fn runtime() -> tokio::runtime::Runtime {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+        }
+
+        #[test]
+        fn entity_exists_reads_only_the_partition_key() {
+            let table = MockTable::<TestTable>::new("test");
+            table
+                .expect::<ops::GetItem>(|e| {
+                    e.times(1).returning_item(Some(
+                        TestEntity {
+                            id: "test1".to_string(),
+                        }
+                        .into_item(),
+                    ))
+                })
+                .expect::<ops::GetItem>(|e| e.times(1).returning_item(None));
+
+            let runtime = runtime();
+            assert!(runtime
+                .block_on(TestEntity::exists("test1").execute(&table))
+                .unwrap());
+            assert!(!runtime
+                .block_on(TestEntity::exists("test2").execute(&table))
+                .unwrap());
+
+            let inputs = table.inputs::<ops::GetItem>();
+            assert_eq!(inputs[0].projection_expression.as_deref(), Some("#prj_key"));
+            assert_eq!(
+                inputs[0].expression_attribute_names.as_ref().unwrap()["#prj_key"],
+                "PK"
+            );
+            table.verify();
+        }
+
+        #[test]
+        fn query_exists_counts_without_reading_items() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::Query>(|e| e.times(1).returning_items(Vec::new()));
+
+            let query = Query::new(expr::KeyCondition::<keys::Primary>::in_partition(
+                "PK#test1",
+            ));
+            assert!(!runtime().block_on(query.exists(&table)).unwrap());
+
+            let inputs = table.inputs::<ops::Query>();
+            assert_eq!(
+                inputs[0].select,
+                Some(aws_sdk_dynamodb::types::Select::Count)
+            );
+            assert_eq!(inputs[0].limit, Some(1));
+            assert!(inputs[0].projection_expression.is_none());
+            table.verify();
+        }
+    }
+
+    mod execute_any {
+        use super::*;
+        use crate::mock::{ops, MockTable};
+
+        struct TestTable;
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct User {
+            id: String,
+            name: String,
+        }
+
+        impl EntityDef for User {
+            const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("user");
+            const PROJECTED_ATTRIBUTES: &'static [&'static str] = &["id", "name"];
+        }
+
+        impl Entity for User {
+            type KeyInput<'a> = &'a str;
+            type Table = TestTable;
+            type IndexKeys = ();
+
+            fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+                keys::Primary {
+                    hash: format!("ACCOUNT#{id}"),
+                    range: "PROFILE".to_string(),
+                }
+            }
+
+            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+                Self::primary_key(&self.id).into()
+            }
+        }
+
+        #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Team {
+            id: String,
+            members: u32,
+        }
+
+        impl EntityDef for Team {
+            const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("team");
+            const PROJECTED_ATTRIBUTES: &'static [&'static str] = &["id", "members"];
+        }
+
+        impl Entity for Team {
+            type KeyInput<'a> = &'a str;
+            type Table = TestTable;
+            type IndexKeys = ();
+
+            fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+                keys::Primary {
+                    hash: format!("ACCOUNT#{id}"),
+                    range: "PROFILE".to_string(),
+                }
+            }
+
+            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+                Self::primary_key(&self.id).into()
+            }
+        }
+
+        projections! {
+            #[derive(Debug, PartialEq)]
+            enum Account {
+                User,
+                Team,
+            }
+        }
+
+        fn user() -> User {
+            User {
+                id: "1".to_string(),
+                name: "Ada".to_string(),
+            }
+        }
+
+        fn runtime() -> tokio::runtime::Runtime {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+        }
+
+        #[test]
+        fn items_of_a_matching_entity_type_are_parsed() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::GetItem>(|e| e.times(2).returning_item(Some(user().into_item())));
+
+            let runtime = runtime();
+            let account = runtime
+                .block_on(
+                    Get::new(User::primary_key("1").into_key()).execute_any::<Account, _>(&table),
+                )
+                .unwrap();
+            assert_eq!(account, Some(Account::User(user())));
+
+            let parsed = runtime
+                .block_on(
+                    Get::new(User::primary_key("1").into_key()).execute_any::<User, _>(&table),
+                )
+                .unwrap();
+            assert_eq!(parsed, Some(user()));
+
+            let input = &table.inputs::<ops::GetItem>()[0];
+            assert!(input.projection_expression.is_some());
+            table.verify();
+        }
+
+        #[test]
+        fn items_of_another_entity_type_are_skipped() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::GetItem>(|e| e.times(1).returning_item(Some(user().into_item())));
+
+            let parsed = runtime()
+                .block_on(
+                    Get::new(Team::primary_key("1").into_key()).execute_any::<Team, _>(&table),
+                )
+                .unwrap();
+            assert_eq!(parsed, None);
+            table.verify();
+        }
+    }
+
+    mod create_or_get {
+        use aws_sdk_dynamodb::{
+            config::http::HttpResponse, error::SdkError, operation::put_item::PutItemError,
+            types::error::ConditionalCheckFailedException,
+        };
+        use aws_smithy_types::body::SdkBody;
+
+        use super::*;
+        use crate::{
+            mock::{ops, MockTable},
+            model::Created,
+        };
+
+        struct TestTable;
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        impl WritableTable for TestTable {}
+
+        #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct TestEntity {
+            id: String,
+            name: String,
+        }
+
+        impl EntityDef for TestEntity {
+            const ENTITY_TYPE: &'static EntityTypeNameRef =
+                EntityTypeNameRef::from_static("test_ent");
+        }
+
+        impl Entity for TestEntity {
+            type KeyInput<'a> = &'a str;
+            type Table = TestTable;
+            type IndexKeys = ();
+
+            fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+                keys::Primary {
+                    hash: format!("PK#{id}"),
+                    range: "TEST".to_string(),
+                }
+            }
+
+            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+                keys::FullKey {
+                    primary: Self::primary_key(&self.id),
+                    indexes: (),
+                }
+            }
+        }
+
+        fn entity(name: &str) -> TestEntity {
+            TestEntity {
+                id: "1".to_string(),
+                name: name.to_string(),
+            }
+        }
+
+        fn already_exists(item: Item) -> SdkError<PutItemError> {
+            let error = ConditionalCheckFailedException::builder()
+                .message("the conditional request failed")
+                .set_item(Some(item))
+                .build();
+            SdkError::service_error(
+                PutItemError::ConditionalCheckFailedException(error),
+                HttpResponse::new(400u16.try_into().unwrap(), SdkBody::empty()),
+            )
+        }
+
+        fn runtime() -> tokio::runtime::Runtime {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+        }
+
+        #[test]
+        fn new_entities_are_created() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::PutItem>(|e| e.times(1));
+
+            let created = runtime()
+                .block_on(entity("new").create_or_get().execute(&table))
+                .unwrap();
+            assert_eq!(created, Created::New(entity("new")));
+
+            let input = &table.inputs::<ops::PutItem>()[0];
+            assert_eq!(
+                input.condition_expression.as_deref(),
+                Some("attribute_not_exists(#cnd_PK)")
+            );
+            assert_eq!(
+                input.return_values_on_condition_check_failure,
+                Some(aws_sdk_dynamodb::types::ReturnValuesOnConditionCheckFailure::AllOld)
+            );
+            table.verify();
+        }
+
+        #[test]
+        fn existing_entities_are_returned() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::PutItem>(|e| {
+                e.times(1)
+                    .responding(|_| Err(already_exists(entity("old").into_item())))
+            });
+
+            let created = runtime()
+                .block_on(entity("new").create_or_get().execute(&table))
+                .unwrap();
+            assert_eq!(created, Created::Existing(entity("old")));
+            table.verify();
+        }
+
+        #[test]
+        fn existing_items_of_another_entity_type_are_rejected() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::PutItem>(|e| {
+                e.times(1).responding(|_| {
+                    let mut item = entity("old").into_item();
+                    item.insert(
+                        TestTable::ENTITY_TYPE_ATTRIBUTE.to_string(),
+                        AttributeValue::S("other_ent".to_string()),
+                    );
+                    Err(already_exists(item))
+                })
+            });
+
+            let error = runtime()
+                .block_on(entity("new").create_or_get().execute(&table))
+                .unwrap_err();
+            assert_eq!(error.context().unwrap().operation(), "PutItem");
+            let context = std::error::Error::source(&error).unwrap();
+            assert_eq!(
+                context.source().unwrap().to_string(),
+                "expected an item of entity type `test_ent`, found `other_ent`"
+            );
+            table.verify();
+        }
+    }
+
+    mod read_modify_write {
+        use aws_sdk_dynamodb::{
+            config::http::HttpResponse, error::SdkError, operation::update_item::UpdateItemError,
+            types::error::ConditionalCheckFailedException,
+        };
+        use aws_smithy_types::body::SdkBody;
+
+        use super::*;
+        use crate::{
+            clock::FixedClock,
+            mock::{ops, MockTable, Operation},
+        };
+
+        struct TestTable;
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        impl WritableTable for TestTable {}
+
+        #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Counter {
+            name: String,
+            count: u32,
+        }
+
+        impl EntityDef for Counter {
+            const ENTITY_TYPE: &'static EntityTypeNameRef =
+                EntityTypeNameRef::from_static("counter");
+        }
+
+        impl Entity for Counter {
+            type KeyInput<'a> = &'a str;
+            type Table = TestTable;
+            type IndexKeys = ();
+
+            fn primary_key(name: Self::KeyInput<'_>) -> keys::Primary {
+                keys::Primary {
+                    hash: format!("COUNTER#{name}"),
+                    range: "COUNTER".to_string(),
+                }
+            }
+
+            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+                Self::primary_key(&self.name).into()
+            }
+        }
+
+        fn counter(count: u32) -> Item {
+            let mut item = Counter {
+                name: "hits".to_string(),
+                count,
+            }
+            .into_item();
+            // An attribute written by another application, unknown to the entity
+            item.insert("audit".to_string(), AttributeValue::S("kept".to_string()));
+            item
+        }
+
+        fn conflict() -> SdkError<UpdateItemError> {
+            let error = ConditionalCheckFailedException::builder()
+                .message("the conditional request failed")
+                .build();
+            SdkError::service_error(
+                UpdateItemError::ConditionalCheckFailedException(error),
+                HttpResponse::new(400u16.try_into().unwrap(), SdkBody::empty()),
+            )
+        }
+
+        fn table() -> MockTable<TestTable> {
+            let start = time::OffsetDateTime::from_unix_timestamp(1_701_950_400).unwrap();
+            MockTable::<TestTable>::new("test").with_clock(FixedClock::new(start))
+        }
+
+        fn increment(counter: Counter) -> Counter {
+            Counter {
+                count: counter.count + 1,
+                ..counter
+            }
+        }
+
+        fn runtime() -> tokio::runtime::Runtime {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+        }
+
+        #[test]
+        fn only_changed_attributes_are_written() {
+            let table = table();
+            table
+                .expect::<ops::GetItem>(|e| e.times(1).returning_item(Some(counter(1))))
+                .expect::<ops::UpdateItem>(|e| e.times(1));
+
+            let updated = runtime()
+                .block_on(Counter::read_modify_write("hits", increment).execute(&table))
+                .unwrap();
+            assert_eq!(updated.unwrap().count, 2);
+
+            let input = &table.inputs::<ops::UpdateItem>()[0];
+            let names = input.expression_attribute_names.as_ref().unwrap();
+            let mut updated: Vec<_> = names
+                .iter()
+                .filter(|(name, _)| name.starts_with("#upd_"))
+                .map(|(_, attr)| attr.as_str())
+                .collect();
+            updated.sort_unstable();
+            assert_eq!(updated, ["count"]);
+            assert!(input
+                .condition_expression
+                .as_deref()
+                .unwrap()
+                .starts_with("attribute_exists(#cnd_PK)"));
+            table.verify();
+        }
+
+        #[test]
+        fn conflicts_are_retried_from_the_read() {
+            let table = table();
+            table
+                .expect::<ops::GetItem>(|e| e.times(1).returning_item(Some(counter(1))))
+                .expect::<ops::GetItem>(|e| e.times(1).returning_item(Some(counter(5))))
+                .expect::<ops::UpdateItem>(|e| e.times(1).responding(|_| Err(conflict())))
+                .expect::<ops::UpdateItem>(|e| e.times(1));
+
+            let before = table.clock().now();
+            let updated = runtime()
+                .block_on(Counter::read_modify_write("hits", increment).execute(&table))
+                .unwrap();
+            assert_eq!(updated.unwrap().count, 6);
+            assert!(table.clock().now() > before);
+
+            let inputs = table.inputs::<ops::UpdateItem>();
+            let written: Vec<_> = inputs
+                .iter()
+                .map(|input| {
+                    input
+                        .expression_attribute_values
+                        .as_ref()
+                        .unwrap()
+                        .iter()
+                        .filter(|(name, _)| name.starts_with(":upd_"))
+                        .map(|(_, value)| value.clone())
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+            assert_eq!(
+                written,
+                [
+                    vec![AttributeValue::N("2".into())],
+                    vec![AttributeValue::N("6".into())]
+                ]
+            );
+            table.verify();
+        }
+
+        #[test]
+        fn conflicts_fail_once_attempts_are_exhausted() {
+            let table = table();
+            table
+                .expect::<ops::GetItem>(|e| e.times(3).returning_item(Some(counter(1))))
+                .expect::<ops::UpdateItem>(|e| e.times(3).responding(|_| Err(conflict())));
+
+            let error = runtime()
+                .block_on(
+                    Counter::read_modify_write("hits", increment)
+                        .max_attempts(3)
+                        .execute(&table),
+                )
+                .unwrap_err();
+            assert!(error.is_conditional_check_failed_exception());
+            assert_eq!(error.context().unwrap().operation(), "UpdateItem");
+            assert_eq!(table.calls(Operation::GetItem), 3);
+            assert_eq!(table.calls(Operation::UpdateItem), 3);
+            table.verify();
+        }
+    }
+
+    mod derived_writes {
+        use super::*;
+        use crate::mock::{ops, MockTable, Operation};
+
+        struct TestTable;
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        impl WritableTable for TestTable {}
+
+        #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+        struct Brand {
+            name: String,
+            slug: String,
+        }
+
+        impl EntityDef for Brand {
+            const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("brand");
+            const UNIQUE_ATTRIBUTES: &'static [&'static str] = &["slug"];
+        }
+
+        impl Entity for Brand {
+            type KeyInput<'a> = &'a str;
+            type Table = TestTable;
+            type IndexKeys = ();
+
+            fn primary_key(name: Self::KeyInput<'_>) -> keys::Primary {
+                keys::Primary {
+                    hash: format!("BRAND#{name}"),
+                    range: format!("BRAND#{name}"),
+                }
+            }
+
+            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+                Self::primary_key(&self.name).into()
+            }
+
+            fn derive_writes(&self) -> Vec<model::TransactWriteItem> {
+                let key = keys::Primary {
+                    hash: "BRANDS".to_string(),
+                    range: "BRANDS".to_string(),
+                };
+                let expression = expr::Update::new("ADD #brands :brands")
+                    .name("#brands", "brands")
+                    .value(
+                        ":brands",
+                        serde_dynamo::string_set::StringSet(vec![&self.name]),
+                    );
+                vec![Update::new(key.into_key()).expression(expression).into()]
+            }
+        }
+
+        fn brand() -> Brand {
+            Brand {
+                name: "acme".to_string(),
+                slug: "acme-co".to_string(),
+            }
+        }
+
+        fn runtime() -> tokio::runtime::Runtime {
+            tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+        }
+
+        fn written_keys(item: &aws_sdk_dynamodb::types::TransactWriteItem) -> (&str, String) {
+            let (kind, key) = if let Some(put) = &item.put {
+                ("put", &put.item)
+            } else if let Some(update) = &item.update {
+                ("update", &update.key)
+            } else {
+                unreachable!("unexpected operation in transaction")
+            };
+            (kind, key["PK"].as_s().unwrap().clone())
+        }
+
+        #[test]
+        fn puts_and_creates_write_only_the_entity() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::PutItem>(|e| e.times(2));
+
+            let runtime = runtime();
+            runtime.block_on(brand().put().execute(&table)).unwrap();
+            runtime.block_on(brand().create().execute(&table)).unwrap();
+
+            let inputs = table.inputs::<ops::PutItem>();
+            assert!(inputs
+                .iter()
+                .all(|input| input.item.as_ref().unwrap()["PK"].as_s().unwrap() == "BRAND#acme"));
+            assert_eq!(table.calls(Operation::TransactWriteItems), 0);
+            table.verify();
+        }
+
+        #[test]
+        fn put_with_derived_includes_the_derived_writes() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::TransactWriteItems>(|e| e.times(1));
+
+            runtime()
+                .block_on(brand().put_with_derived().execute(&table))
+                .unwrap();
+
+            let input = &table.inputs::<ops::TransactWriteItems>()[0];
+            let items = input.transact_items.as_ref().unwrap();
+            let written: Vec<_> = items.iter().map(written_keys).collect();
+            assert_eq!(
+                written,
+                [
+                    ("put", "BRAND#acme".to_string()),
+                    ("update", "BRANDS".to_string())
+                ]
+            );
+            assert!(items[0]
+                .put
+                .as_ref()
+                .unwrap()
+                .condition_expression
+                .is_none());
+            table.verify();
+        }
+
+        #[test]
+        fn create_with_derived_claims_unique_values() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::TransactWriteItems>(|e| e.times(1));
+
+            runtime()
+                .block_on(brand().create_with_derived().execute(&table))
+                .unwrap();
+
+            let input = &table.inputs::<ops::TransactWriteItems>()[0];
+            let items = input.transact_items.as_ref().unwrap();
+            let written: Vec<_> = items.iter().map(written_keys).collect();
+            assert_eq!(written.len(), 3);
+            assert_eq!(written[0], ("put", "BRAND#acme".to_string()));
+            assert_eq!(written[1], ("update", "BRANDS".to_string()));
+            assert_eq!(written[2].0, "put");
+            assert!(written[2].1.contains("acme-co"));
+
+            let conditions: Vec<_> = [&items[0], &items[2]]
+                .iter()
+                .map(|item| item.put.as_ref().unwrap().condition_expression.clone())
+                .collect();
+            assert!(conditions.iter().all(|condition| condition
+                .as_deref()
+                .unwrap()
+                .starts_with("attribute_not_exists")));
+            table.verify();
+        }
+    }
+
+    mod indexed_query {
+        use super::*;
+        use crate::mock::{ops, MockTable};
+
+        struct TestTable;
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+        struct TestEntity {
+            id: String,
+        }
+
+        impl EntityDef for TestEntity {
+            const ENTITY_TYPE: &'static EntityTypeNameRef =
+                EntityTypeNameRef::from_static("test_ent");
+        }
+
+        impl Entity for TestEntity {
+            type KeyInput<'a> = &'a str;
+            type Table = TestTable;
+            type IndexKeys = ();
+
+            fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+                keys::Primary {
+                    hash: format!("PK#{id}"),
+                    range: "TEST".to_string(),
+                }
+            }
+
+            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+                keys::FullKey {
+                    primary: Self::primary_key(&self.id),
+                    indexes: (),
+                }
+            }
+        }
+
+        struct TestInput {
+            newest_first: bool,
+        }
+
+        impl IndexedQueryInput for TestInput {
+            type Left = keys::Gsi1;
+            type Right = keys::Primary;
+            type Aggregate = Vec<TestEntity>;
+
+            fn key_condition(&self) -> IndexChoice<Self::Left, Self::Right> {
+                IndexChoice::when(
+                    self.newest_first,
+                    || expr::KeyCondition::in_partition("BY_DATE"),
+                    || expr::KeyCondition::in_partition("PK#test1"),
+                )
+            }
+
+            fn scan_index_forward(&self) -> bool {
+                !self.newest_first
+            }
+        }
+
+        #[test]
+        fn input_chooses_the_queried_index() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::Query>(|e| e.times(2).returning_items(Vec::new()));
+
+            let newest = TestInput { newest_first: true }.query();
+            assert_eq!(newest.index_name(), Some("GSI1"));
+            let oldest = TestInput {
+                newest_first: false,
+            }
+            .query();
+            assert_eq!(oldest.index_name(), None);
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
+            let newest: Vec<TestEntity> = runtime.block_on(newest.hydrate(&table)).unwrap();
+            let oldest: Vec<TestEntity> = runtime.block_on(oldest.hydrate(&table)).unwrap();
+            assert!(newest.is_empty() && oldest.is_empty());
+
+            let inputs = table.inputs::<ops::Query>();
+            assert_eq!(inputs[0].index_name.as_deref(), Some("GSI1"));
+            assert_eq!(inputs[0].scan_index_forward, Some(false));
+            assert_eq!(inputs[1].index_name, None);
+            assert_ne!(inputs[1].scan_index_forward, Some(false));
+            table.verify();
+        }
+    }
+
+    mod singleton {
+        use super::*;
+        use crate::mock::{ops, MockTable};
+
+        struct TestTable;
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        #[derive(Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+        struct FrontPage {
+            #[serde(default)]
+            featured: Vec<String>,
+        }
+
+        impl EntityDef for FrontPage {
+            const ENTITY_TYPE: &'static EntityTypeNameRef =
+                EntityTypeNameRef::from_static("front_page");
+        }
+
+        impl SingletonEntity for FrontPage {
+            type Table = TestTable;
+            const KEY: &'static str = "FRONTPAGE";
+        }
+
+        #[test]
+        fn singleton_is_stored_at_constant_key() {
+            let key = FrontPage::primary_key(());
+            assert_eq!(key.hash, "FRONTPAGE");
+            assert_eq!(key.range, "FRONTPAGE");
+
+            let item = FrontPage {
+                featured: vec!["deal1".to_string()],
+            }
+            .into_item();
+            assert_eq!(item["PK"].as_s().unwrap(), "FRONTPAGE");
+            assert_eq!(item["SK"].as_s().unwrap(), "FRONTPAGE");
+        }
+
+        #[test]
+        fn get_or_default_returns_default_when_absent() {
+            let stored = FrontPage {
+                featured: vec!["deal1".to_string()],
+            };
+            let table = MockTable::<TestTable>::new("test");
+            table
+                .expect::<ops::GetItem>(|e| e.times(1).returning_item(None))
+                .expect::<ops::GetItem>(|e| e.times(1).returning_item(Some(stored.into_item())));
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
+            let front_page = runtime.block_on(FrontPage::get_or_default(&table)).unwrap();
+            assert_eq!(front_page, FrontPage::default());
+
+            let front_page = runtime.block_on(FrontPage::get_or_default(&table)).unwrap();
+            assert_eq!(front_page.featured, vec!["deal1".to_string()]);
+
+            let inputs = table.inputs::<ops::GetItem>();
+            assert_eq!(
+                inputs[0].key.as_ref().unwrap()["PK"].as_s().unwrap(),
+                "FRONTPAGE"
+            );
+            table.verify();
+        }
+    }
+
+    mod schema_version {
+        use super::*;
+
+        struct TestTable;
+        impl Table for TestTable {
+            const SCHEMA_VERSION_ATTRIBUTE: Option<&'static str> = Some("v");
+
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+        }
+
+        #[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+        struct TestEntity {
+            id: String,
+            full_name: String,
+            #[serde(default)]
+            nickname: Option<String>,
+        }
+
+        impl EntityDef for TestEntity {
+            const ENTITY_TYPE: &'static EntityTypeNameRef =
+                EntityTypeNameRef::from_static("test_ent");
+            const PROJECTED_ATTRIBUTES: &'static [&'static str] = &["id", "full_name", "nickname"];
+            const SCHEMA_UPGRADES: &'static [fn(Item) -> Item] = &[rename_name, drop_alias];
+        }
+
+        impl Entity for TestEntity {
+            type KeyInput<'a> = &'a str;
+            type Table = TestTable;
+            type IndexKeys = ();
+
+            fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+                keys::Primary {
+                    hash: format!("PK#{id}"),
+                    range: "TEST".to_string(),
+                }
+            }
+
+            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+                keys::FullKey {
+                    primary: Self::primary_key(&self.id),
+                    indexes: (),
+                }
+            }
+        }
+
+        fn rename_name(mut item: Item) -> Item {
+            if let Some(name) = item.remove("name") {
+                item.insert("full_name".to_string(), name);
+            }
+            item
+        }
+
+        fn drop_alias(mut item: Item) -> Item {
+            item.remove("alias");
+            item
+        }
+
+        fn old_item(version: Option<&str>) -> Item {
+            let mut item: Item = [
+                ("PK", AttributeValue::S("PK#test1".to_string())),
+                ("SK", AttributeValue::S("TEST".to_string())),
+                ("entity_type", AttributeValue::S("test_ent".to_string())),
+                ("id", AttributeValue::S("test1".to_string())),
+                ("name", AttributeValue::S("Test Name".to_string())),
+                ("alias", AttributeValue::S("Tester".to_string())),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+            if let Some(version) = version {
+                item.insert("v".to_string(), AttributeValue::N(version.to_string()));
+            }
+            item
+        }
+
+        #[test]
+        fn items_are_stamped_with_current_version() {
+            let item = TestEntity {
+                id: "test1".to_string(),
+                full_name: "Test Name".to_string(),
+                nickname: None,
+            }
+            .into_item();
+
+            assert_eq!(item["v"], AttributeValue::N("2".to_string()));
+        }
+
+        #[test]
+        fn unversioned_items_are_upgraded() {
+            let entity = TestEntity::from_item(old_item(None)).unwrap();
+            assert_eq!(entity.full_name, "Test Name");
+        }
+
+        #[test]
+        fn upgrades_start_from_the_item_version() {
+            let mut item = old_item(Some("1"));
+            item.insert(
+                "full_name".to_string(),
+                AttributeValue::S("Upgraded".to_string()),
+            );
+
+            let entity = TestEntity::from_item(item).unwrap();
+            assert_eq!(entity.full_name, "Upgraded");
+        }
+
+        #[test]
+        fn malformed_versions_are_rejected() {
+            let mut item = old_item(None);
+            item.insert("v".to_string(), AttributeValue::S("two".to_string()));
+
+            assert!(TestEntity::from_item(item).is_err());
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        struct TestEntityName {
+            full_name: String,
+        }
+
+        impl Projection for TestEntityName {
+            type Entity = TestEntity;
+            const PROJECTED_ATTRIBUTES: &'static [&'static str] = &["full_name"];
+        }
+
+        struct ById;
+
+        impl QueryInput for ById {
+            type Index = keys::Primary;
+            type Aggregate = Vec<TestEntityName>;
+
+            fn key_condition(&self) -> expr::KeyCondition<Self::Index> {
+                expr::KeyCondition::in_partition("PK#test1")
+            }
+        }
+
+        #[test]
+        fn old_items_are_read_in_full_through_projections() {
+            let table = crate::mock::MockTable::<TestTable>::new("test");
+            table.expect::<crate::mock::ops::Query>(|e| {
+                e.times(1).returning(
+                    aws_sdk_dynamodb::operation::query::QueryOutput::builder()
+                        .items(old_item(None))
+                        .build(),
+                )
+            });
+
+            let names: Vec<TestEntityName> = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(ById.query().hydrate(&table))
+                .unwrap();
+
+            assert_eq!(names[0].full_name, "Test Name");
+            let input = &table.inputs::<crate::mock::ops::Query>()[0];
+            assert_eq!(input.projection_expression, None);
+            assert!(TestEntityName::projection_expression().is_none());
+            assert!(
+                <TestEntityName as crate::__private::ProjectionSetMember>::PROJECTED_ATTRIBUTES
+                    .is_empty()
+            );
+        }
+
+        #[test]
+        fn projections_include_the_version_attribute() {
+            let projection = crate::__private::generate_projection_expression::<TestTable>(&[
+                <TestEntity as EntityDef>::PROJECTED_ATTRIBUTES,
+            ])
+            .unwrap();
+
+            assert!(projection.expression.split(',').any(|attr| attr == "v"));
+        }
+    }
+
+    mod timeout {
+        use aws_sdk_dynamodb::{
+            config::{BehaviorVersion, Credentials, Region},
+            error::SdkError,
+        };
+        use aws_smithy_runtime::client::http::test_util::NeverClient;
+
+        use super::*;
+
+        struct TestTable {
+            client: aws_sdk_dynamodb::Client,
+        }
+
+        impl TestTable {
+            fn unresponsive() -> Self {
+                let config = aws_sdk_dynamodb::Config::builder()
+                    .behavior_version(BehaviorVersion::latest())
+                    .region(Region::new("us-east-1"))
+                    .credentials_provider(Credentials::new("AKID", "SECRET", None, None, "test"))
+                    .endpoint_url("http://localhost:8000")
+                    .http_client(NeverClient::new())
+                    .build();
+
+                Self {
+                    client: aws_sdk_dynamodb::Client::from_conf(config),
+                }
+            }
+        }
+
+        impl Table for TestTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                &self.client
+            }
+
+            fn table_name(&self) -> &str {
+                "test"
+            }
+        }
+
+        #[test]
+        fn elapsed_timeouts_are_reported_as_timeout_errors() {
+            let table = TestTable::unresponsive();
+            let key: Item = [("PK".to_string(), AttributeValue::S("test".to_string()))].into();
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()
+                .unwrap();
+            let error = runtime
+                .block_on(
+                    model::Get::new(key)
+                        .with_timeout(std::time::Duration::from_millis(10))
+                        .execute(&table),
+                )
+                .unwrap_err();
+
+            assert!(matches!(error, SdkError::TimeoutError(_)));
+            assert_eq!(Error::from(error).kind(), ErrorKind::Timeout);
+        }
+    }
+
+    mod expired_items {
+        use aws_sdk_dynamodb::{
+            operation::{
+                batch_get_item::BatchGetItemOutput, get_item::GetItemOutput, query::QueryOutput,
+                transact_get_items::TransactGetItemsOutput,
+            },
+            types::ItemResponse,
+        };
 
-    mod standard {
         use super::*;
+        use crate::mock::{ops, MockTable};
 
         struct TestTable;
         impl Table for TestTable {
+            const TTL_ATTRIBUTE: Option<&'static str> = Some("ttl");
+            const EXCLUDE_EXPIRED_ITEMS: bool = true;
+
             type PrimaryKey = keys::Primary;
-            type IndexKeys = keys::Gsi13;
+            type IndexKeys = keys::Gsi1;
 
             fn client(&self) -> &aws_sdk_dynamodb::Client {
                 unimplemented!()
@@ -1096,87 +6873,174 @@ mod tests {
             }
         }
 
-        #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
-        struct TestEntity {
-            id: String,
-            name: String,
-            email: String,
-        }
-
-        impl EntityDef for TestEntity {
-            const ENTITY_TYPE: &'static EntityTypeNameRef =
-                EntityTypeNameRef::from_static("test_ent");
-        }
-
-        impl Entity for TestEntity {
-            type KeyInput<'a> = (&'a str, &'a str);
-            type Table = TestTable;
-            type IndexKeys = keys::Gsi13;
-
-            fn primary_key((id, email): Self::KeyInput<'_>) -> keys::Primary {
-                keys::Primary {
-                    hash: format!("PK#{id}"),
-                    range: format!("NAME#{email}"),
-                }
-            }
-
-            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
-                keys::FullKey {
-                    primary: Self::primary_key((&self.id, &self.email)),
-                    indexes: keys::Gsi13 {
-                        hash: format!("GSI13#{}", self.id),
-                        range: format!("GSI13#NAME#{}", self.name),
-                    },
-                }
+        fn item(hash: &str, ttl: Option<&str>) -> Item {
+            let mut item: Item = [
+                ("PK".to_string(), AttributeValue::S(hash.to_string())),
+                ("SK".to_string(), AttributeValue::S(hash.to_string())),
+            ]
+            .into();
+            if let Some(ttl) = ttl {
+                item.insert("ttl".to_string(), AttributeValue::N(ttl.to_string()));
             }
+            item
         }
 
         #[test]
-        fn test_entity_serializes_as_expected() {
-            let entity = TestEntity {
-                id: "test1".to_string(),
-                name: "Test".to_string(),
-                email: "my_email@not_real.com".to_string(),
+        fn expired_items_are_excluded_from_reads() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::GetItem>(|e| {
+                e.responding(|input| {
+                    let hash = input.key.as_ref().unwrap()["PK"].as_s().unwrap();
+                    let ttl = match hash.as_str() {
+                        "expired" => Some("1"),
+                        "unexpired" => Some("4000000000"),
+                        _ => None,
+                    };
+                    Ok(GetItemOutput::builder()
+                        .set_item(Some(item(hash, ttl)))
+                        .build())
+                })
+            });
+            table.expect::<ops::Query>(|e| e.times(1).returning(QueryOutput::builder().build()));
+
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
+            let found = |hash: &str| {
+                runtime
+                    .block_on(model::Get::new(item(hash, None)).execute(&table))
+                    .unwrap()
+                    .item
+                    .is_some()
             };
-
-            let item = entity.into_item();
-            assert_eq!(item.len(), 8);
-            assert_eq!(item["entity_type"].as_s().unwrap(), "test_ent");
-            assert_eq!(item["PK"].as_s().unwrap(), "PK#test1");
-            assert_eq!(item["SK"].as_s().unwrap(), "NAME#my_email@not_real.com");
-            assert_eq!(item["GSI13PK"].as_s().unwrap(), "GSI13#test1");
-            assert_eq!(item["GSI13SK"].as_s().unwrap(), "GSI13#NAME#Test");
-            assert_eq!(item["id"].as_s().unwrap(), "test1");
-            assert_eq!(item["name"].as_s().unwrap(), "Test");
-            assert_eq!(item["email"].as_s().unwrap(), "my_email@not_real.com");
+            assert!(!found("expired"));
+            assert!(found("unexpired"));
+            assert!(found("permanent"));
+
+            runtime
+                .block_on(
+                    model::Query::<keys::Primary>::new(expr::KeyCondition::in_partition("A"))
+                        .filter(
+                            expr::Filter::new("#kind = :kind")
+                                .name("#kind", "kind")
+                                .value(":kind", "a"),
+                        )
+                        .execute(&table),
+                )
+                .unwrap();
+
+            let inputs = table.inputs::<ops::Query>();
+            assert_eq!(
+                inputs[0].filter_expression.as_deref(),
+                Some(
+                    "(#flt_kind = :flt_kind) AND (attribute_not_exists(#flt_rd_ttl) OR NOT \
+                     attribute_type(#flt_rd_ttl, :flt_rd_ttl_type) OR #flt_rd_ttl > \
+                     :flt_rd_ttl_now)"
+                )
+            );
+            let names = inputs[0].expression_attribute_names.as_ref().unwrap();
+            assert_eq!(names["#flt_rd_ttl"], "ttl");
+            let values = inputs[0].expression_attribute_values.as_ref().unwrap();
+            assert!(values.contains_key(":flt_rd_ttl_now"));
+            assert_eq!(
+                values[":flt_rd_ttl_type"],
+                AttributeValue::S("N".to_string())
+            );
+            table.verify();
         }
 
         #[test]
-        fn test_entity_deserializes_as_expected() {
-            let entity = TestEntity {
-                id: "test1".to_string(),
-                name: "Test".to_string(),
-                email: "my_email@not_real.com".to_string(),
+        fn expired_items_are_excluded_from_batches_and_transactions() {
+            let mut malformed = item("malformed", None);
+            malformed.insert("ttl".to_string(), AttributeValue::S("soon".to_string()));
+            let mut items = vec![
+                item("expired", Some("1")),
+                item("unexpired", Some("4000000000")),
+                malformed,
+            ];
+            for item in &mut items {
+                item.insert(
+                    "entity_type".to_string(),
+                    AttributeValue::S("session".to_string()),
+                );
+            }
+
+            let table = MockTable::<TestTable>::new("test");
+            let batch_output = BatchGetItemOutput::builder()
+                .responses("test", items.clone())
+                .build();
+            let transact_output = TransactGetItemsOutput::builder()
+                .set_responses(Some(
+                    items
+                        .iter()
+                        .map(|item| ItemResponse::builder().set_item(Some(item.clone())).build())
+                        .collect(),
+                ))
+                .build();
+            table
+                .expect::<ops::BatchGetItem>(|e| e.times(2).returning(batch_output))
+                .expect::<ops::TransactGetItems>(|e| e.times(1).returning(transact_output));
+
+            let gets = || {
+                ["expired", "unexpired", "malformed"].map(|hash| model::Get::new(item(hash, None)))
             };
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
 
-            let item = entity.clone().into_item();
-            let entity_type = TestTable::deserialize_entity_type(&item["entity_type"])
+            let output = runtime
+                .block_on(model::BatchGet::new().operations(gets()).execute(&table))
+                .unwrap();
+            let found: Vec<_> = output.responses.unwrap()["test"]
+                .iter()
+                .map(|item| item["PK"].as_s().unwrap().clone())
+                .collect();
+            assert_eq!(found, ["unexpired", "malformed"]);
+
+            let found = runtime
+                .block_on(
+                    model::BatchGet::new()
+                        .operations(gets())
+                        .typed::<RawItem<TestTable>>()
+                        .execute(&table),
+                )
+                .unwrap();
+            assert_eq!(found.len(), 2);
+
+            let output = runtime
+                .block_on(model::TransactGet::new().operations(gets()).execute(&table))
+                .unwrap();
+            let found: Vec<_> = output
+                .responses
                 .unwrap()
-                .to_owned();
-            let clone = TestEntity::from_item(item).unwrap();
+                .iter()
+                .map(|response| response.item.is_some())
+                .collect();
+            assert_eq!(found, [false, true, true]);
+            table.verify();
+        }
 
-            assert_eq!(entity, clone);
-            assert_eq!(entity_type, TestEntity::ENTITY_TYPE);
+        #[test]
+        fn generated_projections_include_the_ttl_attribute() {
+            let projection =
+                __private::generate_projection_expression::<TestTable>(&[&["name"]]).unwrap();
+            assert!(projection.names.iter().any(|&(_, name)| name == "ttl"));
         }
     }
 
-    mod as_string_set {
+    mod customization {
+        use aws_sdk_dynamodb::operation::get_item::GetItemOutput;
+
         use super::*;
+        use crate::{
+            client::Customization,
+            mock::{ops, MockTable},
+        };
 
         struct TestTable;
         impl Table for TestTable {
             type PrimaryKey = keys::Primary;
-            type IndexKeys = keys::Gsi13;
+            type IndexKeys = keys::Gsi1;
 
             fn client(&self) -> &aws_sdk_dynamodb::Client {
                 unimplemented!()
@@ -1185,110 +7049,49 @@ mod tests {
             fn table_name(&self) -> &str {
                 unimplemented!()
             }
-
-            fn deserialize_entity_type(
-                attr: &AttributeValue,
-            ) -> Result<&EntityTypeNameRef, MalformedEntityTypeError> {
-                let values = attr.as_ss().map_err(|_| {
-                    MalformedEntityTypeError::Custom("expected a string set".into())
-                })?;
-                let value = values
-                    .first()
-                    .expect("a DynamoDB string set always has at least one element");
-                Ok(EntityTypeNameRef::from_str(value.as_str()))
-            }
-
-            fn serialize_entity_type(entity_type: &EntityTypeNameRef) -> AttributeValue {
-                AttributeValue::Ss(vec![entity_type.to_string()])
-            }
-        }
-
-        #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
-        struct TestEntity {
-            id: String,
-            name: String,
-            email: String,
-        }
-
-        impl EntityDef for TestEntity {
-            const ENTITY_TYPE: &'static EntityTypeNameRef =
-                EntityTypeNameRef::from_static("test_ent");
-        }
-
-        impl Entity for TestEntity {
-            type KeyInput<'a> = (&'a str, &'a str);
-            type Table = TestTable;
-            type IndexKeys = keys::Gsi13;
-
-            fn primary_key((id, email): Self::KeyInput<'_>) -> keys::Primary {
-                keys::Primary {
-                    hash: format!("PK#{id}"),
-                    range: format!("NAME#{email}"),
-                }
-            }
-
-            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
-                keys::FullKey {
-                    primary: Self::primary_key((&self.id, &self.email)),
-                    indexes: keys::Gsi13 {
-                        hash: format!("GSI13#{}", self.id),
-                        range: format!("GSI13#NAME#{}", self.name),
-                    },
-                }
-            }
-        }
-
-        #[test]
-        fn test_entity_serializes_as_expected() {
-            let entity = TestEntity {
-                id: "test1".to_string(),
-                name: "Test".to_string(),
-                email: "my_email@not_real.com".to_string(),
-            };
-
-            let item = entity.into_item();
-            assert_eq!(item.len(), 8);
-            assert_eq!(
-                item["entity_type"].as_ss().unwrap(),
-                &["test_ent".to_owned()]
-            );
-            assert_eq!(item["PK"].as_s().unwrap(), "PK#test1");
-            assert_eq!(item["SK"].as_s().unwrap(), "NAME#my_email@not_real.com");
-            assert_eq!(item["GSI13PK"].as_s().unwrap(), "GSI13#test1");
-            assert_eq!(item["GSI13SK"].as_s().unwrap(), "GSI13#NAME#Test");
-            assert_eq!(item["id"].as_s().unwrap(), "test1");
-            assert_eq!(item["name"].as_s().unwrap(), "Test");
-            assert_eq!(item["email"].as_s().unwrap(), "my_email@not_real.com");
         }
 
         #[test]
-        fn test_entity_deserializes_as_expected() {
-            let entity = TestEntity {
-                id: "test1".to_string(),
-                name: "Test".to_string(),
-                email: "my_email@not_real.com".to_string(),
+        fn customizations_are_current_only_while_their_operation_is_sent() {
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::GetItem>(|e| {
+                e.times(2).responding(|input| {
+                    let customized = input.key.as_ref().unwrap()["PK"].as_s().unwrap() == "custom";
+                    assert_eq!(Customization::current().is_some(), customized);
+                    Ok(GetItemOutput::builder().build())
+                })
+            });
+
+            let key = |hash: &str| -> Item {
+                [("PK".to_string(), AttributeValue::S(hash.to_string()))].into()
             };
-
-            let item = entity.clone().into_item();
-            let entity_type = TestTable::deserialize_entity_type(&item["entity_type"])
-                .unwrap()
-                .to_owned();
-            let clone = TestEntity::from_item(item).unwrap();
-
-            assert_eq!(entity, clone);
-            assert_eq!(entity_type, TestEntity::ENTITY_TYPE);
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
+            runtime.block_on(async {
+                model::Get::new(key("custom"))
+                    .with_customization(Customization::new())
+                    .execute(&table)
+                    .await
+                    .unwrap();
+                model::Get::new(key("plain")).execute(&table).await.unwrap();
+            });
+
+            assert!(Customization::current().is_none());
+            table.verify();
         }
     }
 
-    mod alternate_attribute {
+    mod typed_batch_get {
+        use aws_sdk_dynamodb::operation::batch_get_item::BatchGetItemOutput;
+
         use super::*;
+        use crate::mock::{ops, MockTable};
 
         struct TestTable;
         impl Table for TestTable {
-            const ENTITY_TYPE_ATTRIBUTE: &'static str = "et";
-
             type PrimaryKey = keys::Primary;
-            type IndexKeys = keys::Gsi13;
+            type IndexKeys = keys::Gsi1;
 
             fn client(&self) -> &aws_sdk_dynamodb::Client {
                 unimplemented!()
@@ -1300,76 +7103,163 @@ mod tests {
         }
 
         #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
-        struct TestEntity {
+        struct Order {
             id: String,
+        }
+
+        impl EntityDef for Order {
+            const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("order");
+        }
+
+        impl Entity for Order {
+            type KeyInput<'a> = &'a str;
+            type Table = TestTable;
+            type IndexKeys = ();
+
+            fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+                keys::Primary {
+                    hash: "CUSTOMER".to_string(),
+                    range: format!("ORDER#{id}"),
+                }
+            }
+
+            fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+                Self::primary_key(&self.id).into()
+            }
+        }
+
+        #[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+        struct Customer {
             name: String,
-            email: String,
         }
 
-        impl EntityDef for TestEntity {
+        impl EntityDef for Customer {
             const ENTITY_TYPE: &'static EntityTypeNameRef =
-                EntityTypeNameRef::from_static("test_ent");
+                EntityTypeNameRef::from_static("customer");
         }
 
-        impl Entity for TestEntity {
-            type KeyInput<'a> = (&'a str, &'a str);
+        impl Entity for Customer {
+            type KeyInput<'a> = ();
             type Table = TestTable;
-            type IndexKeys = keys::Gsi13;
+            type IndexKeys = ();
 
-            fn primary_key((id, email): Self::KeyInput<'_>) -> keys::Primary {
+            fn primary_key(_: Self::KeyInput<'_>) -> keys::Primary {
                 keys::Primary {
-                    hash: format!("PK#{id}"),
-                    range: format!("NAME#{email}"),
+                    hash: "CUSTOMER".to_string(),
+                    range: "CUSTOMER".to_string(),
                 }
             }
 
             fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
-                keys::FullKey {
-                    primary: Self::primary_key((&self.id, &self.email)),
-                    indexes: keys::Gsi13 {
-                        hash: format!("GSI13#{}", self.id),
-                        range: format!("GSI13#NAME#{}", self.name),
-                    },
-                }
+                Self::primary_key(()).into()
+            }
+        }
+
+        projections! {
+            #[derive(Debug, PartialEq, Eq)]
+            enum CustomerItem {
+                Order,
+                Customer,
             }
         }
 
+        fn order(id: &str) -> Order {
+            Order { id: id.to_string() }
+        }
+
         #[test]
-        fn test_entity_serializes_as_expected() {
-            let entity = TestEntity {
-                id: "test1".to_string(),
-                name: "Test".to_string(),
-                email: "my_email@not_real.com".to_string(),
+        fn items_are_dispatched_by_entity_type_in_key_order() {
+            let customer = Customer {
+                name: "Alex".to_string(),
             };
+            let stored = vec![
+                order("2").into_item(),
+                customer.clone().into_item(),
+                order("1").into_item(),
+            ];
+
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::BatchGetItem>(|e| {
+                e.times(1).returning(
+                    BatchGetItemOutput::builder()
+                        .responses("test", stored)
+                        .build(),
+                )
+            });
 
-            let item = entity.into_item();
-            assert_eq!(item.len(), 8);
-            assert_eq!(item["et"].as_s().unwrap(), "test_ent");
-            assert_eq!(item["PK"].as_s().unwrap(), "PK#test1");
-            assert_eq!(item["SK"].as_s().unwrap(), "NAME#my_email@not_real.com");
-            assert_eq!(item["GSI13PK"].as_s().unwrap(), "GSI13#test1");
-            assert_eq!(item["GSI13SK"].as_s().unwrap(), "GSI13#NAME#Test");
-            assert_eq!(item["id"].as_s().unwrap(), "test1");
-            assert_eq!(item["name"].as_s().unwrap(), "Test");
-            assert_eq!(item["email"].as_s().unwrap(), "my_email@not_real.com");
+            let batch = model::BatchGet::new()
+                .operation(Order::get("1"))
+                .operation(Customer::get(()))
+                .operation(Order::get("3"))
+                .operation(Order::get("2"));
+
+            let items = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap()
+                .block_on(batch.typed::<CustomerItem>().execute(&table))
+                .unwrap();
+
+            assert_eq!(
+                items,
+                [
+                    CustomerItem::Order(order("1")),
+                    CustomerItem::Customer(customer),
+                    CustomerItem::Order(order("2")),
+                ]
+            );
+            table.verify();
         }
 
         #[test]
-        fn test_entity_deserializes_as_expected() {
-            let entity = TestEntity {
-                id: "test1".to_string(),
-                name: "Test".to_string(),
-                email: "my_email@not_real.com".to_string(),
+        fn batch_projections_combine_the_projections_of_each_get() {
+            const ORDER: expr::StaticProjection = expr::StaticProjection {
+                expression: "id,entity_type",
+                names: &[],
+            };
+            const CUSTOMER: expr::StaticProjection = expr::StaticProjection {
+                expression: "#prj_000,entity_type",
+                names: &[("#prj_000", "name")],
             };
 
-            let item = entity.clone().into_item();
-            let entity_type = TestTable::deserialize_entity_type(&item["et"])
-                .unwrap()
-                .to_owned();
-            let clone = TestEntity::from_item(item).unwrap();
+            let table = MockTable::<TestTable>::new("test");
+            table.expect::<ops::BatchGetItem>(|e| {
+                e.times(2).returning(BatchGetItemOutput::builder().build())
+            });
 
-            assert_eq!(entity, clone);
-            assert_eq!(entity_type, TestEntity::ENTITY_TYPE);
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
+            runtime
+                .block_on(
+                    model::BatchGet::new()
+                        .operation(Order::get("1").projection(ORDER))
+                        .operation(Customer::get(()).projection(CUSTOMER))
+                        .execute(&table),
+                )
+                .unwrap();
+            runtime
+                .block_on(
+                    model::BatchGet::new()
+                        .operation(Order::get("1").projection(ORDER))
+                        .operation(Customer::get(()))
+                        .execute(&table),
+                )
+                .unwrap();
+
+            let inputs = table.inputs::<ops::BatchGetItem>();
+            let request = &inputs[0].request_items.as_ref().unwrap()["test"];
+            assert_eq!(
+                request.projection_expression(),
+                Some("id,entity_type,#prj_000,PK,SK")
+            );
+            assert_eq!(
+                request.expression_attribute_names().unwrap()["#prj_000"],
+                "name"
+            );
+
+            let request = &inputs[1].request_items.as_ref().unwrap()["test"];
+            assert_eq!(request.projection_expression(), None);
+            table.verify();
         }
     }
 }
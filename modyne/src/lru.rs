@@ -0,0 +1,141 @@
+//! A minimal least-recently-used map used by the caching layers
+
+use std::{
+    borrow::Borrow,
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+};
+
+/// A map that evicts its least-recently-used entry when over capacity
+pub(crate) struct Lru<K, V> {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<K, (V, u64)>,
+    order: BTreeMap<u64, K>,
+}
+
+impl<K: Hash + Eq + Clone, V> Lru<K, V> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the value for the key, marking it as most recently used
+    pub(crate) fn get<Q>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let tick = self.next_tick();
+        let (value, last_used) = self.entries.get_mut(key)?;
+        let key = self
+            .order
+            .remove(&*last_used)
+            .expect("entries and order are in sync");
+        self.order.insert(tick, key);
+        *last_used = tick;
+        Some(value)
+    }
+
+    /// Inserts a value, evicting the least recently used entry if over capacity
+    pub(crate) fn insert(&mut self, key: K, value: V) {
+        let tick = self.next_tick();
+        if let Some((_, last_used)) = self.entries.insert(key.clone(), (value, tick)) {
+            self.order.remove(&last_used);
+        }
+        self.order.insert(tick, key);
+
+        while self.entries.len() > self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+    }
+
+    pub(crate) fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (value, last_used) = self.entries.remove(key)?;
+        self.order.remove(&last_used);
+        Some(value)
+    }
+
+    /// Retains only the entries for which the predicate returns true
+    pub(crate) fn retain(&mut self, mut f: impl FnMut(&K, &V) -> bool) {
+        let order = &mut self.order;
+        self.entries.retain(|key, (value, last_used)| {
+            let keep = f(key, value);
+            if !keep {
+                order.remove(&*last_used);
+            }
+            keep
+        });
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut lru = Lru::new(2);
+        lru.insert("a", 1);
+        lru.insert("b", 2);
+        assert_eq!(lru.get(&"a"), Some(&1));
+
+        lru.insert("c", 3);
+        assert_eq!(lru.len(), 2);
+        assert_eq!(lru.get(&"b"), None);
+        assert_eq!(lru.get(&"a"), Some(&1));
+        assert_eq!(lru.get(&"c"), Some(&3));
+    }
+
+    #[test]
+    fn reinserting_replaces_value() {
+        let mut lru = Lru::new(2);
+        lru.insert("a", 1);
+        lru.insert("a", 2);
+        assert_eq!(lru.len(), 1);
+        assert_eq!(lru.get(&"a"), Some(&2));
+    }
+
+    #[test]
+    fn retain_and_remove_keep_order_in_sync() {
+        let mut lru = Lru::new(3);
+        lru.insert("a", 1);
+        lru.insert("b", 2);
+        lru.insert("c", 3);
+        lru.retain(|_, v| *v != 2);
+        assert_eq!(lru.remove(&"a"), Some(1));
+        lru.insert("d", 4);
+        lru.insert("e", 5);
+        assert_eq!(lru.len(), 3);
+        assert_eq!(lru.get(&"c"), Some(&3));
+    }
+}
@@ -0,0 +1,790 @@
+//! Programmable stand-ins for DynamoDB, for unit tests
+//!
+//! A [`MockClient`] is a [`DynamoClient`] that answers each operation
+//! according to the expectations programmed into it, without sending any
+//! requests. Each call is matched against the expectations in the order
+//! they were added, and the first matching expectation that has not been
+//! exhausted provides the response. A call that matches no expectation
+//! panics.
+//!
+//! A [`MockTable`] combines a mock client with the key structure of an
+//! existing table type, so that code generic over [`Table`] can be tested
+//! directly. Given an instance of the table with
+//! [`with_table()`][MockTable::with_table()], it also forwards the table's
+//! hooks, such as its key scope and item cache. Code that requires a
+//! specific table type can instead return a mock client from
+//! [`Table::dynamo_client()`].
+//!
+//! ```
+//! use modyne::{
+//!     expr, keys,
+//!     mock::{ops, MockTable, Operation},
+//!     model::Query,
+//!     AttributeValue, Item, Table,
+//! };
+//!
+//! struct App;
+//!
+//! impl Table for App {
+//!     type PrimaryKey = keys::Primary;
+//!     type IndexKeys = keys::Gsi1;
+//!     fn table_name(&self) -> &str { unimplemented!() }
+//!     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! }
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let item: Item = [("entity_type".to_string(), AttributeValue::S("order".into()))].into();
+//!
+//! let table = MockTable::<App>::new("EcommerceTable");
+//! table.expect::<ops::Query>(|e| {
+//!     e.on_index("GSI1")
+//!         .with_key_value("ORDER#1234")
+//!         .returning_items(vec![item.clone()])
+//!         .times(1)
+//! });
+//!
+//! let output = Query::<keys::Gsi1>::new(expr::KeyCondition::in_partition("ORDER#1234"))
+//!     .execute(&table)
+//!     .await
+//!     .unwrap();
+//!
+//! assert_eq!(output.items(), [item]);
+//! assert_eq!(table.calls(Operation::Query), 1);
+//! table.verify();
+//! # });
+//! ```
+
+use std::{
+    any::Any,
+    fmt,
+    sync::{Mutex, PoisonError},
+};
+
+use aws_sdk_dynamodb::{
+    config::BehaviorVersion,
+    error::SdkError,
+    operation::{
+        batch_get_item::{BatchGetItemInput, BatchGetItemOutput},
+        batch_write_item::{BatchWriteItemInput, BatchWriteItemOutput},
+        delete_item::{DeleteItemInput, DeleteItemOutput},
+        get_item::{GetItemInput, GetItemOutput},
+        put_item::{PutItemInput, PutItemOutput},
+        query::{QueryInput, QueryOutput},
+        scan::{ScanInput, ScanOutput},
+        transact_get_items::{TransactGetItemsInput, TransactGetItemsOutput},
+        transact_write_items::{TransactWriteItemsInput, TransactWriteItemsOutput},
+        update_item::{UpdateItemInput, UpdateItemOutput},
+    },
+};
+
+use crate::{
+    cache::ItemCache,
+    client::DynamoClient,
+    clock::{Clock, SystemClock},
+    instrumentation::{PartitionAccess, SemanticConventions, SkippedItems},
+    scope::KeyScope,
+    AttributeValue, EntityDiscriminator, EntityTypeNameRef, Item, MalformedEntityTypeError, Table,
+    WritableTable,
+};
+
+/// A DynamoDB operation
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Operation {
+    /// `GetItem`
+    GetItem,
+    /// `PutItem`
+    PutItem,
+    /// `UpdateItem`
+    UpdateItem,
+    /// `DeleteItem`
+    DeleteItem,
+    /// `Query`
+    Query,
+    /// `Scan`
+    Scan,
+    /// `BatchGetItem`
+    BatchGetItem,
+    /// `BatchWriteItem`
+    BatchWriteItem,
+    /// `TransactGetItems`
+    TransactGetItems,
+    /// `TransactWriteItems`
+    TransactWriteItems,
+}
+
+/// The types of a DynamoDB operation that can be mocked
+///
+/// This trait is implemented by the marker types in [`ops`].
+pub trait MockOperation: Send + Sync + 'static {
+    /// The operation
+    const OPERATION: Operation;
+
+    /// The input of the operation
+    type Input: Clone + fmt::Debug + Send + Sync + 'static;
+
+    /// The output of the operation
+    type Output: Clone + Send + Sync + 'static;
+
+    /// The error returned by the operation
+    type Error: Send + Sync + 'static;
+
+    /// An output with no items or attributes
+    fn empty_output() -> Self::Output;
+}
+
+/// Marker types identifying each mockable operation
+pub mod ops {
+    use super::*;
+
+    macro_rules! operations {
+        ($($name:ident: $input:ty => $output:ty, $error:ty;)*) => {
+            $(
+                #[doc = concat!("The `", stringify!($name), "` operation")]
+                #[derive(Debug)]
+                pub enum $name {}
+
+                impl MockOperation for $name {
+                    const OPERATION: Operation = Operation::$name;
+                    type Input = $input;
+                    type Output = $output;
+                    type Error = $error;
+
+                    fn empty_output() -> Self::Output {
+                        <$output>::builder().build()
+                    }
+                }
+            )*
+        };
+    }
+
+    operations! {
+        GetItem: GetItemInput => GetItemOutput, aws_sdk_dynamodb::operation::get_item::GetItemError;
+        PutItem: PutItemInput => PutItemOutput, aws_sdk_dynamodb::operation::put_item::PutItemError;
+        UpdateItem: UpdateItemInput => UpdateItemOutput, aws_sdk_dynamodb::operation::update_item::UpdateItemError;
+        DeleteItem: DeleteItemInput => DeleteItemOutput, aws_sdk_dynamodb::operation::delete_item::DeleteItemError;
+        Query: QueryInput => QueryOutput, aws_sdk_dynamodb::operation::query::QueryError;
+        Scan: ScanInput => ScanOutput, aws_sdk_dynamodb::operation::scan::ScanError;
+        BatchGetItem: BatchGetItemInput => BatchGetItemOutput, aws_sdk_dynamodb::operation::batch_get_item::BatchGetItemError;
+        BatchWriteItem: BatchWriteItemInput => BatchWriteItemOutput, aws_sdk_dynamodb::operation::batch_write_item::BatchWriteItemError;
+        TransactGetItems: TransactGetItemsInput => TransactGetItemsOutput, aws_sdk_dynamodb::operation::transact_get_items::TransactGetItemsError;
+        TransactWriteItems: TransactWriteItemsInput => TransactWriteItemsOutput, aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
+    }
+}
+
+type Matcher<O> = Box<dyn Fn(&<O as MockOperation>::Input) -> bool + Send + Sync>;
+type Responder<O> = Box<
+    dyn Fn(
+            &<O as MockOperation>::Input,
+        ) -> Result<<O as MockOperation>::Output, SdkError<<O as MockOperation>::Error>>
+        + Send
+        + Sync,
+>;
+
+/// An expected call to an operation and its response
+///
+/// By default, an expectation matches any call to the operation, may be
+/// called any number of times, and responds with an empty output.
+#[must_use]
+pub struct Expectation<O: MockOperation> {
+    conditions: Vec<String>,
+    matchers: Vec<Matcher<O>>,
+    responder: Responder<O>,
+    times: Option<usize>,
+    calls: usize,
+}
+
+impl<O: MockOperation> fmt::Debug for Expectation<O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Expectation")
+            .field("operation", &O::OPERATION)
+            .field("conditions", &self.conditions)
+            .field("times", &self.times)
+            .field("calls", &self.calls)
+            .finish()
+    }
+}
+
+impl<O: MockOperation> Default for Expectation<O> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<O: MockOperation> Expectation<O> {
+    /// Expect any call to the operation
+    #[allow(clippy::result_large_err)]
+    pub fn new() -> Self {
+        Self {
+            conditions: Vec::new(),
+            matchers: Vec::new(),
+            responder: Box::new(|_| Ok(O::empty_output())),
+            times: None,
+            calls: 0,
+        }
+    }
+
+    /// Only match calls whose input satisfies the predicate
+    ///
+    /// The description is used when reporting unmet expectations.
+    pub fn matching(
+        mut self,
+        description: impl Into<String>,
+        predicate: impl Fn(&O::Input) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.conditions.push(description.into());
+        self.matchers.push(Box::new(predicate));
+        self
+    }
+
+    /// Respond to matching calls with the given output
+    #[allow(clippy::result_large_err)]
+    pub fn returning(mut self, output: O::Output) -> Self {
+        self.responder = Box::new(move |_| Ok(output.clone()));
+        self
+    }
+
+    /// Respond to matching calls with the result of the function
+    ///
+    /// This can be used to return errors, or outputs that depend on the input.
+    pub fn responding(
+        mut self,
+        respond: impl Fn(&O::Input) -> Result<O::Output, SdkError<O::Error>> + Send + Sync + 'static,
+    ) -> Self {
+        self.responder = Box::new(respond);
+        self
+    }
+
+    /// Expect exactly the given number of matching calls
+    ///
+    /// Once the expected number of calls has been made, further calls are
+    /// matched against later expectations.
+    pub fn times(mut self, times: usize) -> Self {
+        self.times = Some(times);
+        self
+    }
+
+    fn matches(&self, input: &O::Input) -> bool {
+        self.times.map_or(true, |times| self.calls < times)
+            && self.matchers.iter().all(|matcher| matcher(input))
+    }
+
+    fn is_met(&self) -> bool {
+        self.times
+            .map_or(self.calls > 0, |times| self.calls == times)
+    }
+}
+
+macro_rules! indexed_operation {
+    ($op:ty, $noun:literal) => {
+        impl Expectation<$op> {
+            #[doc = concat!("Only match ", $noun, " of the given secondary index")]
+            pub fn on_index(self, index_name: &'static str) -> Self {
+                self.matching(format!("on index `{index_name}`"), move |input| {
+                    input.index_name.as_deref() == Some(index_name)
+                })
+            }
+
+            #[doc = concat!("Only match ", $noun, " of the table itself")]
+            pub fn on_table(self) -> Self {
+                self.matching("on the table", |input| input.index_name.is_none())
+            }
+
+            #[doc = concat!("Respond to matching ", $noun, " with a single page of items")]
+            pub fn returning_items(self, items: Vec<Item>) -> Self {
+                let count = items.len() as i32;
+                self.returning(
+                    <<$op as MockOperation>::Output>::builder()
+                        .set_items(Some(items))
+                        .count(count)
+                        .scanned_count(count)
+                        .build(),
+                )
+            }
+        }
+    };
+}
+
+indexed_operation!(ops::Query, "queries");
+indexed_operation!(ops::Scan, "scans");
+
+impl Expectation<ops::Query> {
+    /// Only match queries with a key condition comparing against the value
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value cannot be serialized to an `AttributeValue`.
+    pub fn with_key_value(self, value: impl serde::Serialize) -> Self {
+        let value: AttributeValue = serde_dynamo::to_attribute_value(value).unwrap();
+        self.matching(format!("with key value {value:?}"), move |input| {
+            let expression = input.key_condition_expression.as_deref().unwrap_or("");
+            input
+                .expression_attribute_values
+                .iter()
+                .flatten()
+                .any(|(name, v)| *v == value && expression.contains(name.as_str()))
+        })
+    }
+}
+
+impl Expectation<ops::GetItem> {
+    /// Only match gets of the item with the given key
+    pub fn with_key(self, key: Item) -> Self {
+        self.matching(
+            format!("with key {:?}", crate::instrumentation::sorted(&key)),
+            move |input| input.key.as_ref() == Some(&key),
+        )
+    }
+
+    /// Respond to matching gets with the given item, or with no item
+    pub fn returning_item(self, item: Option<Item>) -> Self {
+        self.returning(GetItemOutput::builder().set_item(item).build())
+    }
+}
+
+trait AnyExpectation: Send {
+    fn operation(&self) -> Operation;
+    fn is_met(&self) -> bool;
+    fn describe(&self) -> String;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<O: MockOperation> AnyExpectation for Expectation<O> {
+    fn operation(&self) -> Operation {
+        O::OPERATION
+    }
+
+    fn is_met(&self) -> bool {
+        Expectation::is_met(self)
+    }
+
+    fn describe(&self) -> String {
+        let times = self
+            .times
+            .map_or_else(|| "at least once".to_string(), |t| format!("{t} times"));
+        let conditions = if self.conditions.is_empty() {
+            String::new()
+        } else {
+            format!(" {}", self.conditions.join(", "))
+        };
+        format!(
+            "{:?}{conditions} expected {times}, called {} times",
+            O::OPERATION,
+            self.calls
+        )
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[derive(Default)]
+struct State {
+    expectations: Vec<Box<dyn AnyExpectation>>,
+    calls: Vec<(Operation, Box<dyn Any + Send>)>,
+}
+
+/// A client that responds to operations according to programmed expectations
+#[derive(Default)]
+pub struct MockClient {
+    state: Mutex<State>,
+}
+
+impl fmt::Debug for MockClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        f.debug_struct("MockClient")
+            .field("expectations", &state.expectations.len())
+            .field("calls", &state.calls.len())
+            .finish()
+    }
+}
+
+impl MockClient {
+    /// Construct a client without any expectations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an expectation for calls to an operation
+    pub fn expect<O: MockOperation>(
+        &self,
+        configure: impl FnOnce(Expectation<O>) -> Expectation<O>,
+    ) -> &Self {
+        let expectation = configure(Expectation::new());
+        self.lock().expectations.push(Box::new(expectation));
+        self
+    }
+
+    /// The number of calls made to an operation
+    pub fn calls(&self, operation: Operation) -> usize {
+        self.lock()
+            .calls
+            .iter()
+            .filter(|(op, _)| *op == operation)
+            .count()
+    }
+
+    /// The inputs of the calls made to an operation, in order
+    pub fn inputs<O: MockOperation>(&self) -> Vec<O::Input> {
+        self.lock()
+            .calls
+            .iter()
+            .filter_map(|(_, input)| input.downcast_ref::<O::Input>())
+            .cloned()
+            .collect()
+    }
+
+    /// Asserts that every expectation has been met
+    ///
+    /// An expectation with an expected number of calls must have been called
+    /// exactly that many times. Otherwise, it must have been called at least
+    /// once.
+    ///
+    /// # Panics
+    ///
+    /// Panics, describing each unmet expectation, if any have not been met.
+    pub fn verify(&self) {
+        let unmet: Vec<_> = self
+            .lock()
+            .expectations
+            .iter()
+            .filter(|e| !e.is_met())
+            .map(|e| e.describe())
+            .collect();
+
+        assert!(
+            unmet.is_empty(),
+            "unmet expectations:\n  {}",
+            unmet.join("\n  ")
+        );
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn call<O: MockOperation>(&self, input: O::Input) -> Result<O::Output, SdkError<O::Error>> {
+        let mut state = self.lock();
+        state.calls.push((O::OPERATION, Box::new(input.clone())));
+
+        let expectation = state
+            .expectations
+            .iter_mut()
+            .filter(|e| e.operation() == O::OPERATION)
+            .filter_map(|e| e.as_any_mut().downcast_mut::<Expectation<O>>())
+            .find(|e| e.matches(&input));
+
+        let Some(expectation) = expectation else {
+            drop(state);
+            panic!("unexpected {:?} call: {input:?}", O::OPERATION);
+        };
+
+        expectation.calls += 1;
+        (expectation.responder)(&input)
+    }
+}
+
+#[async_trait::async_trait]
+impl DynamoClient for MockClient {
+    async fn get_item(
+        &self,
+        input: GetItemInput,
+    ) -> Result<GetItemOutput, SdkError<<ops::GetItem as MockOperation>::Error>> {
+        self.call::<ops::GetItem>(input)
+    }
+
+    async fn put_item(
+        &self,
+        input: PutItemInput,
+    ) -> Result<PutItemOutput, SdkError<<ops::PutItem as MockOperation>::Error>> {
+        self.call::<ops::PutItem>(input)
+    }
+
+    async fn update_item(
+        &self,
+        input: UpdateItemInput,
+    ) -> Result<UpdateItemOutput, SdkError<<ops::UpdateItem as MockOperation>::Error>> {
+        self.call::<ops::UpdateItem>(input)
+    }
+
+    async fn delete_item(
+        &self,
+        input: DeleteItemInput,
+    ) -> Result<DeleteItemOutput, SdkError<<ops::DeleteItem as MockOperation>::Error>> {
+        self.call::<ops::DeleteItem>(input)
+    }
+
+    async fn query(
+        &self,
+        input: QueryInput,
+    ) -> Result<QueryOutput, SdkError<<ops::Query as MockOperation>::Error>> {
+        self.call::<ops::Query>(input)
+    }
+
+    async fn scan(
+        &self,
+        input: ScanInput,
+    ) -> Result<ScanOutput, SdkError<<ops::Scan as MockOperation>::Error>> {
+        self.call::<ops::Scan>(input)
+    }
+
+    async fn batch_get_item(
+        &self,
+        input: BatchGetItemInput,
+    ) -> Result<BatchGetItemOutput, SdkError<<ops::BatchGetItem as MockOperation>::Error>> {
+        self.call::<ops::BatchGetItem>(input)
+    }
+
+    async fn batch_write_item(
+        &self,
+        input: BatchWriteItemInput,
+    ) -> Result<BatchWriteItemOutput, SdkError<<ops::BatchWriteItem as MockOperation>::Error>> {
+        self.call::<ops::BatchWriteItem>(input)
+    }
+
+    async fn transact_get_items(
+        &self,
+        input: TransactGetItemsInput,
+    ) -> Result<TransactGetItemsOutput, SdkError<<ops::TransactGetItems as MockOperation>::Error>>
+    {
+        self.call::<ops::TransactGetItems>(input)
+    }
+
+    async fn transact_write_items(
+        &self,
+        input: TransactWriteItemsInput,
+    ) -> Result<TransactWriteItemsOutput, SdkError<<ops::TransactWriteItems as MockOperation>::Error>>
+    {
+        self.call::<ops::TransactWriteItems>(input)
+    }
+}
+
+/// A table with the structure of `T` whose operations are answered by a
+/// [`MockClient`]
+///
+/// The mock client is available through [`Deref`][std::ops::Deref], so
+/// expectations can be added to the table directly.
+pub struct MockTable<T> {
+    table_name: String,
+    client: MockClient,
+    sdk_client: aws_sdk_dynamodb::Client,
+    clock: Option<Box<dyn Clock>>,
+    table: Option<T>,
+}
+
+impl<T> fmt::Debug for MockTable<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockTable")
+            .field("table", &std::any::type_name::<T>())
+            .field("table_name", &self.table_name)
+            .field("client", &self.client)
+            .field("clock", &self.clock)
+            .finish()
+    }
+}
+
+impl<T: Table> MockTable<T> {
+    /// Construct a mock table with the given name
+    pub fn new(table_name: impl Into<String>) -> Self {
+        let config = aws_sdk_dynamodb::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .build();
+
+        Self {
+            table_name: table_name.into(),
+            client: MockClient::new(),
+            sdk_client: aws_sdk_dynamodb::Client::from_conf(config),
+            clock: None,
+            table: None,
+        }
+    }
+
+    /// Forwards the table's hooks to the given instance of the table
+    ///
+    /// Without an instance, the mock table uses the default hooks: it has
+    /// no key scope or item cache, records nothing after writes, on tracing
+    /// spans, on partition accesses, or on skipped items, and uses the system
+    /// clock.
+    pub fn with_table(mut self, table: T) -> Self {
+        self.table = Some(table);
+        self
+    }
+
+    /// Uses the given clock, such as a [`FixedClock`][crate::clock::FixedClock],
+    /// in place of the system clock or the clock of the table
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Box::new(clock));
+        self
+    }
+
+    /// The mock client answering operations on this table
+    #[inline]
+    pub fn mock(&self) -> &MockClient {
+        &self.client
+    }
+}
+
+impl<T> std::ops::Deref for MockTable<T> {
+    type Target = MockClient;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.client
+    }
+}
+
+impl<T: Table> Table for MockTable<T> {
+    const ENTITY_TYPE_ATTRIBUTE: &'static str = T::ENTITY_TYPE_ATTRIBUTE;
+    const ENTITY_DISCRIMINATOR: EntityDiscriminator = T::ENTITY_DISCRIMINATOR;
+    const SEMANTIC_CONVENTIONS: SemanticConventions = T::SEMANTIC_CONVENTIONS;
+    const TTL_ATTRIBUTE: Option<&'static str> = T::TTL_ATTRIBUTE;
+    const EXCLUDE_EXPIRED_ITEMS: bool = T::EXCLUDE_EXPIRED_ITEMS;
+    const SCHEMA_VERSION_ATTRIBUTE: Option<&'static str> = T::SCHEMA_VERSION_ATTRIBUTE;
+    const STREAM_VIEW: Option<aws_sdk_dynamodb::types::StreamViewType> = T::STREAM_VIEW;
+
+    type PrimaryKey = T::PrimaryKey;
+    type IndexKeys = T::IndexKeys;
+
+    #[inline]
+    fn table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    /// An SDK client that is not connected to any endpoint
+    ///
+    /// Operations are sent through the [mock client][MockTable::mock()].
+    #[inline]
+    fn client(&self) -> &aws_sdk_dynamodb::Client {
+        &self.sdk_client
+    }
+
+    #[inline]
+    fn dynamo_client(&self) -> &dyn DynamoClient {
+        &self.client
+    }
+
+    #[inline]
+    fn key_scope(&self) -> Option<&KeyScope> {
+        self.table.as_ref()?.key_scope()
+    }
+
+    #[inline]
+    fn item_cache(&self) -> Option<&ItemCache> {
+        self.table.as_ref()?.item_cache()
+    }
+
+    #[inline]
+    fn after_write(&self, key: &Item) {
+        if let Some(table) = &self.table {
+            table.after_write(key);
+        }
+    }
+
+    #[inline]
+    fn clock(&self) -> &dyn Clock {
+        match (&self.clock, &self.table) {
+            (Some(clock), _) => clock.as_ref(),
+            (None, Some(table)) => table.clock(),
+            (None, None) => &SystemClock,
+        }
+    }
+
+    #[inline]
+    fn record_span_attributes(&self, span: &tracing::Span, operation: &'static str) {
+        if let Some(table) = &self.table {
+            table.record_span_attributes(span, operation);
+        }
+    }
+
+    #[inline]
+    fn record_partition_access(&self, access: &PartitionAccess<'_>) {
+        if let Some(table) = &self.table {
+            table.record_partition_access(access);
+        }
+    }
+
+    #[inline]
+    fn record_skipped_items(&self, operation: &'static str, skipped: &SkippedItems) {
+        if let Some(table) = &self.table {
+            table.record_skipped_items(operation, skipped);
+        }
+    }
+
+    #[inline]
+    fn deserialize_entity_type(
+        attr: &AttributeValue,
+    ) -> Result<&EntityTypeNameRef, MalformedEntityTypeError> {
+        T::deserialize_entity_type(attr)
+    }
+
+    #[inline]
+    fn serialize_entity_type(entity_type: &EntityTypeNameRef) -> AttributeValue {
+        T::serialize_entity_type(entity_type)
+    }
+
+    #[inline]
+    fn redact_key(key: &Item) -> String {
+        T::redact_key(key)
+    }
+}
+
+impl<T: WritableTable> WritableTable for MockTable<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calls_are_matched_in_order_until_exhausted() {
+        let client = MockClient::new();
+        client
+            .expect::<ops::GetItem>(|e| {
+                e.returning_item(Some(Item::from([(
+                    "n".to_string(),
+                    AttributeValue::N("1".into()),
+                )])))
+                .times(1)
+            })
+            .expect::<ops::GetItem>(|e| e.returning_item(None));
+
+        let first = client.call::<ops::GetItem>(GetItemInput::builder().build().unwrap());
+        let second = client.call::<ops::GetItem>(GetItemInput::builder().build().unwrap());
+
+        assert!(first.unwrap().item.is_some());
+        assert!(second.unwrap().item.is_none());
+        assert_eq!(client.calls(Operation::GetItem), 2);
+        assert_eq!(client.inputs::<ops::GetItem>().len(), 2);
+        client.verify();
+    }
+
+    #[test]
+    fn forwards_the_hooks_of_the_table() {
+        use crate::tests::hooks::{assert_forwards_hooks, HookedTable};
+
+        let table = MockTable::new("mock").with_table(HookedTable::new());
+        assert_eq!(table.table_name(), "mock");
+        assert_forwards_hooks(&table, table.table.as_ref().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected Query call")]
+    fn unmatched_calls_panic() {
+        let client = MockClient::new();
+        client.expect::<ops::Query>(|e| e.on_index("GSI1"));
+
+        let _ = client.call::<ops::Query>(QueryInput::builder().build().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "unmet expectations")]
+    fn uncalled_expectations_are_unmet() {
+        let client = MockClient::new();
+        client.expect::<ops::PutItem>(|e| e.times(2));
+
+        let _ = client.call::<ops::PutItem>(PutItemInput::builder().build().unwrap());
+        client.verify();
+    }
+}
@@ -1,20 +1,30 @@
 //! Models for interacting with DynamoDB
 
-use std::{collections::HashMap, fmt, marker::PhantomData};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    marker::PhantomData,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
 
 use aws_sdk_dynamodb::{
     error::SdkError,
     operation::{
-        batch_get_item::{BatchGetItemError, BatchGetItemOutput},
-        batch_write_item::{BatchWriteItemError, BatchWriteItemOutput},
-        delete_item::{DeleteItemError, DeleteItemOutput},
-        get_item::{GetItemError, GetItemOutput},
-        put_item::{PutItemError, PutItemOutput},
-        query::{QueryError, QueryOutput},
-        scan::{ScanError, ScanOutput},
-        transact_get_items::{TransactGetItemsError, TransactGetItemsOutput},
-        transact_write_items::{TransactWriteItemsError, TransactWriteItemsOutput},
-        update_item::{UpdateItemError, UpdateItemOutput},
+        batch_get_item::{BatchGetItemError, BatchGetItemInput, BatchGetItemOutput},
+        batch_write_item::{BatchWriteItemError, BatchWriteItemInput, BatchWriteItemOutput},
+        delete_item::{DeleteItemError, DeleteItemInput, DeleteItemOutput},
+        get_item::{GetItemError, GetItemInput, GetItemOutput},
+        put_item::{PutItemError, PutItemInput, PutItemOutput},
+        query::{QueryError, QueryInput, QueryOutput},
+        scan::{ScanError, ScanInput, ScanOutput},
+        transact_get_items::{
+            TransactGetItemsError, TransactGetItemsInput, TransactGetItemsOutput,
+        },
+        transact_write_items::{
+            TransactWriteItemsError, TransactWriteItemsInput, TransactWriteItemsOutput,
+        },
+        update_item::{UpdateItemError, UpdateItemInput, UpdateItemOutput},
     },
     types::{
         AttributeValue, ConsumedCapacity, KeysAndAttributes, ReturnConsumedCapacity, ReturnValue,
@@ -23,7 +33,17 @@ use aws_sdk_dynamodb::{
 };
 use tracing::{field, Instrument};
 
-use crate::{expr, keys, Item, Table};
+use crate::{
+    client::{self, DynamoClient},
+    error::ErrorContext,
+    expr,
+    idempotency::TokenHasher,
+    instrumentation::{self, operation_span},
+    keys,
+    scope::ScopedClient,
+    stream::ItemStream,
+    Aggregate, EntityTypeNameRef, Error, Item, ProjectionSet, Table, WritableTable,
+};
 
 /// A builder for get item operations
 #[derive(Debug, Clone)]
@@ -31,6 +51,9 @@ use crate::{expr, keys, Item, Table};
 pub struct Get {
     projection: Option<expr::StaticProjection>,
     key: Item,
+    timeout: Option<Duration>,
+    customization: Option<client::Customization>,
+    entity_type: Option<&'static EntityTypeNameRef>,
 }
 
 impl Get {
@@ -40,6 +63,9 @@ impl Get {
         Self {
             key,
             projection: None,
+            timeout: None,
+            customization: None,
+            entity_type: None,
         }
     }
 
@@ -50,6 +76,31 @@ impl Get {
         self
     }
 
+    /// Limit the time allowed for the request to complete
+    ///
+    /// If the timeout elapses before a response is received, the request is
+    /// canceled and a timeout error is returned, which is classified as
+    /// [`ErrorKind::Timeout`][crate::ErrorKind::Timeout].
+    ///
+    /// The timeout is not applied when the operation is included in a
+    /// transaction or a batch.
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Customize how the request is sent by the standard SDK client
+    ///
+    /// See [`Customization`][client::Customization] for what can be
+    /// customized. The customization is not applied when the operation is
+    /// included in a transaction or a batch.
+    #[inline]
+    pub fn with_customization(mut self, customization: client::Customization) -> Self {
+        self.customization = Some(customization);
+        self
+    }
+
     /// Executes a single item get request against the given table
     ///
     /// This function executes the operation with eventual consistency
@@ -80,6 +131,66 @@ impl Get {
         .await
     }
 
+    /// Executes a single item get request against the given table,
+    /// parsing the item as whichever entity type in the projection set is
+    /// stored at the key
+    ///
+    /// This is useful when different entity types may share the same key
+    /// shape, such as in adjacency list designs. If no projection has been
+    /// specified, the projection expression for the projection set is used.
+    /// Returns `None` if no item exists at the key or if the item is of an
+    /// entity type not in the projection set.
+    ///
+    /// This function executes the operation with eventual consistency
+    pub async fn execute_any<P, T>(self, table: &T) -> Result<Option<P>, crate::Error>
+    where
+        P: ProjectionSet,
+        T: Table,
+    {
+        self.any_one::<P>(None).execute_any(table).await
+    }
+
+    /// Executes a single item get request against the given table with a
+    /// specific read consistency, parsing the item as whichever entity type
+    /// in the projection set is stored at the key
+    ///
+    /// See [`execute_any()`][Self::execute_any()] for more details.
+    pub async fn execute_any_with_consistency<P, T>(
+        self,
+        table: &T,
+        consistent_read: bool,
+    ) -> Result<Option<P>, crate::Error>
+    where
+        P: ProjectionSet,
+        T: Table,
+    {
+        self.any_one::<P>(Some(consistent_read))
+            .execute_any(table)
+            .await
+    }
+
+    fn any_one<P: ProjectionSet>(mut self, consistent_read: Option<bool>) -> GetOne {
+        if self.projection.is_none() {
+            self.projection = P::projection_expression();
+        }
+
+        GetOne {
+            inner: self,
+            consistent_read,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn key(&self) -> &Item {
+        &self.key
+    }
+
+    #[inline]
+    pub(crate) fn for_entity_type(mut self, entity_type: &'static EntityTypeNameRef) -> Self {
+        self.entity_type = Some(entity_type);
+        self
+    }
+
     #[inline]
     pub(crate) fn transact(self) -> GetTransact {
         GetTransact { inner: self }
@@ -94,7 +205,32 @@ struct GetOne {
 }
 
 impl GetOne {
+    async fn execute_any<P, T>(self, table: &T) -> Result<Option<P>, crate::Error>
+    where
+        P: ProjectionSet,
+        T: Table,
+    {
+        let output = self.execute(table).await?;
+        match output.item {
+            Some(item) => P::try_from_item(item),
+            None => Ok(None),
+        }
+    }
+
     async fn execute<T: Table>(self, table: &T) -> Result<GetItemOutput, SdkError<GetItemError>> {
+        let cache = table
+            .item_cache()
+            .filter(|_| self.inner.projection.is_none());
+        if let Some(cache) = cache.filter(|_| self.consistent_read != Some(true)) {
+            if let Some(item) = cache.get(table, &self.inner.key) {
+                tracing::debug!(key = %T::redact_key(&self.inner.key), "item served from cache");
+                let mut item = item;
+                discard_expired(table, &mut item);
+                return Ok(GetItemOutput::builder().set_item(item).build());
+            }
+        }
+        let cached_key = cache.map(|cache| (cache.generation(), self.inner.key.clone()));
+
         let (projection_expression, projection_names) = if let Some(e) = self.inner.projection {
             (
                 Some(e.expression.to_owned()),
@@ -107,22 +243,20 @@ impl GetOne {
             (None, Default::default())
         };
 
-        let span = tracing::info_span!(
-            "DynamoDB.GetItem",
-            span.kind = "client",
-            db.system = "dynamodb",
-            db.operation = "GetItem",
-            db.name = table.table_name(),
-            aws.dynamodb.key = ?self.inner.key,
+        let span = operation_span!(
+            table,
+            "GetItem",
+            aws.dynamodb.key = %T::redact_key(&self.inner.key),
             aws.dynamodb.projection = projection_expression,
-            aws.dynamodb.expression_attribute_names = ?projection_names,
+            aws.dynamodb.expression_attribute_names = ?instrumentation::sorted(&projection_names),
             aws.dynamodb.consistent_read = self.consistent_read,
             aws.dynamodb.consumed_read_capacity = field::Empty,
         );
+        instrumentation::record_projection(&span, self.inner.projection.as_ref());
+        instrumentation::record_entity_types(&span, self.inner.entity_type);
+        instrumentation::record_partition_access(table, "GetItem", &self.inner.key);
 
-        let result = table
-            .client()
-            .get_item()
+        let input = GetItemInput::builder()
             .set_key((!self.inner.key.is_empty()).then_some(self.inner.key))
             .set_projection_expression(projection_expression)
             .set_expression_attribute_names(
@@ -131,18 +265,136 @@ impl GetOne {
             .set_consistent_read(self.consistent_read)
             .table_name(table.table_name())
             .return_consumed_capacity(ReturnConsumedCapacity::Total)
-            .send()
-            .instrument(span.clone())
-            .await;
+            .build();
+        let scoped = ScopedClient::new(table);
+        let mut result = client::timeout(
+            &span,
+            self.inner.timeout,
+            client::Customization::scope(
+                self.inner.customization.clone(),
+                client::send(input, |input| scoped.get_item(input)),
+            ),
+        )
+        .instrument(span.clone())
+        .await;
 
-        if let Ok(output) = &result {
+        instrumentation::record_outcome(&span, &result);
+
+        if let Ok(output) = &mut result {
             record_consumed_read_capacity(&span, output.consumed_capacity.as_ref());
+            if let (Some(cache), Some((generation, key))) = (cache, cached_key) {
+                cache.insert(table, &key, output.item.clone(), generation);
+            }
+            discard_expired(table, &mut output.item);
         }
 
         result
     }
 }
 
+/// A builder for operations that check whether an item exists
+///
+/// The item is read with a projection of only its partition key, so the
+/// rest of the item is neither transferred nor deserialized.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Exists {
+    key: Item,
+    timeout: Option<Duration>,
+    customization: Option<client::Customization>,
+    entity_type: Option<&'static EntityTypeNameRef>,
+}
+
+impl Exists {
+    /// Prepare an existence check for the item with the given key
+    #[inline]
+    pub fn new(key: Item) -> Self {
+        Self {
+            key,
+            timeout: None,
+            customization: None,
+            entity_type: None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn for_entity_type(mut self, entity_type: &'static EntityTypeNameRef) -> Self {
+        self.entity_type = Some(entity_type);
+        self
+    }
+
+    /// Limit the time allowed for the request to complete
+    ///
+    /// If the timeout elapses before a response is received, the request is
+    /// canceled and a timeout error is returned, which is classified as
+    /// [`ErrorKind::Timeout`][crate::ErrorKind::Timeout].
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Customize how the request is sent by the standard SDK client
+    ///
+    /// See [`Customization`][client::Customization] for what can be
+    /// customized. The customization is not applied when the operation is
+    /// included in a transaction or a batch.
+    #[inline]
+    pub fn with_customization(mut self, customization: client::Customization) -> Self {
+        self.customization = Some(customization);
+        self
+    }
+
+    /// Checks whether an item exists at the key in the given table
+    ///
+    /// This function executes the operation with eventual consistency
+    pub async fn execute<T: Table>(self, table: &T) -> Result<bool, SdkError<GetItemError>> {
+        self.execute_inner(table, None).await
+    }
+
+    /// Checks whether an item exists at the key in the given table with a
+    /// specific read consistency
+    pub async fn execute_with_consistency<T: Table>(
+        self,
+        table: &T,
+        consistent_read: bool,
+    ) -> Result<bool, SdkError<GetItemError>> {
+        self.execute_inner(table, Some(consistent_read)).await
+    }
+
+    async fn execute_inner<T: Table>(
+        self,
+        table: &T,
+        consistent_read: Option<bool>,
+    ) -> Result<bool, SdkError<GetItemError>> {
+        let output = GetOne {
+            inner: Get {
+                key: self.key,
+                projection: Some(<T::PrimaryKey as KeysOnly>::PROJECTION),
+                timeout: self.timeout,
+                customization: self.customization,
+                entity_type: self.entity_type,
+            },
+            consistent_read,
+        }
+        .execute(table)
+        .await?;
+        Ok(output.item.is_some())
+    }
+}
+
+/// A projection of only the partition key, which every item in the table has
+trait KeysOnly {
+    const PROJECTION: expr::StaticProjection;
+}
+
+impl<P: keys::PrimaryKey> KeysOnly for P {
+    const PROJECTION: expr::StaticProjection = expr::StaticProjection {
+        expression: "#prj_key",
+        names: &[("#prj_key", P::PRIMARY_KEY_DEFINITION.hash_key)],
+    };
+}
+
 /// A get operation for use in a transaction
 #[derive(Debug, Clone)]
 #[must_use]
@@ -182,13 +434,44 @@ impl GetTransact {
 #[must_use]
 pub struct Put {
     item: Item,
+    timeout: Option<Duration>,
+    customization: Option<client::Customization>,
 }
 
 impl Put {
     /// Prepare a put item operation
     #[inline]
     pub fn new(item: Item) -> Self {
-        Self { item }
+        Self {
+            item,
+            timeout: None,
+            customization: None,
+        }
+    }
+
+    /// Limit the time allowed for the request to complete
+    ///
+    /// If the timeout elapses before a response is received, the request is
+    /// canceled and a timeout error is returned, which is classified as
+    /// [`ErrorKind::Timeout`][crate::ErrorKind::Timeout].
+    ///
+    /// The timeout is not applied when the operation is included in a
+    /// transaction or a batch.
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Customize how the request is sent by the standard SDK client
+    ///
+    /// See [`Customization`][client::Customization] for what can be
+    /// customized. The customization is not applied when the operation is
+    /// included in a transaction or a batch.
+    #[inline]
+    pub fn with_customization(mut self, customization: client::Customization) -> Self {
+        self.customization = Some(customization);
+        self
     }
 
     /// Apply a typed conditional expression to the operation
@@ -200,13 +483,15 @@ impl Put {
         ConditionalPut {
             item: self.item,
             condition: Some(condition),
+            timeout: self.timeout,
+            customization: self.customization,
         }
     }
 
     /// Execute a single item put operation against the given table
     ///
     /// This method will not return any old or new values.
-    pub async fn execute<T: Table>(
+    pub async fn execute<T: WritableTable>(
         self,
         table: &T,
     ) -> Result<PutItemOutput, SdkError<PutItemError>> {
@@ -214,8 +499,11 @@ impl Put {
             inner: ConditionalPut {
                 item: self.item,
                 condition: None,
+                timeout: self.timeout,
+                customization: self.customization,
             },
             return_value: None,
+            return_values_on_condition_check_failure: None,
         }
         .execute(table)
         .await
@@ -223,7 +511,7 @@ impl Put {
 
     /// Execute a single item put operation against the given table
     /// with some returned values
-    pub async fn execute_with_return<T: Table>(
+    pub async fn execute_with_return<T: WritableTable>(
         self,
         table: &T,
         return_value: ReturnValue,
@@ -232,8 +520,11 @@ impl Put {
             inner: ConditionalPut {
                 item: self.item,
                 condition: None,
+                timeout: self.timeout,
+                customization: self.customization,
             },
             return_value: Some(return_value),
+            return_values_on_condition_check_failure: None,
         }
         .execute(table)
         .await
@@ -246,6 +537,8 @@ impl Put {
             inner: ConditionalPut {
                 item: self.item,
                 condition: None,
+                timeout: self.timeout,
+                customization: self.customization,
             },
             return_values_on_condition_check_failure: None,
         }
@@ -259,6 +552,8 @@ impl Put {
             inner: ConditionalPut {
                 item: self.item,
                 condition: None,
+                timeout: self.timeout,
+                customization: self.customization,
             },
             return_values_on_condition_check_failure: Some(
                 ReturnValuesOnConditionCheckFailure::AllOld,
@@ -273,19 +568,47 @@ impl Put {
 pub struct ConditionalPut {
     item: Item,
     condition: Option<expr::Condition>,
+    timeout: Option<Duration>,
+    customization: Option<client::Customization>,
 }
 
 impl ConditionalPut {
+    /// Limit the time allowed for the request to complete
+    ///
+    /// If the timeout elapses before a response is received, the request is
+    /// canceled and a timeout error is returned, which is classified as
+    /// [`ErrorKind::Timeout`][crate::ErrorKind::Timeout].
+    ///
+    /// The timeout is not applied when the operation is included in a
+    /// transaction.
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Customize how the request is sent by the standard SDK client
+    ///
+    /// See [`Customization`][client::Customization] for what can be
+    /// customized. The customization is not applied when the operation is
+    /// included in a transaction or a batch.
+    #[inline]
+    pub fn with_customization(mut self, customization: client::Customization) -> Self {
+        self.customization = Some(customization);
+        self
+    }
+
     /// Execute a single item put operation against the given table
     ///
     /// This method will not return any old or new values.
-    pub async fn execute<T: Table>(
+    pub async fn execute<T: WritableTable>(
         self,
         table: &T,
     ) -> Result<PutItemOutput, SdkError<PutItemError>> {
         PutOne {
             inner: self,
             return_value: None,
+            return_values_on_condition_check_failure: None,
         }
         .execute(table)
         .await
@@ -293,7 +616,7 @@ impl ConditionalPut {
 
     /// Execute a single item put operation against the given table
     /// with some returned values
-    pub async fn execute_with_return<T: Table>(
+    pub async fn execute_with_return<T: WritableTable>(
         self,
         table: &T,
         return_value: ReturnValue,
@@ -301,6 +624,7 @@ impl ConditionalPut {
         PutOne {
             inner: self,
             return_value: Some(return_value),
+            return_values_on_condition_check_failure: None,
         }
         .execute(table)
         .await
@@ -328,32 +652,292 @@ impl ConditionalPut {
     }
 }
 
+/// A put operation that returns the existing entity if one already exists
+/// with the same key
+///
+/// Created by [`EntityExt::create_or_get`][crate::EntityExt::create_or_get].
+#[must_use]
+pub struct CreateOrGet<E> {
+    entity: E,
+}
+
+impl<E: fmt::Debug> fmt::Debug for CreateOrGet<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CreateOrGet")
+            .field("entity", &self.entity)
+            .finish()
+    }
+}
+
+impl<E> CreateOrGet<E>
+where
+    E: crate::Entity + serde::Serialize + serde::de::DeserializeOwned,
+{
+    #[inline]
+    pub(crate) fn new(entity: E) -> Self {
+        Self { entity }
+    }
+
+    /// Execute the operation against the given table
+    ///
+    /// If the entity is created, it is returned unchanged. Otherwise, the
+    /// existing entity is returned from the failed conditional check. If the
+    /// existing item is of a different entity type, an error is returned
+    /// rather than attempting to parse it as this entity.
+    pub async fn execute<T: WritableTable>(self, table: &T) -> Result<Created<E>, crate::Error> {
+        let hash_key =
+            <<E::Table as Table>::PrimaryKey as keys::PrimaryKey>::PRIMARY_KEY_DEFINITION.hash_key;
+        let condition = expr::Condition::new("attribute_not_exists(#PK)").name("#PK", hash_key);
+        let item = crate::entity_to_item(&self.entity);
+        let context = ErrorContext::new(table, "PutItem")
+            .with_entity_type(E::ENTITY_TYPE)
+            .with_key::<T>(&primary_key_of::<T>(&item));
+
+        let result = PutOne {
+            inner: ConditionalPut {
+                item,
+                condition: Some(condition),
+                timeout: None,
+                customization: None,
+            },
+            return_value: None,
+            return_values_on_condition_check_failure: Some(
+                ReturnValuesOnConditionCheckFailure::AllOld,
+            ),
+        }
+        .execute(table)
+        .await;
+
+        let error = match result {
+            Ok(_) => return Ok(Created::New(self.entity)),
+            Err(error) => error,
+        };
+
+        let existing = match error.as_service_error() {
+            Some(PutItemError::ConditionalCheckFailedException(e)) => e.item.clone(),
+            _ => None,
+        };
+
+        let Some(item) = existing else {
+            return Err(crate::Error::from(error).with_context(context));
+        };
+
+        let entity_type = crate::__private::get_table_entity_type::<E::Table>(&item)
+            .map_err(|error| error.with_context(context.clone()))?;
+        if entity_type != E::ENTITY_TYPE {
+            let error = crate::error::UnexpectedEntityTypeError {
+                expected: E::ENTITY_TYPE,
+                found: entity_type.to_string(),
+            };
+            return Err(crate::Error::from(error).with_context(context));
+        }
+
+        crate::ProjectionExt::from_item(item)
+            .map(Created::Existing)
+            .map_err(|error| error.with_context(context))
+    }
+}
+
+/// The outcome of a [`CreateOrGet`] operation
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Created<E> {
+    /// The entity was created
+    New(E),
+
+    /// An entity already existed with the same key, and was left unchanged
+    Existing(E),
+}
+
+impl<E> Created<E> {
+    /// Returns true if the entity was created
+    #[inline]
+    pub fn is_new(&self) -> bool {
+        matches!(self, Self::New(_))
+    }
+
+    /// Returns the created or existing entity
+    #[inline]
+    pub fn into_inner(self) -> E {
+        match self {
+            Self::New(entity) | Self::Existing(entity) => entity,
+        }
+    }
+}
+
+/// An optimistic read-modify-write of an entity, retried when the entity
+/// changes between the read and the write
+///
+/// Created by [`EntityExt::read_modify_write`][crate::EntityExt::read_modify_write].
+#[must_use]
+pub struct ReadModifyWrite<E, F> {
+    key: Item,
+    modify: F,
+    max_attempts: u32,
+    entity: PhantomData<fn() -> E>,
+}
+
+impl<E, F> fmt::Debug for ReadModifyWrite<E, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ReadModifyWrite")
+            .field("key", &self.key)
+            .field("max_attempts", &self.max_attempts)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<E, F> ReadModifyWrite<E, F>
+where
+    E: crate::Entity + serde::Serialize + serde::de::DeserializeOwned,
+    F: FnMut(E) -> E,
+{
+    #[inline]
+    pub(crate) fn new(key: Item, modify: F) -> Self {
+        Self {
+            key,
+            modify,
+            max_attempts: 3,
+            entity: PhantomData,
+        }
+    }
+
+    /// Sets the number of attempts made before failing, defaulting to 3
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Execute the operation against the given table
+    ///
+    /// Each attempt reads the entity with strong consistency, applies the
+    /// modification, and writes the attributes that changed on the condition
+    /// that none of the attributes of the entity have changed since it was
+    /// read. Attributes that are not part of the entity, such as those
+    /// added to the item by other writers, are left in place. If the
+    /// condition fails, the operation is retried from the read after an
+    /// exponential backoff with jitter. Because the modification may be
+    /// applied more than once, it should not have side effects.
+    ///
+    /// The modification must not change the primary key of the entity.
+    ///
+    /// Returns `None` if no entity exists with the key. If the condition
+    /// fails on every attempt, the error from the last attempt is returned.
+    pub async fn execute<T: WritableTable>(mut self, table: &T) -> Result<Option<E>, crate::Error> {
+        let context = |operation| {
+            ErrorContext::new(table, operation)
+                .with_entity_type(E::ENTITY_TYPE)
+                .with_key::<T>(&self.key)
+        };
+
+        let mut attempt = 1;
+        loop {
+            let output = Get::new(self.key.clone())
+                .execute_with_consistency(table, true)
+                .await
+                .map_err(|error| crate::Error::from(error).with_context(context("GetItem")))?;
+            let Some(previous) = output.item else {
+                return Ok(None);
+            };
+
+            let condition = crate::unchanged_condition::<E::Table>(
+                previous
+                    .iter()
+                    .map(|(attr, value)| (attr.as_str(), Some(value))),
+            );
+            let entity = crate::ProjectionExt::from_item(previous)
+                .map_err(|error| error.with_context(context("GetItem")))?;
+            // Compare the entity as serialized, so that attributes of the item
+            // that are not part of the entity are not seen as removed
+            let before = crate::entity_to_item(&entity);
+            let entity = (self.modify)(entity);
+
+            let primary =
+                <<E::Table as Table>::PrimaryKey as keys::PrimaryKey>::PRIMARY_KEY_DEFINITION;
+            let diff = expr::ItemDiff::new(&before, &crate::entity_to_item(&entity))
+                .without(std::iter::once(primary.hash_key).chain(primary.range_key));
+            if diff.is_empty() {
+                return Ok(Some(entity));
+            }
+
+            let result = Update::new(self.key.clone())
+                .for_entity_type(E::ENTITY_TYPE)
+                .expression(diff.into_update())
+                .condition(condition)
+                .execute(table)
+                .await;
+
+            let error = match result {
+                Ok(_) => return Ok(Some(entity)),
+                Err(error) => error,
+            };
+
+            let conflict = matches!(
+                error.as_service_error(),
+                Some(UpdateItemError::ConditionalCheckFailedException(_))
+            );
+            if !conflict || attempt >= self.max_attempts {
+                return Err(crate::Error::from(error).with_context(context("UpdateItem")));
+            }
+
+            let delay = crate::clock::backoff(attempt);
+            tracing::debug!(
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                "entity changed during read-modify-write, retrying"
+            );
+            table.clock().sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 #[must_use]
 struct PutOne {
     inner: ConditionalPut,
     return_value: Option<ReturnValue>,
+    return_values_on_condition_check_failure: Option<ReturnValuesOnConditionCheckFailure>,
 }
 
 impl PutOne {
-    async fn execute<T: Table>(self, table: &T) -> Result<PutItemOutput, SdkError<PutItemError>> {
-        let span = tracing::info_span!(
-            "DynamoDB.PutItem",
-            span.kind = "client",
-            db.system = "dynamodb",
-            db.operation = "PutItem",
-            db.name = table.table_name(),
+    async fn execute<T: WritableTable>(
+        self,
+        table: &T,
+    ) -> Result<PutItemOutput, SdkError<PutItemError>> {
+        if let Some(condition) = &self.inner.condition {
+            condition.check().map_err(SdkError::construction_failure)?;
+        }
+
+        let span = operation_span!(
+            table,
+            "PutItem",
             aws.dynamodb.conditional_expression = field::Empty,
             aws.dynamodb.expression_attribute_names = field::Empty,
             aws.dynamodb.expression_attribute_values = field::Empty,
             aws.dynamodb.consumed_write_capacity = field::Empty,
         );
+        instrumentation::record_entity_types(
+            &span,
+            crate::__private::get_table_entity_type::<T>(&self.inner.item).ok(),
+        );
+
+        let key = written_key::<T>(&self.inner.item);
+        instrumentation::record_partition_access(table, "PutItem", &key);
+
+        // The replaced item is needed to tell `after_write` about the index
+        // partitions that the item is moved out of
+        let returns_replaced = self.return_value == Some(ReturnValue::AllOld);
+        let return_value = if <T::IndexKeys as keys::IndexKeys>::KEY_DEFINITIONS.is_empty() {
+            self.return_value
+        } else {
+            Some(ReturnValue::AllOld)
+        };
 
-        let mut query = table
-            .client()
-            .put_item()
+        let mut query = PutItemInput::builder()
             .set_item(Some(self.inner.item))
-            .set_return_values(self.return_value)
+            .set_return_values(return_value)
+            .set_return_values_on_condition_check_failure(
+                self.return_values_on_condition_check_failure,
+            )
             .table_name(table.table_name())
             .return_consumed_capacity(ReturnConsumedCapacity::Total);
 
@@ -363,7 +947,7 @@ impl PutOne {
                 let names: HashMap<_, _> = condition.names.into_iter().collect();
                 span.record(
                     "aws.dynamodb.expression_attribute_names",
-                    field::debug(&names),
+                    field::debug(instrumentation::sorted(&names)),
                 );
                 Some(names)
             } else {
@@ -374,7 +958,7 @@ impl PutOne {
                 let mut values: Item = condition.values.into_iter().collect();
                 span.record(
                     "aws.dynamodb.expression_attribute_values",
-                    field::debug(&values),
+                    field::debug(instrumentation::sorted(&values)),
                 );
 
                 values.extend(condition.sensitive_values);
@@ -390,10 +974,32 @@ impl PutOne {
                 .set_expression_attribute_values(values)
         }
 
-        let result = query.send().instrument(span.clone()).await;
+        let scoped = ScopedClient::new(table);
+        let mut result = client::timeout(
+            &span,
+            self.inner.timeout,
+            client::Customization::scope(
+                self.inner.customization.clone(),
+                client::send(query.build(), |input| scoped.put_item(input)),
+            ),
+        )
+        .instrument(span.clone())
+        .await;
 
-        if let Ok(output) = &result {
+        instrumentation::record_outcome(&span, &result);
+
+        invalidate_cached(table, [&key]);
+        if let Ok(output) = &mut result {
             record_consumed_write_capacity(&span, output.consumed_capacity.as_ref());
+            table.after_write(&key);
+
+            let replaced_key = output.attributes.as_ref().map(written_key::<T>);
+            if let Some(replaced_key) = replaced_key.filter(|replaced| replaced != &key) {
+                table.after_write(&replaced_key);
+            }
+            if !returns_replaced {
+                output.attributes = None;
+            }
         }
 
         result
@@ -447,13 +1053,23 @@ impl PutTransact {
 #[must_use]
 pub struct Update {
     key: Item,
+    entity_type: Option<&'static EntityTypeNameRef>,
 }
 
 impl Update {
     /// Prepare a new update item operation
     #[inline]
     pub fn new(key: Item) -> Self {
-        Self { key }
+        Self {
+            key,
+            entity_type: None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn for_entity_type(mut self, entity_type: &'static EntityTypeNameRef) -> Self {
+        self.entity_type = Some(entity_type);
+        self
     }
 
     /// The typed update expression to be evaluated
@@ -462,6 +1078,9 @@ impl Update {
         UpdateWithExpr {
             key: self.key,
             update,
+            timeout: None,
+            customization: None,
+            entity_type: self.entity_type,
         }
     }
 }
@@ -472,9 +1091,37 @@ impl Update {
 pub struct UpdateWithExpr {
     key: Item,
     update: expr::Update,
+    timeout: Option<Duration>,
+    customization: Option<client::Customization>,
+    entity_type: Option<&'static EntityTypeNameRef>,
 }
 
 impl UpdateWithExpr {
+    /// Limit the time allowed for the request to complete
+    ///
+    /// If the timeout elapses before a response is received, the request is
+    /// canceled and a timeout error is returned, which is classified as
+    /// [`ErrorKind::Timeout`][crate::ErrorKind::Timeout].
+    ///
+    /// The timeout is not applied when the operation is included in a
+    /// transaction.
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Customize how the request is sent by the standard SDK client
+    ///
+    /// See [`Customization`][client::Customization] for what can be
+    /// customized. The customization is not applied when the operation is
+    /// included in a transaction or a batch.
+    #[inline]
+    pub fn with_customization(mut self, customization: client::Customization) -> Self {
+        self.customization = Some(customization);
+        self
+    }
+
     /// Apply a typed conditional expression to the operation
     ///
     /// If the condition evaluates to false, then the operation will fail, but
@@ -485,13 +1132,16 @@ impl UpdateWithExpr {
             key: self.key,
             update: self.update,
             condition: Some(condition),
+            timeout: self.timeout,
+            customization: self.customization,
+            entity_type: self.entity_type,
         }
     }
 
     /// Execute a single item update operation against the given table
     ///
     /// This method will not return any old or new values.
-    pub async fn execute<T: Table>(
+    pub async fn execute<T: WritableTable>(
         self,
         table: &T,
     ) -> Result<UpdateItemOutput, SdkError<UpdateItemError>> {
@@ -500,6 +1150,9 @@ impl UpdateWithExpr {
                 key: self.key,
                 update: self.update,
                 condition: None,
+                timeout: self.timeout,
+                customization: self.customization,
+                entity_type: self.entity_type,
             },
             return_value: None,
         }
@@ -509,7 +1162,7 @@ impl UpdateWithExpr {
 
     /// Execute a single item update operation against the given table,
     /// returning the old and/or new values
-    pub async fn execute_with_return<T: Table>(
+    pub async fn execute_with_return<T: WritableTable>(
         self,
         table: &T,
         return_value: ReturnValue,
@@ -519,6 +1172,9 @@ impl UpdateWithExpr {
                 key: self.key,
                 update: self.update,
                 condition: None,
+                timeout: self.timeout,
+                customization: self.customization,
+                entity_type: self.entity_type,
             },
             return_value: Some(return_value),
         }
@@ -534,6 +1190,9 @@ impl UpdateWithExpr {
                 key: self.key,
                 update: self.update,
                 condition: None,
+                timeout: self.timeout,
+                customization: self.customization,
+                entity_type: self.entity_type,
             },
             return_values_on_condition_check_failure: None,
         }
@@ -548,6 +1207,9 @@ impl UpdateWithExpr {
                 key: self.key,
                 update: self.update,
                 condition: None,
+                timeout: self.timeout,
+                customization: self.customization,
+                entity_type: self.entity_type,
             },
             return_values_on_condition_check_failure: Some(
                 ReturnValuesOnConditionCheckFailure::AllOld,
@@ -563,13 +1225,41 @@ pub struct ConditionalUpdate {
     key: Item,
     update: expr::Update,
     condition: Option<expr::Condition>,
+    timeout: Option<Duration>,
+    customization: Option<client::Customization>,
+    entity_type: Option<&'static EntityTypeNameRef>,
 }
 
 impl ConditionalUpdate {
-    /// Execute a single item update operation against the given table
+    /// Limit the time allowed for the request to complete
     ///
-    /// This method will not return any old or new values.
-    pub async fn execute<T: Table>(
+    /// If the timeout elapses before a response is received, the request is
+    /// canceled and a timeout error is returned, which is classified as
+    /// [`ErrorKind::Timeout`][crate::ErrorKind::Timeout].
+    ///
+    /// The timeout is not applied when the operation is included in a
+    /// transaction.
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Customize how the request is sent by the standard SDK client
+    ///
+    /// See [`Customization`][client::Customization] for what can be
+    /// customized. The customization is not applied when the operation is
+    /// included in a transaction or a batch.
+    #[inline]
+    pub fn with_customization(mut self, customization: client::Customization) -> Self {
+        self.customization = Some(customization);
+        self
+    }
+
+    /// Execute a single item update operation against the given table
+    ///
+    /// This method will not return any old or new values.
+    pub async fn execute<T: WritableTable>(
         self,
         table: &T,
     ) -> Result<UpdateItemOutput, SdkError<UpdateItemError>> {
@@ -583,7 +1273,7 @@ impl ConditionalUpdate {
 
     /// Execute a single item update operation against the given table,
     /// returning the old and/or new values
-    pub async fn execute_with_return<T: Table>(
+    pub async fn execute_with_return<T: WritableTable>(
         self,
         table: &T,
         return_value: ReturnValue,
@@ -626,27 +1316,34 @@ struct UpdateOne {
 }
 
 impl UpdateOne {
-    async fn execute<T: Table>(
+    async fn execute<T: WritableTable>(
         self,
         table: &T,
     ) -> Result<UpdateItemOutput, SdkError<UpdateItemError>> {
-        let span = tracing::info_span!(
-            "DynamoDB.UpdateItem",
-            span.kind = "client",
-            db.system = "dynamodb",
-            db.operation = "UpdateItem",
-            db.name = table.table_name(),
-            aws.dynamodb.key = ?self.inner.key,
+        self.inner
+            .update
+            .check()
+            .map_err(SdkError::construction_failure)?;
+        if let Some(condition) = &self.inner.condition {
+            condition.check().map_err(SdkError::construction_failure)?;
+        }
+
+        let span = operation_span!(
+            table,
+            "UpdateItem",
+            aws.dynamodb.key = %T::redact_key(&self.inner.key),
             aws.dynamodb.update_expression = self.inner.update.expression,
             aws.dynamodb.conditional_expression = field::Empty,
             aws.dynamodb.expression_attribute_names = field::Empty,
             aws.dynamodb.expression_attribute_values = field::Empty,
             aws.dynamodb.consumed_write_capacity = field::Empty,
         );
+        instrumentation::record_entity_types(&span, self.inner.entity_type);
+        instrumentation::record_partition_access(table, "UpdateItem", &self.inner.key);
+
+        let key = self.inner.key.clone();
 
-        let mut query = table
-            .client()
-            .update_item()
+        let mut query = UpdateItemInput::builder()
             .set_key(Some(self.inner.key))
             .set_update_expression(Some(self.inner.update.expression))
             .set_return_values(self.return_value)
@@ -667,7 +1364,7 @@ impl UpdateOne {
             };
 
         let needs_names = !cnd_names.is_empty() || !self.inner.update.names.is_empty();
-        let names = needs_names.then(|| {
+        let names: Option<HashMap<String, String>> = needs_names.then(|| {
             cnd_names
                 .into_iter()
                 .chain(self.inner.update.names)
@@ -676,7 +1373,7 @@ impl UpdateOne {
 
         span.record(
             "aws.dynamodb.expression_attribute_names",
-            field::debug(&names),
+            field::debug(instrumentation::sorted(&names)),
         );
 
         let needs_values = !cnd_values.is_empty()
@@ -696,7 +1393,7 @@ impl UpdateOne {
 
             span.record(
                 "aws.dynamodb.expression_attribute_values",
-                field::debug(&vals),
+                field::debug(instrumentation::sorted(&vals)),
             );
 
             vals.extend(cnd_sensitive_values);
@@ -711,10 +1408,24 @@ impl UpdateOne {
             .set_expression_attribute_names(names)
             .set_expression_attribute_values(values);
 
-        let result = query.send().instrument(span.clone()).await;
+        let scoped = ScopedClient::new(table);
+        let result = client::timeout(
+            &span,
+            self.inner.timeout,
+            client::Customization::scope(
+                self.inner.customization.clone(),
+                client::send(query.build(), |input| scoped.update_item(input)),
+            ),
+        )
+        .instrument(span.clone())
+        .await;
+
+        instrumentation::record_outcome(&span, &result);
 
+        invalidate_cached(table, [&key]);
         if let Ok(output) = &result {
             record_consumed_write_capacity(&span, output.consumed_capacity.as_ref());
+            table.after_write(&key);
         }
 
         result
@@ -797,13 +1508,52 @@ impl UpdateTransact {
 #[must_use]
 pub struct Delete {
     key: Item,
+    timeout: Option<Duration>,
+    customization: Option<client::Customization>,
+    entity_type: Option<&'static EntityTypeNameRef>,
 }
 
 impl Delete {
     /// Prepare a new delete operation
     #[inline]
     pub fn new(key: Item) -> Self {
-        Self { key }
+        Self {
+            key,
+            timeout: None,
+            customization: None,
+            entity_type: None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn for_entity_type(mut self, entity_type: &'static EntityTypeNameRef) -> Self {
+        self.entity_type = Some(entity_type);
+        self
+    }
+
+    /// Limit the time allowed for the request to complete
+    ///
+    /// If the timeout elapses before a response is received, the request is
+    /// canceled and a timeout error is returned, which is classified as
+    /// [`ErrorKind::Timeout`][crate::ErrorKind::Timeout].
+    ///
+    /// The timeout is not applied when the operation is included in a
+    /// transaction or a batch.
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Customize how the request is sent by the standard SDK client
+    ///
+    /// See [`Customization`][client::Customization] for what can be
+    /// customized. The customization is not applied when the operation is
+    /// included in a transaction or a batch.
+    #[inline]
+    pub fn with_customization(mut self, customization: client::Customization) -> Self {
+        self.customization = Some(customization);
+        self
     }
 
     /// Apply a typed conditional expression to the operation
@@ -815,21 +1565,33 @@ impl Delete {
         ConditionalDelete {
             key: self.key,
             condition: Some(condition),
+            timeout: self.timeout,
+            customization: self.customization,
+            entity_type: self.entity_type,
+        }
+    }
+
+    /// Converts the operation into a conditional delete without a condition
+    #[inline]
+    pub(crate) fn unconditional(self) -> ConditionalDelete {
+        ConditionalDelete {
+            key: self.key,
+            condition: None,
+            timeout: self.timeout,
+            customization: self.customization,
+            entity_type: self.entity_type,
         }
     }
 
     /// Execute a single item delete operation against the given table
     ///
     /// This method will not return the old values.
-    pub async fn execute<T: Table>(
+    pub async fn execute<T: WritableTable>(
         self,
         table: &T,
     ) -> Result<DeleteItemOutput, SdkError<DeleteItemError>> {
         DeleteOne {
-            inner: ConditionalDelete {
-                key: self.key,
-                condition: None,
-            },
+            inner: self.unconditional(),
             return_value: None,
         }
         .execute(table)
@@ -838,7 +1600,7 @@ impl Delete {
 
     /// Execute a single item delete operation against the given table,
     /// returning the old values
-    pub async fn execute_with_return<T: Table>(
+    pub async fn execute_with_return<T: WritableTable>(
         self,
         table: &T,
     ) -> Result<DeleteItemOutput, SdkError<DeleteItemError>> {
@@ -846,6 +1608,9 @@ impl Delete {
             inner: ConditionalDelete {
                 key: self.key,
                 condition: None,
+                timeout: self.timeout,
+                customization: self.customization,
+                entity_type: self.entity_type,
             },
             return_value: Some(ReturnValue::AllOld),
         }
@@ -860,6 +1625,9 @@ impl Delete {
             inner: ConditionalDelete {
                 key: self.key,
                 condition: None,
+                timeout: self.timeout,
+                customization: self.customization,
+                entity_type: self.entity_type,
             },
             return_values_on_condition_check_failure: None,
         }
@@ -873,6 +1641,9 @@ impl Delete {
             inner: ConditionalDelete {
                 key: self.key,
                 condition: None,
+                timeout: self.timeout,
+                customization: self.customization,
+                entity_type: self.entity_type,
             },
             return_values_on_condition_check_failure: Some(
                 ReturnValuesOnConditionCheckFailure::AllOld,
@@ -887,13 +1658,41 @@ impl Delete {
 pub struct ConditionalDelete {
     condition: Option<expr::Condition>,
     key: Item,
+    timeout: Option<Duration>,
+    customization: Option<client::Customization>,
+    entity_type: Option<&'static EntityTypeNameRef>,
 }
 
 impl ConditionalDelete {
+    /// Limit the time allowed for the request to complete
+    ///
+    /// If the timeout elapses before a response is received, the request is
+    /// canceled and a timeout error is returned, which is classified as
+    /// [`ErrorKind::Timeout`][crate::ErrorKind::Timeout].
+    ///
+    /// The timeout is not applied when the operation is included in a
+    /// transaction.
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Customize how the request is sent by the standard SDK client
+    ///
+    /// See [`Customization`][client::Customization] for what can be
+    /// customized. The customization is not applied when the operation is
+    /// included in a transaction or a batch.
+    #[inline]
+    pub fn with_customization(mut self, customization: client::Customization) -> Self {
+        self.customization = Some(customization);
+        self
+    }
+
     /// Execute a single item delete operation against the given table
     ///
     /// This method will not return the old values.
-    pub async fn execute<T: Table>(
+    pub async fn execute<T: WritableTable>(
         self,
         table: &T,
     ) -> Result<DeleteItemOutput, SdkError<DeleteItemError>> {
@@ -907,7 +1706,7 @@ impl ConditionalDelete {
 
     /// Execute a single item delete operation against the given table,
     /// returning the old values
-    pub async fn execute_with_return<T: Table>(
+    pub async fn execute_with_return<T: WritableTable>(
         self,
         table: &T,
     ) -> Result<DeleteItemOutput, SdkError<DeleteItemError>> {
@@ -949,26 +1748,29 @@ struct DeleteOne {
 }
 
 impl DeleteOne {
-    async fn execute<T: Table>(
+    async fn execute<T: WritableTable>(
         self,
         table: &T,
     ) -> Result<DeleteItemOutput, SdkError<DeleteItemError>> {
-        let span = tracing::info_span!(
-            "DynamoDB.DeleteItem",
-            span.kind = "client",
-            db.system = "dynamodb",
-            db.operation = "DeleteItem",
-            db.name = table.table_name(),
-            aws.dynamodb.key = ?self.inner.key,
+        if let Some(condition) = &self.inner.condition {
+            condition.check().map_err(SdkError::construction_failure)?;
+        }
+
+        let span = operation_span!(
+            table,
+            "DeleteItem",
+            aws.dynamodb.key = %T::redact_key(&self.inner.key),
             aws.dynamodb.conditional_expression = field::Empty,
             aws.dynamodb.expression_attribute_names = field::Empty,
             aws.dynamodb.expression_attribute_values = field::Empty,
             aws.dynamodb.consumed_write_capacity = field::Empty,
         );
+        instrumentation::record_entity_types(&span, self.inner.entity_type);
+        instrumentation::record_partition_access(table, "DeleteItem", &self.inner.key);
+
+        let key = self.inner.key.clone();
 
-        let mut query = table
-            .client()
-            .delete_item()
+        let mut query = DeleteItemInput::builder()
             .set_key(Some(self.inner.key))
             .set_return_values(self.return_value)
             .table_name(table.table_name())
@@ -980,7 +1782,7 @@ impl DeleteOne {
                 let names: HashMap<_, _> = condition.names.into_iter().collect();
                 span.record(
                     "aws.dynamodb.expression_attribute_names",
-                    field::debug(&names),
+                    field::debug(instrumentation::sorted(&names)),
                 );
                 Some(names)
             } else {
@@ -991,7 +1793,7 @@ impl DeleteOne {
                 let mut values: Item = condition.values.into_iter().collect();
                 span.record(
                     "aws.dynamodb.expression_attribute_values",
-                    field::debug(&values),
+                    field::debug(instrumentation::sorted(&values)),
                 );
 
                 values.extend(condition.sensitive_values);
@@ -1007,10 +1809,24 @@ impl DeleteOne {
                 .set_expression_attribute_values(values)
         }
 
-        let result = query.send().instrument(span.clone()).await;
+        let scoped = ScopedClient::new(table);
+        let result = client::timeout(
+            &span,
+            self.inner.timeout,
+            client::Customization::scope(
+                self.inner.customization.clone(),
+                client::send(query.build(), |input| scoped.delete_item(input)),
+            ),
+        )
+        .instrument(span.clone())
+        .await;
+
+        instrumentation::record_outcome(&span, &result);
 
+        invalidate_cached(table, [&key]);
         if let Ok(output) = &result {
             record_consumed_write_capacity(&span, output.consumed_capacity.as_ref());
+            table.after_write(&key);
         }
 
         result
@@ -1149,6 +1965,68 @@ pub enum TransactWriteItem {
 }
 
 impl TransactWriteItem {
+    fn hash_contents(&self, hasher: &mut TokenHasher) {
+        fn condition(hasher: &mut TokenHasher, condition: Option<&expr::Condition>) {
+            if let Some(c) = condition {
+                hasher.write_expression(&c.expression, &c.names, &c.values, &c.sensitive_values);
+            } else {
+                hasher.write_str("");
+            }
+        }
+
+        match self {
+            TransactWriteItem::PutItem(op) => {
+                hasher.write_str("Put");
+                hasher.write_item(&op.inner.item);
+                condition(hasher, op.inner.condition.as_ref());
+            }
+            TransactWriteItem::UpdateItem(op) => {
+                let update = &op.inner.update;
+                hasher.write_str("Update");
+                hasher.write_item(&op.inner.key);
+                hasher.write_expression(
+                    &update.expression,
+                    &update.names,
+                    &update.values,
+                    &update.sensitive_values,
+                );
+                condition(hasher, op.inner.condition.as_ref());
+            }
+            TransactWriteItem::DeleteItem(op) => {
+                hasher.write_str("Delete");
+                hasher.write_item(&op.inner.key);
+                condition(hasher, op.inner.condition.as_ref());
+            }
+            TransactWriteItem::ConditionCheck(op) => {
+                hasher.write_str("ConditionCheck");
+                hasher.write_item(&op.inner.key);
+                condition(hasher, Some(&op.inner.condition));
+            }
+        }
+    }
+
+    fn check_expressions(&self) -> Result<(), crate::error::InvalidExpressionError> {
+        let condition = match self {
+            TransactWriteItem::PutItem(op) => op.inner.condition.as_ref(),
+            TransactWriteItem::UpdateItem(op) => {
+                op.inner.update.check()?;
+                op.inner.condition.as_ref()
+            }
+            TransactWriteItem::DeleteItem(op) => op.inner.condition.as_ref(),
+            TransactWriteItem::ConditionCheck(op) => Some(&op.inner.condition),
+        };
+        condition.map_or(Ok(()), expr::Condition::check)
+    }
+
+    fn written_key<T: Table>(&self) -> Option<Item> {
+        match self {
+            TransactWriteItem::PutItem(op) => Some(primary_key_of::<T>(&op.inner.item)),
+            TransactWriteItem::UpdateItem(op) => Some(op.inner.key.clone()),
+            TransactWriteItem::DeleteItem(op) => Some(op.inner.key.clone()),
+            TransactWriteItem::ConditionCheck(_) => None,
+        }
+    }
+
     fn into_batch<T: Table>(self, table: &T) -> aws_sdk_dynamodb::types::TransactWriteItem {
         match self {
             TransactWriteItem::PutItem(op) => aws_sdk_dynamodb::types::TransactWriteItem::builder()
@@ -1255,6 +2133,8 @@ impl From<ConditionCheck> for TransactWriteItem {
 #[must_use]
 pub struct TransactGet {
     operations: Vec<GetTransact>,
+    timeout: Option<Duration>,
+    customization: Option<client::Customization>,
 }
 
 impl TransactGet {
@@ -1263,9 +2143,33 @@ impl TransactGet {
     pub fn new() -> Self {
         Self {
             operations: Vec::new(),
+            timeout: None,
+            customization: None,
         }
     }
 
+    /// Limit the time allowed for the request to complete
+    ///
+    /// If the timeout elapses before a response is received, the request is
+    /// canceled and a timeout error is returned, which is classified as
+    /// [`ErrorKind::Timeout`][crate::ErrorKind::Timeout].
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Customize how the request is sent by the standard SDK client
+    ///
+    /// See [`Customization`][client::Customization] for what can be
+    /// customized. The customization is not applied when the operation is
+    /// included in a transaction or a batch.
+    #[inline]
+    pub fn with_customization(mut self, customization: client::Customization) -> Self {
+        self.customization = Some(customization);
+        self
+    }
+
     /// Attach a get operation to the transaction
     #[inline]
     pub fn operation(mut self, op: Get) -> Self {
@@ -1273,48 +2177,65 @@ impl TransactGet {
         self
     }
 
+    /// Attach several get operations to the transaction
+    #[inline]
+    pub fn operations(mut self, ops: impl IntoIterator<Item = Get>) -> Self {
+        self.extend(ops);
+        self
+    }
+
     /// Execute the transaction
+    ///
+    /// A transaction without any operations is rejected before it is sent,
+    /// with an error for which
+    /// [`Error::is_empty_transaction()`][crate::Error::is_empty_transaction()]
+    /// returns true.
     pub async fn execute<T: Table>(
         self,
         table: &T,
     ) -> Result<TransactGetItemsOutput, SdkError<TransactGetItemsError>> {
-        let span = tracing::info_span!(
-            "DynamoDB.TransactGetItems",
-            span.kind = "client",
-            db.system = "dynamodb",
-            db.operation = "TransactGetItems",
-            db.name = table.table_name(),
-            aws.dynamodb.table_names = ?[&table.table_name()],
+        ensure_operations(&self.operations, "TransactGetItems")?;
+
+        let span = operation_span!(
+            table,
+            "TransactGetItems",
             aws.dynamodb.table_count = 1,
             aws.dynamodb.batch_operations = self.operations.len(),
             aws.dynamodb.consumed_read_capacity = field::Empty,
         );
 
-        let items = if self.operations.is_empty() {
-            None
-        } else {
-            Some(
-                self.operations
-                    .into_iter()
-                    .map(move |i| {
-                        aws_sdk_dynamodb::types::TransactGetItem::builder()
-                            .get(i.build(table))
-                            .build()
-                    })
-                    .collect(),
-            )
-        };
+        let items = self
+            .operations
+            .into_iter()
+            .map(move |i| {
+                aws_sdk_dynamodb::types::TransactGetItem::builder()
+                    .get(i.build(table))
+                    .build()
+            })
+            .collect();
 
-        let result = table
-            .client()
-            .transact_get_items()
+        let input = TransactGetItemsInput::builder()
             .return_consumed_capacity(ReturnConsumedCapacity::Total)
-            .set_transact_items(items)
-            .send()
-            .instrument(span.clone())
-            .await;
+            .set_transact_items(Some(items))
+            .build();
+        let scoped = ScopedClient::new(table);
+        let mut result = client::timeout(
+            &span,
+            self.timeout,
+            client::Customization::scope(
+                self.customization.clone(),
+                client::send(input, |input| scoped.transact_get_items(input)),
+            ),
+        )
+        .instrument(span.clone())
+        .await;
 
-        if let Ok(output) = &result {
+        instrumentation::record_outcome(&span, &result);
+
+        if let Ok(output) = &mut result {
+            for response in output.responses.iter_mut().flatten() {
+                discard_expired(table, &mut response.item);
+            }
             let capacity = output.consumed_capacity().iter().fold(
                 ConsumedCapacity::builder().build(),
                 |mut acc, next| {
@@ -1331,12 +2252,27 @@ impl TransactGet {
     }
 }
 
+impl Extend<Get> for TransactGet {
+    fn extend<I: IntoIterator<Item = Get>>(&mut self, ops: I) {
+        self.operations.extend(ops.into_iter().map(Get::transact));
+    }
+}
+
+impl FromIterator<Get> for TransactGet {
+    fn from_iter<I: IntoIterator<Item = Get>>(ops: I) -> Self {
+        Self::new().operations(ops)
+    }
+}
+
 /// A transactional write operation
 #[derive(Debug, Default, Clone)]
 #[must_use]
 pub struct TransactWrite {
     client_request_token: Option<String>,
     operations: Vec<TransactWriteItem>,
+    conflict_attempts: u32,
+    timeout: Option<Duration>,
+    customization: Option<client::Customization>,
 }
 
 impl TransactWrite {
@@ -1346,6 +2282,9 @@ impl TransactWrite {
         Self {
             client_request_token: None,
             operations: Vec::new(),
+            conflict_attempts: 1,
+            timeout: None,
+            customization: None,
         }
     }
 
@@ -1363,44 +2302,152 @@ impl TransactWrite {
         self
     }
 
+    /// Attach several write operations to the transaction
+    #[inline]
+    pub fn operations(
+        mut self,
+        ops: impl IntoIterator<Item = impl Into<TransactWriteItem>>,
+    ) -> Self {
+        self.extend(ops);
+        self
+    }
+
+    /// Retry the transaction with jittered exponential backoff, waited on
+    /// the table's [clock][crate::Table::clock()], when it conflicts with
+    /// another request, making at most `max_attempts` attempts
+    ///
+    /// A transaction is retried when it fails with a
+    /// `TransactionCanceledException` whose cancellation reasons include
+    /// `TransactionConflict` and no `ConditionalCheckFailed`, or with a
+    /// `TransactionInProgressException`. Each attempt sends the same
+    /// [client request token][Self::client_request_token()], if one is set,
+    /// so that a transaction applied by an earlier attempt is not applied
+    /// twice.
+    #[inline]
+    pub fn retry_conflicts(mut self, max_attempts: u32) -> Self {
+        self.conflict_attempts = max_attempts;
+        self
+    }
+
+    /// Limit the time allowed for the request to complete
+    ///
+    /// If the timeout elapses before a response is received, the request is
+    /// canceled and a timeout error is returned, which is classified as
+    /// [`ErrorKind::Timeout`][crate::ErrorKind::Timeout].
+    ///
+    /// When [retrying conflicts][Self::retry_conflicts()], the timeout
+    /// applies to each attempt.
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Customize how the request is sent by the standard SDK client
+    ///
+    /// See [`Customization`][client::Customization] for what can be
+    /// customized. The customization is not applied when the operation is
+    /// included in a transaction or a batch.
+    #[inline]
+    pub fn with_customization(mut self, customization: client::Customization) -> Self {
+        self.customization = Some(customization);
+        self
+    }
+
+    /// The number of operations in the transaction
+    #[inline]
+    pub(crate) fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    /// Derives a client request token from the idempotency key and the operations
+    pub(crate) fn idempotency_token(&self, key: &str) -> String {
+        let mut hasher = TokenHasher::new(key);
+        for op in &self.operations {
+            op.hash_contents(&mut hasher);
+        }
+        hasher.finish()
+    }
+
     /// Execute the write transaction
-    pub async fn execute<T: Table>(
+    ///
+    /// A transaction without any operations is rejected before it is sent,
+    /// with an error for which
+    /// [`Error::is_empty_transaction()`][crate::Error::is_empty_transaction()]
+    /// returns true.
+    pub async fn execute<T: WritableTable>(
         self,
         table: &T,
     ) -> Result<TransactWriteItemsOutput, SdkError<TransactWriteItemsError>> {
-        let span = tracing::info_span!(
-            "DynamoDB.TransactWriteItems",
-            span.kind = "client",
-            db.system = "dynamodb",
-            db.operation = "TransactWriteItems",
-            db.name = table.table_name(),
-            aws.dynamodb.table_names = ?[&table.table_name()],
+        ensure_operations(&self.operations, "TransactWriteItems")?;
+        for operation in &self.operations {
+            operation
+                .check_expressions()
+                .map_err(SdkError::construction_failure)?;
+        }
+
+        let span = operation_span!(
+            table,
+            "TransactWriteItems",
             aws.dynamodb.table_count = 1,
             aws.dynamodb.batch_operations = self.operations.len(),
             aws.dynamodb.consumed_write_capacity = field::Empty,
         );
 
-        let items = if self.operations.is_empty() {
-            None
-        } else {
-            Some(
-                self.operations
-                    .into_iter()
-                    .map(move |i| i.into_batch(table))
-                    .collect(),
-            )
-        };
+        let written_keys: Vec<_> = self
+            .operations
+            .iter()
+            .filter_map(TransactWriteItem::written_key::<T>)
+            .collect();
+
+        let items = self
+            .operations
+            .into_iter()
+            .map(move |i| i.into_batch(table))
+            .collect();
 
-        let result = table
-            .client()
-            .transact_write_items()
+        let input = TransactWriteItemsInput::builder()
             .return_consumed_capacity(ReturnConsumedCapacity::Total)
-            .set_transact_items(items)
+            .set_transact_items(Some(items))
             .set_client_request_token(self.client_request_token)
-            .send()
+            .build()
+            .map_err(SdkError::construction_failure)?;
+        let scoped = ScopedClient::new(table);
+        let max_attempts = self.conflict_attempts.max(1);
+        let mut attempt = 1;
+        let result = loop {
+            let result = client::timeout(
+                &span,
+                self.timeout,
+                client::Customization::scope(
+                    self.customization.clone(),
+                    scoped.transact_write_items(input.clone()),
+                ),
+            )
             .instrument(span.clone())
             .await;
 
+            match &result {
+                Err(error)
+                    if attempt < max_attempts && crate::error::is_transaction_conflict(error) =>
+                {
+                    let delay = crate::clock::backoff(attempt);
+                    tracing::debug!(
+                        parent: &span,
+                        attempt,
+                        delay_ms = delay.as_millis() as u64,
+                        "transaction conflicted, retrying"
+                    );
+                    table.clock().sleep(delay).await;
+                    attempt += 1;
+                }
+                _ => break result,
+            }
+        };
+
+        instrumentation::record_outcome(&span, &result);
+
+        invalidate_cached(table, &written_keys);
         if let Ok(output) = &result {
             let capacity = output.consumed_capacity().iter().fold(
                 ConsumedCapacity::builder().build(),
@@ -1412,12 +2459,27 @@ impl TransactWrite {
                 },
             );
             record_consumed_write_capacity(&span, Some(&capacity));
+            for key in &written_keys {
+                table.after_write(key);
+            }
         }
 
         result
     }
 }
 
+impl<O: Into<TransactWriteItem>> Extend<O> for TransactWrite {
+    fn extend<I: IntoIterator<Item = O>>(&mut self, ops: I) {
+        self.operations.extend(ops.into_iter().map(Into::into));
+    }
+}
+
+impl<O: Into<TransactWriteItem>> FromIterator<O> for TransactWrite {
+    fn from_iter<I: IntoIterator<Item = O>>(ops: I) -> Self {
+        Self::new().operations(ops)
+    }
+}
+
 /// A transactional write operation
 #[derive(Debug, Clone)]
 #[must_use]
@@ -1429,6 +2491,13 @@ pub enum BatchWriteItem {
 }
 
 impl BatchWriteItem {
+    pub(crate) fn written_key<T: Table>(&self) -> Item {
+        match self {
+            Self::PutItem(op) => primary_key_of::<T>(&op.item),
+            Self::DeleteItem(op) => op.key.clone(),
+        }
+    }
+
     #[inline]
     fn into_batch(self) -> aws_sdk_dynamodb::types::WriteRequest {
         match self {
@@ -1450,6 +2519,21 @@ impl BatchWriteItem {
                 .build(),
         }
     }
+
+    /// Recovers the operation from a write request, such as an unprocessed item
+    fn from_batch(request: aws_sdk_dynamodb::types::WriteRequest) -> Option<Self> {
+        match request {
+            aws_sdk_dynamodb::types::WriteRequest {
+                put_request: Some(put),
+                ..
+            } => Some(Self::PutItem(Put::new(put.item))),
+            aws_sdk_dynamodb::types::WriteRequest {
+                delete_request: Some(delete),
+                ..
+            } => Some(Self::DeleteItem(Delete::new(delete.key))),
+            _ => None,
+        }
+    }
 }
 
 impl From<Put> for BatchWriteItem {
@@ -1470,6 +2554,9 @@ impl From<Delete> for BatchWriteItem {
 #[must_use]
 pub struct BatchGet {
     operations: Vec<Get>,
+    projection: Option<expr::Projection>,
+    timeout: Option<Duration>,
+    customization: Option<client::Customization>,
 }
 
 impl BatchGet {
@@ -1478,9 +2565,46 @@ impl BatchGet {
     pub fn new() -> Self {
         Self {
             operations: Vec::new(),
+            projection: None,
+            timeout: None,
+            customization: None,
         }
     }
 
+    /// Specify a projection expression for every item in the batch
+    ///
+    /// By default, the batch reads the attributes projected by each of its
+    /// get operations, or full items if any get operation has no
+    /// projection. The primary key attributes of the table are always read,
+    /// so that the items returned can be matched to their keys.
+    #[inline]
+    pub fn projection(mut self, projection: expr::StaticProjection) -> Self {
+        self.projection = Some(projection.into());
+        self
+    }
+
+    /// Limit the time allowed for the request to complete
+    ///
+    /// If the timeout elapses before a response is received, the request is
+    /// canceled and a timeout error is returned, which is classified as
+    /// [`ErrorKind::Timeout`][crate::ErrorKind::Timeout].
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Customize how the request is sent by the standard SDK client
+    ///
+    /// See [`Customization`][client::Customization] for what can be
+    /// customized. The customization is not applied when the operation is
+    /// included in a transaction or a batch.
+    #[inline]
+    pub fn with_customization(mut self, customization: client::Customization) -> Self {
+        self.customization = Some(customization);
+        self
+    }
+
     /// Attach a get operation to the batch
     #[inline]
     pub fn operation(mut self, op: Get) -> Self {
@@ -1488,49 +2612,83 @@ impl BatchGet {
         self
     }
 
+    /// Attach several get operations to the batch
+    #[inline]
+    pub fn operations(mut self, ops: impl IntoIterator<Item = Get>) -> Self {
+        self.extend(ops);
+        self
+    }
+
     /// Execute the batch
+    ///
+    /// A batch without any operations is rejected before it is sent, with
+    /// an error for which
+    /// [`Error::is_empty_transaction()`][crate::Error::is_empty_transaction()]
+    /// returns true.
     pub async fn execute<T: Table>(
         self,
         table: &T,
     ) -> Result<BatchGetItemOutput, SdkError<BatchGetItemError>> {
-        let span = tracing::info_span!(
-            "DynamoDB.BatchGetItem",
-            span.kind = "client",
-            db.system = "dynamodb",
-            db.operation = "BatchGetItem",
-            db.name = table.table_name(),
-            aws.dynamodb.table_names = ?[&table.table_name()],
+        ensure_operations(&self.operations, "BatchGetItem")?;
+
+        let span = operation_span!(
+            table,
+            "BatchGetItem",
             aws.dynamodb.table_count = 1,
             aws.dynamodb.batch_operations = self.operations.len(),
+            aws.dynamodb.projection = field::Empty,
             aws.dynamodb.consumed_read_capacity = field::Empty,
         );
 
-        let items = if self.operations.is_empty() {
-            None
-        } else {
-            let mut kattr = KeysAndAttributes::builder();
-            for item in self.operations {
-                kattr = kattr.keys(item.key);
-            }
-            let tables = [(
-                table.table_name().to_owned(),
-                kattr.build().expect("keys is always provided"),
-            )]
-            .into_iter()
-            .collect();
-            Some(tables)
-        };
+        let projection = self.combined_projection::<T>();
+        if let Some(projection) = &projection {
+            span.record("aws.dynamodb.projection", projection.expression.as_str());
+        }
 
-        let result = table
-            .client()
-            .batch_get_item()
+        let mut kattr = KeysAndAttributes::builder();
+        if let Some(projection) = projection {
+            kattr = kattr
+                .projection_expression(projection.expression)
+                .set_expression_attribute_names(
+                    (!projection.names.is_empty()).then(|| projection.names.into_iter().collect()),
+                );
+        }
+        for item in self.operations {
+            kattr = kattr.keys(item.key);
+        }
+        let items = [(
+            table.table_name().to_owned(),
+            kattr.build().expect("keys is always provided"),
+        )]
+        .into_iter()
+        .collect();
+
+        let input = BatchGetItemInput::builder()
             .return_consumed_capacity(ReturnConsumedCapacity::Total)
-            .set_request_items(items)
-            .send()
-            .instrument(span.clone())
-            .await;
-
-        if let Ok(output) = &result {
+            .set_request_items(Some(items))
+            .build();
+        let scoped = ScopedClient::new(table);
+        let mut result = client::timeout(
+            &span,
+            self.timeout,
+            client::Customization::scope(
+                self.customization.clone(),
+                client::send(input, |input| scoped.batch_get_item(input)),
+            ),
+        )
+        .instrument(span.clone())
+        .await;
+
+        instrumentation::record_outcome(&span, &result);
+
+        if let Ok(output) = &mut result {
+            if let Some(items) = output
+                .responses
+                .as_mut()
+                .and_then(|responses| responses.get_mut(table.table_name()))
+            {
+                items.retain(|item| !is_expired(table, item));
+            }
             let capacity = output.consumed_capacity().iter().fold(
                 ConsumedCapacity::builder().build(),
                 |mut acc, next| {
@@ -1545,71 +2703,267 @@ impl BatchGet {
 
         result
     }
-}
 
-/// A batch write operation
-#[derive(Debug, Default, Clone)]
-#[must_use]
-pub struct BatchWrite {
-    operations: Vec<BatchWriteItem>,
-}
-
-impl BatchWrite {
-    /// Prepare a new batch write operation
+    /// Read the items in the batch, parsing each into a projection set by
+    /// its entity type
+    ///
+    /// The keys may belong to different entity types, such as those created
+    /// by the [`get()`][crate::EntityExt::get()] builders of several
+    /// entities, as long as each of them is part of the projection set.
+    ///
+    /// Get operations without a projection use the projection expression of
+    /// the projection set.
     #[inline]
-    pub fn new() -> Self {
-        Self {
-            operations: Vec::new(),
+    pub fn typed<P: ProjectionSet>(mut self) -> TypedBatchGet<P> {
+        for op in &mut self.operations {
+            if op.projection.is_none() {
+                op.projection = P::projection_expression();
+            }
+        }
+
+        TypedBatchGet {
+            batch: self,
+            projections: PhantomData,
         }
     }
 
-    /// Attach a write operation to the batch
-    #[inline]
-    pub fn operation(mut self, op: impl Into<BatchWriteItem>) -> Self {
-        self.operations.push(op.into());
-        self
+    /// Combines the projections of the batch into one that reads the
+    /// attributes needed by every get operation, along with the table's
+    /// primary key attributes, or `None` to read full items
+    fn combined_projection<T: Table>(&self) -> Option<expr::Projection> {
+        let mut attributes: Vec<String> = Vec::new();
+        match &self.projection {
+            Some(projection) => {
+                attributes.extend(projection.attribute_names().map(str::to_owned));
+            }
+            None => {
+                for op in &self.operations {
+                    attributes.extend(op.projection.as_ref()?.attribute_names().map(str::to_owned));
+                }
+            }
+        }
+        if attributes.is_empty() {
+            return None;
+        }
+
+        let key = <T::PrimaryKey as keys::PrimaryKey>::PRIMARY_KEY_DEFINITION;
+        attributes.push(key.hash_key.to_owned());
+        attributes.extend(key.range_key.map(str::to_owned));
+        attributes.extend(
+            T::TTL_ATTRIBUTE
+                .filter(|_| T::EXCLUDE_EXPIRED_ITEMS)
+                .map(str::to_owned),
+        );
+        Some(expr::Projection::new(attributes.iter().map(String::as_str)))
     }
+}
 
-    /// Execute the write batch
-    pub async fn execute<T: Table>(
-        self,
+impl Extend<Get> for BatchGet {
+    fn extend<I: IntoIterator<Item = Get>>(&mut self, ops: I) {
+        self.operations.extend(ops);
+    }
+}
+
+impl FromIterator<Get> for BatchGet {
+    fn from_iter<I: IntoIterator<Item = Get>>(ops: I) -> Self {
+        Self::new().operations(ops)
+    }
+}
+
+/// A batch get operation that parses the items read into a projection set
+///
+/// Created by [`BatchGet::typed()`].
+#[must_use]
+pub struct TypedBatchGet<P> {
+    batch: BatchGet,
+    projections: PhantomData<fn() -> P>,
+}
+
+impl<P> fmt::Debug for TypedBatchGet<P> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TypedBatchGet")
+            .field("batch", &self.batch)
+            .field("projections", &std::any::type_name::<P>())
+            .finish()
+    }
+}
+
+impl<P> Clone for TypedBatchGet<P> {
+    fn clone(&self) -> Self {
+        Self {
+            batch: self.batch.clone(),
+            projections: PhantomData,
+        }
+    }
+}
+
+impl<P: ProjectionSet> TypedBatchGet<P> {
+    /// Execute the batch, returning the projections of the items found
+    ///
+    /// The batch is split into requests of up to 100 keys, and keys left
+    /// unprocessed are retried with exponential backoff. The projections
+    /// are returned in the order of the keys; keys with no item, and items
+    /// with an entity type that is not part of the projection set, are
+    /// skipped.
+    pub async fn execute<T: Table>(self, table: &T) -> Result<Vec<P>, crate::Error> {
+        let items = self.read(table).await?;
+        instrumentation::report_skipped_items(table, "BatchGetItem", || {
+            let mut projections = Vec::with_capacity(items.len());
+            for item in items {
+                projections.extend(P::try_from_item(item)?);
+            }
+            Ok::<_, crate::Error>(projections)
+        })
+        .0
+    }
+
+    /// Execute the batch, merging the items found into an aggregate
+    ///
+    /// The items are merged in the order of the keys.
+    pub async fn hydrate<T, A>(self, table: &T) -> Result<A, crate::Error>
+    where
+        T: Table,
+        A: Aggregate<Projections = P>,
+    {
+        let items = self.read(table).await?;
+        let mut aggregate = A::default();
+        instrumentation::report_skipped_items(table, "BatchGetItem", || aggregate.reduce(items))
+            .0?;
+        Ok(aggregate)
+    }
+
+    async fn read<T: Table>(self, table: &T) -> Result<Vec<Item>, crate::Error> {
+        let projection = self.batch.combined_projection::<T>();
+        let keys: Vec<Item> = self.batch.operations.into_iter().map(|op| op.key).collect();
+
+        let mut items = Vec::with_capacity(keys.len());
+        for keys in keys.chunks(BATCH_GET_MAX_KEYS) {
+            items.extend(
+                batch_get_in_order(
+                    table,
+                    keys,
+                    projection.as_ref(),
+                    self.batch.timeout,
+                    self.batch.customization.as_ref(),
+                )
+                .await?,
+            );
+        }
+
+        Ok(items)
+    }
+}
+
+/// A batch write operation
+#[derive(Debug, Default, Clone)]
+#[must_use]
+pub struct BatchWrite {
+    operations: Vec<BatchWriteItem>,
+    timeout: Option<Duration>,
+    customization: Option<client::Customization>,
+}
+
+impl BatchWrite {
+    /// Prepare a new batch write operation
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            operations: Vec::new(),
+            timeout: None,
+            customization: None,
+        }
+    }
+
+    /// Limit the time allowed for the request to complete
+    ///
+    /// If the timeout elapses before a response is received, the request is
+    /// canceled and a timeout error is returned, which is classified as
+    /// [`ErrorKind::Timeout`][crate::ErrorKind::Timeout].
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Customize how the request is sent by the standard SDK client
+    ///
+    /// See [`Customization`][client::Customization] for what can be
+    /// customized. The customization is not applied when the operation is
+    /// included in a transaction or a batch.
+    #[inline]
+    pub fn with_customization(mut self, customization: client::Customization) -> Self {
+        self.customization = Some(customization);
+        self
+    }
+
+    /// Attach a write operation to the batch
+    #[inline]
+    pub fn operation(mut self, op: impl Into<BatchWriteItem>) -> Self {
+        self.operations.push(op.into());
+        self
+    }
+
+    /// Attach several write operations to the batch
+    #[inline]
+    pub fn operations(mut self, ops: impl IntoIterator<Item = impl Into<BatchWriteItem>>) -> Self {
+        self.extend(ops);
+        self
+    }
+
+    /// Execute the write batch
+    ///
+    /// A batch without any operations is rejected before it is sent, with
+    /// an error for which
+    /// [`Error::is_empty_transaction()`][crate::Error::is_empty_transaction()]
+    /// returns true.
+    pub async fn execute<T: WritableTable>(
+        self,
         table: &T,
     ) -> Result<BatchWriteItemOutput, SdkError<BatchWriteItemError>> {
-        let span = tracing::info_span!(
-            "DynamoDB.BatchWriteItem",
-            span.kind = "client",
-            db.system = "dynamodb",
-            db.operation = "BatchWriteItem",
-            db.name = table.table_name(),
-            aws.dynamodb.table_names = ?[&table.table_name()],
+        ensure_operations(&self.operations, "BatchWriteItem")?;
+
+        let span = operation_span!(
+            table,
+            "BatchWriteItem",
             aws.dynamodb.table_count = 1,
             aws.dynamodb.batch_operations = self.operations.len(),
             aws.dynamodb.consumed_write_capacity = field::Empty,
         );
 
-        let items = if self.operations.is_empty() {
-            None
-        } else {
-            let reqs = self
-                .operations
-                .into_iter()
-                .map(BatchWriteItem::into_batch)
-                .collect();
-            let tables = [(table.table_name().to_owned(), reqs)]
-                .into_iter()
-                .collect();
-            Some(tables)
-        };
+        let written_keys: Vec<_> = self
+            .operations
+            .iter()
+            .map(BatchWriteItem::written_key::<T>)
+            .collect();
 
-        let result = table
-            .client()
-            .batch_write_item()
+        let reqs = self
+            .operations
+            .into_iter()
+            .map(BatchWriteItem::into_batch)
+            .collect();
+        let items = [(table.table_name().to_owned(), reqs)]
+            .into_iter()
+            .collect();
+
+        let input = BatchWriteItemInput::builder()
             .return_consumed_capacity(ReturnConsumedCapacity::Total)
-            .set_request_items(items)
-            .send()
-            .instrument(span.clone())
-            .await;
+            .set_request_items(Some(items))
+            .build();
+        let scoped = ScopedClient::new(table);
+        let result = client::timeout(
+            &span,
+            self.timeout,
+            client::Customization::scope(
+                self.customization.clone(),
+                client::send(input, |input| scoped.batch_write_item(input)),
+            ),
+        )
+        .instrument(span.clone())
+        .await;
 
+        instrumentation::record_outcome(&span, &result);
+
+        invalidate_cached(table, &written_keys);
         if let Ok(output) = &result {
             let capacity = output.consumed_capacity().iter().fold(
                 ConsumedCapacity::builder().build(),
@@ -1621,12 +2975,37 @@ impl BatchWrite {
                 },
             );
             record_consumed_write_capacity(&span, Some(&capacity));
+
+            let unprocessed: Vec<_> = output
+                .unprocessed_items
+                .as_ref()
+                .and_then(|items| items.get(table.table_name()))
+                .into_iter()
+                .flatten()
+                .filter_map(|request| BatchWriteItem::from_batch(request.clone()))
+                .map(|op| op.written_key::<T>())
+                .collect();
+            for key in written_keys.iter().filter(|key| !unprocessed.contains(key)) {
+                table.after_write(key);
+            }
         }
 
         result
     }
 }
 
+impl<O: Into<BatchWriteItem>> Extend<O> for BatchWrite {
+    fn extend<I: IntoIterator<Item = O>>(&mut self, ops: I) {
+        self.operations.extend(ops.into_iter().map(Into::into));
+    }
+}
+
+impl<O: Into<BatchWriteItem>> FromIterator<O> for BatchWrite {
+    fn from_iter<I: IntoIterator<Item = O>>(ops: I) -> Self {
+        Self::new().operations(ops)
+    }
+}
+
 /// A builder for index query operations
 #[must_use]
 pub struct Query<K> {
@@ -1638,6 +3017,9 @@ pub struct Query<K> {
     scan_index_forward: bool,
     consistent_read: bool,
     exclusive_start_key: Option<Item>,
+    timeout: Option<Duration>,
+    customization: Option<client::Customization>,
+    entity_types: Vec<&'static EntityTypeNameRef>,
 }
 
 impl<K> fmt::Debug for Query<K> {
@@ -1651,6 +3033,9 @@ impl<K> fmt::Debug for Query<K> {
             .field("consistent_read", &self.consistent_read)
             .field("scan_index_forward", &self.scan_index_forward)
             .field("exclusive_start_key", &self.exclusive_start_key)
+            .field("timeout", &self.timeout)
+            .field("customization", &self.customization)
+            .field("entity_types", &self.entity_types)
             .finish()
     }
 }
@@ -1666,6 +3051,9 @@ impl<K> Clone for Query<K> {
             consistent_read: self.consistent_read,
             scan_index_forward: self.scan_index_forward,
             exclusive_start_key: self.exclusive_start_key.clone(),
+            timeout: self.timeout,
+            customization: self.customization.clone(),
+            entity_types: self.entity_types.clone(),
         }
     }
 }
@@ -1682,6 +3070,9 @@ impl<K: keys::Key> Query<K> {
             scan_index_forward: true,
             consistent_read: false,
             exclusive_start_key: None,
+            timeout: None,
+            customization: None,
+            entity_types: Vec::new(),
         }
     }
 
@@ -1752,6 +3143,12 @@ impl<K: keys::Key> Query<K> {
         self
     }
 
+    /// Override or clear the set of attributes projected into the response
+    pub fn set_projection(mut self, projection: Option<expr::StaticProjection>) -> Self {
+        self.projection = projection;
+        self
+    }
+
     /// Apply a filter expression to the scanned items
     ///
     /// # Note
@@ -1763,79 +3160,180 @@ impl<K: keys::Key> Query<K> {
         self
     }
 
-    /// Execute the query operation against the specified table
-    pub async fn execute<T: Table>(self, table: &T) -> Result<QueryOutput, SdkError<QueryError>> {
-        let (filter_expr, filter_names, filter_values, filter_sensitive_values) = {
-            if let Some(f) = self.filter {
-                (
-                    Some(f.expression),
-                    Some(f.names),
-                    Some(f.values),
-                    Some(f.sensitive_values),
-                )
-            } else {
-                (None, None, None, None)
-            }
-        };
+    /// Limit the time allowed for the request to complete
+    ///
+    /// If the timeout elapses before a response is received, the request is
+    /// canceled and a timeout error is returned, which is classified as
+    /// [`ErrorKind::Timeout`][crate::ErrorKind::Timeout].
+    ///
+    /// When the query is paginated, the timeout applies to each page.
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
 
-        let key_condition_expr = self.key_condition.expression();
+    /// Customize how the request is sent by the standard SDK client
+    ///
+    /// See [`Customization`][client::Customization] for what can be
+    /// customized. The customization is not applied when the operation is
+    /// included in a transaction or a batch.
+    #[inline]
+    pub fn with_customization(mut self, customization: client::Customization) -> Self {
+        self.customization = Some(customization);
+        self
+    }
+
+    pub(crate) fn partition_key(&self) -> &AttributeValue {
+        self.key_condition.partition_key()
+    }
+
+    /// Renders the parameters of the query into a string that identifies
+    /// the request, for use as a cache key
+    pub(crate) fn fingerprint(&self) -> String {
+        let mut values: Vec<(&str, &AttributeValue)> = self
+            .filter
+            .iter()
+            .flat_map(|f| f.values.iter().chain(&f.sensitive_values))
+            .map(|(k, v)| (k.as_str(), v))
+            .collect();
+        values.extend(self.key_condition.values().map(|(k, v)| (k as &str, v)));
+        values.sort_by(|l, r| l.0.cmp(r.0));
 
-        let expression_attribute_names = self
+        let mut names: Vec<(&str, &str)> = self
+            .filter
+            .iter()
+            .flat_map(|f| f.names.iter().map(|(l, r)| (l.as_str(), r.as_str())))
+            .collect();
+        for (l, r) in self
             .key_condition
             .names()
-            .chain(
-                self.projection
-                    .map(|f| f.names)
-                    .into_iter()
-                    .flatten()
-                    .copied(),
-            )
-            .map(|(l, r)| (l.to_string(), r.to_string()))
-            .chain(filter_names.into_iter().flatten())
-            .collect::<HashMap<String, String>>();
+            .chain(self.projection.iter().flat_map(|p| p.names.iter().copied()))
+        {
+            names.push((l, r));
+        }
+        names.sort();
+
+        format!(
+            "{:?}|{}|{:?}|{:?}|{:?}|{:?}|{:?}|{}|{}|{:?}|{:?}",
+            K::DEFINITION.index_name(),
+            self.key_condition.expression(),
+            self.filter.as_ref().map(|f| &f.expression),
+            self.projection.map(|p| p.expression),
+            names,
+            values,
+            self.limit,
+            self.scan_index_forward,
+            self.consistent_read,
+            self.select,
+            self.exclusive_start_key
+                .as_ref()
+                .map(|k| k.iter().collect::<BTreeMap<_, _>>()),
+        )
+    }
+
+    /// Execute the query operation against the specified table
+    pub async fn execute<T: Table>(
+        mut self,
+        table: &T,
+    ) -> Result<QueryOutput, SdkError<QueryError>> {
+        let filter = self.filter.take();
+        let exclusive_start_key = self.exclusive_start_key.take();
+        self.send(table, filter, exclusive_start_key).await
+    }
+
+    /// Execute the query for a single page without consuming the query
+    ///
+    /// The exclusive start key is taken from the query, so that it can be
+    /// replaced with the key of the next page. The filter is cloned into
+    /// each request.
+    pub(crate) async fn execute_page<T: Table>(
+        &mut self,
+        table: &T,
+    ) -> Result<QueryOutput, SdkError<QueryError>> {
+        let exclusive_start_key = self.exclusive_start_key.take();
+        self.send(table, self.filter.clone(), exclusive_start_key)
+            .await
+    }
+
+    async fn send<T: Table>(
+        &self,
+        table: &T,
+        filter: Option<expr::Filter>,
+        exclusive_start_key: Option<Item>,
+    ) -> Result<QueryOutput, SdkError<QueryError>> {
+        let filter = unexpired_filter(table, filter);
+        if let Some(filter) = &filter {
+            filter.check().map_err(SdkError::construction_failure)?;
+        }
+
+        let (filter_expr, filter_names, filter_values, filter_sensitive_values) = match filter {
+            Some(f) => (Some(f.expression), f.names, f.values, f.sensitive_values),
+            None => Default::default(),
+        };
+
+        let key_condition_expr = self.key_condition.expression();
+        let projection_names = self.projection.map_or(&[][..], |p| p.names);
+
+        // Size the maps up front so that they are never rehashed while being filled
+        let mut expression_attribute_names =
+            HashMap::with_capacity(2 + projection_names.len() + filter_names.len());
+        expression_attribute_names.extend(
+            self.key_condition
+                .names()
+                .chain(projection_names.iter().copied())
+                .map(|(l, r)| (l.to_string(), r.to_string()))
+                .chain(filter_names),
+        );
 
-        let mut expression_attribute_values = self
+        let key_condition_values: Item = self
             .key_condition
             .values()
-            .map(|(l, r)| (l.to_string(), r))
-            .chain(filter_values.into_iter().flatten())
-            .collect::<HashMap<String, AttributeValue>>();
-
-        let span = tracing::info_span!(
-            "DynamoDB.Query",
-            span.kind = "client",
-            db.system = "dynamodb",
-            db.operation = "Query",
-            db.name = table.table_name(),
+            .map(|(l, r)| (l.to_string(), r.clone()))
+            .collect();
+        let mut expression_attribute_values =
+            HashMap::with_capacity(3 + filter_values.len() + filter_sensitive_values.len());
+        expression_attribute_values.extend(filter_values);
+
+        let span = operation_span!(
+            table,
+            "Query",
             aws.dynamodb.index_name = K::DEFINITION.index_name(),
             aws.dynamodb.filter_expression = filter_expr.as_deref(),
             aws.dynamodb.projection = self.projection.map(|p| p.expression),
             aws.dynamodb.key_condition_expression = key_condition_expr,
-            aws.dynamodb.exclusive_start_key = self.exclusive_start_key.as_ref().map(tracing::field::debug),
+            aws.dynamodb.key_condition_values = %T::redact_key(&key_condition_values),
+            aws.dynamodb.exclusive_start_key = exclusive_start_key.as_ref().map(|key| field::display(T::redact_key(key))),
             aws.dynamodb.limit = self.limit,
             aws.dynamodb.select = self.select.as_ref().map(tracing::field::debug),
             aws.dynamodb.scan_forward = self.scan_index_forward,
             aws.dynamodb.consistent_read = self.consistent_read,
-            aws.dynamodb.expression_attribute_names = ?expression_attribute_names,
-            aws.dynamodb.expression_attribute_values = ?expression_attribute_values,
+            aws.dynamodb.expression_attribute_names = ?instrumentation::sorted(&expression_attribute_names),
+            aws.dynamodb.expression_attribute_values = ?instrumentation::sorted(&expression_attribute_values),
             aws.dynamodb.consumed_read_capacity = field::Empty,
             aws.dynamodb.scanned_count = field::Empty,
             aws.dynamodb.count = field::Empty,
             aws.dynamodb.has_next_page = field::Empty,
         );
-
-        expression_attribute_values.extend(filter_sensitive_values.into_iter().flatten());
-
-        let result = table
-            .client()
-            .query()
+        instrumentation::record_projection(&span, self.projection.as_ref());
+        instrumentation::record_entity_types(&span, self.entity_types.iter().copied());
+        table.record_partition_access(&instrumentation::PartitionAccess::new(
+            "Query",
+            K::DEFINITION.index_name(),
+            self.partition_key(),
+        ));
+
+        expression_attribute_values.extend(key_condition_values);
+        expression_attribute_values.extend(filter_sensitive_values);
+
+        let input = QueryInput::builder()
             .table_name(table.table_name())
             .set_index_name(K::DEFINITION.index_name().map(|i| i.to_string()))
-            .set_select(self.select)
+            .set_select(self.select.clone())
             .set_limit(self.limit)
             .set_consistent_read(self.consistent_read.then_some(true))
             .set_scan_index_forward((!self.scan_index_forward).then_some(false))
-            .set_exclusive_start_key(self.exclusive_start_key)
+            .set_exclusive_start_key(exclusive_start_key)
             .set_projection_expression(self.projection.map(|p| p.expression.to_string()))
             .set_filter_expression(filter_expr)
             .set_key_condition_expression(Some(key_condition_expr.to_string()))
@@ -1846,9 +3344,20 @@ impl<K: keys::Key> Query<K> {
                 (!expression_attribute_values.is_empty()).then_some(expression_attribute_values),
             )
             .return_consumed_capacity(ReturnConsumedCapacity::Total)
-            .send()
-            .instrument(span.clone())
-            .await;
+            .build();
+        let scoped = ScopedClient::new(table);
+        let result = client::timeout(
+            &span,
+            self.timeout,
+            client::Customization::scope(
+                self.customization.clone(),
+                client::send(input, |input| scoped.query(input)),
+            ),
+        )
+        .instrument(span.clone())
+        .await;
+
+        instrumentation::record_outcome(&span, &result);
 
         if let Ok(output) = &result {
             record_consumed_read_capacity(&span, output.consumed_capacity.as_ref());
@@ -1862,6 +3371,574 @@ impl<K: keys::Key> Query<K> {
 
         result
     }
+
+    /// Execute the query only to check whether any item matches
+    ///
+    /// The query is sent with [`Select::Count`], so no items are returned or
+    /// deserialized. Without a filter, at most one item is evaluated. With a
+    /// filter, pages are read until a matching item is found or the query is
+    /// exhausted.
+    pub async fn exists<T: Table>(mut self, table: &T) -> Result<bool, SdkError<QueryError>> {
+        self.select = Some(Select::Count);
+        self.projection = None;
+        if self.filter.is_none() {
+            self.limit = Some(1);
+        }
+
+        loop {
+            let output = self.execute_page(table).await?;
+            if output.count > 0 {
+                return Ok(true);
+            }
+
+            match output.last_evaluated_key {
+                Some(key) => self.exclusive_start_key = Some(key),
+                None => return Ok(false),
+            }
+        }
+    }
+
+    /// Stream the items matching the query, fetching pages on demand
+    pub fn stream<T: Table>(self, table: &T) -> ItemStream<'_, T, K> {
+        ItemStream::query(table, self)
+    }
+
+    #[inline]
+    pub(crate) fn for_projections<P: ProjectionSet>(mut self) -> Self {
+        self.entity_types = P::entity_types();
+        self
+    }
+
+    /// Execute the query, reading all pages into an aggregate
+    ///
+    /// If a limit has been set on the query, then the limit applies to the
+    /// total number of items evaluated across all pages.
+    pub async fn hydrate<T, A>(self, table: &T) -> Result<A, crate::Error>
+    where
+        T: Table,
+        A: Aggregate,
+    {
+        self.hydrate_with_pages(table, |_, _, _| {}).await
+    }
+
+    /// Execute the query, reading all pages into an aggregate and reporting
+    /// progress after each page
+    ///
+    /// The callback receives the progress so far along with the partially
+    /// hydrated aggregate, which may be used to stream progress updates or
+    /// render partial results while hydration continues.
+    ///
+    /// If a limit has been set on the query, then the limit applies to the
+    /// total number of items evaluated across all pages.
+    pub async fn hydrate_with_progress<T, A, F>(
+        self,
+        table: &T,
+        mut progress: F,
+    ) -> Result<A, crate::Error>
+    where
+        T: Table,
+        A: Aggregate,
+        F: FnMut(&HydrationProgress, &A),
+    {
+        self.hydrate_with_pages(table, |state, _, aggregate| progress(state, aggregate))
+            .await
+    }
+
+    /// Execute the query, reading all pages into an aggregate and reporting
+    /// the metadata of each page as it is fetched
+    ///
+    /// The callback receives the progress so far, the metadata of the page
+    /// that was just merged, and the partially hydrated aggregate. The page
+    /// metadata includes the consumed capacity and the key from which the
+    /// query could be resumed.
+    ///
+    /// If a limit has been set on the query, then the limit applies to the
+    /// total number of items evaluated across all pages.
+    pub async fn hydrate_with_pages<T, A, F>(
+        self,
+        table: &T,
+        mut progress: F,
+    ) -> Result<A, crate::Error>
+    where
+        T: Table,
+        A: Aggregate,
+        F: FnMut(&HydrationProgress, &PageInfo, &A),
+    {
+        let limit = self.limit.map(|l| l as u32);
+        let mut state = HydrationProgress {
+            pages: 0,
+            items: 0,
+            scanned: 0,
+            limit,
+            has_more: true,
+        };
+
+        let mut aggregate = A::default();
+        let mut query = self.for_projections::<A::Projections>();
+        loop {
+            let mut output = query.execute_page(table).await?;
+            let page = PageInfo::from_query(&output);
+            state.pages += 1;
+            state.scanned += page.scanned_count as usize;
+
+            let items = output.items.take().unwrap_or_default();
+            state.items += items.len();
+            instrumentation::report_skipped_items(table, "Query", || aggregate.reduce(items))
+                .0
+                .map_err(|error| error.with_index_projection(K::DEFINITION.index_name()))?;
+
+            let remaining = limit.map(|l| (l as usize).saturating_sub(state.scanned) as u32);
+            state.has_more = output.last_evaluated_key.is_some() && remaining != Some(0);
+            progress(&state, &page, &aggregate);
+
+            match output.last_evaluated_key {
+                Some(key) if state.has_more => {
+                    query = query.exclusive_start_key(key).set_limit(remaining);
+                }
+                _ => break,
+            }
+        }
+
+        Ok(aggregate)
+    }
+
+    /// Apply an update to every item matching the query
+    ///
+    /// The matching items are read a page at a time, and the update is
+    /// applied to each of them before the next page is read. A limit set on
+    /// the query bounds the number of items evaluated for each page, rather
+    /// than in total. See [`bulk_update`][crate::bulk_update] for details.
+    pub async fn update_matching<T: WritableTable>(
+        self,
+        table: &T,
+        update: expr::Update,
+        options: crate::bulk_update::UpdateMatchingOptions,
+    ) -> Result<crate::bulk_update::UpdateMatchingReport, crate::bulk_update::UpdateMatchingError>
+    {
+        options.execute(self, table, update).await
+    }
+
+    /// Execute a query against a keys-only secondary index, reading the
+    /// full items for the returned keys into an aggregate
+    ///
+    /// Only the table's primary key attributes of the items returned by the
+    /// query are used. The full items are read with batched get requests
+    /// after each page, and are merged into the aggregate in the order
+    /// returned by the query. Items that are deleted between the query and
+    /// the get are skipped. Keys left unprocessed by a batch get are retried
+    /// with exponential backoff.
+    ///
+    /// If a limit has been set on the query, then the limit applies to the
+    /// total number of items evaluated across all pages.
+    pub async fn hydrate_from_keys<T, A>(self, table: &T) -> Result<A, crate::Error>
+    where
+        T: Table,
+        A: Aggregate,
+    {
+        let limit = self.limit.map(|l| l as u32);
+        let mut scanned = 0;
+        let projection = A::projection_expression().map(expr::Projection::from);
+        let timeout = self.timeout;
+        let customization = self.customization.clone();
+
+        let mut aggregate = A::default();
+        let mut query = self.for_projections::<A::Projections>();
+        loop {
+            let mut output = query.execute_page(table).await?;
+            scanned += output.scanned_count().max(0) as usize;
+
+            let keys: Vec<Item> = output
+                .items
+                .take()
+                .unwrap_or_default()
+                .iter()
+                .map(primary_key_of::<T>)
+                .collect();
+            for keys in keys.chunks(BATCH_GET_MAX_KEYS) {
+                let items = batch_get_in_order(
+                    table,
+                    keys,
+                    projection.as_ref(),
+                    timeout,
+                    customization.as_ref(),
+                )
+                .await?;
+                // The full items are read from the table rather than the
+                // index, so a missing attribute is not due to its projection
+                instrumentation::report_skipped_items(table, "BatchGetItem", || {
+                    aggregate.reduce(items)
+                })
+                .0?;
+            }
+
+            let remaining = limit.map(|l| (l as usize).saturating_sub(scanned) as u32);
+            match output.last_evaluated_key {
+                Some(key) if remaining != Some(0) => {
+                    query = query.exclusive_start_key(key).set_limit(remaining);
+                }
+                _ => break,
+            }
+        }
+
+        Ok(aggregate)
+    }
+}
+
+/// A query through one of two indexes, chosen at runtime
+///
+/// This is prepared from an [`IndexedQueryInput`][crate::IndexedQueryInput],
+/// which chooses the index based on its parameters. The query can be
+/// configured and executed without matching on the chosen index.
+#[must_use]
+pub enum IndexedQuery<L, R> {
+    /// A query through the left index
+    Left(Query<L>),
+
+    /// A query through the right index
+    Right(Query<R>),
+}
+
+impl<L, R> fmt::Debug for IndexedQuery<L, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Left(query) => f.debug_tuple("Left").field(query).finish(),
+            Self::Right(query) => f.debug_tuple("Right").field(query).finish(),
+        }
+    }
+}
+
+impl<L, R> Clone for IndexedQuery<L, R> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Left(query) => Self::Left(query.clone()),
+            Self::Right(query) => Self::Right(query.clone()),
+        }
+    }
+}
+
+impl<L: keys::Key, R: keys::Key> IndexedQuery<L, R> {
+    /// The name of the chosen index, or `None` if the table itself is queried
+    #[inline]
+    pub fn index_name(&self) -> Option<&'static str> {
+        match self {
+            Self::Left(_) => L::DEFINITION.index_name(),
+            Self::Right(_) => R::DEFINITION.index_name(),
+        }
+    }
+
+    /// Set a specific limit on the number of items scanned before returning
+    pub fn limit(self, limit: u32) -> Self {
+        self.map(|query| query.limit(limit), |query| query.limit(limit))
+    }
+
+    /// Add a filter expression to the query
+    pub fn filter(self, filter: expr::Filter) -> Self {
+        match self {
+            Self::Left(query) => Self::Left(query.filter(filter)),
+            Self::Right(query) => Self::Right(query.filter(filter)),
+        }
+    }
+
+    /// Set a timeout on each request made by the query
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.map(
+            |query| query.with_timeout(timeout),
+            |query| query.with_timeout(timeout),
+        )
+    }
+
+    /// Customize how each request made by the query is sent by the standard
+    /// SDK client
+    pub fn with_customization(self, customization: client::Customization) -> Self {
+        let left = customization.clone();
+        self.map(
+            |query| query.with_customization(left),
+            |query| query.with_customization(customization),
+        )
+    }
+
+    /// Execute the query operation against the specified table
+    pub async fn execute<T: Table>(self, table: &T) -> Result<QueryOutput, SdkError<QueryError>> {
+        match self {
+            Self::Left(query) => query.execute(table).await,
+            Self::Right(query) => query.execute(table).await,
+        }
+    }
+
+    /// Check whether any item matches the query
+    ///
+    /// See [`Query::exists()`] for details.
+    pub async fn exists<T: Table>(self, table: &T) -> Result<bool, SdkError<QueryError>> {
+        match self {
+            Self::Left(query) => query.exists(table).await,
+            Self::Right(query) => query.exists(table).await,
+        }
+    }
+
+    /// Execute the query, reading all pages into an aggregate
+    ///
+    /// See [`Query::hydrate()`] for details.
+    pub async fn hydrate<T, A>(self, table: &T) -> Result<A, crate::Error>
+    where
+        T: Table,
+        A: Aggregate,
+    {
+        match self {
+            Self::Left(query) => query.hydrate(table).await,
+            Self::Right(query) => query.hydrate(table).await,
+        }
+    }
+
+    /// Execute the query, reading all pages into an aggregate and reporting
+    /// progress after each page
+    ///
+    /// See [`Query::hydrate_with_progress()`] for details.
+    pub async fn hydrate_with_progress<T, A, F>(
+        self,
+        table: &T,
+        progress: F,
+    ) -> Result<A, crate::Error>
+    where
+        T: Table,
+        A: Aggregate,
+        F: FnMut(&HydrationProgress, &A),
+    {
+        match self {
+            Self::Left(query) => query.hydrate_with_progress(table, progress).await,
+            Self::Right(query) => query.hydrate_with_progress(table, progress).await,
+        }
+    }
+
+    fn map(
+        self,
+        left: impl FnOnce(Query<L>) -> Query<L>,
+        right: impl FnOnce(Query<R>) -> Query<R>,
+    ) -> Self {
+        match self {
+            Self::Left(query) => Self::Left(left(query)),
+            Self::Right(query) => Self::Right(right(query)),
+        }
+    }
+}
+
+/// The maximum number of keys that can be read in a single batch get
+const BATCH_GET_MAX_KEYS: usize = 100;
+
+/// The number of attempts made to read unprocessed keys in a batch get
+const BATCH_GET_ATTEMPTS: u32 = 8;
+
+/// Rejects a transaction or batch without any operations before it is sent
+#[allow(clippy::result_large_err)]
+fn ensure_operations<O, E>(operations: &[O], operation: &'static str) -> Result<(), SdkError<E>> {
+    if operations.is_empty() {
+        return Err(SdkError::construction_failure(
+            crate::error::EmptyTransactionError { operation },
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the items with the given keys, retrying unprocessed keys with
+/// exponential backoff, and returns the items found in the order of the keys
+async fn batch_get_in_order<T: Table>(
+    table: &T,
+    keys: &[Item],
+    projection: Option<&expr::Projection>,
+    timeout: Option<Duration>,
+    customization: Option<&client::Customization>,
+) -> Result<Vec<Item>, crate::Error> {
+    let mut found = Vec::with_capacity(keys.len());
+    let mut pending = keys.to_vec();
+
+    for attempt in 0..BATCH_GET_ATTEMPTS {
+        if attempt > 0 {
+            table.clock().sleep(crate::clock::backoff(attempt)).await;
+        }
+
+        let batch = BatchGet {
+            operations: pending.drain(..).map(Get::new).collect(),
+            projection: projection.cloned(),
+            timeout,
+            customization: customization.cloned(),
+        };
+        let output = batch.execute(table).await.map_err(|error| {
+            crate::Error::from(error).with_context(ErrorContext::new(table, "BatchGetItem"))
+        })?;
+
+        found.extend(
+            output
+                .responses
+                .and_then(|mut responses| responses.remove(table.table_name()))
+                .unwrap_or_default(),
+        );
+        pending = output
+            .unprocessed_keys
+            .and_then(|mut unprocessed| unprocessed.remove(table.table_name()))
+            .map(|unprocessed| unprocessed.keys)
+            .unwrap_or_default();
+
+        if pending.is_empty() {
+            let mut slots: Vec<Option<Item>> = vec![None; keys.len()];
+            for item in found {
+                let key = primary_key_of::<T>(&item);
+                if let Some(index) = keys.iter().position(|k| *k == key) {
+                    slots[index] = Some(item);
+                }
+            }
+            return Ok(slots.into_iter().flatten().collect());
+        }
+    }
+
+    let mut context = ErrorContext::new(table, "BatchGetItem");
+    if let Some(key) = pending.first() {
+        context = context.with_key::<T>(key);
+    }
+
+    Err(crate::Error::from(crate::error::UnprocessedItemsError {
+        count: pending.len(),
+        attempts: BATCH_GET_ATTEMPTS,
+    })
+    .with_context(context))
+}
+
+/// Executes the write batch, retrying unprocessed items with exponential backoff
+pub(crate) async fn batch_write_with_retry<T: WritableTable>(
+    table: &T,
+    mut batch: BatchWrite,
+    max_attempts: u32,
+) -> Result<(), crate::Error> {
+    let mut pending = Vec::new();
+    let timeout = batch.timeout;
+    let customization = batch.customization.clone();
+
+    for attempt in 0..max_attempts {
+        if attempt > 0 {
+            table.clock().sleep(crate::clock::backoff(attempt)).await;
+        }
+
+        let output = batch.execute(table).await.map_err(|error| {
+            crate::Error::from(error).with_context(ErrorContext::new(table, "BatchWriteItem"))
+        })?;
+        pending = output
+            .unprocessed_items
+            .and_then(|mut unprocessed| unprocessed.remove(table.table_name()))
+            .unwrap_or_default();
+
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        batch = BatchWrite {
+            operations: pending
+                .iter()
+                .cloned()
+                .filter_map(BatchWriteItem::from_batch)
+                .collect(),
+            timeout,
+            customization: customization.clone(),
+        };
+    }
+
+    let mut context = ErrorContext::new(table, "BatchWriteItem");
+    if let Some(key) = pending
+        .first()
+        .and_then(|request| BatchWriteItem::from_batch(request.clone()))
+    {
+        context = context.with_key::<T>(&primary_key_of::<T>(&key.written_key::<T>()));
+    }
+
+    Err(crate::Error::from(crate::error::UnprocessedItemsError {
+        count: pending.len(),
+        attempts: max_attempts,
+    })
+    .with_context(context))
+}
+
+/// Metadata about a single page of query or scan results
+#[derive(Clone, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct PageInfo {
+    /// The number of items evaluated, before any filter is applied
+    pub scanned_count: u32,
+
+    /// The number of items returned, after any filter is applied
+    pub count: u32,
+
+    /// The capacity consumed by the page, if reported
+    pub consumed_capacity: Option<ConsumedCapacity>,
+
+    /// The key from which the next page can be fetched, if more results remain
+    pub last_evaluated_key: Option<Item>,
+}
+
+impl PageInfo {
+    pub(crate) fn from_query(output: &QueryOutput) -> Self {
+        Self {
+            scanned_count: output.scanned_count().max(0) as u32,
+            count: output.count().max(0) as u32,
+            consumed_capacity: output.consumed_capacity.clone(),
+            last_evaluated_key: output.last_evaluated_key.clone(),
+        }
+    }
+
+    pub(crate) fn from_scan(output: &ScanOutput) -> Self {
+        Self {
+            scanned_count: output.scanned_count().max(0) as u32,
+            count: output.count().max(0) as u32,
+            consumed_capacity: output.consumed_capacity.clone(),
+            last_evaluated_key: output.last_evaluated_key.clone(),
+        }
+    }
+
+    /// The read capacity units consumed by the page, if reported
+    #[inline]
+    pub fn capacity_units(&self) -> Option<f64> {
+        self.consumed_capacity.as_ref()?.capacity_units
+    }
+
+    /// Whether more results remain after this page
+    #[inline]
+    pub fn has_next_page(&self) -> bool {
+        self.last_evaluated_key.is_some()
+    }
+}
+
+/// Progress of a multi-page query hydration
+///
+/// Reported between pages by [`Query::hydrate_with_progress`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HydrationProgress {
+    /// The number of pages fetched so far
+    pub pages: u32,
+
+    /// The number of items merged into the aggregate so far
+    pub items: usize,
+
+    /// The number of items evaluated so far, before any filter is applied
+    pub scanned: usize,
+
+    /// The limit on the total number of items to be evaluated, if any
+    pub limit: Option<u32>,
+
+    /// Whether more pages remain to be fetched
+    pub has_more: bool,
+}
+
+impl HydrationProgress {
+    /// The approximate fraction of the hydration that has completed
+    ///
+    /// Completion can only be estimated when the query has a limit. Once no
+    /// more pages remain, the hydration is always considered complete.
+    pub fn approximate_completion(&self) -> Option<f64> {
+        if !self.has_more {
+            return Some(1.0);
+        }
+
+        self.limit
+            .filter(|&limit| limit > 0)
+            .map(|limit| (self.scanned as f64 / f64::from(limit)).min(1.0))
+    }
 }
 
 /// The segment of a scan operation to be performed
@@ -1874,6 +3951,276 @@ pub struct ScanSegment {
     pub total_segments: i32,
 }
 
+/// Options for collecting the results of a scan into an aggregate
+///
+/// By default, the whole index is scanned in a single segment.
+#[derive(Clone, Debug, Default)]
+#[must_use]
+pub struct ScanCollectOptions {
+    segments: u32,
+    page_size: Option<u32>,
+    item_limit: Option<usize>,
+    read_capacity_limit: Option<f64>,
+    cursor: Option<ScanCursor>,
+}
+
+impl ScanCollectOptions {
+    /// Prepares options to scan the whole index in a single segment
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Scans the index using the given number of parallel segments
+    ///
+    /// This setting is ignored when resuming from a cursor, which records
+    /// the number of segments in use.
+    pub fn segments(mut self, segments: u32) -> Self {
+        self.segments = segments;
+        self
+    }
+
+    /// Sets the maximum number of items to evaluate in each scan request
+    pub fn page_size(mut self, page_size: u32) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /// Stops requesting pages once the given number of items have been returned
+    ///
+    /// When scanning a single segment, requests are limited so that no more
+    /// than this number of items are returned, though fewer may be returned
+    /// if a filter expression is in use. With parallel segments, each
+    /// segment may return up to one page beyond the limit.
+    pub fn item_limit(mut self, limit: usize) -> Self {
+        self.item_limit = Some(limit);
+        self
+    }
+
+    /// Stops requesting pages once the given number of read capacity units
+    /// have been consumed
+    ///
+    /// The capacity consumed by a page is only known once it has been read,
+    /// so each segment may exceed the budget by up to one page.
+    pub fn read_capacity_limit(mut self, limit: f64) -> Self {
+        self.read_capacity_limit = Some(limit);
+        self
+    }
+
+    /// Resumes a scan from the cursor returned by an earlier scan
+    pub fn resume_from(mut self, cursor: ScanCursor) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
+
+    fn is_exhausted(&self, count: usize, consumed_read_capacity: f64) -> bool {
+        self.item_limit.is_some_and(|limit| count >= limit)
+            || self
+                .read_capacity_limit
+                .is_some_and(|limit| consumed_read_capacity >= limit)
+    }
+}
+
+/// The progress of each segment of a scan that stopped before reaching the
+/// end of the index
+#[derive(Clone, Debug)]
+pub struct ScanCursor {
+    segments: Vec<SegmentProgress>,
+}
+
+#[derive(Clone, Debug)]
+enum SegmentProgress {
+    Pending(Option<Item>),
+    Complete,
+}
+
+impl ScanCursor {
+    /// The number of segments the scan is divided into
+    #[inline]
+    pub fn total_segments(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// The segments that have not finished, along with the key to resume
+    /// each from, if the segment has been started
+    pub fn pending_segments(&self) -> impl Iterator<Item = (ScanSegment, Option<&Item>)> + '_ {
+        let total_segments = i32::try_from(self.segments.len()).unwrap_or(i32::MAX);
+        self.segments
+            .iter()
+            .enumerate()
+            .filter_map(move |(index, progress)| match progress {
+                SegmentProgress::Pending(start_key) => Some((
+                    ScanSegment {
+                        segment: index as i32,
+                        total_segments,
+                    },
+                    start_key.as_ref(),
+                )),
+                SegmentProgress::Complete => None,
+            })
+    }
+}
+
+/// The results of a scan collected into an aggregate
+#[derive(Debug)]
+pub struct ScanCollected<A> {
+    /// The aggregate of the items returned
+    ///
+    /// With parallel segments, items are merged in the order their pages
+    /// are returned, which is not the order of the index.
+    pub aggregate: A,
+
+    /// The number of items evaluated by the scan, before any filter
+    pub scanned: u64,
+
+    /// The number of items returned by the scan
+    pub count: u64,
+
+    /// The read capacity units consumed by the scan
+    pub consumed_read_capacity: f64,
+
+    /// The items skipped because their entity type is not part of the
+    /// aggregate's projections
+    pub skipped: instrumentation::SkippedItems,
+
+    /// The point to resume the scan from, if it stopped before reaching the
+    /// end of the index due to an item or read capacity limit
+    pub cursor: Option<ScanCursor>,
+}
+
+struct CollectState<A> {
+    aggregate: A,
+    scanned: u64,
+    count: usize,
+    consumed_read_capacity: f64,
+    skipped: instrumentation::SkippedItems,
+    segments: Vec<SegmentProgress>,
+}
+
+/// Runs a scan across its segments, reducing the items into an aggregate
+/// until the scan completes or a limit is reached
+pub(crate) async fn scan_collect<T, K, A>(
+    template: Scan<K>,
+    table: &T,
+    options: ScanCollectOptions,
+) -> Result<ScanCollected<A>, Error>
+where
+    T: Table,
+    K: keys::Key,
+    A: Aggregate,
+{
+    let segments = match &options.cursor {
+        Some(cursor) => cursor.segments.clone(),
+        None => vec![SegmentProgress::Pending(None); options.segments.max(1) as usize],
+    };
+    let total_segments = i32::try_from(segments.len()).unwrap_or(i32::MAX);
+    let template = template.for_projections::<A::Projections>();
+
+    let pending: Vec<_> = segments
+        .iter()
+        .enumerate()
+        .filter_map(|(index, progress)| match progress {
+            SegmentProgress::Pending(start_key) => Some((index, start_key.clone())),
+            SegmentProgress::Complete => None,
+        })
+        .collect();
+
+    let state = Mutex::new(CollectState {
+        aggregate: A::default(),
+        scanned: 0,
+        count: 0,
+        consumed_read_capacity: 0.0,
+        skipped: instrumentation::SkippedItems::new(),
+        segments,
+    });
+
+    let options = &options;
+    let reads = pending.into_iter().map(|(index, start_key)| {
+        let state = &state;
+        let mut scan = template.clone().set_exclusive_start_key(start_key);
+        if total_segments > 1 {
+            scan = scan.segment(ScanSegment {
+                segment: index as i32,
+                total_segments,
+            });
+        }
+
+        async move {
+            loop {
+                let remaining = {
+                    let collected = state.lock().unwrap_or_else(PoisonError::into_inner);
+                    if options.is_exhausted(collected.count, collected.consumed_read_capacity) {
+                        break;
+                    }
+                    options.item_limit.map(|limit| limit - collected.count)
+                };
+
+                let limit = match (options.page_size, remaining) {
+                    (Some(page_size), Some(remaining)) if total_segments == 1 => {
+                        Some(page_size.min(u32::try_from(remaining).unwrap_or(u32::MAX)))
+                    }
+                    (None, Some(remaining)) if total_segments == 1 => {
+                        Some(u32::try_from(remaining).unwrap_or(u32::MAX))
+                    }
+                    (page_size, _) => page_size,
+                };
+                scan = scan.set_limit(limit);
+
+                let output = scan.execute_page(table).await?;
+                let items = output.items.unwrap_or_default();
+
+                let mut collected = state.lock().unwrap_or_else(PoisonError::into_inner);
+                collected.scanned += output.scanned_count.max(0) as u64;
+                collected.count += items.len();
+                collected.consumed_read_capacity += output
+                    .consumed_capacity
+                    .as_ref()
+                    .and_then(|c| c.read_capacity_units().or(c.capacity_units()))
+                    .unwrap_or_default();
+                let aggregate = &mut collected.aggregate;
+                let (result, skipped) =
+                    instrumentation::report_skipped_items(table, "Scan", || {
+                        aggregate.reduce(items)
+                    });
+                collected.skipped.merge(&skipped);
+                result.map_err(|error| error.with_index_projection(K::DEFINITION.index_name()))?;
+
+                match output.last_evaluated_key {
+                    Some(key) => {
+                        collected.segments[index] = SegmentProgress::Pending(Some(key.clone()));
+                        scan = scan.exclusive_start_key(key);
+                    }
+                    None => {
+                        collected.segments[index] = SegmentProgress::Complete;
+                        break;
+                    }
+                }
+            }
+
+            Ok::<_, Error>(())
+        }
+    });
+
+    futures_util::future::try_join_all(reads).await?;
+
+    let state = state.into_inner().unwrap_or_else(PoisonError::into_inner);
+    let complete = state
+        .segments
+        .iter()
+        .all(|progress| matches!(progress, SegmentProgress::Complete));
+
+    Ok(ScanCollected {
+        aggregate: state.aggregate,
+        scanned: state.scanned,
+        count: state.count as u64,
+        consumed_read_capacity: state.consumed_read_capacity,
+        skipped: state.skipped,
+        cursor: (!complete).then_some(ScanCursor {
+            segments: state.segments,
+        }),
+    })
+}
+
 /// A builder for scan operations
 #[must_use]
 pub struct Scan<K> {
@@ -1884,6 +4231,9 @@ pub struct Scan<K> {
     exclusive_start_key: Option<Item>,
     projection: Option<expr::StaticProjection>,
     filter: Option<expr::Filter>,
+    timeout: Option<Duration>,
+    customization: Option<client::Customization>,
+    entity_types: Vec<&'static EntityTypeNameRef>,
     key_type: PhantomData<fn() -> K>,
 }
 
@@ -1898,6 +4248,9 @@ impl<K> fmt::Debug for Scan<K> {
             .field("exclusive_start_key", &self.exclusive_start_key)
             .field("projection", &self.projection)
             .field("filter", &self.filter)
+            .field("timeout", &self.timeout)
+            .field("customization", &self.customization)
+            .field("entity_types", &self.entity_types)
             .finish()
     }
 }
@@ -1912,6 +4265,9 @@ impl<K> Clone for Scan<K> {
             exclusive_start_key: self.exclusive_start_key.clone(),
             projection: self.projection,
             filter: self.filter.clone(),
+            timeout: self.timeout,
+            customization: self.customization.clone(),
+            entity_types: self.entity_types.clone(),
             key_type: PhantomData,
         }
     }
@@ -1934,6 +4290,9 @@ impl<K: keys::Key> Scan<K> {
             exclusive_start_key: None,
             projection: None,
             filter: None,
+            timeout: None,
+            customization: None,
+            entity_types: Vec::new(),
             key_type: PhantomData,
         }
     }
@@ -2016,52 +4375,109 @@ impl<K: keys::Key> Scan<K> {
         self
     }
 
+    /// Limit the time allowed for the request to complete
+    ///
+    /// If the timeout elapses before a response is received, the request is
+    /// canceled and a timeout error is returned, which is classified as
+    /// [`ErrorKind::Timeout`][crate::ErrorKind::Timeout].
+    ///
+    /// When the scan is paginated, the timeout applies to each page.
+    #[inline]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Customize how the request is sent by the standard SDK client
+    ///
+    /// See [`Customization`][client::Customization] for what can be
+    /// customized. The customization is not applied when the operation is
+    /// included in a transaction or a batch.
+    #[inline]
+    pub fn with_customization(mut self, customization: client::Customization) -> Self {
+        self.customization = Some(customization);
+        self
+    }
+
+    /// Stream the scanned items, fetching pages on demand
+    pub fn stream<T: Table>(self, table: &T) -> ItemStream<'_, T, K> {
+        ItemStream::scan(table, self)
+    }
+
+    #[inline]
+    pub(crate) fn for_projections<P: ProjectionSet>(mut self) -> Self {
+        self.entity_types = P::entity_types();
+        self
+    }
+
     /// Execute the scan operation against the specified table
-    pub async fn execute<T: Table>(self, table: &T) -> Result<ScanOutput, SdkError<ScanError>> {
-        let (filter_expr, filter_names, filter_values, filter_sensitive_values) = {
-            if let Some(f) = self.filter {
-                (
-                    Some(f.expression),
-                    Some(f.names),
-                    Some(f.values),
-                    Some(f.sensitive_values),
-                )
-            } else {
-                (None, None, None, None)
-            }
+    pub async fn execute<T: Table>(mut self, table: &T) -> Result<ScanOutput, SdkError<ScanError>> {
+        let filter = self.filter.take();
+        let exclusive_start_key = self.exclusive_start_key.take();
+        self.send(table, filter, exclusive_start_key).await
+    }
+
+    /// Execute the scan for a single page without consuming the scan
+    ///
+    /// The exclusive start key is taken from the scan, so that it can be
+    /// replaced with the key of the next page. The filter is cloned into
+    /// each request.
+    pub(crate) async fn execute_page<T: Table>(
+        &mut self,
+        table: &T,
+    ) -> Result<ScanOutput, SdkError<ScanError>> {
+        let exclusive_start_key = self.exclusive_start_key.take();
+        self.send(table, self.filter.clone(), exclusive_start_key)
+            .await
+    }
+
+    async fn send<T: Table>(
+        &self,
+        table: &T,
+        filter: Option<expr::Filter>,
+        exclusive_start_key: Option<Item>,
+    ) -> Result<ScanOutput, SdkError<ScanError>> {
+        let filter = unexpired_filter(table, filter);
+        if let Some(filter) = &filter {
+            filter.check().map_err(SdkError::construction_failure)?;
+        }
+
+        let (filter_expr, filter_names, filter_values, filter_sensitive_values) = match filter {
+            Some(f) => (Some(f.expression), f.names, f.values, f.sensitive_values),
+            None => Default::default(),
         };
 
-        let expression_attribute_names = self
-            .projection
-            .map(|f| f.names)
-            .into_iter()
-            .flatten()
-            .copied()
-            .map(|(l, r)| (l.to_string(), r.to_string()))
-            .chain(filter_names.into_iter().flatten())
-            .collect::<HashMap<String, String>>();
+        let projection_names = self.projection.map_or(&[][..], |p| p.names);
+
+        // Size the maps up front so that they are never rehashed while being filled
+        let mut expression_attribute_names =
+            HashMap::with_capacity(projection_names.len() + filter_names.len());
+        expression_attribute_names.extend(
+            projection_names
+                .iter()
+                .map(|(l, r)| (l.to_string(), r.to_string()))
+                .chain(filter_names),
+        );
 
-        let mut expression_attribute_values: HashMap<_, _> =
-            filter_values.unwrap_or_default().into_iter().collect();
+        let mut expression_attribute_values =
+            HashMap::with_capacity(filter_values.len() + filter_sensitive_values.len());
+        expression_attribute_values.extend(filter_values);
 
         let segment = self.segment.map(|s| s.segment);
         let total_segments = self.segment.map(|s| s.total_segments);
 
-        let span = tracing::info_span!(
-            "DynamoDB.Scan",
-            span.kind = "client",
-            db.system = "dynamodb",
-            db.operation = "Scan",
-            db.name = table.table_name(),
+        let span = operation_span!(
+            table,
+            "Scan",
             aws.dynamodb.index_name = K::DEFINITION.index_name(),
             aws.dynamodb.filter_expression = filter_expr.as_deref(),
             aws.dynamodb.projection = self.projection.map(|p| p.expression),
-            aws.dynamodb.exclusive_start_key = self.exclusive_start_key.as_ref().map(tracing::field::debug),
+            aws.dynamodb.exclusive_start_key = exclusive_start_key.as_ref().map(|key| field::display(T::redact_key(key))),
             aws.dynamodb.limit = self.limit,
             aws.dynamodb.select = self.select.as_ref().map(tracing::field::debug),
             aws.dynamodb.consistent_read = self.consistent_read,
-            aws.dynamodb.expression_attribute_names = ?expression_attribute_names,
-            aws.dynamodb.expression_attribute_values = ?expression_attribute_values,
+            aws.dynamodb.expression_attribute_names = ?instrumentation::sorted(&expression_attribute_names),
+            aws.dynamodb.expression_attribute_values = ?instrumentation::sorted(&expression_attribute_values),
             aws.dynamodb.segment = segment,
             aws.dynamodb.total_segments = total_segments,
             aws.dynamodb.consumed_read_capacity = field::Empty,
@@ -2069,20 +4485,20 @@ impl<K: keys::Key> Scan<K> {
             aws.dynamodb.count = field::Empty,
             aws.dynamodb.has_next_page = field::Empty,
         );
+        instrumentation::record_projection(&span, self.projection.as_ref());
+        instrumentation::record_entity_types(&span, self.entity_types.iter().copied());
 
-        expression_attribute_values.extend(filter_sensitive_values.into_iter().flatten());
+        expression_attribute_values.extend(filter_sensitive_values);
 
-        let result = table
-            .client()
-            .scan()
+        let input = ScanInput::builder()
             .table_name(table.table_name())
             .set_index_name(K::DEFINITION.index_name().map(|i| i.to_string()))
-            .set_select(self.select)
+            .set_select(self.select.clone())
             .set_limit(self.limit)
             .set_consistent_read(self.consistent_read.then_some(true))
             .set_segment(segment)
             .set_total_segments(total_segments)
-            .set_exclusive_start_key(self.exclusive_start_key)
+            .set_exclusive_start_key(exclusive_start_key)
             .set_projection_expression(self.projection.map(|p| p.expression.to_string()))
             .set_filter_expression(filter_expr)
             .set_expression_attribute_names(
@@ -2092,9 +4508,20 @@ impl<K: keys::Key> Scan<K> {
                 (!expression_attribute_values.is_empty()).then_some(expression_attribute_values),
             )
             .return_consumed_capacity(ReturnConsumedCapacity::Total)
-            .send()
-            .instrument(span.clone())
-            .await;
+            .build();
+        let scoped = ScopedClient::new(table);
+        let result = client::timeout(
+            &span,
+            self.timeout,
+            client::Customization::scope(
+                self.customization.clone(),
+                client::send(input, |input| scoped.scan(input)),
+            ),
+        )
+        .instrument(span.clone())
+        .await;
+
+        instrumentation::record_outcome(&span, &result);
 
         if let Ok(output) = &result {
             record_consumed_read_capacity(&span, output.consumed_capacity.as_ref());
@@ -2110,6 +4537,107 @@ impl<K: keys::Key> Scan<K> {
     }
 }
 
+/// Invalidates the cached items with the given keys
+///
+/// Items are invalidated whether or not the write succeeded, as a request
+/// that failed in transit may still have been applied.
+fn invalidate_cached<'a, T: Table>(table: &T, keys: impl IntoIterator<Item = &'a Item>) {
+    if let Some(cache) = table.item_cache() {
+        for key in keys {
+            cache.invalidate(table, key);
+        }
+    }
+}
+
+/// Extracts the primary and secondary index key attributes from an item
+fn written_key<T: Table>(item: &Item) -> Item {
+    use keys::{IndexKeys, PrimaryKey};
+
+    let () = T::IndexKeys::ASSERT_CONSISTENT;
+    let primary = T::PrimaryKey::PRIMARY_KEY_DEFINITION;
+    let attributes = std::iter::once(primary.hash_key)
+        .chain(primary.range_key)
+        .chain(
+            T::IndexKeys::KEY_DEFINITIONS
+                .iter()
+                .flat_map(|index| std::iter::once(index.hash_key()).chain(index.range_key())),
+        );
+
+    attributes
+        .filter_map(|attr| {
+            item.get_key_value(attr)
+                .map(|(name, value)| (name.clone(), value.clone()))
+        })
+        .collect()
+}
+
+/// Extracts only the table's primary key attributes from an item
+pub(crate) fn primary_key_of<T: Table>(item: &Item) -> Item {
+    use keys::PrimaryKey;
+
+    let definition = T::PrimaryKey::PRIMARY_KEY_DEFINITION;
+    std::iter::once(definition.hash_key)
+        .chain(definition.range_key)
+        .filter_map(|attr| item.get_key_value(attr))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+/// Adds a condition keeping only unexpired items to a filter, if the table
+/// excludes expired items from reads
+fn unexpired_filter<T: Table>(table: &T, filter: Option<expr::Filter>) -> Option<expr::Filter> {
+    let Some(attribute) = T::TTL_ATTRIBUTE.filter(|_| T::EXCLUDE_EXPIRED_ITEMS) else {
+        return filter;
+    };
+
+    let condition = expr::Filter::new(
+        "attribute_not_exists(#rd_ttl) OR NOT attribute_type(#rd_ttl, :rd_ttl_type) \
+         OR #rd_ttl > :rd_ttl_now",
+    );
+    let filter = match filter {
+        Some(mut filter) => {
+            filter.expression = format!("({}) AND ({})", filter.expression, condition.expression);
+            filter
+        }
+        None => condition,
+    };
+
+    let now = crate::types::Expiry::from(table.clock().now());
+    Some(
+        filter
+            .name("#rd_ttl", attribute)
+            .value(":rd_ttl_type", "N")
+            .value(":rd_ttl_now", now),
+    )
+}
+
+/// Whether the time to live of an item has passed, if the table excludes
+/// expired items from reads
+///
+/// As with DynamoDB's own expiry, and the filter added by
+/// [`unexpired_filter()`], items whose TTL attribute is missing or is not a
+/// number never expire.
+fn is_expired<T: Table>(table: &T, item: &Item) -> bool {
+    let Some(attribute) = T::TTL_ATTRIBUTE.filter(|_| T::EXCLUDE_EXPIRED_ITEMS) else {
+        return false;
+    };
+
+    match item.get(attribute) {
+        Some(AttributeValue::N(expiry)) => expiry
+            .parse::<f64>()
+            .is_ok_and(|expiry| expiry <= table.clock().now().unix_timestamp() as f64),
+        _ => false,
+    }
+}
+
+/// Treats an expired item as not found
+fn discard_expired<T: Table>(table: &T, item: &mut Option<Item>) {
+    if item.as_ref().is_some_and(|item| is_expired(table, item)) {
+        tracing::debug!("expired item treated as not found");
+        *item = None;
+    }
+}
+
 fn merge_values(l: Option<f64>, r: Option<f64>) -> Option<f64> {
     l.xor(r).or_else(|| l.zip(r).map(|(l, r)| l + r))
 }
@@ -0,0 +1,268 @@
+//! Enforcement of naming conventions for entity attributes
+//!
+//! In a single-table design, dozens of entity types may share a table, and
+//! inconsistent attribute names make the data harder to query and evolve. A
+//! [`NamingConvention`] checks the attributes projected by entity types,
+//! as listed in [`EntityDef::PROJECTED_ATTRIBUTES`], against a configured set
+//! of rules and reports any violations. Key attributes are defined by the
+//! table and are not checked.
+//!
+//! A convention is typically checked in a unit test, either for each entity
+//! registered with a table using [`table_entities!`][crate::table_entities!]
+//! or for individual entity types.
+//!
+//! ```
+//! # use modyne::{keys, Entity, EntityDef, Table};
+//! use modyne::naming::{NamingConvention, NamingViolation};
+//!
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! #[derive(EntityDef, serde::Serialize, serde::Deserialize)]
+//! struct Order {
+//!     user_name: String,
+//!     #[serde(rename = "orderId")]
+//!     order_id: String,
+//!     status: String,
+//! }
+//! # impl Entity for Order {
+//! #     type KeyInput<'a> = &'a str;
+//! #     type Table = App;
+//! #     type IndexKeys = ();
+//! #     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+//! #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+//! # }
+//!
+//! let violations = NamingConvention::new()
+//!     .snake_case()
+//!     .forbid_reserved_words()
+//!     .check_entity::<Order>();
+//!
+//! assert_eq!(violations.len(), 2);
+//! assert_eq!(
+//!     violations[0].to_string(),
+//!     "attribute `orderId` of `order` is not snake_case",
+//! );
+//! assert_eq!(
+//!     violations[1].to_string(),
+//!     "attribute `status` of `order` is a reserved word",
+//! );
+//! ```
+
+use std::fmt;
+
+use crate::{
+    expr::Projection,
+    registry::{EntityDescriptor, EntityRegistry},
+    EntityDef, EntityTypeNameRef,
+};
+
+/// A set of rules for the names of entity attributes
+///
+/// No rules are enforced by default.
+#[derive(Clone, Debug, Default)]
+#[must_use]
+pub struct NamingConvention {
+    snake_case: bool,
+    forbid_reserved_words: bool,
+    max_length: Option<usize>,
+}
+
+impl NamingConvention {
+    /// Prepares a naming convention that enforces no rules
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require attribute names to be `snake_case`
+    ///
+    /// A `snake_case` name starts with a lowercase ASCII letter or an
+    /// underscore, followed only by lowercase ASCII letters, digits, and
+    /// underscores.
+    pub fn snake_case(mut self) -> Self {
+        self.snake_case = true;
+        self
+    }
+
+    /// Forbid attribute names that are DynamoDB reserved words
+    ///
+    /// Reserved words must be substituted with placeholders whenever they
+    /// are used in an expression.
+    pub fn forbid_reserved_words(mut self) -> Self {
+        self.forbid_reserved_words = true;
+        self
+    }
+
+    /// Limit the length of attribute names, in bytes
+    ///
+    /// Attribute names count towards the size of every item, so shorter
+    /// names reduce storage and throughput costs.
+    pub fn max_length(mut self, max_length: usize) -> Self {
+        self.max_length = Some(max_length);
+        self
+    }
+
+    /// Checks the attributes of every entity type registered with a table
+    ///
+    /// Each violation is also logged as a warning.
+    pub fn check<T: EntityRegistry>(&self) -> Vec<NamingViolation> {
+        let violations: Vec<_> = T::ENTITIES
+            .iter()
+            .flat_map(|entity| self.violations(entity))
+            .collect();
+
+        for violation in &violations {
+            tracing::warn!(%violation, "attribute naming violation");
+        }
+
+        violations
+    }
+
+    /// Checks the attributes of a single entity type
+    ///
+    /// Each violation is also logged as a warning.
+    pub fn check_entity<E: EntityDef>(&self) -> Vec<NamingViolation> {
+        let violations = self.violations(&EntityDescriptor::of::<E>(std::any::type_name::<E>()));
+
+        for violation in &violations {
+            tracing::warn!(%violation, "attribute naming violation");
+        }
+
+        violations
+    }
+
+    fn violations(&self, entity: &EntityDescriptor) -> Vec<NamingViolation> {
+        let mut violations = Vec::new();
+
+        for &attribute in entity.projected_attributes() {
+            let entity_type = entity.entity_type();
+
+            if self.snake_case && !is_snake_case(attribute) {
+                violations.push(NamingViolation::NotSnakeCase {
+                    entity_type,
+                    attribute,
+                });
+            }
+
+            if self.forbid_reserved_words && Projection::is_reserved_word(attribute) {
+                violations.push(NamingViolation::ReservedWord {
+                    entity_type,
+                    attribute,
+                });
+            }
+
+            if let Some(max_length) = self.max_length.filter(|&max| attribute.len() > max) {
+                violations.push(NamingViolation::TooLong {
+                    entity_type,
+                    attribute,
+                    max_length,
+                });
+            }
+        }
+
+        violations
+    }
+}
+
+fn is_snake_case(name: &str) -> bool {
+    let mut bytes = name.bytes();
+    let starts_well = bytes
+        .next()
+        .is_some_and(|b| b.is_ascii_lowercase() || b == b'_');
+
+    starts_well && bytes.all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+}
+
+/// An attribute name that does not follow a [`NamingConvention`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NamingViolation {
+    /// The attribute name is not `snake_case`
+    NotSnakeCase {
+        /// The entity type
+        entity_type: &'static EntityTypeNameRef,
+
+        /// The attribute name
+        attribute: &'static str,
+    },
+
+    /// The attribute name is a DynamoDB reserved word
+    ReservedWord {
+        /// The entity type
+        entity_type: &'static EntityTypeNameRef,
+
+        /// The attribute name
+        attribute: &'static str,
+    },
+
+    /// The attribute name is longer than allowed
+    TooLong {
+        /// The entity type
+        entity_type: &'static EntityTypeNameRef,
+
+        /// The attribute name
+        attribute: &'static str,
+
+        /// The maximum length allowed, in bytes
+        max_length: usize,
+    },
+}
+
+impl fmt::Display for NamingViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotSnakeCase {
+                entity_type,
+                attribute,
+            } => write!(
+                f,
+                "attribute `{attribute}` of `{entity_type}` is not snake_case"
+            ),
+            Self::ReservedWord {
+                entity_type,
+                attribute,
+            } => write!(
+                f,
+                "attribute `{attribute}` of `{entity_type}` is a reserved word"
+            ),
+            Self::TooLong {
+                entity_type,
+                attribute,
+                max_length,
+            } => write!(
+                f,
+                "attribute `{attribute}` of `{entity_type}` is {} bytes, exceeding the maximum \
+                 of {max_length}",
+                attribute.len()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snake_case_names() {
+        assert!(is_snake_case("user_name"));
+        assert!(is_snake_case("_version"));
+        assert!(is_snake_case("address2"));
+        assert!(!is_snake_case("userName"));
+        assert!(!is_snake_case("2fa"));
+        assert!(!is_snake_case("user-name"));
+        assert!(!is_snake_case(""));
+    }
+
+    #[test]
+    fn reserved_words_are_case_insensitive() {
+        assert!(Projection::is_reserved_word("status"));
+        assert!(Projection::is_reserved_word("Name"));
+        assert!(!Projection::is_reserved_word("user_name"));
+        assert!(!Projection::is_reserved_word("a_very_long_attribute_name"));
+    }
+}
@@ -0,0 +1,682 @@
+//! Pagination of filtered queries
+//!
+//! DynamoDB applies a query's limit before its filter expression, so a page
+//! of a filtered query may hold far fewer matching items than requested,
+//! or none at all. A [`Paginator`] keeps fetching pages until it has found
+//! the requested number of matching items or the query is exhausted, and
+//! returns a [`Cursor`] from which the next call can continue.
+//!
+//! ```no_run
+//! use modyne::{pagination::Paginator, QueryInput};
+//! # use modyne::{expr, keys, Entity, EntityDef, Table};
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! # #[derive(Debug, EntityDef, serde::Serialize, serde::Deserialize)]
+//! # struct Order { user_id: String, order_id: String, status: String }
+//! # impl Entity for Order {
+//! #     type KeyInput<'a> = &'a str;
+//! #     type Table = App;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+//! #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+//! # }
+//!
+//! struct OpenOrders {
+//!     user_id: String,
+//! }
+//!
+//! impl QueryInput for OpenOrders {
+//!     type Index = keys::Gsi1;
+//!     type Aggregate = Vec<Order>;
+//!
+//!     fn key_condition(&self) -> expr::KeyCondition<Self::Index> {
+//!         expr::KeyCondition::in_partition(format!("USER#{}", self.user_id))
+//!     }
+//!
+//!     fn filter_expression(&self) -> Option<expr::Filter> {
+//!         Some(expr::Filter::new("#status = :open").name("#status", "status").value(":open", "OPEN"))
+//!     }
+//! }
+//!
+//! # async fn example(app: App) -> Result<(), modyne::Error> {
+//! let paginator = Paginator::new(OpenOrders { user_id: "alexdebrie".into() })
+//!     .max_capacity_units(50.0);
+//!
+//! let first = paginator.fetch(&app, 25, None).await?;
+//! if let Some(cursor) = &first.cursor {
+//!     let second = paginator.fetch(&app, 25, Some(cursor)).await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! ## Entity cursors
+//!
+//! By default, a cursor holds the key DynamoDB reports as the last one
+//! evaluated. Alternatively, a cursor can be derived from the last entity
+//! returned, recomputing its key with [`Entity::full_key()`]. Such a cursor
+//! does not depend on the key attributes stored on the item, so pagination
+//! remains stable when an index's key format changes, and the cursor can be
+//! [encoded][Cursor::encode()] as a compact, opaque string holding only the
+//! key values.
+//!
+//! ```no_run
+//! # use modyne::{expr, keys, Entity, EntityDef, QueryInput, Table};
+//! # use modyne::pagination::{Cursor, Paginator};
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! # #[derive(Debug, EntityDef, serde::Serialize, serde::Deserialize)]
+//! # struct Order { user_id: String, order_id: String, status: String }
+//! # impl Entity for Order {
+//! #     type KeyInput<'a> = &'a str;
+//! #     type Table = App;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+//! #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+//! # }
+//! # struct OpenOrders { user_id: String }
+//! # impl QueryInput for OpenOrders {
+//! #     type Index = keys::Gsi1;
+//! #     type Aggregate = Vec<Order>;
+//! #     fn key_condition(&self) -> expr::KeyCondition<Self::Index> { unimplemented!() }
+//! # }
+//! # async fn example(app: App, token: Option<&str>) -> Result<(), modyne::Error> {
+//! let paginator = Paginator::new(OpenOrders { user_id: "alexdebrie".into() })
+//!     .entity_cursors::<Order>();
+//!
+//! let cursor = token
+//!     .map(Cursor::decode::<App, keys::Gsi1>)
+//!     .transpose()?;
+//! let page = paginator.fetch(&app, 25, cursor.as_ref()).await?;
+//! let next_token = page.cursor.map(|c| c.encode::<App, keys::Gsi1>());
+//! # Ok(())
+//! # }
+//! ```
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde_json::Value;
+
+use crate::{
+    error::InvalidCursorError,
+    instrumentation::{self, SkippedItems},
+    keys::{self, PrimaryKey},
+    Aggregate, Entity, Error, Item, ProjectionExt, QueryInput, QueryInputExt, Table,
+};
+
+/// A position in the results of a query from which pagination can continue
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cursor {
+    key: Item,
+}
+
+impl Cursor {
+    /// Construct a cursor from the key of the last item read
+    ///
+    /// The key must contain the primary key attributes of the table, along
+    /// with the key attributes of the index being queried.
+    #[inline]
+    pub fn from_key(key: Item) -> Self {
+        Self { key }
+    }
+
+    /// The key of the last item read
+    #[inline]
+    pub fn key(&self) -> &Item {
+        &self.key
+    }
+
+    /// Construct a cursor positioned after the given entity in a query of
+    /// the index
+    ///
+    /// The key is recomputed from the entity, as with
+    /// [`start_key_after()`].
+    #[inline]
+    pub fn after<E: Entity, K: keys::Key>(entity: &E) -> Self {
+        Self::from_key(start_key_after::<E, K>(entity))
+    }
+
+    /// Converts the cursor into the exclusive start key of the next query
+    #[inline]
+    pub fn into_key(self) -> Item {
+        self.key
+    }
+
+    /// Encodes the cursor as an opaque, URL-safe string
+    ///
+    /// Only the values of the key attributes of the table and the index are
+    /// encoded, so the cursor must be [decoded][Self::decode()] with the
+    /// same table and index.
+    pub fn encode<T: Table, K: keys::Key>(&self) -> String {
+        let values: Vec<_> = cursor_attributes::<T, K>()
+            .map(|attr| {
+                self.key
+                    .get(attr)
+                    .and_then(|value| crate::json::typed(value).ok())
+                    .unwrap_or(Value::Null)
+            })
+            .collect();
+
+        URL_SAFE_NO_PAD.encode(Value::Array(values).to_string())
+    }
+
+    /// Decodes a cursor [encoded][Self::encode()] for the table and index
+    pub fn decode<T: Table, K: keys::Key>(encoded: &str) -> Result<Self, Error> {
+        let invalid = |reason| Error::from(InvalidCursorError { reason });
+
+        let bytes = URL_SAFE_NO_PAD
+            .decode(encoded)
+            .map_err(|_| invalid("not a URL-safe base64 string"))?;
+        let Ok(Value::Array(values)) = serde_json::from_slice(&bytes) else {
+            return Err(invalid("expected a list of key values"));
+        };
+
+        let attributes: Vec<_> = cursor_attributes::<T, K>().collect();
+        if values.len() != attributes.len() {
+            return Err(invalid("wrong number of key values for the index"));
+        }
+
+        let mut key = Item::new();
+        for (attr, value) in attributes.into_iter().zip(values) {
+            if value.is_null() {
+                continue;
+            }
+            let value =
+                crate::json::from_typed(value).map_err(|_| invalid("malformed key value"))?;
+            key.insert(attr.to_string(), value);
+        }
+
+        Ok(Self { key })
+    }
+}
+
+/// Builds the exclusive start key that continues a query of the index after
+/// the given entity
+///
+/// The key is recomputed from the entity's [`full_key()`][Entity::full_key()]
+/// and [`entity_type_index_key()`][Entity::entity_type_index_key()] rather
+/// than read from a stored item, and holds the primary key attributes of the
+/// table along with the key attributes of the index.
+pub fn start_key_after<E: Entity, K: keys::Key>(entity: &E) -> Item {
+    cursor_key::<E::Table, K>(&crate::computed_keys(entity))
+}
+
+/// A page of matching entities
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Page<A> {
+    /// The aggregate of the matching items
+    pub aggregate: A,
+
+    /// The number of matching items read into the aggregate
+    pub count: usize,
+
+    /// The position from which to fetch the next page, if more results may remain
+    pub cursor: Option<Cursor>,
+
+    /// The number of query requests made to fill the page
+    pub requests: u32,
+
+    /// The read capacity units consumed filling the page
+    pub consumed_capacity_units: f64,
+
+    /// The items skipped because their entity type is not part of the
+    /// aggregate's projections
+    pub skipped: SkippedItems,
+}
+
+type EntityCursor = fn(&Item) -> Result<Option<Item>, Error>;
+
+/// Fetches pages holding a requested number of matching entities
+///
+/// When the query has a filter expression, the items are read without a
+/// projection expression, so that a cursor can be derived from the key of
+/// the last matching item. Read capacity is consumed by the full size of
+/// each item whether or not it is projected, so this only increases the
+/// amount of data transferred.
+#[derive(Debug)]
+pub struct Paginator<Q> {
+    input: Q,
+    page_size: Option<u32>,
+    max_capacity_units: Option<f64>,
+    entity_cursor: Option<EntityCursor>,
+}
+
+impl<Q> Paginator<Q>
+where
+    Q: QueryInput,
+{
+    /// Prepares to paginate the results of the query
+    pub fn new(input: Q) -> Self {
+        Self {
+            input,
+            page_size: None,
+            max_capacity_units: None,
+            entity_cursor: None,
+        }
+    }
+
+    /// Limits the number of items evaluated by each query request
+    ///
+    /// By default, each request evaluates items until DynamoDB's 1 MB
+    /// response limit is reached.
+    pub fn page_size(mut self, page_size: u32) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    /// Stops fetching once the read capacity consumed in a single call
+    /// reaches the given number of units
+    ///
+    /// The check is made after each request, so a call may exceed the bound
+    /// by the capacity consumed by one request. Use
+    /// [`page_size()`][Self::page_size()] to limit the capacity consumed by
+    /// each request. The page returned may then hold fewer entities than
+    /// requested while still having a cursor.
+    pub fn max_capacity_units(mut self, units: f64) -> Self {
+        self.max_capacity_units = Some(units);
+        self
+    }
+
+    /// Derives cursors from the last entity returned, rather than from the
+    /// last key evaluated by DynamoDB
+    ///
+    /// Each cursor is positioned after the last item read, deserialized as
+    /// the entity type, using the key recomputed by
+    /// [`start_key_after()`]. Items are read without a projection
+    /// expression, so that the full entity can be deserialized. If the last
+    /// item read is of another entity type, the cursor is positioned after
+    /// its stored key attributes instead. If a request returns no items but
+    /// more may remain, the last evaluated key is used.
+    pub fn entity_cursors<E>(mut self) -> Self
+    where
+        E: Entity + serde::de::DeserializeOwned,
+    {
+        self.entity_cursor = Some(entity_cursor_key::<E, Q::Index>);
+        self
+    }
+
+    /// The query being paginated
+    #[inline]
+    pub fn input(&self) -> &Q {
+        &self.input
+    }
+
+    /// Fetches up to `count` matching entities, continuing from the cursor
+    /// if one is given
+    pub async fn fetch<T: Table>(
+        &self,
+        table: &T,
+        count: usize,
+        cursor: Option<&Cursor>,
+    ) -> Result<Page<Q::Aggregate>, Error> {
+        let filtered = self.input.filter_expression().is_some();
+        let mut query = self.input.query();
+        if filtered || self.entity_cursor.is_some() {
+            query = query.set_projection(None);
+        }
+        query = query.set_exclusive_start_key(cursor.map(|c| c.key.clone()));
+
+        let mut page = Page {
+            aggregate: Q::Aggregate::default(),
+            count: 0,
+            cursor: cursor.cloned(),
+            requests: 0,
+            consumed_capacity_units: 0.0,
+            skipped: SkippedItems::new(),
+        };
+
+        while page.count < count {
+            let remaining = (count - page.count).min(u32::MAX as usize) as u32;
+            let limit = if filtered {
+                self.page_size
+            } else {
+                Some(self.page_size.map_or(remaining, |size| size.min(remaining)))
+            };
+            query = query.set_limit(limit);
+
+            let output = query.execute_page(table).await?;
+            page.requests += 1;
+            page.consumed_capacity_units += output
+                .consumed_capacity()
+                .and_then(|c| c.capacity_units)
+                .unwrap_or_default();
+
+            let mut items = output.items.unwrap_or_default();
+            let truncated = items.len() > count - page.count;
+            items.truncate(count - page.count);
+            page.cursor = match (self.entity_cursor, items.last()) {
+                (Some(derive), Some(last)) if truncated || output.last_evaluated_key.is_some() => {
+                    let key = derive(last)?.unwrap_or_else(|| cursor_key::<T, Q::Index>(last));
+                    Some(Cursor::from_key(key))
+                }
+                (None, Some(last)) if truncated => {
+                    Some(Cursor::from_key(cursor_key::<T, Q::Index>(last)))
+                }
+                _ => output.last_evaluated_key.map(Cursor::from_key),
+            };
+
+            page.count += items.len();
+            let aggregate = &mut page.aggregate;
+            let (result, skipped) =
+                instrumentation::report_skipped_items(table, "Query", || aggregate.reduce(items));
+            page.skipped.merge(&skipped);
+            result.map_err(|error| {
+                error.with_index_projection(<Q::Index as keys::Key>::DEFINITION.index_name())
+            })?;
+
+            let over_budget = self
+                .max_capacity_units
+                .is_some_and(|max| page.consumed_capacity_units >= max);
+            match &page.cursor {
+                Some(cursor) if !over_budget => {
+                    query = query.exclusive_start_key(cursor.key.clone());
+                }
+                _ => break,
+            }
+        }
+
+        Ok(page)
+    }
+}
+
+/// Extracts the attributes of an item needed to continue a query after it
+fn cursor_key<T: Table, K: keys::Key>(item: &Item) -> Item {
+    cursor_attributes::<T, K>()
+        .filter_map(|attr| item.get_key_value(attr))
+        .map(|(name, value)| (name.clone(), value.clone()))
+        .collect()
+}
+
+/// Recomputes the cursor key for an item from the entity it holds, or
+/// returns `None` if the item holds another type of entity
+fn entity_cursor_key<E, K>(item: &Item) -> Result<Option<Item>, Error>
+where
+    E: Entity + serde::de::DeserializeOwned,
+    K: keys::Key,
+{
+    let entity_type = crate::__private::get_table_entity_type::<E::Table>(item)?;
+    if entity_type != E::ENTITY_TYPE {
+        return Ok(None);
+    }
+
+    let entity = E::from_item(item.clone())?;
+    Ok(Some(start_key_after::<E, K>(&entity)))
+}
+
+/// The key attributes of the table and index, in a stable order and without
+/// repetition
+fn cursor_attributes<T: Table, K: keys::Key>() -> impl Iterator<Item = &'static str> {
+    let primary = T::PrimaryKey::PRIMARY_KEY_DEFINITION;
+    let index = K::DEFINITION;
+
+    let mut attributes = Vec::with_capacity(4);
+    for attr in std::iter::once(primary.hash_key)
+        .chain(primary.range_key)
+        .chain(std::iter::once(index.hash_key()))
+        .chain(index.range_key())
+    {
+        if !attributes.contains(&attr) {
+            attributes.push(attr);
+        }
+    }
+    attributes.into_iter()
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_dynamodb::operation::query::QueryOutput;
+
+    use super::*;
+    use crate::{
+        mock::{ops, MockTable},
+        AttributeValue, EntityExt, RawItem,
+    };
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = (keys::Gsi1, keys::EntityTypeIndex);
+
+        fn table_name(&self) -> &str {
+            unimplemented!()
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    #[test]
+    fn cursor_key_includes_table_and_index_keys() {
+        let item: Item = [
+            ("PK", "CUSTOMER#alexdebrie"),
+            ("SK", "#ORDER#1234"),
+            ("GSI1PK", "ORDER#1234"),
+            ("GSI1SK", "ORDER#1234"),
+            ("status", "OPEN"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), AttributeValue::S(v.to_string())))
+        .collect();
+
+        let key = cursor_key::<TestTable, keys::Gsi1>(&item);
+        assert_eq!(key.len(), 4);
+        assert!(!key.contains_key("status"));
+
+        let key = cursor_key::<TestTable, keys::Primary>(&item);
+        assert_eq!(key.len(), 2);
+        assert_eq!(key.get("SK"), item.get("SK"));
+    }
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    struct Order {
+        user_id: String,
+        order_id: String,
+    }
+
+    impl crate::EntityDef for Order {
+        const ENTITY_TYPE: &'static crate::EntityTypeNameRef =
+            crate::EntityTypeNameRef::from_static("order");
+    }
+
+    impl Entity for Order {
+        type KeyInput<'a> = &'a str;
+        type Table = TestTable;
+        type IndexKeys = keys::Gsi1;
+
+        fn primary_key(order_id: Self::KeyInput<'_>) -> keys::Primary {
+            keys::Primary {
+                hash: format!("ORDER#{order_id}"),
+                range: format!("ORDER#{order_id}"),
+            }
+        }
+
+        fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+            keys::FullKey {
+                primary: Self::primary_key(&self.order_id),
+                indexes: keys::Gsi1 {
+                    hash: format!("CUSTOMER#{}", self.user_id),
+                    range: format!("#ORDER#{}", self.order_id),
+                },
+            }
+        }
+
+        fn entity_type_index_key(&self) -> Option<String> {
+            Some(self.order_id.clone())
+        }
+    }
+
+    #[test]
+    fn start_key_after_recomputes_the_index_key() {
+        let order = Order {
+            user_id: "alexdebrie".to_string(),
+            order_id: "1234".to_string(),
+        };
+
+        let key = start_key_after::<_, keys::Gsi1>(&order);
+        assert_eq!(key.len(), 4);
+        assert_eq!(
+            key.get("GSI1PK"),
+            Some(&AttributeValue::S("CUSTOMER#alexdebrie".to_string()))
+        );
+
+        let key = start_key_after::<_, keys::Primary>(&order);
+        assert_eq!(key.len(), 2);
+    }
+
+    #[test]
+    fn start_key_after_includes_the_entity_type_index_key() {
+        let order = Order {
+            user_id: "alexdebrie".to_string(),
+            order_id: "1234".to_string(),
+        };
+
+        let key = start_key_after::<_, keys::EntityTypeIndex>(&order);
+        assert_eq!(key.len(), 4);
+        assert_eq!(
+            key.get("ETPK"),
+            Some(&AttributeValue::S("order".to_string()))
+        );
+        assert_eq!(
+            key.get("ETSK"),
+            Some(&AttributeValue::S("1234".to_string()))
+        );
+    }
+
+    #[test]
+    fn encoded_cursors_round_trip() {
+        let order = Order {
+            user_id: "alexdebrie".to_string(),
+            order_id: "1234".to_string(),
+        };
+        let cursor = Cursor::after::<_, keys::Gsi1>(&order);
+
+        let encoded = cursor.encode::<TestTable, keys::Gsi1>();
+        assert!(!encoded.contains("GSI1PK"));
+        let decoded = Cursor::decode::<TestTable, keys::Gsi1>(&encoded).unwrap();
+        assert_eq!(decoded, cursor);
+
+        assert!(Cursor::decode::<TestTable, keys::Primary>(&encoded).is_err());
+        assert!(Cursor::decode::<TestTable, keys::Gsi1>("not a cursor!").is_err());
+    }
+
+    struct OrdersAndCustomers;
+
+    impl QueryInput for OrdersAndCustomers {
+        type Index = keys::Gsi1;
+        type Aggregate = Vec<RawItem<TestTable>>;
+
+        fn key_condition(&self) -> crate::expr::KeyCondition<Self::Index> {
+            crate::expr::KeyCondition::in_partition("CUSTOMER#alexdebrie")
+        }
+    }
+
+    struct Orders;
+
+    impl QueryInput for Orders {
+        type Index = keys::Gsi1;
+        type Aggregate = Vec<Order>;
+
+        fn key_condition(&self) -> crate::expr::KeyCondition<Self::Index> {
+            crate::expr::KeyCondition::in_partition("CUSTOMER#alexdebrie")
+        }
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn pages_missing_unprojected_attributes_name_the_index() {
+        let mut order = Order {
+            user_id: "alexdebrie".to_string(),
+            order_id: "1234".to_string(),
+        }
+        .into_item();
+        order.remove("user_id");
+
+        let table = MockTable::<TestTable>::new("test");
+        table.expect::<ops::Query>(|e| {
+            e.times(1)
+                .returning(QueryOutput::builder().items(order).build())
+        });
+
+        let error = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(Paginator::new(Orders).fetch(&table, 1, None))
+            .unwrap_err();
+
+        let source = std::error::Error::source(&error).unwrap();
+        assert!(
+            source
+                .to_string()
+                .starts_with("item read through index `GSI1` is missing attribute `user_id`"),
+            "{source}"
+        );
+    }
+
+    #[test]
+    fn entity_cursors_use_the_stored_key_of_other_entity_types() {
+        let item = |attrs: &[(&str, &str)]| -> Item {
+            attrs
+                .iter()
+                .map(|(k, v)| (k.to_string(), AttributeValue::S(v.to_string())))
+                .collect()
+        };
+        let order = item(&[
+            ("PK", "ORDER#1234"),
+            ("SK", "ORDER#1234"),
+            ("GSI1PK", "CUSTOMER#alexdebrie"),
+            ("GSI1SK", "#ORDER#1234"),
+            ("entity_type", "order"),
+            ("user_id", "alexdebrie"),
+            ("order_id", "1234"),
+        ]);
+        let customer = item(&[
+            ("PK", "CUSTOMER#alexdebrie"),
+            ("SK", "CUSTOMER#alexdebrie"),
+            ("GSI1PK", "CUSTOMER#alexdebrie"),
+            ("GSI1SK", "CUSTOMER#alexdebrie"),
+            ("entity_type", "customer"),
+            ("name", "Alex"),
+        ]);
+
+        assert!(entity_cursor_key::<Order, keys::Gsi1>(&customer)
+            .unwrap()
+            .is_none());
+
+        let table = MockTable::<TestTable>::new("test");
+        let last_evaluated_key = cursor_key::<TestTable, keys::Gsi1>(&customer);
+        table.expect::<ops::Query>(|e| {
+            e.times(1).returning(
+                QueryOutput::builder()
+                    .items(order.clone())
+                    .items(customer.clone())
+                    .set_last_evaluated_key(Some(last_evaluated_key.clone()))
+                    .build(),
+            )
+        });
+
+        let page = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(
+                Paginator::new(OrdersAndCustomers)
+                    .entity_cursors::<Order>()
+                    .fetch(&table, 2, None),
+            )
+            .unwrap();
+
+        assert_eq!(page.count, 2);
+        assert_eq!(page.cursor.unwrap().key(), &last_evaluated_key);
+    }
+}
@@ -0,0 +1,316 @@
+//! Retrying reads until a recent write becomes visible
+//!
+//! Reads through a global secondary index are always eventually consistent,
+//! so an item written a moment ago may not yet be returned by a query
+//! against the index. [`ReadAfterWrite`] repeats a read a bounded number of
+//! times, backing off exponentially between attempts, until the expected
+//! item appears. This is mostly useful in tests and in workflows that write
+//! an item and then immediately read it back through an index.
+//!
+//! ```no_run
+//! # use modyne::{keys, Entity, EntityDef, EntityExt, EntityTypeNameRef, Table, WritableTable};
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! # impl WritableTable for App {}
+//! # #[derive(serde::Serialize)]
+//! # struct Order { id: String, customer: String }
+//! # impl EntityDef for Order {
+//! #     const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("order");
+//! # }
+//! # impl Entity for Order {
+//! #     type KeyInput<'a> = &'a str;
+//! #     type Table = App;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+//! #         keys::Primary { hash: format!("ORDER#{id}"), range: "ORDER".into() }
+//! #     }
+//! #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+//! #         unimplemented!()
+//! #     }
+//! # }
+//! use modyne::{
+//!     expr::KeyCondition, keys::PrimaryKey, model::Query, read_after_write::ReadAfterWrite,
+//! };
+//!
+//! # async fn example(app: App) -> Result<(), modyne::Error> {
+//! let order = Order { id: "1234".into(), customer: "alice".into() };
+//! order.create().execute(&app).await?;
+//!
+//! let key = Order::primary_key("1234").into_key();
+//! let query = Query::<keys::Gsi1>::new(KeyCondition::in_partition("CUSTOMER#alice"));
+//! let item = ReadAfterWrite::new().query(&app, query, &key).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Delays are taken from the [clock][crate::Table::clock()] of the table.
+//! With a [`FixedClock`][crate::clock::FixedClock], the clock is advanced
+//! instead, so that tests run without waiting.
+
+use std::{future::Future, time::Duration};
+
+use crate::{
+    error::ReadAfterWriteError,
+    keys,
+    model::{Get, Query},
+    Error, Item, Table,
+};
+
+/// Repeats a read until a recently written item is visible
+#[derive(Clone, Copy, Debug)]
+#[must_use]
+pub struct ReadAfterWrite {
+    attempts: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+}
+
+impl Default for ReadAfterWrite {
+    fn default() -> Self {
+        Self {
+            attempts: 8,
+            initial_delay: Duration::from_millis(20),
+            max_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl ReadAfterWrite {
+    /// Retries up to 8 times, starting with a 20 millisecond delay that
+    /// doubles after each attempt, up to one second
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum number of reads, including the first
+    ///
+    /// At least one read is always made.
+    pub fn attempts(mut self, attempts: u32) -> Self {
+        self.attempts = attempts;
+        self
+    }
+
+    /// Sets the delay after the first unsuccessful read
+    pub fn initial_delay(mut self, delay: Duration) -> Self {
+        self.initial_delay = delay;
+        self
+    }
+
+    /// Sets the upper bound for the delay between reads
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// The delay to wait after the given unsuccessful attempt
+    fn delay(&self, attempt: u32) -> Duration {
+        self.initial_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay)
+    }
+
+    /// Repeats the read until it produces a value
+    ///
+    /// The read returns `Ok(None)` while the expected write is not yet
+    /// visible. Errors returned by the read are not retried.
+    pub async fn until<T, F, Fut, R>(&self, table: &T, mut read: F) -> Result<R, Error>
+    where
+        T: Table,
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Option<R>, Error>>,
+    {
+        let attempts = self.attempts.max(1);
+        let mut attempt = 1;
+        loop {
+            if let Some(value) = read().await? {
+                return Ok(value);
+            }
+
+            if attempt >= attempts {
+                return Err(ReadAfterWriteError { attempts }.into());
+            }
+
+            let delay = self.delay(attempt);
+            tracing::debug!(
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                "expected item not yet visible, retrying"
+            );
+            table.clock().sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Repeats a get until the item exists, returning the item
+    pub async fn get<T: Table>(&self, table: &T, op: Get) -> Result<Item, Error> {
+        let op = &op;
+        self.until(table, || async move {
+            let output = op.clone().execute(table).await?;
+            Ok::<_, Error>(output.item)
+        })
+        .await
+    }
+
+    /// Repeats a query until it returns an item with the expected key,
+    /// returning that item
+    ///
+    /// An item matches when it holds every attribute in `expected_key` with
+    /// the same value, so the primary key of the written item is usually
+    /// sufficient. All pages of the query are read on each attempt.
+    pub async fn query<T: Table, K: keys::Key>(
+        &self,
+        table: &T,
+        query: Query<K>,
+        expected_key: &Item,
+    ) -> Result<Item, Error> {
+        let query = &query;
+        self.until(table, || async move {
+            let mut query = query.clone();
+            loop {
+                let output = query.execute_page(table).await.map_err(Error::from)?;
+                let found = output
+                    .items
+                    .into_iter()
+                    .flatten()
+                    .find(|item| matches_key(item, expected_key));
+                if found.is_some() {
+                    return Ok::<_, Error>(found);
+                }
+
+                match output.last_evaluated_key {
+                    Some(key) => query = query.exclusive_start_key(key),
+                    None => return Ok(None),
+                }
+            }
+        })
+        .await
+    }
+}
+
+fn matches_key(item: &Item, key: &Item) -> bool {
+    key.iter()
+        .all(|(name, value)| item.get(name) == Some(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    use aws_sdk_dynamodb::operation::{get_item::GetItemOutput, query::QueryOutput};
+
+    use super::*;
+    use crate::{
+        clock::FixedClock,
+        expr,
+        mock::{ops, MockTable},
+        AttributeValue,
+    };
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            unimplemented!()
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    fn key(pk: &str) -> Item {
+        [
+            ("PK".to_string(), AttributeValue::S(pk.into())),
+            ("SK".to_string(), AttributeValue::S("ORDER".into())),
+        ]
+        .into()
+    }
+
+    fn start() -> time::OffsetDateTime {
+        time::OffsetDateTime::from_unix_timestamp(1_701_950_400).unwrap()
+    }
+
+    #[test]
+    fn delays_double_up_to_the_maximum() {
+        let retry = ReadAfterWrite::new()
+            .initial_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_millis(300));
+
+        assert_eq!(retry.delay(1), Duration::from_millis(100));
+        assert_eq!(retry.delay(2), Duration::from_millis(200));
+        assert_eq!(retry.delay(3), Duration::from_millis(300));
+        assert_eq!(retry.delay(40), Duration::from_millis(300));
+    }
+
+    #[test]
+    fn queries_are_retried_until_the_expected_key_appears() {
+        let table = MockTable::<TestTable>::new("test").with_clock(FixedClock::new(start()));
+        let calls = Arc::new(AtomicU32::new(0));
+        let counter = calls.clone();
+        table.expect::<ops::Query>(move |e| {
+            e.responding(move |_| {
+                let items = if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                    vec![key("ORDER#1")]
+                } else {
+                    vec![key("ORDER#1"), key("ORDER#2")]
+                };
+                Ok(QueryOutput::builder().set_items(Some(items)).build())
+            })
+        });
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let item = rt
+            .block_on(ReadAfterWrite::new().query(
+                &table,
+                Query::new(expr::KeyCondition::<keys::Gsi1>::in_partition("CUSTOMER#1")),
+                &key("ORDER#2"),
+            ))
+            .unwrap();
+
+        assert_eq!(item, key("ORDER#2"));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(
+            table.clock().now() - start(),
+            Duration::from_millis(20 + 40)
+        );
+    }
+
+    #[test]
+    fn gets_fail_after_exhausting_all_attempts() {
+        let table = MockTable::<TestTable>::new("test").with_clock(FixedClock::new(start()));
+        table.expect::<ops::GetItem>(|e| {
+            e.times(3)
+                .responding(|_| Ok(GetItemOutput::builder().build()))
+        });
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let error = rt
+            .block_on(
+                ReadAfterWrite::new()
+                    .attempts(3)
+                    .get(&table, Get::new(key("ORDER#1"))),
+            )
+            .unwrap_err();
+
+        assert_eq!(
+            std::error::Error::source(&error).unwrap().to_string(),
+            "expected item was not visible after 3 attempts"
+        );
+        table.verify();
+    }
+}
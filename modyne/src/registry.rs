@@ -0,0 +1,395 @@
+//! Registration of the entity types stored in a table
+//!
+//! The [`table_entities!`][crate::table_entities!] macro registers the
+//! entity types stored in a table by implementing [`EntityRegistry`] for the
+//! table. The registry enables features that apply across the whole table:
+//!
+//! * [`EntityRegistry::scan_registered()`] prepares a scan that only returns
+//!   items of the registered entity types, projecting the attributes used by
+//!   those entities,
+//! * [`EntityRegistry::schema()`] describes the table's keys, indexes, and
+//!   entity types, which can be serialized for documentation or tooling, and
+//! * [`EntityRegistry::validate()`] checks that no two registered entities
+//!   share an entity type name.
+//!
+//! ```
+//! use modyne::{keys, registry::EntityRegistry, Entity, EntityDef, Table};
+//!
+//! struct App;
+//!
+//! impl Table for App {
+//!     type PrimaryKey = keys::Primary;
+//!     type IndexKeys = keys::Gsi1;
+//!     fn table_name(&self) -> &str { unimplemented!() }
+//!     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! }
+//!
+//! #[derive(EntityDef, serde::Serialize, serde::Deserialize)]
+//! struct Customer {
+//!     user_name: String,
+//! }
+//!
+//! # impl Entity for Customer {
+//! #     type KeyInput<'a> = &'a str;
+//! #     type Table = App;
+//! #     type IndexKeys = ();
+//! #     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+//! #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+//! # }
+//! #[derive(EntityDef, serde::Serialize, serde::Deserialize)]
+//! struct Order {
+//!     user_name: String,
+//!     order_id: String,
+//! }
+//!
+//! # impl Entity for Order {
+//! #     type KeyInput<'a> = &'a str;
+//! #     type Table = App;
+//! #     type IndexKeys = ();
+//! #     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+//! #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+//! # }
+//! modyne::table_entities!(App => [Customer, Order]);
+//!
+//! App::validate().unwrap();
+//! assert_eq!(App::ENTITIES.len(), 2);
+//! assert!(App::descriptor(Order::ENTITY_TYPE).is_some());
+//! ```
+
+use crate::{
+    error::DuplicateEntityTypeError, expr, keys, model::Scan, EntityDef, EntityDiscriminator,
+    EntityTypeNameRef, Error, Table,
+};
+
+/// Registers the entity types stored in a table
+///
+/// This implements [`EntityRegistry`] for the table. Each entity type must
+/// be stored in the table.
+///
+/// ```ignore
+/// modyne::table_entities!(App => [Customer, CustomerEmail, Order, OrderItem]);
+/// ```
+#[macro_export]
+macro_rules! table_entities {
+    ($table:ty => [$($entity:ty),* $(,)?]) => {
+        impl $crate::registry::EntityRegistry for $table {
+            const ENTITIES: &'static [$crate::registry::EntityDescriptor] = &[
+                $(
+                    $crate::registry::EntityDescriptor::of::<$entity>(
+                        ::std::stringify!($entity),
+                    ),
+                )*
+            ];
+
+            fn projection_expression() -> ::std::option::Option<$crate::expr::StaticProjection> {
+                static PROJECTION_ONCE: $crate::__private::OnceLock<
+                    ::std::option::Option<$crate::expr::StaticProjection>,
+                > = $crate::__private::OnceLock::new();
+
+                *PROJECTION_ONCE.get_or_init(|| {
+                    $crate::__private::generate_projection_expression::<$table>(&[
+                        $($crate::__private::projected_attributes::<$entity>(
+                            <$entity as $crate::EntityDef>::PROJECTED_ATTRIBUTES,
+                        ),)*
+                    ])
+                })
+            }
+        }
+
+        const _: fn() = || {
+            fn assert_entity_is_stored_in_table<E: $crate::Entity<Table = $table>>() {}
+            $(assert_entity_is_stored_in_table::<$entity>();)*
+        };
+    };
+}
+
+/// A description of an entity type registered with a table
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize)]
+pub struct EntityDescriptor {
+    #[serde(skip)]
+    name: &'static str,
+    entity_type: &'static EntityTypeNameRef,
+    projected_attributes: &'static [&'static str],
+    unique_attributes: &'static [&'static str],
+}
+
+impl EntityDescriptor {
+    /// Describes the entity, which is identified in diagnostics by the given name
+    pub const fn of<E: EntityDef>(name: &'static str) -> Self {
+        Self {
+            name,
+            entity_type: E::ENTITY_TYPE,
+            projected_attributes: E::PROJECTED_ATTRIBUTES,
+            unique_attributes: E::UNIQUE_ATTRIBUTES,
+        }
+    }
+
+    /// The name of the Rust type of the entity
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The entity type name
+    #[inline]
+    pub fn entity_type(&self) -> &'static EntityTypeNameRef {
+        self.entity_type
+    }
+
+    /// The attributes projected into the entity
+    #[inline]
+    pub fn projected_attributes(&self) -> &'static [&'static str] {
+        self.projected_attributes
+    }
+
+    /// The attributes whose values are unique across entities of this type
+    #[inline]
+    pub fn unique_attributes(&self) -> &'static [&'static str] {
+        self.unique_attributes
+    }
+}
+
+/// The entity types stored in a table
+///
+/// This trait is implemented using the [`table_entities!`][crate::table_entities!] macro.
+pub trait EntityRegistry: Table {
+    /// The registered entity types
+    const ENTITIES: &'static [EntityDescriptor];
+
+    /// The projection expression covering the attributes of all registered
+    /// entity types
+    ///
+    /// Returns `None` if any registered entity type projects all attributes.
+    fn projection_expression() -> Option<expr::StaticProjection>;
+
+    /// Looks up a registered entity type by name
+    fn descriptor(entity_type: &EntityTypeNameRef) -> Option<&'static EntityDescriptor> {
+        Self::ENTITIES
+            .iter()
+            .find(|entity| entity.entity_type == entity_type)
+    }
+
+    /// Checks that no two registered entities share an entity type name
+    fn validate() -> Result<(), Error> {
+        for (index, entity) in Self::ENTITIES.iter().enumerate() {
+            if let Some(other) = Self::ENTITIES[..index]
+                .iter()
+                .find(|other| other.entity_type == entity.entity_type)
+            {
+                return Err(DuplicateEntityTypeError {
+                    entity_type: entity.entity_type,
+                    first: other.name,
+                    second: entity.name,
+                }
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Prepares a scan of the table that only returns items of the registered
+    /// entity types
+    ///
+    /// When the table stores the entity type in an
+    /// [attribute][EntityDiscriminator::Attribute], items of other entity
+    /// types are excluded by a filter expression. A filter expression can
+    /// compare against at most 100 values, so no filter is applied if more
+    /// entity types are registered. The items returned should still be read
+    /// using a [`ProjectionSet`][crate::ProjectionSet], which skips items of
+    /// unknown entity types.
+    fn scan_registered() -> Scan<Self::PrimaryKey>
+    where
+        Self::PrimaryKey: keys::Key,
+    {
+        let mut scan = Scan::new();
+
+        if let Some(projection) = Self::projection_expression() {
+            scan = scan.projection(projection);
+        }
+
+        let count = Self::ENTITIES.len();
+        if let EntityDiscriminator::Attribute = Self::ENTITY_DISCRIMINATOR {
+            if (1..=100).contains(&count) {
+                let placeholders: Vec<_> = (0..count).map(|i| format!(":et{i}")).collect();
+                let filter = expr::Filter::new(format!("#et IN ({})", placeholders.join(", ")))
+                    .name("#et", Self::ENTITY_TYPE_ATTRIBUTE);
+                let filter = Self::ENTITIES.iter().zip(&placeholders).fold(
+                    filter,
+                    |filter, (entity, placeholder)| {
+                        filter
+                            .raw_value(placeholder, Self::serialize_entity_type(entity.entity_type))
+                    },
+                );
+                scan = scan.filter(filter);
+            }
+        }
+
+        scan
+    }
+
+    /// Describes the keys, indexes, and registered entity types of the table
+    fn schema() -> TableSchema {
+        use keys::{IndexKeys, PrimaryKey};
+
+        let primary = Self::PrimaryKey::PRIMARY_KEY_DEFINITION;
+        TableSchema {
+            hash_key: primary.hash_key,
+            range_key: primary.range_key,
+            entity_type_attribute: Self::ENTITY_TYPE_ATTRIBUTE,
+            indexes: Self::IndexKeys::KEY_DEFINITIONS
+                .iter()
+                .map(|index| IndexSchema {
+                    index_name: index.index_name(),
+                    global: matches!(index, keys::SecondaryIndexDefinition::Global(_)),
+                    hash_key: index.hash_key(),
+                    range_key: index.range_key(),
+                })
+                .collect(),
+            entities: Self::ENTITIES,
+        }
+    }
+}
+
+/// A description of a table and the entity types stored in it
+///
+/// Created by [`EntityRegistry::schema()`].
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+#[non_exhaustive]
+pub struct TableSchema {
+    /// The name of the partition key attribute
+    pub hash_key: &'static str,
+
+    /// The name of the sort key attribute, if any
+    pub range_key: Option<&'static str>,
+
+    /// The name of the attribute storing the entity type
+    pub entity_type_attribute: &'static str,
+
+    /// The secondary indexes of the table
+    pub indexes: Vec<IndexSchema>,
+
+    /// The registered entity types
+    pub entities: &'static [EntityDescriptor],
+}
+
+/// A description of a secondary index
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize)]
+#[non_exhaustive]
+pub struct IndexSchema {
+    /// The name of the index
+    pub index_name: &'static str,
+
+    /// Whether the index is a global secondary index
+    pub global: bool,
+
+    /// The name of the partition key attribute
+    pub hash_key: &'static str,
+
+    /// The name of the sort key attribute, if any
+    pub range_key: Option<&'static str>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Entity;
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            unimplemented!()
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    macro_rules! test_entity {
+        ($name:ident, $entity_type:literal, $attrs:expr) => {
+            struct $name;
+
+            impl EntityDef for $name {
+                const ENTITY_TYPE: &'static EntityTypeNameRef =
+                    EntityTypeNameRef::from_static($entity_type);
+                const PROJECTED_ATTRIBUTES: &'static [&'static str] = $attrs;
+            }
+
+            impl Entity for $name {
+                type KeyInput<'a> = ();
+                type Table = TestTable;
+                type IndexKeys = ();
+
+                fn primary_key(_: Self::KeyInput<'_>) -> keys::Primary {
+                    unimplemented!()
+                }
+
+                fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+                    unimplemented!()
+                }
+            }
+        };
+    }
+
+    test_entity!(Customer, "customer", &["user_name", "email"]);
+    test_entity!(Order, "order", &["user_name", "order_id"]);
+    test_entity!(LegacyOrder, "order", &["order_id"]);
+
+    crate::table_entities!(TestTable => [Customer, Order]);
+
+    #[test]
+    fn registered_entities_are_described() {
+        assert!(TestTable::validate().is_ok());
+        assert_eq!(
+            TestTable::descriptor(EntityTypeNameRef::from_static("order")).map(|e| e.name()),
+            Some("Order")
+        );
+
+        let schema = TestTable::schema();
+        assert_eq!(schema.hash_key, "PK");
+        assert_eq!(schema.indexes.len(), 1);
+        assert_eq!(schema.indexes[0].index_name, "GSI1");
+        assert_eq!(schema.entities.len(), 2);
+    }
+
+    #[test]
+    fn duplicate_entity_types_are_rejected() {
+        struct OtherTable;
+
+        impl Table for OtherTable {
+            type PrimaryKey = keys::Primary;
+            type IndexKeys = keys::Gsi1;
+
+            fn table_name(&self) -> &str {
+                unimplemented!()
+            }
+
+            fn client(&self) -> &aws_sdk_dynamodb::Client {
+                unimplemented!()
+            }
+        }
+
+        impl EntityRegistry for OtherTable {
+            const ENTITIES: &'static [EntityDescriptor] = &[
+                EntityDescriptor::of::<Order>("Order"),
+                EntityDescriptor::of::<LegacyOrder>("LegacyOrder"),
+            ];
+
+            fn projection_expression() -> Option<expr::StaticProjection> {
+                None
+            }
+        }
+
+        let error = OtherTable::validate().unwrap_err();
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(
+            source.to_string(),
+            "entity type `order` is claimed by both `Order` and `LegacyOrder`"
+        );
+    }
+}
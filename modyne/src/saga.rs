@@ -0,0 +1,951 @@
+//! Multi-step writes with compensation
+//!
+//! A DynamoDB transaction can include at most 100 operations. Workflows that
+//! need more, or that must be split across several requests, can be written
+//! as a [`Saga`]: a sequence of steps, each with a forward transaction and a
+//! compensating transaction that undoes it. Steps are applied in order. If a
+//! step fails, the compensations of the steps already applied are run in
+//! reverse order.
+//!
+//! The progress of a saga is persisted in a [`SagaState`] item stored in the
+//! table. Each step's transaction also advances the saga state, so a step is
+//! recorded as applied if and only if its writes were made. A saga that was
+//! interrupted, such as by a crash or throttling, can be resumed by
+//! executing a saga with the same identifier and steps again, or rolled back
+//! with [`Saga::compensate()`].
+//!
+//! ```no_run
+//! # use modyne::{keys, Table, WritableTable};
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! # impl WritableTable for App {}
+//! # fn reserve_inventory() -> modyne::model::TransactWrite { unimplemented!() }
+//! # fn release_inventory() -> modyne::model::TransactWrite { unimplemented!() }
+//! # fn charge_account() -> modyne::model::TransactWrite { unimplemented!() }
+//! # fn refund_account() -> modyne::model::TransactWrite { unimplemented!() }
+//! use modyne::saga::{Saga, SagaOutcome};
+//!
+//! # async fn example(app: App) -> Result<(), modyne::Error> {
+//! let saga = Saga::new("order-1234")
+//!     .step("reserve inventory", reserve_inventory(), release_inventory())
+//!     .step("charge account", charge_account(), refund_account());
+//!
+//! match saga.execute(&app).await? {
+//!     SagaOutcome::Completed => println!("order placed"),
+//!     SagaOutcome::Compensated { failed_step, .. } => {
+//!         println!("order rolled back after {failed_step:?} failed");
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Because the saga state is updated within each step's transaction, each
+//! step can include at most 99 operations of its own, which is checked
+//! before any step is applied. Compensations must
+//! not fail for reasons that retrying cannot fix, as a saga that cannot be
+//! compensated is left in the [`Compensating`][SagaStatus::Compensating]
+//! status until it is resumed.
+
+use std::{fmt, marker::PhantomData};
+
+use crate::{
+    error::InvalidSagaError,
+    expr, keys,
+    model::{ConditionalUpdate, TransactWrite},
+    Entity, EntityDef, EntityExt, EntityTypeNameRef, Error, ErrorKind, Table, WritableTable,
+};
+
+/// The maximum number of operations in a step's transaction, leaving room
+/// for the update of the saga state
+const MAX_STEP_OPERATIONS: usize = 99;
+
+/// A sequence of transactional steps that are compensated if any step fails
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct Saga {
+    id: String,
+    steps: Vec<SagaStep>,
+}
+
+#[derive(Clone, Debug)]
+struct SagaStep {
+    name: String,
+    forward: TransactWrite,
+    compensation: TransactWrite,
+}
+
+impl Saga {
+    /// Prepares a saga identified by the given key
+    ///
+    /// The identifier must be unique to the workflow, as it is used to find
+    /// the persisted progress of the saga when it is resumed.
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            steps: Vec::new(),
+        }
+    }
+
+    /// Adds a step with a forward transaction and the transaction that compensates for it
+    ///
+    /// A step without any writes to undo can use an empty transaction as
+    /// its compensation.
+    pub fn step(
+        mut self,
+        name: impl Into<String>,
+        forward: TransactWrite,
+        compensation: TransactWrite,
+    ) -> Self {
+        self.steps.push(SagaStep {
+            name: name.into(),
+            forward,
+            compensation,
+        });
+        self
+    }
+
+    /// The identifier of the saga
+    #[inline]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Reads the persisted state of the saga, if it has been started
+    pub async fn state<T>(&self, table: &T) -> Result<Option<SagaState<T>>, Error>
+    where
+        T: Table<PrimaryKey = keys::Primary> + 'static,
+    {
+        let output = SagaState::<T>::get(&self.id)
+            .execute_with_consistency(table, true)
+            .await?;
+
+        output.item.map(crate::ProjectionExt::from_item).transpose()
+    }
+
+    /// Executes the saga, resuming from its persisted state if it was
+    /// started previously
+    ///
+    /// If a step fails because of a failed condition or an invalid request,
+    /// the steps already applied are compensated and
+    /// [`SagaOutcome::Compensated`] is returned. Any other failure, such as
+    /// throttling, is returned as an error, leaving the saga to be resumed
+    /// later.
+    ///
+    /// A saga must be resumed with the same steps that it was started with.
+    /// Resuming a saga with steps that differ in number or name is an
+    /// error, as is a step with more than 99 operations in its forward or
+    /// compensating transaction.
+    pub async fn execute<T>(&self, table: &T) -> Result<SagaOutcome, Error>
+    where
+        T: WritableTable<PrimaryKey = keys::Primary> + 'static,
+    {
+        self.validate()?;
+        let mut state = self.start(table).await?;
+        let mut step_error = None;
+
+        loop {
+            match state.status {
+                SagaStatus::Completed => return Ok(SagaOutcome::Completed),
+                SagaStatus::Compensated => {
+                    return Ok(SagaOutcome::Compensated {
+                        failed_step: state.failed_step,
+                        error: step_error,
+                    });
+                }
+                SagaStatus::Compensating => self.compensate_steps(table, &mut state).await?,
+                SagaStatus::Running => {
+                    let index = state.completed as usize;
+                    let step = &self.steps[index];
+                    let result = step
+                        .forward
+                        .clone()
+                        .operation(state.advance())
+                        .execute(table)
+                        .await;
+
+                    let error = match result {
+                        Ok(_) => {
+                            state = state.advanced();
+                            continue;
+                        }
+                        Err(error) => Error::from(error),
+                    };
+
+                    if self.reload(table, &mut state).await? {
+                        continue;
+                    }
+
+                    if !matches!(
+                        error.kind(),
+                        ErrorKind::ConditionFailed
+                            | ErrorKind::Validation
+                            | ErrorKind::ItemTooLarge
+                    ) {
+                        return Err(error);
+                    }
+
+                    tracing::debug!(
+                        saga = %self.id,
+                        step = %step.name,
+                        "saga step failed; compensating"
+                    );
+                    let failure = error_chain(&error);
+                    self.begin_compensation(table, &mut state, Some(&step.name), Some(&failure))
+                        .await?;
+                    step_error = Some(error);
+                }
+            }
+        }
+    }
+
+    /// Rolls back an interrupted saga, compensating the steps already applied
+    ///
+    /// A saga that has not been started has nothing to compensate. A saga
+    /// that has already completed cannot be compensated.
+    pub async fn compensate<T>(&self, table: &T) -> Result<SagaOutcome, Error>
+    where
+        T: WritableTable<PrimaryKey = keys::Primary> + 'static,
+    {
+        self.validate()?;
+        let Some(mut state) = self.state(table).await? else {
+            return Ok(SagaOutcome::Compensated {
+                failed_step: None,
+                error: None,
+            });
+        };
+        self.check_steps(&state)?;
+
+        loop {
+            match state.status {
+                SagaStatus::Completed => {
+                    return Err(self.invalid("has already completed".to_string()));
+                }
+                SagaStatus::Compensated => {
+                    return Ok(SagaOutcome::Compensated {
+                        failed_step: state.failed_step,
+                        error: None,
+                    });
+                }
+                SagaStatus::Compensating => self.compensate_steps(table, &mut state).await?,
+                SagaStatus::Running => {
+                    self.begin_compensation(table, &mut state, None, None)
+                        .await?
+                }
+            }
+        }
+    }
+
+    /// Reads the state of the saga, creating it if the saga has not been started
+    async fn start<T>(&self, table: &T) -> Result<SagaState<T>, Error>
+    where
+        T: WritableTable<PrimaryKey = keys::Primary> + 'static,
+    {
+        loop {
+            if let Some(state) = self.state(table).await? {
+                self.check_steps(&state)?;
+                return Ok(state);
+            }
+
+            let state = SagaState::<T> {
+                id: self.id.clone(),
+                status: if self.steps.is_empty() {
+                    SagaStatus::Completed
+                } else {
+                    SagaStatus::Running
+                },
+                steps: self.steps.iter().map(|step| step.name.clone()).collect(),
+                completed: 0,
+                failed_step: None,
+                failure: None,
+                table: PhantomData,
+            };
+
+            let result = state.clone().create().execute(table).await;
+            match result {
+                Ok(_) => return Ok(state),
+                Err(error) => {
+                    let error = Error::from(error);
+                    if error.kind() != ErrorKind::ConditionFailed {
+                        return Err(error);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Marks the saga as compensating, or as compensated if no steps have
+    /// been applied
+    async fn begin_compensation<T>(
+        &self,
+        table: &T,
+        state: &mut SagaState<T>,
+        failed_step: Option<&str>,
+        failure: Option<&str>,
+    ) -> Result<(), Error>
+    where
+        T: WritableTable<PrimaryKey = keys::Primary> + 'static,
+    {
+        let status = if state.completed == 0 {
+            SagaStatus::Compensated
+        } else {
+            SagaStatus::Compensating
+        };
+
+        let mut expression = String::from("SET #status = :status");
+        if failed_step.is_some() {
+            expression.push_str(", #failed_step = :failed_step");
+        }
+        if failure.is_some() {
+            expression.push_str(", #failure = :failure");
+        }
+
+        let mut update = expr::Update::new(expression)
+            .name("#status", "status")
+            .value(":status", status);
+        if let Some(failed_step) = failed_step {
+            update = update
+                .name("#failed_step", "failed_step")
+                .value(":failed_step", failed_step);
+        }
+        if let Some(failure) = failure {
+            update = update
+                .name("#failure", "failure")
+                .value(":failure", failure);
+        }
+
+        let result = SagaState::<T>::update(&self.id)
+            .expression(update)
+            .condition(state.unchanged())
+            .execute(table)
+            .await;
+
+        match result {
+            Ok(_) => {
+                state.status = status;
+                state.failed_step = failed_step.map(str::to_string);
+                state.failure = failure.map(str::to_string);
+                Ok(())
+            }
+            Err(error) => {
+                let error = Error::from(error);
+                if self.reload(table, state).await? {
+                    Ok(())
+                } else {
+                    Err(error)
+                }
+            }
+        }
+    }
+
+    /// Runs the compensations of the applied steps in reverse order
+    async fn compensate_steps<T>(&self, table: &T, state: &mut SagaState<T>) -> Result<(), Error>
+    where
+        T: WritableTable<PrimaryKey = keys::Primary> + 'static,
+    {
+        while state.status == SagaStatus::Compensating && state.completed > 0 {
+            let step = &self.steps[state.completed as usize - 1];
+            let result = step
+                .compensation
+                .clone()
+                .operation(state.retreat())
+                .execute(table)
+                .await;
+
+            match result {
+                Ok(_) => *state = state.retreated(),
+                Err(error) => {
+                    let error = Error::from(error);
+                    if !self.reload(table, state).await? {
+                        return Err(error);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reloads the state of the saga, returning true if it changed
+    async fn reload<T>(&self, table: &T, state: &mut SagaState<T>) -> Result<bool, Error>
+    where
+        T: Table<PrimaryKey = keys::Primary> + 'static,
+    {
+        let latest = self
+            .state(table)
+            .await?
+            .ok_or_else(|| self.invalid("state was deleted while executing".to_string()))?;
+        let changed = latest.status != state.status || latest.completed != state.completed;
+        *state = latest;
+        Ok(changed)
+    }
+
+    /// Checks that each step fits in a transaction alongside the saga state
+    fn validate(&self) -> Result<(), Error> {
+        for step in &self.steps {
+            let transactions = [
+                ("forward transaction", &step.forward),
+                ("compensation", &step.compensation),
+            ];
+            for (kind, transaction) in transactions {
+                if transaction.len() > MAX_STEP_OPERATIONS {
+                    return Err(self.invalid(format!(
+                        "step `{}` has {} operations in its {kind}, but at most {MAX_STEP_OPERATIONS} are allowed",
+                        step.name,
+                        transaction.len(),
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the saga is resumed with the steps it was started with
+    fn check_steps<T>(&self, state: &SagaState<T>) -> Result<(), Error> {
+        if state.steps.len() != self.steps.len() {
+            return Err(self.invalid(format!(
+                "was started with {} steps, but {} were given",
+                state.steps.len(),
+                self.steps.len()
+            )));
+        }
+
+        let renamed = state
+            .steps
+            .iter()
+            .zip(&self.steps)
+            .enumerate()
+            .find(|(_, (started, step))| **started != step.name);
+        if let Some((index, (started, step))) = renamed {
+            return Err(self.invalid(format!(
+                "was started with step {} named `{started}`, but `{}` was given",
+                index + 1,
+                step.name
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn invalid(&self, reason: String) -> Error {
+        InvalidSagaError {
+            id: self.id.clone(),
+            reason,
+        }
+        .into()
+    }
+}
+
+/// Describes an error and its sources
+fn error_chain(error: &Error) -> String {
+    let mut causes = vec![error.to_string()];
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        causes.push(cause.to_string());
+        source = cause.source();
+    }
+    causes.join(": ")
+}
+
+/// The result of executing a [`Saga`]
+#[derive(Debug)]
+pub enum SagaOutcome {
+    /// Every step of the saga was applied
+    Completed,
+
+    /// The saga was rolled back, and the compensations of every applied step were applied
+    Compensated {
+        /// The name of the step that failed, if the saga was rolled back due
+        /// to a failed step
+        failed_step: Option<String>,
+
+        /// The error from the failed step, if it failed during this execution
+        error: Option<Error>,
+    },
+}
+
+impl SagaOutcome {
+    /// Returns true if every step of the saga was applied
+    #[inline]
+    pub fn is_completed(&self) -> bool {
+        matches!(self, Self::Completed)
+    }
+}
+
+/// The status of a [`Saga`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SagaStatus {
+    /// Steps are being applied
+    Running,
+
+    /// Every step was applied
+    Completed,
+
+    /// The applied steps are being compensated
+    Compensating,
+
+    /// Every applied step was compensated
+    Compensated,
+}
+
+/// The persisted progress of a [`Saga`]
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct SagaState<T> {
+    /// The identifier of the saga
+    pub id: String,
+
+    /// The status of the saga
+    pub status: SagaStatus,
+
+    /// The names of the steps of the saga, in order
+    pub steps: Vec<String>,
+
+    /// The number of steps that have been applied and not compensated
+    pub completed: u32,
+
+    /// The name of the step whose failure caused the saga to be compensated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failed_step: Option<String>,
+
+    /// A description of the error that caused the saga to be compensated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<String>,
+
+    #[serde(skip)]
+    table: PhantomData<fn() -> T>,
+}
+
+impl<T> SagaState<T>
+where
+    T: Table<PrimaryKey = keys::Primary> + 'static,
+{
+    /// A condition that the persisted status and progress match this state
+    fn unchanged(&self) -> expr::Condition {
+        expr::Condition::new("#status = :expected_status AND #completed = :expected_completed")
+            .name("#status", "status")
+            .name("#completed", "completed")
+            .value(":expected_status", self.status)
+            .value(":expected_completed", self.completed)
+    }
+
+    /// An update recording that the next step has been applied
+    fn advance(&self) -> ConditionalUpdate {
+        let next = self.advanced();
+        Self::update(&self.id)
+            .expression(
+                expr::Update::new("SET #completed = :completed, #status = :status")
+                    .name("#completed", "completed")
+                    .name("#status", "status")
+                    .value(":completed", next.completed)
+                    .value(":status", next.status),
+            )
+            .condition(self.unchanged())
+    }
+
+    fn advanced(&self) -> Self {
+        let completed = self.completed + 1;
+        Self {
+            completed,
+            status: if completed as usize == self.steps.len() {
+                SagaStatus::Completed
+            } else {
+                SagaStatus::Running
+            },
+            ..self.clone()
+        }
+    }
+
+    /// An update recording that the last applied step has been compensated
+    fn retreat(&self) -> ConditionalUpdate {
+        let next = self.retreated();
+        Self::update(&self.id)
+            .expression(
+                expr::Update::new("SET #completed = :completed, #status = :status")
+                    .name("#completed", "completed")
+                    .name("#status", "status")
+                    .value(":completed", next.completed)
+                    .value(":status", next.status),
+            )
+            .condition(self.unchanged())
+    }
+
+    fn retreated(&self) -> Self {
+        let completed = self.completed - 1;
+        Self {
+            completed,
+            status: if completed == 0 {
+                SagaStatus::Compensated
+            } else {
+                SagaStatus::Compensating
+            },
+            ..self.clone()
+        }
+    }
+}
+
+impl<T> fmt::Debug for SagaState<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SagaState")
+            .field("id", &self.id)
+            .field("status", &self.status)
+            .field("steps", &self.steps)
+            .field("completed", &self.completed)
+            .field("failed_step", &self.failed_step)
+            .field("failure", &self.failure)
+            .finish()
+    }
+}
+
+impl<T> Clone for SagaState<T> {
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            status: self.status,
+            steps: self.steps.clone(),
+            completed: self.completed,
+            failed_step: self.failed_step.clone(),
+            failure: self.failure.clone(),
+            table: PhantomData,
+        }
+    }
+}
+
+impl<T> EntityDef for SagaState<T> {
+    const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("saga");
+}
+
+impl<T: Table<PrimaryKey = keys::Primary>> Entity for SagaState<T> {
+    type KeyInput<'a> = &'a str;
+    type Table = T;
+    type IndexKeys = ();
+
+    fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+        keys::Primary {
+            hash: format!("SAGA#{id}"),
+            range: "SAGA".to_string(),
+        }
+    }
+
+    fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+        Self::primary_key(&self.id).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use aws_sdk_dynamodb::{
+        config::http::HttpResponse,
+        error::SdkError,
+        operation::{
+            get_item::GetItemOutput,
+            transact_write_items::{TransactWriteItemsError, TransactWriteItemsOutput},
+        },
+        types::{error::TransactionCanceledException, CancellationReason},
+    };
+    use aws_smithy_types::body::SdkBody;
+
+    use super::*;
+    use crate::{
+        mock::{ops, MockTable, Operation},
+        AttributeValue, Item,
+    };
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            "TestTable"
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    impl WritableTable for TestTable {}
+
+    fn transaction(account: &str, amount: i32) -> TransactWrite {
+        TransactWrite::new().operation(update(account, amount))
+    }
+
+    fn update(account: &str, amount: i32) -> crate::model::UpdateWithExpr {
+        let key: Item = [
+            (
+                "PK".to_string(),
+                AttributeValue::S(format!("ACCOUNT#{account}")),
+            ),
+            (
+                "SK".to_string(),
+                AttributeValue::S(format!("ACCOUNT#{account}")),
+            ),
+        ]
+        .into();
+
+        crate::model::Update::new(key).expression(
+            expr::Update::new("SET balance = balance + :amount").value(":amount", amount),
+        )
+    }
+
+    fn transfer() -> Saga {
+        Saga::new("transfer-1")
+            .step("debit", transaction("1", -100), transaction("1", 100))
+            .step("credit", transaction("2", 100), transaction("2", -100))
+    }
+
+    fn state(status: SagaStatus, completed: u32) -> Item {
+        SagaState::<TestTable> {
+            id: "transfer-1".to_string(),
+            status,
+            steps: vec!["debit".to_string(), "credit".to_string()],
+            completed,
+            failed_step: Some("audit".to_string()),
+            failure: None,
+            table: PhantomData,
+        }
+        .into_item()
+    }
+
+    fn written_accounts(table: &MockTable<TestTable>) -> Vec<String> {
+        table
+            .inputs::<ops::TransactWriteItems>()
+            .iter()
+            .map(|input| {
+                let items = input.transact_items.as_ref().unwrap();
+                assert_eq!(items.len(), 2);
+                let update = items[0].update.as_ref().unwrap();
+                update.key["PK"].as_s().unwrap().clone()
+            })
+            .collect()
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn steps_are_applied_in_order_with_the_saga_state() {
+        let table = MockTable::<TestTable>::new("TestTable");
+        table
+            .expect::<ops::GetItem>(|e| e.returning_item(None).times(1))
+            .expect::<ops::PutItem>(|e| e.times(1))
+            .expect::<ops::TransactWriteItems>(|e| e.times(2));
+
+        let outcome = runtime().block_on(transfer().execute(&table)).unwrap();
+        assert!(outcome.is_completed());
+
+        assert_eq!(written_accounts(&table), ["ACCOUNT#1", "ACCOUNT#2"]);
+        let last = &table.inputs::<ops::TransactWriteItems>()[1];
+        let update = last.transact_items.as_ref().unwrap()[1]
+            .update
+            .as_ref()
+            .unwrap();
+        assert_eq!(
+            update.key["PK"],
+            AttributeValue::S("SAGA#transfer-1".into())
+        );
+        let values = update.expression_attribute_values.as_ref().unwrap();
+        assert_eq!(values[":upd_completed"], AttributeValue::N("2".into()));
+        assert_eq!(values[":upd_status"], AttributeValue::S("completed".into()));
+        assert_eq!(
+            values[":cnd_expected_completed"],
+            AttributeValue::N("1".into())
+        );
+        table.verify();
+    }
+
+    #[test]
+    fn interrupted_compensation_resumes_in_reverse_order() {
+        let table = MockTable::<TestTable>::new("TestTable");
+        table
+            .expect::<ops::GetItem>(|e| {
+                e.returning_item(Some(state(SagaStatus::Compensating, 2)))
+                    .times(1)
+            })
+            .expect::<ops::TransactWriteItems>(|e| e.times(2));
+
+        let outcome = runtime().block_on(transfer().execute(&table)).unwrap();
+        match outcome {
+            SagaOutcome::Compensated { failed_step, error } => {
+                assert_eq!(failed_step.as_deref(), Some("audit"));
+                assert!(error.is_none());
+            }
+            SagaOutcome::Completed => panic!("expected the saga to be compensated"),
+        }
+
+        assert_eq!(written_accounts(&table), ["ACCOUNT#2", "ACCOUNT#1"]);
+        assert_eq!(table.calls(Operation::PutItem), 0);
+        table.verify();
+    }
+
+    #[test]
+    fn resuming_with_different_steps_is_an_error() {
+        let table = MockTable::<TestTable>::new("TestTable");
+        table.expect::<ops::GetItem>(|e| e.returning_item(Some(state(SagaStatus::Running, 1))));
+
+        let saga =
+            Saga::new("transfer-1").step("debit", transaction("1", -100), TransactWrite::new());
+        let error = runtime().block_on(saga.execute(&table)).unwrap_err();
+        assert_eq!(
+            std::error::Error::source(&error).unwrap().to_string(),
+            "saga `transfer-1` was started with 2 steps, but 1 were given"
+        );
+        assert_eq!(table.calls(Operation::TransactWriteItems), 0);
+    }
+
+    fn condition_failed() -> SdkError<TransactWriteItemsError> {
+        let error = TransactionCanceledException::builder()
+            .message("transaction cancelled")
+            .cancellation_reasons(CancellationReason::builder().code("None").build())
+            .cancellation_reasons(
+                CancellationReason::builder()
+                    .code("ConditionalCheckFailed")
+                    .build(),
+            )
+            .build();
+        SdkError::service_error(
+            TransactWriteItemsError::TransactionCanceledException(error),
+            HttpResponse::new(400u16.try_into().unwrap(), SdkBody::empty()),
+        )
+    }
+
+    #[test]
+    fn failed_steps_are_compensated_in_reverse_order() {
+        let table = MockTable::<TestTable>::new("TestTable");
+        let reads = AtomicUsize::new(0);
+        let writes = AtomicUsize::new(0);
+        table
+            .expect::<ops::GetItem>(|e| {
+                e.responding(move |_| {
+                    let item = (reads.fetch_add(1, Ordering::SeqCst) > 0).then(|| {
+                        SagaState::<TestTable> {
+                            id: "transfer-1".to_string(),
+                            status: SagaStatus::Running,
+                            steps: vec!["debit".into(), "credit".into(), "audit".into()],
+                            completed: 2,
+                            failed_step: None,
+                            failure: None,
+                            table: PhantomData,
+                        }
+                        .into_item()
+                    });
+                    Ok(GetItemOutput::builder().set_item(item).build())
+                })
+            })
+            .expect::<ops::PutItem>(|e| e.times(1))
+            .expect::<ops::UpdateItem>(|e| e.times(1))
+            .expect::<ops::TransactWriteItems>(|e| {
+                e.responding(move |_| match writes.fetch_add(1, Ordering::SeqCst) {
+                    2 => Err(condition_failed()),
+                    _ => Ok(TransactWriteItemsOutput::builder().build()),
+                })
+            });
+
+        let saga = transfer().step("audit", transaction("3", 1), transaction("3", -1));
+        let outcome = runtime().block_on(saga.execute(&table)).unwrap();
+        match outcome {
+            SagaOutcome::Compensated { failed_step, error } => {
+                assert_eq!(failed_step.as_deref(), Some("audit"));
+                assert_eq!(error.unwrap().kind(), ErrorKind::ConditionFailed);
+            }
+            SagaOutcome::Completed => panic!("expected the saga to be compensated"),
+        }
+
+        assert_eq!(
+            written_accounts(&table),
+            [
+                "ACCOUNT#1",
+                "ACCOUNT#2",
+                "ACCOUNT#3",
+                "ACCOUNT#2",
+                "ACCOUNT#1"
+            ]
+        );
+        let amounts: Vec<_> = table
+            .inputs::<ops::TransactWriteItems>()
+            .iter()
+            .map(|input| {
+                let update = input.transact_items.as_ref().unwrap()[0]
+                    .update
+                    .as_ref()
+                    .unwrap();
+                let values = update.expression_attribute_values.as_ref().unwrap();
+                values[":upd_amount"].as_n().unwrap().clone()
+            })
+            .collect();
+        assert_eq!(amounts, ["-100", "100", "1", "-100", "100"]);
+
+        let update = &table.inputs::<ops::UpdateItem>()[0];
+        let values = update.expression_attribute_values.as_ref().unwrap();
+        assert_eq!(
+            values[":upd_status"],
+            AttributeValue::S("compensating".into())
+        );
+        assert_eq!(
+            values[":upd_failed_step"],
+            AttributeValue::S("audit".into())
+        );
+        table.verify();
+    }
+
+    #[test]
+    fn oversized_steps_are_rejected_before_starting() {
+        let table = MockTable::<TestTable>::new("TestTable");
+        let forward = TransactWrite::new()
+            .operations((0..=MAX_STEP_OPERATIONS).map(|n| update(&n.to_string(), 1)));
+
+        let saga = transfer().step("fan out", forward, TransactWrite::new());
+        let error = runtime().block_on(saga.execute(&table)).unwrap_err();
+        assert_eq!(
+            std::error::Error::source(&error).unwrap().to_string(),
+            "saga `transfer-1` step `fan out` has 100 operations in its forward transaction, \
+             but at most 99 are allowed"
+        );
+        assert_eq!(table.calls(Operation::GetItem), 0);
+        assert_eq!(table.calls(Operation::TransactWriteItems), 0);
+    }
+
+    #[test]
+    fn resuming_with_renamed_steps_is_an_error() {
+        let table = MockTable::<TestTable>::new("TestTable");
+        table.expect::<ops::GetItem>(|e| e.returning_item(Some(state(SagaStatus::Running, 1))));
+
+        let saga = Saga::new("transfer-1")
+            .step("debit", transaction("1", -100), transaction("1", 100))
+            .step("refund", transaction("2", 100), transaction("2", -100));
+        let error = runtime().block_on(saga.execute(&table)).unwrap_err();
+        assert_eq!(
+            std::error::Error::source(&error).unwrap().to_string(),
+            "saga `transfer-1` was started with step 2 named `credit`, but `refund` was given"
+        );
+        assert_eq!(table.calls(Operation::TransactWriteItems), 0);
+    }
+
+    #[test]
+    fn completed_sagas_cannot_be_compensated() {
+        let table = MockTable::<TestTable>::new("TestTable");
+        table.expect::<ops::GetItem>(|e| e.returning_item(Some(state(SagaStatus::Completed, 2))));
+
+        let error = runtime()
+            .block_on(transfer().compensate(&table))
+            .unwrap_err();
+        assert_eq!(
+            std::error::Error::source(&error).unwrap().to_string(),
+            "saga `transfer-1` has already completed"
+        );
+    }
+}
@@ -0,0 +1,895 @@
+//! Tenant isolation by scoping partition keys
+//!
+//! In a multi-tenant table, each tenant's items are typically kept apart by
+//! prefixing every partition key with the tenant's identifier. Relying on
+//! each [`Entity::primary_key()`][crate::Entity::primary_key()] and index
+//! key to remember that prefix is fragile, as a single omission can expose
+//! one tenant's data to another.
+//!
+//! A [`KeyScope`] moves that responsibility into the data layer. When a
+//! table returns a scope from [`Table::key_scope()`], every operation on
+//! that table prefixes the string partition keys of the table and its
+//! global secondary indexes on the way out, and strips the prefix from the
+//! items that come back. Items that do not carry the prefix are discarded,
+//! and scans only return items within the scope. Values compared for
+//! equality against a partition key attribute in a key condition, filter,
+//! condition, or update expression are prefixed as well.
+//!
+//! The simplest way to scope an existing table is with a [`Scoped`] handle:
+//!
+//! ```no_run
+//! # use modyne::{keys, Entity, EntityDef, EntityExt, EntityTypeNameRef, Table, WritableTable};
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! # impl WritableTable for App {}
+//! # #[derive(serde::Serialize)]
+//! # struct Order { id: String }
+//! # impl EntityDef for Order {
+//! #     const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("order");
+//! # }
+//! # impl Entity for Order {
+//! #     type KeyInput<'a> = &'a str;
+//! #     type Table = App;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+//! #         keys::Primary { hash: format!("ORDER#{id}"), range: "ORDER".into() }
+//! #     }
+//! #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+//! #         unimplemented!()
+//! #     }
+//! # }
+//! use modyne::scope::{KeyScope, Scoped};
+//!
+//! # async fn example(app: App) -> Result<(), modyne::Error> {
+//! let tenant = Scoped::new(app, KeyScope::new("acme"));
+//!
+//! // Stored with a partition key of `4:acme#ORDER#1234`
+//! Order { id: "1234".into() }.create().execute(&tenant).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Partition keys with number or binary attributes cannot be prefixed, and
+//! are sent unchanged.
+
+use std::collections::{HashMap, HashSet};
+
+use aws_sdk_dynamodb::{
+    error::SdkError,
+    operation::{
+        batch_get_item::{BatchGetItemError, BatchGetItemInput, BatchGetItemOutput},
+        batch_write_item::{BatchWriteItemError, BatchWriteItemInput, BatchWriteItemOutput},
+        delete_item::{DeleteItemError, DeleteItemInput, DeleteItemOutput},
+        get_item::{GetItemError, GetItemInput, GetItemOutput},
+        put_item::{PutItemError, PutItemInput, PutItemOutput},
+        query::{QueryError, QueryInput, QueryOutput},
+        scan::{ScanError, ScanInput, ScanOutput},
+        transact_get_items::{
+            TransactGetItemsError, TransactGetItemsInput, TransactGetItemsOutput,
+        },
+        transact_write_items::{
+            TransactWriteItemsError, TransactWriteItemsInput, TransactWriteItemsOutput,
+        },
+        update_item::{UpdateItemError, UpdateItemInput, UpdateItemOutput},
+    },
+};
+
+use crate::{
+    client::DynamoClient, keys, AttributeValue, EntityDiscriminator, EntityTypeNameRef, Item,
+    MalformedEntityTypeError, Table, WritableTable,
+};
+
+/// A prefix applied to the partition keys of every item in a table
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyScope {
+    prefix: String,
+}
+
+impl KeyScope {
+    /// Scopes partition keys to the given tenant, separating the tenant
+    /// from the rest of the key with `#`
+    ///
+    /// The tenant is preceded by its length, as in `4:acme#`, so that
+    /// tenants such as `a` and `a#b` cannot share a prefix even when the
+    /// tenant identifier itself contains `#`.
+    pub fn new(tenant: impl AsRef<str>) -> Self {
+        let tenant = tenant.as_ref();
+        Self::with_prefix(format!("{}:{tenant}#", tenant.len()))
+    }
+
+    /// Scopes partition keys with the given prefix, used as-is
+    pub fn with_prefix(prefix: impl Into<String>) -> Self {
+        Self {
+            prefix: prefix.into(),
+        }
+    }
+
+    /// The prefix applied to partition keys
+    #[inline]
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    fn apply(&self, value: &mut AttributeValue) {
+        if let AttributeValue::S(s) = value {
+            s.insert_str(0, &self.prefix);
+        }
+    }
+
+    /// Strips the prefix from the value, returning false if it was not present
+    fn strip(&self, value: &mut AttributeValue) -> bool {
+        match value {
+            AttributeValue::S(s) if s.starts_with(&self.prefix) => {
+                s.replace_range(..self.prefix.len(), "");
+                true
+            }
+            AttributeValue::S(_) => false,
+            _ => true,
+        }
+    }
+}
+
+/// A handle to a table with all of its partition keys scoped
+///
+/// See the [module documentation][self] for details.
+#[derive(Clone, Debug)]
+pub struct Scoped<T> {
+    table: T,
+    scope: KeyScope,
+}
+
+impl<T: Table> Scoped<T> {
+    /// Wraps the table so that all operations are within the given scope
+    #[inline]
+    pub fn new(table: T, scope: KeyScope) -> Self {
+        Self { table, scope }
+    }
+
+    /// Returns a reference to the wrapped table
+    #[inline]
+    pub fn inner(&self) -> &T {
+        &self.table
+    }
+
+    /// Unwraps the handle, returning the wrapped table
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.table
+    }
+}
+
+impl<T: Table> Table for Scoped<T> {
+    const ENTITY_TYPE_ATTRIBUTE: &'static str = T::ENTITY_TYPE_ATTRIBUTE;
+    const ENTITY_DISCRIMINATOR: EntityDiscriminator = T::ENTITY_DISCRIMINATOR;
+    const SEMANTIC_CONVENTIONS: crate::instrumentation::SemanticConventions =
+        T::SEMANTIC_CONVENTIONS;
+    const TTL_ATTRIBUTE: Option<&'static str> = T::TTL_ATTRIBUTE;
+    const EXCLUDE_EXPIRED_ITEMS: bool = T::EXCLUDE_EXPIRED_ITEMS;
+    const SCHEMA_VERSION_ATTRIBUTE: Option<&'static str> = T::SCHEMA_VERSION_ATTRIBUTE;
+    const STREAM_VIEW: Option<aws_sdk_dynamodb::types::StreamViewType> = T::STREAM_VIEW;
+
+    type PrimaryKey = T::PrimaryKey;
+    type IndexKeys = T::IndexKeys;
+
+    #[inline]
+    fn table_name(&self) -> &str {
+        self.table.table_name()
+    }
+
+    #[inline]
+    fn client(&self) -> &aws_sdk_dynamodb::Client {
+        self.table.client()
+    }
+
+    #[inline]
+    fn dynamo_client(&self) -> &dyn DynamoClient {
+        self.table.dynamo_client()
+    }
+
+    #[inline]
+    fn key_scope(&self) -> Option<&KeyScope> {
+        Some(&self.scope)
+    }
+
+    #[inline]
+    fn item_cache(&self) -> Option<&crate::cache::ItemCache> {
+        self.table.item_cache()
+    }
+
+    #[inline]
+    fn deserialize_entity_type(
+        attr: &AttributeValue,
+    ) -> Result<&EntityTypeNameRef, MalformedEntityTypeError> {
+        T::deserialize_entity_type(attr)
+    }
+
+    #[inline]
+    fn serialize_entity_type(entity_type: &EntityTypeNameRef) -> AttributeValue {
+        T::serialize_entity_type(entity_type)
+    }
+
+    #[inline]
+    fn after_write(&self, key: &Item) {
+        self.table.after_write(key);
+    }
+
+    #[inline]
+    fn clock(&self) -> &dyn crate::clock::Clock {
+        self.table.clock()
+    }
+
+    #[inline]
+    fn record_span_attributes(&self, span: &tracing::Span, operation: &'static str) {
+        self.table.record_span_attributes(span, operation);
+    }
+
+    #[inline]
+    fn record_partition_access(&self, access: &crate::instrumentation::PartitionAccess<'_>) {
+        self.table.record_partition_access(access);
+    }
+
+    #[inline]
+    fn record_skipped_items(
+        &self,
+        operation: &'static str,
+        skipped: &crate::instrumentation::SkippedItems,
+    ) {
+        self.table.record_skipped_items(operation, skipped);
+    }
+
+    #[inline]
+    fn redact_key(key: &Item) -> String {
+        T::redact_key(key)
+    }
+}
+
+impl<T: WritableTable> WritableTable for Scoped<T> {}
+
+/// The client through which operations on a table are sent, applying the
+/// table's key scope, if any
+pub(crate) struct ScopedClient<'a> {
+    inner: &'a dyn DynamoClient,
+    scoping: Option<Scoping<'a>>,
+}
+
+struct Scoping<'a> {
+    scope: &'a KeyScope,
+    hash_key: &'static str,
+    index_hash_keys: Vec<(&'static str, &'static str)>,
+}
+
+impl<'a> ScopedClient<'a> {
+    pub(crate) fn new<T: Table>(table: &'a T) -> Self {
+        use keys::{IndexKeys, PrimaryKey};
+
+        let scoping = table.key_scope().map(|scope| Scoping {
+            scope,
+            hash_key: T::PrimaryKey::PRIMARY_KEY_DEFINITION.hash_key,
+            index_hash_keys: T::IndexKeys::KEY_DEFINITIONS
+                .iter()
+                .map(|index| (index.index_name(), index.hash_key()))
+                .collect(),
+        });
+
+        Self {
+            inner: table.dynamo_client(),
+            scoping,
+        }
+    }
+}
+
+impl Scoping<'_> {
+    fn is_partition_key(&self, attr: &str) -> bool {
+        attr == self.hash_key || self.index_hash_keys.iter().any(|(_, hash)| *hash == attr)
+    }
+
+    fn partition_key_of(&self, index_name: Option<&str>) -> &'static str {
+        index_name
+            .and_then(|name| {
+                self.index_hash_keys
+                    .iter()
+                    .find(|(index, _)| *index == name)
+                    .map(|(_, hash)| *hash)
+            })
+            .unwrap_or(self.hash_key)
+    }
+
+    fn scope_item(&self, item: &mut Item) {
+        for (attr, value) in item.iter_mut() {
+            if self.is_partition_key(attr) {
+                self.scope.apply(value);
+            }
+        }
+    }
+
+    fn scope_opt_item(&self, item: Option<&mut Item>) {
+        if let Some(item) = item {
+            self.scope_item(item);
+        }
+    }
+
+    /// Strips the scope from an item, returning false if any of its
+    /// partition keys were outside of the scope
+    fn unscope_item(&self, item: &mut Item) -> bool {
+        let mut within = true;
+        for (attr, value) in item.iter_mut() {
+            if self.is_partition_key(attr) {
+                within &= self.scope.strip(value);
+            }
+        }
+        within
+    }
+
+    /// Strips the scope from an item, discarding it if it was outside of the scope
+    fn unscope_opt_item(&self, item: &mut Option<Item>) {
+        if let Some(i) = item {
+            if !self.unscope_item(i) {
+                *item = None;
+            }
+        }
+    }
+
+    /// Strips the scope from items, discarding those outside of the scope
+    fn unscope_items(&self, items: Option<&mut Vec<Item>>) {
+        if let Some(items) = items {
+            items.retain_mut(|item| self.unscope_item(item));
+        }
+    }
+
+    /// Prefixes the values compared for equality with partition key
+    /// attributes in the given expressions
+    fn scope_values(
+        &self,
+        expressions: &[Option<&str>],
+        names: Option<&HashMap<String, String>>,
+        values: Option<&mut HashMap<String, AttributeValue>>,
+    ) {
+        let (Some(names), Some(values)) = (names, values) else {
+            return;
+        };
+
+        let is_partition_name = |token: &str| {
+            names
+                .get(token)
+                .is_some_and(|attr| self.is_partition_key(attr))
+        };
+
+        let mut scoped = HashSet::new();
+        for expression in expressions.iter().flatten() {
+            let tokens = tokenize(expression);
+            for window in tokens.windows(3) {
+                let [lhs, "=", rhs] = window else {
+                    continue;
+                };
+                if is_partition_name(lhs) && rhs.starts_with(':') {
+                    scoped.insert(*rhs);
+                } else if is_partition_name(rhs) && lhs.starts_with(':') {
+                    scoped.insert(*lhs);
+                }
+            }
+        }
+
+        for placeholder in scoped {
+            if let Some(value) = values.get_mut(placeholder) {
+                self.scope.apply(value);
+            }
+        }
+    }
+}
+
+/// Splits an expression into operands and operators, discarding whitespace
+/// and punctuation
+fn tokenize(expression: &str) -> Vec<&str> {
+    let is_operand =
+        |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '#' | ':' | '.' | '[' | ']');
+    let is_operator = |c: char| matches!(c, '=' | '<' | '>');
+
+    let mut tokens = Vec::new();
+    let mut rest = expression;
+    while let Some(c) = rest.chars().next() {
+        let len = if is_operand(c) {
+            rest.find(|c| !is_operand(c)).unwrap_or(rest.len())
+        } else if is_operator(c) {
+            rest.find(|c| !is_operator(c)).unwrap_or(rest.len())
+        } else {
+            rest = &rest[c.len_utf8()..];
+            continue;
+        };
+        tokens.push(&rest[..len]);
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+#[async_trait::async_trait]
+impl DynamoClient for ScopedClient<'_> {
+    async fn get_item(
+        &self,
+        mut input: GetItemInput,
+    ) -> Result<GetItemOutput, SdkError<GetItemError>> {
+        let Some(scoping) = &self.scoping else {
+            return self.inner.get_item(input).await;
+        };
+
+        scoping.scope_opt_item(input.key.as_mut());
+        let mut output = self.inner.get_item(input).await?;
+        scoping.unscope_opt_item(&mut output.item);
+        Ok(output)
+    }
+
+    async fn put_item(
+        &self,
+        mut input: PutItemInput,
+    ) -> Result<PutItemOutput, SdkError<PutItemError>> {
+        let Some(scoping) = &self.scoping else {
+            return self.inner.put_item(input).await;
+        };
+
+        scoping.scope_opt_item(input.item.as_mut());
+        scoping.scope_values(
+            &[input.condition_expression.as_deref()],
+            input.expression_attribute_names.as_ref(),
+            input.expression_attribute_values.as_mut(),
+        );
+        let mut output = self.inner.put_item(input).await?;
+        scoping.unscope_opt_item(&mut output.attributes);
+        Ok(output)
+    }
+
+    async fn update_item(
+        &self,
+        mut input: UpdateItemInput,
+    ) -> Result<UpdateItemOutput, SdkError<UpdateItemError>> {
+        let Some(scoping) = &self.scoping else {
+            return self.inner.update_item(input).await;
+        };
+
+        scoping.scope_opt_item(input.key.as_mut());
+        scoping.scope_values(
+            &[
+                input.update_expression.as_deref(),
+                input.condition_expression.as_deref(),
+            ],
+            input.expression_attribute_names.as_ref(),
+            input.expression_attribute_values.as_mut(),
+        );
+        let mut output = self.inner.update_item(input).await?;
+        scoping.unscope_opt_item(&mut output.attributes);
+        Ok(output)
+    }
+
+    async fn delete_item(
+        &self,
+        mut input: DeleteItemInput,
+    ) -> Result<DeleteItemOutput, SdkError<DeleteItemError>> {
+        let Some(scoping) = &self.scoping else {
+            return self.inner.delete_item(input).await;
+        };
+
+        scoping.scope_opt_item(input.key.as_mut());
+        scoping.scope_values(
+            &[input.condition_expression.as_deref()],
+            input.expression_attribute_names.as_ref(),
+            input.expression_attribute_values.as_mut(),
+        );
+        let mut output = self.inner.delete_item(input).await?;
+        scoping.unscope_opt_item(&mut output.attributes);
+        Ok(output)
+    }
+
+    async fn query(&self, mut input: QueryInput) -> Result<QueryOutput, SdkError<QueryError>> {
+        let Some(scoping) = &self.scoping else {
+            return self.inner.query(input).await;
+        };
+
+        scoping.scope_opt_item(input.exclusive_start_key.as_mut());
+        scoping.scope_values(
+            &[
+                input.key_condition_expression.as_deref(),
+                input.filter_expression.as_deref(),
+            ],
+            input.expression_attribute_names.as_ref(),
+            input.expression_attribute_values.as_mut(),
+        );
+        let mut output = self.inner.query(input).await?;
+        scoping.unscope_items(output.items.as_mut());
+        scoping.unscope_opt_item(&mut output.last_evaluated_key);
+        Ok(output)
+    }
+
+    async fn scan(&self, mut input: ScanInput) -> Result<ScanOutput, SdkError<ScanError>> {
+        let Some(scoping) = &self.scoping else {
+            return self.inner.scan(input).await;
+        };
+
+        scoping.scope_opt_item(input.exclusive_start_key.as_mut());
+        scoping.scope_values(
+            &[input.filter_expression.as_deref()],
+            input.expression_attribute_names.as_ref(),
+            input.expression_attribute_values.as_mut(),
+        );
+
+        let within_scope = "begins_with(#scope_pk, :scope_prefix)";
+        input.filter_expression = Some(match input.filter_expression.take() {
+            Some(filter) => format!("{within_scope} AND ({filter})"),
+            None => within_scope.to_string(),
+        });
+        input
+            .expression_attribute_names
+            .get_or_insert_with(HashMap::new)
+            .insert(
+                "#scope_pk".to_string(),
+                scoping
+                    .partition_key_of(input.index_name.as_deref())
+                    .to_string(),
+            );
+        input
+            .expression_attribute_values
+            .get_or_insert_with(HashMap::new)
+            .insert(
+                ":scope_prefix".to_string(),
+                AttributeValue::S(scoping.scope.prefix.clone()),
+            );
+
+        let mut output = self.inner.scan(input).await?;
+        scoping.unscope_items(output.items.as_mut());
+        scoping.unscope_opt_item(&mut output.last_evaluated_key);
+        Ok(output)
+    }
+
+    async fn batch_get_item(
+        &self,
+        mut input: BatchGetItemInput,
+    ) -> Result<BatchGetItemOutput, SdkError<BatchGetItemError>> {
+        let Some(scoping) = &self.scoping else {
+            return self.inner.batch_get_item(input).await;
+        };
+
+        for request in input.request_items.iter_mut().flat_map(HashMap::values_mut) {
+            request
+                .keys
+                .iter_mut()
+                .for_each(|key| scoping.scope_item(key));
+        }
+        let mut output = self.inner.batch_get_item(input).await?;
+        for items in output.responses.iter_mut().flat_map(HashMap::values_mut) {
+            scoping.unscope_items(Some(items));
+        }
+        for request in output
+            .unprocessed_keys
+            .iter_mut()
+            .flat_map(HashMap::values_mut)
+        {
+            request.keys.iter_mut().for_each(|key| {
+                scoping.unscope_item(key);
+            });
+        }
+        Ok(output)
+    }
+
+    async fn batch_write_item(
+        &self,
+        mut input: BatchWriteItemInput,
+    ) -> Result<BatchWriteItemOutput, SdkError<BatchWriteItemError>> {
+        let Some(scoping) = &self.scoping else {
+            return self.inner.batch_write_item(input).await;
+        };
+
+        for request in input
+            .request_items
+            .iter_mut()
+            .flat_map(HashMap::values_mut)
+            .flatten()
+        {
+            if let Some(put) = &mut request.put_request {
+                scoping.scope_item(&mut put.item);
+            }
+            if let Some(delete) = &mut request.delete_request {
+                scoping.scope_item(&mut delete.key);
+            }
+        }
+        let mut output = self.inner.batch_write_item(input).await?;
+        for request in output
+            .unprocessed_items
+            .iter_mut()
+            .flat_map(HashMap::values_mut)
+            .flatten()
+        {
+            if let Some(put) = &mut request.put_request {
+                scoping.unscope_item(&mut put.item);
+            }
+            if let Some(delete) = &mut request.delete_request {
+                scoping.unscope_item(&mut delete.key);
+            }
+        }
+        Ok(output)
+    }
+
+    async fn transact_get_items(
+        &self,
+        mut input: TransactGetItemsInput,
+    ) -> Result<TransactGetItemsOutput, SdkError<TransactGetItemsError>> {
+        let Some(scoping) = &self.scoping else {
+            return self.inner.transact_get_items(input).await;
+        };
+
+        for get in input.transact_items.iter_mut().flatten() {
+            if let Some(get) = &mut get.get {
+                scoping.scope_item(&mut get.key);
+            }
+        }
+        let mut output = self.inner.transact_get_items(input).await?;
+        for response in output.responses.iter_mut().flatten() {
+            scoping.unscope_opt_item(&mut response.item);
+        }
+        Ok(output)
+    }
+
+    async fn transact_write_items(
+        &self,
+        mut input: TransactWriteItemsInput,
+    ) -> Result<TransactWriteItemsOutput, SdkError<TransactWriteItemsError>> {
+        let Some(scoping) = &self.scoping else {
+            return self.inner.transact_write_items(input).await;
+        };
+
+        for item in input.transact_items.iter_mut().flatten() {
+            if let Some(put) = &mut item.put {
+                scoping.scope_item(&mut put.item);
+                scoping.scope_values(
+                    &[put.condition_expression.as_deref()],
+                    put.expression_attribute_names.as_ref(),
+                    put.expression_attribute_values.as_mut(),
+                );
+            }
+            if let Some(update) = &mut item.update {
+                scoping.scope_item(&mut update.key);
+                scoping.scope_values(
+                    &[
+                        Some(update.update_expression.as_str()),
+                        update.condition_expression.as_deref(),
+                    ],
+                    update.expression_attribute_names.as_ref(),
+                    update.expression_attribute_values.as_mut(),
+                );
+            }
+            if let Some(delete) = &mut item.delete {
+                scoping.scope_item(&mut delete.key);
+                scoping.scope_values(
+                    &[delete.condition_expression.as_deref()],
+                    delete.expression_attribute_names.as_ref(),
+                    delete.expression_attribute_values.as_mut(),
+                );
+            }
+            if let Some(check) = &mut item.condition_check {
+                scoping.scope_item(&mut check.key);
+                scoping.scope_values(
+                    &[Some(check.condition_expression.as_str())],
+                    check.expression_attribute_names.as_ref(),
+                    check.expression_attribute_values.as_mut(),
+                );
+            }
+        }
+        self.inner.transact_write_items(input).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_dynamodb::operation::query::QueryOutput;
+
+    use super::*;
+    use crate::{
+        expr,
+        mock::{ops, MockTable},
+        model::{Put, Query, Scan},
+    };
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            unimplemented!()
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    impl WritableTable for TestTable {}
+
+    fn item(attrs: &[(&str, &str)]) -> Item {
+        attrs
+            .iter()
+            .map(|(name, value)| (name.to_string(), AttributeValue::S(value.to_string())))
+            .collect()
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn writes_prefix_partition_keys() {
+        let table = Scoped::new(MockTable::<TestTable>::new("test"), KeyScope::new("acme"));
+        table.inner().expect::<ops::PutItem>(|e| e);
+
+        let condition = expr::Condition::new("#pk <> :pk AND #name = :name")
+            .name("#pk", "GSI1PK")
+            .value(":pk", "ORDER#1")
+            .name("#name", "name")
+            .value(":name", "widget");
+        let put = Put::new(item(&[
+            ("PK", "ORDER#1"),
+            ("SK", "ORDER#1"),
+            ("GSI1PK", "CUSTOMER#alex"),
+            ("name", "widget"),
+        ]))
+        .condition(
+            expr::Condition::new("#pk = :pk")
+                .name("#pk", "PK")
+                .value(":pk", "ORDER#1"),
+        );
+        runtime().block_on(put.execute(&table)).unwrap();
+        runtime()
+            .block_on(
+                Put::new(item(&[("PK", "ORDER#2"), ("SK", "ORDER#2")]))
+                    .condition(condition)
+                    .execute(&table),
+            )
+            .unwrap();
+
+        let inputs = table.inner().inputs::<ops::PutItem>();
+        let written = inputs[0].item.as_ref().unwrap();
+        assert_eq!(written["PK"].as_s().unwrap(), "4:acme#ORDER#1");
+        assert_eq!(written["SK"].as_s().unwrap(), "ORDER#1");
+        assert_eq!(written["GSI1PK"].as_s().unwrap(), "4:acme#CUSTOMER#alex");
+        assert_eq!(written["name"].as_s().unwrap(), "widget");
+
+        let values = inputs[0].expression_attribute_values.as_ref().unwrap();
+        assert_eq!(values[":cnd_pk"].as_s().unwrap(), "4:acme#ORDER#1");
+
+        // Only values compared for equality with a partition key are scoped
+        let values = inputs[1].expression_attribute_values.as_ref().unwrap();
+        assert_eq!(values[":cnd_pk"].as_s().unwrap(), "ORDER#1");
+        assert_eq!(values[":cnd_name"].as_s().unwrap(), "widget");
+    }
+
+    #[test]
+    fn reads_strip_the_scope_and_discard_foreign_items() {
+        let table = Scoped::new(MockTable::<TestTable>::new("test"), KeyScope::new("acme"));
+        table.inner().expect::<ops::Query>(|e| {
+            e.returning(
+                QueryOutput::builder()
+                    .items(item(&[
+                        ("GSI1PK", "4:acme#CUSTOMER#alex"),
+                        ("PK", "4:acme#ORDER#1"),
+                    ]))
+                    .items(item(&[
+                        ("GSI1PK", "other#CUSTOMER#alex"),
+                        ("PK", "other#ORDER#9"),
+                    ]))
+                    .set_last_evaluated_key(Some(item(&[
+                        ("GSI1PK", "4:acme#CUSTOMER#alex"),
+                        ("PK", "4:acme#ORDER#1"),
+                    ])))
+                    .build(),
+            )
+        });
+
+        let output = runtime()
+            .block_on(
+                Query::<keys::Gsi1>::new(expr::KeyCondition::in_partition("CUSTOMER#alex"))
+                    .execute(&table),
+            )
+            .unwrap();
+
+        let items = output.items.unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0]["GSI1PK"].as_s().unwrap(), "CUSTOMER#alex");
+        assert_eq!(items[0]["PK"].as_s().unwrap(), "ORDER#1");
+        assert_eq!(
+            output.last_evaluated_key.unwrap()["PK"].as_s().unwrap(),
+            "ORDER#1"
+        );
+
+        let input = &table.inner().inputs::<ops::Query>()[0];
+        let values = input.expression_attribute_values.as_ref().unwrap();
+        assert_eq!(values[":key_PK"].as_s().unwrap(), "4:acme#CUSTOMER#alex");
+    }
+
+    #[test]
+    fn tenants_sharing_a_prefix_are_kept_apart() {
+        let outer = KeyScope::new("a");
+        let nested = KeyScope::new("a#b");
+        assert_eq!(outer.prefix(), "1:a#");
+        assert_eq!(nested.prefix(), "3:a#b#");
+
+        // `b#ORDER#1` in tenant `a` and `ORDER#1` in tenant `a#b` are distinct
+        let mut in_outer = AttributeValue::S("b#ORDER#1".to_string());
+        let mut in_nested = AttributeValue::S("ORDER#1".to_string());
+        outer.apply(&mut in_outer);
+        nested.apply(&mut in_nested);
+        assert_ne!(in_outer, in_nested);
+
+        let table = Scoped::new(MockTable::<TestTable>::new("test"), outer);
+        table.inner().expect::<ops::Query>(|e| {
+            e.returning(
+                QueryOutput::builder()
+                    .items(item(&[
+                        ("GSI1PK", "3:a#b#CUSTOMER#alex"),
+                        ("PK", "3:a#b#ORDER#1"),
+                    ]))
+                    .build(),
+            )
+        });
+
+        let output = runtime()
+            .block_on(
+                Query::<keys::Gsi1>::new(expr::KeyCondition::in_partition("b#CUSTOMER#alex"))
+                    .execute(&table),
+            )
+            .unwrap();
+
+        assert!(output.items.unwrap().is_empty());
+    }
+
+    #[test]
+    fn scans_are_filtered_to_the_scope() {
+        let table = Scoped::new(MockTable::<TestTable>::new("test"), KeyScope::new("acme"));
+        table.inner().expect::<ops::Scan>(|e| e);
+
+        runtime()
+            .block_on(
+                Scan::<keys::Gsi1>::new()
+                    .filter(
+                        expr::Filter::new("#n = :n")
+                            .name("#n", "name")
+                            .value(":n", "x"),
+                    )
+                    .execute(&table),
+            )
+            .unwrap();
+
+        let input = &table.inner().inputs::<ops::Scan>()[0];
+        assert_eq!(
+            input.filter_expression.as_deref(),
+            Some("begins_with(#scope_pk, :scope_prefix) AND (#flt_n = :flt_n)")
+        );
+        let names = input.expression_attribute_names.as_ref().unwrap();
+        assert_eq!(names["#scope_pk"], "GSI1PK");
+        let values = input.expression_attribute_values.as_ref().unwrap();
+        assert_eq!(values[":scope_prefix"].as_s().unwrap(), "4:acme#");
+    }
+
+    #[test]
+    fn tokenize_splits_operands_and_operators() {
+        assert_eq!(
+            tokenize("#a.#b[0] <= :v AND begins_with(#c, :p) OR #d=:e"),
+            [
+                "#a.#b[0]",
+                "<=",
+                ":v",
+                "AND",
+                "begins_with",
+                "#c",
+                ":p",
+                "OR",
+                "#d",
+                "=",
+                ":e"
+            ]
+        );
+    }
+}
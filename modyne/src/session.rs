@@ -0,0 +1,313 @@
+//! Read-your-writes consistency for a logical session
+//!
+//! DynamoDB reads are eventually consistent by default, which means that a
+//! read issued shortly after a write may not observe that write. A
+//! [`Session`] tracks the keys written through it and, for a configurable
+//! window after each write, escalates reads against the same partition to
+//! strongly consistent reads.
+//!
+//! Writes are tracked by executing them against the session rather than
+//! the underlying table:
+//!
+//! ```no_run
+//! # use modyne::{keys, Entity, EntityDef, EntityExt, EntityTypeNameRef, Table, WritableTable};
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! # impl WritableTable for App {}
+//! # #[derive(serde::Serialize)]
+//! # struct Order { id: String }
+//! # impl EntityDef for Order {
+//! #     const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("order");
+//! # }
+//! # impl Entity for Order {
+//! #     type KeyInput<'a> = &'a str;
+//! #     type Table = App;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+//! #         keys::Primary { hash: format!("ORDER#{id}"), range: "ORDER".into() }
+//! #     }
+//! #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+//! #         unimplemented!()
+//! #     }
+//! # }
+//! use std::time::Duration;
+//!
+//! use modyne::session::Session;
+//!
+//! # async fn example(app: App) -> Result<(), modyne::Error> {
+//! let session = Session::new(&app, Duration::from_secs(5));
+//!
+//! Order { id: "1234".into() }.create().execute(&session).await?;
+//!
+//! // Escalated to a consistent read, as the item was just written
+//! let order = session.get(Order::get("1234")).await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Queries against global secondary indexes cannot be made strongly
+//! consistent. When a leader table is configured with
+//! [`Session::with_leader`], such as the region receiving writes in a global
+//! table setup, escalated reads are routed to the leader instead.
+
+use std::{
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use aws_sdk_dynamodb::{
+    error::SdkError,
+    operation::{
+        get_item::{GetItemError, GetItemOutput},
+        query::{QueryError, QueryOutput},
+    },
+};
+
+use crate::{
+    keys::{self, KeyDefinition, SecondaryIndexDefinition},
+    model::{Get, Query},
+    AttributeValue, EntityDiscriminator, EntityTypeNameRef, Item, MalformedEntityTypeError, Table,
+    WritableTable,
+};
+
+/// A logical session providing read-your-writes consistency
+#[derive(Debug)]
+pub struct Session<'a, T> {
+    table: &'a T,
+    leader: Option<&'a T>,
+    window: Duration,
+    written: Mutex<Vec<(Item, Instant)>>,
+}
+
+impl<'a, T: Table> Session<'a, T> {
+    /// Creates a session that escalates reads for `window` after each write
+    pub fn new(table: &'a T, window: Duration) -> Self {
+        Self {
+            table,
+            leader: None,
+            window,
+            written: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Routes escalated reads to the given leader table
+    pub fn with_leader(mut self, leader: &'a T) -> Self {
+        self.leader = Some(leader);
+        self
+    }
+
+    /// Records a write of the item with the given key attributes
+    ///
+    /// Writes executed against the session are recorded automatically.
+    pub fn record_write(&self, key: &Item) {
+        let now = Instant::now();
+        let mut written = self.written.lock().unwrap_or_else(PoisonError::into_inner);
+        written.retain(|(_, at)| now.duration_since(*at) < self.window);
+        written.push((key.clone(), now));
+    }
+
+    /// Returns true if a write to the given partition has been recorded within the window
+    pub fn is_recently_written(&self, attribute: &str, partition: &AttributeValue) -> bool {
+        let now = Instant::now();
+        let written = self.written.lock().unwrap_or_else(PoisonError::into_inner);
+        written.iter().any(|(key, at)| {
+            now.duration_since(*at) < self.window && key.get(attribute) == Some(partition)
+        })
+    }
+
+    /// Executes a get operation, using a consistent read if the item was
+    /// recently written in this session
+    pub async fn get(&self, op: Get) -> Result<GetItemOutput, SdkError<GetItemError>> {
+        let hash_key = <T::PrimaryKey as keys::PrimaryKey>::PRIMARY_KEY_DEFINITION.hash_key;
+        let escalate = op
+            .key()
+            .get(hash_key)
+            .is_some_and(|partition| self.is_recently_written(hash_key, partition));
+
+        if escalate {
+            op.execute_with_consistency(self.leader.unwrap_or(self.table), true)
+                .await
+        } else {
+            op.execute(self.table).await
+        }
+    }
+
+    /// Executes a query, using a consistent read if the partition was
+    /// recently written in this session
+    ///
+    /// Queries against global secondary indexes are routed to the leader
+    /// table, if one is configured, but otherwise remain eventually consistent.
+    pub async fn query<K: keys::Key>(
+        &self,
+        query: Query<K>,
+    ) -> Result<QueryOutput, SdkError<QueryError>> {
+        if !self.is_recently_written(K::DEFINITION.hash_key(), query.partition_key()) {
+            return query.execute(self.table).await;
+        }
+
+        let supports_consistent_read = matches!(
+            K::DEFINITION,
+            KeyDefinition::Primary(_)
+                | KeyDefinition::Secondary(SecondaryIndexDefinition::Local(_))
+        );
+
+        let table = self.leader.unwrap_or(self.table);
+        if supports_consistent_read {
+            query.consistent_read().execute(table).await
+        } else {
+            query.execute(table).await
+        }
+    }
+}
+
+impl<'a, T: Table> Table for Session<'a, T> {
+    const ENTITY_TYPE_ATTRIBUTE: &'static str = T::ENTITY_TYPE_ATTRIBUTE;
+    const ENTITY_DISCRIMINATOR: EntityDiscriminator = T::ENTITY_DISCRIMINATOR;
+    const SEMANTIC_CONVENTIONS: crate::instrumentation::SemanticConventions =
+        T::SEMANTIC_CONVENTIONS;
+    const TTL_ATTRIBUTE: Option<&'static str> = T::TTL_ATTRIBUTE;
+    const EXCLUDE_EXPIRED_ITEMS: bool = T::EXCLUDE_EXPIRED_ITEMS;
+    const SCHEMA_VERSION_ATTRIBUTE: Option<&'static str> = T::SCHEMA_VERSION_ATTRIBUTE;
+    const STREAM_VIEW: Option<aws_sdk_dynamodb::types::StreamViewType> = T::STREAM_VIEW;
+
+    type PrimaryKey = T::PrimaryKey;
+    type IndexKeys = T::IndexKeys;
+
+    #[inline]
+    fn table_name(&self) -> &str {
+        self.table.table_name()
+    }
+
+    #[inline]
+    fn client(&self) -> &aws_sdk_dynamodb::Client {
+        self.table.client()
+    }
+
+    #[inline]
+    fn dynamo_client(&self) -> &dyn crate::client::DynamoClient {
+        self.table.dynamo_client()
+    }
+
+    #[inline]
+    fn key_scope(&self) -> Option<&crate::scope::KeyScope> {
+        self.table.key_scope()
+    }
+
+    #[inline]
+    fn item_cache(&self) -> Option<&crate::cache::ItemCache> {
+        self.table.item_cache()
+    }
+
+    #[inline]
+    fn deserialize_entity_type(
+        attr: &AttributeValue,
+    ) -> Result<&EntityTypeNameRef, MalformedEntityTypeError> {
+        T::deserialize_entity_type(attr)
+    }
+
+    #[inline]
+    fn serialize_entity_type(entity_type: &EntityTypeNameRef) -> AttributeValue {
+        T::serialize_entity_type(entity_type)
+    }
+
+    fn after_write(&self, key: &Item) {
+        self.record_write(key);
+        self.table.after_write(key);
+    }
+
+    #[inline]
+    fn clock(&self) -> &dyn crate::clock::Clock {
+        self.table.clock()
+    }
+
+    #[inline]
+    fn record_span_attributes(&self, span: &tracing::Span, operation: &'static str) {
+        self.table.record_span_attributes(span, operation);
+    }
+
+    #[inline]
+    fn record_partition_access(&self, access: &crate::instrumentation::PartitionAccess<'_>) {
+        self.table.record_partition_access(access);
+    }
+
+    #[inline]
+    fn record_skipped_items(
+        &self,
+        operation: &'static str,
+        skipped: &crate::instrumentation::SkippedItems,
+    ) {
+        self.table.record_skipped_items(operation, skipped);
+    }
+
+    #[inline]
+    fn redact_key(key: &Item) -> String {
+        T::redact_key(key)
+    }
+}
+
+impl<'a, T: WritableTable> WritableTable for Session<'a, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            "TestTable"
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    fn key(pk: &str, gsi1pk: Option<&str>) -> Item {
+        let mut key: Item = [("PK".to_string(), AttributeValue::S(pk.into()))].into();
+        if let Some(gsi1pk) = gsi1pk {
+            key.insert("GSI1PK".to_string(), AttributeValue::S(gsi1pk.into()));
+        }
+        key
+    }
+
+    #[test]
+    fn tracks_written_partitions() {
+        let session = Session::new(&TestTable, Duration::from_secs(60));
+        session.after_write(&key("USER#1", Some("ORG#1")));
+
+        assert!(session.is_recently_written("PK", &AttributeValue::S("USER#1".into())));
+        assert!(session.is_recently_written("GSI1PK", &AttributeValue::S("ORG#1".into())));
+        assert!(!session.is_recently_written("PK", &AttributeValue::S("USER#2".into())));
+    }
+
+    #[test]
+    fn forwards_every_hook() {
+        use crate::tests::hooks::{assert_forwards_hooks, HookedTable};
+
+        let table = HookedTable::new();
+        let session = Session::new(&table, Duration::from_secs(60));
+        let _ = session.dynamo_client();
+        assert_eq!(table.calls(), ["dynamo_client"]);
+        assert_eq!(session.table_name(), table.table_name());
+
+        let table = HookedTable::new();
+        assert_forwards_hooks(&Session::new(&table, Duration::from_secs(60)), &table);
+    }
+
+    #[test]
+    fn forgets_writes_outside_the_window() {
+        let session = Session::new(&TestTable, Duration::ZERO);
+        session.after_write(&key("USER#1", None));
+
+        assert!(!session.is_recently_written("PK", &AttributeValue::S("USER#1".into())));
+    }
+}
@@ -0,0 +1,121 @@
+//! Estimation of the stored size of DynamoDB items and attributes
+//!
+//! The sizes computed here follow the rules described in the
+//! [AWS documentation][AWS] and are intended to be used to keep items
+//! within the DynamoDB item size limit and to estimate capacity usage.
+//!
+//! [AWS]: https://docs.aws.amazon.com/amazondynamodb/latest/developerguide/CapacityUnitCalculations.html
+
+use aws_sdk_dynamodb::types::AttributeValue;
+
+use crate::Item;
+
+/// The maximum size of a single DynamoDB item, in bytes
+pub const MAX_ITEM_SIZE: usize = 400 * 1024;
+
+/// Overhead applied to list and map attribute values, in bytes
+const COLLECTION_OVERHEAD: usize = 3;
+
+/// Overhead applied to each element of a list or map attribute value, in bytes
+const ELEMENT_OVERHEAD: usize = 1;
+
+/// Estimates the stored size of an item, in bytes
+///
+/// The size of an item is the sum of the lengths of its attribute names
+/// and the sizes of its attribute values.
+pub fn item_size(item: &Item) -> usize {
+    item.iter()
+        .map(|(name, value)| name.len() + attribute_value_size(value))
+        .sum()
+}
+
+/// Estimates the stored size of a single attribute value, in bytes
+pub fn attribute_value_size(value: &AttributeValue) -> usize {
+    match value {
+        AttributeValue::S(s) => s.len(),
+        AttributeValue::N(n) => number_size(n),
+        AttributeValue::B(b) => b.as_ref().len(),
+        AttributeValue::Bool(_) | AttributeValue::Null(_) => 1,
+        AttributeValue::Ss(ss) => ss.iter().map(String::len).sum(),
+        AttributeValue::Ns(ns) => ns.iter().map(|n| number_size(n)).sum(),
+        AttributeValue::Bs(bs) => bs.iter().map(|b| b.as_ref().len()).sum(),
+        AttributeValue::L(l) => {
+            COLLECTION_OVERHEAD
+                + l.iter()
+                    .map(|v| attribute_value_size(v) + ELEMENT_OVERHEAD)
+                    .sum::<usize>()
+        }
+        AttributeValue::M(m) => {
+            COLLECTION_OVERHEAD
+                + m.iter()
+                    .map(|(k, v)| k.len() + attribute_value_size(v) + ELEMENT_OVERHEAD)
+                    .sum::<usize>()
+        }
+        _ => 0,
+    }
+}
+
+/// Numbers are stored with one byte per two significant digits, plus one byte
+fn number_size(n: &str) -> usize {
+    let digits = n
+        .split(['e', 'E'])
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches(['-', '+'])
+        .replace('.', "");
+    let significant = digits.trim_start_matches('0').trim_end_matches('0').len();
+
+    significant.div_ceil(2) + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_dynamodb::primitives::Blob;
+
+    use super::*;
+
+    #[test]
+    fn scalar_sizes() {
+        assert_eq!(attribute_value_size(&AttributeValue::S("hello".into())), 5);
+        assert_eq!(attribute_value_size(&AttributeValue::Bool(true)), 1);
+        assert_eq!(attribute_value_size(&AttributeValue::Null(true)), 1);
+        assert_eq!(
+            attribute_value_size(&AttributeValue::B(Blob::new(vec![0; 10]))),
+            10
+        );
+    }
+
+    #[test]
+    fn number_sizes_count_significant_digits() {
+        assert_eq!(attribute_value_size(&AttributeValue::N("0".into())), 1);
+        assert_eq!(attribute_value_size(&AttributeValue::N("12".into())), 2);
+        assert_eq!(attribute_value_size(&AttributeValue::N("123".into())), 3);
+        assert_eq!(attribute_value_size(&AttributeValue::N("-1200".into())), 2);
+        assert_eq!(
+            attribute_value_size(&AttributeValue::N("0.00123".into())),
+            3
+        );
+    }
+
+    #[test]
+    fn collection_sizes_include_overhead() {
+        let list = AttributeValue::L(vec![
+            AttributeValue::S("ab".into()),
+            AttributeValue::Bool(false),
+        ]);
+        assert_eq!(attribute_value_size(&list), 3 + (2 + 1) + (1 + 1));
+
+        let map = AttributeValue::M([("key".to_string(), AttributeValue::S("ab".into()))].into());
+        assert_eq!(attribute_value_size(&map), 3 + 3 + 2 + 1);
+    }
+
+    #[test]
+    fn item_size_includes_attribute_names() {
+        let item: Item = [
+            ("PK".to_string(), AttributeValue::S("USER#1".into())),
+            ("count".to_string(), AttributeValue::N("10".into())),
+        ]
+        .into();
+        assert_eq!(item_size(&item), 2 + 6 + 5 + 2);
+    }
+}
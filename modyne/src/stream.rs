@@ -0,0 +1,560 @@
+//! Incremental consumption of query and scan results
+//!
+//! Rather than collecting every page before processing, an [`ItemStream`]
+//! fetches pages on demand and yields items one at a time. Items can be
+//! merged into an [`Aggregate`] as they arrive, or deserialized lazily into
+//! entities, so that only a single page of raw items is held in memory at
+//! any time.
+//!
+//! When pagination is restarted after items have been added or removed, the
+//! same item can be returned more than once. A stream can
+//! [de-duplicate][ItemStream::deduplicate] items by primary key, and the
+//! [`SeenKeys`] window can be carried from one stream into the stream that
+//! resumes it, so that each item is delivered at most once over the whole
+//! pagination session.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+use crate::{
+    keys,
+    model::{PageInfo, Query, Scan},
+    Aggregate, AttributeValue, Error, Item, ProjectionSet, Table,
+};
+
+/// An iterator that lazily deserializes items into entities
+///
+/// Items with an unknown entity type are skipped.
+pub struct Entities<P, I> {
+    items: I,
+    projection: PhantomData<fn() -> P>,
+}
+
+impl<P, I: fmt::Debug> fmt::Debug for Entities<P, I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Entities")
+            .field("projection", &std::any::type_name::<P>())
+            .field("items", &self.items)
+            .finish()
+    }
+}
+
+impl<P, I> Entities<P, I>
+where
+    P: ProjectionSet,
+    I: Iterator<Item = Item>,
+{
+    /// Wraps a set of items to be deserialized as they are consumed
+    pub fn new(items: impl IntoIterator<Item = Item, IntoIter = I>) -> Self {
+        Self {
+            items: items.into_iter(),
+            projection: PhantomData,
+        }
+    }
+}
+
+impl<P, I> Iterator for Entities<P, I>
+where
+    P: ProjectionSet,
+    I: Iterator<Item = Item>,
+{
+    type Item = Result<P, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for item in self.items.by_ref() {
+            match P::try_from_item(item) {
+                Ok(Some(entity)) => return Some(Ok(entity)),
+                Ok(None) => continue,
+                Err(error) => return Some(Err(error)),
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.items.size_hint().1)
+    }
+}
+
+enum Source<K> {
+    Query(Query<K>),
+    Scan(Scan<K>),
+}
+
+impl<K: keys::Key> Source<K> {
+    async fn fetch<T: Table>(
+        self,
+        table: &T,
+    ) -> Result<(Vec<Item>, PageInfo, Option<Self>), Error> {
+        match self {
+            Self::Query(mut query) => {
+                let output = query.execute_page(table).await?;
+                let page = PageInfo::from_query(&output);
+                let next = output
+                    .last_evaluated_key
+                    .map(|key| Self::Query(query.exclusive_start_key(key)));
+                Ok((output.items.unwrap_or_default(), page, next))
+            }
+            Self::Scan(mut scan) => {
+                let output = scan.execute_page(table).await?;
+                let page = PageInfo::from_scan(&output);
+                let next = output
+                    .last_evaluated_key
+                    .map(|key| Self::Scan(scan.exclusive_start_key(key)));
+                Ok((output.items.unwrap_or_default(), page, next))
+            }
+        }
+    }
+}
+
+/// A bounded window of the primary keys of items already delivered
+///
+/// Keys are remembered as 64-bit fingerprints of the primary key
+/// attributes. Once the window is full, the key seen least recently is
+/// forgotten, so an item can only be recognized as a duplicate if it
+/// reappears within the last `window` distinct keys.
+#[derive(Clone)]
+pub struct SeenKeys {
+    window: usize,
+    generation: u64,
+    keys: HashMap<u64, u64>,
+    order: VecDeque<(u64, u64)>,
+}
+
+impl fmt::Debug for SeenKeys {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SeenKeys")
+            .field("window", &self.window)
+            .field("len", &self.keys.len())
+            .finish()
+    }
+}
+
+impl SeenKeys {
+    /// Creates an empty window remembering up to `window` keys
+    ///
+    /// # Panics
+    ///
+    /// Panics if the window is zero.
+    pub fn new(window: usize) -> Self {
+        assert!(window > 0, "the de-duplication window must not be empty");
+        Self {
+            window,
+            generation: 0,
+            keys: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// The maximum number of keys remembered
+    #[inline]
+    pub fn window(&self) -> usize {
+        self.window
+    }
+
+    /// The number of keys currently remembered
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Whether no keys are currently remembered
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Forgets every remembered key
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.order.clear();
+    }
+
+    /// Records the primary key of an item, returning whether it was not
+    /// already remembered
+    ///
+    /// Items missing the table's partition key are always treated as new.
+    pub fn insert<T: Table>(&mut self, item: &Item) -> bool {
+        match key_fingerprint::<T>(item) {
+            Some(fingerprint) => self.insert_fingerprint(fingerprint),
+            None => true,
+        }
+    }
+
+    fn insert_fingerprint(&mut self, fingerprint: u64) -> bool {
+        self.generation += 1;
+        let is_new = self.keys.insert(fingerprint, self.generation).is_none();
+        self.order.push_back((fingerprint, self.generation));
+
+        // A key seen again is queued anew rather than moved, so entries whose
+        // generation no longer matches are stale and are skipped on eviction
+        while self.keys.len() > self.window {
+            let (oldest, generation) = self
+                .order
+                .pop_front()
+                .expect("every remembered key is queued");
+            if self.keys.get(&oldest) == Some(&generation) {
+                self.keys.remove(&oldest);
+            }
+        }
+
+        if self.order.len() > 2 * self.window {
+            let keys = &self.keys;
+            self.order
+                .retain(|(key, generation)| keys.get(key) == Some(generation));
+        }
+
+        is_new
+    }
+}
+
+/// Fingerprints the table's primary key attributes of an item
+fn key_fingerprint<T: Table>(item: &Item) -> Option<u64> {
+    use keys::PrimaryKey;
+
+    let definition = T::PrimaryKey::PRIMARY_KEY_DEFINITION;
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    hash_key_attribute(item.get(definition.hash_key)?, &mut hasher);
+    if let Some(range) = definition.range_key.and_then(|attr| item.get(attr)) {
+        hash_key_attribute(range, &mut hasher);
+    }
+
+    Some(hasher.finish())
+}
+
+fn hash_key_attribute(value: &AttributeValue, hasher: &mut impl Hasher) {
+    match value {
+        AttributeValue::S(s) => (0u8, s).hash(hasher),
+        AttributeValue::N(n) => (1u8, n).hash(hasher),
+        AttributeValue::B(b) => (2u8, b.as_ref()).hash(hasher),
+        other => (3u8, format!("{other:?}")).hash(hasher),
+    }
+}
+
+/// A stream of items from a query or scan, fetching pages on demand
+///
+/// Created by [`Query::stream`] or [`Scan::stream`]. Any limit set on the
+/// operation applies to each page.
+#[must_use]
+pub struct ItemStream<'a, T, K> {
+    table: &'a T,
+    next: Option<Source<K>>,
+    buffer: std::vec::IntoIter<Item>,
+    pages: u32,
+    last_page: Option<PageInfo>,
+    seen: Option<SeenKeys>,
+    duplicates: u64,
+}
+
+impl<'a, T, K> fmt::Debug for ItemStream<'a, T, K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ItemStream")
+            .field("pages", &self.pages)
+            .field("buffered", &self.buffer.len())
+            .field("has_next_page", &self.next.is_some())
+            .field("seen", &self.seen)
+            .field("duplicates", &self.duplicates)
+            .finish()
+    }
+}
+
+impl<'a, T: Table, K: keys::Key> ItemStream<'a, T, K> {
+    pub(crate) fn query(table: &'a T, query: Query<K>) -> Self {
+        Self::new(table, Source::Query(query))
+    }
+
+    #[inline]
+    fn index_name() -> Option<&'static str> {
+        K::DEFINITION.index_name()
+    }
+
+    pub(crate) fn scan(table: &'a T, scan: Scan<K>) -> Self {
+        Self::new(table, Source::Scan(scan))
+    }
+
+    fn new(table: &'a T, source: Source<K>) -> Self {
+        Self {
+            table,
+            next: Some(source),
+            buffer: Vec::new().into_iter(),
+            pages: 0,
+            last_page: None,
+            seen: None,
+            duplicates: 0,
+        }
+    }
+
+    /// Skips items whose primary key was already delivered within the last
+    /// `window` distinct keys
+    ///
+    /// # Panics
+    ///
+    /// Panics if the window is zero.
+    pub fn deduplicate(self, window: usize) -> Self {
+        self.deduplicate_with(SeenKeys::new(window))
+    }
+
+    /// Skips items whose primary key is remembered by the given window,
+    /// continuing a de-duplication session from an earlier stream
+    ///
+    /// ```no_run
+    /// # async fn example<T: modyne::Table>(
+    /// #     table: &T,
+    /// #     query: modyne::model::Query<modyne::keys::Primary>,
+    /// #     resumed: modyne::model::Query<modyne::keys::Primary>,
+    /// # ) -> Result<(), modyne::Error> {
+    /// let mut stream = query.stream(table).deduplicate(1_000);
+    /// if let Some(item) = stream.next_item().await {
+    ///     let _item = item?;
+    /// }
+    ///
+    /// // Later, after restarting pagination from an earlier position
+    /// let seen = stream.take_seen().unwrap();
+    /// let mut stream = resumed.stream(table).deduplicate_with(seen);
+    /// while let Some(item) = stream.next_item().await {
+    ///     let _item = item?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn deduplicate_with(mut self, seen: SeenKeys) -> Self {
+        self.seen = Some(seen);
+        self
+    }
+
+    /// Removes the de-duplication window from the stream, so that it can be
+    /// passed to a stream that resumes the same pagination session
+    ///
+    /// After this call, the stream no longer skips duplicate items.
+    #[inline]
+    pub fn take_seen(&mut self) -> Option<SeenKeys> {
+        self.seen.take()
+    }
+
+    /// The number of duplicate items skipped so far
+    #[inline]
+    pub fn duplicates(&self) -> u64 {
+        self.duplicates
+    }
+
+    /// The number of pages fetched so far
+    #[inline]
+    pub fn pages(&self) -> u32 {
+        self.pages
+    }
+
+    /// Metadata about the most recently fetched page, if any
+    #[inline]
+    pub fn page_info(&self) -> Option<&PageInfo> {
+        self.last_page.as_ref()
+    }
+
+    /// Returns the next item, fetching the next page if necessary
+    pub async fn next_item(&mut self) -> Option<Result<Item, Error>> {
+        loop {
+            if let Some(item) = self.buffer.next() {
+                if let Some(seen) = &mut self.seen {
+                    if !seen.insert::<T>(&item) {
+                        self.duplicates += 1;
+                        continue;
+                    }
+                }
+                return Some(Ok(item));
+            }
+
+            let source = self.next.take()?;
+            match source.fetch(self.table).await {
+                Ok((items, page, next)) => {
+                    self.pages += 1;
+                    self.last_page = Some(page);
+                    self.buffer = items.into_iter();
+                    self.next = next;
+                }
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+
+    /// Returns the next entity, skipping items with an unknown entity type
+    pub async fn next_entity<P: ProjectionSet>(&mut self) -> Option<Result<P, Error>> {
+        loop {
+            let item = match self.next_item().await? {
+                Ok(item) => item,
+                Err(error) => return Some(Err(error)),
+            };
+
+            match P::try_from_item(item) {
+                Ok(Some(entity)) => return Some(Ok(entity)),
+                Ok(None) => continue,
+                Err(error) => return Some(Err(error.with_index_projection(Self::index_name()))),
+            }
+        }
+    }
+
+    /// Merges every remaining item into the aggregate, one at a time
+    pub async fn merge_into<A: Aggregate>(mut self, aggregate: &mut A) -> Result<(), Error> {
+        while let Some(item) = self.next_item().await {
+            aggregate
+                .merge(item?)
+                .map_err(|error| error.with_index_projection(Self::index_name()))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AttributeValue, Entity, EntityDef, EntityExt, EntityTypeNameRef};
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            unimplemented!()
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    #[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+    struct TestEntity {
+        id: String,
+    }
+
+    impl EntityDef for TestEntity {
+        const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("test");
+    }
+
+    impl Entity for TestEntity {
+        type KeyInput<'a> = &'a str;
+        type Table = TestTable;
+        type IndexKeys = keys::Gsi1;
+
+        fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+            keys::Primary {
+                hash: format!("TEST#{id}"),
+                range: "TEST".to_string(),
+            }
+        }
+
+        fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+            keys::FullKey {
+                primary: Self::primary_key(&self.id),
+                indexes: keys::Gsi1 {
+                    hash: "TEST".to_string(),
+                    range: format!("TEST#{}", self.id),
+                },
+            }
+        }
+    }
+
+    #[test]
+    fn entities_skip_unknown_entity_types() {
+        let mut unknown = TestEntity { id: "2".into() }.into_item();
+        unknown.insert(
+            TestTable::ENTITY_TYPE_ATTRIBUTE.to_string(),
+            AttributeValue::S("other".into()),
+        );
+
+        let items = vec![
+            TestEntity { id: "1".into() }.into_item(),
+            unknown,
+            TestEntity { id: "3".into() }.into_item(),
+        ];
+
+        let entities: Vec<TestEntity> = Entities::new(items).collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            entities,
+            vec![TestEntity { id: "1".into() }, TestEntity { id: "3".into() }]
+        );
+    }
+
+    #[test]
+    fn seen_keys_forget_the_least_recently_seen_key() {
+        let item = |id: &str| TestEntity { id: id.into() }.into_item();
+        let mut seen = SeenKeys::new(2);
+
+        assert!(seen.insert::<TestTable>(&item("1")));
+        assert!(seen.insert::<TestTable>(&item("2")));
+        assert!(!seen.insert::<TestTable>(&item("1")));
+
+        // "2" is now the least recently seen, so it is forgotten first
+        assert!(seen.insert::<TestTable>(&item("3")));
+        assert_eq!(seen.len(), 2);
+        assert!(!seen.insert::<TestTable>(&item("1")));
+        assert!(seen.insert::<TestTable>(&item("2")));
+
+        assert!(seen.insert::<TestTable>(&Item::new()));
+        assert!(seen.insert::<TestTable>(&Item::new()));
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    fn entities_missing_unprojected_attributes_name_the_index() {
+        use aws_sdk_dynamodb::operation::query::QueryOutput;
+
+        use crate::{
+            expr::KeyCondition,
+            mock::{ops, MockTable},
+        };
+
+        let mut item = TestEntity { id: "1".into() }.into_item();
+        item.remove("id");
+
+        let table = MockTable::<TestTable>::new("test");
+        table.expect::<ops::Query>(|e| {
+            e.times(1)
+                .returning(QueryOutput::builder().items(item).build())
+        });
+
+        let error = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(async {
+                Query::<keys::Gsi1>::new(KeyCondition::in_partition("TEST"))
+                    .stream(&table)
+                    .next_entity::<TestEntity>()
+                    .await
+            })
+            .unwrap()
+            .unwrap_err();
+
+        let source = std::error::Error::source(&error).unwrap();
+        assert!(
+            source
+                .to_string()
+                .starts_with("item read through index `GSI1` is missing attribute `id`"),
+            "{source}"
+        );
+    }
+
+    #[test]
+    fn page_info_reports_query_output_metadata() {
+        use aws_sdk_dynamodb::{operation::query::QueryOutput, types::ConsumedCapacity};
+
+        let output = QueryOutput::builder()
+            .scanned_count(10)
+            .count(3)
+            .consumed_capacity(ConsumedCapacity::builder().capacity_units(1.5).build())
+            .last_evaluated_key("PK", AttributeValue::S("TEST#3".into()))
+            .build();
+
+        let page = PageInfo::from_query(&output);
+        assert_eq!(page.scanned_count, 10);
+        assert_eq!(page.count, 3);
+        assert_eq!(page.capacity_units(), Some(1.5));
+        assert!(page.has_next_page());
+    }
+}
@@ -0,0 +1,323 @@
+//! Utilities for testing models built with modyne
+//!
+//! [`assert_query()`] renders the request that a [`QueryInput`] sends as
+//! plain values, so that the full shape of a query, and not just its
+//! projection, can be verified in unit or snapshot tests without a table.
+//!
+//! ```
+//! use modyne::{expr, keys, testing, QueryInput};
+//! # use modyne::{EntityDef, Entity, Table};
+//! #
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! #
+//! # #[derive(Debug, EntityDef, serde::Serialize, serde::Deserialize)]
+//! # struct Order { user_id: String, order_id: String }
+//! # impl Entity for Order {
+//! #     type KeyInput<'a> = &'a str;
+//! #     type Table = App;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn primary_key(input: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+//! #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+//! # }
+//!
+//! struct UserOrders {
+//!     user_id: String,
+//! }
+//!
+//! impl QueryInput for UserOrders {
+//!     type Index = keys::Gsi1;
+//!     type Aggregate = Vec<Order>;
+//!
+//!     fn key_condition(&self) -> expr::KeyCondition<Self::Index> {
+//!         expr::KeyCondition::in_partition(format!("USER#{}", self.user_id))
+//!     }
+//! }
+//!
+//! let rendered = testing::assert_query(&UserOrders {
+//!     user_id: "alexdebrie".to_string(),
+//! });
+//!
+//! assert_eq!(rendered.index_name, Some("GSI1"));
+//! assert_eq!(rendered.key_condition.expression, "#key_PK = :key_PK");
+//! assert_eq!(rendered.key_condition.names["#key_PK"], "GSI1PK");
+//! assert_eq!(
+//!     rendered.key_condition.values[":key_PK"].as_s().unwrap(),
+//!     "USER#alexdebrie"
+//! );
+//! assert!(rendered.filter.is_none());
+//! ```
+//!
+//! With the `proptest` feature, the `test_entities!` macro also generates
+//! round-trip tests for entity definitions.
+//!
+//! With the `testcontainers` feature, `dynamodb_local()` and
+//! `localstack()` start a container for integration tests, returning a
+//! guard that provides a client configured to use it. The container is
+//! removed when the guard is dropped.
+//!
+//! ```no_run
+//! # #[cfg(feature = "testcontainers")]
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! use modyne::{testing, TestTableExt};
+//! # use modyne::{keys, Table};
+//! # struct App { client: aws_sdk_dynamodb::Client }
+//! # impl App { fn new(client: aws_sdk_dynamodb::Client) -> Self { Self { client } } }
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { "app" }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { &self.client }
+//! # }
+//!
+//! let db = testing::dynamodb_local().await;
+//! let app = App::new(db.client().clone());
+//! app.create_table().send().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+#[cfg(feature = "testcontainers")]
+mod container;
+#[cfg(feature = "proptest")]
+pub(crate) mod round_trip;
+
+use std::collections::BTreeMap;
+
+use aws_sdk_dynamodb::types::AttributeValue;
+
+#[cfg(feature = "testcontainers")]
+pub use self::container::{dynamodb_local, localstack, LocalDynamoDb};
+use crate::{keys::Key, Aggregate, QueryInput};
+
+/// A query request rendered as plain values
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderedQuery {
+    /// The name of the index queried, or `None` for the table itself
+    pub index_name: Option<&'static str>,
+
+    /// The key condition expression
+    pub key_condition: RenderedExpression,
+
+    /// The filter expression, if any
+    pub filter: Option<RenderedExpression>,
+
+    /// The projection expression, if any
+    pub projection: Option<RenderedExpression>,
+
+    /// Whether the query uses consistent reads
+    pub consistent_read: bool,
+
+    /// Whether the query scans the index forward
+    pub scan_index_forward: bool,
+}
+
+/// An expression rendered along with the placeholders that it uses
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RenderedExpression {
+    /// The parameterized expression
+    pub expression: String,
+
+    /// The attribute names substituted for each name placeholder
+    pub names: BTreeMap<String, String>,
+
+    /// The attribute values substituted for each value placeholder,
+    /// including any sensitive values
+    pub values: BTreeMap<String, AttributeValue>,
+}
+
+/// Renders the query request for an input, asserting that it is well formed
+///
+/// # Panics
+///
+/// Panics if DynamoDB would reject the rendered request: if the filter
+/// expression is invalid, such as when it references an undefined
+/// placeholder, or if two expressions use the same placeholder for
+/// different attribute names or values.
+#[track_caller]
+pub fn assert_query<Q: QueryInput + ?Sized>(input: &Q) -> RenderedQuery {
+    let key_condition = input.key_condition();
+    let key_condition = RenderedExpression {
+        expression: key_condition.expression().to_string(),
+        names: key_condition
+            .names()
+            .map(|(l, r)| (l.to_string(), r.to_string()))
+            .collect(),
+        values: key_condition
+            .values()
+            .map(|(l, r)| (l.to_string(), r.clone()))
+            .collect(),
+    };
+
+    let filter = input.filter_expression().map(|filter| {
+        if let Err(error) = filter.check() {
+            panic!("query filter is invalid: {error}");
+        }
+
+        RenderedExpression {
+            expression: filter.expression,
+            names: filter.names.into_iter().collect(),
+            values: filter
+                .values
+                .into_iter()
+                .chain(filter.sensitive_values)
+                .collect(),
+        }
+    });
+
+    let projection =
+        <Q::Aggregate as Aggregate>::projection_expression().map(|projection| RenderedExpression {
+            expression: projection.expression.to_string(),
+            names: projection
+                .names
+                .iter()
+                .map(|(l, r)| (l.to_string(), r.to_string()))
+                .collect(),
+            values: BTreeMap::new(),
+        });
+
+    let mut names = BTreeMap::new();
+    let mut values = BTreeMap::new();
+    let expressions = std::iter::once(&key_condition)
+        .chain(&filter)
+        .chain(&projection);
+    for expression in expressions {
+        for (placeholder, name) in &expression.names {
+            if let Some(other) = names.insert(placeholder, name) {
+                assert_eq!(
+                    other, name,
+                    "attribute name placeholder `{placeholder}` is used for different names"
+                );
+            }
+        }
+        for (placeholder, value) in &expression.values {
+            if let Some(other) = values.insert(placeholder, value) {
+                assert_eq!(
+                    other, value,
+                    "attribute value placeholder `{placeholder}` is used for different values"
+                );
+            }
+        }
+    }
+
+    RenderedQuery {
+        index_name: Q::Index::DEFINITION.index_name(),
+        key_condition,
+        filter,
+        projection,
+        consistent_read: input.consistent_read(),
+        scan_index_forward: input.scan_index_forward(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{expr, keys, Entity, EntityDef, EntityTypeNameRef, Table};
+
+    struct TestTable;
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+
+        fn table_name(&self) -> &str {
+            unimplemented!()
+        }
+    }
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    struct TestEntity {
+        id: String,
+    }
+
+    impl EntityDef for TestEntity {
+        const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("test_ent");
+    }
+
+    impl Entity for TestEntity {
+        type KeyInput<'a> = &'a str;
+        type Table = TestTable;
+        type IndexKeys = ();
+
+        fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+            keys::Primary {
+                hash: format!("PK#{id}"),
+                range: "TEST".to_string(),
+            }
+        }
+
+        fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+            keys::FullKey {
+                primary: Self::primary_key(&self.id),
+                indexes: (),
+            }
+        }
+    }
+
+    struct TestQuery {
+        filter: expr::Filter,
+    }
+
+    impl QueryInput for TestQuery {
+        const SCAN_INDEX_FORWARD: bool = false;
+
+        type Index = keys::Primary;
+        type Aggregate = Vec<TestEntity>;
+
+        fn key_condition(&self) -> expr::KeyCondition<Self::Index> {
+            expr::KeyCondition::in_partition("PK#test").begins_with("TEST")
+        }
+
+        fn filter_expression(&self) -> Option<expr::Filter> {
+            Some(self.filter.clone())
+        }
+    }
+
+    #[test]
+    fn renders_the_full_request() {
+        let rendered = assert_query(&TestQuery {
+            filter: expr::Filter::new("#id <> :id")
+                .name("#id", "id")
+                .value(":id", "test2"),
+        });
+
+        assert_eq!(rendered.index_name, None);
+        assert_eq!(
+            rendered.key_condition.expression,
+            "#key_PK = :key_PK AND begins_with(#key_SK, :key_SK)"
+        );
+        assert_eq!(rendered.key_condition.names["#key_SK"], "SK");
+        assert_eq!(
+            rendered.key_condition.values[":key_PK"],
+            AttributeValue::S("PK#test".to_string())
+        );
+
+        let filter = rendered.filter.unwrap();
+        assert_eq!(filter.expression, "#flt_id <> :flt_id");
+        assert_eq!(filter.names["#flt_id"], "id");
+        assert_eq!(
+            filter.values[":flt_id"],
+            AttributeValue::S("test2".to_string())
+        );
+
+        assert!(!rendered.consistent_read);
+        assert!(!rendered.scan_index_forward);
+    }
+
+    #[test]
+    #[should_panic(expected = "query filter is invalid")]
+    fn undefined_filter_placeholders_panic() {
+        assert_query(&TestQuery {
+            filter: expr::Filter::new("#id <> :id").name("#id", "id"),
+        });
+    }
+}
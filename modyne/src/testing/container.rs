@@ -0,0 +1,109 @@
+//! Containers running DynamoDB-compatible services for integration tests
+
+use aws_sdk_dynamodb::config::{BehaviorVersion, Credentials, Region};
+use testcontainers::{core::WaitFor, runners::AsyncRunner, ContainerAsync, GenericImage};
+
+const DYNAMODB_LOCAL_IMAGE: (&str, &str) = ("amazon/dynamodb-local", "2.4.0");
+const DYNAMODB_LOCAL_PORT: u16 = 8000;
+
+const LOCALSTACK_IMAGE: (&str, &str) = (
+    "localstack/localstack",
+    "3.3.0@sha256:91271bdd1a2c3e59cd43c97483c1394672c1e4d2e13e883cfac3f832b23b3876",
+);
+const LOCALSTACK_PORT: u16 = 4566;
+
+/// A DynamoDB-compatible container started for a test
+///
+/// The container is stopped and removed when this guard is dropped, so the
+/// guard should be held for as long as the client is used.
+pub struct LocalDynamoDb {
+    client: aws_sdk_dynamodb::Client,
+    endpoint_url: String,
+    container: ContainerAsync<GenericImage>,
+}
+
+impl std::fmt::Debug for LocalDynamoDb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalDynamoDb")
+            .field("endpoint_url", &self.endpoint_url)
+            .field("container", &self.container.id())
+            .finish()
+    }
+}
+
+impl LocalDynamoDb {
+    /// A client configured to send requests to the container
+    #[inline]
+    pub fn client(&self) -> &aws_sdk_dynamodb::Client {
+        &self.client
+    }
+
+    /// The URL of the DynamoDB endpoint exposed by the container
+    #[inline]
+    pub fn endpoint_url(&self) -> &str {
+        &self.endpoint_url
+    }
+
+    async fn start(image: GenericImage, port: u16) -> Self {
+        let container = image.with_exposed_port(port).start().await;
+        let endpoint_url = format!(
+            "http://127.0.0.1:{}",
+            container.get_host_port_ipv4(port).await
+        );
+
+        let config = aws_sdk_dynamodb::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("test", "test", None, None, "static"))
+            .endpoint_url(&endpoint_url)
+            .build();
+
+        tracing::debug!(%endpoint_url, "started local DynamoDB container");
+
+        Self {
+            client: aws_sdk_dynamodb::Client::from_conf(config),
+            endpoint_url,
+            container,
+        }
+    }
+}
+
+/// Starts a [DynamoDB Local] container
+///
+/// Tables are held in memory, and are discarded along with the container
+/// when the returned guard is dropped.
+///
+/// # Panics
+///
+/// Panics if the container cannot be started, such as when Docker is not
+/// available.
+///
+/// [DynamoDB Local]: https://docs.aws.amazon.com/amazondynamodb/latest/developerguide/DynamoDBLocal.html
+pub async fn dynamodb_local() -> LocalDynamoDb {
+    let (name, tag) = DYNAMODB_LOCAL_IMAGE;
+    let image = GenericImage::new(name, tag).with_wait_for(WaitFor::message_on_stdout(
+        "Initializing DynamoDB Local with the following configuration",
+    ));
+
+    LocalDynamoDb::start(image, DYNAMODB_LOCAL_PORT).await
+}
+
+/// Starts a [LocalStack] container running only the DynamoDB service
+///
+/// The container is stopped and removed when the returned guard is dropped.
+///
+/// # Panics
+///
+/// Panics if the container cannot be started, such as when Docker is not
+/// available.
+///
+/// [LocalStack]: https://www.localstack.cloud/
+pub async fn localstack() -> LocalDynamoDb {
+    let (name, tag) = LOCALSTACK_IMAGE;
+    let image = GenericImage::new(name, tag)
+        .with_env_var("SERVICES", "dynamodb")
+        .with_env_var("EAGER_SERVICE_LOADING", "1")
+        .with_wait_for(WaitFor::message_on_stdout("Ready."));
+
+    LocalDynamoDb::start(image, LOCALSTACK_PORT).await
+}
@@ -0,0 +1,268 @@
+//! Generated round-trip tests for entity definitions
+
+use std::collections::HashMap;
+
+use proptest::{
+    arbitrary::{any, Arbitrary},
+    test_runner::{TestCaseError, TestRunner},
+};
+
+use crate::{keys, Entity, EntityExt, EntityTypeNameRef, Item, ProjectionSet, Table};
+
+/// Generates tests verifying the serialization of a set of entities
+///
+/// For each entity, arbitrary values are generated using [`proptest`], which
+/// requires that each entity implement [`Arbitrary`], for example by using
+/// `#[derive(proptest_derive::Arbitrary)]`. The generated tests verify that:
+///
+/// * every entity type has a unique entity type name,
+/// * each entity serializes into an item containing its primary key and the
+///   index key attributes produced by [`Entity::full_key`],
+/// * the item is recognized as the same entity type when read back, and
+/// * deserializing the item and serializing it again produces the same item,
+///   so that keys are stable across round trips.
+///
+/// The tests are placed in a `modyne_entity_tests` module, so this macro can
+/// be invoked at most once per module. This macro requires the `proptest`
+/// feature.
+///
+/// # Example
+///
+/// ```ignore
+/// modyne::test_entities!(Customer, Order, OrderItem);
+/// ```
+///
+/// [`proptest`]: https://docs.rs/proptest
+#[macro_export]
+macro_rules! test_entities {
+    ($($entity:ty),+ $(,)?) => {
+        #[cfg(test)]
+        mod modyne_entity_tests {
+            #[allow(unused_imports)]
+            use super::*;
+
+            #[test]
+            fn entity_types_are_unique() {
+                $crate::__private::assert_unique_entity_types(&[
+                    $((
+                        ::std::stringify!($entity),
+                        <$entity as $crate::EntityDef>::ENTITY_TYPE,
+                    ),)+
+                ]);
+            }
+
+            #[test]
+            fn entities_round_trip() {
+                $(
+                    $crate::__private::assert_entity_round_trip::<$entity>(
+                        ::std::stringify!($entity),
+                    );
+                )+
+            }
+        }
+    };
+}
+
+/// Asserts that no two entities share the same entity type name
+pub fn assert_unique_entity_types(entity_types: &[(&str, &EntityTypeNameRef)]) {
+    let mut seen = HashMap::new();
+    for &(name, entity_type) in entity_types {
+        if let Some(other) = seen.insert(entity_type, name) {
+            panic!("`{other}` and `{name}` share the entity type `{entity_type}`");
+        }
+    }
+}
+
+/// Asserts that arbitrary values of an entity survive a round trip through
+/// an item
+pub fn assert_entity_round_trip<E>(name: &str)
+where
+    E: Entity + Arbitrary + serde::Serialize + serde::de::DeserializeOwned + 'static,
+{
+    let mut runner = TestRunner::default();
+    let result = runner.run(&any::<E>(), |entity| {
+        check_round_trip(entity).map_err(TestCaseError::fail)
+    });
+
+    if let Err(error) = result {
+        panic!("`{name}` failed to round trip: {error}");
+    }
+}
+
+fn check_round_trip<E>(entity: E) -> Result<(), String>
+where
+    E: Entity + serde::Serialize + serde::de::DeserializeOwned + 'static,
+{
+    use keys::PrimaryKey;
+
+    let key = entity.full_key().into_key();
+    let item = entity.into_item();
+
+    let primary = <<E::Table as Table>::PrimaryKey as PrimaryKey>::PRIMARY_KEY_DEFINITION;
+    for attr in std::iter::once(primary.hash_key).chain(primary.range_key) {
+        if !item.contains_key(attr) {
+            return Err(format!("primary key attribute `{attr}` is missing"));
+        }
+    }
+
+    for (attr, value) in &key {
+        if item.get(attr) != Some(value) {
+            return Err(format!(
+                "key attribute `{attr}` does not match the value from `full_key()`"
+            ));
+        }
+    }
+
+    let entity = match E::try_from_item(item.clone()) {
+        Ok(Some(entity)) => entity,
+        Ok(None) => return Err("item was not recognized as the entity type".to_string()),
+        Err(error) => return Err(format!("item failed to deserialize: {error}")),
+    };
+
+    let round_tripped: Item = entity.into_item();
+    if round_tripped != item {
+        return Err(format!(
+            "item changed after a round trip\n  before: {item:?}\n  after:  {round_tripped:?}"
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::strategy::{BoxedStrategy, Strategy};
+
+    use super::*;
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            "TestTable"
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    struct Note {
+        id: String,
+        body: String,
+    }
+
+    impl crate::EntityDef for Note {
+        const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("note");
+    }
+
+    impl Entity for Note {
+        type KeyInput<'a> = &'a str;
+        type Table = TestTable;
+        type IndexKeys = keys::Gsi1;
+
+        fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+            keys::Primary {
+                hash: format!("NOTE#{id}"),
+                range: "NOTE".to_string(),
+            }
+        }
+
+        fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+            keys::FullKey {
+                primary: Self::primary_key(&self.id),
+                indexes: keys::Gsi1 {
+                    hash: "NOTES".to_string(),
+                    range: format!("NOTE#{}", self.id),
+                },
+            }
+        }
+    }
+
+    impl Arbitrary for Note {
+        type Parameters = ();
+        type Strategy = BoxedStrategy<Self>;
+
+        fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+            any::<(String, String)>()
+                .prop_map(|(id, body)| Note { id, body })
+                .boxed()
+        }
+    }
+
+    /// Loses its identifier when serialized, so that its keys change after
+    /// a round trip
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    struct Lossy {
+        #[serde(skip)]
+        id: String,
+    }
+
+    impl crate::EntityDef for Lossy {
+        const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("lossy");
+    }
+
+    impl Entity for Lossy {
+        type KeyInput<'a> = &'a str;
+        type Table = TestTable;
+        type IndexKeys = ();
+
+        fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary {
+            keys::Primary {
+                hash: format!("LOSSY#{id}"),
+                range: "LOSSY".to_string(),
+            }
+        }
+
+        fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+            keys::FullKey {
+                primary: Self::primary_key(&self.id),
+                indexes: (),
+            }
+        }
+    }
+
+    crate::test_entities!(Note);
+
+    #[test]
+    fn entities_that_survive_a_round_trip_pass() {
+        let note = Note {
+            id: "1".to_string(),
+            body: "hello".to_string(),
+        };
+        assert_eq!(check_round_trip(note), Ok(()));
+    }
+
+    #[test]
+    fn entities_that_lose_data_in_a_round_trip_fail() {
+        let lossy = Lossy {
+            id: "1".to_string(),
+        };
+        let error = check_round_trip(lossy).unwrap_err();
+        assert!(
+            error.starts_with("item changed after a round trip"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn unique_entity_types_pass() {
+        assert_unique_entity_types(&[
+            ("A", EntityTypeNameRef::from_static("a")),
+            ("B", EntityTypeNameRef::from_static("b")),
+        ]);
+    }
+
+    #[test]
+    #[should_panic(expected = "`A` and `B` share the entity type `a`")]
+    fn duplicate_entity_types_panic() {
+        assert_unique_entity_types(&[
+            ("A", EntityTypeNameRef::from_static("a")),
+            ("B", EntityTypeNameRef::from_static("a")),
+        ]);
+    }
+}
@@ -0,0 +1,457 @@
+//! Time-window partitions for time-series entities
+//!
+//! Time-series data is often spread across partitions that each hold a
+//! window of time, such as an hour, day, or month, so that no single
+//! partition grows without bound or becomes hot. A [`TimeSeriesKey`] maps
+//! timestamps to the partition keys of those windows and determines the
+//! partitions needed to cover a range of time. A [`TimeSeriesQuery`] then
+//! queries each of those partitions in turn, merging the results in time
+//! order.
+//!
+//! ```
+//! use modyne::time_series::{Bucket, TimeSeriesKey};
+//! use time::macros::datetime;
+//!
+//! let key = TimeSeriesKey::new("READINGS", Bucket::Day);
+//! assert_eq!(key.partition(datetime!(2024-02-28 13:45 UTC)).key(), "READINGS#2024-02-28");
+//!
+//! let partitions = key.partitions(
+//!     datetime!(2024-02-28 13:45 UTC),
+//!     datetime!(2024-03-01 00:00 UTC),
+//! );
+//! let keys: Vec<_> = partitions.iter().map(|p| p.key()).collect();
+//! assert_eq!(
+//!     keys,
+//!     ["READINGS#2024-02-28", "READINGS#2024-02-29", "READINGS#2024-03-01"],
+//! );
+//! ```
+
+use futures_util::{stream, StreamExt};
+use time::{Date, Duration, OffsetDateTime, UtcOffset};
+
+use crate::{
+    instrumentation::report_skipped_items, keys, Aggregate, Error, Item, QueryInput, QueryInputExt,
+    Table,
+};
+
+/// The number of partitions read at once by default
+const DEFAULT_CONCURRENCY: usize = 8;
+
+/// The window of time held by each partition
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Bucket {
+    /// Each partition holds an hour, labeled as `YYYY-MM-DDTHH`
+    Hour,
+
+    /// Each partition holds a day, labeled as `YYYY-MM-DD`
+    Day,
+
+    /// Each partition holds a calendar month, labeled as `YYYY-MM`
+    Month,
+}
+
+impl Bucket {
+    /// The start of the window containing the given time, in UTC
+    fn start_of(self, at: OffsetDateTime) -> OffsetDateTime {
+        let at = at.to_offset(UtcOffset::UTC);
+        let date = at.date();
+        match self {
+            Self::Hour => date.with_hms(at.hour(), 0, 0).unwrap().assume_utc(),
+            Self::Day => date.midnight().assume_utc(),
+            Self::Month => Date::from_calendar_date(date.year(), date.month(), 1)
+                .unwrap()
+                .midnight()
+                .assume_utc(),
+        }
+    }
+
+    /// The start of the window following the one starting at the given time
+    fn next_start(self, start: OffsetDateTime) -> OffsetDateTime {
+        match self {
+            Self::Hour => start + Duration::HOUR,
+            Self::Day => start + Duration::DAY,
+            Self::Month => {
+                let date = start.date();
+                let year = match date.month() {
+                    time::Month::December => date.year() + 1,
+                    _ => date.year(),
+                };
+                Date::from_calendar_date(year, date.month().next(), 1)
+                    .unwrap()
+                    .midnight()
+                    .assume_utc()
+            }
+        }
+    }
+
+    fn label(self, start: OffsetDateTime) -> String {
+        let (year, month, day) = (start.year(), u8::from(start.month()), start.day());
+        match self {
+            Self::Hour => format!("{year:04}-{month:02}-{day:02}T{:02}", start.hour()),
+            Self::Day => format!("{year:04}-{month:02}-{day:02}"),
+            Self::Month => format!("{year:04}-{month:02}"),
+        }
+    }
+}
+
+/// A partition holding a window of time
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimePartition {
+    key: String,
+    start: OffsetDateTime,
+    end: OffsetDateTime,
+}
+
+impl TimePartition {
+    /// The partition key value
+    #[inline]
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The inclusive start of the window, in UTC
+    #[inline]
+    pub fn start(&self) -> OffsetDateTime {
+        self.start
+    }
+
+    /// The exclusive end of the window, in UTC
+    #[inline]
+    pub fn end(&self) -> OffsetDateTime {
+        self.end
+    }
+
+    /// Converts the partition into its partition key value
+    #[inline]
+    pub fn into_key(self) -> String {
+        self.key
+    }
+}
+
+/// Maps timestamps to the partitions of a time series
+///
+/// Partition keys are formed from a prefix and the label of the window,
+/// separated by `#`, such as `READINGS#2024-02-28`. Windows are aligned to
+/// UTC.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TimeSeriesKey {
+    prefix: String,
+    bucket: Bucket,
+}
+
+impl TimeSeriesKey {
+    /// Construct a time series with the given partition key prefix and window size
+    pub fn new(prefix: impl Into<String>, bucket: Bucket) -> Self {
+        Self {
+            prefix: prefix.into(),
+            bucket,
+        }
+    }
+
+    /// The window of time held by each partition
+    #[inline]
+    pub fn bucket(&self) -> Bucket {
+        self.bucket
+    }
+
+    /// The partition holding the given time
+    pub fn partition(&self, at: OffsetDateTime) -> TimePartition {
+        let start = self.bucket.start_of(at);
+        TimePartition {
+            key: format!("{}#{}", self.prefix, self.bucket.label(start)),
+            start,
+            end: self.bucket.next_start(start),
+        }
+    }
+
+    /// The partitions covering the times from `start` to `end`, inclusive,
+    /// in ascending order
+    ///
+    /// If `start` is after `end`, no partitions are returned.
+    pub fn partitions(&self, start: OffsetDateTime, end: OffsetDateTime) -> Vec<TimePartition> {
+        let mut partitions = Vec::new();
+        if start > end {
+            return partitions;
+        }
+
+        let mut partition = self.partition(start);
+        while partition.start <= end {
+            let next = self.partition(partition.end);
+            partitions.push(partition);
+            partition = next;
+        }
+
+        partitions
+    }
+
+    /// Prepares a query of the partitions covering the times from `start` to
+    /// `end`, inclusive
+    pub fn query(&self, start: OffsetDateTime, end: OffsetDateTime) -> TimeSeriesQuery {
+        TimeSeriesQuery {
+            partitions: self.partitions(start, end),
+            limit: None,
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+
+/// A query across the partitions of a time series
+///
+/// The partitions are read in the scan direction of the query input:
+/// oldest first when scanning forward, or newest first when scanning
+/// backward. Key conditions should order items by time within each
+/// partition, so that the merged results are in time order.
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct TimeSeriesQuery {
+    partitions: Vec<TimePartition>,
+    limit: Option<usize>,
+    concurrency: usize,
+}
+
+impl TimeSeriesQuery {
+    /// Stop once the given number of items have been read
+    ///
+    /// With a limit, partitions are read one at a time until the limit is
+    /// reached. Otherwise, partitions are read concurrently, up to the
+    /// [`concurrency()`][Self::concurrency()] bound.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Sets the maximum number of partitions read at once when no limit is set
+    ///
+    /// Defaults to 8.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// The partitions to be queried, in ascending order
+    #[inline]
+    pub fn partitions(&self) -> &[TimePartition] {
+        &self.partitions
+    }
+
+    /// Executes the query, using the function to prepare the query input
+    /// for each partition
+    pub async fn execute<T, Q, F>(self, table: &T, input: F) -> Result<Q::Aggregate, Error>
+    where
+        T: Table,
+        Q: QueryInput,
+        F: FnMut(&TimePartition) -> Q,
+    {
+        let mut inputs: Vec<Q> = self.partitions.iter().map(input).collect();
+        if !inputs.first().map_or(true, Q::scan_index_forward) {
+            inputs.reverse();
+        }
+
+        let index_name = <Q::Index as keys::Key>::DEFINITION.index_name();
+        let mut aggregate = Q::Aggregate::default();
+        match self.limit {
+            Some(mut remaining) => {
+                for input in &inputs {
+                    if remaining == 0 {
+                        break;
+                    }
+
+                    let items = read_partition(table, input, Some(remaining)).await?;
+                    remaining = remaining.saturating_sub(items.len());
+                    report_skipped_items(table, "Query", || aggregate.reduce(items))
+                        .0
+                        .map_err(|error| error.with_index_projection(index_name))?;
+                }
+            }
+            None => {
+                let mut reads = stream::iter(&inputs)
+                    .map(|input| read_partition(table, input, None))
+                    .buffered(self.concurrency);
+                while let Some(items) = reads.next().await {
+                    report_skipped_items(table, "Query", || aggregate.reduce(items?))
+                        .0
+                        .map_err(|error| error.with_index_projection(index_name))?;
+                }
+            }
+        }
+
+        Ok(aggregate)
+    }
+}
+
+/// Reads the items of a partition, up to the limit if one is given
+async fn read_partition<T, Q>(
+    table: &T,
+    input: &Q,
+    limit: Option<usize>,
+) -> Result<Vec<Item>, Error>
+where
+    T: Table,
+    Q: QueryInput,
+{
+    let mut items = Vec::new();
+    let mut exclusive_start_key = None;
+
+    loop {
+        let remaining = limit.map(|limit| (limit - items.len()).min(u32::MAX as usize) as u32);
+        let output = input
+            .query()
+            .set_limit(remaining)
+            .set_exclusive_start_key(exclusive_start_key)
+            .execute(table)
+            .await?;
+
+        items.extend(output.items.unwrap_or_default());
+        exclusive_start_key = output.last_evaluated_key;
+        if exclusive_start_key.is_none() || limit.is_some_and(|limit| items.len() >= limit) {
+            break;
+        }
+    }
+
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_dynamodb::{operation::query::QueryOutput, types::AttributeValue};
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::{
+        expr, keys,
+        mock::{ops, MockTable, Operation},
+        RawItem,
+    };
+
+    struct TestTable;
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+
+        fn table_name(&self) -> &str {
+            unimplemented!()
+        }
+    }
+
+    struct Readings(TimePartition);
+    impl QueryInput for Readings {
+        type Index = keys::Primary;
+        type Aggregate = Vec<RawItem<TestTable>>;
+
+        fn key_condition(&self) -> expr::KeyCondition<Self::Index> {
+            expr::KeyCondition::in_partition(self.0.key())
+        }
+    }
+
+    fn reading(partition: &str, n: usize) -> Item {
+        [
+            ("PK", partition.to_string()),
+            ("SK", format!("{n}")),
+            ("entity_type", "reading".to_string()),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), AttributeValue::S(v)))
+        .collect()
+    }
+
+    #[test]
+    fn partitions_cover_the_range_inclusively() {
+        let key = TimeSeriesKey::new("EVENTS", Bucket::Hour);
+        let partitions = key.partitions(
+            datetime!(2024-01-01 22:30 +01:00),
+            datetime!(2024-01-01 23:00 UTC),
+        );
+        let keys: Vec<_> = partitions.iter().map(TimePartition::key).collect();
+        assert_eq!(
+            keys,
+            [
+                "EVENTS#2024-01-01T21",
+                "EVENTS#2024-01-01T22",
+                "EVENTS#2024-01-01T23"
+            ]
+        );
+        assert_eq!(partitions[0].start(), datetime!(2024-01-01 21:00 UTC));
+        assert_eq!(partitions[0].end(), datetime!(2024-01-01 22:00 UTC));
+    }
+
+    #[test]
+    fn month_partitions_roll_over_years() {
+        let key = TimeSeriesKey::new("EVENTS", Bucket::Month);
+        let partitions = key.partitions(
+            datetime!(2023-11-15 00:00 UTC),
+            datetime!(2024-01-31 23:59 UTC),
+        );
+        let keys: Vec<_> = partitions.iter().map(TimePartition::key).collect();
+        assert_eq!(keys, ["EVENTS#2023-11", "EVENTS#2023-12", "EVENTS#2024-01"]);
+        assert_eq!(partitions[1].end(), datetime!(2024-01-01 00:00 UTC));
+    }
+
+    #[test]
+    fn reversed_ranges_have_no_partitions() {
+        let key = TimeSeriesKey::new("EVENTS", Bucket::Day);
+        let partitions = key.partitions(
+            datetime!(2024-01-02 00:00 UTC),
+            datetime!(2024-01-01 00:00 UTC),
+        );
+        assert!(partitions.is_empty());
+    }
+
+    #[test]
+    fn partitions_are_read_concurrently_and_merged_in_order() {
+        let table = MockTable::<TestTable>::new("test");
+        table.expect::<ops::Query>(|e| {
+            e.responding(|input| {
+                let values = input.expression_attribute_values.as_ref().unwrap();
+                let partition = values[":key_PK"].as_s().unwrap();
+                let items: Vec<_> = (0..2).map(|n| reading(partition, n)).collect();
+                Ok(QueryOutput::builder()
+                    .count(items.len() as i32)
+                    .set_items(Some(items))
+                    .build())
+            })
+        });
+
+        let query = TimeSeriesKey::new("READINGS", Bucket::Day)
+            .query(
+                datetime!(2024-01-01 00:00 UTC),
+                datetime!(2024-01-04 00:00 UTC),
+            )
+            .concurrency(2);
+        let readings = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(query.execute(&table, |partition| Readings(partition.clone())))
+            .unwrap();
+
+        let keys: Vec<_> = readings
+            .iter()
+            .map(|reading| {
+                let item = reading.item();
+                format!(
+                    "{}/{}",
+                    item["PK"].as_s().unwrap(),
+                    item["SK"].as_s().unwrap()
+                )
+            })
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "READINGS#2024-01-01/0",
+                "READINGS#2024-01-01/1",
+                "READINGS#2024-01-02/0",
+                "READINGS#2024-01-02/1",
+                "READINGS#2024-01-03/0",
+                "READINGS#2024-01-03/1",
+                "READINGS#2024-01-04/0",
+                "READINGS#2024-01-04/1",
+            ]
+        );
+        assert_eq!(table.calls(Operation::Query), 4);
+    }
+}
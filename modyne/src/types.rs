@@ -1,9 +1,19 @@
 //! Types useful as attributes in DynamoDB items
 
-use std::time::SystemTime;
+pub mod ids;
+mod sets;
+
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime},
+};
 
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
+pub use self::sets::{AttributeSet, BinarySet, NumberSet, StringSet};
+use crate::clock::Clock;
+
 /// A type representing the expiry (TTL) of a DynamoDB item
 ///
 /// This type is used to represent the expiry of a DynamoDB item. It is
@@ -26,6 +36,28 @@ impl Expiry {
     pub fn key_format(&self) -> String {
         self.inner.format(&Rfc3339).unwrap()
     }
+
+    /// An expiry the given duration after the current time of the clock
+    pub fn after(clock: &dyn Clock, ttl: Duration) -> Self {
+        (clock.now() + ttl).into()
+    }
+
+    /// Whether the expiry has passed according to the clock
+    pub fn is_expired(&self, clock: &dyn Clock) -> bool {
+        self.inner <= clock.now()
+    }
+
+    /// A filter that excludes items whose expiry, stored in the given
+    /// attribute, has passed according to the clock
+    ///
+    /// DynamoDB deletes expired items in the background, so expired items
+    /// may still be returned by reads for some time after they expire.
+    /// Items without the attribute are not excluded.
+    pub fn unexpired_filter(attribute: &str, clock: &dyn Clock) -> crate::expr::Filter {
+        crate::expr::Filter::new("attribute_not_exists(#ttl) OR #ttl > :now")
+            .name("#ttl", attribute)
+            .value(":now", clock.now().unix_timestamp())
+    }
 }
 
 impl From<OffsetDateTime> for Expiry {
@@ -58,6 +90,465 @@ impl From<Expiry> for SystemTime {
     }
 }
 
+/// A timestamp formatted so that lexicographic ordering matches chronological ordering
+///
+/// RFC 3339 timestamps only sort correctly as strings when they share the
+/// same offset and the same number of fractional digits. This type is
+/// always normalized to UTC and serialized as an RFC 3339 string with
+/// exactly nine fractional digits, such as `2023-12-07T13:45:00.250000000Z`,
+/// making it suitable for use as, or as a component of, a range key.
+///
+/// Only timestamps with years between 0 and 9999 can be represented.
+///
+/// ```
+/// use modyne::types::SortableTimestamp;
+/// use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+///
+/// let ts = OffsetDateTime::parse("2023-12-07T15:45:00.25+02:00", &Rfc3339).unwrap();
+/// let ts = SortableTimestamp::try_from(ts).unwrap();
+/// assert_eq!(ts.to_string(), "2023-12-07T13:45:00.250000000Z");
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub struct SortableTimestamp {
+    inner: OffsetDateTime,
+}
+
+impl SortableTimestamp {
+    /// The current time
+    pub fn now() -> Self {
+        Self {
+            inner: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// The current time according to the clock
+    pub fn now_from(clock: &dyn Clock) -> Result<Self, TimestampOutOfRangeError> {
+        Self::try_from(clock.now())
+    }
+}
+
+impl fmt::Display for SortableTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let ts = self.inner;
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:09}Z",
+            ts.year(),
+            u8::from(ts.month()),
+            ts.day(),
+            ts.hour(),
+            ts.minute(),
+            ts.second(),
+            ts.nanosecond(),
+        )
+    }
+}
+
+impl FromStr for SortableTimestamp {
+    type Err = ParseSortableTimestampError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let inner =
+            OffsetDateTime::parse(s, &Rfc3339).map_err(|_| ParseSortableTimestampError(()))?;
+        let ts = Self::try_from(inner).map_err(|_| ParseSortableTimestampError(()))?;
+
+        // Only the canonical form is accepted, so that stored keys always sort correctly
+        if ts.to_string() != s {
+            return Err(ParseSortableTimestampError(()));
+        }
+
+        Ok(ts)
+    }
+}
+
+impl TryFrom<OffsetDateTime> for SortableTimestamp {
+    type Error = TimestampOutOfRangeError;
+
+    #[inline]
+    fn try_from(ts: OffsetDateTime) -> Result<Self, Self::Error> {
+        let inner = ts.to_offset(time::UtcOffset::UTC);
+        if (0..=9999).contains(&inner.year()) {
+            Ok(Self { inner })
+        } else {
+            Err(TimestampOutOfRangeError(()))
+        }
+    }
+}
+
+impl From<SortableTimestamp> for OffsetDateTime {
+    #[inline]
+    fn from(ts: SortableTimestamp) -> Self {
+        ts.inner
+    }
+}
+
+impl TryFrom<SystemTime> for SortableTimestamp {
+    type Error = TimestampOutOfRangeError;
+
+    #[inline]
+    fn try_from(ts: SystemTime) -> Result<Self, Self::Error> {
+        OffsetDateTime::from(ts).try_into()
+    }
+}
+
+impl From<SortableTimestamp> for SystemTime {
+    #[inline]
+    fn from(ts: SortableTimestamp) -> Self {
+        OffsetDateTime::from(ts).into()
+    }
+}
+
+impl serde::Serialize for SortableTimestamp {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for SortableTimestamp {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A timestamp was outside the range representable by a [`SortableTimestamp`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("timestamp year must be between 0 and 9999")]
+pub struct TimestampOutOfRangeError(());
+
+/// A string was not a timestamp in the canonical [`SortableTimestamp`] format
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("expected an RFC 3339 timestamp in UTC with nine fractional digits")]
+pub struct ParseSortableTimestampError(());
+
+/// An unsigned integer formatted as a fixed-width, zero-padded string
+///
+/// Numbers formatted as strings do not sort numerically unless they are
+/// padded to the same width. This type is serialized as a string of exactly
+/// `WIDTH` digits, suitable for use as a component of a range key, so that
+/// lexicographic ordering always matches numeric ordering. Values that
+/// cannot be represented in `WIDTH` digits are rejected on construction.
+///
+/// ```
+/// use modyne::types::ZeroPadded;
+///
+/// let number = ZeroPadded::<10>::new(42).unwrap();
+/// assert_eq!(format!("ISSUE#{number}"), "ISSUE#0000000042");
+///
+/// assert!(ZeroPadded::<2>::new(100).is_err());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Ord, PartialOrd)]
+pub struct ZeroPadded<const WIDTH: usize>(u64);
+
+impl<const WIDTH: usize> ZeroPadded<WIDTH> {
+    /// The largest value that can be represented in `WIDTH` digits
+    pub const MAX: Self = Self(match 10u64.checked_pow(WIDTH as u32) {
+        Some(limit) => limit - 1,
+        None => u64::MAX,
+    });
+
+    /// Wraps a value, failing if it cannot be represented in `WIDTH` digits
+    pub const fn new(value: u64) -> Result<Self, WidthExceededError> {
+        if value > Self::MAX.0 {
+            Err(WidthExceededError {
+                value,
+                width: WIDTH,
+            })
+        } else {
+            Ok(Self(value))
+        }
+    }
+
+    /// The wrapped value
+    #[inline]
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+}
+
+impl<const WIDTH: usize> fmt::Display for ZeroPadded<WIDTH> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:0width$}", self.0, width = WIDTH)
+    }
+}
+
+impl<const WIDTH: usize> FromStr for ZeroPadded<WIDTH> {
+    type Err = ParseZeroPaddedError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != WIDTH || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(ParseZeroPaddedError { width: WIDTH });
+        }
+
+        s.parse()
+            .map(Self)
+            .map_err(|_| ParseZeroPaddedError { width: WIDTH })
+    }
+}
+
+impl<const WIDTH: usize> TryFrom<u64> for ZeroPadded<WIDTH> {
+    type Error = WidthExceededError;
+
+    #[inline]
+    fn try_from(value: u64) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl<const WIDTH: usize> From<ZeroPadded<WIDTH>> for u64 {
+    #[inline]
+    fn from(value: ZeroPadded<WIDTH>) -> Self {
+        value.0
+    }
+}
+
+impl<const WIDTH: usize> serde::Serialize for ZeroPadded<WIDTH> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de, const WIDTH: usize> serde::Deserialize<'de> for ZeroPadded<WIDTH> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A value that should not be logged
+///
+/// When a `Sensitive` value is added to a filter, condition, or update
+/// expression with `value()`, it is treated as though it had been added
+/// with `sensitive_value()`, and so is omitted from the expression's
+/// debug output. The value is also redacted from the debug output of any
+/// type that contains it. Wrapping a value in `Some` preserves this
+/// behavior, but values nested within other types are only redacted from
+/// debug output.
+///
+/// Otherwise, the value is serialized and deserialized as the inner value.
+///
+/// ```
+/// use modyne::{expr, types::Sensitive};
+///
+/// let filter = expr::Filter::new("#email = :email")
+///     .name("#email", "email")
+///     .value(":email", Sensitive("alex@example.com"));
+///
+/// assert!(filter.values.is_empty());
+/// assert_eq!(filter.sensitive_values.len(), 1);
+/// assert!(!format!("{filter:?}").contains("alex@example.com"));
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Sensitive<T>(pub T);
+
+/// The newtype name used to identify a [`Sensitive`] value during serialization
+const SENSITIVE_NAME: &str = "$modyne::Sensitive";
+
+impl<T> Sensitive<T> {
+    /// Unwraps the inner value
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for Sensitive<T> {
+    #[inline]
+    fn from(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> std::ops::Deref for Sensitive<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> fmt::Debug for Sensitive<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Sensitive(<redacted>)")
+    }
+}
+
+impl<T: serde::Serialize> serde::Serialize for Sensitive<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(SENSITIVE_NAME, &self.0)
+    }
+}
+
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Sensitive<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Self)
+    }
+}
+
+/// Determines whether a value is a [`Sensitive`] value, possibly wrapped in `Some`
+pub(crate) fn is_sensitive<T: serde::Serialize + ?Sized>(value: &T) -> bool {
+    value.serialize(SensitiveProbe).unwrap_or(false)
+}
+
+/// A serializer that only inspects the outermost value for the [`Sensitive`] marker
+struct SensitiveProbe;
+
+/// The probed value is not a [`Sensitive`] value
+#[derive(Debug, thiserror::Error)]
+#[error("value is not sensitive")]
+struct NotSensitive;
+
+impl serde::ser::Error for NotSensitive {
+    fn custom<M: fmt::Display>(_msg: M) -> Self {
+        Self
+    }
+}
+
+macro_rules! probe_scalars {
+    ($($method:ident($ty:ty)),* $(,)?) => {
+        $(
+            fn $method(self, _v: $ty) -> Result<bool, NotSensitive> {
+                Ok(false)
+            }
+        )*
+    };
+}
+
+impl serde::Serializer for SensitiveProbe {
+    type Ok = bool;
+    type Error = NotSensitive;
+    type SerializeSeq = serde::ser::Impossible<bool, NotSensitive>;
+    type SerializeTuple = serde::ser::Impossible<bool, NotSensitive>;
+    type SerializeTupleStruct = serde::ser::Impossible<bool, NotSensitive>;
+    type SerializeTupleVariant = serde::ser::Impossible<bool, NotSensitive>;
+    type SerializeMap = serde::ser::Impossible<bool, NotSensitive>;
+    type SerializeStruct = serde::ser::Impossible<bool, NotSensitive>;
+    type SerializeStructVariant = serde::ser::Impossible<bool, NotSensitive>;
+
+    probe_scalars! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+        serialize_bytes(&[u8]),
+        serialize_unit_struct(&'static str),
+    }
+
+    fn serialize_none(self) -> Result<bool, NotSensitive> {
+        Ok(false)
+    }
+
+    fn serialize_some<T: serde::Serialize + ?Sized>(self, value: &T) -> Result<bool, NotSensitive> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<bool, NotSensitive> {
+        Ok(false)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<bool, NotSensitive> {
+        Ok(false)
+    }
+
+    fn serialize_newtype_struct<T: serde::Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        _value: &T,
+    ) -> Result<bool, NotSensitive> {
+        Ok(name == SENSITIVE_NAME)
+    }
+
+    fn serialize_newtype_variant<T: serde::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<bool, NotSensitive> {
+        Ok(false)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, NotSensitive> {
+        Err(NotSensitive)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, NotSensitive> {
+        Err(NotSensitive)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, NotSensitive> {
+        Err(NotSensitive)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, NotSensitive> {
+        Err(NotSensitive)
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, NotSensitive> {
+        Err(NotSensitive)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, NotSensitive> {
+        Err(NotSensitive)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, NotSensitive> {
+        Err(NotSensitive)
+    }
+}
+
+/// A value could not be represented in the required number of digits
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("value {value} cannot be represented in {width} digits")]
+pub struct WidthExceededError {
+    value: u64,
+    width: usize,
+}
+
+/// A string was not a zero-padded number of the required width
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("expected a zero-padded number of exactly {width} digits")]
+pub struct ParseZeroPaddedError {
+    width: usize,
+}
+
 #[cfg(test)]
 mod tests {
     use aws_sdk_dynamodb::types::AttributeValue;
@@ -96,4 +587,136 @@ mod tests {
         let attribute = crate::codec::to_attribute_value(ts).unwrap();
         assert_eq!(attribute, AttributeValue::N("12345321".to_string()));
     }
+
+    #[test]
+    fn zero_padded_matches_expected_format() {
+        let number = ZeroPadded::<10>::new(1234).unwrap();
+        assert_eq!(number.to_string(), "0000001234");
+
+        let attribute = crate::codec::to_attribute_value(number).unwrap();
+        assert_eq!(attribute, AttributeValue::S("0000001234".to_string()));
+    }
+
+    #[test]
+    fn zero_padded_rejects_values_wider_than_width() {
+        assert_eq!(ZeroPadded::<3>::MAX.get(), 999);
+        assert!(ZeroPadded::<3>::new(999).is_ok());
+        assert!(ZeroPadded::<3>::new(1000).is_err());
+        assert_eq!(ZeroPadded::<20>::MAX.get(), u64::MAX);
+        assert_eq!(ZeroPadded::<25>::MAX.get(), u64::MAX);
+    }
+
+    #[test]
+    fn zero_padded_sorts_numerically() {
+        let mut formatted: Vec<_> = [10, 9, 100, 1]
+            .into_iter()
+            .map(|n| ZeroPadded::<4>::new(n).unwrap().to_string())
+            .collect();
+        formatted.sort();
+        assert_eq!(formatted, ["0001", "0009", "0010", "0100"]);
+    }
+
+    #[test]
+    fn zero_padded_round_trips() {
+        let attribute = AttributeValue::S("0042".to_string());
+        let number: ZeroPadded<4> = crate::codec::from_attribute_value(attribute).unwrap();
+        assert_eq!(number.get(), 42);
+
+        assert!("42".parse::<ZeroPadded<4>>().is_err());
+        assert!("+042".parse::<ZeroPadded<4>>().is_err());
+    }
+
+    #[test]
+    fn sortable_timestamp_uses_fixed_precision_in_utc() {
+        let ts = OffsetDateTime::parse("1970-05-23T21:15:21+03:30", &Rfc3339).unwrap();
+        let ts = SortableTimestamp::try_from(ts).unwrap();
+        assert_eq!(ts.to_string(), "1970-05-23T17:45:21.000000000Z");
+
+        let attribute = crate::codec::to_attribute_value(ts).unwrap();
+        assert_eq!(
+            attribute,
+            AttributeValue::S("1970-05-23T17:45:21.000000000Z".to_string())
+        );
+    }
+
+    #[test]
+    fn sortable_timestamp_sorts_chronologically() {
+        let mut formatted: Vec<_> = [
+            "1970-05-23T21:15:21.5Z",
+            "1970-05-23T21:15:21Z",
+            "1970-05-23T23:15:20.999+02:00",
+            "1970-05-23T21:15:21.05Z",
+        ]
+        .into_iter()
+        .map(|s| {
+            let ts = OffsetDateTime::parse(s, &Rfc3339).unwrap();
+            SortableTimestamp::try_from(ts).unwrap().to_string()
+        })
+        .collect();
+        formatted.sort();
+        assert_eq!(
+            formatted,
+            [
+                "1970-05-23T21:15:20.999000000Z",
+                "1970-05-23T21:15:21.000000000Z",
+                "1970-05-23T21:15:21.050000000Z",
+                "1970-05-23T21:15:21.500000000Z",
+            ]
+        );
+    }
+
+    #[test]
+    fn sortable_timestamp_round_trips() {
+        let attribute = AttributeValue::S("1970-05-23T21:15:21.012345678Z".to_string());
+        let ts: SortableTimestamp = crate::codec::from_attribute_value(attribute).unwrap();
+        assert_eq!(
+            OffsetDateTime::from(ts),
+            OffsetDateTime::parse("1970-05-23T21:15:21.012345678Z", &Rfc3339).unwrap()
+        );
+
+        assert!("1970-05-23T21:15:21Z".parse::<SortableTimestamp>().is_err());
+        assert!("1970-05-23T21:15:21.012345678+00:00"
+            .parse::<SortableTimestamp>()
+            .is_err());
+    }
+
+    #[test]
+    fn expiry_follows_the_clock() {
+        let clock =
+            crate::clock::FixedClock::new(OffsetDateTime::from_unix_timestamp(1000).unwrap());
+        let expiry = Expiry::after(&clock, Duration::from_secs(60));
+        assert!(!expiry.is_expired(&clock));
+
+        clock.advance(Duration::from_secs(60));
+        assert!(expiry.is_expired(&clock));
+
+        let filter = Expiry::unexpired_filter("ttl", &clock);
+        assert_eq!(
+            filter.values,
+            [(
+                ":flt_now".to_string(),
+                AttributeValue::N("1060".to_string())
+            )]
+        );
+    }
+
+    #[test]
+    fn sensitive_values_are_detected_through_options() {
+        assert!(is_sensitive(&Sensitive("secret")));
+        assert!(is_sensitive(&Some(Sensitive(42))));
+        assert!(!is_sensitive(&"secret"));
+        assert!(!is_sensitive(&[Sensitive("secret")]));
+    }
+
+    #[test]
+    fn sensitive_values_serialize_as_inner_value_and_redact_debug() {
+        let value = Sensitive("secret".to_string());
+        let attribute = crate::codec::to_attribute_value(&value).unwrap();
+        assert_eq!(attribute, AttributeValue::S("secret".to_string()));
+        assert_eq!(format!("{value:?}"), "Sensitive(<redacted>)");
+
+        let round_tripped: Sensitive<String> =
+            crate::codec::from_attribute_value(attribute).unwrap();
+        assert_eq!(round_tripped, value);
+    }
 }
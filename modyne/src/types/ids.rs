@@ -0,0 +1,361 @@
+//! Sortable, time-ordered identifiers
+//!
+//! Identifiers that begin with the time they were created sort in creation
+//! order, which makes them well suited to range keys: the most recent
+//! entities can be read by querying in reverse, and paginating by the last
+//! identifier seen resumes where the previous page left off. A
+//! [`TimeOrderedId`] wraps one of several identifier formats, tagged with
+//! the entity type it identifies, so that the identifiers of different
+//! entity types cannot be mixed up.
+//!
+//! Each format is provided behind a feature:
+//!
+//! * `ksuid`: [`KsuidId`], a [KSUID](https://github.com/segmentio/ksuid)
+//!   with one-second precision
+//! * `ulid`: [`UlidId`], a [ULID](https://github.com/ulid/spec) with
+//!   millisecond precision
+//! * `uuid`: [`UuidV7Id`], a version 7 UUID with millisecond precision
+//!
+//! Other formats can be supported by implementing [`IdFormat`].
+//!
+//! ```
+//! # #[cfg(feature = "ksuid")] {
+//! use modyne::types::ids::KsuidId;
+//! use time::macros::datetime;
+//!
+//! struct Deal;
+//! type DealId = KsuidId<Deal>;
+//!
+//! let id = DealId::at(datetime!(2024-02-28 13:45 UTC));
+//! assert_eq!(id.created_at(), datetime!(2024-02-28 13:45 UTC));
+//!
+//! let parsed: DealId = id.to_string().parse().unwrap();
+//! assert_eq!(parsed, id);
+//! assert!(id < DealId::at(datetime!(2024-02-28 13:46 UTC)));
+//! # }
+//! ```
+
+use std::{cmp::Ordering, fmt, hash::Hash, marker::PhantomData, str::FromStr};
+
+use time::OffsetDateTime;
+
+use crate::clock::Clock;
+
+/// A format of identifier that encodes the time it was created
+///
+/// The string form of an identifier must sort in the same order as the
+/// identifiers themselves, so that identifiers stored as attributes sort
+/// by creation time.
+pub trait IdFormat: Copy + Ord + fmt::Debug + fmt::Display + Send + Sync + 'static {
+    /// The name of the format, used in parse errors
+    const NAME: &'static str;
+
+    /// Generates a new identifier for the given time
+    fn generate(at: OffsetDateTime) -> Self;
+
+    /// The time encoded in the identifier
+    fn created_at(&self) -> OffsetDateTime;
+
+    /// Parses an identifier from its string form
+    fn parse(s: &str) -> Option<Self>;
+}
+
+/// A time-ordered identifier for entities of type `E`
+///
+/// The identifier is serialized in the string form of its format.
+pub struct TimeOrderedId<E, F> {
+    id: F,
+    entity: PhantomData<fn() -> E>,
+}
+
+impl<E, F: IdFormat> TimeOrderedId<E, F> {
+    /// Generates a new identifier for the current time
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        Self::at(OffsetDateTime::now_utc())
+    }
+
+    /// Generates a new identifier for the current time according to the clock
+    pub fn now_from(clock: &dyn Clock) -> Self {
+        Self::at(clock.now())
+    }
+
+    /// Generates a new identifier for the given time
+    pub fn at(at: OffsetDateTime) -> Self {
+        Self::from_inner(F::generate(at))
+    }
+
+    /// The time encoded in the identifier, at the precision of its format
+    #[inline]
+    pub fn created_at(&self) -> OffsetDateTime {
+        self.id.created_at()
+    }
+
+    /// Wraps an existing identifier
+    #[inline]
+    pub fn from_inner(id: F) -> Self {
+        Self {
+            id,
+            entity: PhantomData,
+        }
+    }
+
+    /// The underlying identifier
+    #[inline]
+    pub fn inner(&self) -> &F {
+        &self.id
+    }
+
+    /// Converts into the underlying identifier
+    #[inline]
+    pub fn into_inner(self) -> F {
+        self.id
+    }
+}
+
+impl<E, F: Clone> Clone for TimeOrderedId<E, F> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            id: self.id.clone(),
+            entity: PhantomData,
+        }
+    }
+}
+
+impl<E, F: Copy> Copy for TimeOrderedId<E, F> {}
+
+impl<E, F: PartialEq> PartialEq for TimeOrderedId<E, F> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl<E, F: Eq> Eq for TimeOrderedId<E, F> {}
+
+impl<E, F: PartialOrd> PartialOrd for TimeOrderedId<E, F> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.id.partial_cmp(&other.id)
+    }
+}
+
+impl<E, F: Ord> Ord for TimeOrderedId<E, F> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.id.cmp(&other.id)
+    }
+}
+
+impl<E, F: Hash> Hash for TimeOrderedId<E, F> {
+    #[inline]
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
+}
+
+impl<E, F: fmt::Debug> fmt::Debug for TimeOrderedId<E, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("TimeOrderedId").field(&self.id).finish()
+    }
+}
+
+impl<E, F: fmt::Display> fmt::Display for TimeOrderedId<E, F> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.id.fmt(f)
+    }
+}
+
+impl<E, F: IdFormat> FromStr for TimeOrderedId<E, F> {
+    type Err = ParseIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        F::parse(s)
+            .map(Self::from_inner)
+            .ok_or(ParseIdError { format: F::NAME })
+    }
+}
+
+impl<E, F: IdFormat> serde::Serialize for TimeOrderedId<E, F> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de, E, F: IdFormat> serde::Deserialize<'de> for TimeOrderedId<E, F> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A string was not an identifier in the expected format
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("expected a {format} identifier")]
+pub struct ParseIdError {
+    format: &'static str,
+}
+
+/// A time-ordered identifier using the KSUID format
+#[cfg(feature = "ksuid")]
+pub type KsuidId<E> = TimeOrderedId<E, svix_ksuid::Ksuid>;
+
+#[cfg(feature = "ksuid")]
+impl IdFormat for svix_ksuid::Ksuid {
+    const NAME: &'static str = "KSUID";
+
+    #[inline]
+    fn generate(at: OffsetDateTime) -> Self {
+        <Self as svix_ksuid::KsuidLike>::new(Some(at), None)
+    }
+
+    #[inline]
+    fn created_at(&self) -> OffsetDateTime {
+        svix_ksuid::KsuidLike::timestamp(self)
+    }
+
+    #[inline]
+    fn parse(s: &str) -> Option<Self> {
+        <svix_ksuid::Ksuid as core::str::FromStr>::from_str(s).ok()
+    }
+}
+
+/// A time-ordered identifier using the ULID format
+#[cfg(feature = "ulid")]
+pub type UlidId<E> = TimeOrderedId<E, ulid::Ulid>;
+
+#[cfg(feature = "ulid")]
+impl IdFormat for ulid::Ulid {
+    const NAME: &'static str = "ULID";
+
+    #[inline]
+    fn generate(at: OffsetDateTime) -> Self {
+        Self::from_datetime(at.into())
+    }
+
+    #[inline]
+    fn created_at(&self) -> OffsetDateTime {
+        self.datetime().into()
+    }
+
+    #[inline]
+    fn parse(s: &str) -> Option<Self> {
+        Self::from_string(s).ok()
+    }
+}
+
+/// A time-ordered identifier using the version 7 UUID format
+///
+/// Only version 7 UUIDs are accepted when parsing.
+#[cfg(feature = "uuid")]
+pub type UuidV7Id<E> = TimeOrderedId<E, uuid::Uuid>;
+
+#[cfg(feature = "uuid")]
+impl IdFormat for uuid::Uuid {
+    const NAME: &'static str = "version 7 UUID";
+
+    fn generate(at: OffsetDateTime) -> Self {
+        let nanos = at.unix_timestamp_nanos().max(0);
+        let seconds = (nanos / 1_000_000_000) as u64;
+        let subsec_nanos = (nanos % 1_000_000_000) as u32;
+        Self::new_v7(uuid::Timestamp::from_unix(
+            uuid::NoContext,
+            seconds,
+            subsec_nanos,
+        ))
+    }
+
+    fn created_at(&self) -> OffsetDateTime {
+        let (seconds, nanos) = self
+            .get_timestamp()
+            .map_or((0, 0), |timestamp| timestamp.to_unix());
+        OffsetDateTime::from_unix_timestamp_nanos(
+            i128::from(seconds) * 1_000_000_000 + i128::from(nanos),
+        )
+        .unwrap_or(OffsetDateTime::UNIX_EPOCH)
+    }
+
+    #[inline]
+    fn parse(s: &str) -> Option<Self> {
+        Self::try_parse(s)
+            .ok()
+            .filter(|uuid| uuid.get_version() == Some(uuid::Version::SortRand))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    /// A format holding a whole number of seconds, zero-padded to sort
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+    struct Seconds(u32);
+
+    impl fmt::Display for Seconds {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{:010}", self.0)
+        }
+    }
+
+    impl IdFormat for Seconds {
+        const NAME: &'static str = "seconds";
+
+        fn generate(at: OffsetDateTime) -> Self {
+            Self(at.unix_timestamp() as u32)
+        }
+
+        fn created_at(&self) -> OffsetDateTime {
+            OffsetDateTime::from_unix_timestamp(self.0.into()).unwrap()
+        }
+
+        fn parse(s: &str) -> Option<Self> {
+            (s.len() == 10).then(|| s.parse().ok().map(Self)).flatten()
+        }
+    }
+
+    struct Order;
+    type OrderId = TimeOrderedId<Order, Seconds>;
+
+    #[test]
+    fn ids_round_trip_through_attributes() {
+        let id = OrderId::at(datetime!(2024-02-28 13:45 UTC));
+        let value: crate::AttributeValue = serde_dynamo::to_attribute_value(id).unwrap();
+        assert_eq!(value, crate::AttributeValue::S("1709127900".into()));
+
+        let parsed: OrderId = serde_dynamo::from_attribute_value(value).unwrap();
+        assert_eq!(parsed, id);
+        assert_eq!(parsed.created_at(), datetime!(2024-02-28 13:45 UTC));
+    }
+
+    #[test]
+    fn ids_from_a_clock_sort_by_time() {
+        let clock = crate::clock::FixedClock::new(datetime!(2024-02-28 13:45 UTC));
+        let earlier = OrderId::now_from(&clock);
+        clock.advance(std::time::Duration::from_secs(1));
+        let later = OrderId::now_from(&clock);
+
+        assert!(earlier < later);
+        assert!(earlier.to_string() < later.to_string());
+    }
+
+    #[test]
+    fn malformed_ids_are_rejected() {
+        let error = "17091279".parse::<OrderId>().unwrap_err();
+        assert_eq!(error.to_string(), "expected a seconds identifier");
+    }
+
+    #[cfg(feature = "uuid")]
+    #[test]
+    fn only_version_7_uuids_are_accepted() {
+        let id = UuidV7Id::<Order>::at(datetime!(2024-02-28 13:45:00.123 UTC));
+        assert_eq!(id.created_at(), datetime!(2024-02-28 13:45:00.123 UTC));
+        assert_eq!(id.to_string().parse::<UuidV7Id<Order>>().unwrap(), id);
+
+        let v4 = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        assert!(v4.parse::<UuidV7Id<Order>>().is_err());
+    }
+}
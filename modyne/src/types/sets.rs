@@ -0,0 +1,384 @@
+//! Set attributes
+
+use std::{collections::BTreeSet, fmt};
+
+use crate::keys::Binary;
+
+/// DynamoDB rejects sets without any elements
+const EMPTY_SET_MESSAGE: &str = "DynamoDB does not allow empty sets; skip serializing the \
+                                 attribute when the set is empty";
+
+/// A set type stored as a DynamoDB set attribute
+///
+/// This trait is sealed, and is implemented by [`StringSet`],
+/// [`NumberSet`], and [`BinarySet`].
+pub trait AttributeSet: serde::Serialize + sealed::Sealed {
+    /// Whether the set has no elements
+    ///
+    /// An empty set cannot be stored, as DynamoDB rejects sets without any
+    /// elements.
+    fn is_empty(&self) -> bool;
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+macro_rules! typed_set {
+    ($(#[$meta:meta])* $name:ident, $module:ident) => {
+        $(#[$meta])*
+        #[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name<T>(BTreeSet<T>);
+
+        impl<T: Ord> $name<T> {
+            /// Creates an empty set
+            #[inline]
+            pub fn new() -> Self {
+                Self(BTreeSet::new())
+            }
+
+            /// Adds an element to the set, returning whether it was newly added
+            #[inline]
+            pub fn insert(&mut self, value: T) -> bool {
+                self.0.insert(value)
+            }
+
+            /// Removes an element from the set, returning whether it was present
+            #[inline]
+            pub fn remove(&mut self, value: &T) -> bool {
+                self.0.remove(value)
+            }
+
+            /// Whether the set contains the element
+            #[inline]
+            pub fn contains(&self, value: &T) -> bool {
+                self.0.contains(value)
+            }
+
+            /// The number of elements in the set
+            #[inline]
+            pub fn len(&self) -> usize {
+                self.0.len()
+            }
+
+            /// Whether the set has no elements
+            #[inline]
+            pub fn is_empty(&self) -> bool {
+                self.0.is_empty()
+            }
+
+            /// Iterates over the elements of the set in order
+            #[inline]
+            pub fn iter(&self) -> std::collections::btree_set::Iter<'_, T> {
+                self.0.iter()
+            }
+
+            /// Unwraps the elements of the set
+            #[inline]
+            pub fn into_inner(self) -> BTreeSet<T> {
+                self.0
+            }
+        }
+
+        impl<T> Default for $name<T> {
+            #[inline]
+            fn default() -> Self {
+                Self(BTreeSet::new())
+            }
+        }
+
+        impl<T: fmt::Debug> fmt::Debug for $name<T> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_set().entries(&self.0).finish()
+            }
+        }
+
+        impl<T> From<BTreeSet<T>> for $name<T> {
+            #[inline]
+            fn from(values: BTreeSet<T>) -> Self {
+                Self(values)
+            }
+        }
+
+        impl<T: Ord> FromIterator<T> for $name<T> {
+            #[inline]
+            fn from_iter<I: IntoIterator<Item = T>>(values: I) -> Self {
+                Self(values.into_iter().collect())
+            }
+        }
+
+        impl<T: Ord> Extend<T> for $name<T> {
+            #[inline]
+            fn extend<I: IntoIterator<Item = T>>(&mut self, values: I) {
+                self.0.extend(values);
+            }
+        }
+
+        impl<T> IntoIterator for $name<T> {
+            type Item = T;
+            type IntoIter = std::collections::btree_set::IntoIter<T>;
+
+            #[inline]
+            fn into_iter(self) -> Self::IntoIter {
+                self.0.into_iter()
+            }
+        }
+
+        impl<'a, T> IntoIterator for &'a $name<T> {
+            type Item = &'a T;
+            type IntoIter = std::collections::btree_set::Iter<'a, T>;
+
+            #[inline]
+            fn into_iter(self) -> Self::IntoIter {
+                self.0.iter()
+            }
+        }
+
+        impl<T: serde::Serialize> serde::Serialize for $name<T> {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                if self.0.is_empty() {
+                    return Err(serde::ser::Error::custom(EMPTY_SET_MESSAGE));
+                }
+
+                serde_dynamo::$module::$name(&self.0).serialize(serializer)
+            }
+        }
+
+        impl<'de, T: serde::Deserialize<'de> + Ord> serde::Deserialize<'de> for $name<T> {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                BTreeSet::<T>::deserialize(deserializer).map(Self)
+            }
+        }
+
+        impl<T: serde::Serialize + Ord> AttributeSet for $name<T> {
+            #[inline]
+            fn is_empty(&self) -> bool {
+                self.0.is_empty()
+            }
+        }
+
+        impl<T> sealed::Sealed for $name<T> {}
+    };
+}
+
+typed_set! {
+    /// A set of values stored as a DynamoDB string set (`SS`)
+    ///
+    /// The elements must serialize as strings. DynamoDB does not allow empty
+    /// sets, so serializing an empty set fails; an attribute holding a set
+    /// that may be empty should be skipped when the set is empty, and
+    /// defaulted when it is missing. Deriving [`EntityDef`][crate::EntityDef]
+    /// requires set fields to be skipped when empty.
+    ///
+    /// ```
+    /// use modyne::types::StringSet;
+    ///
+    /// #[derive(serde::Serialize, serde::Deserialize)]
+    /// struct Post {
+    ///     #[serde(default, skip_serializing_if = "StringSet::is_empty")]
+    ///     tags: StringSet<String>,
+    /// }
+    ///
+    /// let post = Post {
+    ///     tags: ["rust", "dynamodb"].map(String::from).into_iter().collect(),
+    /// };
+    /// let item: modyne::Item = serde_dynamo::to_item(&post).unwrap();
+    /// assert_eq!(item["tags"].as_ss().unwrap(), &["dynamodb", "rust"]);
+    ///
+    /// let post = Post { tags: StringSet::new() };
+    /// let item: modyne::Item = serde_dynamo::to_item(&post).unwrap();
+    /// assert!(!item.contains_key("tags"));
+    /// ```
+    StringSet, string_set
+}
+
+typed_set! {
+    /// A set of numbers stored as a DynamoDB number set (`NS`)
+    ///
+    /// The elements must serialize as numbers. DynamoDB does not allow empty
+    /// sets, so serializing an empty set fails; see [`StringSet`] for how to
+    /// store a set that may be empty.
+    NumberSet, number_set
+}
+
+/// A set of byte strings stored as a DynamoDB binary set (`BS`)
+///
+/// DynamoDB does not allow empty sets, so serializing an empty set fails;
+/// see [`StringSet`] for how to store a set that may be empty.
+#[derive(Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BinarySet(BTreeSet<Vec<u8>>);
+
+impl BinarySet {
+    /// Creates an empty set
+    #[inline]
+    pub fn new() -> Self {
+        Self(BTreeSet::new())
+    }
+
+    /// Adds an element to the set, returning whether it was newly added
+    #[inline]
+    pub fn insert(&mut self, value: impl Into<Vec<u8>>) -> bool {
+        self.0.insert(value.into())
+    }
+
+    /// Removes an element from the set, returning whether it was present
+    #[inline]
+    pub fn remove(&mut self, value: &[u8]) -> bool {
+        self.0.remove(value)
+    }
+
+    /// Whether the set contains the element
+    #[inline]
+    pub fn contains(&self, value: &[u8]) -> bool {
+        self.0.contains(value)
+    }
+
+    /// The number of elements in the set
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether the set has no elements
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Iterates over the elements of the set in order
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &[u8]> {
+        self.0.iter().map(Vec::as_slice)
+    }
+
+    /// Unwraps the elements of the set
+    #[inline]
+    pub fn into_inner(self) -> BTreeSet<Vec<u8>> {
+        self.0
+    }
+}
+
+impl fmt::Debug for BinarySet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(&self.0).finish()
+    }
+}
+
+impl From<BTreeSet<Vec<u8>>> for BinarySet {
+    #[inline]
+    fn from(values: BTreeSet<Vec<u8>>) -> Self {
+        Self(values)
+    }
+}
+
+impl<V: Into<Vec<u8>>> FromIterator<V> for BinarySet {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = V>>(values: I) -> Self {
+        Self(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<V: Into<Vec<u8>>> Extend<V> for BinarySet {
+    #[inline]
+    fn extend<I: IntoIterator<Item = V>>(&mut self, values: I) {
+        self.0.extend(values.into_iter().map(Into::into));
+    }
+}
+
+impl IntoIterator for BinarySet {
+    type Item = Vec<u8>;
+    type IntoIter = std::collections::btree_set::IntoIter<Vec<u8>>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// Bytes serialized as a binary value rather than as a list of numbers
+struct Bytes<'a>(&'a [u8]);
+
+impl serde::Serialize for Bytes<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl serde::Serialize for BinarySet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0.is_empty() {
+            return Err(serde::ser::Error::custom(EMPTY_SET_MESSAGE));
+        }
+
+        let values: Vec<_> = self.0.iter().map(|value| Bytes(value)).collect();
+        serde_dynamo::binary_set::BinarySet(values).serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for BinarySet {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<Binary>::deserialize(deserializer)
+            .map(|values| Self(values.into_iter().map(|value| value.0).collect()))
+    }
+}
+
+impl AttributeSet for BinarySet {
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl sealed::Sealed for BinarySet {}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_dynamodb::{primitives::Blob, types::AttributeValue};
+
+    use super::*;
+
+    #[test]
+    fn sets_round_trip_through_set_attributes() {
+        let strings: StringSet<String> = ["b", "a", "b"].map(String::from).into_iter().collect();
+        let value: AttributeValue = serde_dynamo::to_attribute_value(&strings).unwrap();
+        assert_eq!(value, AttributeValue::Ss(vec!["a".into(), "b".into()]));
+        assert_eq!(
+            serde_dynamo::from_attribute_value::<_, StringSet<String>>(value).unwrap(),
+            strings
+        );
+
+        let numbers: NumberSet<u32> = [3, 1, 2].into_iter().collect();
+        let value: AttributeValue = serde_dynamo::to_attribute_value(&numbers).unwrap();
+        assert_eq!(
+            value,
+            AttributeValue::Ns(vec!["1".into(), "2".into(), "3".into()])
+        );
+        assert_eq!(
+            serde_dynamo::from_attribute_value::<_, NumberSet<u32>>(value).unwrap(),
+            numbers
+        );
+
+        let binaries: BinarySet = [vec![2u8], vec![1u8]].into_iter().collect();
+        let value: AttributeValue = serde_dynamo::to_attribute_value(&binaries).unwrap();
+        assert_eq!(
+            value,
+            AttributeValue::Bs(vec![Blob::new(vec![1u8]), Blob::new(vec![2u8])])
+        );
+        assert_eq!(
+            serde_dynamo::from_attribute_value::<_, BinarySet>(value).unwrap(),
+            binaries
+        );
+    }
+
+    #[test]
+    fn empty_sets_are_not_serialized() {
+        assert!(
+            serde_dynamo::to_attribute_value::<_, AttributeValue>(StringSet::<String>::new())
+                .is_err()
+        );
+        assert!(
+            serde_dynamo::to_attribute_value::<_, AttributeValue>(NumberSet::<u32>::new()).is_err()
+        );
+        assert!(serde_dynamo::to_attribute_value::<_, AttributeValue>(BinarySet::new()).is_err());
+    }
+}
@@ -0,0 +1,591 @@
+//! Uniqueness constraints on entity attributes
+//!
+//! DynamoDB only enforces the uniqueness of an item's primary key. To ensure
+//! that no two entities of the same type share a value for some other
+//! attribute, such as an email address, each value is claimed by a separate
+//! constraint item whose primary key is derived from the entity type, the
+//! attribute name, and the value. The constraint item is written in the same
+//! transaction as the entity, with a condition that it does not already
+//! exist, so the transaction fails if the value has already been claimed.
+//!
+//! Unique attributes are declared with
+//! [`EntityDef::UNIQUE_ATTRIBUTES`][crate::EntityDef::UNIQUE_ATTRIBUTES], or
+//! with `#[entity(unique = "...")]` when using the derive macro. The
+//! constraint items are then maintained by
+//! [`EntityExt::create_with_derived()`][crate::EntityExt::create_with_derived()],
+//! [`EntityExt::replace_with_derived()`][crate::EntityExt::replace_with_derived()],
+//! and [`EntityExt::delete_with_unique()`][crate::EntityExt::delete_with_unique()].
+//!
+//! Constraint items set both the partition and sort key of the table to
+//! `UNIQUE#<entity type>#<attribute>#<value>`, so the table's primary key
+//! attributes must be strings. Only string, number, and binary attributes
+//! can be constrained. An entity without a value for a unique attribute, or
+//! with a value of another type, does not claim one.
+//!
+//! Constraint items are stored with the [`CLAIM_ENTITY_TYPE`] entity type,
+//! and record the primary key of the entity that claimed the value. A claim
+//! is only released by the entity that owns it, so that releasing a stale
+//! value cannot remove a claim since made by another entity.
+//!
+//! The key of an entity in a secondary index can be constrained in the same
+//! way, by listing the index in
+//! [`EntityDef::UNIQUE_INDEXES`][crate::EntityDef::UNIQUE_INDEXES] or with
+//! `#[entity(unique_index = "...")]`, or for a single write with
+//! [`EntityExt::create_if_absent_on()`][crate::EntityExt::create_if_absent_on()].
+//! Index key constraint items use the key
+//! `UNIQUE#<entity type>#INDEX:<index name>#<hash>`, or
+//! `UNIQUE#<entity type>#INDEX:<index name>#<hash length>:<hash>#<range>` for
+//! indexes with a sort key, so that the boundary between the two is not
+//! ambiguous. An entity without a key in the index, as in a sparse index,
+//! does not claim one. Each unique index must be one of the entity's index
+//! keys, which is checked when the entity's claims are compiled.
+
+use std::{fmt, marker::PhantomData};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+use crate::{
+    expr,
+    keys::{self, IndexKeys},
+    model::{primary_key_of, ConditionalDelete, ConditionalPut, Delete, Put},
+    AttributeValue, Entity, EntityDiscriminator, EntityTypeNameRef, Item, Table,
+};
+
+const UNIQUE_PREFIX: &str = "UNIQUE#";
+const INDEX_PREFIX: &str = "INDEX:";
+const OWNER_ATTRIBUTE: &str = "unique_owner";
+
+/// The entity type of constraint items
+pub const CLAIM_ENTITY_TYPE: &EntityTypeNameRef = EntityTypeNameRef::from_static("unique_claim");
+
+/// A claim on a unique value of an attribute of an entity, or on a unique
+/// key of an entity in a secondary index
+pub struct Unique<E> {
+    attribute: &'static str,
+    value: String,
+    index: bool,
+    owner: Option<Item>,
+    entity: PhantomData<fn() -> E>,
+}
+
+impl<E> fmt::Debug for Unique<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Unique")
+            .field("attribute", &self.attribute)
+            .field("value", &self.value)
+            .field("index", &self.index)
+            .field("owner", &self.owner)
+            .finish()
+    }
+}
+
+impl<E> Clone for Unique<E> {
+    fn clone(&self) -> Self {
+        Self {
+            attribute: self.attribute,
+            value: self.value.clone(),
+            index: self.index,
+            owner: self.owner.clone(),
+            entity: PhantomData,
+        }
+    }
+}
+
+impl<E> PartialEq for Unique<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.attribute == other.attribute
+            && self.value == other.value
+            && self.index == other.index
+            && self.owner == other.owner
+    }
+}
+
+impl<E> Eq for Unique<E> {}
+
+impl<E: Entity> Unique<E> {
+    /// Construct a claim on a value of the given attribute
+    ///
+    /// Number values should be given in their canonical string form, and
+    /// binary values encoded as standard base64.
+    pub fn new(attribute: &'static str, value: impl Into<String>) -> Self {
+        Self {
+            attribute,
+            value: value.into(),
+            index: false,
+            owner: None,
+            entity: PhantomData,
+        }
+    }
+
+    /// Construct a claim on a key of the given secondary index
+    ///
+    /// The value is the index's hash key value, followed by `#` and its
+    /// range key value if the index has one. Values are encoded as for
+    /// [`new()`][Self::new()].
+    pub fn for_index(index_name: &'static str, value: impl Into<String>) -> Self {
+        Self {
+            attribute: index_name,
+            value: value.into(),
+            index: true,
+            owner: None,
+            entity: PhantomData,
+        }
+    }
+
+    /// Records the primary key of the entity making the claim
+    ///
+    /// The owner is stored on the constraint item when the claim is made,
+    /// and must match for the claim to be released.
+    pub fn owned_by(mut self, key: Item) -> Self {
+        self.owner = Some(key);
+        self
+    }
+
+    /// The name of the constrained attribute, or of the constrained index
+    #[inline]
+    pub fn attribute(&self) -> &'static str {
+        self.attribute
+    }
+
+    /// Whether this is a claim on a secondary index key
+    #[inline]
+    pub fn is_index(&self) -> bool {
+        self.index
+    }
+
+    /// The claimed value
+    #[inline]
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// The primary key of the entity making the claim, if known
+    #[inline]
+    pub fn owner(&self) -> Option<&Item> {
+        self.owner.as_ref()
+    }
+
+    /// The primary key of the constraint item
+    pub fn key(&self) -> Item {
+        use keys::PrimaryKey;
+
+        let definition = <E::Table as Table>::PrimaryKey::PRIMARY_KEY_DEFINITION;
+        let kind = if self.index { INDEX_PREFIX } else { "" };
+        let key = format!(
+            "{UNIQUE_PREFIX}{}#{kind}{}#{}",
+            E::ENTITY_TYPE,
+            self.attribute,
+            self.value
+        );
+
+        std::iter::once(definition.hash_key)
+            .chain(definition.range_key)
+            .map(|attr| (attr.to_string(), AttributeValue::S(key.clone())))
+            .collect()
+    }
+
+    /// Prepares a put of the constraint item that fails if the value has
+    /// already been claimed
+    pub fn claim(&self) -> ConditionalPut {
+        use keys::PrimaryKey;
+
+        let mut item = self.key();
+        if let EntityDiscriminator::Attribute = <E::Table as Table>::ENTITY_DISCRIMINATOR {
+            item.insert(
+                <E::Table as Table>::ENTITY_TYPE_ATTRIBUTE.to_string(),
+                <E::Table as Table>::serialize_entity_type(CLAIM_ENTITY_TYPE),
+            );
+        }
+        if let Some(owner) = &self.owner {
+            item.insert(
+                OWNER_ATTRIBUTE.to_string(),
+                AttributeValue::M(owner.clone()),
+            );
+        }
+
+        let condition = expr::Condition::new("attribute_not_exists(#PK)").name(
+            "#PK",
+            <E::Table as Table>::PrimaryKey::PRIMARY_KEY_DEFINITION.hash_key,
+        );
+        Put::new(item).condition(condition)
+    }
+
+    /// Prepares a delete of the constraint item, releasing the value
+    ///
+    /// If the owner of the claim is known, the delete is conditioned on the
+    /// constraint item belonging to that owner, or to no recorded owner, so
+    /// that a claim made by another entity is not released. Without an
+    /// owner, the constraint item is deleted unconditionally.
+    pub fn release(&self) -> ConditionalDelete {
+        let delete = Delete::new(self.key());
+        let Some(owner) = &self.owner else {
+            return delete.unconditional();
+        };
+
+        let condition = expr::Condition::new("attribute_not_exists(#owner) OR #owner = :owner")
+            .name("#owner", OWNER_ATTRIBUTE)
+            .raw_value(":owner", AttributeValue::M(owner.clone()));
+        delete.condition(condition)
+    }
+
+    /// Extracts the claims made by an entity from its serialized item
+    ///
+    /// Each claim is owned by the primary key of the item.
+    pub(crate) fn from_item(item: &Item) -> Vec<Self> {
+        let attributes = E::UNIQUE_ATTRIBUTES
+            .iter()
+            .filter_map(|&attribute| Self::from_attribute(item, attribute));
+        let indexes = unique_indexes::<E>().filter_map(|index| Self::from_index(item, index));
+        attributes.chain(indexes).collect()
+    }
+
+    fn from_attribute(item: &Item, attribute: &'static str) -> Option<Self> {
+        let value = encode_value::<E>(item, attribute)?;
+        Some(Self::new(attribute, value).owned_by(primary_key_of::<E::Table>(item)))
+    }
+
+    /// Extracts the claim on the key of a secondary index from an entity's
+    /// serialized item, owned by the primary key of the item
+    pub(crate) fn from_index(item: &Item, index: keys::SecondaryIndexDefinition) -> Option<Self> {
+        let hash = encode_value::<E>(item, index.hash_key())?;
+        let value = match index.range_key() {
+            Some(range_key) => {
+                let range = encode_value::<E>(item, range_key)?;
+                format!("{}:{hash}#{range}", hash.len())
+            }
+            None => hash,
+        };
+
+        Some(Self::for_index(index.index_name(), value).owned_by(primary_key_of::<E::Table>(item)))
+    }
+}
+
+/// Checks that each of the entity's unique indexes is one of its index keys
+struct UniqueIndexes<E>(PhantomData<fn() -> E>);
+
+impl<E: Entity> UniqueIndexes<E> {
+    const DEFINED: () = {
+        let mut i = 0;
+        while i < E::UNIQUE_INDEXES.len() {
+            assert!(
+                has_index(E::IndexKeys::KEY_DEFINITIONS, E::UNIQUE_INDEXES[i]),
+                "a unique index is not one of the entity's index keys"
+            );
+            i += 1;
+        }
+    };
+}
+
+/// The definitions of the entity's unique indexes
+fn unique_indexes<E: Entity>() -> impl Iterator<Item = keys::SecondaryIndexDefinition> {
+    #[allow(clippy::let_unit_value)]
+    let () = UniqueIndexes::<E>::DEFINED;
+
+    E::IndexKeys::KEY_DEFINITIONS
+        .iter()
+        .copied()
+        .filter(|index| E::UNIQUE_INDEXES.contains(&index.index_name()))
+}
+
+/// Whether an index with the given name is among the definitions
+pub(crate) const fn has_index(definitions: &[keys::SecondaryIndexDefinition], name: &str) -> bool {
+    let mut i = 0;
+    while i < definitions.len() {
+        let candidate = definitions[i].index_name().as_bytes();
+        let name = name.as_bytes();
+        if candidate.len() == name.len() {
+            let mut j = 0;
+            while j < name.len() && candidate[j] == name[j] {
+                j += 1;
+            }
+            if j == name.len() {
+                return true;
+            }
+        }
+        i += 1;
+    }
+    false
+}
+
+/// Encodes the value of a constrained attribute, if present
+///
+/// Values other than strings, numbers, and binary values cannot be claimed.
+fn encode_value<E: Entity>(item: &Item, attribute: &str) -> Option<String> {
+    let value = match item.get(attribute)? {
+        AttributeValue::S(s) => s.clone(),
+        AttributeValue::N(n) => n.clone(),
+        AttributeValue::B(b) => STANDARD.encode(b.as_ref()),
+        AttributeValue::Null(_) => return None,
+        _ => {
+            tracing::warn!(
+                entity_type = %E::ENTITY_TYPE,
+                attribute,
+                "unique attribute is not a string, number, or binary value and was not claimed",
+            );
+            return None;
+        }
+    };
+
+    Some(value)
+}
+
+/// Computes the claims to release and to make when an entity changes
+pub(crate) fn changes<E: Entity>(
+    previous: &Item,
+    current: &Item,
+) -> (Vec<Unique<E>>, Vec<Unique<E>>) {
+    let mut released = Vec::new();
+    let mut claimed = Vec::new();
+
+    for &attribute in E::UNIQUE_ATTRIBUTES {
+        let before = Unique::<E>::from_attribute(previous, attribute);
+        let after = Unique::<E>::from_attribute(current, attribute);
+        if before != after {
+            released.extend(before);
+            claimed.extend(after);
+        }
+    }
+
+    for index in unique_indexes::<E>() {
+        let before = Unique::<E>::from_index(previous, index);
+        let after = Unique::<E>::from_index(current, index);
+        if before != after {
+            released.extend(before);
+            claimed.extend(after);
+        }
+    }
+
+    (released, claimed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{ops, MockTable},
+        EntityDef, EntityTypeNameRef, WritableTable,
+    };
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+    }
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            unimplemented!()
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    impl WritableTable for TestTable {}
+
+    #[derive(Clone, Debug, serde::Serialize)]
+    struct Customer {
+        username: String,
+        email: String,
+        phone: Option<String>,
+    }
+
+    impl EntityDef for Customer {
+        const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("customer");
+        const UNIQUE_ATTRIBUTES: &'static [&'static str] = &["email", "phone"];
+    }
+
+    impl Entity for Customer {
+        type KeyInput<'a> = &'a str;
+        type Table = TestTable;
+        type IndexKeys = keys::Gsi1;
+
+        fn primary_key(username: Self::KeyInput<'_>) -> keys::Primary {
+            keys::Primary {
+                hash: format!("CUSTOMER#{username}"),
+                range: format!("CUSTOMER#{username}"),
+            }
+        }
+
+        fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> {
+            keys::FullKey {
+                primary: Self::primary_key(&self.username),
+                indexes: keys::Gsi1 {
+                    hash: "CUSTOMERS".to_string(),
+                    range: format!("CUSTOMER#{}", self.username),
+                },
+            }
+        }
+    }
+
+    fn owner() -> Item {
+        keys::PrimaryKey::into_key(Customer::primary_key("alexdebrie"))
+    }
+
+    fn customer(email: &str, phone: Option<&str>) -> Item {
+        crate::entity_to_item(&Customer {
+            username: "alexdebrie".into(),
+            email: email.into(),
+            phone: phone.map(Into::into),
+        })
+    }
+
+    #[test]
+    fn key_is_derived_from_entity_type_attribute_and_value() {
+        let key = Unique::<Customer>::new("email", "alex@example.com").key();
+
+        let expected = AttributeValue::S("UNIQUE#customer#email#alex@example.com".into());
+        assert_eq!(key.len(), 2);
+        assert_eq!(key.get("PK"), Some(&expected));
+        assert_eq!(key.get("SK"), Some(&expected));
+    }
+
+    #[test]
+    fn claims_are_extracted_from_present_attributes() {
+        let claims = Unique::<Customer>::from_item(&customer("alex@example.com", None));
+
+        assert_eq!(
+            claims,
+            vec![Unique::new("email", "alex@example.com").owned_by(owner())]
+        );
+    }
+
+    #[test]
+    fn changed_values_are_released_and_claimed() {
+        let previous = customer("alex@example.com", Some("555-0100"));
+        let current = customer("alex@example.org", Some("555-0100"));
+
+        let (released, claimed) = changes::<Customer>(&previous, &current);
+
+        assert_eq!(
+            released,
+            vec![Unique::new("email", "alex@example.com").owned_by(owner())]
+        );
+        assert_eq!(
+            claimed,
+            vec![Unique::new("email", "alex@example.org").owned_by(owner())]
+        );
+    }
+
+    #[test]
+    fn removed_values_are_only_released() {
+        let previous = customer("alex@example.com", Some("555-0100"));
+        let current = customer("alex@example.com", None);
+
+        let (released, claimed) = changes::<Customer>(&previous, &current);
+
+        assert_eq!(
+            released,
+            vec![Unique::new("phone", "555-0100").owned_by(owner())]
+        );
+        assert!(claimed.is_empty());
+    }
+
+    #[test]
+    fn index_claims_are_keyed_separately_from_attributes() {
+        use keys::IndexKey;
+
+        let claim = Unique::<Customer>::from_index(
+            &customer("alex@example.com", None),
+            keys::Gsi1::INDEX_DEFINITION,
+        )
+        .unwrap();
+
+        assert_eq!(
+            claim,
+            Unique::for_index("GSI1", "9:CUSTOMERS#CUSTOMER#alexdebrie").owned_by(owner())
+        );
+        assert_eq!(
+            claim.key().get("PK"),
+            Some(&AttributeValue::S(
+                "UNIQUE#customer#INDEX:GSI1#9:CUSTOMERS#CUSTOMER#alexdebrie".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn index_claims_separate_the_hash_and_range_unambiguously() {
+        use keys::IndexKey;
+
+        let claim = |hash: &str, range: &str| {
+            let mut item = customer("alex@example.com", None);
+            item.insert("GSI1PK".into(), AttributeValue::S(hash.into()));
+            item.insert("GSI1SK".into(), AttributeValue::S(range.into()));
+            Unique::<Customer>::from_index(&item, keys::Gsi1::INDEX_DEFINITION)
+                .unwrap()
+                .key()
+        };
+
+        assert_ne!(claim("A#B", "C"), claim("A", "B#C"));
+    }
+
+    #[test]
+    fn missing_index_keys_are_not_claimed() {
+        use keys::IndexKey;
+
+        let claim = Unique::<Customer>::from_index(
+            &customer("alex@example.com", None),
+            keys::Gsi2::INDEX_DEFINITION,
+        );
+
+        assert!(claim.is_none());
+    }
+
+    #[test]
+    fn non_scalar_values_are_not_claimed() {
+        let mut item = customer("alex@example.com", Some("555-0100"));
+        item.insert("email".into(), AttributeValue::Bool(true));
+
+        let claims = Unique::<Customer>::from_item(&item);
+
+        assert_eq!(
+            claims,
+            vec![Unique::new("phone", "555-0100").owned_by(owner())]
+        );
+    }
+
+    #[test]
+    fn claims_record_their_owner_and_entity_type() {
+        let table = MockTable::<TestTable>::new("test");
+        table.expect::<ops::PutItem>(|e| e.times(1));
+
+        let claim = Unique::<Customer>::new("email", "alex@example.com").owned_by(owner());
+        runtime().block_on(claim.claim().execute(&table)).unwrap();
+
+        let item = table.inputs::<ops::PutItem>()[0].item.clone().unwrap();
+        assert_eq!(
+            item.get(TestTable::ENTITY_TYPE_ATTRIBUTE),
+            Some(&AttributeValue::S(CLAIM_ENTITY_TYPE.to_string()))
+        );
+        assert_eq!(item.get(OWNER_ATTRIBUTE), Some(&AttributeValue::M(owner())));
+    }
+
+    #[test]
+    fn releases_are_conditioned_on_the_owner() {
+        let table = MockTable::<TestTable>::new("test");
+        table.expect::<ops::DeleteItem>(|e| e.times(2));
+
+        let runtime = runtime();
+        let owned = Unique::<Customer>::new("email", "alex@example.com").owned_by(owner());
+        runtime.block_on(owned.release().execute(&table)).unwrap();
+        let unowned = Unique::<Customer>::new("email", "alex@example.com");
+        runtime.block_on(unowned.release().execute(&table)).unwrap();
+
+        let inputs = table.inputs::<ops::DeleteItem>();
+        assert_eq!(
+            inputs[0].condition_expression.as_deref(),
+            Some("attribute_not_exists(#cnd_owner) OR #cnd_owner = :cnd_owner")
+        );
+        assert_eq!(
+            inputs[0].expression_attribute_values.as_ref().unwrap()[":cnd_owner"],
+            AttributeValue::M(owner())
+        );
+        assert!(inputs[1].condition_expression.is_none());
+    }
+}
@@ -0,0 +1,614 @@
+//! Buffering writes from many tasks into batches
+//!
+//! A [`WriteBuffer`] collects put and delete operations, which may be pushed
+//! concurrently from many tasks, and writes them to the table in batches of
+//! up to 25 items. A batch is written as soon as it is full and, if a
+//! [flush interval][WriteBufferOptions::flush_interval()] is configured,
+//! whenever the interval elapses. Items left unprocessed by DynamoDB are
+//! retried with exponential backoff. This suits ingestion pipelines, where
+//! items arrive one at a time but are best written in bulk.
+//!
+//! ```no_run
+//! # use modyne::{keys, Entity, EntityDef, EntityTypeNameRef, Table, WritableTable};
+//! # struct App;
+//! # impl Table for App {
+//! #     type PrimaryKey = keys::Primary;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn table_name(&self) -> &str { unimplemented!() }
+//! #     fn client(&self) -> &aws_sdk_dynamodb::Client { unimplemented!() }
+//! # }
+//! # impl WritableTable for App {}
+//! # #[derive(serde::Serialize)]
+//! # struct Order { id: String }
+//! # impl EntityDef for Order {
+//! #     const ENTITY_TYPE: &'static EntityTypeNameRef = EntityTypeNameRef::from_static("order");
+//! # }
+//! # impl Entity for Order {
+//! #     type KeyInput<'a> = &'a str;
+//! #     type Table = App;
+//! #     type IndexKeys = keys::Gsi1;
+//! #     fn primary_key(id: Self::KeyInput<'_>) -> keys::Primary { unimplemented!() }
+//! #     fn full_key(&self) -> keys::FullKey<keys::Primary, Self::IndexKeys> { unimplemented!() }
+//! # }
+//! use std::time::Duration;
+//!
+//! use modyne::{
+//!     write_buffer::{WriteBuffer, WriteBufferOptions},
+//!     EntityExt,
+//! };
+//!
+//! # async fn example(app: App, orders: Vec<Order>) -> Result<(), modyne::Error> {
+//! let buffer = WriteBuffer::new(
+//!     app,
+//!     WriteBufferOptions::new().flush_interval(Duration::from_secs(1)),
+//! );
+//!
+//! futures_util::future::try_join_all(orders.into_iter().map(|order| buffer.push(order.put())))
+//!     .await?;
+//!
+//! buffer.flush().await?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! If several operations on the same key are waiting to be written, only
+//! the one pushed last is written. Operations on the same key that are
+//! written in different batches may be applied in any order.
+//!
+//! A buffer must be [flushed][WriteBuffer::flush()] before it is dropped.
+//! Dropping a buffer with operations that have not been written panics,
+//! rather than silently discarding them. To give up on the operations that
+//! are waiting, take them out of the buffer with
+//! [`into_pending()`][WriteBuffer::into_pending()].
+
+use std::{
+    fmt,
+    pin::pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
+};
+
+use futures_util::future::{self, Either};
+
+use crate::{
+    model::{batch_write_with_retry, BatchWrite, BatchWriteItem},
+    Error, WritableTable,
+};
+
+const MAX_BATCH_SIZE: usize = 25;
+
+/// Options for a [`WriteBuffer`]
+///
+/// By default, operations are only written once a full batch is waiting or
+/// when the buffer is flushed.
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct WriteBufferOptions {
+    flush_interval: Option<Duration>,
+    max_attempts: u32,
+}
+
+impl Default for WriteBufferOptions {
+    fn default() -> Self {
+        Self {
+            flush_interval: None,
+            max_attempts: 8,
+        }
+    }
+}
+
+impl WriteBufferOptions {
+    /// Prepares the default options
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes any waiting operations each time the interval elapses, even
+    /// if they do not fill a batch
+    ///
+    /// The interval is timed by a background task, which requires the
+    /// buffer to be created within a Tokio runtime.
+    pub fn flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval);
+        self
+    }
+
+    /// Sets the number of attempts made to write each batch before failing
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+}
+
+/// A buffer that writes the operations pushed into it in batches
+///
+/// See the [module documentation][self] for details.
+pub struct WriteBuffer<T: WritableTable> {
+    shared: Arc<Shared<T>>,
+}
+
+struct Shared<T> {
+    table: T,
+    max_attempts: u32,
+    pending: Mutex<Pending>,
+    background_error: Mutex<Option<Error>>,
+
+    /// Held for reading while a batch is being written, so that a flush can
+    /// wait for batches written by other tasks
+    writing: tokio::sync::RwLock<()>,
+
+    /// Set once the buffer is dropped, so that the flush timer stops before
+    /// taking any further operations from the buffer
+    stopped: AtomicBool,
+    stop: tokio::sync::Notify,
+}
+
+/// The operations taken from or waiting in the buffer
+///
+/// Both are counted under the same lock, so that an operation is always
+/// seen as either waiting or being written.
+struct Pending {
+    waiting: Vec<BatchWriteItem>,
+    in_flight: usize,
+}
+
+impl Pending {
+    fn take(&mut self, count: usize) -> Vec<BatchWriteItem> {
+        let ops: Vec<_> = self.waiting.drain(..count).collect();
+        self.in_flight += ops.len();
+        ops
+    }
+}
+
+impl<T: WritableTable> fmt::Debug for WriteBuffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("WriteBuffer")
+            .field("table", &self.shared.table.table_name())
+            .field("pending", &self.shared.pending_len())
+            .field("max_attempts", &self.shared.max_attempts)
+            .finish()
+    }
+}
+
+impl<T: WritableTable + Send + Sync + 'static> WriteBuffer<T> {
+    /// Prepares an empty buffer writing to the table
+    ///
+    /// # Panics
+    ///
+    /// Panics if a flush interval is configured and the buffer is not
+    /// created within a Tokio runtime.
+    pub fn new(table: T, options: WriteBufferOptions) -> Self {
+        let shared = Arc::new(Shared {
+            table,
+            max_attempts: options.max_attempts,
+            pending: Mutex::new(Pending {
+                waiting: Vec::with_capacity(MAX_BATCH_SIZE),
+                in_flight: 0,
+            }),
+            background_error: Mutex::new(None),
+            writing: tokio::sync::RwLock::new(()),
+            stopped: AtomicBool::new(false),
+            stop: tokio::sync::Notify::new(),
+        });
+
+        if let Some(interval) = options.flush_interval {
+            let shared = Arc::clone(&shared);
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                ticker.tick().await;
+                loop {
+                    let tick = pin!(ticker.tick());
+                    let stop = pin!(shared.stop.notified());
+                    if let Either::Right(_) = future::select(tick, stop).await {
+                        break;
+                    }
+
+                    // A write started here is never cancelled, so its
+                    // operations are either written or returned to the buffer
+                    if shared.stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    if let Err(error) = shared.write_pending().await {
+                        tracing::warn!(
+                            table = shared.table.table_name(),
+                            %error,
+                            "failed to flush write buffer"
+                        );
+                        shared.record_error(error);
+                    }
+                }
+            });
+        }
+
+        Self { shared }
+    }
+}
+
+impl<T: WritableTable> WriteBuffer<T> {
+    /// The table written to
+    #[inline]
+    pub fn table(&self) -> &T {
+        &self.shared.table
+    }
+
+    /// Pushes an operation into the buffer
+    ///
+    /// If the operation fills a batch, the batch is written before this
+    /// method returns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a batch cannot be written. Errors from batches
+    /// written in the background are returned by the next call to this
+    /// method or to [`flush()`][Self::flush()]. The operations in a batch
+    /// that failed are returned to the buffer, to be written with a later
+    /// batch.
+    pub async fn push(&self, op: impl Into<BatchWriteItem>) -> Result<(), Error> {
+        let op = op.into();
+        let _writing = self.shared.writing.read().await;
+        let batch = {
+            let mut pending = self.shared.lock_pending();
+            let key = op.written_key::<T>();
+            match pending
+                .waiting
+                .iter_mut()
+                .find(|p| p.written_key::<T>() == key)
+            {
+                Some(existing) => *existing = op,
+                None => pending.waiting.push(op),
+            }
+
+            (pending.waiting.len() >= MAX_BATCH_SIZE).then(|| pending.take(MAX_BATCH_SIZE))
+        };
+
+        if let Some(batch) = batch {
+            self.shared.write(batch).await?;
+        }
+
+        self.shared.take_error()
+    }
+
+    /// Writes every operation in the buffer, waiting for any batches that
+    /// are being written by other tasks
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a batch cannot be written, or if a batch written
+    /// in the background failed since the buffer was last flushed. The
+    /// operations that were not written remain in the buffer.
+    pub async fn flush(&self) -> Result<(), Error> {
+        self.shared.write_pending().await?;
+        drop(self.shared.writing.write().await);
+        self.shared.take_error()
+    }
+
+    /// The number of operations waiting to be written
+    pub fn pending(&self) -> usize {
+        self.shared.pending_len()
+    }
+
+    /// Consumes the buffer without writing the operations waiting in it,
+    /// returning them instead
+    ///
+    /// The flush timer is stopped, and any batches being written by other
+    /// tasks are waited for; operations in those batches that could not be
+    /// written are included. Errors from batches written in the background
+    /// are discarded.
+    pub async fn into_pending(self) -> Vec<BatchWriteItem> {
+        self.shared.stop();
+        let _writing = self.shared.writing.write().await;
+        std::mem::take(&mut self.shared.lock_pending().waiting)
+    }
+}
+
+impl<T: WritableTable> Drop for WriteBuffer<T> {
+    fn drop(&mut self) {
+        self.shared.stop();
+
+        if std::thread::panicking() {
+            return;
+        }
+
+        let (pending, in_flight) = {
+            let pending = self.shared.lock_pending();
+            (pending.waiting.len(), pending.in_flight)
+        };
+        if pending > 0 || in_flight > 0 {
+            panic!(
+                "write buffer for table `{}` dropped with unflushed writes; call `flush()` \
+                 before dropping the buffer ({pending} operations waiting)",
+                self.shared.table.table_name(),
+            );
+        }
+    }
+}
+
+impl<T: WritableTable> Shared<T> {
+    fn lock_pending(&self) -> std::sync::MutexGuard<'_, Pending> {
+        self.pending.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn pending_len(&self) -> usize {
+        self.lock_pending().waiting.len()
+    }
+
+    /// Stops the flush timer, letting any write it has started finish
+    fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.stop.notify_one();
+    }
+
+    /// Writes every waiting operation, in batches
+    ///
+    /// If a batch cannot be written, its operations and those of the
+    /// batches after it are returned to the buffer.
+    async fn write_pending(&self) -> Result<(), Error> {
+        let _writing = self.writing.read().await;
+        let mut pending = {
+            let mut pending = self.lock_pending();
+            let count = pending.waiting.len();
+            pending.take(count)
+        };
+        while !pending.is_empty() {
+            let rest = pending.split_off(pending.len().min(MAX_BATCH_SIZE));
+            if let Err(error) = self.write(std::mem::replace(&mut pending, rest)).await {
+                self.finish(pending, false);
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes a single batch, retrying unprocessed items with exponential backoff
+    ///
+    /// The caller must hold the `writing` guard from before the operations
+    /// were taken from the buffer. If the batch cannot be written, its
+    /// operations are returned to the buffer.
+    async fn write(&self, ops: Vec<BatchWriteItem>) -> Result<(), Error> {
+        let batch = ops
+            .iter()
+            .cloned()
+            .fold(BatchWrite::new(), |batch, op| batch.operation(op));
+        let result = batch_write_with_retry(&self.table, batch, self.max_attempts).await;
+        self.finish(ops, result.is_ok());
+        result
+    }
+
+    /// Marks operations taken from the buffer as no longer being written
+    ///
+    /// Operations that were not written are returned to the buffer, unless
+    /// a later operation on the same key has been pushed in the meantime.
+    fn finish(&self, ops: Vec<BatchWriteItem>, written: bool) {
+        let mut pending = self.lock_pending();
+        pending.in_flight -= ops.len();
+        if written {
+            return;
+        }
+
+        for op in ops {
+            let key = op.written_key::<T>();
+            if !pending.waiting.iter().any(|p| p.written_key::<T>() == key) {
+                pending.waiting.push(op);
+            }
+        }
+    }
+
+    /// Records an error from a background flush, keeping only the first
+    fn record_error(&self, error: Error) {
+        self.background_error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get_or_insert(error);
+    }
+
+    fn take_error(&self) -> Result<(), Error> {
+        match self
+            .background_error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+        {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_dynamodb::error::SdkError;
+
+    use super::*;
+    use crate::{
+        keys,
+        mock::{ops, MockTable},
+        model::{Delete, Put},
+        AttributeValue, Item, Table,
+    };
+
+    struct TestTable;
+
+    impl Table for TestTable {
+        type PrimaryKey = keys::Primary;
+        type IndexKeys = keys::Gsi1;
+
+        fn table_name(&self) -> &str {
+            unimplemented!()
+        }
+
+        fn client(&self) -> &aws_sdk_dynamodb::Client {
+            unimplemented!()
+        }
+    }
+
+    impl WritableTable for TestTable {}
+
+    fn key(id: u32) -> Item {
+        Item::from([
+            ("PK".to_string(), AttributeValue::S(format!("ORDER#{id}"))),
+            ("SK".to_string(), AttributeValue::S(format!("ORDER#{id}"))),
+        ])
+    }
+
+    fn item(id: u32, version: u32) -> Item {
+        let mut item = key(id);
+        item.insert(
+            "version".to_string(),
+            AttributeValue::N(version.to_string()),
+        );
+        item
+    }
+
+    fn runtime() -> tokio::runtime::Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap()
+    }
+
+    fn buffer(options: WriteBufferOptions) -> WriteBuffer<MockTable<TestTable>> {
+        WriteBuffer::new(MockTable::new("buffer"), options)
+    }
+
+    fn batch_sizes(table: &MockTable<TestTable>) -> Vec<usize> {
+        table
+            .inputs::<ops::BatchWriteItem>()
+            .iter()
+            .map(|input| input.request_items.as_ref().unwrap()["buffer"].len())
+            .collect()
+    }
+
+    #[test]
+    fn full_batches_are_written_as_they_fill() {
+        runtime().block_on(async {
+            let buffer = buffer(WriteBufferOptions::new());
+            buffer.table().expect::<ops::BatchWriteItem>(|e| e.times(2));
+
+            for id in 0..26 {
+                buffer.push(Put::new(item(id, 1))).await.unwrap();
+            }
+            assert_eq!(batch_sizes(buffer.table()), [25]);
+            assert_eq!(buffer.pending(), 1);
+
+            buffer.flush().await.unwrap();
+            assert_eq!(buffer.pending(), 0);
+            assert_eq!(batch_sizes(buffer.table()), [25, 1]);
+            buffer.table().verify();
+        });
+    }
+
+    #[test]
+    fn later_operations_replace_waiting_operations_on_the_same_key() {
+        runtime().block_on(async {
+            let buffer = buffer(WriteBufferOptions::new());
+            buffer.table().expect::<ops::BatchWriteItem>(|e| e.times(1));
+
+            buffer.push(Put::new(item(1, 1))).await.unwrap();
+            buffer.push(Put::new(item(1, 2))).await.unwrap();
+            buffer.push(Put::new(item(2, 1))).await.unwrap();
+            buffer.push(Delete::new(key(2))).await.unwrap();
+            buffer.flush().await.unwrap();
+
+            let requests = buffer.table().inputs::<ops::BatchWriteItem>()[0]
+                .request_items
+                .clone()
+                .unwrap()
+                .remove("buffer")
+                .unwrap();
+            assert_eq!(requests.len(), 2);
+            let put = requests[0].put_request.as_ref().unwrap();
+            assert_eq!(put.item["version"], AttributeValue::N("2".to_string()));
+            assert!(requests[1].delete_request.is_some());
+        });
+    }
+
+    #[test]
+    fn waiting_operations_are_flushed_on_an_interval() {
+        runtime().block_on(async {
+            let buffer =
+                buffer(WriteBufferOptions::new().flush_interval(Duration::from_millis(10)));
+            buffer.table().expect::<ops::BatchWriteItem>(|e| e.times(1));
+
+            buffer.push(Put::new(item(1, 1))).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(buffer.pending(), 0);
+            assert_eq!(batch_sizes(buffer.table()), [1]);
+
+            buffer.flush().await.unwrap();
+            buffer.table().verify();
+        });
+    }
+
+    #[test]
+    fn operations_in_a_failed_batch_are_returned_to_the_buffer() {
+        runtime().block_on(async {
+            let buffer = buffer(WriteBufferOptions::new().max_attempts(1));
+            buffer
+                .table()
+                .expect::<ops::BatchWriteItem>(|e| {
+                    e.times(1)
+                        .responding(|_| Err(SdkError::timeout_error("timed out")))
+                })
+                .expect::<ops::BatchWriteItem>(|e| e.times(1));
+
+            buffer.push(Put::new(item(1, 1))).await.unwrap();
+            buffer.push(Put::new(item(2, 1))).await.unwrap();
+            assert!(buffer.flush().await.is_err());
+            assert_eq!(buffer.pending(), 2);
+
+            buffer.push(Put::new(item(1, 2))).await.unwrap();
+            buffer.flush().await.unwrap();
+            assert_eq!(buffer.pending(), 0);
+            assert_eq!(batch_sizes(buffer.table()), [2, 2]);
+            buffer.table().verify();
+        });
+    }
+
+    #[test]
+    fn waiting_operations_can_be_taken_out_of_the_buffer() {
+        runtime().block_on(async {
+            let buffer = buffer(WriteBufferOptions::new().flush_interval(Duration::from_secs(60)));
+
+            buffer.push(Put::new(item(1, 1))).await.unwrap();
+            buffer.push(Delete::new(key(2))).await.unwrap();
+
+            let pending = buffer.into_pending().await;
+            assert_eq!(pending.len(), 2);
+            assert_eq!(pending[0].written_key::<TestTable>(), key(1));
+            assert_eq!(pending[1].written_key::<TestTable>(), key(2));
+        });
+    }
+
+    #[test]
+    fn a_flushed_buffer_stops_its_timer_when_dropped() {
+        runtime().block_on(async {
+            let buffer =
+                buffer(WriteBufferOptions::new().flush_interval(Duration::from_millis(10)));
+            buffer.table().expect::<ops::BatchWriteItem>(|e| e.times(1));
+
+            buffer.push(Put::new(item(1, 1))).await.unwrap();
+            buffer.flush().await.unwrap();
+            let shared = Arc::clone(&buffer.shared);
+            drop(buffer);
+
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert_eq!(Arc::strong_count(&shared), 1);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "dropped with unflushed writes")]
+    fn dropping_an_unflushed_buffer_panics() {
+        runtime().block_on(async {
+            let buffer = buffer(WriteBufferOptions::new());
+            buffer.push(Put::new(item(1, 1))).await.unwrap();
+        });
+    }
+}